@@ -0,0 +1,463 @@
+//! Per-project "always allow" rules. Every bash command segment matching one
+//! of the bash patterns skips the permission prompt, and so does every edit
+//! whose project relative path matches one of the edit patterns. Patterns
+//! are globs, `*` matches anything (spaces and slashes included), `?` one
+//! character: `cargo *`, `npm test*`, `git status`, `src/*`.
+//!
+//! Stored one pattern per line in `.blitz/bash_allow` and `.blitz/edit_allow`.
+//! Commands matching `always_ask` still prompt, no matter what the list says.
+//!
+//! `blitz.set_bash_rules` adds config rules on top, the same globs: allow
+//! patterns skip the prompt like the project list, deny patterns refuse the
+//! command without asking.
+const std = @import("std");
+const util = @import("util.zig");
+
+pub const FILENAME = util.BLITZ_DIR ++ "/bash_allow";
+pub const EDIT_FILENAME = util.BLITZ_DIR ++ "/edit_allow";
+pub const MAX_PATTERNS = 64;
+
+pub const always_ask: []const []const u8 = &.{
+    "git push*",
+    "git * push*",
+    "rm",
+    "rm *",
+};
+
+pub fn alwaysAsks(segment: []const u8) bool {
+    const trimmed = std.mem.trim(u8, segment, " \t\r\n");
+    for (always_ask) |p| {
+        if (globMatch(p, trimmed)) return true;
+    }
+    return shellWrapper(trimmed);
+}
+
+/// `sh -c …` and friends, a rule for them would be `bash *` and cover
+/// every command.
+pub fn shellWrapper(segment: []const u8) bool {
+    var words = std.mem.tokenizeAny(u8, segment, " \t\r\n");
+    var shell = false;
+    while (words.next()) |word| {
+        if (!shell) {
+            if (isAssignment(word)) continue;
+            var name = std.mem.trim(u8, word, "'\"");
+            name = std.mem.trimLeft(u8, name, "\\");
+            name = name[if (std.mem.lastIndexOfScalar(u8, name, '/')) |i| i + 1 else 0..];
+            if (isWrapper(name)) continue;
+            for ([_][]const u8{ "sh", "bash", "zsh", "dash", "ksh", "fish" }) |s| {
+                if (std.mem.eql(u8, name, s)) shell = true;
+            }
+            if (!shell) return false;
+            continue;
+        }
+        if (word.len < 2 or word[0] != '-') return false;
+        if (word[1] != '-' and std.mem.indexOfScalar(u8, word, 'c') != null) return true;
+    }
+    return false;
+}
+
+/// `rel_path` from `projectPath` stays inside the project, no absolute
+/// path and no `..` component.
+pub fn insideProject(rel_path: []const u8) bool {
+    if (rel_path.len == 0 or std.fs.path.isAbsolute(rel_path)) return false;
+    var it = std.mem.tokenizeScalar(u8, rel_path, '/');
+    while (it.next()) |part| {
+        if (std.mem.eql(u8, part, "..")) return false;
+    }
+    return true;
+}
+
+pub const BashAllowList = PatternList(FILENAME, always_ask);
+pub const EditAllowList = PatternList(EDIT_FILENAME, &.{});
+
+fn PatternList(comptime filename: []const u8, comptime never: []const []const u8) type {
+    return struct {
+        patterns: std.ArrayList([]const u8) = .empty,
+
+        const Self = @This();
+
+        pub fn deinit(self: *Self, alloc: std.mem.Allocator) void {
+            for (self.patterns.items) |p| alloc.free(p);
+            self.patterns.deinit(alloc);
+        }
+
+        /// Pattern matching `segment`, never one of the `always_ask` commands
+        /// or a shell wrapper.
+        pub fn match(self: *const Self, segment: []const u8) ?[]const u8 {
+            const trimmed = std.mem.trim(u8, segment, " \t\r\n");
+            for (never) |p| {
+                if (globMatch(p, trimmed)) return null;
+            }
+            if (never.len > 0 and shellWrapper(trimmed)) return null;
+            for (self.patterns.items) |p| {
+                if (globMatch(p, trimmed)) return p;
+            }
+            return null;
+        }
+
+        /// Returns false for duplicates or a full list.
+        pub fn add(self: *Self, alloc: std.mem.Allocator, pattern: []const u8) !bool {
+            const trimmed = std.mem.trim(u8, pattern, " \t\r\n");
+            if (trimmed.len == 0 or self.patterns.items.len >= MAX_PATTERNS) return false;
+            for (self.patterns.items) |p| {
+                if (std.mem.eql(u8, p, trimmed)) return false;
+            }
+            try self.patterns.append(alloc, try alloc.dupe(u8, trimmed));
+            return true;
+        }
+
+        pub fn remove(self: *Self, alloc: std.mem.Allocator, idx: usize) void {
+            if (idx >= self.patterns.items.len) return;
+            alloc.free(self.patterns.orderedRemove(idx));
+        }
+
+        pub fn parse(self: *Self, alloc: std.mem.Allocator, content: []const u8) !void {
+            var it = std.mem.splitScalar(u8, content, '\n');
+            while (it.next()) |line| {
+                const trimmed = std.mem.trim(u8, line, " \t\r");
+                if (trimmed.len == 0 or trimmed[0] == '#') continue;
+                _ = try self.add(alloc, trimmed);
+            }
+        }
+
+        /// Load `<cwd>/.blitz/<file>`, a missing file is an empty list.
+        pub fn load(self: *Self, io: std.Io, alloc: std.mem.Allocator) void {
+            const file = std.Io.Dir.cwd().openFile(io, filename, .{}) catch return;
+            defer file.close(io);
+
+            var read_buf: [1024]u8 = undefined;
+            var file_reader = file.reader(io, &read_buf);
+            const content = file_reader.interface.allocRemaining(alloc, .limited(64 * 1024)) catch return;
+            defer alloc.free(content);
+            self.parse(alloc, content) catch |err| {
+                std.log.warn("failed to parse {s}: {s}", .{ filename, @errorName(err) });
+            };
+        }
+
+        pub fn save(self: *const Self, io: std.Io) !void {
+            std.Io.Dir.cwd().createDirPath(io, util.BLITZ_DIR) catch {};
+            const file = try std.Io.Dir.cwd().createFile(io, filename, .{});
+            defer file.close(io);
+
+            var write_buf: [1024]u8 = undefined;
+            var writer = file.writer(io, &write_buf);
+            for (self.patterns.items) |p| try writer.interface.print("{s}\n", .{p});
+            try writer.interface.flush();
+        }
+    };
+}
+
+/// Config defined `bash` rules, see `blitz.set_bash_rules`.
+pub const BashRules = struct {
+    allow: []const []const u8 = &.{},
+    deny: []const []const u8 = &.{},
+
+    pub const Decision = union(enum) {
+        ask,
+        allow: []const u8,
+        deny: []const u8,
+    };
+
+    /// A deny pattern matching the whole command or any segment wins, also
+    /// in its `normalize`d form. The command is allowed when every segment
+    /// matches an allow pattern. Subshells and redirects need the prompt
+    /// unless denied.
+    pub fn decide(self: *const BashRules, alloc: std.mem.Allocator, cmd: []const u8) !Decision {
+        if (try self.denied(alloc, cmd)) |p| return .{ .deny = p };
+        var rest: []const u8 = cmd;
+        while (rest.len > 0) {
+            const segment, const remaining = nextSegment(rest);
+            rest = remaining;
+            if (try self.denied(alloc, segment)) |p| return .{ .deny = p };
+        }
+
+        if (self.allow.len == 0 or hasShellEscapes(cmd)) return .ask;
+        var pattern: ?[]const u8 = null;
+        rest = cmd;
+        while (rest.len > 0) {
+            const segment, const remaining = nextSegment(rest);
+            rest = remaining;
+            if (std.mem.trim(u8, segment, " \t\r\n").len == 0) continue;
+            if (alwaysAsks(segment)) return .ask;
+            pattern = matchAny(self.allow, segment) orelse return .ask;
+        }
+        return if (pattern) |p| .{ .allow = p } else .ask;
+    }
+
+    fn denied(self: *const BashRules, alloc: std.mem.Allocator, text: []const u8) !?[]const u8 {
+        if (matchAny(self.deny, text)) |p| return p;
+        const normal = try normalize(alloc, text);
+        defer alloc.free(normal);
+        return matchAny(self.deny, normal);
+    }
+
+    fn matchAny(patterns: []const []const u8, text: []const u8) ?[]const u8 {
+        const trimmed = std.mem.trim(u8, text, " \t\r\n");
+        if (trimmed.len == 0) return null;
+        for (patterns) |p| {
+            if (globMatch(p, trimmed)) return p;
+        }
+        return null;
+    }
+};
+
+/// `cmd` the way deny patterns see it: single spaces between words, no
+/// leading `VAR=value` assignments or `env`/`command`/`exec` wrappers, and
+/// argv[0] as the bare name of the program. `/bin/rm  -rf x`, `\rm -rf x`
+/// and `env rm -rf x` all become `rm -rf x`.
+pub fn normalize(alloc: std.mem.Allocator, cmd: []const u8) ![]const u8 {
+    var out: std.ArrayList(u8) = .empty;
+    errdefer out.deinit(alloc);
+    var words = std.mem.tokenizeAny(u8, cmd, " \t\r\n");
+    var in_wrapper = false;
+    var program = true;
+    while (words.next()) |word| {
+        if (program) {
+            if (isAssignment(word)) continue;
+            if (in_wrapper and word[0] == '-') continue;
+            var name = std.mem.trim(u8, word, "'\"");
+            name = std.mem.trimLeft(u8, name, "\\");
+            name = name[if (std.mem.lastIndexOfScalar(u8, name, '/')) |i| i + 1 else 0..];
+            if (isWrapper(name)) {
+                in_wrapper = true;
+                continue;
+            }
+            program = false;
+            try out.appendSlice(alloc, name);
+            continue;
+        }
+        try out.append(alloc, ' ');
+        try out.appendSlice(alloc, word);
+    }
+    return out.toOwnedSlice(alloc);
+}
+
+fn isAssignment(word: []const u8) bool {
+    const eq = std.mem.indexOfScalar(u8, word, '=') orelse return false;
+    if (eq == 0 or std.ascii.isDigit(word[0])) return false;
+    for (word[0..eq]) |c| {
+        if (!std.ascii.isAlphanumeric(c) and c != '_') return false;
+    }
+    return true;
+}
+
+fn isWrapper(name: []const u8) bool {
+    for ([_][]const u8{ "env", "command", "exec", "builtin", "nohup" }) |w| {
+        if (std.mem.eql(u8, name, w)) return true;
+    }
+    return false;
+}
+
+/// Subshells and redirects, what a per segment match can't see through.
+pub fn hasShellEscapes(cmd: []const u8) bool {
+    for (cmd, 0..) |c, i| switch (c) {
+        '`', '>', '<' => return true,
+        '$' => if (i + 1 < cmd.len and cmd[i + 1] == '(') return true,
+        else => {},
+    };
+    return false;
+}
+
+/// Splits off the first command of a `|`, `||`, `&&`, `;`, `&` or newline
+/// chain. Separators inside quotes or escaped by a backslash don't split,
+/// the `&` of `2>&1` and `&>` is a redirect.
+pub fn nextSegment(input: []const u8) struct { []const u8, []const u8 } {
+    var quote: ?u8 = null;
+    var i: usize = 0;
+    while (i < input.len) : (i += 1) {
+        const c = input[i];
+        if (quote) |q| {
+            if (c == q) {
+                quote = null;
+            } else if (c == '\\' and q == '"') {
+                i += 1;
+            }
+            continue;
+        }
+        switch (c) {
+            '\'', '"' => quote = c,
+            '\\' => i += 1,
+            '|' => {
+                if (i + 1 < input.len and input[i + 1] == '|') {
+                    return .{ input[0..i], if (i + 2 < input.len) input[i + 2 ..] else "" };
+                }
+                return .{ input[0..i], if (i + 1 < input.len) input[i + 1 ..] else "" };
+            },
+            '&' => {
+                if (i + 1 < input.len and input[i + 1] == '&') {
+                    return .{ input[0..i], if (i + 2 < input.len) input[i + 2 ..] else "" };
+                }
+                const redirect = (i > 0 and (input[i - 1] == '>' or input[i - 1] == '<')) or
+                    (i + 1 < input.len and input[i + 1] == '>');
+                if (!redirect) return .{ input[0..i], if (i + 1 < input.len) input[i + 1 ..] else "" };
+            },
+            ';', '\n' => {
+                return .{ input[0..i], if (i + 1 < input.len) input[i + 1 ..] else "" };
+            },
+            else => {},
+        }
+    }
+    return .{ input, "" };
+}
+
+/// `path` relative to the project root `cwd`, as edit patterns see it.
+pub fn projectPath(cwd: []const u8, path: []const u8) []const u8 {
+    var rel = path;
+    if (cwd.len > 0 and std.mem.startsWith(u8, rel, cwd) and rel.len > cwd.len and rel[cwd.len] == '/') {
+        rel = rel[cwd.len + 1 ..];
+    }
+    while (std.mem.startsWith(u8, rel, "./")) rel = rel[2..];
+    return rel;
+}
+
+/// Rule covering `segment` and its variations: the command and its first
+/// argument, `cargo build --release` becomes `cargo build*`.
+pub fn commandPattern(alloc: std.mem.Allocator, segment: []const u8) ![]const u8 {
+    const trimmed = std.mem.trim(u8, segment, " \t\r\n");
+    var words = std.mem.tokenizeAny(u8, trimmed, " \t");
+    var end: usize = 0;
+    var n: usize = 0;
+    while (words.next()) |w| : (n += 1) {
+        if (n == 2 or w[0] == '-') break;
+        end = words.index;
+    }
+    if (end == 0) return alloc.dupe(u8, trimmed);
+    return std.fmt.allocPrint(alloc, "{s}*", .{trimmed[0..end]});
+}
+
+/// Rule covering the directory of `rel_path`, a file at the root only
+/// covers itself.
+pub fn pathPattern(alloc: std.mem.Allocator, rel_path: []const u8) ![]const u8 {
+    const dir = std.fs.path.dirname(rel_path) orelse return alloc.dupe(u8, rel_path);
+    return std.fmt.allocPrint(alloc, "{s}/*", .{dir});
+}
+
+pub fn globMatch(pattern: []const u8, text: []const u8) bool {
+    if (pattern.len == 0) return text.len == 0;
+    switch (pattern[0]) {
+        '*' => {
+            var i: usize = 0;
+            while (i <= text.len) : (i += 1) {
+                if (globMatch(pattern[1..], text[i..])) return true;
+            }
+            return false;
+        },
+        '?' => return text.len > 0 and globMatch(pattern[1..], text[1..]),
+        else => return text.len > 0 and text[0] == pattern[0] and globMatch(pattern[1..], text[1..]),
+    }
+}
+
+test "globMatch" {
+    try std.testing.expect(globMatch("cargo *", "cargo build --release"));
+    try std.testing.expect(globMatch("npm test*", "npm test"));
+    try std.testing.expect(globMatch("npm test*", "npm test -- src/a.test.js"));
+    try std.testing.expect(!globMatch("git status", "git status --short"));
+    try std.testing.expect(!globMatch("cargo *", "cargo"));
+}
+
+test "BashAllowList.match" {
+    const alloc = std.testing.allocator;
+    var list: BashAllowList = .{};
+    defer list.deinit(alloc);
+
+    try list.parse(alloc, "# project allowlist\ncargo *\ngit *\n\nrm *\n");
+    try std.testing.expectEqual(@as(usize, 3), list.patterns.items.len);
+    try std.testing.expect(!try list.add(alloc, "cargo *"));
+
+    try std.testing.expectEqualStrings("cargo *", list.match(" cargo test ").?);
+    try std.testing.expect(list.match("git status") != null);
+    try std.testing.expect(list.match("git push origin main") == null);
+    try std.testing.expect(list.match("git -C sub push") == null);
+    try std.testing.expect(list.match("rm -rf target") == null);
+    try std.testing.expect(list.match("make") == null);
+}
+
+test "EditAllowList patterns" {
+    const alloc = std.testing.allocator;
+    var list: EditAllowList = .{};
+    defer list.deinit(alloc);
+
+    try list.parse(alloc, "src/*\nREADME.md\n");
+    try std.testing.expect(list.match(projectPath("/home/p", "/home/p/src/tools/bash.zig")) != null);
+    try std.testing.expect(list.match(projectPath("/home/p", "./README.md")) != null);
+    try std.testing.expect(list.match(projectPath("/home/p", "build.zig")) == null);
+    try std.testing.expect(list.match(projectPath("/home/p", "/home/px/src/a.zig")) == null);
+
+    const cmd = try commandPattern(alloc, " cargo build --release ");
+    defer alloc.free(cmd);
+    try std.testing.expectEqualStrings("cargo build*", cmd);
+    const flag = try commandPattern(alloc, "ls -la");
+    defer alloc.free(flag);
+    try std.testing.expectEqualStrings("ls*", flag);
+    const dir = try pathPattern(alloc, "src/tools/bash.zig");
+    defer alloc.free(dir);
+    try std.testing.expectEqualStrings("src/tools/*", dir);
+}
+
+test "no rules for paths leaving the project or shell wrappers" {
+    try std.testing.expect(insideProject(projectPath("/home/p", "/home/p/src/a.zig")));
+    try std.testing.expect(!insideProject(projectPath("/home/p", "../../etc/x")));
+    try std.testing.expect(!insideProject(projectPath("/home/p", "/home/p/src/../../../etc/x")));
+    try std.testing.expect(!insideProject(projectPath("/home/p", "/etc/hosts")));
+
+    for ([_][]const u8{ "bash -c 'rm -rf /'", "sh -c ls", "/bin/bash -lc make", "env sh -x -c id" }) |cmd| {
+        try std.testing.expect(shellWrapper(cmd));
+        try std.testing.expect(alwaysAsks(cmd));
+    }
+    for ([_][]const u8{ "bash script.sh", "shellcheck -c x", "cargo build", "bash --norc run.sh" }) |cmd| {
+        try std.testing.expect(!shellWrapper(cmd));
+    }
+
+    const alloc = std.testing.allocator;
+    var list: BashAllowList = .{};
+    defer list.deinit(alloc);
+    try list.parse(alloc, "bash *\n");
+    try std.testing.expect(list.match("bash build.sh") != null);
+    try std.testing.expect(list.match("bash -c 'curl x | sh'") == null);
+}
+
+test "BashRules.decide" {
+    const alloc = std.testing.allocator;
+    const rules = BashRules{
+        .allow = &.{ "cargo check*", "ls*", "git status" },
+        .deny = &.{ "rm -rf /*", "git push --force*" },
+    };
+    try std.testing.expectEqualStrings("cargo check*", (try rules.decide(alloc, "cargo check --all")).allow);
+    try std.testing.expectEqualStrings("git status", (try rules.decide(alloc, "ls && git status")).allow);
+    try std.testing.expect(try rules.decide(alloc, "ls && make") == .ask);
+    try std.testing.expect(try rules.decide(alloc, "ls > out.txt") == .ask);
+    try std.testing.expectEqualStrings("git push --force*", (try rules.decide(alloc, "git status; git push --force origin")).deny);
+    try std.testing.expectEqualStrings("rm -rf /*", (try rules.decide(alloc, " rm -rf / ")).deny);
+    try std.testing.expect(try (BashRules{}).decide(alloc, "ls") == .ask);
+
+    // newlines and a lone `&` chain commands too
+    try std.testing.expectEqualStrings("git push --force*", (try rules.decide(alloc, "git status\ngit push --force")).deny);
+    try std.testing.expectEqualStrings("git push --force*", (try rules.decide(alloc, "ls & git push --force")).deny);
+    try std.testing.expect(try rules.decide(alloc, "ls\nmake") == .ask);
+    try std.testing.expect(try rules.decide(alloc, "ls & make") == .ask);
+    try std.testing.expectEqualStrings("git status", (try rules.decide(alloc, "ls\ngit status")).allow);
+}
+
+test "deny rules see through spacing, paths and wrappers" {
+    const alloc = std.testing.allocator;
+    const rules = BashRules{ .deny = &.{ "rm -rf *", "git push --force*" } };
+    for ([_][]const u8{
+        "rm  -rf build",
+        "rm\t-rf build",
+        "/bin/rm -rf build",
+        "\\rm -rf build",
+        "env rm -rf build",
+        "env -i HOME=/tmp /usr/bin/rm -rf build",
+        "FOO=1 command rm -rf build",
+        "ls; exec   rm -rf build",
+        "'rm' -rf build",
+    }) |cmd| {
+        try std.testing.expectEqualStrings("rm -rf *", (try rules.decide(alloc, cmd)).deny);
+    }
+    try std.testing.expectEqualStrings("git push --force*", (try rules.decide(alloc, "/usr/bin/git   push  --force")).deny);
+    try std.testing.expect(try rules.decide(alloc, "rmdir -rf build") == .ask);
+
+    const normal = try normalize(alloc, "  env  LANG=C   /usr/local/bin/cargo   build ");
+    defer alloc.free(normal);
+    try std.testing.expectEqualStrings("cargo build", normal);
+}
@@ -8,13 +8,69 @@ pub const FULL_MODE_REMINDER_AFTER_USER_MSG_COUNT = 4;
 pub const PROMPT_HISTORY_FILENAME = "prompt_history.json";
 pub const MAX_HISTORY = 32;
 pub const CONTEXT_LIMIT = 124 * 1024;
+/// dollars of estimated input above which a new turn asks first
+pub const DEFAULT_COST_CONFIRM = 0.5;
 const COMMAND_COMPLETION_ROWS = 8;
 
+const GREP_CONTEXT_LINES = 15;
+
+/// Poll timeout while something animates or agents emit events.
+pub const FRAME_MS = 16;
+/// Poll timeout when idle, input still wakes the loop immediately.
+pub const IDLE_POLL_MS = 1000;
+/// Poll timeout while agents only wait on the network, stream data and
+/// permission requests wake the loop early through `App.waker`.
+pub const NETWORK_POLL_MS = 96;
+/// One sweep of the dashboard header wave, then it rests.
+const DASHBOARD_WAVE_FRAMES = 170;
+pub const DEFAULT_RESEARCH_TURNS = 12;
+/// Messages longer than this many lines show only their head and tail.
+const FOLD_MIN_LINES = 400;
+const FOLD_HEAD_LINES = 80;
+const FOLD_TAIL_LINES = 80;
+/// Read-only lookups whose finished calls collapse into one exploration
+/// line, `:expand` shows every call.
+const exploration_tools = [_][]const u8{ "read", "ripgrep", "grep", "glob", "ls", "who_owns" };
+/// targets listed on an exploration line before the rest is counted
+const EXPLORATION_TARGETS = 6;
+
 const builtin_command_completions: []const []const u8 = &.{
     ":clear",
     ":help",
     ":ssh user@host:/path/to/cwd",
     ":cd /path/to/new/cwd",
+    ":resume",
+    ":allow cargo *",
+    ":allow edit src/*",
+    ":undo",
+    ":undo turn",
+    ":undo list",
+    ":mouse",
+    ":new template-name project-name",
+    ":handoff",
+    ":compact",
+    ":expand",
+    ":notifications",
+    ":usage",
+    ":todos",
+    ":todo add subject | description",
+    ":settings",
+    ":set compact_threshold 80",
+    ":research 12",
+    ":search query",
+    ":output",
+    ":tab new name",
+    ":tab close",
+    ":task prompt",
+    ":tasks",
+    ":memory",
+    ":providers",
+    ":provider name",
+    ":image",
+    ":export",
+    ":errors",
+    ":memory add section | note",
+    ":sessions encrypt",
 };
 
 pub const PermisionLevel = enum {
@@ -38,6 +94,18 @@ pub const AppFlags = packed struct {
     /// Toggle from TUI / r.lua.
     ssh_agent_control: bool = true,
     skip_permissions: bool = true,
+    /// Off keeps native terminal selection/copy but loses wheel scrolling
+    /// and in-app selection. `blitz.set_mouse_capture` or `:mouse`.
+    mouse_capture: bool = true,
+    /// Show huge messages in full instead of folding their middle, and every
+    /// call of an exploration block on its own line. `:expand`
+    expand_messages: bool = false,
+    /// Approve a command, edit or commit again without asking when it is
+    /// identical to one approved earlier in the session, e.g. after a retry.
+    replay_approvals: bool = true,
+    /// Open generated images in a preview popup where the terminal can draw
+    /// them, see `media.zig`. Off still saves them.
+    image_preview: bool = true,
 };
 
 pub const Theme = struct {
@@ -80,6 +148,8 @@ pub const InputMode = union(enum) {
     pub const PermMessage = struct {
         buf: [512]u8 = undefined,
         len: usize = 0,
+        /// typing a review comment on the selected hunk, not a reply
+        comment: bool = false,
     };
     pub const Passphrase = struct {
         buf: [256]u8 = undefined,
@@ -91,6 +161,150 @@ pub const InputMode = union(enum) {
     };
 };
 
+pub const DEFAULT_TODO_NUDGE = "You have unfinished work on your todo list. Continue with the next open todo, or update the list if the remaining items are done or no longer needed.";
+
+/// Follow-up sent when the main run ends with open todos, `blitz.set_todo_nudge`.
+pub const TodoNudge = struct {
+    text: []const u8 = DEFAULT_TODO_NUDGE,
+    /// nudges in a row without a user message in between, 0 disables them
+    max: u32 = 3,
+};
+
+/// `@model:<name>` in front of a prompt routes that one turn to another model.
+pub const ModelHint = struct {
+    name: []const u8,
+    /// the prompt without the hint
+    rest: []const u8,
+};
+
+pub fn parseModelHint(input: []const u8) ?ModelHint {
+    const prefix = "@model:";
+    if (!std.mem.startsWith(u8, input, prefix)) return null;
+    const after = input[prefix.len..];
+    const end = std.mem.indexOfAny(u8, after, " \t\n") orelse after.len;
+    if (end == 0) return null;
+    return .{ .name = after[0..end], .rest = std.mem.trimLeft(u8, after[end..], " \t\n") };
+}
+
+/// The agent's own model config, put back once the hinted turn ends.
+pub const TurnModel = struct {
+    agent_id: prv.Swarm.AgentId,
+    /// backs the swapped in config's model name
+    entry: prv.config.ModelEntry,
+    restore: prv.adapter.Config,
+    restore_minimal: bool,
+};
+
+/// Tokens and dollars summed over usage records.
+pub const UsageSum = struct {
+    usage: prv.adapter.TokenUsage = .{},
+    cost: f64 = 0,
+    /// a record had no known price, `cost` undercounts
+    unpriced: bool = false,
+
+    fn add(self: *UsageSum, usage: prv.adapter.TokenUsage, price: ?r.cost.Price) void {
+        self.usage.add(usage);
+        if (price) |p| self.cost += r.cost.cost(p, usage) else self.unpriced = true;
+    }
+};
+
+/// The swarm's usage log summed per prompt, model and tool. A response that
+/// calls several tools is split evenly between them, one without tool calls
+/// counts as `answer`.
+pub const UsageBreakdown = struct {
+    turns: std.AutoArrayHashMapUnmanaged(u32, UsageSum) = .empty,
+    models: std.StringArrayHashMapUnmanaged(UsageSum) = .empty,
+    tools: std.StringArrayHashMapUnmanaged(UsageSum) = .empty,
+    total: UsageSum = .{},
+
+    /// `prices` has `modelPrice(model) ?r.cost.Price`, the App does.
+    pub fn build(alloc: std.mem.Allocator, records: []const prv.Swarm.UsageRecord, prices: anytype) !UsageBreakdown {
+        var self: UsageBreakdown = .{};
+        for (records) |record| {
+            const price = prices.modelPrice(record.model);
+            self.total.add(record.usage, price);
+            const turn = try self.turns.getOrPutValue(alloc, record.turn, .{});
+            turn.value_ptr.add(record.usage, price);
+            const model = try self.models.getOrPutValue(alloc, record.model, .{});
+            model.value_ptr.add(record.usage, price);
+
+            if (record.tools.len == 0) {
+                const answer = try self.tools.getOrPutValue(alloc, "answer", .{});
+                answer.value_ptr.add(record.usage, price);
+                continue;
+            }
+            const share = splitUsage(record.usage, record.tools.len);
+            for (record.tools) |name| {
+                const tool = try self.tools.getOrPutValue(alloc, name, .{});
+                tool.value_ptr.add(share, price);
+            }
+        }
+        return self;
+    }
+
+    fn splitUsage(usage: prv.adapter.TokenUsage, n: usize) prv.adapter.TokenUsage {
+        return .{
+            .input_tokens = usage.input_tokens / n,
+            .output_tokens = usage.output_tokens / n,
+            .cached_tokens = usage.cached_tokens / n,
+            .cache_creation_tokens = usage.cache_creation_tokens / n,
+        };
+    }
+};
+
+/// A conversation kept next to the current one, see `App.openTab`.
+pub const Tab = struct {
+    /// stored session it lives in
+    name: []const u8,
+    /// spent while it was the active tab
+    cost: f64 = 0,
+};
+
+/// Lifecycle of the main run as seen by the UI. `queued` means the agent is
+/// busy and at least one follow-up waits for its turn.
+pub const RunState = enum {
+    idle,
+    running,
+    queued,
+    cancelling,
+
+    pub fn active(self: RunState) bool {
+        return self != .idle;
+    }
+
+    pub fn label(self: RunState) []const u8 {
+        return switch (self) {
+            .idle => "",
+            .running => "",
+            .queued => "| QUEUED ",
+            .cancelling => "| CANCELLING ",
+        };
+    }
+};
+
+/// Coarse state mirrored into the terminal title and tab progress.
+pub const TerminalStatus = enum {
+    idle,
+    running,
+    needs_input,
+
+    pub fn label(self: TerminalStatus) []const u8 {
+        return switch (self) {
+            .idle => "idle",
+            .running => "running",
+            .needs_input => "needs input",
+        };
+    }
+
+    pub fn progress(self: TerminalStatus) r.tui.Terminal.Progress {
+        return switch (self) {
+            .idle => .none,
+            .running => .indeterminate,
+            .needs_input => .paused,
+        };
+    }
+};
+
 pub const QueuedMessage = struct {
     agent_id: prv.Swarm.AgentId,
     entry: ?ChatEntry = null,
@@ -127,6 +341,19 @@ pub const MessageQueue = struct {
         return null;
     }
 
+    /// True if the same text is already waiting for `agent_id`.
+    pub fn containsText(self: *const MessageQueue, agent_id: prv.Swarm.AgentId, text: []const u8) bool {
+        for (self.items.items) |item| {
+            if (!sameAgent(item.agent_id, agent_id)) continue;
+            if (item.parts.len == 0) continue;
+            switch (item.parts[0]) {
+                .text => |t| if (std.mem.eql(u8, t, text)) return true,
+                else => {},
+            }
+        }
+        return false;
+    }
+
     pub fn count(self: *const MessageQueue) usize {
         return self.items.items.len;
     }
@@ -136,15 +363,173 @@ pub const MessageQueue = struct {
     }
 };
 
+/// Prompts for `:task`, each one runs as its own turn once the previous run
+/// finished. A failed or cancelled run pauses the queue, `:task run`
+/// continues it.
+pub const TaskQueue = struct {
+    items: std.ArrayList([]const u8) = .empty,
+    /// started since the queue was last empty, for the "task 2/5" line
+    started: usize = 0,
+    paused: bool = false,
+
+    /// One task per non-empty line of `text`. Returns how many were added.
+    pub fn add(self: *TaskQueue, alloc: std.mem.Allocator, text: []const u8) !usize {
+        var added: usize = 0;
+        var lines = std.mem.splitScalar(u8, text, '\n');
+        while (lines.next()) |line| {
+            const prompt = std.mem.trim(u8, line, " \t\r");
+            if (prompt.len == 0) continue;
+            try self.items.append(alloc, try alloc.dupe(u8, prompt));
+            added += 1;
+        }
+        return added;
+    }
+
+    pub fn pop(self: *TaskQueue) ?[]const u8 {
+        if (self.items.items.len == 0) return null;
+        self.started += 1;
+        return self.items.orderedRemove(0);
+    }
+
+    /// Swap task `i` with its neighbour, returns where it ended up.
+    pub fn move(self: *TaskQueue, i: usize, delta: isize) usize {
+        const items = self.items.items;
+        const to = @as(isize, @intCast(i)) + delta;
+        if (to < 0 or to >= items.len) return i;
+        const j: usize = @intCast(to);
+        std.mem.swap([]const u8, &items[i], &items[j]);
+        return j;
+    }
+
+    pub fn count(self: *const TaskQueue) usize {
+        return self.items.items.len;
+    }
+
+    /// total of the current batch, the running task included
+    pub fn total(self: *const TaskQueue) usize {
+        return self.started + self.items.items.len;
+    }
+};
+
+/// Failed tool calls of the main agent in the current turn, for the error
+/// strip above the prompt and the `.tool_errors` panel. A new prompt starts
+/// over. Memory belongs to the session arena.
+pub const ToolFailures = struct {
+    /// `user_turn` the list belongs to
+    turn: u32 = 0,
+    items: std.ArrayList(Failure) = .empty,
+    /// failure waiting for a second enter to run again
+    confirm: ?usize = null,
+
+    pub const Failure = struct {
+        call: prv.adapter.ToolCall,
+        message: []const u8,
+        /// outcome of the last retry, null until one finished
+        retried: ?Retried = null,
+    };
+
+    pub const Retried = enum { ok, failed };
+
+    /// Track a part of a main agent broadcast made during `turn`. Only an
+    /// error result is kept, with `arguments` of its call from the chat.
+    pub fn note(self: *ToolFailures, alloc: std.mem.Allocator, turn: u32, part: prv.adapter.ContentPart, arguments: ?[]const u8) !void {
+        if (turn != self.turn) {
+            self.* = .{ .turn = turn };
+        }
+        const res = switch (part) {
+            .tool_result => |res| res,
+            else => return,
+        };
+        if (!res.is_error) return;
+        const call = try r.util.deepClone(prv.adapter.ToolCall, .{
+            .id = res.call_id,
+            .name = res.name,
+            .arguments = arguments orelse "{}",
+        }, alloc);
+        try self.items.append(alloc, .{ .call = call, .message = try alloc.dupe(u8, res.content) });
+    }
+
+    /// Failures of `turn`, 0 once a newer prompt went out.
+    pub fn count(self: *const ToolFailures, turn: u32) usize {
+        return if (turn == self.turn) self.items.items.len else 0;
+    }
+
+    pub fn last(self: *const ToolFailures) ?*const Failure {
+        if (self.items.items.len == 0) return null;
+        return &self.items.items[self.items.items.len - 1];
+    }
+};
+
+/// A failed call run again from the `.tool_errors` panel, gpa owned. The
+/// tool allocates into `arena` until `running.done` is set.
+const ToolRetry = struct {
+    running: prv.tool.RunningTool,
+    arena: prv.ThreadSafeArena,
+    call: prv.adapter.ToolCall,
+    /// entry in `tool_failures.items` while its `turn` lasts
+    index: usize,
+    turn: u32,
+};
+
+/// Toasts in the top right corner, plus the log behind `:notifications`.
+/// Every entry stays in the log after its toast is gone, the oldest drop out
+/// once `MAX_ENTRIES` is reached.
 pub const Notifications = struct {
     list: [MAX_ENTRIES]Entry = @splat(.empty),
+    /// wall clock for the toast timers and log timestamps, unset in tests
+    io: ?std.Io = null,
+    /// clock reading of the last `tick`, advanced by hand in tests
+    now_ms: i64 = 0,
+    /// a toast appeared since the last `tick`
+    pushed: bool = false,
+
+    pub const Level = enum {
+        info,
+        warn,
+        err,
+
+        pub fn label(self: Level) []const u8 {
+            return switch (self) {
+                .info => "info",
+                .warn => "warn",
+                .err => "error",
+            };
+        }
 
-    pub const Entry = union(enum) { empty, used: struct { msg: []const u8, alive: f32 } };
-    const MAX_ENTRIES = 16;
-    pub const DISPLAY_SECONDS: f32 = 8.0;
+        /// errors stay up longer, they usually need a reaction
+        pub fn durationMs(self: Level) u32 {
+            return switch (self) {
+                .info, .warn => DISPLAY_MS,
+                .err => 2 * DISPLAY_MS,
+            };
+        }
+    };
+    pub const Entry = union(enum) { empty, used: struct {
+        msg: []const u8,
+        level: Level = .info,
+        /// unix milliseconds the toast appeared
+        shown_ms: i64 = 0,
+        /// how long the toast stays, every entry runs its own timer
+        duration_ms: u32 = DISPLAY_MS,
+
+        fn expiresMs(self: @This()) i64 {
+            return self.shown_ms + self.duration_ms;
+        }
+    } };
+    const MAX_ENTRIES = 128;
+    pub const DISPLAY_MS: u32 = 8 * std.time.ms_per_s;
     pub const MAX_VISIBLE: usize = 4;
 
     pub fn append(self: *Notifications, alloc: std.mem.Allocator, comptime fmt: []const u8, args: anytype) !void {
+        return self.push(alloc, .info, fmt, args);
+    }
+
+    pub fn push(self: *Notifications, alloc: std.mem.Allocator, level: Level, comptime fmt: []const u8, args: anytype) !void {
+        return self.pushFor(alloc, level, level.durationMs(), fmt, args);
+    }
+
+    /// `push` with a toast duration of its own.
+    pub fn pushFor(self: *Notifications, alloc: std.mem.Allocator, level: Level, duration_ms: u32, comptime fmt: []const u8, args: anytype) !void {
         switch (self.list[MAX_ENTRIES - 1]) {
             .used => |en| {
                 alloc.free(en.msg);
@@ -154,16 +539,41 @@ pub const Notifications = struct {
 
         std.mem.copyBackwards(Entry, self.list[1..], self.list[0 .. MAX_ENTRIES - 1]);
         const text = try std.fmt.allocPrint(alloc, fmt, args);
-        self.list[0] = .{ .used = .{ .msg = text, .alive = 0 } };
+        self.list[0] = .{ .used = .{ .msg = text, .level = level, .shown_ms = self.clockMs(), .duration_ms = duration_ms } };
+        self.pushed = true;
     }
 
-    pub fn tick(self: *Notifications, dt: f32) void {
-        for (&self.list) |*en| {
-            switch (en.*) {
-                .used => |*slot| slot.alive += dt,
-                else => {},
-            }
+    fn clockMs(self: *const Notifications) i64 {
+        return if (self.io) |io| prv.http.nowMs(io) else self.now_ms;
+    }
+
+    /// Advances the toast timers to `now_ms`. True when a toast appeared or
+    /// expired since the last tick, so the screen needs a redraw.
+    pub fn tick(self: *Notifications, now_ms: i64) bool {
+        const prev_ms = self.now_ms;
+        self.now_ms = now_ms;
+        var changed = self.pushed;
+        self.pushed = false;
+        for (self.list) |en| switch (en) {
+            .used => |slot| {
+                const expires = slot.expiresMs();
+                if (expires > prev_ms and expires <= now_ms) changed = true;
+            },
+            .empty => break,
+        };
+        return changed;
+    }
+
+    /// Milliseconds until the next toast expires, the main loop sleeps at
+    /// most this long. Null without a toast on screen.
+    pub fn msUntilExpiry(self: *const Notifications) ?i64 {
+        var next: ?i64 = null;
+        var it = self.iter();
+        while (it.next()) |en| {
+            const left = en.used.expiresMs() - self.now_ms;
+            next = if (next) |n| @min(n, left) else left;
         }
+        return next;
     }
 
     pub fn hasVisible(self: *const Notifications) bool {
@@ -171,14 +581,25 @@ pub const Notifications = struct {
         return it.next() != null;
     }
 
+    /// Entries whose toast is still showing, newest first.
     pub fn iter(self: *const Notifications) Iterator {
         return .{
             .list = &self.list,
+            .now_ms = self.now_ms,
         };
     }
 
+    /// The whole log, newest first.
+    pub fn history(self: *const Notifications) []const Entry {
+        for (self.list, 0..) |en, i| {
+            if (en == .empty) return self.list[0..i];
+        }
+        return &self.list;
+    }
+
     const Iterator = struct {
         list: *const [MAX_ENTRIES]Entry,
+        now_ms: i64,
         i: u8 = 0,
         pub fn next(self: *Iterator) ?*const Entry {
             while (self.i < MAX_ENTRIES) {
@@ -187,7 +608,7 @@ pub const Notifications = struct {
 
                 switch (n.*) {
                     .used => |en| {
-                        if (en.alive < DISPLAY_SECONDS) return n;
+                        if (en.expiresMs() > self.now_ms) return n;
                     },
                     else => {},
                 }
@@ -203,6 +624,9 @@ const ToolStatusEntry = struct {
     lines: std.ArrayList(r.tui.Line) = .empty,
     child_id: ?prv.Swarm.AgentId = null,
     is_error: ?bool = null,
+    /// full output that was cut for the model, see `tools.spillOutputToOwned`
+    spill: ?[]const u8 = null,
+    spill_lines: usize = 0,
 };
 
 pub const ToolStatusLineInput = struct {
@@ -217,6 +641,8 @@ const ToolStatusAgent = struct {
 
 const ToolStatusStore = struct {
     agents: [prv.Swarm.MAX_AGENTS]ToolStatusAgent = [_]ToolStatusAgent{.{}} ** prv.Swarm.MAX_AGENTS,
+    /// newest spill file of any agent, opened by `:output`
+    last_spill: ?[]const u8 = null,
 
     fn setResult(self: *ToolStatusStore, alloc: std.mem.Allocator, agent_id: prv.Swarm.AgentId, result: prv.adapter.ToolResult) !void {
         const agent = &self.agents[agent_id.index];
@@ -231,6 +657,345 @@ const ToolStatusStore = struct {
     }
 };
 
+pub const FileChangeKind = enum {
+    added,
+    modified,
+    deleted,
+
+    pub fn icon(self: FileChangeKind) []const u8 {
+        return switch (self) {
+            .added => "+",
+            .modified => "~",
+            .deleted => "-",
+        };
+    }
+};
+
+pub const FileChange = struct {
+    kind: FileChangeKind,
+    tool: []const u8,
+    timestamp: i96,
+};
+
+/// Files touched by agent tools since the session started, keyed by absolute
+/// path. Each path keeps its full change history, oldest first.
+pub const WorkspaceChanges = struct {
+    files: std.array_hash_map.String(std.ArrayList(FileChange)) = .empty,
+    /// files already copied to the session backup
+    backed_up: std.array_hash_map.String(void) = .empty,
+
+    /// True the first time `path` is seen, the caller then takes the backup.
+    pub fn claimBackup(self: *WorkspaceChanges, alloc: std.mem.Allocator, path: []const u8) !bool {
+        const entry = try self.backed_up.getOrPut(alloc, path);
+        if (entry.found_existing) return false;
+        entry.key_ptr.* = try alloc.dupe(u8, path);
+        return true;
+    }
+
+    pub fn record(self: *WorkspaceChanges, alloc: std.mem.Allocator, path: []const u8, change: FileChange) !void {
+        const entry = try self.files.getOrPut(alloc, path);
+        if (!entry.found_existing) {
+            entry.key_ptr.* = try alloc.dupe(u8, path);
+            entry.value_ptr.* = .empty;
+        }
+        try entry.value_ptr.append(alloc, .{
+            .kind = change.kind,
+            .tool = try alloc.dupe(u8, change.tool),
+            .timestamp = change.timestamp,
+        });
+    }
+
+    /// Net change against the session baseline. A file created and then
+    /// deleted again within the session is unchanged.
+    pub fn netKind(history: []const FileChange) ?FileChangeKind {
+        if (history.len == 0) return null;
+        const first = history[0].kind;
+        const last = history[history.len - 1].kind;
+        if (first == .added and last == .deleted) return null;
+        if (last == .deleted) return .deleted;
+        if (first == .added) return .added;
+        return .modified;
+    }
+
+    pub fn changedCount(self: *const WorkspaceChanges) usize {
+        var count: usize = 0;
+        for (self.files.values()) |history| {
+            if (netKind(history.items) != null) count += 1;
+        }
+        return count;
+    }
+};
+
+/// what `:output` reads of a spill file, and the lines space and b move
+const OUTPUT_POPUP_MAX_BYTES = 4 * 1024 * 1024;
+const OUTPUT_POPUP_PAGE = 20;
+
+/// Centered overlay. Closed with esc, scrolled with the arrow keys.
+/// Popups other than `.info` and `.tool_output` highlight a row and act on
+/// `targets[selected]`.
+pub const Popup = struct {
+    title: []const u8,
+    lines: std.ArrayList(r.tui.Line) = .empty,
+    scroll: usize = 0,
+    kind: Kind = .info,
+    selected: usize = 0,
+    /// per line payload, the chat message index for the token heatmap
+    targets: []const usize = &.{},
+
+    pub const Kind = enum { info, token_heatmap, bash_allow, grep, references, sessions, checkpoints, todos, settings, tool_output, tasks, memory, providers, image, cost_confirm, links, tool_errors, tool_trust };
+
+    /// Plain text to scroll through, no row to select.
+    pub fn scrollsOnly(self: *const Popup) bool {
+        return switch (self.kind) {
+            .info, .tool_output, .image, .cost_confirm, .links, .tool_trust => true,
+            else => false,
+        };
+    }
+
+    pub fn moveSelection(self: *Popup, delta: isize) void {
+        if (self.scrollsOnly()) {
+            self.scroll = if (delta < 0) self.scroll -| 1 else self.scroll + 1;
+            return;
+        }
+        if (self.targets.len == 0) return;
+        const next = @as(isize, @intCast(self.selected)) + delta;
+        self.selected = @intCast(std.math.clamp(next, 0, @as(isize, @intCast(self.targets.len - 1))));
+    }
+};
+
+/// Estimated input of the next turn, what the `.cost_confirm` popup shows.
+/// Token counts are the byte estimate compaction uses, cache reads are
+/// priced at the full input rate, so this errs high.
+pub const CostPreview = struct {
+    model: []const u8,
+    /// history, tools and the model name already in the request
+    context_tokens: u64,
+    prompt_tokens: u64,
+    price: r.cost.Price,
+
+    pub fn dollars(self: CostPreview) f64 {
+        return @as(f64, @floatFromInt(self.context_tokens + self.prompt_tokens)) * self.price.input;
+    }
+
+    /// Whether sending waits for a confirmation, never with `threshold` 0.
+    pub fn needsConfirm(self: CostPreview, threshold: f64) bool {
+        return threshold > 0 and self.dollars() >= threshold;
+    }
+};
+
+/// Image of the `.image` popup. `area` is set while the popup renders, the
+/// main loop draws the image there once the frame is out.
+pub const ImageView = struct {
+    /// data owned by the gpa
+    image: prv.adapter.ImageContent,
+    size: ?r.media.Size,
+    area: ?r.tui.Rect = null,
+};
+
+/// Largest image preview in cells, the popup is capped at 96 columns.
+const IMAGE_PREVIEW_COLS = 90;
+const IMAGE_PREVIEW_ROWS = 30;
+
+/// State of the C-f grep popup. The query is typed into the popup, every
+/// change cancels the running rg and starts a new one.
+pub const GrepSearch = struct {
+    query_buf: [128]u8 = undefined,
+    query_len: usize = 0,
+    handle: ?prv.exec.CmdPool.Handle = null,
+    hits: []const r.tools.rg.Hit = &.{},
+
+    pub fn query(self: *const GrepSearch) []const u8 {
+        return self.query_buf[0..self.query_len];
+    }
+};
+
+/// State of the C-s chat search. While `editing` the keys build the query,
+/// afterwards n and N step between matching chat entries and the chat
+/// renders with the query highlighted until esc.
+pub const ChatSearch = struct {
+    query_buf: [128]u8 = undefined,
+    query_len: usize = 0,
+    active: bool = false,
+    editing: bool = false,
+    /// chat entry of the current match
+    current: ?usize = null,
+    /// chat entry `renderChatArea` scrolls into view on the next frame
+    jump_to: ?usize = null,
+
+    pub fn query(self: *const ChatSearch) []const u8 {
+        return self.query_buf[0..self.query_len];
+    }
+
+    /// Moves to the next matching entry above (`older`) or below the
+    /// current one, wrapping around at the ends. False without any match.
+    pub fn step(self: *ChatSearch, entries: []const ChatEntry, older: bool) bool {
+        const n = entries.len;
+        if (n == 0 or self.query_len == 0) return false;
+        var idx = @min(self.current orelse n, n);
+        for (0..n) |_| {
+            idx = if (older)
+                (if (idx == 0) n - 1 else idx - 1)
+            else if (idx + 1 >= n) 0 else idx + 1;
+            if (!entryMatches(entries[idx], self.query())) continue;
+            self.current = idx;
+            self.jump_to = idx;
+            return true;
+        }
+        return false;
+    }
+
+    /// Number of the current match counted from the top, and of all matches.
+    pub fn position(self: *const ChatSearch, entries: []const ChatEntry) struct { usize, usize } {
+        var index: usize = 0;
+        var total: usize = 0;
+        for (entries, 0..) |entry, i| {
+            if (!entryMatches(entry, self.query())) continue;
+            total += 1;
+            if (self.current) |current| {
+                if (i <= current) index = total;
+            }
+        }
+        return .{ index, total };
+    }
+};
+
+/// Walking the chat by message from the keyboard. C-j selects the newest
+/// message, j and k move between messages, y copies one, c its next code
+/// block and o opens it in $EDITOR. Tool calls are stops too, e expands
+/// the output cut for the model and esc collapses it.
+pub const MessageSelect = struct {
+    /// chat entry of the highlighted message, null while not selecting
+    current: ?usize = null,
+    /// code block of `current` the next c copies
+    code_block: usize = 0,
+
+    /// Moves to the next entry with message text above (`older`) or below
+    /// the current one, the newest without a current one. False at the ends.
+    pub fn step(self: *MessageSelect, entries: []const ChatEntry, older: bool) bool {
+        var idx = @min(self.current orelse entries.len, entries.len);
+        while (true) {
+            if (older) {
+                if (idx == 0) return false;
+                idx -= 1;
+            } else {
+                if (idx + 1 >= entries.len) return false;
+                idx += 1;
+            }
+            if (!hasMessageText(entries[idx]) and !hasToolCall(entries[idx])) continue;
+            self.current = idx;
+            self.code_block = 0;
+            return true;
+        }
+    }
+};
+
+/// What the main loop does with the terminal for the app, which does not
+/// own it.
+pub const TerminalRequest = union(enum) {
+    /// text for the system clipboard, sent as OSC 52
+    copy: []const u8,
+    /// file to open in $EDITOR, the TUI pauses meanwhile
+    edit: []const u8,
+    /// `edit` for a `privateScratch` file, deleted with its directory after
+    scratch: []const u8,
+};
+
+fn hasMessageText(entry: ChatEntry) bool {
+    for (entry.parts) |part| switch (part) {
+        .message, .plain_text => return true,
+        else => {},
+    };
+    return false;
+}
+
+fn hasToolCall(entry: ChatEntry) bool {
+    for (entry.parts) |part| {
+        if (part == .tool_call) return true;
+    }
+    return false;
+}
+
+/// The text parts of `entry`, blank line separated.
+pub fn messageBody(alloc: std.mem.Allocator, entry: ChatEntry) ![]const u8 {
+    var out: std.ArrayList(u8) = .empty;
+    for (entry.parts) |part| switch (part) {
+        .message, .plain_text => |text| {
+            if (out.items.len > 0) try out.appendSlice(alloc, "\n\n");
+            try out.appendSlice(alloc, text);
+        },
+        else => {},
+    };
+    return out.toOwnedSlice(alloc);
+}
+
+/// The `n`th fenced code block of markdown `text`, without its fences.
+pub fn codeBlock(text: []const u8, n: usize) ?[]const u8 {
+    var lines = std.mem.splitScalar(u8, text, '\n');
+    var index: usize = 0;
+    var start: ?usize = null;
+    var fence: []const u8 = "";
+    while (lines.index) |line_start| {
+        const line = std.mem.trimStart(u8, lines.next().?, " ");
+        if (start) |block_start| {
+            if (!std.mem.startsWith(u8, line, fence)) continue;
+            if (std.mem.trim(u8, line[fence.len..], " \r`").len > 0) continue;
+            if (index == n) return std.mem.trimEnd(u8, text[block_start..line_start], "\r\n");
+            index += 1;
+            start = null;
+        } else if (std.mem.startsWith(u8, line, "```")) {
+            fence = line[0 .. std.mem.indexOfNone(u8, line, "`") orelse line.len];
+            start = lines.index orelse text.len;
+        }
+    }
+    return null;
+}
+
+/// Case-insensitive match in the text an entry shows, tool cards and diffs
+/// left out.
+pub fn entryMatches(entry: ChatEntry, query: []const u8) bool {
+    if (query.len == 0) return false;
+    for (entry.parts) |part| {
+        const text = switch (part) {
+            .message, .plain_text, .thinking => |t| t,
+            .provider_error => |err| err.message,
+            else => continue,
+        };
+        if (std.ascii.indexOfIgnoreCase(text, query) != null) return true;
+    }
+    return false;
+}
+
+/// Splits the spans of `line` around case-insensitive matches of `query`
+/// and colors the matched pieces with `style`. True when anything matched.
+pub fn highlightLine(alloc: std.mem.Allocator, line: *r.tui.Line, query: []const u8, style: r.tui.Style) !bool {
+    if (query.len == 0) return false;
+    var out: std.ArrayList(r.tui.Span) = .empty;
+    var changed = false;
+    for (line.spans.items) |span| {
+        if (span.kind != .text) {
+            try out.append(alloc, span);
+            continue;
+        }
+        var rest = span.content;
+        while (std.ascii.indexOfIgnoreCase(rest, query)) |at| {
+            changed = true;
+            if (at > 0) try out.append(alloc, .{ .content = rest[0..at], .style = span.style });
+            var hit = span.style;
+            hit.fg = style.fg;
+            hit.bg = style.bg;
+            try out.append(alloc, .{ .content = rest[at..][0..query.len], .style = hit });
+            rest = rest[at + query.len ..];
+        }
+        if (rest.len == span.content.len) {
+            try out.append(alloc, span);
+        } else if (rest.len > 0) {
+            try out.append(alloc, .{ .content = rest, .style = span.style });
+        }
+    }
+    if (changed) line.spans = out;
+    return changed;
+}
+
 pub const App = struct {
     gpa: std.mem.Allocator,
     /// app arena,
@@ -241,34 +1006,102 @@ pub const App = struct {
     arena_streaming_preview: prv.ThreadSafeArena,
     /// frame render arena
     arena_frame: std.heap.ArenaAllocator,
+    /// grep popup hits and lines, reset per search result
+    arena_grep: std.heap.ArenaAllocator,
+    /// spill file text and lines of the output pager, reset per open
+    arena_output: std.heap.ArenaAllocator,
     mu: std.Io.Mutex = .init,
     io: std.Io,
     input_buffer: std.ArrayList(u8) = .empty,
     input_cursor: u32 = 0,
-    input_scroll_offset: u16 = 0,
+    input_scroll_offset: usize = 0,
     // ---------------
     // async interface
     permission_queue: Locked(std.ArrayList(*r.prv.Swarm.PermissionReq)),
     broadcast_queue: Locked(std.ArrayList(r.prv.Swarm.BroadcastEntry)),
     tool_status_entries: Locked(ToolStatusStore) = .{},
+    workspace_changes: Locked(WorkspaceChanges) = .{},
+    /// pre-edit snapshots for `:undo`, added from tool workers
+    checkpoints: Locked(r.checkpoint.Checkpoints) = .{},
+    /// bumped on every prompt the user sends, groups checkpoints by turn
+    user_turn: u32 = 0,
+    /// project bash allowlist, read by the bash tool from agent threads
+    bash_allow: Locked(r.allowlist.BashAllowList) = .{},
+    /// `blitz.set_bash_rules`, config allow and deny patterns for bash
+    bash_rules: Locked(r.allowlist.BashRules) = .{},
+    /// project edit allowlist, checked on the main thread when edits queue up
+    edit_allow: r.allowlist.EditAllowList = .{},
+    /// `.blitz/memory.md`, written by the `memory` tool from agent threads
+    memory: Locked(r.memory.Memory) = .{},
+    sensitive_paths: r.sensitive.SensitivePaths = .defaults,
+    sandbox: r.sandbox.Sandbox = .{},
+    /// `blitz.set_web_search`, backend of the `web_search` tool
+    web_search: r.tools.websearch.Config = .{},
+    /// `blitz.set_session_encryption`, seals stored sessions
+    vault: r.vault.Vault = .{},
+    /// model globs that get first-sentence tool descriptions, `blitz.set_minimal_schemas`
+    minimal_schema_models: []const []const u8 = &.{},
+    /// `blitz.set_retry_policy`, backoff for transient provider errors
+    retry_policy: prv.retry.Policy = .{},
+    /// `blitz.set_fallback_models`, tried in order once a model gives up
+    fallback_models: []const []const u8 = &.{},
+    popup: ?Popup = null,
+    grep: GrepSearch = .{},
+    chat_search: ChatSearch = .{},
+    message_select: MessageSelect = .{},
+    terminal_request: ?TerminalRequest = null,
+    /// rows of the C-l references popup, pinned first
+    ref_list: []const r.refs.Ref = &.{},
+    /// references kept across answers, in the session arena
+    pinned_refs: std.ArrayList(r.refs.Ref) = .empty,
+    /// `permissionHash` of every request approved this session
+    approved_requests: std.AutoHashMapUnmanaged(u64, void) = .empty,
+    /// rows of the C-b session browser
+    session_list: []const r.sessions.Entry = &.{},
+    /// file name of the stored session this one was resumed from
+    session_name: ?[]const u8 = null,
+    /// session C-n or `:clear` archived last, see `session.archiveAndReset`
+    archived_buf: [128]u8 = undefined,
+    archived_len: usize = 0,
+    archived_at_ms: i64 = 0,
+    /// open conversations, empty until the first `:tab new`
+    tabs: std.ArrayList(Tab) = .empty,
+    active_tab: usize = 0,
+    /// `sessionCost` when the active tab was entered
+    tab_cost_mark: f64 = 0,
     //-----------------
     active_permission: ?*r.prv.Swarm.PermissionReq = null,
+    /// hunk comments on the pending diff
+    review: r.review.Review = .{},
     swarm: *prv.Swarm = undefined,
     config: prv.config.BlitzdenkCfg = .{},
     main_agent_id: ?prv.Swarm.AgentId = null,
-    running: bool = false,
+    run_state: RunState = .idle,
     frame_count: usize = 0,
+    /// Wakes the main loop out of its input poll from worker threads.
+    waker: r.tui.Waker = .{},
     scroll_offset: usize = 0,
     auto_scroll: bool = true,
     input_mode: InputMode = .text,
     mode: r.ContextFactory.Mode = @enumFromInt(0),
+    /// turns a research-mode run may take before it has to report
+    research_turns: u32 = DEFAULT_RESEARCH_TURNS,
     context_factory: *r.ContextFactory,
     theme: Theme = .default,
+    appearance: r.dash.Appearance = .{},
+    todo_nudge: TodoNudge = .{},
+    todo_nudges_sent: u32 = 0,
+    /// set while a `@model:` turn runs
+    turn_model: ?TurnModel = null,
     cwd: []const u8,
     remote_cwd: []const u8 = "/",
     flags: AppFlags = .{},
     default_context_limit: u32 = CONTEXT_LIMIT,
+    compact_threshold: u8 = prv.compact.DEFAULT_THRESHOLD_PERCENT,
+    /// cheaper model for compaction summaries, empty keeps the agent's
+    compact_model: []const u8 = "",
     screenshot_buf: ?[]const u8 = null,
+    screenshot_media_type: []const u8 = "image/png",
     dirty: bool = true,
     history: std.ArrayList(PromptEntry) = .empty,
     history_cursor: usize = 0,
@@ -280,6 +1113,8 @@ pub const App = struct {
     current_plan_file: ?[]const u8 = null,
     passphrase_args_buf: [512]u8 = undefined,
     queued: MessageQueue = .{},
+    /// `:task` prompts waiting for the current run to end
+    tasks: TaskQueue = .{},
     ui_state: UiState = .chat,
     keymap: r.keys.KeyMap = .{},
     cmd_queue: r.cmd.CommandQueue,
@@ -289,8 +1124,46 @@ pub const App = struct {
     lua_status_bar_cache_len: usize = 0,
     mcp_manager: r.mcp.Manager,
     lsp_manager: r.lsp.Manager,
+    plugin_manager: r.plugins.Manager,
     notifications: Notifications = .{},
     event_bus: r.events.EventBus = .{},
+    event_log: r.events.JsonlLogger = .{},
+    /// `blitz.add_hook` commands
+    hooks: r.hooks.Hooks = .{},
+    /// `.blitz/backups/<backup_session>` for this session, set on reset
+    backup_session: []const u8 = "",
+    backup_session_buf: [32]u8 = undefined,
+    /// graphics protocol of the terminal, none outside the TUI
+    graphics: r.media.Protocol = .none,
+    /// images saved to `.blitz/media/<backup_session>` so far
+    media_saved: usize = 0,
+    /// newest saved image, `:image` opens it
+    last_image: []const u8 = "",
+    image_view: ?ImageView = null,
+    /// restored session has an unfinished turn, see `session.recoverInterruptedRun`
+    resume_pending: bool = false,
+    /// session totals last reported through `usage_updated`
+    last_usage: r.prv.adapter.TokenUsage = .{},
+    /// background request loading the default ollama model
+    model_preload: ?prv.exec.CmdPool.Handle = null,
+    /// LiteLLM price list, null until a cached copy exists
+    prices: ?r.cost.PriceList = null,
+    /// `blitz.set_model_price`, checked before the LiteLLM list
+    custom_prices: r.cost.PriceList,
+    /// pages of links in prompts, see links.zig
+    links: r.links.Links = .{},
+    /// failed tool calls of the turn, the strip above the prompt
+    tool_failures: ToolFailures = .{},
+    /// a failed call running again from the `.tool_errors` panel
+    tool_retry: ?*ToolRetry = null,
+    /// today's spend over every instance, see spend.zig
+    spend: r.spend.Spend = .{},
+    /// `blitz.set_cost_confirm`, dollars of estimated input that make a
+    /// new turn ask first, 0 never asks
+    cost_confirm: f64 = DEFAULT_COST_CONFIRM,
+    price_fetch: ?prv.exec.CmdPool.Handle = null,
+    /// `.blitz/last_session.json` exists, the dashboard offers `:handoff`
+    last_session_available: bool = false,
     injection_hooks: r.inject.InjectionsHooks = .{},
     completion_suggestion: ?[]const u8 = null,
 
@@ -310,12 +1183,17 @@ pub const App = struct {
             .arena_session = .init(gpa, io),
             .arena_streaming_preview = .init(gpa, io),
             .arena_frame = .init(gpa),
+            .arena_grep = .init(gpa),
+            .arena_output = .init(gpa),
             .context_factory = agent_factory,
             .io = io,
+            .notifications = .{ .io = io },
+            .custom_prices = .{ .arena = .init(gpa) },
             .cwd = cwd,
             .cmd_queue = try r.cmd.CommandQueue.init(gpa),
             .lua_vm = lua_vm,
             .mcp_manager = r.mcp.Manager.init(gpa, agent_factory.io),
+            .plugin_manager = r.plugins.Manager.init(gpa, agent_factory.io),
             .lsp_manager = r.lsp.Manager.init(gpa, agent_factory.io),
             .injection_hooks = try r.inject.InjectionsHooks.init(gpa),
             .permission_queue = .{
@@ -324,6 +1202,7 @@ pub const App = struct {
             .broadcast_queue = .{
                 .value = try .initCapacity(gpa, 255),
             },
+            .waker = .init(),
         };
     }
 
@@ -337,9 +1216,31 @@ pub const App = struct {
             defer g.unlock();
             for (g.ptr.list.items) |e| self.swarm.exec.cancel(e.handle);
         }
+        self.dropToolRetry();
+        self.spend.deinit();
+        self.event_log.deinit();
 
         self.lsp_manager.deinit();
         self.mcp_manager.deinit();
+        self.plugin_manager.deinit();
+        {
+            const g = self.bash_allow.lock(self.io);
+            defer g.unlock();
+            g.ptr.deinit(self.gpa);
+        }
+        self.edit_allow.deinit(self.gpa);
+        {
+            const g = self.memory.lock(self.io);
+            defer g.unlock();
+            g.ptr.deinit(self.gpa);
+        }
+        self.dropImageView();
+        self.waker.deinit();
+        if (self.prices) |*p| p.deinit();
+        self.custom_prices.deinit();
+        if (self.grep.handle) |h| self.swarm.exec.release(h);
+        self.arena_grep.deinit();
+        self.arena_output.deinit();
         self.arena_streaming_preview.deinit();
         self.lua_vm.deinit();
         self.arena_session.deinit();
@@ -347,6 +1248,7 @@ pub const App = struct {
     }
 
     pub fn cancelPermissions(self: *App) void {
+        self.review.clear();
         if (self.active_permission) |req| {
             req.state = .denied;
             req.event.set(self.io);
@@ -365,6 +1267,17 @@ pub const App = struct {
         self.returnToText();
     }
 
+    /// Deny every pending permission right away and cancel the run that
+    /// asked for them. Tools waiting on a dialog get `.denied` before the
+    /// cancel command reaches the swarm.
+    pub fn denyAndStop(self: *App) !void {
+        self.cancelPermissions();
+        self.cancelToolRetry();
+        if (!self.isRunning()) return;
+        self.run_state = .cancelling;
+        try self.cmd_queue.append(self.io, .cancel);
+    }
+
     /// Session-scoped allocator. Wiped on reset.
     pub fn sessionAlloc(self: *App) std.mem.Allocator {
         return self.arena_session.allocator();
@@ -429,79 +1342,2359 @@ pub const App = struct {
         try g.ptr.setResult(self.sessionAlloc(), agent_id, result);
     }
 
-    /// App-scoped allocator. Survives session resets.
-    pub fn appAlloc(self: *App) std.mem.Allocator {
-        return self.arena_app.allocator();
-    }
+    /// Called from tool workers when the output of `call_id` was cut and
+    /// the whole of it written to `path`.
+    pub fn setToolSpill(self: *App, agent_id: prv.Swarm.AgentId, call_id: []const u8, path: []const u8, lines: usize) !void {
+        if (agent_id.index >= prv.Swarm.MAX_AGENTS) return error.InvalidAgent;
+        const g = self.tool_status_entries.lock(self.io);
+        defer g.unlock();
 
-    pub fn reset(self: *App) void {
-        self.dropStreamingPreview();
-        self.cancelPermissions();
-        self.swarm.cancelAll();
+        const alloc = self.sessionAlloc();
+        const agent = &g.ptr.agents[agent_id.index];
+        if (agent.generation != agent_id.generation) {
+            agent.* = .{ .generation = agent_id.generation };
+        }
 
-        self.main_agent_id = null;
-        self.frame_count = 0;
-        self.scroll_offset = 0;
-        self.input_mode = .text;
-        self.input_cursor = 0;
-        self.streaming_entry = null;
-        self.compaction_indicator_active = false;
-        self.compaction_completion_seen_count = 0;
-        self.swarm.reset();
-        self.screenshot_buf = null;
+        const res = try agent.entries.getOrPut(alloc, call_id);
+        if (!res.found_existing) {
+            res.key_ptr.* = try alloc.dupe(u8, call_id);
+            res.value_ptr.* = .{};
+        }
+        const owned = try alloc.dupe(u8, path);
+        res.value_ptr.spill = owned;
+        res.value_ptr.spill_lines = lines;
+        g.ptr.last_spill = owned;
+    }
+
+    /// Called from tool workers after a file was written, patched or removed.
+    pub fn recordFileChange(self: *App, path: []const u8, kind: FileChangeKind, tool: []const u8) !void {
+        const g = self.workspace_changes.lock(self.io);
+        defer g.unlock();
+        try g.ptr.record(self.sessionAlloc(), path, .{
+            .kind = kind,
+            .tool = tool,
+            .timestamp = std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds,
+        });
         self.dirty = true;
-        _ = self.arena_session.reset(.free_all);
-        // Backing storage just got freed — reset list headers to .empty so
-        // stale ptr/capacity don't cause UB on next append.
-        self.input_buffer = .empty;
-        self.chat_entries = .empty;
-        self.queued = .{};
-        self.lua_vm.disableAllMcp();
-        self.event_bus.emit(self, .session_reset) catch {};
-        self.reloadMcpTools() catch {};
+    }
 
-        // cleanup
-        self.tool_status_entries = .{};
-        self.permission_queue.value.clearRetainingCapacity();
-        self.broadcast_queue.value.clearRetainingCapacity();
+    pub fn claimBackup(self: *App, path: []const u8) bool {
+        const g = self.workspace_changes.lock(self.io);
+        defer g.unlock();
+        return g.ptr.claimBackup(self.sessionAlloc(), path) catch false;
     }
 
-    pub fn tick(self: *App) !void {
+    /// Called from tool workers before a file is modified.
+    pub fn addCheckpoint(self: *App, path: []const u8, tool: []const u8) ?r.checkpoint.Checkpoint {
+        const g = self.checkpoints.lock(self.io);
+        defer g.unlock();
+        const now = std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds;
+        return g.ptr.add(self.sessionAlloc(), path, tool, self.user_turn, now) catch null;
+    }
 
-        // --------------------------------------------------
-        // drain broadcoast
-        var saw_final_agent_broadcast = false;
-        {
-            const g = self.broadcast_queue.lock(self.io);
+    /// `:undo` reverts the last edit, `:undo turn` every edit of the newest
+    /// turn, `:undo list` opens the checkpoint popup.
+    pub fn undoCommand(self: *App, arg: []const u8) !void {
+        if (std.mem.eql(u8, arg, "list")) return self.openCheckpointsPopup();
+        if (arg.len == 0 and try r.session.undoArchive(self)) return;
+        const from = blk: {
+            const g = self.checkpoints.lock(self.io);
             defer g.unlock();
+            if (std.mem.eql(u8, arg, "turn")) break :blk g.ptr.lastTurnStart();
+            break :blk g.ptr.items.items.len -| 1;
+        };
+        try self.undoFrom(from);
+    }
 
-            for (g.ptr.items) |en| {
-                if (en.role == .agent) {
+    /// Put back every checkpoint from index `from` on, newest first.
+    fn undoFrom(self: *App, from: usize) !void {
+        var tmp = std.heap.ArenaAllocator.init(self.gpa);
+        defer tmp.deinit();
+        const alloc = tmp.allocator();
+
+        const undone = blk: {
+            const g = self.checkpoints.lock(self.io);
+            defer g.unlock();
+            const items = g.ptr.items.items;
+            if (from >= items.len) break :blk &[_]r.checkpoint.Checkpoint{};
+            const copy = try alloc.dupe(r.checkpoint.Checkpoint, items[from..]);
+            g.ptr.items.shrinkRetainingCapacity(from);
+            break :blk copy;
+        };
+        if (undone.len == 0) {
+            try self.notifications.append(self.arena_app.allocator(), "nothing to undo", .{});
+            return;
+        }
+
+        var failed: usize = 0;
+        var i = undone.len;
+        while (i > 0) {
+            i -= 1;
+            const cp = undone[i];
+            const script = try r.checkpoint.restoreScript(alloc, self.backup_session, cp.id, cp.path);
+            const res = self.swarm.exec.runAndWait(.{ .argv = &.{ "/bin/sh", "-c", script } }) catch {
+                failed += 1;
+                continue;
+            };
+            defer self.swarm.exec.alloc.free(res.stdout);
+            defer self.swarm.exec.alloc.free(res.stderr);
+            if (res.ty == .success) continue;
+            failed += 1;
+            if (std.mem.find(u8, res.stderr, r.checkpoint.MISSING_SNAPSHOT) != null) {
+                try self.notifications.push(self.arena_app.allocator(), .warn, "left {s} as is, no snapshot was taken before the {s}", .{ cp.path, cp.tool });
+            }
+        }
+
+        if (failed > 0) {
+            try self.notifications.push(self.arena_app.allocator(), .warn, "undid {d} edit(s), {d} failed", .{ undone.len - failed, failed });
+        } else if (undone.len == 1) {
+            try self.notifications.append(self.arena_app.allocator(), "undid {s} of {s}", .{ undone[0].tool, std.fs.path.basename(undone[0].path) });
+        } else {
+            try self.notifications.append(self.arena_app.allocator(), "undid {d} edits", .{undone.len});
+        }
+        self.dirty = true;
+    }
+
+    /// Checkpoints newest first, `u` undoes the selected one and all newer.
+    pub fn openCheckpointsPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "checkpoints · u undo back to here", .kind = .checkpoints };
+
+        const g = self.checkpoints.lock(self.io);
+        defer g.unlock();
+        const items = g.ptr.items.items;
+
+        if (items.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no edits this session", .{}, .{ .fg = self.theme.muted }));
+        }
+        const now_s: i64 = @intCast(@divTrunc(std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds, std.time.ns_per_s));
+        const targets = try alloc.alloc(usize, items.len);
+        for (0..items.len) |i| {
+            const cp = items[items.len - 1 - i];
+            targets[i] = items.len - 1 - i;
+            var age_buf: [16]u8 = undefined;
+            const cp_s: i64 = @intCast(@divTrunc(cp.timestamp, std.time.ns_per_s));
+
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "turn {d:<3} ", .{cp.turn}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s:<6} ", .{cp.tool}, .{ .fg = self.theme.info });
+            try l.pushSpanPrint(alloc, "{s}  ", .{r.allowlist.projectPath(self.cwd, cp.path)}, .{ .fg = self.theme.text });
+            try l.pushSpanPrint(alloc, "{s}", .{r.sessions.formatAge(&age_buf, now_s - cp_s)}, .{ .fg = self.theme.muted });
+            try popup.lines.append(alloc, l);
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn checkpointsKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .checkpoints) return false;
+
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'u' => {
+                if (popup.targets.len == 0) return true;
+                try self.undoFrom(popup.targets[popup.selected]);
+                try self.openCheckpointsPopup();
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// Absolute paths touched by tools this session.
+    pub fn changedPaths(self: *App, alloc: std.mem.Allocator) ![]const []const u8 {
+        const g = self.workspace_changes.lock(self.io);
+        defer g.unlock();
+        const keys = g.ptr.files.keys();
+        const out = try alloc.alloc([]const u8, keys.len);
+        for (keys, out) |key, *slot| slot.* = try alloc.dupe(u8, key);
+        return out;
+    }
+
+    pub fn changedFileCount(self: *App) usize {
+        const g = self.workspace_changes.tryLock(self.io) orelse return 0;
+        defer g.unlock();
+        return g.ptr.changedCount();
+    }
+
+    /// Insert the most recent failed tool result into the prompt as a
+    /// markdown blockquote.
+    pub fn quoteLastToolError(self: *App) !void {
+        const agent = self.mainAgent() orelse return;
+        const messages = agent.chat.messages.items;
+
+        var i = messages.len;
+        const result: prv.adapter.ToolResult = outer: while (i > 0) {
+            i -= 1;
+            var j = messages[i].parts.len;
+            while (j > 0) {
+                j -= 1;
+                switch (messages[i].parts[j]) {
+                    .tool_result => |res| if (res.is_error) break :outer res,
+                    else => {},
+                }
+            }
+        } else {
+            try self.notifications.append(self.arena_app.allocator(), "no tool error to quote", .{});
+            return;
+        };
+
+        const alloc = self.sessionAlloc();
+        const quote = try formatBlockquote(alloc, result.name, result.content, QUOTE_MAX_LINES);
+        const input = self.input_buffer.items;
+        if (self.input_cursor > 0 and input.len > 0 and input[@min(self.input_cursor, input.len) - 1] != '\n') {
+            self.appendBytes("\n");
+        }
+        self.appendBytes(quote);
+        self.dirty = true;
+    }
+
+    /// Failed tool calls of the turn, oldest first, the selected one's
+    /// arguments below. Enter asks to run the call again with the same
+    /// arguments, a second enter does.
+    pub fn openToolErrorsPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        const failures = &self.tool_failures;
+        const reopen = if (self.popup) |old| old.kind == .tool_errors else false;
+        if (!reopen) failures.confirm = null;
+        var popup = Popup{ .title = "tool errors · enter retries the call · esc closes", .kind = .tool_errors };
+
+        const items = failures.items.items[0..failures.count(self.user_turn)];
+        if (items.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no tool errors this turn", .{}, .{ .fg = self.theme.muted }));
+        }
+        const targets = try alloc.alloc(usize, items.len);
+        for (items, 0..) |failure, i| {
+            targets[i] = i;
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{d:>2}. ", .{i + 1}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s} ", .{failure.call.name}, .{ .fg = self.theme.info, .modifier = .{ .bold = true } });
+            try l.pushSpanPrint(alloc, "{s}", .{firstLine(failure.message)}, .{ .fg = self.theme.err });
+            if (failure.retried) |retried| switch (retried) {
+                .ok => try l.pushSpanPrint(alloc, "  ✓ retried", .{}, .{ .fg = self.theme.ok }),
+                .failed => try l.pushSpanPrint(alloc, "  ✗ retried", .{}, .{ .fg = self.theme.warn }),
+            };
+            try popup.lines.append(alloc, l);
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (reopen) self.popup.?.selected else targets.len -| 1, targets.len -| 1);
+
+        if (items.len > 0) {
+            const failure = items[popup.selected];
+            try popup.lines.append(alloc, .{});
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "arguments: {s}", .{failure.call.arguments}, .{ .fg = self.theme.muted }));
+            if (self.tool_retry) |retry| {
+                try popup.lines.append(alloc, try r.tui.Line.new(alloc, "retrying {s}…", .{retry.call.name}, .{ .fg = self.theme.info }));
+            } else if (failures.confirm == popup.selected) {
+                try popup.lines.append(alloc, try r.tui.Line.new(alloc, "retry {s} with the same arguments? enter runs it · j/k picks another", .{failure.call.name}, .{ .fg = self.theme.warn, .modifier = .{ .bold = true } }));
+            }
+        }
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn toolErrorsKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .tool_errors) return false;
+
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            else => return false,
+        }
+        self.tool_failures.confirm = null;
+        try self.openToolErrorsPopup();
+        return true;
+    }
+
+    /// Enter on the `.tool_errors` panel. The first asks, the second on the
+    /// same call runs it again.
+    pub fn toolErrorsConfirm(self: *App) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .tool_errors) return false;
+        if (popup.targets.len == 0 or self.tool_retry != null) return true;
+        if (self.isRunning()) {
+            try self.notifications.push(self.arena_app.allocator(), .warn, "retry once the run is over, its own calls may touch the same files", .{});
+            return true;
+        }
+
+        const index = popup.targets[popup.selected];
+        if (self.tool_failures.confirm != index) {
+            self.tool_failures.confirm = index;
+            try self.openToolErrorsPopup();
+            return true;
+        }
+        self.tool_failures.confirm = null;
+        self.popup = null;
+        try self.retryToolFailure(index);
+        return true;
+    }
+
+    /// Run failure `index` again with its arguments, through the same
+    /// checks and hooks as the agent's own calls.
+    fn retryToolFailure(self: *App, index: usize) !void {
+        const notify = self.arena_app.allocator();
+        const id = self.main_agent_id orelse return;
+        const agent = self.swarm.getAgent(id) orelse return;
+        const call = self.tool_failures.items.items[index].call;
+        const tool = agent.findTool(call.name) orelse {
+            try self.notifications.push(notify, .warn, "retry: no `{s}` tool anymore", .{call.name});
+            return;
+        };
+
+        const retry = try self.gpa.create(ToolRetry);
+        retry.* = .{
+            .running = .{ .fut = .{ .any_future = null, .result = undefined } },
+            .arena = prv.ThreadSafeArena.init(self.gpa, self.io),
+            .call = call,
+            .index = index,
+            .turn = self.tool_failures.turn,
+        };
+        const alloc = retry.arena.allocator();
+        var checked_call = call;
+        switch (prv.schema.check(alloc, tool.def.parameters_schema, call.arguments)) {
+            .ok => |arguments| checked_call.arguments = arguments,
+            .invalid => |msg| {
+                self.notifications.push(notify, .warn, "retry: {s}", .{firstLine(msg)}) catch {};
+                retry.arena.deinit();
+                self.gpa.destroy(retry);
+                return;
+            },
+        }
+        const ctx = prv.tool.ToolContext{
+            .alloc = alloc,
+            .io = self.io,
+            .swarm = self.swarm,
+            .self_id = id,
+            .cancel = &retry.running.cancel,
+            .cwd = self.swarm.context.cwd(self.swarm.context.ptr),
+        };
+        retry.running.fut = std.Io.async(self.io, prv.agent.Agent.runToolWrapper, .{ tool.func, ctx, checked_call, &retry.running.done });
+        self.tool_retry = retry;
+        self.pushSystemMessage("retrying {s}", .{call.name});
+        self.dirty = true;
+    }
+
+    /// A finished retry shows up in the chat and goes to the main agent, so
+    /// the run continues from the new result.
+    fn pollToolRetry(self: *App) !void {
+        const retry = self.tool_retry orelse return;
+        if (!retry.running.done.load(.acquire)) return;
+        self.tool_retry = null;
+        defer {
+            retry.arena.deinit();
+            self.gpa.destroy(retry);
+        }
+        const result = retry.running.fut.await(self.io);
+        const alloc = self.sessionAlloc();
+        const call = retry.call;
+        // a new prompt may have started over meanwhile
+        if (self.tool_failures.turn == retry.turn) {
+            self.tool_failures.items.items[retry.index].retried = if (result.is_error) .failed else .ok;
+        }
+        if (retry.running.cancel.load(.acquire)) {
+            self.pushSystemMessage("retry of {s} cancelled", .{call.name});
+            if (self.popup) |p| if (p.kind == .tool_errors) try self.openToolErrorsPopup();
+            self.dirty = true;
+            return;
+        }
+        const content = try alloc.dupe(u8, result.content);
+        self.pushSystemMessage("retried {s}, {s}: {s}", .{ call.name, if (result.is_error) "failed again" else "ok", firstLine(content) });
+        if (self.popup) |p| if (p.kind == .tool_errors) try self.openToolErrorsPopup();
+        self.dirty = true;
+
+        const agent_id = self.main_agent_id orelse return;
+        const text = try std.fmt.allocPrint(alloc, "[retry] The user ran your failed `{s}` call again with the same arguments, it {s}.\narguments: {s}\nresult:\n{s}", .{
+            call.name,
+            if (result.is_error) "failed again" else "succeeded this time",
+            call.arguments,
+            content,
+        });
+        const parts = try alloc.dupe(prv.adapter.ContentPart, &.{.{ .text = text }});
+        try self.cmd_queue.append(self.io, .{ .queue_agent_message = .{ .agent_id = agent_id, .parts = parts } });
+    }
+
+    /// Esc and cancel: the retry stops like the agent's own calls, its
+    /// result is dropped in `pollToolRetry`.
+    pub fn cancelToolRetry(self: *App) void {
+        const retry = self.tool_retry orelse return;
+        retry.running.cancel.store(true, .release);
+    }
+
+    /// Stop a running retry and wait for its worker, for a reset or exit.
+    fn dropToolRetry(self: *App) void {
+        const retry = self.tool_retry orelse return;
+        self.tool_retry = null;
+        retry.running.cancel.store(true, .release);
+        _ = retry.running.fut.cancel(self.io);
+        retry.arena.deinit();
+        self.gpa.destroy(retry);
+    }
+
+    /// Failed calls of the main agent go to the error strip.
+    fn noteToolFailures(self: *App, en: r.prv.Swarm.BroadcastEntry) void {
+        const id = self.main_agent_id orelse return;
+        if (!std.meta.eql(en.agent_id, id)) return;
+        const agent = self.swarm.getAgent(id) orelse return;
+        const before = self.tool_failures.count(self.user_turn);
+        for (en.parts) |part| {
+            const arguments = switch (part) {
+                .tool_result => |res| if (res.is_error) findToolCallArguments(agent, res.call_id) else null,
+                else => null,
+            };
+            self.tool_failures.note(self.sessionAlloc(), self.user_turn, part, arguments) catch |err| {
+                log.err("tool failure note failed {any}", .{err});
+                return;
+            };
+        }
+        if (self.tool_failures.count(self.user_turn) == before) return;
+        self.dirty = true;
+        if (self.popup) |p| if (p.kind == .tool_errors) self.openToolErrorsPopup() catch {};
+    }
+
+    pub fn openHelpPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "help" };
+
+        inline for (r.dash.keybinds) |bind| {
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{s: <8}", .{bind[0]}, .{ .fg = self.theme.info, .modifier = .{ .bold = true } });
+            try l.pushSpanPrint(alloc, "{s}", .{bind[1]}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+        try popup.lines.append(alloc, .{});
+        for (builtin_command_completions) |cmd| {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "{s}", .{cmd}, .{ .fg = self.theme.text }));
+        }
+        try popup.lines.append(alloc, .{});
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "mouse capture is {s}, toggle with :mouse", .{if (self.flags.mouse_capture) "on" else "off"}, .{ .fg = self.theme.info }));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "on: wheel scrolling and in-app selection, copy through OSC 52", .{}, .{ .fg = self.theme.muted }));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "off: native terminal selection and copy, scroll with c+u / c+d", .{}, .{ .fg = self.theme.muted }));
+
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    /// Every notification of this run, newest first.
+    pub fn openNotificationsPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "notifications" };
+
+        const entries = self.notifications.history();
+        if (entries.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no notifications yet", .{}, .{ .fg = self.theme.muted }));
+        }
+        for (entries) |entry| {
+            const en = entry.used;
+            const es = std.time.epoch.EpochSeconds{ .secs = @intCast(@max(@divTrunc(en.shown_ms, std.time.ms_per_s), 0)) };
+            const ds = es.getDaySeconds();
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{d:0>2}:{d:0>2}:{d:0>2} ", .{ ds.getHoursIntoDay(), ds.getMinutesIntoHour(), ds.getSecondsIntoMinute() }, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s: <6}", .{en.level.label()}, .{ .fg = levelColor(self, en.level), .modifier = .{ .bold = true } });
+            try l.pushSpanPrint(alloc, "{s}", .{en.msg}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    /// Per-file change history since the session started.
+    pub fn openChangesPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "changes since session start" };
+
+        const g = self.workspace_changes.lock(self.io);
+        defer g.unlock();
+
+        if (g.ptr.files.count() == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no files changed yet", .{}, .{ .fg = self.theme.muted }));
+        }
+
+        const now = std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds;
+        var it = g.ptr.files.iterator();
+        while (it.next()) |entry| {
+            const history = entry.value_ptr.items;
+            const net = WorkspaceChanges.netKind(history);
+            const color = if (net) |k| switch (k) {
+                .added => self.theme.ok,
+                .modified => self.theme.info,
+                .deleted => self.theme.err,
+            } else self.theme.muted;
+
+            var head = r.tui.Line{};
+            try head.pushSpanPrint(alloc, "{s} ", .{if (net) |k| k.icon() else "="}, .{ .fg = color, .modifier = .{ .bold = true } });
+            try head.pushSpanPrint(alloc, "{s}", .{relativeToCwd(self.cwd, entry.key_ptr.*)}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, head);
+
+            for (history) |change| {
+                const ago_s: i64 = @intCast(@divTrunc(@max(now - change.timestamp, 0), std.time.ns_per_s));
+                var l = r.tui.Line{};
+                try l.pushSpanPrint(alloc, "    {s} {s} {d}s ago", .{ change.kind.icon(), change.tool, ago_s }, .{ .fg = self.theme.muted });
+                try popup.lines.append(alloc, l);
+            }
+        }
+
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    /// Project allowlists, bash patterns first, then edit paths. `d` removes
+    /// the selected pattern.
+    pub fn openBashAllowPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "allowlist · d delete · :allow <pattern> · :allow edit <glob>", .kind = .bash_allow };
+
+        const g = self.bash_allow.lock(self.io);
+        defer g.unlock();
+
+        const patterns = g.ptr.patterns.items;
+        const edits = self.edit_allow.patterns.items;
+        if (patterns.len + edits.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no patterns yet, add one with :allow cargo * or :allow edit src/*", .{}, .{ .fg = self.theme.muted }));
+        }
+        const targets = try alloc.alloc(usize, patterns.len + edits.len);
+        for (patterns, 0..) |p, i| {
+            targets[i] = i;
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "bash  {s}", .{p}, .{ .fg = self.theme.text }));
+        }
+        for (edits, patterns.len..) |p, i| {
+            targets[i] = i;
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "edit  {s}", .{p}, .{ .fg = self.theme.text }));
+        }
+        var l = r.tui.Line{};
+        try l.pushSpanPrint(alloc, "always asks: ", .{}, .{ .fg = self.theme.muted });
+        for (r.allowlist.always_ask) |p| try l.pushSpanPrint(alloc, "`{s}` ", .{p}, .{ .fg = self.theme.warn });
+        try popup.lines.append(alloc, l);
+
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn allowBashPattern(self: *App, pattern: []const u8) !void {
+        if (try self.addBashPattern(pattern)) try self.openBashAllowPopup();
+    }
+
+    pub fn allowEditPattern(self: *App, pattern: []const u8) !void {
+        if (try self.addEditPattern(pattern)) try self.openBashAllowPopup();
+    }
+
+    fn addBashPattern(self: *App, pattern: []const u8) !bool {
+        const g = self.bash_allow.lock(self.io);
+        defer g.unlock();
+        if (!try g.ptr.add(self.gpa, pattern)) {
+            try self.notifications.append(self.arena_app.allocator(), "pattern already listed or list full", .{});
+            return false;
+        }
+        g.ptr.save(self.io) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .err, "failed to save allowlist: {s}", .{@errorName(err)});
+        };
+        return true;
+    }
+
+    fn addEditPattern(self: *App, pattern: []const u8) !bool {
+        if (!try self.edit_allow.add(self.gpa, pattern)) {
+            try self.notifications.append(self.arena_app.allocator(), "pattern already listed or list full", .{});
+            return false;
+        }
+        self.edit_allow.save(self.io) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .err, "failed to save allowlist: {s}", .{@errorName(err)});
+        };
+        return true;
+    }
+
+    /// Edits under an allowed path skip the prompt. Bash calls never get here
+    /// allowlisted, the bash tool checks its own list before asking.
+    pub fn permissionAllowlisted(self: *App, payload: r.prv.Swarm.PermissionPayload) bool {
+        if (payload != .diff) return false;
+        const rel = r.allowlist.projectPath(self.cwd, payload.diff.path);
+        return r.allowlist.insideProject(rel) and self.edit_allow.match(rel) != null;
+    }
+
+    /// Identity of a request for approval replay. Null for questions and plan
+    /// approvals, those are answered rather than approved.
+    pub fn permissionHash(payload: r.prv.Swarm.PermissionPayload) ?u64 {
+        var h = std.hash.Wyhash.init(0);
+        h.update(@tagName(payload));
+        const fields: []const []const u8 = switch (payload) {
+            .call => |c| &.{ c.tool_name, c.tool_arguments },
+            .diff => |d| &.{ d.path, d.before orelse "", d.after },
+            .commit => |c| &.{ c.message, c.diff },
+            .ask, .plan => return null,
+        };
+        for (fields) |field| {
+            h.update(std.mem.asBytes(&field.len));
+            h.update(field);
+        }
+        return h.final();
+    }
+
+    /// Same request as one approved earlier this session.
+    pub fn approvedBefore(self: *const App, payload: r.prv.Swarm.PermissionPayload) bool {
+        if (!self.flags.replay_approvals) return false;
+        const hash = permissionHash(payload) orelse return false;
+        return self.approved_requests.contains(hash);
+    }
+
+    /// "always allow" in the permission dialog: remember a rule covering the
+    /// pending bash call or edit, then approve it. An edit with rejected
+    /// hunks is not the edit the rule would allow, it stays pending.
+    pub fn alwaysAllowActivePermission(self: *App) !void {
+        const perm = self.active_permission orelse return;
+        if (perm.payload == .diff and self.review.rejectedCount() > 0) {
+            try self.notifications.append(self.arena_app.allocator(), "always allow needs every hunk accepted, yes writes the rest", .{});
+            return;
+        }
+        var tmp = std.heap.ArenaAllocator.init(self.gpa);
+        defer tmp.deinit();
+        const alloc = tmp.allocator();
+
+        var added = std.ArrayList([]const u8).empty;
+        switch (perm.payload) {
+            .diff => |d| {
+                const rel = r.allowlist.projectPath(self.cwd, d.path);
+                if (r.allowlist.insideProject(rel)) {
+                    const pattern = try r.allowlist.pathPattern(alloc, rel);
+                    if (try self.addEditPattern(pattern)) try added.append(alloc, pattern);
+                }
+            },
+            .call => |c| if (std.mem.eql(u8, c.tool_name, r.tools.bash.BashTool.def.name)) {
+                const Args = struct { command: []const u8 };
+                if (std.json.parseFromSliceLeaky(Args, alloc, c.tool_arguments, .{ .ignore_unknown_fields = true })) |args| {
+                    var rest: []const u8 = args.command;
+                    while (rest.len > 0) {
+                        const segment, const remaining = r.tools.bash.nextSegment(rest);
+                        rest = remaining;
+                        const trimmed = std.mem.trim(u8, segment, " \t\r\n");
+                        if (trimmed.len == 0 or r.allowlist.alwaysAsks(trimmed)) continue;
+                        const pattern = try r.allowlist.commandPattern(alloc, trimmed);
+                        if (try self.addBashPattern(pattern)) try added.append(alloc, pattern);
+                    }
+                } else |_| {}
+            },
+            else => {},
+        }
+
+        if (added.items.len == 0) {
+            try self.notifications.append(self.arena_app.allocator(), "no new rule for this request, allowed once", .{});
+        } else {
+            const joined = try std.mem.join(alloc, "`, `", added.items);
+            try self.notifications.append(self.arena_app.allocator(), "always allowing `{s}`", .{joined});
+        }
+        try self.persist_permission_to_history(perm);
+        self.resolveActivePermission(.approved);
+    }
+
+    /// Search through the chat, the query is typed into the input title.
+    pub fn openChatSearch(self: *App) void {
+        self.popup = null;
+        self.chat_search = .{ .active = true, .editing = true };
+        self.dirty = true;
+    }
+
+    pub fn closeChatSearch(self: *App) void {
+        self.chat_search = .{};
+        self.dirty = true;
+    }
+
+    /// Typing into the chat search query, or n (older) and N (newer)
+    /// between matches while the prompt is empty.
+    pub fn chatSearchKey(self: *App, bytes: []const u8) !bool {
+        const s = &self.chat_search;
+        if (!s.active) return false;
+        if (s.editing) {
+            if (s.query_len + bytes.len <= s.query_buf.len) {
+                @memcpy(s.query_buf[s.query_len..][0..bytes.len], bytes);
+                s.query_len += bytes.len;
+            }
+            return true;
+        }
+        if (self.input_buffer.items.len > 0 or bytes.len != 1) return false;
+        if (bytes[0] != 'n' and bytes[0] != 'N') return false;
+        _ = s.step(self.chat_entries.items, bytes[0] == 'n');
+        self.dirty = true;
+        return true;
+    }
+
+    pub fn chatSearchBackspace(self: *App) bool {
+        const s = &self.chat_search;
+        if (!s.editing) return false;
+        while (s.query_len > 0) {
+            s.query_len -= 1;
+            if ((s.query_buf[s.query_len] & 0xC0) != 0x80) break;
+        }
+        return true;
+    }
+
+    /// Enter ends the query and jumps to the newest match.
+    pub fn chatSearchConfirm(self: *App) !bool {
+        const s = &self.chat_search;
+        if (!s.editing) return false;
+        s.editing = false;
+        if (!s.step(self.chat_entries.items, true)) {
+            if (s.query_len > 0) try self.notifications.push(self.arena_app.allocator(), .warn, "no match for \"{s}\" in the chat", .{s.query()});
+            self.closeChatSearch();
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// C-j selects the newest message, then moves the selection to newer
+    /// ones, `older` (k) to older ones.
+    pub fn selectMessage(self: *App, older: bool) void {
+        const s = &self.message_select;
+        if (s.current == null) {
+            self.popup = null;
+            if (!s.step(self.chat_entries.items, true)) {
+                self.notifications.append(self.arena_app.allocator(), "no message to select", .{}) catch {};
+                return;
+            }
+        } else _ = s.step(self.chat_entries.items, older);
+        self.chat_search.jump_to = s.current;
+        self.dirty = true;
+    }
+
+    /// Spill file of the first tool call in `entry` whose output was cut.
+    fn entrySpill(self: *App, entry: ChatEntry) ?[]const u8 {
+        const g = self.tool_status_entries.lock(self.io);
+        defer g.unlock();
+        for (entry.parts) |part| {
+            const call = switch (part) {
+                .tool_call => |call| call,
+                else => continue,
+            };
+            if (call.agent_id.index >= prv.Swarm.MAX_AGENTS) continue;
+            const agent = &g.ptr.agents[call.agent_id.index];
+            if (agent.generation != call.agent_id.generation) continue;
+            const status = agent.entries.getPtr(call.call_id) orelse continue;
+            if (status.spill) |spill| return spill;
+        }
+        return null;
+    }
+
+    pub fn closeMessageSelect(self: *App) void {
+        self.message_select = .{};
+        self.dirty = true;
+    }
+
+    /// j and k move, y copies the message, c its next code block, o opens it
+    /// in $EDITOR, e expands cut tool output. Any other key ends the
+    /// selection and goes to the prompt.
+    pub fn messageSelectKey(self: *App, c: u8) !bool {
+        const s = &self.message_select;
+        const idx = s.current orelse return false;
+        if (idx >= self.chat_entries.items.len or std.mem.indexOfScalar(u8, "jkycoe", c) == null) {
+            self.closeMessageSelect();
+            return false;
+        }
+        if (c == 'j' or c == 'k') {
+            self.selectMessage(c == 'k');
+            return true;
+        }
+
+        const notify = self.arena_app.allocator();
+        if (c == 'e') {
+            const path = self.entrySpill(self.chat_entries.items[idx]) orelse {
+                try self.notifications.append(notify, "no cut tool output in this message", .{});
+                return true;
+            };
+            try self.openSpillPopup(path);
+            return true;
+        }
+        const alloc = self.sessionAlloc();
+        const body = try messageBody(alloc, self.chat_entries.items[idx]);
+        if (body.len == 0) {
+            try self.notifications.append(notify, "no text in this message", .{});
+            return true;
+        }
+        switch (c) {
+            'y' => {
+                self.terminal_request = .{ .copy = body };
+                try self.notifications.append(notify, "copied the message ({d} lines)", .{std.mem.count(u8, body, "\n") + 1});
+            },
+            'c' => {
+                if (codeBlock(body, s.code_block) == null) s.code_block = 0;
+                const block = codeBlock(body, s.code_block) orelse {
+                    try self.notifications.append(notify, "no code block in this message", .{});
+                    return true;
+                };
+                self.terminal_request = .{ .copy = block };
+                s.code_block += 1;
+                try self.notifications.append(notify, "copied code block {d}", .{s.code_block});
+            },
+            else => {
+                const path = self.privateScratch(alloc, "message.md", body) catch |err| {
+                    try self.notifications.push(notify, .err, "cannot write the message out: {s}", .{@errorName(err)});
+                    return true;
+                };
+                self.terminal_request = .{ .scratch = path };
+            },
+        }
+        return true;
+    }
+
+    /// Writes `content` to `$TMPDIR/blitz-<random>/<name>`. The directory is
+    /// created fresh with 0700 and the file exclusively, so nothing planted
+    /// in the shared temp dir gets followed or read by others.
+    fn privateScratch(self: *App, alloc: std.mem.Allocator, name: []const u8, content: []const u8) ![]const u8 {
+        const tmp = self.swarm.exec.env.get("TMPDIR") orelse "/tmp";
+        var nonce: u64 = undefined;
+        self.io.random(std.mem.asBytes(&nonce));
+        const dir = try std.fmt.allocPrintSentinel(alloc, "{s}/blitz-{x:0>16}", .{ std.mem.trimEnd(u8, tmp, "/"), nonce }, 0);
+        if (std.c.mkdir(dir, 0o700) != 0) return error.ScratchDirFailed;
+        errdefer std.Io.Dir.cwd().deleteDir(self.io, dir) catch {};
+
+        const path = try std.fmt.allocPrint(alloc, "{s}/{s}", .{ dir, name });
+        const flags: std.posix.O = .{ .ACCMODE = .WRONLY, .CREAT = true, .EXCL = true, .NOFOLLOW = true };
+        const fd = try std.posix.openat(std.posix.AT.FDCWD, path, flags, 0o600);
+        defer _ = std.c.close(fd);
+        errdefer std.Io.Dir.cwd().deleteFile(self.io, path) catch {};
+        var rest = content;
+        while (rest.len > 0) {
+            const n = std.c.write(fd, rest.ptr, rest.len);
+            if (n <= 0) return error.WriteFailed;
+            rest = rest[@intCast(n)..];
+        }
+        return path;
+    }
+
+    /// Project search for the user, typed into the popup. Enter inserts
+    /// `path:line` into the prompt, tab shows the surrounding lines.
+    pub fn openGrepPopup(self: *App) !void {
+        self.popup = .{ .title = "", .kind = .grep };
+        try self.refreshGrepPopup();
+    }
+
+    fn isGrepOpen(self: *const App) bool {
+        const popup = self.popup orelse return false;
+        return popup.kind == .grep;
+    }
+
+    /// Typed text goes to the query while the popup is open.
+    pub fn grepKey(self: *App, bytes: []const u8) !bool {
+        if (!self.isGrepOpen()) return false;
+        const g = &self.grep;
+        if (g.query_len + bytes.len > g.query_buf.len) return true;
+        @memcpy(g.query_buf[g.query_len..][0..bytes.len], bytes);
+        g.query_len += bytes.len;
+        try self.startGrep();
+        return true;
+    }
+
+    pub fn grepBackspace(self: *App) !bool {
+        if (!self.isGrepOpen()) return false;
+        const g = &self.grep;
+        while (g.query_len > 0) {
+            g.query_len -= 1;
+            if ((g.query_buf[g.query_len] & 0xC0) != 0x80) break;
+        }
+        try self.startGrep();
+        return true;
+    }
+
+    fn startGrep(self: *App) !void {
+        if (self.grep.handle) |h| self.swarm.exec.release(h);
+        self.grep.handle = null;
+        if (self.grep.query_len == 0) {
+            _ = self.arena_grep.reset(.retain_capacity);
+            self.grep.hits = &.{};
+            try self.refreshGrepPopup();
+            return;
+        }
+        const argv = r.tools.rg.userSearchArgv(self.grep.query());
+        self.grep.handle = self.swarm.exec.runWithOpts(.{ .argv = &argv }) catch |err| blk: {
+            try self.notifications.push(self.arena_app.allocator(), .err, "grep failed: {s}", .{@errorName(err)});
+            break :blk null;
+        };
+        try self.refreshGrepPopup();
+    }
+
+    fn pollGrep(self: *App) void {
+        const handle = self.grep.handle orelse return;
+        const res = self.swarm.exec.poll(handle) orelse return;
+        self.dirty = true;
+        if (!self.isGrepOpen()) {
+            self.swarm.exec.release(handle);
+            self.grep.handle = null;
+            return;
+        }
+
+        _ = self.arena_grep.reset(.retain_capacity);
+        const alloc = self.arena_grep.allocator();
+        const owned = alloc.dupe(u8, res.stdout) catch "";
+        self.swarm.exec.release(handle);
+        self.grep.handle = null;
+        // same redaction as the ripgrep tool
+        const output = r.sensitive.redactSearchOutput(alloc, &self.sensitive_paths, self.cwd, self.swarm.exec.env.get("HOME"), owned) catch owned;
+
+        var hits: std.ArrayList(r.tools.rg.Hit) = .empty;
+        var rows = std.mem.splitScalar(u8, output, '\n');
+        while (rows.next()) |row| {
+            if (hits.items.len >= r.tools.rg.MAX_USER_HITS) break;
+            const hit = r.tools.rg.parseHit(row) orelse continue;
+            hits.append(alloc, hit) catch break;
+        }
+        self.grep.hits = hits.items;
+        self.refreshGrepPopup() catch {};
+    }
+
+    fn refreshGrepPopup(self: *App) !void {
+        const popup = if (self.popup) |*p| p else return;
+        if (popup.kind != .grep) return;
+        const alloc = self.arena_grep.allocator();
+        const g = &self.grep;
+
+        const status: []const u8 = if (g.handle != null) "searching…" else try std.fmt.allocPrint(alloc, "{d} hits", .{g.hits.len});
+        popup.title = try std.fmt.allocPrint(alloc, "grep: {s}▏ · {s} · enter insert · tab open", .{ g.query(), status });
+        popup.lines = .empty;
+        const targets = try alloc.alloc(usize, g.hits.len);
+        for (g.hits, 0..) |hit, i| {
+            targets[i] = i;
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{s}:{d} ", .{ hit.path, hit.line }, .{ .fg = self.theme.info });
+            try l.pushSpanPrint(alloc, "{s}", .{std.mem.trim(u8, hit.text, " \t")}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+        popup.targets = targets;
+        popup.selected = @min(popup.selected, targets.len -| 1);
+        self.dirty = true;
+    }
+
+    /// Enter in the grep popup puts `path:line` into the prompt.
+    pub fn grepInsertSelected(self: *App) !bool {
+        if (!self.isGrepOpen()) return false;
+        const popup = self.popup.?;
+        if (popup.targets.len > 0) {
+            const hit = self.grep.hits[popup.targets[popup.selected]];
+            const ref = try std.fmt.allocPrint(self.arena_grep.allocator(), "{s}:{d} ", .{ hit.path, hit.line });
+            self.appendBytes(ref);
+        }
+        self.popup = null;
+        return true;
+    }
+
+    /// Tab in the grep popup shows the lines around the selected hit.
+    pub fn grepOpenSelected(self: *App) !bool {
+        if (!self.isGrepOpen()) return false;
+        const popup = self.popup.?;
+        if (popup.targets.len == 0) return true;
+        const hit = self.grep.hits[popup.targets[popup.selected]];
+        try self.openLocationView(hit.path, hit.line);
+        return true;
+    }
+
+    /// Read only view of the lines around `path:line`, replaces the popup.
+    fn openLocationView(self: *App, path: []const u8, line_no: u32) !void {
+        const alloc = self.sessionAlloc();
+
+        const first = line_no -| GREP_CONTEXT_LINES;
+        const range = try std.fmt.allocPrint(alloc, "{d},{d}p", .{ @max(first, 1), line_no + GREP_CONTEXT_LINES });
+        const res = self.swarm.exec.runAndWaitTimeout(.{ .argv = &.{ "sed", "-n", range, path } }, 5_000) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .err, "failed to read {s}: {s}", .{ path, @errorName(err) });
+            return;
+        };
+        defer self.swarm.exec.alloc.free(res.stdout);
+        defer self.swarm.exec.alloc.free(res.stderr);
+
+        var view = Popup{ .title = try std.fmt.allocPrint(alloc, "{s}:{d}", .{ relativeToCwd(self.cwd, path), line_no }) };
+        var ln: u32 = @max(first, 1);
+        var it = std.mem.splitScalar(u8, res.stdout, '\n');
+        while (it.next()) |line| : (ln += 1) {
+            if (it.peek() == null and line.len == 0) break;
+            const style: r.tui.Style = if (ln == line_no) .{ .fg = self.theme.warn, .modifier = .{ .bold = true } } else .{ .fg = self.theme.text };
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{d:>5} ", .{ln}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s}", .{line}, style);
+            try view.lines.append(alloc, l);
+        }
+        self.popup = view;
+        self.dirty = true;
+    }
+
+    /// Code references from the last answer plus the pinned ones. Enter or
+    /// tab opens the definition, `p` pins a reference across answers.
+    pub fn openReferencesPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var list: std.ArrayList(r.refs.Ref) = .empty;
+        try list.appendSlice(alloc, self.pinned_refs.items);
+        if (self.lastAnswer()) |text| {
+            var found: std.ArrayList(r.refs.Ref) = .empty;
+            try r.refs.extract(alloc, text, &found);
+            outer: for (found.items) |ref| {
+                for (self.pinned_refs.items) |p| {
+                    if (std.mem.eql(u8, p.text, ref.text)) continue :outer;
+                }
+                try list.append(alloc, ref);
+            }
+        }
+
+        var popup = Popup{ .title = "references · enter open · p pin", .kind = .references };
+        if (list.items.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no code references in the last answer", .{}, .{ .fg = self.theme.muted }));
+        }
+        const targets = try alloc.alloc(usize, list.items.len);
+        for (list.items, 0..) |ref, i| {
+            targets[i] = i;
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{s} ", .{if (ref.pinned) "●" else " "}, .{ .fg = self.theme.warn });
+            try l.pushSpanPrint(alloc, "{s: <8}", .{@tagName(ref.kind)}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s}", .{ref.text}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.len -| 1);
+        self.ref_list = list.items;
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    fn lastAnswer(self: *App) ?[]const u8 {
+        const agent = self.mainAgent() orelse return null;
+        const messages = agent.chat.messages.items;
+        var i = messages.len;
+        while (i > 0) {
+            i -= 1;
+            if (messages[i].role != .agent) continue;
+            for (messages[i].parts) |part| {
+                switch (part) {
+                    .text => |text| if (text.len > 0) return text,
+                    else => {},
+                }
+            }
+        }
+        return null;
+    }
+
+    pub fn referencesKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .references) return false;
+
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'p' => {
+                if (popup.targets.len == 0) return true;
+                const ref = self.ref_list[popup.targets[popup.selected]];
+                if (ref.pinned) {
+                    for (self.pinned_refs.items, 0..) |p, i| {
+                        if (std.mem.eql(u8, p.text, ref.text)) {
+                            _ = self.pinned_refs.orderedRemove(i);
+                            break;
+                        }
+                    }
+                } else {
+                    const alloc = self.sessionAlloc();
+                    var pinned = ref;
+                    pinned.pinned = true;
+                    pinned.text = try alloc.dupe(u8, ref.text);
+                    pinned.kind = switch (ref.kind) {
+                        .location => |loc| .{ .location = .{ .path = try alloc.dupe(u8, loc.path), .line = loc.line } },
+                        .symbol => |name| .{ .symbol = try alloc.dupe(u8, name) },
+                    };
+                    try self.pinned_refs.append(alloc, pinned);
+                }
+                try self.openReferencesPopup();
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// Locations open directly, symbols are looked up through the language
+    /// servers first and a definition search with rg after.
+    pub fn referencesOpenSelected(self: *App) !bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .references) return false;
+        if (popup.targets.len == 0) return true;
+        const ref = self.ref_list[popup.targets[popup.selected]];
+        switch (ref.kind) {
+            .location => |loc| try self.openLocationView(loc.path, loc.line),
+            .symbol => |name| {
+                const loc = try self.findDefinition(name) orelse {
+                    try self.notifications.append(self.arena_app.allocator(), "no definition found for {s}", .{name});
+                    return true;
+                };
+                try self.openLocationView(loc.path, loc.line);
+            },
+        }
+        return true;
+    }
+
+    fn findDefinition(self: *App, name: []const u8) !?r.refs.Location {
+        const alloc = self.sessionAlloc();
+        if (self.lsp_manager.findDefinition(alloc, name)) |loc| return .{ .path = loc.path, .line = loc.line };
+
+        const pattern = try r.tools.rg.definitionPattern(alloc, name);
+        const argv = r.tools.rg.definitionArgv(pattern);
+        const res = self.swarm.exec.runAndWaitTimeout(.{ .argv = &argv }, 5_000) catch return null;
+        defer self.swarm.exec.alloc.free(res.stdout);
+        defer self.swarm.exec.alloc.free(res.stderr);
+        const owned = try alloc.dupe(u8, res.stdout);
+        // same redaction as the ripgrep tool
+        const output = r.sensitive.redactSearchOutput(alloc, &self.sensitive_paths, self.cwd, self.swarm.exec.env.get("HOME"), owned) catch owned;
+
+        var rows = std.mem.splitScalar(u8, output, '\n');
+        while (rows.next()) |row| {
+            const hit = r.tools.rg.parseHit(row) orelse continue;
+            return .{ .path = hit.path, .line = hit.line };
+        }
+        return null;
+    }
+
+    /// Stored sessions of all projects. Enter resumes, `r` renames via the
+    /// prompt, `d` deletes.
+    pub fn openSessionsPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "sessions · enter resume · r rename · d delete", .kind = .sessions };
+
+        const home = self.swarm.exec.env.get("HOME") orelse return;
+        var dir = r.sessions.openDir(self.io, home) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .err, "no session directory: {s}", .{@errorName(err)});
+            return;
+        };
+        defer dir.close(self.io);
+        const entries = try r.sessions.list(self.io, alloc, self.gpa, dir, &self.vault);
+
+        if (entries.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no stored sessions yet, they are kept once a session ends", .{}, .{ .fg = self.theme.muted }));
+        }
+        const now_s: i64 = @intCast(@divTrunc(std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds, std.time.ns_per_s));
+        const targets = try alloc.alloc(usize, entries.len);
+        for (entries, 0..) |e, i| {
+            targets[i] = i;
+            var age_buf: [16]u8 = undefined;
+            var in_buf: [16]u8 = undefined;
+            var out_buf: [16]u8 = undefined;
+            const mtime_s: i64 = @intCast(@divTrunc(e.mtime, std.time.ns_per_s));
+            const current = if (self.session_name) |n| std.mem.eql(u8, n, e.name) else false;
+
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{s} ", .{if (current) "●" else " "}, .{ .fg = self.theme.warn });
+            try l.pushSpanPrint(alloc, "{s}  ", .{e.name}, .{ .fg = self.theme.text, .modifier = .{ .bold = true } });
+            try l.pushSpanPrint(alloc, "{s}  ", .{e.meta.model}, .{ .fg = self.theme.info });
+            try l.pushSpanPrint(alloc, "↑{s} ↓{s}  {s}  {s}", .{
+                formatTokenCount(&in_buf, e.meta.usage.input_tokens),
+                formatTokenCount(&out_buf, e.meta.usage.output_tokens),
+                r.sessions.formatAge(&age_buf, now_s - mtime_s),
+                e.meta.title,
+            }, .{ .fg = self.theme.muted });
+            try popup.lines.append(alloc, l);
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.len -| 1);
+        self.session_list = entries;
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn sessionsKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .sessions) return false;
+
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'r' => {
+                if (popup.targets.len == 0) return true;
+                const name = self.session_list[popup.targets[popup.selected]].name;
+                self.input_buffer.clearRetainingCapacity();
+                self.input_cursor = 0;
+                self.appendBytes(try std.fmt.allocPrint(self.sessionAlloc(), ":sessions rename {s} ", .{name}));
+                self.popup = null;
+            },
+            'd' => {
+                if (popup.targets.len == 0) return true;
+                const name = self.session_list[popup.targets[popup.selected]].name;
+                self.sessionsCommand(&.{ "delete", name }) catch |err| {
+                    try self.notifications.push(self.arena_app.allocator(), .err, "delete {s}: {s}", .{ name, @errorName(err) });
+                };
+                try self.openSessionsPopup();
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    pub fn sessionsResumeSelected(self: *App) !bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .sessions) return false;
+        if (popup.targets.len == 0) return true;
+        if (self.isRunning()) {
+            try self.notifications.append(self.arena_app.allocator(), "agent is busy, cancel first", .{});
+            return true;
+        }
+        // the list lives in the session arena, which resuming resets
+        var name_buf: [128]u8 = undefined;
+        const selected = self.session_list[popup.targets[popup.selected]].name;
+        const name = name_buf[0..@min(selected.len, name_buf.len)];
+        @memcpy(name, selected[0..name.len]);
+        self.popup = null;
+        r.session.resumeStored(self, name) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .err, "resume {s}: {s}", .{ name, @errorName(err) });
+        };
+        return true;
+    }
+
+    /// `:sessions` opens the browser, `:sessions rename <old> <new>` and
+    /// `:sessions delete <name>` change the stored files.
+    pub fn sessionsCommand(self: *App, args: []const []const u8) !void {
+        if (args.len == 0) return self.openSessionsPopup();
+        const home = self.swarm.exec.env.get("HOME") orelse return error.NoHomeFound;
+        var dir = try r.sessions.openDir(self.io, home);
+        defer dir.close(self.io);
+
+        if (std.mem.eql(u8, args[0], "rename") and args.len == 3) {
+            try r.sessions.rename(self.io, dir, args[1], args[2]);
+            if (self.session_name) |n| {
+                if (std.mem.eql(u8, n, args[1])) self.session_name = try self.sessionAlloc().dupe(u8, args[2]);
+            }
+            if (self.tabIndex(args[1])) |i| self.tabs.items[i].name = try self.appAlloc().dupe(u8, args[2]);
+            try self.openSessionsPopup();
+        } else if (std.mem.eql(u8, args[0], "delete") and args.len == 2) {
+            try r.sessions.remove(self.io, dir, args[1]);
+            if (self.session_name) |n| {
+                if (std.mem.eql(u8, n, args[1])) self.session_name = null;
+            }
+        } else if (std.mem.eql(u8, args[0], "encrypt") and args.len == 1) {
+            const sealed = r.sessions.encryptAll(self.io, self.gpa, dir, &self.vault) catch |err| switch (err) {
+                error.EncryptionDisabled => {
+                    try self.notifications.append(self.arena_app.allocator(), "session encryption is off, see blitz.set_session_encryption", .{});
+                    return;
+                },
+                else => return err,
+            };
+            try self.notifications.append(self.arena_app.allocator(), "encrypted {d} stored sessions, spill files, logs and prompt history stay plaintext", .{sealed});
+        } else {
+            try self.notifications.append(self.arena_app.allocator(), "usage: :sessions [rename <old> <new> | delete <name> | encrypt]", .{});
+        }
+    }
+
+    /// Name the current session will be stored under, one is made up for a
+    /// session that has none yet.
+    pub fn ensureSessionName(self: *App) ![]const u8 {
+        if (self.session_name) |n| return n;
+        var buf: [128]u8 = undefined;
+        const now: u64 = @intCast(@divTrunc(std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds, std.time.ns_per_s));
+        self.session_name = try self.sessionAlloc().dupe(u8, try r.sessions.newName(&buf, self.cwd, now));
+        return self.session_name.?;
+    }
+
+    fn tabIndex(self: *const App, name: []const u8) ?usize {
+        for (self.tabs.items, 0..) |tab, i| {
+            if (std.mem.eql(u8, tab.name, name)) return i;
+        }
+        return null;
+    }
+
+    /// Cost of the active tab, what it spent before plus since it was entered.
+    pub fn tabCost(self: *const App) ?f64 {
+        const total = self.sessionCost() orelse return null;
+        if (self.tabs.items.len == 0) return total;
+        return self.tabs.items[self.active_tab].cost + total - self.tab_cost_mark;
+    }
+
+    /// Books the active tab's spending before it goes to the background. A
+    /// session cleared or resumed inside the tab takes over its slot.
+    fn parkTab(self: *App) !void {
+        const tab = &self.tabs.items[self.active_tab];
+        const name = try self.ensureSessionName();
+        if (!std.mem.eql(u8, tab.name, name)) tab.name = try self.appAlloc().dupe(u8, name);
+        const total = self.sessionCost() orelse 0;
+        tab.cost += total - self.tab_cost_mark;
+        self.tab_cost_mark = total;
+    }
+
+    /// `:tab new [name]`. The current conversation becomes the first tab if
+    /// there are none yet, the new one starts empty with its own chat, todos
+    /// and cost.
+    pub fn openTab(self: *App, requested: ?[]const u8) !void {
+        if (self.isRunning()) {
+            try self.notifications.append(self.arena_app.allocator(), "agent is busy, cancel first", .{});
+            return;
+        }
+        const alloc = self.appAlloc();
+        if (requested) |name| {
+            if (!r.sessions.validName(name)) return error.InvalidSessionName;
+            if (self.tabIndex(name) != null) return error.TabExists;
+        }
+        if (self.tabs.items.len == 0) {
+            try self.tabs.append(alloc, .{ .name = try alloc.dupe(u8, try self.ensureSessionName()) });
+            self.active_tab = 0;
+            self.tab_cost_mark = self.sessionCost() orelse 0;
+        }
+        var name_buf: [160]u8 = undefined;
+        var n = self.tabs.items.len + 1;
+        const name = requested orelse while (true) : (n += 1) {
+            const candidate = try std.fmt.bufPrint(&name_buf, "{s}-{d}", .{ self.tabs.items[0].name, n });
+            if (self.tabIndex(candidate) == null) break candidate;
+        } else unreachable;
+        const owned = try alloc.dupe(u8, name);
+        try self.parkTab();
+        try r.session.switchTo(self, owned);
+        try self.tabs.append(alloc, .{ .name = owned });
+        self.active_tab = self.tabs.items.len - 1;
+        self.dirty = true;
+    }
+
+    /// ctrl+left/right, wraps around. The tab left behind is stored and
+    /// picked up again from its file.
+    pub fn switchTab(self: *App, delta: i32) !void {
+        const count = self.tabs.items.len;
+        if (count < 2) return;
+        if (self.isRunning()) {
+            try self.notifications.append(self.arena_app.allocator(), "agent is busy, cancel first", .{});
+            return;
+        }
+        const target: usize = @intCast(@mod(@as(i64, @intCast(self.active_tab)) + delta, @as(i64, @intCast(count))));
+        try self.parkTab();
+        try r.session.switchTo(self, self.tabs.items[target].name);
+        self.active_tab = target;
+        self.dirty = true;
+    }
+
+    /// `:tab close` drops the active tab, its session stays in the browser.
+    pub fn closeTab(self: *App) !void {
+        if (self.tabs.items.len == 0) return;
+        if (self.isRunning()) {
+            try self.notifications.append(self.arena_app.allocator(), "agent is busy, cancel first", .{});
+            return;
+        }
+        const closing = self.active_tab;
+        if (self.tabs.items.len > 1) {
+            const target = if (closing + 1 < self.tabs.items.len) closing + 1 else closing - 1;
+            try self.parkTab();
+            try r.session.switchTo(self, self.tabs.items[target].name);
+        }
+        _ = self.tabs.orderedRemove(closing);
+        self.active_tab = if (self.tabs.items.len == 0) 0 else @min(closing, self.tabs.items.len - 1);
+        // a single conversation is no tab
+        if (self.tabs.items.len == 1) self.tabs.clearRetainingCapacity();
+        self.tab_cost_mark = self.sessionCost() orelse 0;
+        self.dirty = true;
+    }
+
+    /// The main agent's todo list. Space cycles the state, `+`/`-` move an
+    /// item up or down, `d` deletes, `a` and `e` add or edit via the prompt.
+    /// Every change counts as a user edit, the agent hears about it on its
+    /// next turn.
+    pub fn openTodosPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "todos · space state · a add · e edit · +/- move · d delete", .kind = .todos };
+
+        var targets = std.ArrayList(usize).empty;
+        if (self.mainAgent()) |agent| {
+            const g = agent.todo_list.lock(self.io);
+            defer g.unlock();
+            for (g.ptr.todos[0..g.ptr.count]) |t| {
+                var l = r.tui.Line{};
+                const color = switch (t.state) {
+                    .pending => self.theme.text,
+                    .in_progress => self.theme.warn,
+                    .done => self.theme.muted,
+                };
+                try l.pushSpanPrint(alloc, "{s} ", .{t.state.icon()}, .{ .fg = color });
+                try l.pushSpanPrint(alloc, "#{d:<3} ", .{t.id}, .{ .fg = self.theme.muted });
+                try l.pushSpanPrint(alloc, "{s}", .{t.subject}, .{ .fg = color, .modifier = .{ .bold = t.state == .in_progress } });
+                for (t.depends_on, 0..) |dep, i| {
+                    try l.pushSpanPrint(alloc, "{s}#{d}", .{ if (i == 0) "  after " else ", ", dep }, .{ .fg = if (t.state != .done and g.ptr.isBlocked(t)) self.theme.warn else self.theme.muted });
+                }
+                if (t.description.len > 0) {
+                    try l.pushSpanPrint(alloc, "  {s}", .{std.mem.sliceTo(t.description, '\n')}, .{ .fg = self.theme.muted });
+                }
+                try popup.lines.append(alloc, l);
+                try targets.append(alloc, t.id);
+            }
+        }
+        if (targets.items.len == 0) {
+            const hint = if (self.main_agent_id == null) "no session yet, todos belong to the running agent" else "no todos, `a` adds one";
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, hint, .{}, .{ .fg = self.theme.muted }));
+        }
+        popup.targets = targets.items;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.items.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn todosKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .todos) return false;
+
+        const selected: ?u32 = if (popup.targets.len > 0) @intCast(popup.targets[popup.selected]) else null;
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'a' => {
+                if (self.main_agent_id == null) return true;
+                self.input_buffer.clearRetainingCapacity();
+                self.input_cursor = 0;
+                self.appendBytes(":todo add ");
+                self.popup = null;
+            },
+            'e' => {
+                const id = selected orelse return true;
+                const agent = self.mainAgent() orelse return true;
+                const prefill = blk: {
+                    const g = agent.todo_list.lock(self.io);
+                    defer g.unlock();
+                    const t = g.ptr.findById(id) orelse return true;
+                    break :blk if (t.description.len > 0)
+                        try std.fmt.allocPrint(self.sessionAlloc(), ":todo edit {d} {s} | {s}", .{ id, t.subject, t.description })
+                    else
+                        try std.fmt.allocPrint(self.sessionAlloc(), ":todo edit {d} {s}", .{ id, t.subject });
+                };
+                self.input_buffer.clearRetainingCapacity();
+                self.input_cursor = 0;
+                self.appendBytes(prefill);
+                self.popup = null;
+            },
+            ' ' => {
+                const id = selected orelse return true;
+                const agent = self.mainAgent() orelse return true;
+                const next: prv.agent.TodoState = blk: {
+                    const g = agent.todo_list.lock(self.io);
+                    defer g.unlock();
+                    const t = g.ptr.findById(id) orelse return true;
+                    break :blk switch (t.state) {
+                        .pending => .in_progress,
+                        .in_progress => .done,
+                        .done => .pending,
+                    };
+                };
+                try self.applyTodo(.{ .set_state = .{ .id = id, .state = next } });
+                try self.openTodosPopup();
+            },
+            '+', '-' => {
+                const id = selected orelse return true;
+                try self.applyTodo(.{ .move = .{ .id = id, .delta = if (c == '+') -1 else 1 } });
+                try self.openTodosPopup();
+                // keep the moved item selected
+                const moved = &self.popup.?;
+                for (moved.targets, 0..) |t, i| {
+                    if (t == id) moved.selected = i;
+                }
+            },
+            'd' => {
+                const id = selected orelse return true;
+                try self.applyTodo(.{ .remove = id });
+                try self.openTodosPopup();
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// The `:task` queue in run order. `+`/`-` move a task, `d` drops it,
+    /// `a` adds one and `r` continues a paused queue.
+    pub fn openTasksPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "tasks · a add · +/- move · d delete · r run", .kind = .tasks };
+
+        const targets = try alloc.alloc(usize, self.tasks.count());
+        for (self.tasks.items.items, 0..) |task, i| {
+            targets[i] = i;
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{d:>2}. ", .{i + 1}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s}", .{task}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+        if (targets.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no queued tasks, `a` adds one", .{}, .{ .fg = self.theme.muted }));
+        } else if (self.tasks.paused) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "paused after a failed or cancelled run, `r` continues", .{}, .{ .fg = self.theme.warn }));
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn tasksKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .tasks) return false;
+
+        const selected: ?usize = if (popup.targets.len > 0) popup.targets[popup.selected] else null;
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'a' => {
+                self.input_buffer.clearRetainingCapacity();
+                self.input_cursor = 0;
+                self.appendBytes(":task ");
+                self.popup = null;
+            },
+            '+', '-' => {
+                const i = selected orelse return true;
+                const moved = self.tasks.move(i, if (c == '+') -1 else 1);
+                try self.openTasksPopup();
+                self.popup.?.selected = moved;
+            },
+            'd' => {
+                const i = selected orelse return true;
+                _ = self.tasks.items.orderedRemove(i);
+                try self.openTasksPopup();
+            },
+            'r' => {
+                self.popup = null;
+                try self.taskCommand("run");
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// `:task <prompt>` queues a prompt, one per line when several are sent
+    /// at once. `:task run` continues a paused queue, `:task clear` drops it
+    /// and a bare `:tasks` opens the queue.
+    pub fn taskCommand(self: *App, args: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        if (args.len == 0) return self.openTasksPopup();
+        if (std.mem.eql(u8, args, "clear")) {
+            self.tasks.items.clearRetainingCapacity();
+            self.tasks.started = 0;
+            self.tasks.paused = false;
+            try self.notifications.append(notify, "task queue cleared", .{});
+            return;
+        }
+        if (std.mem.eql(u8, args, "run")) {
+            self.tasks.paused = false;
+        } else {
+            const added = try self.tasks.add(self.appAlloc(), args);
+            try self.notifications.append(notify, "{d} task{s} queued, {d} waiting", .{ added, if (added == 1) "" else "s", self.tasks.count() });
+        }
+        if (!self.isRunning()) _ = try self.startNextTask();
+    }
+
+    /// Notes in `.blitz/memory.md` with their sections. `a` adds one to the
+    /// selected note's section, `e` edits and `d` removes the selected note.
+    pub fn openMemoryPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        const g = self.memory.lock(self.io);
+        defer g.unlock();
+        const notes = g.ptr.notes.items;
+
+        const title = try std.fmt.allocPrint(alloc, "memory · {d}/{d} bytes · a add · e edit · d delete", .{ g.ptr.size(), r.memory.MAX_BYTES });
+        var popup = Popup{ .title = title, .kind = .memory };
+        const targets = try alloc.alloc(usize, notes.len);
+        for (notes, 0..) |note, i| {
+            targets[i] = i;
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{d:>2}. ", .{i + 1}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s}  ", .{note.section}, .{ .fg = self.theme.info });
+            try l.pushSpanPrint(alloc, "{s}", .{note.text}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+        if (notes.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no notes yet, the agent keeps them with the memory tool, `a` adds one", .{}, .{ .fg = self.theme.muted }));
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else 0, targets.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn memoryKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .memory) return false;
+
+        const selected: ?usize = if (popup.targets.len > 0) popup.targets[popup.selected] else null;
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'a', 'e' => {
+                const prefill = blk: {
+                    const g = self.memory.lock(self.io);
+                    defer g.unlock();
+                    const i = selected orelse break :blk ":memory add ";
+                    const note = g.ptr.notes.items[i];
+                    break :blk if (c == 'a')
+                        try std.fmt.allocPrint(self.sessionAlloc(), ":memory add {s} | ", .{note.section})
+                    else
+                        try std.fmt.allocPrint(self.sessionAlloc(), ":memory edit {d} {s}", .{ i + 1, note.text });
+                };
+                self.input_buffer.clearRetainingCapacity();
+                self.input_cursor = 0;
+                self.appendBytes(prefill);
+                self.popup = null;
+            },
+            'd' => {
+                const i = selected orelse return true;
+                {
+                    const g = self.memory.lock(self.io);
+                    defer g.unlock();
+                    g.ptr.remove(self.gpa, i);
+                    g.ptr.save(self.io, self.cwd) catch |err| {
+                        try self.notifications.push(self.arena_app.allocator(), .err, "failed to save memory: {s}", .{@errorName(err)});
+                    };
+                }
+                try self.openMemoryPopup();
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// `:memory add [section |] <note>` and `:memory edit <n> <note>` change
+    /// the project memory, a bare `:memory` opens it.
+    pub fn memoryCommand(self: *App, args: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        const usage = "usage: :memory add [section |] <note> | :memory edit <n> <note>";
+        var it = std.mem.tokenizeScalar(u8, args, ' ');
+        const verb = it.next() orelse return self.openMemoryPopup();
+
+        {
+            const g = self.memory.lock(self.io);
+            defer g.unlock();
+            const memory = g.ptr;
+            if (std.mem.eql(u8, verb, "add")) {
+                const text = it.rest();
+                const sep = std.mem.indexOfScalar(u8, text, '|');
+                const section = if (sep) |i| text[0..i] else "";
+                memory.add(self.gpa, section, if (sep) |i| text[i + 1 ..] else text) catch |err| {
+                    try self.notifications.push(notify, .err, "memory: {s}", .{@errorName(err)});
+                    return;
+                };
+            } else if (std.mem.eql(u8, verb, "edit")) {
+                const n = std.fmt.parseInt(usize, it.next() orelse "", 10) catch 0;
+                if (n == 0 or n > memory.notes.items.len) {
+                    try self.notifications.append(notify, usage, .{});
+                    return;
+                }
+                memory.replace(self.gpa, n - 1, it.rest()) catch |err| {
+                    try self.notifications.push(notify, .err, "memory: {s}", .{@errorName(err)});
+                    return;
+                };
+            } else {
+                try self.notifications.append(notify, usage, .{});
+                return;
+            }
+            memory.save(self.io, self.cwd) catch |err| {
+                try self.notifications.push(notify, .err, "failed to save memory: {s}", .{@errorName(err)});
+            };
+        }
+        try self.openMemoryPopup();
+    }
+
+    /// Run the next queued task as a turn of its own, so `:undo turn` takes
+    /// back one task at a time. False when nothing was started.
+    pub fn startNextTask(self: *App) !bool {
+        if (self.isRunning() or self.tasks.paused) return false;
+        if (self.dailyBudgetReached()) {
+            self.tasks.paused = true;
+            return false;
+        }
+        const prompt = self.tasks.pop() orelse {
+            self.tasks.started = 0;
+            return false;
+        };
+        const alloc = self.sessionAlloc();
+        // a pending screenshot belongs to the next typed prompt
+        const shot = self.screenshot_buf;
+        self.screenshot_buf = null;
+        defer self.screenshot_buf = shot;
+        const parts = try self.promptParts(alloc, prompt) orelse return false;
+        const chat_entry = try self.promptChatEntry(alloc, prompt);
+
+        self.todo_nudges_sent = 0;
+        self.user_turn +%= 1;
+        self.swarm.turn = self.user_turn;
+        self.pushSystemMessage("task {d}/{d}", .{ self.tasks.started, self.tasks.total() });
+        try self.event_bus.emit(self, .{ .user_message_sent = prompt });
+        if (self.main_agent_id) |id| {
+            self.swarm.getAgent(id).?.chat.tools_disabled = false;
+            // through the queue, so pre_run hooks see every task
+            try self.cmd_queue.append(self.io, .{ .queue_agent_message = .{
+                .agent_id = id,
+                .parts = parts,
+                .chat_entry = chat_entry,
+            } });
+        } else {
+            const id = self.swarm.reserveFreeSlot() orelse return error.NoFreeAgentSlot;
+            try self.cmd_queue.append(self.io, .{ .spawn_agent = .{
+                .agent_id = id,
+                .prompt = parts,
+                .chat_entry = chat_entry,
+            } });
+        }
+        self.run_state = .running;
+        self.dirty = true;
+        return true;
+    }
+
+    /// Called when the main run ended. A clean finish starts the next task,
+    /// anything else pauses the queue for the user to look first.
+    pub fn advanceTasks(self: *App, completed: bool) !void {
+        if (self.tasks.count() == 0) {
+            self.tasks.started = 0;
+            return;
+        }
+        if (completed) {
+            _ = try self.startNextTask();
+            return;
+        }
+        if (self.tasks.paused) return;
+        self.tasks.paused = true;
+        try self.notifications.push(self.arena_app.allocator(), .warn, "task queue paused, {d} left. :task run continues", .{self.tasks.count()});
+    }
+
+    /// The newest tool output that was cut for the model, read back from its
+    /// spill file. Space and b page, g and G jump to either end.
+    pub fn openOutputPopup(self: *App) !void {
+        const path = blk: {
+            const g = self.tool_status_entries.lock(self.io);
+            defer g.unlock();
+            break :blk g.ptr.last_spill;
+        } orelse {
+            try self.notifications.push(self.arena_app.allocator(), .warn, "no cut tool output in this session", .{});
+            return;
+        };
+        try self.openSpillPopup(path);
+    }
+
+    /// `openOutputPopup` for the spill file at `path`.
+    fn openSpillPopup(self: *App, path: []const u8) !void {
+        // the previous pager goes, spills run up to OUTPUT_POPUP_MAX_BYTES
+        _ = self.arena_output.reset(.free_all);
+        const alloc = self.arena_output.allocator();
+        const content = readSpill(self.io, alloc, path) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .warn, "{s}: {s}", .{ path, @errorName(err) });
+            return;
+        };
+
+        var popup = Popup{
+            .title = try std.fmt.allocPrint(alloc, "{s} · space/b page · g/G ends", .{std.fs.path.basename(path)}),
+            .kind = .tool_output,
+        };
+        var lines = std.mem.splitScalar(u8, r.tools.ensureValidUtf8(alloc, content), '\n');
+        while (lines.next()) |text| {
+            const line = std.mem.trimRight(u8, text, "\r");
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "{s}", .{line}, .{ .fg = self.theme.text }));
+        }
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    fn readSpill(io: std.Io, alloc: std.mem.Allocator, path: []const u8) ![]const u8 {
+        const file = try std.Io.Dir.cwd().openFile(io, path, .{});
+        defer file.close(io);
+        var buf: [4096]u8 = undefined;
+        var reader = file.reader(io, &buf);
+        // a bigger spill shows its first part
+        const content = try alloc.alloc(u8, OUTPUT_POPUP_MAX_BYTES);
+        const n = try reader.interface.readSliceShort(content);
+        return content[0..n];
+    }
+
+    pub fn outputKey(self: *App, c: u8) bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .tool_output) return false;
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            ' ', 'f' => popup.scroll += OUTPUT_POPUP_PAGE,
+            'b' => popup.scroll -|= OUTPUT_POPUP_PAGE,
+            'g' => popup.scroll = 0,
+            // clamped to the last page when rendered
+            'G' => popup.scroll = popup.lines.items.len,
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// `:todo add <subject> [| description]` and
+    /// `:todo edit <id> <subject> [| description]`, bare `:todo` opens the list.
+    pub fn todoCommand(self: *App, args: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        const usage = "usage: :todo add <subject> [| description] | :todo edit <id> <subject> [| description]";
+        var it = std.mem.tokenizeScalar(u8, args, ' ');
+        const verb = it.next() orelse return self.openTodosPopup();
+        const agent = self.mainAgent() orelse {
+            try self.notifications.append(notify, "no session yet, todos belong to the running agent", .{});
+            return;
+        };
+
+        const edit_id: ?u32 = if (std.mem.eql(u8, verb, "edit"))
+            std.fmt.parseInt(u32, it.next() orelse "", 10) catch {
+                try self.notifications.append(notify, usage, .{});
+                return;
+            }
+        else if (std.mem.eql(u8, verb, "add"))
+            null
+        else {
+            try self.notifications.append(notify, usage, .{});
+            return;
+        };
+
+        const text = std.mem.trim(u8, it.rest(), " ");
+        const sep = std.mem.indexOfScalar(u8, text, '|');
+        const subject = std.mem.trim(u8, if (sep) |i| text[0..i] else text, " ");
+        if (subject.len == 0) {
+            try self.notifications.append(notify, usage, .{});
+            return;
+        }
+        // todo strings live as long as the agent
+        const alloc = agent.arena.allocator();
+        const owned_subject = try alloc.dupe(u8, subject);
+        const description: ?[]const u8 = if (sep) |i| try alloc.dupe(u8, std.mem.trim(u8, text[i + 1 ..], " ")) else null;
+
+        if (edit_id) |id| {
+            try self.applyTodo(.{ .set_subject = .{ .id = id, .subject = owned_subject } });
+            if (description) |d| try self.applyTodo(.{ .set_description = .{ .id = id, .description = d } });
+        } else {
+            try self.applyTodo(.{ .add = .{ .subject = owned_subject, .description = description orelse "" } });
+        }
+        try self.openTodosPopup();
+    }
+
+    fn applyTodo(self: *App, op: prv.agent.TodoList.Op) !void {
+        const agent = self.mainAgent() orelse return;
+        const g = agent.todo_list.lock(self.io);
+        defer g.unlock();
+        _ = g.ptr.apply(op, .user) catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .err, "todo: {s}", .{@errorName(err)});
+        };
+    }
+
+    pub fn openSettingsPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "settings · space toggle · e edit · saved to settings.lua", .kind = .settings };
+
+        var targets = std.ArrayList(usize).empty;
+        for (r.settings.all, 0..) |setting, i| {
+            const value = try r.settings.current(self, alloc, setting.id);
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{s: <12}", .{@tagName(setting.category)}, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "{s: <18}", .{@tagName(setting.id)}, .{ .fg = self.theme.text });
+            try l.pushSpanPrint(alloc, "{s: <24}", .{if (value.len == 0) "-" else value}, .{ .fg = self.theme.info, .modifier = .{ .bold = true } });
+            try l.pushSpanPrint(alloc, "  {s}", .{setting.desc}, .{ .fg = self.theme.muted });
+            try popup.lines.append(alloc, l);
+            try targets.append(alloc, i);
+        }
+        popup.targets = targets.items;
+        popup.selected = @min(if (self.popup) |old| if (old.kind == .settings) old.selected else 0 else 0, targets.items.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn settingsKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .settings) return false;
+
+        const setting = r.settings.all[popup.targets[popup.selected]];
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            ' ' => {
+                const value = try r.settings.current(self, self.sessionAlloc(), setting.id);
+                const next: []const u8 = switch (setting.kind) {
+                    .boolean => if (std.mem.eql(u8, value, "true")) "false" else "true",
+                    .choice => |choices| blk: {
+                        for (choices, 0..) |choice, i| {
+                            if (std.mem.eql(u8, choice, value)) break :blk choices[(i + 1) % choices.len];
+                        }
+                        break :blk choices[0];
+                    },
+                    else => return true,
+                };
+                _ = try self.applySetting(setting, next);
+                try self.openSettingsPopup();
+            },
+            'e' => {
+                const value = try r.settings.current(self, self.sessionAlloc(), setting.id);
+                const prefill = try std.fmt.allocPrint(self.sessionAlloc(), ":set {s} {s}", .{ @tagName(setting.id), value });
+                self.input_buffer.clearRetainingCapacity();
+                self.input_cursor = 0;
+                self.appendBytes(prefill);
+                self.popup = null;
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// `:set <key> <value>`, bare `:set` or `:settings` opens the popup.
+    pub fn setCommand(self: *App, args: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        const space = std.mem.indexOfScalar(u8, args, ' ') orelse args.len;
+        if (args.len == 0) return self.openSettingsPopup();
+        const setting = r.settings.find(args[0..space]) orelse {
+            try self.notifications.push(notify, .err, "unknown setting {s}, :settings lists them", .{args[0..space]});
+            return;
+        };
+        if (try self.applySetting(setting, args[space..])) try self.openSettingsPopup();
+    }
+
+    /// Check `raw`, apply it through Lua like a config would and keep it in
+    /// the settings file. False when it was refused.
+    fn applySetting(self: *App, setting: r.settings.Setting, raw: []const u8) !bool {
+        const notify = self.arena_app.allocator();
+        const alloc = self.sessionAlloc();
+        const name = @tagName(setting.id);
+        const value = r.settings.literal(alloc, setting, raw) catch |err| {
+            try self.notifications.push(notify, .err, "{s}: {s}", .{ name, try r.settings.describeError(alloc, setting, err) });
+            return false;
+        };
+        const call = r.settings.luaCall(self, alloc, setting.id, value) catch |err| switch (err) {
+            error.UnnamedProvider => {
+                try self.notifications.push(notify, .err, "{s}: the current provider has no name to save it under", .{name});
+                return false;
+            },
+            else => |e| return e,
+        };
+        {
+            if (!self.lua_vm.vm_mu.tryLock()) {
+                try self.notifications.append(notify, "lua is busy, try again", .{});
+                return false;
+            }
+            defer self.lua_vm.vm_mu.unlock(self.io);
+            self.lua_vm.exec(call) catch {
+                try self.notifications.push(notify, .err, "{s}: {s}", .{ name, self.lua_vm.getLastError() });
+                return false;
+            };
+        }
+        const config_dir = self.context_factory.config_dir orelse {
+            try self.notifications.push(notify, .warn, "{s} applied, no config directory to save it in", .{name});
+            return true;
+        };
+        r.settings.save(self.io, alloc, config_dir, setting.id, call) catch |err| {
+            try self.notifications.push(notify, .err, "{s} applied, saving failed: {s}", .{ name, @errorName(err) });
+            return true;
+        };
+        self.dirty = true;
+        return true;
+    }
+
+    /// `:new <template> <name>`, a fresh session prompted and todo-seeded from
+    /// `~/.config/blitzdenk/templates/<template>/`.
+    pub fn startTemplate(self: *App, template: []const u8, name: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        if (template.len == 0 or name.len == 0) {
+            try self.notifications.append(notify, "usage: :new <template> <name>", .{});
+            return;
+        }
+        if (self.isRunning()) {
+            try self.notifications.append(notify, "agent is busy, cancel first", .{});
+            return;
+        }
+        const config_dir = self.context_factory.config_dir orelse {
+            try self.notifications.append(notify, "no ~/.config/blitzdenk directory", .{});
+            return;
+        };
+
+        // a missing or broken template leaves the current session alone
+        var tmp = std.heap.ArenaAllocator.init(self.gpa);
+        defer tmp.deinit();
+        const loaded = r.template.load(self.io, tmp.allocator(), config_dir, template, name) catch |err| {
+            try self.notifications.push(notify, .err, "template {s}: {s}", .{ template, @errorName(err) });
+            return;
+        };
+
+        self.reset();
+        const alloc = self.sessionAlloc();
+        const tpl = try loaded.dupe(alloc);
+        const id = self.swarm.reserveFreeSlot() orelse {
+            try self.notifications.append(notify, "no free agent slot", .{});
+            return;
+        };
+
+        const parts = try alloc.dupe(prv.adapter.ContentPart, &.{.{ .text = tpl.prompt }});
+        const display = try std.fmt.allocPrint(alloc, ":new {s} {s} ({d} todos)", .{ template, name, tpl.todos.len });
+        try self.cmd_queue.append(self.io, .{ .spawn_agent = .{
+            .agent_id = id,
+            .prompt = parts,
+            .chat_entry = try ChatEntry.userMessageSimple(alloc, .user, display),
+            .todos = tpl.todos,
+        } });
+    }
+
+    /// Keys of the allowlist popup. Returns false for keys it does not handle.
+    pub fn bashAllowKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .bash_allow) return false;
+
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'd' => {
+                if (popup.targets.len == 0) return true;
+                const target = popup.targets[popup.selected];
+                {
+                    const g = self.bash_allow.lock(self.io);
+                    defer g.unlock();
+                    const bash_len = g.ptr.patterns.items.len;
+                    if (target < bash_len) {
+                        g.ptr.remove(self.gpa, target);
+                        g.ptr.save(self.io) catch |err| {
+                            try self.notifications.push(self.arena_app.allocator(), .err, "failed to save allowlist: {s}", .{@errorName(err)});
+                        };
+                    } else {
+                        self.edit_allow.remove(self.gpa, target - bash_len);
+                        self.edit_allow.save(self.io) catch |err| {
+                            try self.notifications.push(self.arena_app.allocator(), .err, "failed to save allowlist: {s}", .{@errorName(err)});
+                        };
+                    }
+                }
+                try self.openBashAllowPopup();
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// Main agent messages ordered by estimated token footprint.
+    pub fn openTokenHeatmap(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        const agent = self.mainAgent() orelse {
+            try self.notifications.append(self.arena_app.allocator(), "no conversation yet", .{});
+            return;
+        };
+        const messages = agent.chat.messages.items;
+        const compact = prv.compact;
+
+        var total: u64 = 0;
+        const order = try alloc.alloc(usize, messages.len);
+        const tokens = try alloc.alloc(u64, messages.len);
+        for (messages, 0..) |msg, i| {
+            order[i] = i;
+            tokens[i] = compact.messageTokens(msg);
+            total += tokens[i];
+        }
+        std.mem.sort(usize, order, @as([]const u64, tokens), struct {
+            fn lessThan(t: []const u64, a: usize, b: usize) bool {
+                return t[a] > t[b];
+            }
+        }.lessThan);
+
+        const tool_bytes = agent.chat.toolDescriptionBytes();
+        const tool_tokens = compact.approxTokens(if (agent.chat.minimal_tool_descriptions) tool_bytes.minimal else tool_bytes.verbose);
+        const tools_note = if (agent.chat.minimal_tool_descriptions)
+            try std.fmt.allocPrint(alloc, "tools ~{d} (minimal, saves ~{d})", .{ tool_tokens, compact.approxTokens(tool_bytes.verbose) - tool_tokens })
+        else
+            try std.fmt.allocPrint(alloc, "tools ~{d} (minimal would save ~{d})", .{ tool_tokens, tool_tokens - compact.approxTokens(tool_bytes.minimal) });
+
+        var popup = Popup{
+            .title = try std.fmt.allocPrint(alloc, "token heatmap · ~{d} tokens · {s} · x prune · s compact", .{ total, tools_note }),
+            .kind = .token_heatmap,
+            .targets = order,
+        };
+        // keep the cursor in place when refreshing after a prune
+        if (self.popup) |p| {
+            if (p.kind == .token_heatmap) popup.selected = p.selected;
+        }
+
+        var cumulative: u64 = 0;
+        for (order) |idx| {
+            cumulative += tokens[idx];
+            const pct = percentOf(tokens[idx], total);
+            const color = if (pct >= 10) self.theme.err else if (pct >= 3) self.theme.warn else self.theme.muted;
+
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{d:>5.1}% ", .{pct}, .{ .fg = color, .modifier = .{ .bold = true } });
+            try l.pushSpanPrint(alloc, "{d:>5.1}% ~{d:>6} ", .{ percentOf(cumulative, total), tokens[idx] }, .{ .fg = self.theme.muted });
+            try l.pushSpanPrint(alloc, "#{d:<3} {s:<6} ", .{ idx, @tagName(messages[idx].role) }, .{ .fg = self.theme.info });
+            try l.pushSpanPrint(alloc, "{s}", .{messagePreview(messages[idx], 48)}, .{ .fg = self.theme.text });
+            try popup.lines.append(alloc, l);
+        }
+        popup.selected = @min(popup.selected, order.len -| 1);
+
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    /// Token counts and cost per prompt, model and tool, sub-agents included.
+    pub fn openUsagePopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        const breakdown = try UsageBreakdown.build(alloc, self.swarm.usage_log.items, self);
+        var popup = Popup{ .title = "usage · input / output / cached tokens and cost" };
+
+        if (self.swarm.usage_log.items.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no responses yet", .{}, .{ .fg = self.theme.muted }));
+        } else {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "per prompt", .{}, .{ .fg = self.theme.info, .modifier = .{ .bold = true } }));
+            for (breakdown.turns.keys(), breakdown.turns.values()) |turn, sum| {
+                try popup.lines.append(alloc, try self.usageLine(alloc, try std.fmt.allocPrint(alloc, "#{d}", .{turn}), sum));
+            }
+            try popup.lines.append(alloc, .{});
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "per model", .{}, .{ .fg = self.theme.info, .modifier = .{ .bold = true } }));
+            for (breakdown.models.keys(), breakdown.models.values()) |model, sum| {
+                try popup.lines.append(alloc, try self.usageLine(alloc, model, sum));
+            }
+            try popup.lines.append(alloc, .{});
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "per tool", .{}, .{ .fg = self.theme.info, .modifier = .{ .bold = true } }));
+            const order = try alloc.dupe(UsageSum, breakdown.tools.values());
+            const names = try alloc.dupe([]const u8, breakdown.tools.keys());
+            // most expensive first, by output then input
+            const Ctx = struct {
+                sums: []UsageSum,
+                names: [][]const u8,
+                pub fn lessThan(c: @This(), a: usize, b: usize) bool {
+                    const ta = c.sums[a].usage.output_tokens + c.sums[a].usage.input_tokens;
+                    const tb = c.sums[b].usage.output_tokens + c.sums[b].usage.input_tokens;
+                    return ta > tb;
+                }
+                pub fn swap(c: @This(), a: usize, b: usize) void {
+                    std.mem.swap(UsageSum, &c.sums[a], &c.sums[b]);
+                    std.mem.swap([]const u8, &c.names[a], &c.names[b]);
+                }
+            };
+            std.mem.sortUnstableContext(0, order.len, Ctx{ .sums = order, .names = names });
+            for (names, order) |name, sum| {
+                try popup.lines.append(alloc, try self.usageLine(alloc, name, sum));
+            }
+            try popup.lines.append(alloc, .{});
+            try popup.lines.append(alloc, try self.usageLine(alloc, "total", breakdown.total));
+        }
+
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    fn usageLine(self: *const App, alloc: std.mem.Allocator, label: []const u8, sum: UsageSum) !r.tui.Line {
+        var in_buf: [16]u8 = undefined;
+        var out_buf: [16]u8 = undefined;
+        var cached_buf: [16]u8 = undefined;
+        var cost_buf: [16]u8 = undefined;
+        var l = r.tui.Line{};
+        try l.pushSpanPrint(alloc, "  {s: <28} ", .{label[0..@min(label.len, 28)]}, .{ .fg = self.theme.text });
+        try l.pushSpanPrint(alloc, "{s: >8} {s: >8} {s: >8} ", .{
+            formatTokenCount(&in_buf, sum.usage.input_tokens + sum.usage.cache_creation_tokens),
+            formatTokenCount(&out_buf, sum.usage.output_tokens),
+            formatTokenCount(&cached_buf, sum.usage.cached_tokens),
+        }, .{ .fg = self.theme.muted });
+        const dollars = if (sum.cost == 0 and sum.unpriced) "?" else r.cost.format(&cost_buf, sum.cost);
+        try l.pushSpanPrint(alloc, "{s: >8}{s}", .{ dollars, if (sum.unpriced and sum.cost > 0) "+" else "" }, .{ .fg = self.theme.warn });
+        return l;
+    }
+
+    /// Act on the selected heatmap row. Returns false for keys the popup
+    /// does not handle.
+    pub fn tokenHeatmapKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .token_heatmap) return false;
+
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            'x' => {
+                if (self.isRunning()) {
+                    try self.notifications.append(self.arena_app.allocator(), "stop the agent before pruning", .{});
+                    return true;
+                }
+                const agent = self.mainAgent() orelse return true;
+                if (popup.targets.len == 0) return true;
+                const idx = popup.targets[popup.selected];
+                if (idx >= agent.chat.messages.items.len) return true;
+                const msg = &agent.chat.messages.items[idx];
+                if (msg.role == .system) {
+                    try self.notifications.append(self.arena_app.allocator(), "system prompt can't be pruned", .{});
+                    return true;
+                }
+                try prv.compact.pruneMessage(agent.arena.allocator(), msg);
+                try self.openTokenHeatmap();
+            },
+            's' => {
+                self.popup = null;
+                try self.cmd_queue.append(self.io, .compact);
+            },
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    /// App-scoped allocator. Survives session resets.
+    pub fn appAlloc(self: *App) std.mem.Allocator {
+        return self.arena_app.allocator();
+    }
+
+    pub fn reset(self: *App) void {
+        self.dropStreamingPreview();
+        self.cancelPermissions();
+        self.hooks.dropChecks(self);
+        self.swarm.cancelAll();
+
+        self.main_agent_id = null;
+        self.frame_count = 0;
+        self.scroll_offset = 0;
+        self.chat_search = .{};
+        self.message_select = .{};
+        self.terminal_request = null;
+        self.input_mode = .text;
+        self.input_cursor = 0;
+        self.streaming_entry = null;
+        self.compaction_indicator_active = false;
+        self.compaction_completion_seen_count = 0;
+        self.swarm.reset();
+        self.screenshot_buf = null;
+        self.links.clear(&self.swarm.exec);
+        self.dropToolRetry();
+        self.tool_failures = .{};
+        self.dirty = true;
+        _ = self.arena_session.reset(.free_all);
+        // Backing storage just got freed — reset list headers to .empty so
+        // stale ptr/capacity don't cause UB on next append.
+        self.input_buffer = .empty;
+        self.chat_entries = .empty;
+        self.ref_list = &.{};
+        self.pinned_refs = .empty;
+        self.approved_requests = .empty;
+        self.session_list = &.{};
+        self.session_name = null;
+        self.todo_nudges_sent = 0;
+        self.turn_model = null;
+        self.queued = .{};
+        self.lua_vm.disableAllMcp();
+        self.event_bus.emit(self, .session_reset) catch {};
+        self.reloadMcpTools() catch {};
+
+        // cleanup
+        self.tool_status_entries = .{};
+        self.workspace_changes = .{};
+        self.checkpoints = .{};
+        self.popup = null;
+        self.last_usage = .{};
+        self.resume_pending = false;
+        self.backup_session = r.backup.sessionName(&self.backup_session_buf, std.Io.Clock.Timestamp.now(self.io, .real).raw.nanoseconds);
+        self.media_saved = 0;
+        self.last_image = "";
+        self.permission_queue.value.clearRetainingCapacity();
+        self.broadcast_queue.value.clearRetainingCapacity();
+    }
+
+    pub fn dashboardAnimating(self: *const App) bool {
+        return self.main_agent_id == null and self.appearance.title.len > 0 and self.frame_count < DASHBOARD_WAVE_FRAMES;
+    }
+
+    /// Current frame of the configured throbber.
+    pub fn spinner(self: *const App) []const u8 {
+        return text_utils.spinner(self.appearance.spinner, self.frame_count);
+    }
+
+    /// How long the main loop may block on input. Agents that only wait on
+    /// the network tick at the spinner cadence, their data arrives through
+    /// the waker. A toast on screen wakes the loop when it expires.
+    pub fn pollTimeoutMs(self: *const App) i32 {
+        const timeout = self.activityTimeoutMs();
+        const expiry = self.notifications.msUntilExpiry() orelse return timeout;
+        return @intCast(std.math.clamp(expiry, 1, timeout));
+    }
+
+    fn activityTimeoutMs(self: *const App) i32 {
+        if (self.dashboardAnimating() or
+            self.compaction_indicator_active or
+            self.model_preload != null or
+            self.links.fetching() > 0 or
+            self.tool_retry != null or
+            self.grep.handle != null) return FRAME_MS;
+        // running marks every iteration dirty, the spinner alone does not
+        // need 60 fps
+        if (self.isRunning()) return if (self.swarm.allAwaitingNetwork()) NETWORK_POLL_MS else FRAME_MS;
+        return if (self.dirty) FRAME_MS else IDLE_POLL_MS;
+    }
+
+    pub fn tick(self: *App) !void {
+        self.pollModelPreload();
+        self.pollPriceFetch();
+        self.pollGrep();
+        try self.pollLinks();
+        try self.pollToolRetry();
+        self.syncSpend();
+        self.hooks.poll(self);
+
+        // --------------------------------------------------
+        // drain broadcoast
+        var saw_final_agent_broadcast = false;
+        var saw_broadcast = false;
+        {
+            const g = self.broadcast_queue.lock(self.io);
+            defer g.unlock();
+
+            saw_broadcast = g.ptr.items.len > 0;
+
+            for (g.ptr.items) |en| {
+                if (en.role == .agent) {
                     self.dropStreamingPreview();
                     saw_final_agent_broadcast = true;
                 }
 
+                self.emitBroadcastEvents(en);
+                self.noteToolFailures(en);
+                if (en.role == .user) continue;
+
                 const alloc = self.sessionAlloc();
                 if (renderableParts(alloc, en.agent_id, en.parts, en.plain_text)) |parts| {
-                    try self.chat_entries.append(alloc, .{
+                    const entry_parts = if (en.plain_text) withProviderErrorCard(alloc, en.http_status, parts) else parts;
+                    if (entry_parts[0] == .provider_error) {
+                        const kind = entry_parts[0].provider_error.kind;
+                        const level: Notifications.Level = switch (kind) {
+                            .rate_limited, .overloaded => .warn,
+                            else => .err,
+                        };
+                        self.notifications.push(self.arena_app.allocator(), level, "{s}", .{kind.title()}) catch {};
+                    }
+                    try self.pushEntry(alloc, .{
                         .role = en.role,
-                        .parts = parts,
+                        .parts = entry_parts,
                     });
                 }
+                if (en.role == .agent) self.saveGeneratedImages(en.parts);
             }
 
             g.ptr.clearRetainingCapacity();
         }
         if (!saw_final_agent_broadcast) self.syncStreamingPreview();
+        if (saw_broadcast and self.isRunning()) r.session.writeAutosave(self);
+        self.syncRunState();
 
-        // --------------------------------------------------
-        // pop permission
-        {}
+        const usage = self.swarm.usage();
+        if (!std.meta.eql(usage, self.last_usage)) {
+            self.last_usage = usage;
+            self.event_bus.emit(self, .{ .usage_updated = usage }) catch {};
+        }
 
         self.syncCompactionIndicator();
     }
 
+    fn emitBroadcastEvents(self: *App, en: r.prv.Swarm.BroadcastEntry) void {
+        self.event_bus.emit(self, .{ .agent_broadcast = .{ .id = en.agent_id, .role = en.role } }) catch {};
+        for (en.parts) |part| switch (part) {
+            .tool_call => |call| self.event_bus.emit(self, .{ .tool_call_started = .{
+                .agent_id = en.agent_id,
+                .call_id = call.id,
+                .name = call.name,
+            } }) catch {},
+            .tool_result => |res| self.event_bus.emit(self, .{ .tool_call_complete = .{
+                .agent_id = en.agent_id,
+                .call_id = res.call_id,
+                .name = res.name,
+                .is_error = res.is_error,
+            } }) catch {},
+            else => {},
+        };
+    }
+
     pub fn enterPermSelect(self: *App) void {
+        // free-text questions have nothing to pick, go straight to typing
+        if (self.active_permission) |perm| {
+            if (perm.payload == .ask and perm.payload.ask.options.len == 0) {
+                self.enterPermMessage();
+                return;
+            }
+        }
         self.input_mode = .{ .perm_select = .{} };
     }
 
@@ -509,6 +3702,75 @@ pub const App = struct {
         self.input_mode = .{ .perm_message = .{} };
     }
 
+    /// `n`/`p` select a hunk of the pending diff, `c` comments on it.
+    /// Returns false for keys it does not handle.
+    pub fn reviewKey(self: *App, c: u8) !bool {
+        const perm = self.active_permission orelse return false;
+        if (perm.payload != .diff) return false;
+        switch (c) {
+            'n', 'p' => {
+                var tmp = std.heap.ArenaAllocator.init(self.gpa);
+                defer tmp.deinit();
+                var lines = std.ArrayList(r.tui.DiffLine).empty;
+                emitDiffLines(&lines, perm.payload.diff, tmp.allocator());
+                self.review.moveHunk(lines.items, if (c == 'n') 1 else -1);
+            },
+            'c' => self.input_mode = .{ .perm_message = .{ .comment = true } },
+            'x' => self.review.toggleReject(),
+            else => return false,
+        }
+        return true;
+    }
+
+    /// Approving a diff with rejected hunks hands the tool the content with
+    /// only the accepted ones, rejecting every hunk declines it.
+    pub fn approveWithReview(self: *App) !void {
+        const perm = self.active_permission orelse return;
+        if (perm.payload != .diff or self.review.rejectedCount() == 0) {
+            try self.persist_permission_to_history(perm);
+            self.resolveActivePermission(.approved);
+            return;
+        }
+        const diff = perm.payload.diff;
+        const alloc = self.sessionAlloc();
+        const content = applyHunks(alloc, diff, &self.review) orelse {
+            try self.notifications.append(self.arena_app.allocator(), "this diff can only be approved whole", .{});
+            return;
+        };
+        if (std.mem.eql(u8, content, diff.before orelse "")) return self.declineWithReview("");
+
+        var partial = perm.*;
+        partial.payload = .{ .diff = .{ .path = diff.path, .before = diff.before, .after = content } };
+        try self.persist_permission_to_history(&partial);
+        self.resolveActivePermission(.{ .edited = content });
+    }
+
+    pub fn addReviewComment(self: *App, text: []const u8) !void {
+        const perm = self.active_permission orelse return;
+        if (perm.payload != .diff) return;
+        var tmp = std.heap.ArenaAllocator.init(self.gpa);
+        defer tmp.deinit();
+        var lines = std.ArrayList(r.tui.DiffLine).empty;
+        emitDiffLines(&lines, perm.payload.diff, tmp.allocator());
+        if (!try self.review.add(self.sessionAlloc(), lines.items, text)) {
+            try self.notifications.append(self.arena_app.allocator(), "comment not added (empty or {d} max)", .{r.review.MAX_COMMENTS});
+        }
+    }
+
+    /// Declining a diff hands review comments and `note` back to the agent.
+    pub fn declineWithReview(self: *App, note: []const u8) !void {
+        const perm = self.active_permission orelse return;
+        if (perm.payload != .diff or (self.review.comments.items.len == 0 and note.len == 0)) {
+            self.resolveActivePermission(.denied);
+            return;
+        }
+        const msg = if (self.review.comments.items.len == 0)
+            try self.sessionAlloc().dupe(u8, note)
+        else
+            try r.review.compose(self.sessionAlloc(), perm.payload.diff.path, &self.review, note);
+        self.resolveActivePermission(.{ .message = msg });
+    }
+
     pub fn enterPassphrase(self: *App, user: []const u8, host: []const u8, cwd: []const u8) void {
         if (user.len + host.len + cwd.len > self.passphrase_args_buf.len) {
             std.log.warn("ssh args too long for passphrase buffer ({d} bytes)", .{user.len + host.len + cwd.len});
@@ -585,12 +3847,19 @@ pub const App = struct {
         const parts = alloc.alloc(ChatPart, 1) catch return;
         parts[0] = .{ .message = text };
 
-        self.chat_entries.append(alloc, .{
+        self.pushEntry(alloc, .{
             .role = .system,
             .parts = parts,
         }) catch return;
     }
 
+    /// Append to the chat, stamped with the time for transcripts.
+    pub fn pushEntry(self: *App, alloc: std.mem.Allocator, entry: ChatEntry) !void {
+        var timed = entry;
+        timed.time_ms = prv.http.nowMs(self.io);
+        try self.chat_entries.append(alloc, timed);
+    }
+
     pub fn mainAgent(self: *const App) ?*prv.agent.Agent {
         const id = self.main_agent_id orelse return null;
         return self.swarm.getAgent(id);
@@ -599,6 +3868,18 @@ pub const App = struct {
     pub fn configureAgent(self: *const App, agent: *prv.agent.Agent) !void {
         try self.context_factory.configureAgent(agent, self.cwd);
         agent.context_limit = self.default_context_limit;
+        agent.compact_threshold = self.compact_threshold;
+        agent.compact_model = self.compact_model;
+        agent.retry_policy = self.retry_policy;
+        agent.fallback_models = self.fallback_models;
+        agent.chat.minimal_tool_descriptions = self.wantsMinimalSchemas(agent.config.model);
+    }
+
+    pub fn wantsMinimalSchemas(self: *const App, model: []const u8) bool {
+        for (self.minimal_schema_models) |pattern| {
+            if (r.allowlist.globMatch(pattern, model)) return true;
+        }
+        return false;
     }
 
     pub fn contextPercent(self: *const App) f32 {
@@ -669,7 +3950,7 @@ pub const App = struct {
                     //     const opts: u16 = @intCast(@min(entry.payload.ask.options.len, r.tools.ask.MAX_OPTIONS));
                     //     break :blk @min(@as(u16, 4) + opts, area.height / 2);
                     // }
-                    break :blk 6; // .call, .diff, .plan all have header + options
+                    break :blk 7; // .call, .diff, .plan all have header + 4 options
                 },
             }
         };
@@ -677,9 +3958,11 @@ pub const App = struct {
         const main_status_height: u16 = 3; //renderMainProgressRequiredLines(app);
 
         // Combined chat + main-agent-status region; status floats right after chat.
-        const _combined_area, const _input_area, const _status_area =
+        const error_strip_height: u16 = if (app.tool_failures.count(app.user_turn) > 0) 1 else 0;
+        const _combined_area, const _error_strip_area, const _input_area, const _status_area =
             r.tui.Col(area, .{
                 r.tui.Constr.fill, // chat + status
+                r.tui.Constr{ .fixed = error_strip_height }, // tool errors of the turn
                 r.tui.Constr{ .fixed = input_height }, // input
                 r.tui.Constr{ .fixed = 1 }, // statusbar (pinned bottom)
             });
@@ -726,6 +4009,10 @@ pub const App = struct {
             .height = @min(main_status_height, status_remaining),
         }, buf);
 
+        renderToolErrorStrip(app, frame_alloc, _error_strip_area, buf) catch |err| {
+            log.err("tool error strip render failed with {any}", .{err});
+        };
+
         // Input/Permission
         switch (app.input_mode) {
             .perm_select => renderPermissionWidget(app, _input_area, buf),
@@ -744,18 +4031,21 @@ pub const App = struct {
         // Statusbar
         renderStatusBar(app, _status_area, buf);
 
-        // command completion
+        renderPopup(app, frame_alloc, area, buf);
+
+        // command / file completion
         if (app.input_buffer.items.len > 0) {
-            const completions = commandCompletions(app, app.input_buffer.items, app.input_cursor);
+            const suggestions = promptSuggestions(app, frame_alloc);
             var p = r.tui.Paragraph{};
             p.border = .single;
             p.style.bg = app.theme.overlay_dark;
 
-            for (completions) |cmp| {
-                if (cmp.len > 0) {
-                    p.appendText(frame_alloc, cmp, .{ .modifier = .{ .bold = true } }) catch {};
-                    app.completion_suggestion = cmp;
-                }
+            var width: u16 = 32;
+            app.completion_suggestion = null;
+            for (suggestions.items()) |cmp| {
+                p.appendText(frame_alloc, cmp, .{ .modifier = .{ .bold = true } }) catch {};
+                app.completion_suggestion = cmp;
+                width = @max(width, @as(u16, @intCast(@min(cmp.len + 4, area.width))));
             }
 
             const height: u16 = @intCast(p.lines.items.len + 2);
@@ -764,21 +4054,43 @@ pub const App = struct {
                 const completion_area = r.tui.Rect{
                     .x = _input_area.x + 1,
                     .y = _input_area.y -| height + 1,
-                    .width = 32,
+                    .width = width,
                     .height = height,
                 };
 
                 p.renderSimple(frame_alloc, completion_area, buf);
-            } else {
-                app.completion_suggestion = null;
             }
+
+            if (app.input_mode == .text) renderInputHints(app, frame_alloc, _input_area, buf, &suggestions);
+        }
+    }
+
+    /// Accept the current completion: commands replace the whole input,
+    /// `@path` candidates only replace the token under the cursor.
+    pub fn acceptCompletion(self: *App) !void {
+        const sug = self.completion_suggestion orelse return;
+        const trigger = r.suggest.triggerAt(self.input_buffer.items, self.input_cursor);
+        const owned = try self.sessionAlloc().dupe(u8, sug);
+        if (trigger == .none) {
+            self.input_buffer.clearRetainingCapacity();
+            try self.input_buffer.appendSlice(self.sessionAlloc(), owned);
+            self.input_cursor = @intCast(owned.len);
+        } else {
+            const cursor = try r.suggest.apply(self.sessionAlloc(), &self.input_buffer, trigger, owned);
+            self.input_cursor = @intCast(cursor);
         }
+        self.completion_suggestion = null;
     }
 
     /// Set the swarm-side state for a permission. Tools poll this state to
     /// decide whether to proceed. Clears `pending_perm` if it matches.
     pub fn resolveActivePermission(self: *App, state: prv.Swarm.PermissionState) void {
+        self.review.clear();
         if (self.active_permission) |perm| {
+            self.event_bus.emit(self, .{ .permission_resolved = .{ .call_id = perm.call_id, .state = state } }) catch {};
+            if (state == .approved) {
+                if (permissionHash(perm.payload)) |hash| self.approved_requests.put(self.sessionAlloc(), hash, {}) catch {};
+            }
             if (self.swarm.getSlotState(perm.agent_id) == .active) {
                 perm.state = state;
                 perm.event.set(self.io);
@@ -815,6 +4127,142 @@ pub const App = struct {
         return self.input_buffer.items;
     }
 
+    /// Parts for a prompt: the text without its `@image:` refs, the pasted
+    /// screenshot and every referenced image. Null after an error
+    /// notification when an image can't be loaded, the input stays for a fix.
+    pub fn promptParts(self: *App, alloc: std.mem.Allocator, input: []const u8) !?[]prv.adapter.ContentPart {
+        const refs = try r.images.parseRefs(alloc, input);
+        var parts = std.ArrayList(prv.adapter.ContentPart).empty;
+        try parts.append(alloc, .{ .text = refs.text });
+        if (self.screenshot_buf) |buf| {
+            try parts.append(alloc, .{ .image = .{ .media_type = self.screenshot_media_type, .data = buf } });
+        }
+        for (refs.paths) |path| {
+            const image = r.images.load(self.io, alloc, self.cwd, path) catch |err| {
+                try self.notifications.push(self.arena_app.allocator(), .err, "image {s}: {s}", .{ path, @errorName(err) });
+                return null;
+            };
+            try parts.append(alloc, .{ .image = image });
+        }
+        for (try r.links.findUrls(alloc, refs.text)) |url| {
+            const link = self.links.find(url) orelse continue;
+            if (link.state == .ready) try parts.append(alloc, .{ .text = try r.links.block(alloc, link) });
+        }
+        return try parts.toOwnedSlice(alloc);
+    }
+
+    /// The prompt as shown in the chat, a placeholder line per image and
+    /// attached link below it.
+    pub fn promptChatEntry(self: *App, alloc: std.mem.Allocator, input: []const u8) !ChatEntry {
+        const refs = try r.images.parseRefs(alloc, input);
+        var parts = std.ArrayList(ChatPart).empty;
+        try parts.append(alloc, .{ .message = refs.text });
+        if (self.screenshot_buf != null) {
+            try parts.append(alloc, .{ .plain_text = try std.fmt.allocPrint(alloc, "[image: pasted {s}]", .{self.screenshot_media_type}) });
+        }
+        for (refs.paths) |path| {
+            try parts.append(alloc, .{ .plain_text = try std.fmt.allocPrint(alloc, "[image: {s}]", .{path}) });
+        }
+        for (try r.links.findUrls(alloc, refs.text)) |url| {
+            const link = self.links.find(url) orelse continue;
+            if (link.state != .ready) continue;
+            try parts.append(alloc, .{ .plain_text = try std.fmt.allocPrint(alloc, "[link: {s}, {d} KB]", .{ url, link.content.len / 1024 + 1 }) });
+        }
+        if (parts.items.len == 1) return ChatEntry.userMessageSimple(alloc, .user, input);
+        return .{ .role = .user, .parts = try parts.toOwnedSlice(alloc) };
+    }
+
+    /// Links of a new prompt, see links.zig. True when the prompt waits in
+    /// the input, for the `.links` popup or for the pages to come in.
+    pub fn holdForLinks(self: *App, text: []const u8) !bool {
+        if (self.links.mode == .off) return false;
+        const alloc = self.sessionAlloc();
+        const urls = try self.links.unseen(alloc, text);
+        if (urls.len == 0) {
+            if (self.links.fetching() == 0) return false;
+            self.links.send_pending = true;
+            return true;
+        }
+        if (self.links.mode == .auto) {
+            try self.fetchLinks(urls);
+            return true;
+        }
+        self.links.asked = urls;
+        var popup = Popup{ .title = "links · enter fetches them · s sends without · esc goes back", .kind = .links };
+        for (urls) |url| try popup.lines.append(alloc, try r.tui.Line.new(alloc, "{s}", .{url}, .{ .fg = self.theme.text }));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "", .{}, .{}));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "the pages go along as context, blitz.set_link_context(\"auto\") stops asking", .{}, .{ .fg = self.theme.muted }));
+        self.popup = popup;
+        self.dirty = true;
+        return true;
+    }
+
+    fn fetchLinks(self: *App, urls: []const []const u8) !void {
+        try self.links.start(self.sessionAlloc(), &self.swarm.exec, urls);
+        self.links.send_pending = true;
+        try self.notifications.append(self.arena_app.allocator(), "fetching {d} link{s}, the prompt goes out once they are in, esc keeps it", .{ urls.len, if (urls.len == 1) "" else "s" });
+        self.dirty = true;
+    }
+
+    /// Lists the tools the plugin manager held back, see plugins.zig.
+    pub fn askToolTrust(self: *App) !void {
+        const untrusted = self.plugin_manager.untrusted.items;
+        if (untrusted.len == 0) return;
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "untrusted tools · enter trusts and loads them · esc skips", .kind = .tool_trust };
+        for (untrusted) |tool| {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "{s}", .{r.allowlist.projectPath(self.cwd, tool.path)}, .{ .fg = self.theme.text }));
+        }
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "", .{}, .{}));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "these run with --schema once trusted, a changed file asks again", .{}, .{ .fg = self.theme.muted }));
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    /// Enter on the `.tool_trust` popup, the caller reloads the config to
+    /// load what got trusted.
+    pub fn toolTrustConfirm(self: *App) !bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .tool_trust) return false;
+        self.popup = null;
+        self.plugin_manager.trustUntrusted() catch |err| {
+            try self.notifications.push(self.arena_app.allocator(), .warn, "tools not trusted: {s}", .{@errorName(err)});
+        };
+        return true;
+    }
+
+    /// Enter on the `.links` popup fetches the links asked about.
+    pub fn linksConfirm(self: *App) !bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .links) return false;
+        self.popup = null;
+        try self.fetchLinks(self.links.asked);
+        self.links.asked = &.{};
+        return true;
+    }
+
+    /// `s` on the `.links` popup sends the prompt without the pages.
+    pub fn linksKey(self: *App, c: u8) !bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .links or c != 's') return false;
+        self.popup = null;
+        try self.links.skip(self.sessionAlloc(), self.links.asked);
+        self.links.asked = &.{};
+        self.links.send_pending = true;
+        self.dirty = true;
+        return true;
+    }
+
+    fn pollLinks(self: *App) !void {
+        if (self.links.fetching() == 0) return;
+        var failed = std.ArrayList(*const r.links.Link).empty;
+        if (!try self.links.poll(self.sessionAlloc(), &self.swarm.exec, &failed)) return;
+        for (failed.items) |link| {
+            try self.notifications.push(self.arena_app.allocator(), .warn, "link {s}: {s}, sent without it", .{ link.url, link.content });
+        }
+        self.dirty = true;
+    }
+
     pub fn pushHistory(self: *App, allocator: std.mem.Allocator, text: []const u8) void {
         if (text.len == 0) return;
         const dupe = allocator.dupe(u8, text) catch return;
@@ -910,10 +4358,495 @@ pub const App = struct {
         file_writer.interface.flush() catch return;
     }
 
+    /// Warm up the default model when it is served by ollama, cold starts
+    /// otherwise stall the first request. No-op for other providers.
+    pub fn preloadModel(self: *App) void {
+        if (self.model_preload != null) return;
+        const cfg = self.config.buildConfig(self.swarm.exec.env) orelse return;
+        const keep_alive = switch (cfg.provider) {
+            .ollama => |oc| oc.keep_alive,
+            else => return,
+        };
+
+        const alloc = self.appAlloc();
+        const pre = prv.openai.ollamaPreload(alloc, cfg.base_url, cfg.model, keep_alive) catch return;
+        self.model_preload = self.swarm.exec.runWithOpts(.{
+            .argv = &.{ "curl", "-sf", "-X", "POST", "-H", "Content-Type: application/json", "-d", pre.body, pre.url },
+            .force_local = true,
+        }) catch |err| blk: {
+            std.log.warn("ollama preload failed to start: {s}", .{@errorName(err)});
+            break :blk null;
+        };
+        self.dirty = true;
+    }
+
+    fn pollModelPreload(self: *App) void {
+        const handle = self.model_preload orelse return;
+        const res = self.swarm.exec.poll(handle) orelse return;
+        if (res.ty != .success) std.log.warn("ollama preload failed: {s}", .{res.stderr});
+        self.swarm.exec.release(handle);
+        self.model_preload = null;
+        self.dirty = true;
+    }
+
+    /// Load the cached price list and refresh it in the background once it
+    /// is stale. Always local, also while the tools run over SSH.
+    pub fn loadPrices(self: *App) void {
+        const home = self.swarm.exec.env.get("HOME") orelse return;
+        if (self.prices == null) self.prices = r.cost.loadCached(self.io, self.gpa, home);
+        if (self.price_fetch != null or !r.cost.isStale(self.io, home)) return;
+
+        const script = r.cost.fetchScript(self.appAlloc(), home) catch return;
+        self.price_fetch = self.swarm.exec.runWithOpts(.{
+            .argv = &.{ "/bin/sh", "-c", script },
+            .force_local = true,
+        }) catch |err| blk: {
+            std.log.warn("price list refresh failed to start: {s}", .{@errorName(err)});
+            break :blk null;
+        };
+    }
+
+    fn pollPriceFetch(self: *App) void {
+        const handle = self.price_fetch orelse return;
+        const res = self.swarm.exec.poll(handle) orelse return;
+        const ok = res.ty == .success;
+        if (!ok) std.log.warn("price list refresh failed: {s}", .{res.stderr});
+        self.swarm.exec.release(handle);
+        self.price_fetch = null;
+        if (!ok) return;
+
+        const home = self.swarm.exec.env.get("HOME") orelse return;
+        const fresh = r.cost.loadCached(self.io, self.gpa, home) orelse return;
+        if (self.prices) |*p| p.deinit();
+        self.prices = fresh;
+        self.dirty = true;
+    }
+
+    /// Custom price for `model`, else the LiteLLM one.
+    pub fn modelPrice(self: *const App, model: []const u8) ?r.cost.Price {
+        if (self.custom_prices.lookup(model)) |p| return p;
+        const prices = if (self.prices) |*p| p else return null;
+        return prices.lookup(model);
+    }
+
+    /// Dollars spent this session over all models with a known price, null
+    /// when no model could be priced.
+    pub fn sessionCost(self: *const App) ?f64 {
+        var total: f64 = 0;
+        var priced = false;
+        for (self.swarm.model_stats.keys(), self.swarm.model_stats.values()) |model, usage| {
+            const price = self.modelPrice(model) orelse continue;
+            total += r.cost.cost(price, usage);
+            priced = true;
+        }
+        return if (priced) total else null;
+    }
+
+    /// Books finished responses into the shared daily spend and picks up
+    /// what other instances booked, see spend.zig. Warns when the total
+    /// nears or reaches `blitz.set_daily_budget`.
+    fn syncSpend(self: *App) void {
+        const home = self.swarm.exec.env.get("HOME") orelse return;
+        const now = prv.http.nowMs(self.io);
+        const records = self.swarm.usage_log.items;
+        if (self.spend.booked == records.len and now < self.spend.next_check_ms) return;
+        self.spend.next_check_ms = now + r.spend.CHECK_MS;
+
+        var day_buf: [10]u8 = undefined;
+        const day = r.spend.dayName(&day_buf, now);
+        for (records[self.spend.booked..]) |record| {
+            // unpriced models are unknown, not free, they are left out
+            const price = self.modelPrice(record.model) orelse continue;
+            self.spend.book(self.io, home, day, .{
+                .time_ms = now,
+                .pid = std.c.getpid(),
+                .session = self.session_name orelse "",
+                .model = record.model,
+                .dollars = r.cost.cost(price, record.usage),
+            }) catch |err| std.log.warn("daily spend not booked: {s}", .{@errorName(err)});
+        }
+        self.spend.booked = records.len;
+        if (self.spend.budget <= 0) return;
+
+        self.spend.refresh(self.io, self.gpa, home, day) catch |err| {
+            std.log.warn("daily spend unreadable: {s}", .{@errorName(err)});
+            return;
+        };
+        const line = self.spend.crossed() orelse return;
+        var spent_buf: [32]u8 = undefined;
+        var budget_buf: [32]u8 = undefined;
+        const spent = r.cost.format(&spent_buf, self.spend.today.dollars);
+        const budget = r.cost.format(&budget_buf, self.spend.budget);
+        const instances = self.spend.today.instances;
+        const notify = self.arena_app.allocator();
+        switch (line) {
+            .near => self.notifications.push(notify, .warn, "{s} of the {s} daily budget spent today over {d} instance{s}", .{ spent, budget, instances, if (instances == 1) "" else "s" }) catch {},
+            .reached => self.notifications.push(notify, .err, "daily budget of {s} reached, {s} spent today over {d} instance{s}, new turns wait for tomorrow", .{ budget, spent, instances, if (instances == 1) "" else "s" }) catch {},
+        }
+        self.dirty = true;
+    }
+
+    /// Refuses a new turn once today's spend over all instances reached
+    /// `blitz.set_daily_budget`. True when it was refused.
+    pub fn dailyBudgetReached(self: *App) bool {
+        return self.budgetRefusal() != null;
+    }
+
+    /// Why no run may start now, null when one may. Today's file is read
+    /// again first, and one that can't be read refuses the run rather than
+    /// letting spend through uncounted. Runs of every kind pass here, see
+    /// the `pre_run` check in main.zig.
+    pub fn budgetRefusal(self: *App) ?[]const u8 {
+        if (self.spend.budget <= 0) return null;
+        const notify = self.arena_app.allocator();
+        var budget_buf: [32]u8 = undefined;
+        const budget = r.cost.format(&budget_buf, self.spend.budget);
+        if (self.swarm.exec.env.get("HOME")) |home| {
+            var day_buf: [10]u8 = undefined;
+            const day = r.spend.dayName(&day_buf, prv.http.nowMs(self.io));
+            self.spend.refresh(self.io, self.gpa, home, day) catch |err| {
+                self.notifications.push(notify, .err, "daily spend unreadable ({s}), runs wait until the {s} budget can be checked", .{ @errorName(err), budget }) catch {};
+                return "daily spend unreadable";
+            };
+        }
+        if (!self.spend.reached()) return null;
+        var spent_buf: [32]u8 = undefined;
+        self.notifications.push(notify, .err, "daily budget of {s} reached ({s} today over all instances), raise it with blitz.set_daily_budget", .{
+            budget,
+            r.cost.format(&spent_buf, self.spend.today.dollars),
+        }) catch {};
+        return "daily budget reached";
+    }
+
+    /// Estimated input of sending `parts` as a new turn, on `model_hint`
+    /// or the model the main agent runs. Null when the model has no price.
+    pub fn costPreview(self: *const App, parts: []prv.adapter.ContentPart, model_hint: ?[]const u8) ?CostPreview {
+        const agent = self.mainAgent();
+        var model: []const u8 = if (agent) |a| a.config.model else if (self.config.default_model.bound) self.config.default_model.getName() else "";
+        if (model_hint) |name| {
+            model = name;
+            for (self.config.model_aliases[0..self.config.model_alias_count]) |*alias| {
+                if (std.mem.eql(u8, alias.getAlias(), name)) model = alias.entry.getName();
+            }
+        }
+        if (model.len == 0) return null;
+        return .{
+            .model = model,
+            .context_tokens = if (agent) |a| prv.compact.estimateNextRequestTokens(a) else 0,
+            .prompt_tokens = prv.compact.messageTokens(.{ .role = .user, .parts = parts }),
+            .price = self.modelPrice(model) orelse return null,
+        };
+    }
+
+    /// Opens the `.cost_confirm` popup when sending `parts` costs more than
+    /// `cost_confirm`. True when it opened, the prompt then stays in the
+    /// input until enter confirms through `costPreviewConfirmed`.
+    pub fn openCostPreview(self: *App, parts: []prv.adapter.ContentPart, model_hint: ?[]const u8) !bool {
+        const preview = self.costPreview(parts, model_hint) orelse return false;
+        if (!preview.needsConfirm(self.cost_confirm)) return false;
+
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "expensive prompt · enter sends · esc goes back", .kind = .cost_confirm };
+        var cost_buf: [32]u8 = undefined;
+        var limit_buf: [32]u8 = undefined;
+        const muted: r.tui.Style = .{ .fg = self.theme.muted };
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "model            {s}", .{preview.model}, .{ .fg = self.theme.text }));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "context          ~{d} tokens of history and tools", .{preview.context_tokens}, muted));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "prompt           ~{d} tokens", .{preview.prompt_tokens}, muted));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "input price      ${d:.2} per 1M tokens", .{preview.price.input * 1e6}, muted));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "estimated input  {s}, asks above {s}", .{
+            r.cost.format(&cost_buf, preview.dollars()),
+            r.cost.format(&limit_buf, self.cost_confirm),
+        }, .{ .fg = self.theme.warn, .modifier = .{ .bold = true } }));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "", .{}, muted));
+        try popup.lines.append(alloc, try r.tui.Line.new(alloc, "cache reads are priced as full input, the real cost is usually lower", .{}, muted));
+        self.popup = popup;
+        self.dirty = true;
+        return true;
+    }
+
+    /// Enter on the cost preview closes it, true when the prompt should go
+    /// out without asking again.
+    pub fn costPreviewConfirmed(self: *App) bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .cost_confirm) return false;
+        self.popup = null;
+        self.dirty = true;
+        return true;
+    }
+
+    /// Swaps the agent onto the model `name` resolves to, until
+    /// `restoreTurnModel`. Only call while the agent is idle.
+    pub fn useModelForTurn(self: *App, agent_id: prv.Swarm.AgentId, name: []const u8) bool {
+        const agent = self.swarm.getAgent(agent_id) orelse return false;
+        self.restoreTurnModel();
+        const entry = self.config.resolveModel(name) orelse {
+            self.notifications.push(self.arena_app.allocator(), .warn, "@model:{s}: no default provider to run it on", .{name}) catch {};
+            return false;
+        };
+        self.turn_model = .{
+            .agent_id = agent_id,
+            .entry = entry,
+            .restore = agent.config,
+            .restore_minimal = agent.chat.minimal_tool_descriptions,
+        };
+        const cfg = self.config.buildConfigFor(&self.turn_model.?.entry, self.swarm.exec.env) orelse {
+            self.turn_model = null;
+            self.notifications.push(self.arena_app.allocator(), .warn, "@model:{s}: provider inactive or API key missing", .{name}) catch {};
+            return false;
+        };
+        agent.config = cfg;
+        agent.chat.minimal_tool_descriptions = self.wantsMinimalSchemas(cfg.model);
+        self.pushSystemMessage("@model: {s} for this turn", .{cfg.model});
+        return true;
+    }
+
+    /// Save the images of an agent message to the session's media folder
+    /// and preview the newest one.
+    fn saveGeneratedImages(self: *App, parts: []const prv.adapter.ContentPart) void {
+        var saved = false;
+        for (parts) |part| {
+            const image = switch (part) {
+                .image => |img| img,
+                else => continue,
+            };
+            self.media_saved += 1;
+            const path = r.media.save(self.io, self.sessionAlloc(), self.backup_session, self.media_saved, image) catch |err| {
+                self.notifications.push(self.arena_app.allocator(), .err, "could not save image: {s}", .{@errorName(err)}) catch {};
+                continue;
+            };
+            self.last_image = path;
+            self.pushSystemMessage("image saved to {s}", .{path});
+            saved = true;
+        }
+        if (saved and self.flags.image_preview and self.graphics != .none and self.popup == null) {
+            self.openImagePreview(self.last_image) catch {};
+        }
+    }
+
+    /// `:image [path]`, the newest saved image without a path.
+    pub fn imageCommand(self: *App, arg: []const u8) !void {
+        const path = if (arg.len > 0) arg else self.last_image;
+        if (path.len == 0) {
+            try self.notifications.append(self.arena_app.allocator(), "no image saved in this session yet", .{});
+            return;
+        }
+        try self.openImagePreview(path);
+    }
+
+    /// Popup drawing a saved image inline, the path where the terminal
+    /// cannot.
+    pub fn openImagePreview(self: *App, path: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        if (self.graphics == .none) {
+            try self.notifications.append(notify, "this terminal shows no inline images, see {s}", .{path});
+            return;
+        }
+        const image = r.images.load(self.io, self.gpa, self.cwd, path) catch |err| {
+            try self.notifications.push(notify, .err, "image {s}: {s}", .{ path, @errorName(err) });
+            return;
+        };
+        if (!self.graphics.shows(image.media_type)) {
+            self.gpa.free(image.data);
+            try self.notifications.append(notify, "{s} does not preview in this terminal, see {s}", .{ image.media_type, path });
+            return;
+        }
+
+        self.dropImageView();
+        const alloc = self.sessionAlloc();
+        const size = r.media.pngSize(image.data);
+        const title = if (size) |px|
+            try std.fmt.allocPrint(alloc, "{s} · {d}x{d}", .{ path, px.width, px.height })
+        else
+            try alloc.dupe(u8, path);
+        var popup = Popup{ .title = title, .kind = .image };
+        // blank rows the image is drawn over
+        const cells = r.media.fit(size, .{ .cols = IMAGE_PREVIEW_COLS, .rows = IMAGE_PREVIEW_ROWS });
+        for (0..cells.rows) |_| try popup.lines.append(alloc, .{});
+        self.image_view = .{ .image = image, .size = size };
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    /// Image to draw over the frame, dropped once its popup is closed.
+    pub fn imageOverlay(self: *App) ?ImageView {
+        const showing = if (self.popup) |p| p.kind == .image else false;
+        if (!showing) {
+            self.dropImageView();
+            return null;
+        }
+        const view = self.image_view orelse return null;
+        return if (view.area != null) view else null;
+    }
+
+    fn dropImageView(self: *App) void {
+        const view = self.image_view orelse return;
+        self.gpa.free(view.image.data);
+        self.image_view = null;
+    }
+
+    /// Registered providers, the current one marked. Enter switches to the
+    /// selected profile, see `switchProvider`.
+    pub fn openProvidersPopup(self: *App) !void {
+        const alloc = self.sessionAlloc();
+        var popup = Popup{ .title = "providers · enter switch · :provider <name>", .kind = .providers };
+        const cfg = &self.config;
+        const targets = try alloc.alloc(usize, cfg.provider_count);
+        for (cfg.providers[0..cfg.provider_count], 0..) |*prov, i| {
+            targets[i] = i;
+            const current = cfg.default_model.bound and @intFromEnum(cfg.default_model.provider) == i;
+            const key = prov.getKeyEnvar();
+            const key_state = if (key.len == 0) "no key" else if (self.swarm.exec.env.get(key) != null) "key set" else "key missing";
+            var l = r.tui.Line{};
+            try l.pushSpanPrint(alloc, "{s} ", .{if (current) ">" else " "}, .{ .fg = self.theme.ok });
+            try l.pushSpanPrint(alloc, "{s: <12} ", .{if (prov.name_len > 0) prov.getName() else "(unnamed)"}, .{ .fg = if (prov.name_len > 0) self.theme.text else self.theme.muted, .modifier = .{ .bold = current } });
+            try l.pushSpanPrint(alloc, "{s}", .{prov.getUrl()}, .{ .fg = self.theme.muted });
+            const model = if (current) cfg.default_model.getName() else prov.getModel();
+            if (model.len > 0) try l.pushSpanPrint(alloc, "  {s}", .{model}, .{ .fg = self.theme.info });
+            try l.pushSpanPrint(alloc, "  {s}", .{key_state}, .{ .fg = if (std.mem.eql(u8, key_state, "key missing")) self.theme.warn else self.theme.muted });
+            try popup.lines.append(alloc, l);
+        }
+        if (targets.len == 0) {
+            try popup.lines.append(alloc, try r.tui.Line.new(alloc, "no providers, add one with blitz.add_provider in your config", .{}, .{ .fg = self.theme.muted }));
+        }
+        popup.targets = targets;
+        popup.selected = @min(if (self.popup) |old| old.selected else @intFromEnum(cfg.default_model.provider), targets.len -| 1);
+        self.popup = popup;
+        self.dirty = true;
+    }
+
+    pub fn providersKey(self: *App, c: u8) !bool {
+        const popup = if (self.popup) |*p| p else return false;
+        if (popup.kind != .providers) return false;
+        switch (c) {
+            'j' => popup.moveSelection(1),
+            'k' => popup.moveSelection(-1),
+            else => return false,
+        }
+        self.dirty = true;
+        return true;
+    }
+
+    pub fn providersUseSelected(self: *App) !bool {
+        const popup = self.popup orelse return false;
+        if (popup.kind != .providers) return false;
+        if (popup.targets.len == 0) return true;
+        const prov = &self.config.providers[popup.targets[popup.selected]];
+        if (prov.name_len == 0) {
+            try self.notifications.append(self.arena_app.allocator(), "give the provider a name in its blitz.add_provider to switch to it", .{});
+            return true;
+        }
+        try self.switchProvider(prov.getName());
+        return true;
+    }
+
+    /// `:provider <name>` moves the default model onto the named profile and
+    /// keeps the choice in the settings file. An idle main agent switches
+    /// right away, its conversation continues on the new endpoint.
+    pub fn switchProvider(self: *App, name: []const u8) !void {
+        const notify = self.arena_app.allocator();
+        if (name.len == 0) return self.openProvidersPopup();
+        if (self.isRunning()) {
+            try self.notifications.append(notify, "agent is busy, cancel first", .{});
+            return;
+        }
+        const handle = self.config.findProvider(name) orelse {
+            try self.notifications.push(notify, .err, "no provider named {s}, :providers lists them", .{name});
+            return;
+        };
+        if (!self.config.useProvider(handle)) {
+            try self.notifications.push(notify, .err, "{s} has no model, set one with its `model` field or blitz.set_model", .{name});
+            return;
+        }
+        _ = try self.applySetting(r.settings.find("model").?, self.config.default_model.getName());
+
+        if (self.mainAgent()) |agent| {
+            if (self.config.buildConfig(self.swarm.exec.env)) |cfg| {
+                agent.config = cfg;
+                agent.chat.minimal_tool_descriptions = self.wantsMinimalSchemas(cfg.model);
+            } else {
+                try self.notifications.push(notify, .warn, "{s}: API key missing, the next session starts there once it is set", .{name});
+            }
+        }
+        self.pushSystemMessage("now on {s} via {s}", .{ self.config.default_model.getName(), name });
+        if (self.popup != null and self.popup.?.kind == .providers) try self.openProvidersPopup();
+    }
+
+    pub fn restoreTurnModel(self: *App) void {
+        const turn = self.turn_model orelse return;
+        self.turn_model = null;
+        const agent = self.swarm.getAgent(turn.agent_id) orelse return;
+        agent.config = turn.restore;
+        agent.chat.minimal_tool_descriptions = turn.restore_minimal;
+        self.pushSystemMessage("answered by {s}, back on {s}", .{ turn.entry.getName(), turn.restore.model });
+    }
+
+    /// Keeps the main agent going while its todo list has open items. Capped
+    /// by `todo_nudge.max`, so a model that never updates the list can't
+    /// ping-pong forever; a user message resets the count. True when nudged.
+    pub fn nudgeUnfinishedTodos(self: *App) !bool {
+        if (self.todo_nudges_sent >= self.todo_nudge.max) return false;
+        const agent_id = self.main_agent_id orelse return false;
+        const agent = self.swarm.getAgent(agent_id) orelse return false;
+        const alloc = self.sessionAlloc();
+        var open: usize = 0;
+        // names the todo to pick up, a bare reminder tends to get "all done" back
+        const next: ?[]const u8 = blk: {
+            const g = agent.todo_list.lock(self.io);
+            defer g.unlock();
+            for (g.ptr.todos[0..g.ptr.count]) |t| {
+                if (t.state != .done) open += 1;
+            }
+            const todo = g.ptr.nextActionable() orelse break :blk null;
+            break :blk try std.fmt.allocPrint(alloc, "Next up: todo {d} \"{s}\" ({s}).", .{ todo.id, todo.subject, todo.state.toString() });
+        };
+        if (open == 0) return false;
+
+        self.todo_nudges_sent += 1;
+        const text = if (next) |n| try std.fmt.allocPrint(alloc, "{s}\n\n{s}", .{ self.todo_nudge.text, n }) else self.todo_nudge.text;
+        const parts = try alloc.dupe(prv.adapter.ContentPart, &.{.{ .text = text }});
+        try self.cmd_queue.append(self.io, .{ .queue_agent_message = .{ .agent_id = agent_id, .parts = parts } });
+        self.pushSystemMessage("{d} open todo{s}, continuing ({d}/{d})", .{ open, if (open == 1) "" else "s", self.todo_nudges_sent, self.todo_nudge.max });
+        return true;
+    }
+
+    /// Append "run took 3m12s over 14 turns" once the main run ended.
+    pub fn pushRunSummary(self: *App, agent: *const prv.agent.Agent) void {
+        const timing = agent.timing;
+        if (timing.turns == 0) return;
+        var buf: [16]u8 = undefined;
+        const took = formatDuration(&buf, prv.http.nowMs(self.io) - timing.start_ms);
+        self.pushSystemMessage("run took {s} over {d} turn{s}", .{ took, timing.turns, if (timing.turns == 1) "" else "s" });
+    }
+
+    pub fn terminalStatus(self: *const App) TerminalStatus {
+        if (self.active_permission != null) return .needs_input;
+        return if (self.isRunning()) .running else .idle;
+    }
+
+    pub fn isRunning(self: *const App) bool {
+        return self.run_state.active();
+    }
+
+    /// Move between `running` and `queued` as follow-ups get consumed.
+    /// `idle` and `cancelling` are only left through explicit transitions.
+    fn syncRunState(self: *App) void {
+        switch (self.run_state) {
+            .running, .queued => {
+                const next: RunState = if (self.queued.count() > 0) .queued else .running;
+                if (next != self.run_state) {
+                    self.run_state = next;
+                    self.dirty = true;
+                }
+            },
+            .idle, .cancelling => {},
+        }
+    }
+
     pub fn popQueuedMessage(self: *App, agent_id: prv.Swarm.AgentId, alloc: std.mem.Allocator) ?[]const prv.adapter.ContentPart {
         const queued = self.queued.popFor(agent_id) orelse return null;
 
-        if (queued.entry) |entry| self.chat_entries.append(self.sessionAlloc(), entry) catch {};
+        if (queued.entry) |entry| self.pushEntry(self.sessionAlloc(), entry) catch {};
 
         const parts = alloc.alloc(prv.adapter.ContentPart, queued.parts.len) catch return null;
         for (queued.parts, 0..) |*part, i| {
@@ -929,6 +4862,29 @@ pub const App = struct {
         return self.popQueuedMessage(agent_id, alloc);
     }
 
+    /// Puts a classified error card in front of a provider error body. The
+    /// raw body stays below it unless the card already shows all of it.
+    fn withProviderErrorCard(alloc: std.mem.Allocator, http_status: ?u16, parts: []ChatPart) []ChatPart {
+        if (parts.len == 0 or parts[0] != .plain_text) return parts;
+        const body = parts[0].plain_text;
+        const info = prv.errors.classify(alloc, http_status, body);
+        const keep_raw = !std.mem.eql(u8, info.message, body);
+        const out = alloc.alloc(ChatPart, parts.len + @intFromBool(keep_raw)) catch return parts;
+        out[0] = .{ .provider_error = .{
+            .kind = info.kind,
+            .http_status = http_status,
+            .message = alloc.dupe(u8, info.message) catch return parts,
+        } };
+        if (keep_raw) {
+            @memcpy(out[1..], parts);
+        } else {
+            @memcpy(out[1..], parts[1..]);
+            alloc.free(body);
+        }
+        alloc.free(parts);
+        return out;
+    }
+
     /// Convert an agent message's content parts into renderable ChatEntry
     /// message parts (trim + dupe text/thinking, drop everything else).
     /// Returns null if no renderable parts remain.
@@ -1014,7 +4970,7 @@ pub const App = struct {
             var parts = try arena.alloc(ChatPart, 1);
             parts[0] = .{ .diff = .{
                 .path = perm.payload.diff.path,
-                .diff_lines = lines.items,
+                .diff_lines = try r.review.annotate(arena, lines.items, &self.review),
             } };
 
             const block_height = try buildChatEntryParagraph(arena, &stack, self.mainAgent(), self, .{
@@ -1045,7 +5001,7 @@ pub const App = struct {
                     .diff_lines = try lines.toOwnedSlice(alloc),
                 } };
 
-                try self.chat_entries.append(alloc, .{
+                try self.pushEntry(alloc, .{
                     .role = .agent,
                     .parts = parts,
                 });
@@ -1079,6 +5035,24 @@ pub fn emitDiffLines(out: *std.ArrayList(r.tui.DiffLine), snap: prv.Swarm.ToolDi
     }
 }
 
+/// Lines of `git diff` output, classified by their prefix.
+pub fn emitUnifiedDiffLines(out: *std.ArrayList(r.tui.DiffLine), text: []const u8, alloc: std.mem.Allocator) void {
+    var it = std.mem.splitScalar(u8, std.mem.trimEnd(u8, text, "\n"), '\n');
+    while (it.next()) |line| {
+        const kind: r.tui.DiffLineKind = if (std.mem.startsWith(u8, line, "diff --git") or std.mem.startsWith(u8, line, "@@"))
+            .header
+        else if (std.mem.startsWith(u8, line, "+++") or std.mem.startsWith(u8, line, "---"))
+            .header
+        else if (std.mem.startsWith(u8, line, "+"))
+            .addition
+        else if (std.mem.startsWith(u8, line, "-"))
+            .deletion
+        else
+            .context;
+        pushDiffLine(out, alloc, .{ .kind = kind, .content = line });
+    }
+}
+
 /// Myers' O(ND) shortest edit script with context collapsing. `base_line` is
 /// the 1-based line number in the original where `old_lines` begins.
 fn emitMyersDiff(
@@ -1100,21 +5074,10 @@ fn emitMyersDiff(
 
     if (ops.len == 0) return;
 
-    const ctx_radius = 3;
-
-    const visible = alloc.alloc(bool, ops.len) catch {
+    const visible = diffVisibility(ops, alloc) orelse {
         emitAllOps(out, ops, base_line, alloc);
         return;
     };
-    @memset(visible, false);
-
-    for (ops, 0..) |op, idx| {
-        if (op != .keep) {
-            const start = if (idx >= ctx_radius) idx - ctx_radius else 0;
-            const end = @min(idx + ctx_radius + 1, ops.len);
-            @memset(visible[start..end], true);
-        }
-    }
 
     var old_ln: u32 = 0;
     var in_gap = false;
@@ -1148,6 +5111,60 @@ fn emitMyersDiff(
     }
 }
 
+/// Unchanged lines shown around every change, longer runs collapse into a
+/// `...` separator between hunks.
+const DIFF_CONTEXT_LINES = 3;
+
+/// Which ops are shown, every change with its context.
+fn diffVisibility(ops: []const DiffOp, alloc: std.mem.Allocator) ?[]bool {
+    const visible = alloc.alloc(bool, ops.len) catch return null;
+    @memset(visible, false);
+    for (ops, 0..) |op, idx| {
+        if (op != .keep) {
+            const start = idx -| DIFF_CONTEXT_LINES;
+            const end = @min(idx + DIFF_CONTEXT_LINES + 1, ops.len);
+            @memset(visible[start..end], true);
+        }
+    }
+    return visible;
+}
+
+/// `snap.after` with the hunks rejected in `review` left as they are in
+/// `snap.before`. Hunks are counted like the separators `emitDiffLines`
+/// puts between them. Null for a new file, which is one hunk.
+pub fn applyHunks(alloc: std.mem.Allocator, snap: prv.Swarm.ToolDiff, review: *const r.review.Review) ?[]const u8 {
+    const before = snap.before orelse return null;
+    const old_lines = splitLinesAlloc(before, alloc) orelse return null;
+    const new_lines = splitLinesAlloc(snap.after, alloc) orelse return null;
+    const ops = myersDiff(old_lines, new_lines, alloc) orelse return null;
+    const visible = diffVisibility(ops, alloc) orelse return null;
+
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    var hunk: usize = 0;
+    var in_gap = false;
+    var emitted_any = false;
+    var first = true;
+    for (ops, visible) |op, shown| {
+        if (!shown) {
+            in_gap = true;
+        } else {
+            if (in_gap and emitted_any) hunk += 1;
+            in_gap = false;
+            emitted_any = true;
+        }
+        const rejected = review.isRejected(hunk);
+        const line = switch (op) {
+            .keep => |l| l,
+            .delete => |l| if (rejected) l else continue,
+            .insert => |l| if (rejected) continue else l,
+        };
+        if (!first) out.writer.writeByte('\n') catch return null;
+        out.writer.writeAll(line) catch return null;
+        first = false;
+    }
+    return out.written();
+}
+
 fn emitAllOps(out: *std.ArrayList(r.tui.DiffLine), ops: []const DiffOp, base_line: u32, alloc: std.mem.Allocator) void {
     var old_ln: u32 = 0;
     for (ops) |op| {
@@ -1170,12 +5187,16 @@ fn emitAllOps(out: *std.ArrayList(r.tui.DiffLine), ops: []const DiffOp, base_lin
 pub const ChatEntry = struct {
     role: prv.adapter.Role,
     parts: []ChatPart,
+    /// wall clock ms it entered the chat, 0 in sessions saved before entries
+    /// were timed
+    time_ms: i64 = 0,
 
     pub fn free(self: *ChatEntry, alloc: std.mem.Allocator) void {
         for (self.parts) |part| {
             switch (part) {
                 .message => |slice| alloc.free(slice),
                 .plain_text => |slice| alloc.free(slice),
+                .provider_error => |err| alloc.free(err.message),
                 .thinking => |slice| alloc.free(slice),
                 .plan => |plan| {
                     alloc.free(plan.lines);
@@ -1202,14 +5223,20 @@ pub const ChatEntry = struct {
 };
 
 pub const ChatPart = union(enum) {
-    // TODO add time tracking
     thinking: []const u8,
     message: []const u8,
     plain_text: []const u8,
+    provider_error: ProviderErrorEntry,
     diff: DiffEntry,
     plan: PlanEntry, // rename to proposal
     tool_call: ToolCallEntry,
 
+    pub const ProviderErrorEntry = struct {
+        kind: prv.errors.Kind,
+        http_status: ?u16,
+        message: []const u8,
+    };
+
     pub const PlanEntry = struct {
         lines: []const r.tui.Line,
     };
@@ -1391,13 +5418,7 @@ fn containsCommandCompletion(items: []?[]const u8, needle: []const u8) bool {
     return false;
 }
 
-fn startsWithIgnoreCase(value: []const u8, prefix: []const u8) bool {
-    if (prefix.len > value.len) return false;
-    for (prefix, 0..) |c, i| {
-        if (std.ascii.toLower(c) != std.ascii.toLower(value[i])) return false;
-    }
-    return true;
-}
+const startsWithIgnoreCase = r.suggest.startsWithIgnoreCase;
 
 fn appendBuiltinCommandCompletions(prefix: []const u8, out: []?[]const u8, count: *usize) void {
     for (builtin_command_completions) |completion| {
@@ -1430,6 +5451,71 @@ fn commandCompletions(app: *App, input: []const u8, cursor: u32) [COMMAND_COMPLE
     return rows;
 }
 
+const PromptSuggestions = struct {
+    trigger: r.suggest.Trigger = .none,
+    rows: [COMMAND_COMPLETION_ROWS][]const u8 = [_][]const u8{""} ** COMMAND_COMPLETION_ROWS,
+    count: usize = 0,
+
+    fn items(self: *const PromptSuggestions) []const []const u8 {
+        return self.rows[0..self.count];
+    }
+};
+
+/// Suggestions for the token under the cursor: slash commands at the start
+/// of the input, file paths after `@`. `alloc` should be the frame arena.
+fn promptSuggestions(app: *App, alloc: std.mem.Allocator) PromptSuggestions {
+    const input = app.inputSlice();
+    var out = PromptSuggestions{ .trigger = r.suggest.triggerAt(input, app.input_cursor) };
+    switch (out.trigger) {
+        .none => {},
+        .command => {
+            out.rows = commandCompletions(app, input, app.input_cursor);
+            while (out.count < out.rows.len and out.rows[out.count].len > 0) out.count += 1;
+        },
+        .file => |token| {
+            // remote cwd is not browsable from here
+            if (app.swarm.exec.ssh_target != null) return out;
+            var list: std.ArrayList([]const u8) = .empty;
+            r.suggest.appendFileCandidates(app.io, alloc, app.cwd, token.prefix, &list, COMMAND_COMPLETION_ROWS) catch {};
+            for (list.items, 0..) |item, i| out.rows[i] = item;
+            out.count = list.items.len;
+        },
+    }
+    return out;
+}
+
+/// One-line hint in the prompt footer: what `tab` inserts and how many
+/// other candidates match.
+fn renderInputHints(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, buf: *r.tui.Buffer, suggestions: *const PromptSuggestions) void {
+    if (area.height < 2) return;
+    const y = area.y + area.height - 1;
+    const style: r.tui.Style = .{ .fg = app.theme.muted, .bg = app.theme.overlay_dark };
+
+    const label: []const u8 = switch (suggestions.trigger) {
+        .none => {
+            const input = app.inputSlice();
+            if (!app.isRunning() and input.len > 0 and input[0] == '?') {
+                buf.setStringMax(area.x + 2, y, "ask · answered without tools", style, area.width -| 4);
+            }
+            return;
+        },
+        .command => "command",
+        .file => "file",
+    };
+
+    const hint = if (app.completion_suggestion) |sug|
+        std.fmt.allocPrint(arena, "{s} · tab ▸ {s} · {d} match{s}", .{
+            label,
+            sug,
+            suggestions.count,
+            if (suggestions.count == 1) "" else "es",
+        }) catch return
+    else
+        std.fmt.allocPrint(arena, "{s} · no matches", .{label}) catch return;
+
+    buf.setStringMax(area.x + 2, y, hint, style, area.width -| 4);
+}
+
 // TODO: move to input popup instead
 fn renderCommandPalette(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, buf: *r.tui.Buffer) !void {
     _ = arena;
@@ -1455,7 +5541,7 @@ fn renderCommandPalette(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, b
 }
 
 fn renderInput(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, buf: *r.tui.Buffer) !void {
-    const border_color = if (app.running)
+    const border_color = if (app.isRunning())
         app.theme.muted
     else
         app.context_factory.getMode(app.mode).color;
@@ -1529,7 +5615,17 @@ fn renderInput(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, buf: *r.tu
     para.scroll_offset = app.input_scroll_offset;
 
     const mode_name = app.context_factory.getMode(app.mode).name;
-    const title = try std.fmt.allocPrint(arena, "┤{s}├", .{mode_name});
+    const search = &app.chat_search;
+    const title = if (app.message_select.current != null)
+        "┤message: j/k move · y copy · c code block · o $EDITOR · e expand output · esc done├"
+    else if (!search.active)
+        try std.fmt.allocPrint(arena, "┤{s}├", .{mode_name})
+    else if (search.editing)
+        try std.fmt.allocPrint(arena, "┤search: {s}▏ · enter jump · esc close├", .{search.query()})
+    else blk: {
+        const index, const total = search.position(app.chat_entries.items);
+        break :blk try std.fmt.allocPrint(arena, "┤search: {s} {d}/{d} · n older · N newer · esc close├", .{ search.query(), index, total });
+    };
     const block = r.tui.Block{
         .title = title,
         .title_style = .{ .fg = border_color },
@@ -1550,7 +5646,7 @@ fn renderPermMessage(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void {
     const pm = &app.input_mode.perm_message;
     const input_widget: r.tui.Input = .{
         .text = pm.buf[0..pm.len],
-        .border_style = .{ .fg = app.theme.warn },
+        .border_style = .{ .fg = if (pm.comment) app.theme.info else app.theme.warn },
         .has_screenshot = app.screenshot_buf != null,
     };
     input_widget.render(area, buf);
@@ -1585,6 +5681,111 @@ fn renderPassphraseModal(app: *App, full_area: r.tui.Rect, buf: *r.tui.Buffer) v
     buf.set(x, y, .{ .char = '_', .style = .{ .fg = Theme.default.warn } });
 }
 
+fn renderPopup(app: *App, arena: std.mem.Allocator, full_area: r.tui.Rect, buf: *r.tui.Buffer) void {
+    const popup = if (app.popup) |*p| p else return;
+    const w: u16 = @min(full_area.width -| 4, 96);
+    const h: u16 = @min(full_area.height -| 4, @as(u16, @intCast(@min(popup.lines.items.len + 2, std.math.maxInt(u16)))));
+    if (w < 8 or h < 3) return;
+    const area = full_area.center(w, h);
+
+    const title = std.fmt.allocPrint(arena, " {s} ", .{popup.title}) catch popup.title;
+    const block: r.tui.Block = .{
+        .title = title,
+        .title_style = .{ .fg = app.theme.info, .modifier = .{ .bold = true } },
+        .style = .{ .fg = app.theme.info, .bg = app.theme.overlay_dark },
+        .borders = .all,
+    };
+    const inner = block.innerArea(area);
+    block.render(area, buf);
+
+    if (!popup.scrollsOnly()) {
+        // keep the selected row in view
+        if (popup.selected < popup.scroll) popup.scroll = popup.selected;
+        if (popup.selected >= popup.scroll + inner.height) popup.scroll = popup.selected + 1 - inner.height;
+    }
+    popup.scroll = @min(popup.scroll, popup.lines.items.len -| inner.height);
+    const para = r.tui.Paragraph{
+        .lines = popup.lines,
+        .scroll_offset = popup.scroll,
+        .padding = .{ .left = 1, .right = 1 },
+        .style = .{ .bg = app.theme.overlay_dark },
+    };
+    para.renderSimple(arena, inner, buf);
+    if (popup.kind == .image) {
+        if (app.image_view) |*view| view.area = .{ .x = inner.x + 1, .y = inner.y, .width = inner.width -| 2, .height = inner.height };
+    }
+
+    if (!popup.scrollsOnly() and popup.selected >= popup.scroll and popup.selected - popup.scroll < inner.height) {
+        const y = inner.y + @as(u16, @intCast(popup.selected - popup.scroll));
+        var x = inner.x;
+        while (x < inner.x +| inner.width) : (x += 1) {
+            var cell = buf.get(x, y);
+            cell.style.modifier.reverse = true;
+            buf.set(x, y, cell);
+        }
+    }
+}
+
+const QUOTE_MAX_LINES = 20;
+
+/// `> **<tool> failed:**` followed by `text` as quoted lines, cut after
+/// `max_lines`. Ends with an empty line so the user can type below it.
+pub fn formatBlockquote(alloc: std.mem.Allocator, tool: []const u8, text: []const u8, max_lines: usize) ![]const u8 {
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    errdefer out.deinit();
+
+    try out.writer.print("> **{s} failed:**\n", .{tool});
+    var lines = std.mem.splitScalar(u8, std.mem.trim(u8, text, "\n"), '\n');
+    var n: usize = 0;
+    while (lines.next()) |line| : (n += 1) {
+        if (n == max_lines) {
+            try out.writer.writeAll("> …\n");
+            break;
+        }
+        try out.writer.print("> {s}\n", .{std.mem.trimRight(u8, line, "\r")});
+    }
+    try out.writer.writeAll("\n");
+    return out.toOwnedSlice();
+}
+
+/// First non-blank line of a tool error, for one-row summaries.
+fn firstLine(text: []const u8) []const u8 {
+    return std.mem.sliceTo(std.mem.trim(u8, text, " \t\r\n"), '\n');
+}
+
+fn percentOf(part: u64, total: u64) f64 {
+    if (total == 0) return 0;
+    return @as(f64, @floatFromInt(part)) * 100.0 / @as(f64, @floatFromInt(total));
+}
+
+/// First line of text, or the tool a message calls / answers.
+fn messagePreview(msg: prv.adapter.Message, max_len: usize) []const u8 {
+    for (msg.parts) |part| {
+        const txt = switch (part) {
+            .text => |t| std.mem.trim(u8, t, " \t\r\n"),
+            .tool_call => |call| call.name,
+            .tool_result => |res| res.name,
+            .image => "[image]",
+            .thinking => continue,
+        };
+        const line = txt[0 .. std.mem.indexOfScalar(u8, txt, '\n') orelse txt.len];
+        if (line.len <= max_len) return line;
+        var end = max_len;
+        while (end > 0 and (line[end] & 0xC0) == 0x80) end -= 1;
+        return line[0..end];
+    }
+    return "";
+}
+
+/// Strip the cwd prefix for display. Paths outside the cwd stay absolute.
+fn relativeToCwd(cwd: []const u8, path: []const u8) []const u8 {
+    if (!std.mem.startsWith(u8, path, cwd)) return path;
+    const rest = path[cwd.len..];
+    if (rest.len == 0) return path;
+    if (rest[0] == '/') return rest[1..];
+    return if (cwd.len > 0 and cwd[cwd.len - 1] == '/') rest else path;
+}
+
 fn renderNotifications(app: *App, arena: std.mem.Allocator, full_area: r.tui.Rect, buf: *r.tui.Buffer) void {
     const notif_w: u16 = @min(full_area.width / 3, 40);
     if (notif_w < 4) return;
@@ -1600,11 +5801,11 @@ fn renderNotifications(app: *App, arena: std.mem.Allocator, full_area: r.tui.Rec
             .used => |en| {
                 var para = r.tui.Paragraph{
                     .border = .single,
-                    .style = .{ .fg = app.theme.text, .bg = app.theme.overlay_dark },
+                    .style = .{ .fg = levelColor(app, en.level), .bg = app.theme.overlay_dark },
                     .padding = .{ .left = 1, .right = 1, .top = 0, .bottom = 0 },
                 };
                 var l = r.tui.Line{};
-                l.pushText(arena, en.msg, .{}) catch {};
+                l.pushText(arena, en.msg, .{ .fg = app.theme.text }) catch {};
                 para.lines.append(arena, l) catch {};
 
                 const total_h = para.totalHeight(notif_w);
@@ -1626,6 +5827,14 @@ fn renderNotifications(app: *App, arena: std.mem.Allocator, full_area: r.tui.Rec
     }
 }
 
+fn levelColor(app: *const App, level: Notifications.Level) r.tui.Color {
+    return switch (level) {
+        .info => app.theme.text,
+        .warn => app.theme.warn,
+        .err => app.theme.err,
+    };
+}
+
 fn renderStatusBar(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void {
     for (area.x..area.x +| area.width) |x| {
         buf.set(@intCast(x), area.y, .{ .char = ' ', .style = .{ .fg = .white, .bg = app.theme.overlay_dark } });
@@ -1647,7 +5856,7 @@ fn renderStatusBar(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void {
 
     const ctx_pct: u8 = @intFromFloat(@min(app.contextPercent(), 100));
 
-    var status_buf: [256]u8 = undefined;
+    var status_buf: [384]u8 = undefined;
     var in_buf: [16]u8 = undefined;
     var out_buf: [16]u8 = undefined;
     var cache_buf: [16]u8 = undefined;
@@ -1656,14 +5865,46 @@ fn renderStatusBar(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void {
     const usage = app.swarm.usage();
     const in_str = formatTokenCount(&in_buf, usage.input_tokens);
     const out_str = formatTokenCount(&out_buf, usage.output_tokens);
-    const cache_str = formatTokenCount(&cache_buf, usage.cached_tokens);
+    const cache_tokens = formatTokenCount(&cache_buf, usage.cached_tokens);
+    var hit_buf: [32]u8 = undefined;
+    const cache_str = if (r.cost.cacheHitPercent(usage)) |hit|
+        std.fmt.bufPrint(&hit_buf, "{s} ({d}% hit)", .{ cache_tokens, hit }) catch cache_tokens
+    else
+        cache_tokens;
     const ctx_str = std.fmt.bufPrint(&ctx_buf, "{d}%", .{ctx_pct}) catch "0%";
     const skip_str = if (app.flags.skip_permissions) "| AUTO APPROVAL" else "";
+    const loading_str = if (app.model_preload != null) "| loading model… " else if (app.links.fetching() > 0) "| fetching links… " else "";
+
+    var delta_buf: [32]u8 = undefined;
+    const changed = app.changedFileCount();
+    const delta_str = if (changed > 0)
+        std.fmt.bufPrint(&delta_buf, "| Δ {d} file{s} ", .{ changed, if (changed == 1) "" else "s" }) catch ""
+    else
+        "";
+
+    var cost_buf: [24]u8 = undefined;
+    var dollars_buf: [16]u8 = undefined;
+    const cost_str = if (app.tabCost()) |dollars|
+        std.fmt.bufPrint(&cost_buf, " {s}", .{r.cost.format(&dollars_buf, dollars)}) catch ""
+    else
+        "";
+
+    var tasks_buf: [32]u8 = undefined;
+    const tasks_str = if (app.tasks.count() > 0)
+        std.fmt.bufPrint(&tasks_buf, "| {d} TASK{s}{s} ", .{ app.tasks.count(), if (app.tasks.count() == 1) "" else "S", if (app.tasks.paused) " PAUSED" else "" }) catch ""
+    else
+        "";
+
+    var name_buf: [160]u8 = undefined;
+    const name_str = if (app.session_name) |name| blk: {
+        if (app.tabs.items.len == 0) break :blk std.fmt.bufPrint(&name_buf, "{s} | ", .{name}) catch "";
+        break :blk std.fmt.bufPrint(&name_buf, "{s} {d}/{d} | ", .{ name, app.active_tab + 1, app.tabs.items.len }) catch "";
+    } else "";
 
     const status = std.fmt.bufPrint(
         &status_buf,
-        "IN:{s} OUT:{s} CACHE:{s} | CTX:{s} {s}",
-        .{ in_str, out_str, cache_str, ctx_str, skip_str },
+        "{s}IN:{s} OUT:{s} CACHE:{s}{s} | CTX:{s} {s}{s}{s}{s}{s}",
+        .{ name_str, in_str, out_str, cache_str, cost_str, ctx_str, loading_str, app.run_state.label(), tasks_str, delta_str, skip_str },
     ) catch " ?? ";
 
     renderCenteredStatusText(app, area, buf, status);
@@ -1716,7 +5957,59 @@ fn renderLuaError(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, buf: *r
     p.renderSimple(arena, area, buf);
 }
 
+/// One row above the prompt while the turn has tool errors: how many, the
+/// key to the panel and the last one.
+fn renderToolErrorStrip(app: *App, arena: std.mem.Allocator, area: r.tui.Rect, buf: *r.tui.Buffer) !void {
+    if (area.width == 0 or area.height == 0) return;
+    const last = app.tool_failures.last() orelse return;
+    const n = app.tool_failures.count(app.user_turn);
+    const bg = app.theme.overlay_dark;
+    buf.fill(area, .{ .style = .{ .bg = bg } });
+
+    var l = r.tui.Line{};
+    try l.pushSpanPrint(arena, " ✗ {d} tool error{s} ", .{ n, if (n == 1) "" else "s" }, .{ .fg = app.theme.err, .bg = bg, .modifier = .{ .bold = true } });
+    try l.pushSpanPrint(arena, "c+x details ", .{}, .{ .fg = app.theme.muted, .bg = bg });
+    try l.pushSpanPrint(arena, "· last: {s}: {s}", .{ last.call.name, firstLine(last.message) }, .{ .fg = app.theme.text, .bg = bg });
+    l.render(area.x, area.y, area.width, buf);
+}
+
 const RenderParagraphItem = struct { p: r.tui.Paragraph, h: usize };
+
+/// `buildChatEntryParagraph` with the chat search query highlighted, the
+/// current match (`index` in `chat_entries`) stronger than the rest. The
+/// selected message gets a lighter background.
+fn buildSearchedEntry(
+    arena: std.mem.Allocator,
+    out: *std.ArrayList(RenderParagraphItem),
+    agent: ?*prv.agent.Agent,
+    app: *App,
+    entry: ChatEntry,
+    index: ?usize,
+    is_streaming: bool,
+    inner_w: u16,
+) !usize {
+    const start = out.items.len;
+    var height = try buildChatEntryParagraph(arena, out, agent, app, entry, is_streaming, inner_w);
+    if (index != null and app.message_select.current == index) {
+        for (out.items[start..]) |*item| item.p.style.bg = app.theme.overlay;
+    }
+    const search = &app.chat_search;
+    if (!search.active or !entryMatches(entry, search.query())) return height;
+
+    const current = if (index) |idx| search.current == idx else false;
+    const style: r.tui.Style = .{ .fg = app.theme.bg, .bg = if (current) app.theme.warn else app.theme.muted };
+    for (out.items[start..]) |*item| {
+        var changed = false;
+        for (item.p.lines.items) |*line| {
+            if (try highlightLine(arena, line, search.query(), style)) changed = true;
+        }
+        if (!changed) continue;
+        const h = item.p.totalHeightLong(inner_w);
+        height = height - item.h + h;
+        item.h = h;
+    }
+    return height;
+}
 /// Build one r.tui.Paragraph per ChatEntry. Allocations live in `arena`; do not
 /// deinit the result. All paragraphs use `reverse = true` so the chat-area
 /// caller can stack them bottom-up.
@@ -1755,19 +6048,19 @@ fn buildChatEntryParagraph(
     };
 
     if (is_streaming and flags.is_thinking) {
-        const spinner = text_utils.spinnerDots(app.frame_count);
+        const spinner = app.spinner();
         try header_line.pushSpan(arena, .{ .content = "  thinking .. ", .style = .{ .fg = app.theme.muted } });
         try header_line.pushSpan(arena, .{ .content = spinner, .style = .{ .modifier = .{ .bold = true } } });
     }
 
     if (is_streaming and flags.is_writing) {
-        const spinner = text_utils.spinnerDots(app.frame_count);
+        const spinner = app.spinner();
         try header_line.pushSpan(arena, .{ .content = "  writing .. ", .style = .{ .fg = app.theme.muted } });
         try header_line.pushSpan(arena, .{ .content = spinner, .style = .{ .modifier = .{ .bold = true } } });
     }
 
     if (is_streaming and flags.is_calling) {
-        const spinner = text_utils.spinnerDots(app.frame_count);
+        const spinner = app.spinner();
         try header_line.pushSpan(arena, .{ .content = "  calling .. ", .style = .{ .fg = app.theme.muted } });
         try header_line.pushSpan(arena, .{ .content = spinner, .style = .{ .modifier = .{ .bold = true } } });
     }
@@ -1782,6 +6075,7 @@ fn buildChatEntryParagraph(
                 if (app.flags.show_thinking) {
                     var p = r.tui.Paragraph{};
                     try p.appendText(arena, text, .{ .fg = app.theme.muted });
+                    try foldLongMessage(&p, arena, app);
                     const h = p.totalHeightLong(inner_w);
                     try out.append(arena, .{ .p = p, .h = h });
                     total += h;
@@ -1790,6 +6084,7 @@ fn buildChatEntryParagraph(
             .message => |text| {
                 var p = r.tui.Paragraph{};
                 try appendMarkdownText(&p, arena, text);
+                try foldLongMessage(&p, arena, app);
                 const h = p.totalHeightLong(inner_w);
                 try out.append(arena, .{ .p = p, .h = h });
                 total += h;
@@ -1797,6 +6092,13 @@ fn buildChatEntryParagraph(
             .plain_text => |text| {
                 var p = r.tui.Paragraph{};
                 try p.appendText(arena, text, .{});
+                try foldLongMessage(&p, arena, app);
+                const h = p.totalHeightLong(inner_w);
+                try out.append(arena, .{ .p = p, .h = h });
+                total += h;
+            },
+            .provider_error => |err| {
+                const p = try buildProviderErrorParagraph(arena, app, err);
                 const h = p.totalHeightLong(inner_w);
                 try out.append(arena, .{ .p = p, .h = h });
                 total += h;
@@ -1827,6 +6129,15 @@ fn buildChatEntryParagraph(
     return total;
 }
 
+/// Folds the middle of a huge message, a 3000 line file pasted back would
+/// otherwise bury the rest of the chat and get wrapped on every frame.
+fn foldLongMessage(p: *r.tui.Paragraph, arena: std.mem.Allocator, app: *const App) !void {
+    const n = p.lines.items.len;
+    if (app.flags.expand_messages or n <= FOLD_MIN_LINES) return;
+    const marker = try r.tui.Line.new(arena, "··· {d} lines folded, :expand shows them ···", .{n - FOLD_HEAD_LINES - FOLD_TAIL_LINES}, .{ .fg = app.theme.muted, .modifier = .{ .italic = true } });
+    try p.fold(arena, FOLD_HEAD_LINES, FOLD_TAIL_LINES, marker);
+}
+
 fn buildToolGroupParagraph(
     app: *App,
     arena: std.mem.Allocator,
@@ -1841,8 +6152,21 @@ fn buildToolGroupParagraph(
     const statuses = app.tool_status_entries.lock(app.io);
     defer statuses.unlock();
 
-    for (calls) |call| {
-        const agent = app.swarm.getAgent(call.agent_id) orelse continue;
+    var idx: usize = 0;
+    while (idx < calls.len) : (idx += 1) {
+        const call = calls[idx];
+        if (app.swarm.getAgent(call.agent_id) == null) continue;
+
+        if (!app.flags.expand_messages) {
+            var end = idx;
+            while (end < calls.len and isExplorationTool(calls[end].tool_name) and toolOutcome(app, statuses.ptr, calls[end]) != null) end += 1;
+            if (end - idx >= 2) {
+                try p.lines.append(arena, try buildExplorationLine(app, arena, statuses.ptr, calls[idx..end]));
+                idx = end - 1;
+                continue;
+            }
+        }
+
         var line = r.tui.Line{};
 
         const status_agent = &statuses.ptr.agents[call.agent_id.index];
@@ -1850,8 +6174,7 @@ fn buildToolGroupParagraph(
             status_agent.entries.getPtr(call.call_id)
         else
             null;
-        const live_result: ?prv.adapter.ToolResult = findToolResult(agent, call.call_id) orelse agent.tool_call_done.get(call.call_id);
-        const is_error = if (live_result) |result| result.is_error else if (status) |entry| entry.is_error else null;
+        const is_error = toolOutcome(app, statuses.ptr, call);
         if (is_error) |failed| {
             if (failed) {
                 try line.pushSpan(arena, .{ .content = r.tui.icon.fail, .style = .{ .fg = app.theme.err, .modifier = .{ .bold = true } } });
@@ -1859,7 +6182,7 @@ fn buildToolGroupParagraph(
                 try line.pushSpan(arena, .{ .content = r.tui.icon.ok, .style = .{ .fg = app.theme.ok, .modifier = .{ .bold = true } } });
             }
         } else {
-            try line.pushSpan(arena, .{ .content = text_utils.spinnerDots(app.frame_count), .style = .{ .fg = app.theme.text } });
+            try line.pushSpan(arena, .{ .content = app.spinner(), .style = .{ .fg = app.theme.text } });
         }
 
         try line.pushSpan(arena, .{ .content = " " });
@@ -1896,6 +6219,16 @@ fn buildToolGroupParagraph(
                     try p.lines.append(arena, extra);
                 }
             }
+            if (entry.spill) |spill| {
+                const latest = if (statuses.ptr.last_spill) |last| last.ptr == spill.ptr else false;
+                const note = if (latest)
+                    try std.fmt.allocPrint(arena, "  {d} lines, cut for the model, :output or C-j then e pages through them", .{entry.spill_lines})
+                else
+                    try std.fmt.allocPrint(arena, "  {d} lines, cut for the model, C-j then e pages through them, kept in {s}", .{ entry.spill_lines, spill });
+                var extra = r.tui.Line{};
+                try extra.pushSpan(arena, .{ .content = note, .style = .{ .fg = app.theme.muted } });
+                try p.lines.append(arena, extra);
+            }
 
             const child_id = entry.child_id orelse continue;
             if (child_id.index >= prv.Swarm.MAX_AGENTS) continue;
@@ -1938,7 +6271,7 @@ fn buildCompactionIndicatorParagraph(arena: std.mem.Allocator, app: *App) r.tui.
     };
 
     var line = r.tui.Line{};
-    line.pushSpan(arena, .{ .content = text_utils.spinnerDots(app.frame_count), .style = .{ .fg = app.theme.text } }) catch {};
+    line.pushSpan(arena, .{ .content = app.spinner(), .style = .{ .fg = app.theme.text } }) catch {};
     line.pushText(arena, " compacting context", .{ .fg = app.theme.muted, .modifier = .{ .bold = true } }) catch {};
     p.lines.append(arena, line) catch {};
 
@@ -1995,6 +6328,101 @@ fn buildMessageParagraph(
     return p;
 }
 
+/// Whether the call failed, null while it still runs.
+fn toolOutcome(app: *App, statuses: *const ToolStatusStore, call: ChatPart.ToolCallEntry) ?bool {
+    const agent = app.swarm.getAgent(call.agent_id) orelse return null;
+    if (findToolResult(agent, call.call_id) orelse agent.tool_call_done.get(call.call_id)) |result| return result.is_error;
+    const status_agent = &statuses.agents[call.agent_id.index];
+    if (status_agent.generation != call.agent_id.generation) return null;
+    const entry = status_agent.entries.getPtr(call.call_id) orelse return null;
+    return entry.is_error;
+}
+
+fn isExplorationTool(name: []const u8) bool {
+    for (exploration_tools) |tool| {
+        if (std.mem.eql(u8, tool, name)) return true;
+    }
+    return false;
+}
+
+/// An agent turn that did nothing but look things up.
+fn isExplorationEntry(entry: ChatEntry) bool {
+    if (entry.role != .agent or entry.parts.len == 0) return false;
+    for (entry.parts) |part| switch (part) {
+        .tool_call => |call| if (!isExplorationTool(call.tool_name)) return false,
+        else => return false,
+    };
+    return true;
+}
+
+/// `✓ explored  4 read, 2 ripgrep  main.zig, app.zig, "fn run" +3`
+fn buildExplorationLine(app: *App, arena: std.mem.Allocator, statuses: *const ToolStatusStore, calls: []const ChatPart.ToolCallEntry) !r.tui.Line {
+    var failed = false;
+    for (calls) |call| {
+        if (toolOutcome(app, statuses, call) orelse false) failed = true;
+    }
+    var line = r.tui.Line{};
+    if (failed) {
+        try line.pushSpan(arena, .{ .content = r.tui.icon.fail, .style = .{ .fg = app.theme.err, .modifier = .{ .bold = true } } });
+    } else {
+        try line.pushSpan(arena, .{ .content = r.tui.icon.ok, .style = .{ .fg = app.theme.ok, .modifier = .{ .bold = true } } });
+    }
+    try line.pushSpan(arena, .{ .content = " explored ", .style = .{ .modifier = .{ .bold = true } } });
+
+    var counts: [exploration_tools.len]usize = @splat(0);
+    for (calls) |call| {
+        for (exploration_tools, 0..) |tool, t| {
+            if (std.mem.eql(u8, tool, call.tool_name)) counts[t] += 1;
+        }
+    }
+    var sep: []const u8 = " ";
+    for (exploration_tools, counts) |tool, n| {
+        if (n == 0) continue;
+        try line.pushSpanPrint(arena, "{s}{d} {s}", .{ sep, n, tool }, .{});
+        sep = ", ";
+    }
+
+    sep = "  ";
+    var listed: usize = 0;
+    for (calls) |call| {
+        if (listed == EXPLORATION_TARGETS) break;
+        const target = explorationTarget(app, arena, call) orelse continue;
+        try line.pushSpanPrint(arena, "{s}{s}", .{ sep, target }, .{ .fg = app.theme.muted });
+        sep = ", ";
+        listed += 1;
+    }
+    if (calls.len > listed) try line.pushSpanPrint(arena, " +{d}", .{calls.len - listed}, .{ .fg = app.theme.muted });
+    return line;
+}
+
+/// File name or search pattern of an exploration call, from its arguments.
+fn explorationTarget(app: *App, arena: std.mem.Allocator, call: ChatPart.ToolCallEntry) ?[]const u8 {
+    const agent = app.swarm.getAgent(call.agent_id) orelse return null;
+    const arguments = findToolCallArguments(agent, call.call_id) orelse return null;
+    const parsed = std.json.parseFromSliceLeaky(std.json.Value, arena, arguments, .{}) catch return null;
+    if (parsed != .object) return null;
+    if (parsed.object.get("path")) |v| if (v == .string) return std.fs.path.basename(v.string);
+    for ([_][]const u8{ "pattern", "args", "query" }) |key| {
+        const v = parsed.object.get(key) orelse continue;
+        if (v != .string) continue;
+        const text = v.string[0..@min(v.string.len, 40)];
+        return std.fmt.allocPrint(arena, "\"{s}{s}\"", .{ text, if (text.len < v.string.len) "…" else "" }) catch null;
+    }
+    return null;
+}
+
+fn findToolCallArguments(agent: *prv.agent.Agent, call_id: []const u8) ?[]const u8 {
+    var i = agent.chat.messages.items.len;
+    while (i > 0) {
+        i -= 1;
+        for (agent.chat.messages.items[i].parts) |part| switch (part) {
+            .tool_call => |call| if (std.mem.eql(u8, call.id, call_id)) return call.arguments,
+            else => {},
+        };
+    }
+    return null;
+}
+
 /// Scan chat history for a tool_result with the given call_id. Source of
 /// truth for "did this tool finish": `tool_call_done` is cleared right
 /// after commit, but the result lives on in the chat as a tool_result part.
@@ -2025,6 +6453,26 @@ fn buildPlanParagraph(arena: std.mem.Allocator, plan: ChatEntry.PlanEntry) r.tui
     return p;
 }
 
+fn buildProviderErrorParagraph(arena: std.mem.Allocator, app: *App, err: ChatPart.ProviderErrorEntry) !r.tui.Paragraph {
+    const theme = app.theme;
+    var p: r.tui.Paragraph = .{
+        .border = .single,
+        .sides = .left_only,
+        .style = .{ .fg = theme.err },
+    };
+
+    var title = r.tui.Line{};
+    try title.pushSpan(arena, .{ .content = r.tui.icon.fail, .style = .{ .fg = theme.err, .modifier = .{ .bold = true } } });
+    try title.pushSpanPrint(arena, " {s}", .{err.kind.title()}, .{ .fg = theme.err, .modifier = .{ .bold = true } });
+    if (err.http_status) |status| try title.pushSpanPrint(arena, "  HTTP {d}", .{status}, .{ .fg = theme.muted });
+    try p.lines.append(arena, title);
+
+    try p.appendText(arena, err.message, .{ .fg = theme.text });
+    const hint = err.kind.hint();
+    if (hint.len > 0) try p.appendText(arena, hint, .{ .fg = theme.info });
+    return p;
+}
+
 fn buildDiffParagraph(arena: std.mem.Allocator, app: *App, d: ChatPart.DiffEntry) r.tui.Paragraph {
     const theme = app.theme;
     var p: r.tui.Paragraph = .{
@@ -2148,7 +6596,7 @@ fn renderChatArea(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) !usize {
     const inner_h: u16 = area.height;
 
     var scroll_offset_usize: usize = if (app.auto_scroll) 0 else app.scroll_offset;
-    const target: usize = @as(usize, inner_h) +| scroll_offset_usize;
+    var target: usize = @as(usize, inner_h) +| scroll_offset_usize;
 
     var stack: std.ArrayList(RenderParagraphItem) = .empty;
     var total: usize = 0;
@@ -2197,6 +6645,21 @@ fn renderChatArea(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) !usize {
                 .diff_lines = lines.items,
             } };
 
+            const block_height = try buildChatEntryParagraph(alloc, &stack, maybe_agent, app, .{
+                .role = .agent,
+                .parts = parts,
+            }, false, inner_w);
+            total += block_height;
+        } else if (perm.payload == .commit) {
+            var lines = std.ArrayList(r.tui.DiffLine).empty;
+            emitUnifiedDiffLines(&lines, perm.payload.commit.diff, alloc);
+
+            var parts = try alloc.alloc(ChatPart, 1);
+            parts[0] = .{ .diff = .{
+                .path = try std.fmt.allocPrint(alloc, "git commit: {s}", .{std.mem.sliceTo(perm.payload.commit.message, '\n')}),
+                .diff_lines = lines.items,
+            } };
+
             const block_height = try buildChatEntryParagraph(alloc, &stack, maybe_agent, app, .{
                 .role = .agent,
                 .parts = parts,
@@ -2206,18 +6669,53 @@ fn renderChatArea(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) !usize {
     }
 
     if (app.streaming_entry) |entry| {
-        const block_height = try buildChatEntryParagraph(alloc, &stack, maybe_agent, app, entry, true, inner_w);
+        const block_height = try buildSearchedEntry(alloc, &stack, maybe_agent, app, entry, null, true, inner_w);
         total += block_height;
     }
 
-    while (i > 0 and total < target) {
+    // a search match to bring into view, built down to even when above the
+    // current scroll position
+    const jump = app.chat_search.jump_to;
+    app.chat_search.jump_to = null;
+
+    while (i > 0 and (total < target or (jump != null and i > jump.?))) {
         i -= 1;
         const entry = app.chat_entries.items[i];
 
         if (maybe_agent == null and entry.role != .system) continue;
 
-        const block_height = try buildChatEntryParagraph(alloc, &stack, maybe_agent, app, entry, false, inner_w);
+        // consecutive lookup-only turns render as one exploration block
+        if (!app.flags.expand_messages and isExplorationEntry(entry)) {
+            var first = i;
+            var count = entry.parts.len;
+            while (first > 0 and isExplorationEntry(app.chat_entries.items[first - 1])) {
+                first -= 1;
+                count += app.chat_entries.items[first].parts.len;
+            }
+            if (first < i) {
+                const parts = try alloc.alloc(ChatPart, count);
+                var n: usize = 0;
+                for (app.chat_entries.items[first .. i + 1]) |run| {
+                    @memcpy(parts[n..][0..run.parts.len], run.parts);
+                    n += run.parts.len;
+                }
+                i = first;
+                total += try buildChatEntryParagraph(alloc, &stack, maybe_agent, app, .{ .role = .agent, .parts = parts }, false, inner_w);
+                continue;
+            }
+        }
+
+        const below = total;
+        const block_height = try buildSearchedEntry(alloc, &stack, maybe_agent, app, entry, i, false, inner_w);
         total += block_height;
+
+        if (jump != null and jump.? == i) {
+            // the entry's first row at the top of the viewport
+            scroll_offset_usize = (below + block_height) -| inner_h;
+            target = @as(usize, inner_h) +| scroll_offset_usize;
+            app.scroll_offset = scroll_offset_usize;
+            app.auto_scroll = scroll_offset_usize == 0;
+        }
     }
 
     if (i == 0) {
@@ -2287,7 +6785,7 @@ fn renderMainProgress(app: *App, id: ?prv.Swarm.AgentId, area: r.tui.Rect, buf:
 
     var b: [255]u8 = undefined;
     const line = if (state == .active) blk: {
-        const spinner_str = text_utils.spinnerDots(app.frame_count);
+        const spinner_str = app.spinner();
         const exec_pool = app.swarm.exec;
         const ssh_suffix: []const u8 = if (exec_pool.ssh_active and exec_pool.ssh_target != null) " (SSH ON)" else "";
 
@@ -2300,9 +6798,18 @@ fn renderMainProgress(app: *App, id: ?prv.Swarm.AgentId, area: r.tui.Rect, buf:
         else
             std.fmt.bufPrint(&queued_buf, "({d} queued messages up)", .{queued_count}) catch "(queued messages up)";
 
-        break :blk std.fmt.bufPrint(&b, "{s} ({d}s) Consuming tokens …{s} {s}", .{
+        const timing = slot.agent.timing;
+        var avg_buf: [32]u8 = undefined;
+        const avg_str: []const u8 = if (timing.avgTurnMs()) |avg|
+            std.fmt.bufPrint(&avg_buf, " · avg {d:.1}s/turn", .{@as(f64, @floatFromInt(avg)) / 1000.0}) catch ""
+        else
+            "";
+
+        break :blk std.fmt.bufPrint(&b, "{s} turn {d} · {d}s elapsed{s} · Consuming tokens …{s} {s}", .{
             spinner_str,
+            timing.turns,
             secs,
+            avg_str,
             ssh_suffix,
             queued_suffix,
         }) catch "…";
@@ -2337,6 +6844,12 @@ fn renderPermissionWidget(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void
 
     const entry = app.active_permission orelse return;
 
+    const stop_hint = "s deny & stop run";
+    if (inner.height > 1 and inner.width > stop_hint.len + 2) {
+        const x = inner.x + inner.width - @as(u16, stop_hint.len) - 1;
+        buf.setString(x, inner.y + inner.height - 1, stop_hint, .{ .fg = app.theme.muted });
+    }
+
     if (entry.payload == .ask) {
         renderAskWidget(app, entry, inner, buf);
         return;
@@ -2351,6 +6864,18 @@ fn renderPermissionWidget(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void
             break :blk n;
         },
         .diff => |p| blk: {
+            if (p.truncationWarning()) |pct| {
+                const n = std.fmt.bufPrint(&header_buf, "edit: {s} (warning: {d}% smaller, truncated?)", .{ p.path, pct }) catch "edit";
+                break :blk n;
+            }
+            if (app.review.rejectedCount() > 0) {
+                const n = std.fmt.bufPrint(&header_buf, "edit: {s} ({d} hunks rejected, yes writes the rest)", .{ p.path, app.review.rejectedCount() }) catch "edit";
+                break :blk n;
+            }
+            if (app.review.comments.items.len > 0) {
+                const n = std.fmt.bufPrint(&header_buf, "edit: {s} ({d} comments, sent on no)", .{ p.path, app.review.comments.items.len }) catch "edit";
+                break :blk n;
+            }
             const n = std.fmt.bufPrint(&header_buf, "edit: {s}", .{p.path}) catch "edit";
             break :blk n;
         },
@@ -2359,18 +6884,22 @@ fn renderPermissionWidget(app: *App, area: r.tui.Rect, buf: *r.tui.Buffer) void
             const n = std.fmt.bufPrint(&header_buf, "plan: {s}", .{plan_trunc}) catch "plan";
             break :blk n;
         },
+        .commit => |p| blk: {
+            const n = std.fmt.bufPrint(&header_buf, "commit: {s} ({d} files)", .{ std.mem.sliceTo(p.message, '\n'), p.files.len }) catch "commit";
+            break :blk n;
+        },
         .ask => unreachable,
     };
     buf.setStringMax(inner.x + 1, inner.y, header_line, .{ .fg = app.theme.warn }, inner.width -| 1);
 
-    const labels = [3][]const u8{ "allow?  yes", "        no", "        enter message" };
-    const labels_sel = [3][]const u8{ "allow? >yes", "       >no", "       >enter message" };
+    const labels = [4][]const u8{ "allow?  yes", "        always allow", "        no", "        enter message" };
+    const labels_sel = [4][]const u8{ "allow? >yes", "       >always allow", "       >no", "       >enter message" };
 
     const plan_labels = [4][]const u8{ "plan?  approve & clear", "       approve & keep", "       no", "       enter message" };
     const plan_labels_sel = [4][]const u8{ "plan? >approve & clear", "      >approve & keep", "      >no", "      >enter message" };
 
     const is_plan = entry.payload == .plan;
-    const count: usize = if (is_plan) 4 else 3;
+    const count: usize = 4;
 
     const cur_sel: u8 = switch (app.input_mode) {
         .perm_select => |ps| ps.selected,
@@ -2426,6 +6955,14 @@ fn renderAskWidget(app: *App, req: *prv.Swarm.PermissionReq, inner: r.tui.Rect,
     }
 }
 
+/// `38s`, `3m12s` or `1h04m`.
+pub fn formatDuration(dest: []u8, ms: i64) []const u8 {
+    const secs: u64 = @intCast(@divTrunc(@max(ms, 0), std.time.ms_per_s));
+    if (secs < 60) return std.fmt.bufPrint(dest, "{d}s", .{secs}) catch "?";
+    if (secs < 3600) return std.fmt.bufPrint(dest, "{d}m{d:0>2}s", .{ secs / 60, secs % 60 }) catch "?";
+    return std.fmt.bufPrint(dest, "{d}h{d:0>2}m", .{ secs / 3600, (secs % 3600) / 60 }) catch "?";
+}
+
 fn formatTokenCount(dest: []u8, count: u64) []const u8 {
     if (count < 1000) {
         return std.fmt.bufPrint(dest, "{d}", .{count}) catch "0";
@@ -2610,3 +7147,313 @@ test "ToolStatusStore retains terminal tool result" {
     try std.testing.expectEqual(agent_id.generation, agent.generation);
     try std.testing.expect(agent.entries.get("call_1").?.is_error.?);
 }
+
+test "WorkspaceChanges nets out files created and removed in the same session" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var changes: WorkspaceChanges = .{};
+    try changes.record(alloc, "/w/a.zig", .{ .kind = .modified, .tool = "edit", .timestamp = 1 });
+    try changes.record(alloc, "/w/a.zig", .{ .kind = .modified, .tool = "patch", .timestamp = 2 });
+    try changes.record(alloc, "/w/tmp.txt", .{ .kind = .added, .tool = "write", .timestamp = 3 });
+    try changes.record(alloc, "/w/tmp.txt", .{ .kind = .deleted, .tool = "patch", .timestamp = 4 });
+    try changes.record(alloc, "/w/new.zig", .{ .kind = .added, .tool = "write", .timestamp = 5 });
+    try changes.record(alloc, "/w/new.zig", .{ .kind = .modified, .tool = "edit", .timestamp = 6 });
+
+    try std.testing.expectEqual(@as(usize, 2), changes.changedCount());
+    try std.testing.expectEqual(FileChangeKind.added, WorkspaceChanges.netKind(changes.files.get("/w/new.zig").?.items).?);
+    try std.testing.expectEqual(@as(usize, 2), changes.files.get("/w/a.zig").?.items.len);
+    try std.testing.expectEqualStrings("src/main.zig", relativeToCwd("/w", "/w/src/main.zig"));
+    try std.testing.expectEqualStrings("/wx/main.zig", relativeToCwd("/w", "/wx/main.zig"));
+}
+
+test "formatBlockquote" {
+    const alloc = std.testing.allocator;
+    const q = try formatBlockquote(alloc, "bash", "line one\r\nline two\nline three\n", 2);
+    defer alloc.free(q);
+    try std.testing.expectEqualStrings("> **bash failed:**\n> line one\n> line two\n> …\n\n", q);
+}
+
+test "formatDuration" {
+    var buf: [16]u8 = undefined;
+    try std.testing.expectEqualStrings("38s", formatDuration(&buf, 38_400));
+    try std.testing.expectEqualStrings("3m12s", formatDuration(&buf, 192_000));
+    try std.testing.expectEqualStrings("1h04m", formatDuration(&buf, 3_840_000));
+}
+
+test "parseModelHint" {
+    const hint = parseModelHint("@model:haiku what does `tick` return?").?;
+    try std.testing.expectEqualStrings("haiku", hint.name);
+    try std.testing.expectEqualStrings("what does `tick` return?", hint.rest);
+    try std.testing.expectEqualStrings("", parseModelHint("@model:gpt-5.4-mini").?.rest);
+    try std.testing.expect(parseModelHint("@model: haiku") == null);
+    try std.testing.expect(parseModelHint("use @model:haiku") == null);
+}
+
+test "notifications stay in the log after their toast" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var n: Notifications = .{};
+    try std.testing.expectEqual(@as(usize, 0), n.history().len);
+    try n.append(alloc, "first", .{});
+    try std.testing.expect(n.tick(Notifications.DISPLAY_MS));
+    try std.testing.expect(!n.hasVisible());
+    try std.testing.expect(!n.tick(Notifications.DISPLAY_MS + 1));
+    try n.push(alloc, .err, "rate limited {d}", .{2});
+    try std.testing.expect(n.hasVisible());
+
+    const log = n.history();
+    try std.testing.expectEqual(@as(usize, 2), log.len);
+    try std.testing.expectEqualStrings("rate limited 2", log[0].used.msg);
+    try std.testing.expectEqual(Notifications.Level.err, log[0].used.level);
+    try std.testing.expectEqual(Notifications.Level.info, log[1].used.level);
+}
+
+test "notification toasts expire on their own timers" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var n: Notifications = .{};
+    try n.push(alloc, .err, "build failed", .{});
+    _ = n.tick(1000);
+    try n.pushFor(alloc, .info, 500, "copied", .{});
+    try std.testing.expectEqual(@as(?i64, 500), n.msUntilExpiry());
+
+    try std.testing.expect(n.tick(1500));
+    var it = n.iter();
+    try std.testing.expectEqualStrings("build failed", it.next().?.used.msg);
+    try std.testing.expect(it.next() == null);
+    try std.testing.expectEqual(@as(?i64, 2 * Notifications.DISPLAY_MS - 1500), n.msUntilExpiry());
+
+    try std.testing.expect(!n.tick(2000));
+    try std.testing.expect(n.tick(2 * Notifications.DISPLAY_MS));
+    try std.testing.expectEqual(@as(?i64, null), n.msUntilExpiry());
+}
+
+test "permission hashes identify identical requests" {
+    const bash = App.permissionHash(.{ .call = .{ .tool_name = "bash", .tool_arguments = "{\"command\":\"zig build\"}" } }).?;
+    try std.testing.expectEqual(bash, App.permissionHash(.{ .call = .{ .tool_name = "bash", .tool_arguments = "{\"command\":\"zig build\"}" } }).?);
+    try std.testing.expect(bash != App.permissionHash(.{ .call = .{ .tool_name = "bash", .tool_arguments = "{\"command\":\"zig build test\"}" } }).?);
+
+    const edit = App.permissionHash(.{ .diff = .{ .path = "a.zig", .before = "x", .after = "y" } }).?;
+    try std.testing.expect(edit != App.permissionHash(.{ .diff = .{ .path = "a.zig", .before = "xy", .after = "" } }).?);
+    try std.testing.expect(App.permissionHash(.{ .ask = .{ .header = "", .question = "continue?", .options = &.{} } }) == null);
+}
+
+test "usage breakdown per prompt, model and tool" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const Prices = struct {
+        fn modelPrice(_: @This(), model: []const u8) ?r.cost.Price {
+            if (std.mem.eql(u8, model, "big")) return .{ .input = 1e-6, .output = 1e-5 };
+            return null;
+        }
+    };
+    const records = [_]prv.Swarm.UsageRecord{
+        .{ .turn = 1, .model = "big", .usage = .{ .input_tokens = 1000, .output_tokens = 100 }, .tools = &.{ "bash", "read" } },
+        .{ .turn = 1, .model = "big", .usage = .{ .input_tokens = 2000, .output_tokens = 200 }, .tools = &.{} },
+        .{ .turn = 2, .model = "local", .usage = .{ .input_tokens = 500, .output_tokens = 50 }, .tools = &.{"bash"} },
+    };
+    const b = try UsageBreakdown.build(arena.allocator(), &records, Prices{});
+
+    try std.testing.expectEqual(@as(usize, 2), b.turns.count());
+    try std.testing.expectEqual(@as(u64, 3000), b.turns.get(1).?.usage.input_tokens);
+    try std.testing.expectApproxEqAbs(@as(f64, 0.006), b.turns.get(1).?.cost, 1e-9);
+    try std.testing.expect(b.turns.get(2).?.unpriced);
+    try std.testing.expect(!b.models.get("big").?.unpriced);
+    try std.testing.expectEqual(@as(u64, 1000), b.tools.get("bash").?.usage.input_tokens);
+    try std.testing.expectEqual(@as(u64, 500), b.tools.get("read").?.usage.input_tokens);
+    try std.testing.expectEqual(@as(u64, 2000), b.tools.get("answer").?.usage.input_tokens);
+    try std.testing.expectEqual(@as(u64, 350), b.total.usage.output_tokens);
+}
+
+test "cost preview asks above the threshold" {
+    var preview = CostPreview{
+        .model = "claude-opus-4",
+        .context_tokens = 90_000,
+        .prompt_tokens = 10_000,
+        .price = .{ .input = 15.0 / 1_000_000.0, .output = 75.0 / 1_000_000.0 },
+    };
+    try std.testing.expectApproxEqAbs(@as(f64, 1.5), preview.dollars(), 1e-9);
+    try std.testing.expect(preview.needsConfirm(DEFAULT_COST_CONFIRM));
+    try std.testing.expect(!preview.needsConfirm(2.0));
+    try std.testing.expect(!preview.needsConfirm(0));
+    preview.context_tokens = 0;
+    try std.testing.expect(!preview.needsConfirm(DEFAULT_COST_CONFIRM));
+}
+
+test "chat search steps between matches and highlights them" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var texts = [_]ChatPart{
+        .{ .message = "fix the Parser" },
+        .{ .message = "done" },
+        .{ .thinking = "the parser is slow" },
+        .{ .plain_text = "nothing here" },
+    };
+    const entries = [_]ChatEntry{
+        .{ .role = .user, .parts = texts[0..1] },
+        .{ .role = .agent, .parts = texts[1..2] },
+        .{ .role = .agent, .parts = texts[2..3] },
+        .{ .role = .system, .parts = texts[3..4] },
+    };
+
+    var search: ChatSearch = .{ .active = true };
+    @memcpy(search.query_buf[0..6], "parser");
+    search.query_len = 6;
+
+    try std.testing.expect(search.step(&entries, true));
+    try std.testing.expectEqual(@as(?usize, 2), search.current);
+    try std.testing.expectEqual(@as(?usize, 2), search.jump_to);
+    const index, const total = search.position(&entries);
+    try std.testing.expectEqual(@as(usize, 2), index);
+    try std.testing.expectEqual(@as(usize, 2), total);
+    try std.testing.expect(search.step(&entries, true));
+    try std.testing.expectEqual(@as(?usize, 0), search.current);
+    try std.testing.expect(search.step(&entries, true));
+    try std.testing.expectEqual(@as(?usize, 2), search.current);
+    try std.testing.expect(search.step(&entries, false));
+    try std.testing.expectEqual(@as(?usize, 0), search.current);
+
+    search.query_len = 0;
+    try std.testing.expect(!search.step(&entries, true));
+
+    const hit: r.tui.Style = .{ .bg = .red };
+    var line = try r.tui.Line.new(alloc, "a parser, the PARSER", .{}, .{});
+    try std.testing.expect(try highlightLine(alloc, &line, "parser", hit));
+    try std.testing.expectEqual(@as(usize, 4), line.spans.items.len);
+    try std.testing.expectEqualStrings("PARSER", line.spans.items[3].content);
+    try std.testing.expect(line.spans.items[3].style.bg.eql(hit.bg));
+    try std.testing.expect(!try highlightLine(alloc, &line, "lexer", hit));
+}
+
+test "message selection walks messages and finds code blocks" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var parts = [_]ChatPart{
+        .{ .message = "fix it" },
+        .{ .thinking = "hmm" },
+        .{ .message = "use this:\n```zig\nconst a = 1;\n```\nor\n  ````\nx ``` y\n  ````" },
+        .{ .plain_text = "tail" },
+    };
+    const entries = [_]ChatEntry{
+        .{ .role = .user, .parts = parts[0..1] },
+        .{ .role = .agent, .parts = parts[1..2] },
+        .{ .role = .agent, .parts = parts[2..4] },
+    };
+
+    var select: MessageSelect = .{};
+    try std.testing.expect(select.step(&entries, true));
+    try std.testing.expectEqual(@as(?usize, 2), select.current);
+    try std.testing.expect(select.step(&entries, true));
+    try std.testing.expectEqual(@as(?usize, 0), select.current);
+    try std.testing.expect(!select.step(&entries, true));
+    try std.testing.expect(select.step(&entries, false));
+    try std.testing.expectEqual(@as(?usize, 2), select.current);
+
+    // a tool call is a stop, e expands its cut output
+    var call_part = [_]ChatPart{.{ .tool_call = .{ .agent_id = .{ .index = 0, .generation = 1 }, .call_id = "a", .tool_name = "bash" } }};
+    const with_call = [_]ChatEntry{ entries[0], entries[1], .{ .role = .agent, .parts = &call_part } };
+    var call_select: MessageSelect = .{};
+    try std.testing.expect(call_select.step(&with_call, true));
+    try std.testing.expectEqual(@as(?usize, 2), call_select.current);
+    try std.testing.expect(call_select.step(&with_call, true));
+    try std.testing.expectEqual(@as(?usize, 0), call_select.current);
+
+    const body = try messageBody(alloc, entries[2]);
+    try std.testing.expect(std.mem.endsWith(u8, body, "````\n\ntail"));
+    try std.testing.expectEqualStrings("const a = 1;", codeBlock(body, 0).?);
+    try std.testing.expectEqualStrings("x ``` y", codeBlock(body, 1).?);
+    try std.testing.expect(codeBlock(body, 2) == null);
+    try std.testing.expect(codeBlock("```\nunclosed", 0) == null);
+}
+
+test "lookup-only turns count as exploration" {
+    const id: prv.Swarm.AgentId = .{ .index = 0, .generation = 1 };
+    var reads = [_]ChatPart{
+        .{ .tool_call = .{ .agent_id = id, .call_id = "a", .tool_name = "read" } },
+        .{ .tool_call = .{ .agent_id = id, .call_id = "b", .tool_name = "ripgrep" } },
+    };
+    var mixed = [_]ChatPart{
+        .{ .tool_call = .{ .agent_id = id, .call_id = "c", .tool_name = "read" } },
+        .{ .tool_call = .{ .agent_id = id, .call_id = "d", .tool_name = "edit" } },
+    };
+    var text = [_]ChatPart{.{ .message = "found it" }};
+    try std.testing.expect(isExplorationEntry(.{ .role = .agent, .parts = &reads }));
+    try std.testing.expect(!isExplorationEntry(.{ .role = .agent, .parts = &mixed }));
+    try std.testing.expect(!isExplorationEntry(.{ .role = .agent, .parts = &text }));
+    try std.testing.expect(!isExplorationEntry(.{ .role = .user, .parts = &reads }));
+}
+
+test "applyHunks keeps rejected hunks as they were" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const before = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+    const after = "A\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nL\n";
+    const snap: prv.Swarm.ToolDiff = .{ .path = "x", .before = before, .after = after };
+
+    var lines = std.ArrayList(r.tui.DiffLine).empty;
+    emitDiffLines(&lines, snap, alloc);
+    try std.testing.expectEqual(@as(usize, 2), r.review.hunkCount(lines.items));
+
+    var review: r.review.Review = .{};
+    try std.testing.expectEqualStrings(after, applyHunks(alloc, snap, &review).?);
+    review.hunk = 1;
+    review.toggleReject();
+    try std.testing.expectEqualStrings("A\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n", applyHunks(alloc, snap, &review).?);
+    try std.testing.expect(applyHunks(alloc, .{ .path = "x", .before = null, .after = after }, &review) == null);
+}
+
+test "task queue splits lines and keeps order" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var tasks: TaskQueue = .{};
+    try std.testing.expectEqual(@as(usize, 3), try tasks.add(alloc, "rename Foo to Bar\n\n  add tests \nupdate docs"));
+    try std.testing.expectEqual(@as(usize, 2), tasks.move(1, 1));
+    try std.testing.expectEqual(@as(usize, 0), tasks.move(0, -1));
+
+    try std.testing.expectEqualStrings("rename Foo to Bar", tasks.pop().?);
+    try std.testing.expectEqualStrings("update docs", tasks.pop().?);
+    try std.testing.expectEqual(@as(usize, 3), tasks.total());
+    try std.testing.expectEqualStrings("add tests", tasks.pop().?);
+    try std.testing.expect(tasks.pop() == null);
+}
+
+test "tool failures keep the arguments of the failed call per turn" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var failures: ToolFailures = .{};
+    try failures.note(alloc, 1, .{ .tool_call = .{ .id = "a", .name = "bash", .arguments = "{\"command\":\"make\"}" } }, null);
+    try failures.note(alloc, 1, .{ .tool_call = .{ .id = "b", .name = "read", .arguments = "{\"path\":\"x\"}" } }, null);
+    try failures.note(alloc, 1, .{ .tool_result = .{ .call_id = "b", .name = "read", .content = "ok" } }, "{\"path\":\"x\"}");
+    try std.testing.expectEqual(@as(usize, 0), failures.count(1));
+    try failures.note(alloc, 1, .{ .tool_result = .{ .call_id = "a", .name = "bash", .content = "\nmake: *** no rule\nstop", .is_error = true } }, "{\"command\":\"make\"}");
+    try std.testing.expectEqual(@as(usize, 1), failures.count(1));
+    try std.testing.expectEqualStrings("{\"command\":\"make\"}", failures.last().?.call.arguments);
+    try std.testing.expectEqualStrings("make: *** no rule", firstLine(failures.last().?.message));
+
+    // a result without its call still lists, with empty arguments
+    try failures.note(alloc, 1, .{ .tool_result = .{ .call_id = "z", .name = "edit", .content = "no match", .is_error = true } }, null);
+    try std.testing.expectEqualStrings("{}", failures.last().?.call.arguments);
+    try std.testing.expectEqual(@as(usize, 0), failures.count(2));
+
+    failures.confirm = 0;
+    try failures.note(alloc, 2, .{ .tool_call = .{ .id = "c", .name = "bash", .arguments = "{}" } }, null);
+    try std.testing.expectEqual(@as(usize, 0), failures.count(2));
+    try std.testing.expect(failures.last() == null);
+    try std.testing.expect(failures.confirm == null);
+}
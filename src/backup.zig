@@ -0,0 +1,160 @@
+//! Plain copies of files as they were before a tool first modified them in
+//! a session, a safety net for directories without git. Layout:
+//! `.blitz/backups/<session>/<path relative to cwd>`, files outside the cwd
+//! are stored below `_abs/`. The backup root carries a `*` .gitignore so the
+//! copies never show up in `git status`.
+const std = @import("std");
+const util = @import("util.zig");
+
+pub const BACKUP_DIR = util.BLITZ_DIR ++ "/backups";
+const ABS_PREFIX = "_abs";
+
+/// Sortable session directory name, `YYYYMMDD-HHMMSS` in UTC.
+pub fn sessionName(buf: []u8, timestamp_ns: i96) []const u8 {
+    const secs: u64 = @intCast(@max(@divTrunc(timestamp_ns, std.time.ns_per_s), 0));
+    const epoch = std.time.epoch.EpochSeconds{ .secs = secs };
+    const year_day = epoch.getEpochDay().calculateYearDay();
+    const month_day = year_day.calculateMonthDay();
+    const day_secs = epoch.getDaySeconds();
+    return std.fmt.bufPrint(buf, "{d:0>4}{d:0>2}{d:0>2}-{d:0>2}{d:0>2}{d:0>2}", .{
+        year_day.year,
+        month_day.month.numeric(),
+        month_day.day_index + 1,
+        day_secs.getHoursIntoDay(),
+        day_secs.getMinutesIntoHour(),
+        day_secs.getSecondsIntoMinute(),
+    }) catch "session";
+}
+
+/// Location of `path` inside a session directory.
+pub fn relativeBackupPath(alloc: std.mem.Allocator, cwd: []const u8, path: []const u8) ![]const u8 {
+    if (std.mem.startsWith(u8, path, cwd) and path.len > cwd.len and path[cwd.len] == '/') {
+        return alloc.dupe(u8, path[cwd.len + 1 ..]);
+    }
+    return std.fmt.allocPrint(alloc, ABS_PREFIX ++ "{s}", .{path});
+}
+
+/// Inverse of `relativeBackupPath`.
+pub fn originalPath(alloc: std.mem.Allocator, cwd: []const u8, rel: []const u8) ![]const u8 {
+    if (std.mem.startsWith(u8, rel, ABS_PREFIX ++ "/")) return alloc.dupe(u8, rel[ABS_PREFIX.len..]);
+    return std.fs.path.join(alloc, &.{ cwd, rel });
+}
+
+/// Shell snippet copying `path` into the session backup. A missing source
+/// (the tool is about to create it) is not an error.
+pub fn backupScript(alloc: std.mem.Allocator, cwd: []const u8, session: []const u8, path: []const u8) ![]const u8 {
+    const rel = try relativeBackupPath(alloc, cwd, path);
+    defer alloc.free(rel);
+    const root = try std.fs.path.join(alloc, &.{ cwd, BACKUP_DIR });
+    defer alloc.free(root);
+    const dest = try std.fs.path.join(alloc, &.{ root, session, rel });
+    defer alloc.free(dest);
+
+    const q_path = try shellQuote(alloc, path);
+    defer alloc.free(q_path);
+    const q_dest = try shellQuote(alloc, dest);
+    defer alloc.free(q_dest);
+    const q_dir = try shellQuote(alloc, std.fs.path.dirname(dest) orelse root);
+    defer alloc.free(q_dir);
+    const ignore = try std.fs.path.join(alloc, &.{ root, ".gitignore" });
+    defer alloc.free(ignore);
+    const q_ignore = try shellQuote(alloc, ignore);
+    defer alloc.free(q_ignore);
+
+    return std.fmt.allocPrint(
+        alloc,
+        "[ -f {s} ] || exit 0; mkdir -p {s} && cp -p {s} {s} && {{ [ -f {s} ] || echo '*' > {s}; }}",
+        .{ q_path, q_dir, q_path, q_dest, q_ignore, q_ignore },
+    );
+}
+
+pub fn shellQuote(alloc: std.mem.Allocator, s: []const u8) ![]const u8 {
+    var out: std.ArrayList(u8) = .empty;
+    errdefer out.deinit(alloc);
+
+    try out.append(alloc, '\'');
+    for (s) |c| {
+        if (c == '\'') {
+            try out.appendSlice(alloc, "'\\''");
+        } else {
+            try out.append(alloc, c);
+        }
+    }
+    try out.append(alloc, '\'');
+    return out.toOwnedSlice(alloc);
+}
+
+/// `blitzdenk restore [session [file]]`. Without a session the available
+/// backups are listed, without a file the whole session is copied back.
+pub fn restore(io: std.Io, alloc: std.mem.Allocator, cwd: []const u8, session: ?[]const u8, file: ?[]const u8) !void {
+    const root = try std.fs.path.join(alloc, &.{ cwd, BACKUP_DIR });
+    const sess = session orelse {
+        const res = try std.process.run(alloc, io, .{
+            .argv = &.{ "find", root, "-mindepth", "2", "-type", "f", "!", "-name", ".gitignore" },
+        });
+        if (res.stdout.len == 0) {
+            std.debug.print("no backups in {s}\n", .{root});
+            return;
+        }
+        std.debug.print("{s}\nrestore with: blitz restore <session> [file]\n", .{res.stdout});
+        return;
+    };
+
+    const session_dir = try std.fs.path.join(alloc, &.{ root, sess });
+    const res = try std.process.run(alloc, io, .{
+        .argv = &.{ "find", session_dir, "-type", "f" },
+    });
+    if (res.stdout.len == 0) {
+        std.debug.print("no backups for session {s}\n", .{sess});
+        return;
+    }
+
+    var restored: usize = 0;
+    var it = std.mem.tokenizeScalar(u8, res.stdout, '\n');
+    while (it.next()) |backup| {
+        const rel = backup[session_dir.len + 1 ..];
+        if (file) |f| {
+            if (!std.mem.eql(u8, rel, f)) continue;
+        }
+
+        const dest = try originalPath(alloc, cwd, rel);
+        if (std.fs.path.dirname(dest)) |dir| {
+            _ = try std.process.run(alloc, io, .{ .argv = &.{ "mkdir", "-p", dir } });
+        }
+        const cp = try std.process.run(alloc, io, .{ .argv = &.{ "cp", "-p", backup, dest } });
+        switch (cp.term) {
+            .exited => |code| if (code == 0) {
+                restored += 1;
+                std.debug.print("restored {s}\n", .{dest});
+                continue;
+            },
+            else => {},
+        }
+        std.debug.print("failed to restore {s}: {s}\n", .{ dest, cp.stderr });
+    }
+
+    if (restored == 0 and file != null) std.debug.print("{s} is not part of session {s}\n", .{ file.?, sess });
+}
+
+test "backup paths round trip" {
+    const alloc = std.testing.allocator;
+
+    const inside = try relativeBackupPath(alloc, "/w", "/w/src/main.zig");
+    defer alloc.free(inside);
+    try std.testing.expectEqualStrings("src/main.zig", inside);
+    const back = try originalPath(alloc, "/w", inside);
+    defer alloc.free(back);
+    try std.testing.expectEqualStrings("/w/src/main.zig", back);
+
+    const outside = try relativeBackupPath(alloc, "/w", "/etc/hosts");
+    defer alloc.free(outside);
+    try std.testing.expectEqualStrings("_abs/etc/hosts", outside);
+    const abs = try originalPath(alloc, "/w", outside);
+    defer alloc.free(abs);
+    try std.testing.expectEqualStrings("/etc/hosts", abs);
+}
+
+test "sessionName" {
+    var buf: [32]u8 = undefined;
+    try std.testing.expectEqualStrings("19700102-010203", sessionName(&buf, (86400 + 3723) * std.time.ns_per_s));
+}
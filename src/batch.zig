@@ -0,0 +1,239 @@
+//! `blitz batch "PROMPT" FILE...`: one request per file through the
+//! provider's batch API, for bulk jobs like "summarize every module" that
+//! can wait a few hours for half the price. Each request is the prompt with
+//! the file attached, no tools and no system prompt. File names come from
+//! the arguments or, when none are given, one per line on stdin:
+//!
+//!     git ls-files '*.zig' | blitz batch "Summarize this module"
+//!
+//! The job is kept in `.blitz/batches/<id>.json` and polled until it ends,
+//! `blitz batch resume <id>` picks an interrupted wait back up. Answers land
+//! in `.blitz/batches/<id>/`, one markdown file per input.
+const std = @import("std");
+const r = @import("root.zig");
+const App = r.app.App;
+const prv = r.prv;
+
+pub const DIR = r.util.BLITZ_DIR ++ "/batches";
+const MAX_FILE_BYTES = 256 * 1024;
+const MAX_LIST_BYTES = 1024 * 1024;
+/// published discount of both batch APIs
+const PRICE_FACTOR = 0.5;
+
+pub const Options = struct {
+    prompt: []const u8 = "",
+    files: []const []const u8 = &.{},
+    /// id of a submitted batch to wait for instead of a new one
+    resume_id: ?[]const u8 = null,
+    poll_s: u32 = 30,
+};
+
+/// What is kept on disk between submit and results.
+pub const Job = struct {
+    id: []const u8,
+    model: []const u8,
+    /// the input file of request `f<i>`
+    files: []const []const u8,
+};
+
+/// Read the file list piped into `blitz batch`.
+pub fn readFileList(io: std.Io, alloc: std.mem.Allocator) ![]const []const u8 {
+    var buf: [4096]u8 = undefined;
+    var stdin = std.Io.File.stdin().reader(io, &buf);
+    const text = try stdin.interface.allocRemaining(alloc, .limited(MAX_LIST_BYTES));
+    var files = std.ArrayList([]const u8).empty;
+    var lines = std.mem.tokenizeScalar(u8, text, '\n');
+    while (lines.next()) |line| {
+        const path = std.mem.trim(u8, line, " \t\r");
+        if (path.len > 0) try files.append(alloc, path);
+    }
+    return files.items;
+}
+
+/// Returns the exit status: 0 when every request got an answer.
+pub fn run(app: *App, opts: Options) !u8 {
+    const alloc = app.sessionAlloc();
+    const io = app.io;
+    const cfg = switch (app.context_factory.buildAgentApiConfig(.general, &app.config, app.swarm.exec.env)) {
+        .config => |c| c,
+        .diagnostic => |d| {
+            std.debug.print("no usable model config: {s}\n", .{@tagName(d)});
+            return 1;
+        },
+    };
+    if (!prv.batch.Client.supported(std.meta.activeTag(cfg.provider))) {
+        std.debug.print("{s} has no batch API, use blitz exec per file instead\n", .{@tagName(cfg.provider)});
+        return 1;
+    }
+    var client: prv.batch.Client = .{ .pool = &app.swarm.pool, .alloc = alloc, .cfg = cfg };
+    if (opts.resume_id == null) {
+        if (app.budgetRefusal()) |reason| {
+            std.debug.print("not submitting: {s}\n", .{reason});
+            return 1;
+        }
+    }
+
+    const found: ?Job = if (opts.resume_id) |id|
+        loadJob(io, alloc, id)
+    else
+        submit(io, alloc, &client, opts) catch |err| blk: {
+            std.debug.print("submitting the batch failed: {s}\n{s}\n", .{ @errorName(err), client.last_error });
+            break :blk null;
+        };
+    const job = found orelse {
+        if (opts.resume_id) |id| std.debug.print("no batch {s} in " ++ DIR ++ "\n", .{id});
+        return 1;
+    };
+
+    const poll = while (true) {
+        const p = client.poll(job.id) catch |err| {
+            std.debug.print("polling {s} failed: {s}\n{s}\n", .{ job.id, @errorName(err), client.last_error });
+            return 1;
+        };
+        std.debug.print("{s}: {d} done, {d} failed, {d} pending\n", .{ job.id, p.succeeded, p.failed, p.pending });
+        if (p.done) break p;
+        std.Io.sleep(io, std.Io.Duration.fromSeconds(opts.poll_s), .real) catch return 1;
+    };
+
+    const out_dir = try std.fmt.allocPrint(alloc, DIR ++ "/{s}", .{job.id});
+    try std.Io.Dir.cwd().createDirPath(io, out_dir);
+    var usage: prv.adapter.TokenUsage = .{};
+    var answered: usize = 0;
+    for (poll.results) |ref| {
+        const results = client.results(ref) catch |err| {
+            std.debug.print("fetching results failed: {s}\n{s}\n", .{ @errorName(err), client.last_error });
+            return 1;
+        };
+        for (results) |res| {
+            const file = inputOf(job, res.custom_id) orelse continue;
+            const response = res.response orelse {
+                std.debug.print("{s}: {s}\n", .{ file, res.err });
+                continue;
+            };
+            if (response.usage) |u| usage.add(u);
+            try writeAnswer(io, alloc, out_dir, file, response.message);
+            answered += 1;
+        }
+    }
+
+    var cost_buf: [32]u8 = undefined;
+    const cost = if (app.modelPrice(job.model)) |price|
+        r.cost.format(&cost_buf, r.cost.cost(price, usage) * PRICE_FACTOR)
+    else
+        "unknown";
+    std.debug.print("{d}/{d} answered into {s}, {d} in / {d} out tokens, {s} at batch pricing\n", .{
+        answered,
+        job.files.len,
+        out_dir,
+        usage.input_tokens,
+        usage.output_tokens,
+        cost,
+    });
+    return if (answered == job.files.len) 0 else 1;
+}
+
+fn submit(io: std.Io, alloc: std.mem.Allocator, client: *prv.batch.Client, opts: Options) !?Job {
+    if (opts.prompt.len == 0 or opts.files.len == 0) {
+        std.debug.print("usage: batch \"PROMPT\" FILE... (or file names on stdin)\n", .{});
+        return null;
+    }
+    var files = std.ArrayList([]const u8).empty;
+    var requests = std.ArrayList(prv.batch.Request).empty;
+    for (opts.files) |path| {
+        const content = readFile(io, alloc, path, MAX_FILE_BYTES) catch |err| {
+            std.debug.print("skipping {s}: {s}\n", .{ path, @errorName(err) });
+            continue;
+        };
+        const chat = try alloc.create(prv.adapter.Chat);
+        chat.* = .{};
+        const parts = try alloc.alloc(prv.adapter.ContentPart, 1);
+        parts[0] = .{ .text = try requestText(alloc, opts.prompt, path, content) };
+        try chat.addMessage(alloc, .user, parts);
+        try requests.append(alloc, .{ .custom_id = try customId(alloc, files.items.len), .chat = chat });
+        try files.append(alloc, path);
+    }
+    if (requests.items.len == 0) return null;
+
+    const job: Job = .{ .id = try client.submit(requests.items), .model = client.cfg.model, .files = files.items };
+    try saveJob(io, alloc, job);
+    std.debug.print("submitted {s} with {d} requests, `blitz batch resume {s}` if this wait is interrupted\n", .{ job.id, files.items.len, job.id });
+    return job;
+}
+
+fn readFile(io: std.Io, alloc: std.mem.Allocator, path: []const u8, limit: usize) ![]const u8 {
+    const file = try std.Io.Dir.cwd().openFile(io, path, .{});
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    return reader.interface.allocRemaining(alloc, .limited(limit));
+}
+
+pub fn requestText(alloc: std.mem.Allocator, prompt: []const u8, path: []const u8, content: []const u8) ![]const u8 {
+    return std.fmt.allocPrint(alloc, "{s}\n\n<file path=\"{s}\">\n{s}\n</file>", .{ prompt, path, content });
+}
+
+fn customId(alloc: std.mem.Allocator, index: usize) ![]const u8 {
+    return std.fmt.allocPrint(alloc, "f{d}", .{index});
+}
+
+pub fn inputOf(job: Job, custom_id: []const u8) ?[]const u8 {
+    if (custom_id.len < 2 or custom_id[0] != 'f') return null;
+    const index = std.fmt.parseInt(usize, custom_id[1..], 10) catch return null;
+    return if (index < job.files.len) job.files[index] else null;
+}
+
+/// `src/app.zig` is answered in `src__app.zig.md`.
+pub fn answerName(alloc: std.mem.Allocator, path: []const u8) ![]const u8 {
+    const trimmed = if (std.mem.startsWith(u8, path, "./")) path[2..] else path;
+    const name = try std.mem.replaceOwned(u8, alloc, trimmed, "/", "__");
+    return std.fmt.allocPrint(alloc, "{s}.md", .{name});
+}
+
+fn writeAnswer(io: std.Io, alloc: std.mem.Allocator, out_dir: []const u8, input: []const u8, message: prv.adapter.Message) !void {
+    const path = try std.fmt.allocPrint(alloc, "{s}/{s}", .{ out_dir, try answerName(alloc, input) });
+    const file = try std.Io.Dir.cwd().createFile(io, path, .{});
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    for (message.parts) |part| switch (part) {
+        .text => |text| try writer.interface.writeAll(text),
+        else => {},
+    };
+    try writer.interface.writeByte('\n');
+    try writer.interface.flush();
+}
+
+fn jobPath(alloc: std.mem.Allocator, id: []const u8) ![]const u8 {
+    return std.fmt.allocPrint(alloc, DIR ++ "/{s}.json", .{id});
+}
+
+fn saveJob(io: std.Io, alloc: std.mem.Allocator, job: Job) !void {
+    try std.Io.Dir.cwd().createDirPath(io, DIR);
+    const file = try std.Io.Dir.cwd().createFile(io, try jobPath(alloc, job.id), .{});
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    try std.json.Stringify.value(job, .{}, &writer.interface);
+    try writer.interface.flush();
+}
+
+fn loadJob(io: std.Io, alloc: std.mem.Allocator, id: []const u8) ?Job {
+    const path = jobPath(alloc, id) catch return null;
+    const content = readFile(io, alloc, path, MAX_LIST_BYTES) catch return null;
+    return std.json.parseFromSliceLeaky(Job, alloc, content, .{ .ignore_unknown_fields = true }) catch null;
+}
+
+test "batch inputs and answers" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const job: Job = .{ .id = "msgbatch_1", .model = "m", .files = &.{ "src/app.zig", "./README.md" } };
+    try std.testing.expectEqualStrings("./README.md", inputOf(job, try customId(alloc, 1)).?);
+    try std.testing.expect(inputOf(job, "f2") == null);
+    try std.testing.expect(inputOf(job, "x0") == null);
+
+    try std.testing.expectEqualStrings("src__app.zig.md", try answerName(alloc, "src/app.zig"));
+    try std.testing.expectEqualStrings("README.md.md", try answerName(alloc, "./README.md"));
+    try std.testing.expect(std.mem.endsWith(u8, try requestText(alloc, "Summarize", "a.zig", "const x = 1;"), "<file path=\"a.zig\">\nconst x = 1;\n</file>"));
+}
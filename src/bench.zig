@@ -0,0 +1,252 @@
+//! `blitz bench [SUITE] [TASK]`: run recorded tasks against the current
+//! prompts, tools and model and score them, so a prompt or tool change can
+//! be judged on more than one lucky session. A suite is a directory of
+//! tasks, `bench/` by default:
+//!
+//!   <task>/prompt.md   what the agent is asked
+//!   <task>/repo/       the snapshot it starts in
+//!   <task>/check.sh    run in the finished copy, exit 0 is a pass
+//!
+//! Every task runs `blitz exec --json --allow=all` in a scratch copy of its
+//! snapshot, with the config the user would get there. A run still going
+//! after `TASK_TIMEOUT_MS` is killed and counts as a fail. The report lists
+//! pass or fail, turns, tokens and cost per task and the totals. Copies of
+//! failed tasks are kept for a look at what went wrong.
+const std = @import("std");
+const r = @import("root.zig");
+
+pub const DEFAULT_SUITE = "bench";
+const PROMPT_FILE = "prompt.md";
+const REPO_DIR = "repo";
+const CHECK_FILE = "check.sh";
+const MAX_PROMPT_BYTES = 256 * 1024;
+/// one stuck task should not hang the whole suite
+const TASK_TIMEOUT_MS = 30 * std.time.ms_per_min;
+
+/// The `result` line `blitz exec --json` ends with.
+pub const RunStats = struct {
+    ok: bool = false,
+    input_tokens: u64 = 0,
+    output_tokens: u64 = 0,
+    cost: f64 = 0,
+    turns: u32 = 0,
+};
+
+pub const TaskResult = struct {
+    name: []const u8,
+    passed: bool,
+    stats: RunStats,
+    ms: i64,
+    /// why a task failed before its check ran, or where its copy was kept
+    note: []const u8 = "",
+};
+
+/// `gpa` backs the command pool the agent runs are started from, its
+/// workers may run on other threads.
+pub fn run(io: std.Io, alloc: std.mem.Allocator, gpa: std.mem.Allocator, env: *const std.process.Environ.Map, exe: []const u8, suite: []const u8, only: ?[]const u8, json: bool) !u8 {
+    var buf: [std.posix.PATH_MAX]u8 = undefined;
+    const len = std.Io.Dir.cwd().realPathFile(io, suite, &buf) catch {
+        std.debug.print("no bench suite at {s}\n", .{suite});
+        return 1;
+    };
+    const suite_path = try alloc.dupe(u8, buf[0..len]);
+
+    var names = std.ArrayList([]const u8).empty;
+    {
+        var dir = try std.Io.Dir.openDirAbsolute(io, suite_path, .{ .iterate = true });
+        defer dir.close(io);
+        var it = dir.iterate();
+        while (it.next(io) catch null) |entry| {
+            if (entry.kind != .directory) continue;
+            if (only) |name| if (!std.mem.eql(u8, name, entry.name)) continue;
+            try names.append(alloc, try alloc.dupe(u8, entry.name));
+        }
+    }
+    if (names.items.len == 0) {
+        std.debug.print("no tasks in {s}\n", .{suite_path});
+        return 1;
+    }
+    std.mem.sortUnstable([]const u8, names.items, {}, lessThan);
+
+    var pool = r.prv.exec.CmdPool.init(gpa, io, env);
+    defer pool.deinit();
+    var results = std.ArrayList(TaskResult).empty;
+    for (names.items) |name| {
+        std.debug.print("{s} ...\n", .{name});
+        const task = try std.fmt.allocPrint(alloc, "{s}/{s}", .{ suite_path, name });
+        try results.append(alloc, try runTask(io, alloc, &pool, exe, name, task));
+    }
+
+    var out_buf: [4096]u8 = undefined;
+    var stdout = std.Io.File.stdout().writer(io, &out_buf);
+    try report(&stdout.interface, results.items, json);
+    try stdout.interface.flush();
+    for (results.items) |res| if (!res.passed) return 1;
+    return 0;
+}
+
+fn lessThan(_: void, a: []const u8, b: []const u8) bool {
+    return std.mem.lessThan(u8, a, b);
+}
+
+fn runTask(io: std.Io, alloc: std.mem.Allocator, pool: *r.prv.exec.CmdPool, exe: []const u8, name: []const u8, task: []const u8) !TaskResult {
+    var result: TaskResult = .{ .name = name, .passed = false, .stats = .{}, .ms = 0 };
+    const prompt = readPrompt(io, alloc, task) orelse {
+        result.note = "missing " ++ PROMPT_FILE;
+        return result;
+    };
+
+    const mktemp = try std.process.run(alloc, io, .{ .argv = &.{ "mktemp", "-d", "-t", "blitz-bench.XXXXXX" } });
+    const work = std.mem.trim(u8, mktemp.stdout, " \r\n");
+    if (!exitedOk(mktemp.term) or work.len == 0) {
+        result.note = "mktemp failed";
+        return result;
+    }
+    const repo = try std.fmt.allocPrint(alloc, "{s}/" ++ REPO_DIR ++ "/.", .{task});
+    if (!exitedOk((try std.process.run(alloc, io, .{ .argv = &.{ "cp", "-R", repo, work } })).term)) {
+        result.note = "copying " ++ REPO_DIR ++ "/ failed";
+        return result;
+    }
+
+    const start = r.prv.http.nowMs(io);
+    // its own process group, so the tools the agent started die with it
+    const agent = try pool.runAndWaitTimeout(.{
+        .argv = &.{ exe, "exec", "--json", "--allow=all", prompt },
+        .cwd = work,
+        .force_local = true,
+        .kill_process_group = true,
+    }, TASK_TIMEOUT_MS);
+    defer pool.alloc.free(agent.stdout);
+    defer pool.alloc.free(agent.stderr);
+    result.ms = r.prv.http.nowMs(io) - start;
+    if (agent.ty == .timeout) {
+        result.note = try std.fmt.allocPrint(alloc, "timed out after {d}m, kept {s}", .{ TASK_TIMEOUT_MS / std.time.ms_per_min, work });
+        return result;
+    }
+    if (parseResult(alloc, agent.stdout)) |stats| {
+        result.stats = stats;
+    } else {
+        result.note = "no result line from exec";
+    }
+
+    const check = try std.fmt.allocPrint(alloc, "{s}/" ++ CHECK_FILE, .{task});
+    const checked = try std.process.run(alloc, io, .{ .argv = &.{ "sh", check }, .cwd = .{ .path = work } });
+    result.passed = exitedOk(checked.term);
+
+    if (result.passed) {
+        _ = std.process.run(alloc, io, .{ .argv = &.{ "rm", "-rf", work } }) catch {};
+    } else if (result.note.len == 0) {
+        result.note = try std.fmt.allocPrint(alloc, "kept {s}", .{work});
+    }
+    return result;
+}
+
+fn readPrompt(io: std.Io, alloc: std.mem.Allocator, task: []const u8) ?[]const u8 {
+    const path = std.fmt.allocPrint(alloc, "{s}/" ++ PROMPT_FILE, .{task}) catch return null;
+    const file = std.Io.Dir.openFileAbsolute(io, path, .{}) catch return null;
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    const text = reader.interface.allocRemaining(alloc, .limited(MAX_PROMPT_BYTES)) catch return null;
+    const prompt = std.mem.trim(u8, text, " \t\r\n");
+    return if (prompt.len == 0) null else prompt;
+}
+
+fn exitedOk(term: std.process.Child.Term) bool {
+    return switch (term) {
+        .exited => |code| code == 0,
+        else => false,
+    };
+}
+
+/// Stats from the last `result` line of `blitz exec --json` output, null
+/// when the run died before writing one.
+pub fn parseResult(alloc: std.mem.Allocator, output: []const u8) ?RunStats {
+    var lines = std.mem.splitBackwardsScalar(u8, output, '\n');
+    while (lines.next()) |raw| {
+        const line = std.mem.trim(u8, raw, " \r");
+        if (line.len == 0) continue;
+        const value = std.json.parseFromSliceLeaky(std.json.Value, alloc, line, .{}) catch continue;
+        if (value != .object) continue;
+        const kind = value.object.get("type") orelse continue;
+        if (kind != .string or !std.mem.eql(u8, kind.string, "result")) continue;
+        return std.json.parseFromValueLeaky(RunStats, alloc, value, .{ .ignore_unknown_fields = true }) catch null;
+    }
+    return null;
+}
+
+pub fn report(w: *std.Io.Writer, results: []const TaskResult, json: bool) !void {
+    var total: RunStats = .{};
+    var passed: usize = 0;
+    for (results) |res| {
+        passed += @intFromBool(res.passed);
+        total.turns += res.stats.turns;
+        total.input_tokens += res.stats.input_tokens;
+        total.output_tokens += res.stats.output_tokens;
+        total.cost += res.stats.cost;
+    }
+    const rate = @as(f64, @floatFromInt(passed)) / @as(f64, @floatFromInt(@max(results.len, 1)));
+
+    if (json) {
+        try std.json.Stringify.value(.{
+            .tasks = results,
+            .passed = passed,
+            .success_rate = rate,
+            .turns = total.turns,
+            .input_tokens = total.input_tokens,
+            .output_tokens = total.output_tokens,
+            .cost = total.cost,
+        }, .{}, w);
+        try w.writeByte('\n');
+        return;
+    }
+
+    try w.print("{s: <24} {s: <6} {s: >5} {s: >9} {s: >9} {s: >8} {s: >7}\n", .{ "task", "result", "turns", "in", "out", "cost", "time" });
+    for (results) |res| {
+        try w.print("{s: <24} {s: <6} {d: >5} {d: >9} {d: >9} {d: >8.4} {d: >6}s", .{
+            res.name,
+            if (res.passed) "pass" else "FAIL",
+            res.stats.turns,
+            res.stats.input_tokens,
+            res.stats.output_tokens,
+            res.stats.cost,
+            @divTrunc(res.ms, std.time.ms_per_s),
+        });
+        if (res.note.len > 0) try w.print("  {s}", .{res.note});
+        try w.writeByte('\n');
+    }
+    try w.print("\n{d}/{d} passed ({d:.0}%), {d} turns, {d} in / {d} out tokens, ${d:.4}\n", .{
+        passed,
+        results.len,
+        rate * 100,
+        total.turns,
+        total.input_tokens,
+        total.output_tokens,
+        total.cost,
+    });
+}
+
+test "bench result parsing and report" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const output =
+        \\{"type":"message","text":"done"}
+        \\{"type":"result","ok":true,"input_tokens":1200,"output_tokens":300,"cost":0.01,"turns":4}
+        \\
+    ;
+    const stats = parseResult(alloc, output).?;
+    try std.testing.expect(stats.ok);
+    try std.testing.expectEqual(@as(u32, 4), stats.turns);
+    try std.testing.expectEqual(@as(u64, 1200), stats.input_tokens);
+    try std.testing.expect(parseResult(alloc, "{\"type\":\"error\",\"text\":\"boom\"}\n") == null);
+
+    var out = std.Io.Writer.Allocating.init(alloc);
+    try report(&out.writer, &.{
+        .{ .name = "fix-typo", .passed = true, .stats = stats, .ms = 2000 },
+        .{ .name = "add-flag", .passed = false, .stats = .{ .turns = 2 }, .ms = 1000, .note = "kept /tmp/x" },
+    }, false);
+    try std.testing.expect(std.mem.indexOf(u8, out.written(), "1/2 passed (50%), 6 turns") != null);
+    try std.testing.expect(std.mem.indexOf(u8, out.written(), "kept /tmp/x") != null);
+}
@@ -22,6 +22,21 @@ blitz.set_compact_edge(200000)
 -- 	key_envar = "OPENAI_API_KEY",
 -- })
 -- blitz.set_model("gpt-5.4-mini", provider)
+--
+-- Endpoints without streaming support take `stream = false`, answers then
+-- show up once they are complete.
+--
+-- `@model:mini <prompt>` sends one message to another model and switches back
+-- afterwards. Aliases are optional, `@model:gpt-5.4` works on the default provider.
+-- blitz.add_model_alias("mini", "gpt-5.4-mini", provider)
+
+-- Title screen and throbber. title = "" hides the art, banner shows the
+-- project name and git branch.
+-- blitz.set_appearance({ spinner = "dots", banner = true })
+
+-- A run that ends with open todos gets a follow-up, at most `max` times in a
+-- row before it waits for you. max = 0 turns it off.
+-- blitz.set_todo_nudge({ text = "Finish the open todos or update the list.", max = 3 })
 
 -- Add custom bindings, using vim style keybind strings
 blitz.bind("<C-s>", function()
@@ -46,6 +61,10 @@ blitz.add_command(":plan", function(rem)
 	})
 end)
 
+-- Files the agent may only read after you confirm ("ask") or never ("block").
+-- Ripgrep matches inside these files are redacted.
+-- blitz.set_sensitive_paths({ ".env", ".env.*", "secrets/**", "~/.ssh/*" }, "ask")
+
 --- Skills: just create a `skills` dir in this at this CWD. Put your markdown skills there
 
 --- MCP support
@@ -118,6 +137,7 @@ blitz.set_agent_tools(blitz.AGENT_GENERAL, {
 	blitz.tools.AWAIT_AGENT,
 	blitz.tools.CANCEL_AGENT,
 	blitz.tools.SEND_MESSAGE_TO_AGENT,
+	blitz.tools.TASK,
 	blitz.tools.RIPGREP,
 	blitz.tools.LOADSKILL,
 	blitz.tools.START_LSP,
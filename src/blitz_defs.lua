@@ -79,6 +79,8 @@
 ---@field WRITE string
 ---@field EDIT string
 ---@field PATCH string
+---@field APPLY_PATCH string
+---@field DRAFT_EDIT string
 ---@field AGENT string
 ---@field LIST_TODOS string
 ---@field UPDATE_TODO_STATE string
@@ -89,10 +91,16 @@
 ---@field SEND_MESSAGE_TO_AGENT string
 ---@field AWAIT_AGENT string
 ---@field CANCEL_AGENT string
+---@field TASK string
 ---@field RIPGREP string
 ---@field LOADSKILL string
 ---@field START_MCP string
 ---@field START_LSP string
+---@field GIT_COMMIT string
+---@field WHO_OWNS string
+---@field WEB_SEARCH string
+---@field MEMORY string
+---@field EVAL string
 ---@field LSP string
 
 ---@class BlitzEventDef
@@ -128,6 +136,8 @@
 ---@field USER_MESSAGE_SENT integer
 ---Emitted after MCP tools are reloaded.
 ---@field MCP_TOOLS_RELOADED integer
+---Emitted when the session token usage changes. Receives the session totals.
+---@field USAGE_UPDATED integer
 ---Bind an event listener.
 ---Example: blitz.events.add_listener(blitz.events.MODE_CHANGED, function(new_mode_id) end)
 ---@field add_listener fun(event: integer, func: function)
@@ -161,19 +171,24 @@
 ---@field description string
 ---@field schema? string
 ---@field args? table<string, BlitzArgDef>
----@field func fun(ctx: BlitzCtx, call: BlitzCall): BlitzStatus
+---@field func? fun(ctx: BlitzCtx, call: BlitzCall): BlitzStatus
+---@field command? string
 
 ---@class BlitzThinking
 ---@field type string
 ---@field budget_tokens? integer
 
 ---@class BlitzProviderDef
----'openai' | 'response' | 'anthropic' | 'ollama'
+---'openai' | 'response' | 'anthropic' | 'ollama' | 'openrouter'
 ---@field type string
----the endpoint url
----@field url string
----the ENVAR holding the api key (not the key itself!)
----@field key_envar string
+---the endpoint url, optional for openrouter
+---@field url? string
+---the ENVAR holding the api key (not the key itself!), none for local servers
+---@field key_envar? string
+---profile name for blitz.use_provider and :providers
+---@field name? string
+---model a switch to this profile starts on, also the default model while none is set
+---@field model? string
 ---@field effort? string
 ---@field temperature? number
 ---@field max_tokens? integer
@@ -185,6 +200,10 @@
 ---@field presence_penalty? number
 ---@field enable_thinking? boolean
 ---@field thinking? BlitzThinking
+---ollama only: seconds the model stays loaded, -1 forever
+---@field keep_alive? integer
+---false for endpoints without streaming, answers then show up once complete
+---@field stream? boolean
 
 ---@class BlitzAgentDef
 ---@field name string
@@ -214,6 +233,24 @@
 ---@field debug_log? boolean
 ---@field ssh_agent_control? boolean
 ---@field skip_permissions? boolean
+---@field replay_approvals? boolean
+---@field image_preview? boolean
+
+---@class BlitzRetryPolicy
+---@field max_retries? integer
+---@field base_delay_ms? integer
+---@field max_delay_ms? integer
+---@field jitter? number
+
+---@class BlitzWebSearch
+---@field backend? string
+---@field url? string
+---@field brave_key_env? string
+
+---@class BlitzHookDef
+---@field event string
+---@field command string
+---@field min_seconds? integer
 
 ---@class BlitzTheme
 ---@field bg? string
@@ -229,6 +266,20 @@
 ---@field diff_add? string
 ---@field diff_remove? string
 
+---@class BlitzAppearance
+---title screen art, "" hides it
+---@field title? string
+---'dots' | 'bar' | 'wave' | 'line' | 'none'
+---@field spinner? string
+---project name and git branch on the title screen
+---@field banner? boolean
+
+---@class BlitzTodoNudge
+---message sent when a run ends with open todos
+---@field text? string
+---nudges in a row before waiting for the user, 0 disables
+---@field max? integer
+
 ---@class Blitz
 ---@field mcp BlitzMcp
 ---@field lsp BlitzLsp
@@ -241,7 +292,10 @@
 ---@field RET_ERR integer
 ---@field RET_EXIT_LOOP integer
 ---@field AGENT_GENERAL integer
+---@field AGENT_TASK integer
+---@field AGENT_DRAFT integer
 ---@field MODE_EXEC integer
+---@field MODE_RESEARCH integer
 ---@field REQ_STATUS_PENDING integer
 ---@field REQ_STATUS_APPROVED integer
 ---@field REQ_STATUS_DENIED integer
@@ -251,7 +305,7 @@
 ---@field AWAIT_FAILED integer
 ---@field AWAIT_CANCELED integer
 ---@field AWAIT_INVALID integer
----Register a tool.
+---Register a tool. Either `func` runs it in Lua or `command` runs a shell command, `{arg}` filled with the quoted argument.
 ---@field register_tool fun(def: ToolDef): string
 ---Add a single tool from the tool pool to an agent type's tool set.
 ---@field add_tool fun(agent_type: integer, tool_name: string)
@@ -269,6 +323,10 @@
 ---@field add_agent fun(def: BlitzAgentDef): integer
 ---Set the default model.
 ---@field set_model fun(model: string, handle: integer)
+---Switch the default model to the provider registered under `name`, on `model` or else the provider's own.
+---@field use_provider fun(name: string, model?: string)
+---Name a model for `@model:<alias>` prompt hints, which route a single message to it.
+---@field add_model_alias fun(alias: string, model: string, handle: integer)
 ---Set the model config for a specific agent.
 ---@field set_model_agent fun(agent_type: integer, model: string, effort: string, handle: integer)
 ---Return token usage currently shown by the statusbar.
@@ -277,8 +335,23 @@
 ---@field token_usage_by_model fun(): BlitzModelTokenUsage[]
 ---Return main-agent context fill percentage currently shown by the statusbar.
 ---@field context_percent fun(): number
+---Return the session's cost in dollars from the LiteLLM price list, nil while no model is priced.
+---@field session_cost fun(): number|nil
+---Price a model in dollars per million tokens, wins over the LiteLLM list. Cache rates default to the input rate.
+---Example: blitz.set_model_price("qwen3-coder", 0.2, 0.8)
+---@field set_model_price fun(model: string, input: number, output: number, cache_read?: number, cache_write?: number)
+---Ask before sending a prompt whose input is estimated above this many dollars (default 0.5). 0 never asks.
+---@field set_cost_confirm fun(dollars: number)
+---Cap the dollars spent per day (UTC) over every running blitzdenk instance. Warns at 80%, refuses new turns at the cap. 0 turns it off.
+---@field set_daily_budget fun(dollars: number)
+---What links in a new prompt do: "ask" (default) offers to fetch them, "auto" fetches them, "off" leaves them as text. Fetched pages go along as context.
+---@field set_link_context fun(mode: string)
 ---Set the default context edge, in tokens, used for statusbar percentage and auto-compaction.
 ---@field set_compact_edge fun(tokens: integer)
+---Start auto-compaction at this percentage of the context edge (default 90).
+---@field set_compact_threshold fun(percent: integer)
+---Write compaction summaries with this (cheaper) model on the agent's provider. Empty string uses the agent's model.
+---@field set_compact_model fun(model: string)
 ---Bind a vim-style key combo to a Lua callback.
 ---Examples: "<C-c>", "<M-S-a>", "<Esc>", "<Up>", "<F1>", "a"
 ---@field bind fun(key: string, func: function)
@@ -290,8 +363,52 @@
 ---Override the tool set for a given agent type. Replaces defaults entirely.
 ---Names must match built-in tool names or names of tools registered via blitz.register_tool.
 ---@field set_agent_tools fun(agent_type: integer, tool_names: string[])
+---Replace the sensitive path globs guarded in read and ripgrep (default: .env, .env.*, secrets/**, ~/.ssh/*).
+---policy is "ask" (default, user confirms each read) or "block".
+---@field set_sensitive_paths fun(patterns: string[], policy?: string)
+---Keep writes of write, edit, patch and bash inside the project root (on by default).
+---allow lists paths outside that stay writable, `~/` and relative entries work. Example: blitz.set_sandbox(true, { "/tmp" })
+---@field set_sandbox fun(enabled: boolean, allow?: string[])
+---Glob rules for the bash tool, checked per command segment. allow skips the prompt, deny refuses without asking.
+---Every decision is noted in the chat. Example: blitz.set_bash_rules({ "cargo check*", "git status" }, { "git push --force*" })
+---@field set_bash_rules fun(allow: string[], deny?: string[])
+---Backend of the web_search tool: "searxng" with the instance url, "brave" with the API key in brave_key_env
+---(default BRAVE_API_KEY) or "duckduckgo". "auto" (default) takes the first one configured, DuckDuckGo needs no setup.
+---Example: blitz.set_web_search({ backend = "searxng", url = "https://search.example.org" })
+---@field set_web_search fun(config: BlitzWebSearch)
+---Encrypt stored sessions at rest with a key kept in the OS keychain (secret-tool or security), created on first use.
+---passphrase_env names an env var whose passphrase derives the key instead. `:sessions encrypt` seals existing files.
+---Spill files, the --log event log and prompt_history.json stay plaintext.
+---@field set_session_encryption fun(enabled: boolean, passphrase_env?: string)
+---Send only the first sentence of each tool description to models matching these globs (e.g. "claude-*").
+---Saves prompt tokens per request, the token heatmap shows the difference.
+---@field set_minimal_schemas fun(models: string[])
+---Retry transient provider errors (rate limits, overload, 5xx, dropped connections) with exponential backoff.
+---Missing fields keep their defaults: max_retries 3, base_delay_ms 1000, max_delay_ms 30000, jitter 0.25.
+---@field set_retry_policy fun(policy: BlitzRetryPolicy)
+---Models on the same provider that take over, in order, once a request keeps failing. The agent stays on the fallback until its run ends.
+---Example: blitz.set_fallback_models({ "claude-sonnet-4-5", "claude-haiku-4-5" })
+---@field set_fallback_models fun(models: string[])
+---Run a shell command on an event: "turn_finished", "error" or "permission_requested". {message}, {model}, {cost}, {seconds} and {event} expand shell quoted.
+---min_seconds skips turn_finished, error and post_run for shorter runs. Commands run in the background.
+---Policy events "pre_tool", "post_tool", "pre_run" and "on_permission" pass the action as JSON on stdin and wait for the command: a non-zero exit vetoes with stdout as the reason, output of a zero exit is attached as a note. "post_run" gets the finished run on stdin.
+---Example: blitz.add_hook({ event = "turn_finished", command = "notify-send blitz {message}", min_seconds = 30 })
+---@field add_hook fun(hook: BlitzHookDef)
+---Enable or disable mouse capture (default: enabled). Disabled keeps native terminal selection and copy, scrolling is keyboard only.
+---@field set_mouse_capture fun(enabled: boolean)
 ---Override the system prompt for a given agent type.
 ---@field set_prompt fun(agent_type: integer, prompt: string)
+---Set the project's test command, replaces the one detected from manifests.
+---Example: blitz.set_test_command("zig build test --summary failures")
+---@field set_test_command fun(command: string)
+---Put the last `count` shell commands into the system prompt of new sessions, 0 turns it off (default).
+---Reads `.blitz/shell_history` in the project if present, else the zsh, bash or fish history.
+---@field set_shell_history fun(count: integer)
+---The project's test command, configured or detected. Empty when unknown.
+---@field test_command fun(): string
+---Fill `{{name}}` in agent prompts, AGENTS.md files, mode prompts and skills with `value`.
+---Example: blitz.set_prompt_var("service_name", "billing")
+---@field set_prompt_var fun(name: string, value: string)
 ---Override the mode reminder prompt (full variant).
 ---@field set_mode_prompt fun(mode: integer, prompt: string)
 ---Override the sparse mode reminder prompt (subsequent turns).
@@ -306,10 +423,16 @@
 ---@field get_flags fun(): BlitzAppFlags
 ---Set the app flags from a table. Missing fields are set to their default values.
 ---@field set_flags fun(flags: BlitzAppFlags)
+---Set one app flag by name, the others keep their value.
+---@field set_flag fun(name: string, value: boolean)
 ---Return the current theme as a table of hex color strings.
 ---@field get_theme fun(): BlitzTheme
 ---Set the theme from a table of hex color strings. Missing fields keep their current value.
 ---@field set_theme fun(theme: BlitzTheme)
+---Customize the title art, throbber style and startup banner. Missing fields keep their current value.
+---@field set_appearance fun(appearance: BlitzAppearance)
+---Configure the follow-up sent when a run ends with open todos. Missing fields keep their current value.
+---@field set_todo_nudge fun(nudge: BlitzTodoNudge)
 ---Write a debug log line.
 ---@field log fun(msg: string)
 ---Execute a shell command.
@@ -0,0 +1,122 @@
+//! Undo for agent edits. Every edit, write and patch snapshots the file
+//! before it changes it, numbered per session:
+//! `~/.cache/blitzdenk/checkpoints/<session>/<id>`. A file that did not
+//! exist yet gets an `<id>.new` marker instead, undoing it deletes the file
+//! again. Without either the snapshot failed and undo leaves the file alone.
+//!
+//! Snapshots and restores run as shell scripts through the command pool, so
+//! they work the same over SSH (the cache then lives on the remote host).
+const std = @import("std");
+const backup = @import("backup.zig");
+
+pub const CACHE_DIR = ".cache/blitzdenk/checkpoints";
+pub const MAX_CHECKPOINTS = 512;
+/// stderr of a restore that had nothing to restore from
+pub const MISSING_SNAPSHOT = "missing snapshot";
+
+pub const Checkpoint = struct {
+    id: u32,
+    path: []const u8,
+    tool: []const u8,
+    /// user prompt the edit belongs to
+    turn: u32,
+    timestamp: i96,
+};
+
+pub const Checkpoints = struct {
+    items: std.ArrayList(Checkpoint) = .empty,
+    next_id: u32 = 0,
+
+    /// Record a checkpoint for `path`, null once the session is full.
+    pub fn add(self: *Checkpoints, alloc: std.mem.Allocator, path: []const u8, tool: []const u8, turn: u32, timestamp: i96) !?Checkpoint {
+        if (self.items.items.len >= MAX_CHECKPOINTS) return null;
+        const cp = Checkpoint{
+            .id = self.next_id,
+            .path = try alloc.dupe(u8, path),
+            .tool = try alloc.dupe(u8, tool),
+            .turn = turn,
+            .timestamp = timestamp,
+        };
+        try self.items.append(alloc, cp);
+        self.next_id += 1;
+        return cp;
+    }
+
+    /// Index of the first checkpoint of the newest turn.
+    pub fn lastTurnStart(self: *const Checkpoints) usize {
+        const items = self.items.items;
+        if (items.len == 0) return 0;
+        const turn = items[items.len - 1].turn;
+        var i = items.len;
+        while (i > 0 and items[i - 1].turn == turn) i -= 1;
+        return i;
+    }
+
+    pub fn deinit(self: *Checkpoints, alloc: std.mem.Allocator) void {
+        for (self.items.items) |cp| {
+            alloc.free(cp.path);
+            alloc.free(cp.tool);
+        }
+        self.items.deinit(alloc);
+    }
+};
+
+/// Shell snippet storing the current content of `path` as checkpoint `id`.
+pub fn snapshotScript(alloc: std.mem.Allocator, session: []const u8, id: u32, path: []const u8) ![]const u8 {
+    const dir = try std.fmt.allocPrint(alloc, CACHE_DIR ++ "/{s}", .{session});
+    defer alloc.free(dir);
+    const q_dir = try backup.shellQuote(alloc, dir);
+    defer alloc.free(q_dir);
+    const q_path = try backup.shellQuote(alloc, path);
+    defer alloc.free(q_path);
+
+    return std.fmt.allocPrint(
+        alloc,
+        "d=\"$HOME\"/{s}; mkdir -p \"$d\" && rm -f \"$d\"/{d} \"$d\"/{d}.new; [ -e {s} ] || {{ : > \"$d\"/{d}.new; exit; }}; cp -p {s} \"$d\"/{d}",
+        .{ q_dir, id, id, q_path, id, q_path, id },
+    );
+}
+
+/// Shell snippet putting checkpoint `id` back, or deleting `path` when it
+/// did not exist at the time. Fails with `MISSING_SNAPSHOT` on stderr when
+/// neither was recorded.
+pub fn restoreScript(alloc: std.mem.Allocator, session: []const u8, id: u32, path: []const u8) ![]const u8 {
+    const dir = try std.fmt.allocPrint(alloc, CACHE_DIR ++ "/{s}", .{session});
+    defer alloc.free(dir);
+    const q_dir = try backup.shellQuote(alloc, dir);
+    defer alloc.free(q_dir);
+    const q_path = try backup.shellQuote(alloc, path);
+    defer alloc.free(q_path);
+
+    return std.fmt.allocPrint(
+        alloc,
+        "f=\"$HOME\"/{s}/{d}; if [ -f \"$f\" ]; then cp -p \"$f\" {s}; elif [ -f \"$f\".new ]; then rm -f {s}; else echo " ++ MISSING_SNAPSHOT ++ " >&2; exit 1; fi",
+        .{ q_dir, id, q_path, q_path },
+    );
+}
+
+test "checkpoint turns and scripts" {
+    const alloc = std.testing.allocator;
+    var cps: Checkpoints = .{};
+    defer cps.deinit(alloc);
+
+    try std.testing.expectEqual(@as(usize, 0), cps.lastTurnStart());
+    _ = try cps.add(alloc, "/w/a.zig", "edit", 1, 0);
+    _ = try cps.add(alloc, "/w/b.zig", "write", 2, 0);
+    const last = (try cps.add(alloc, "/w/a.zig", "edit", 2, 0)).?;
+    try std.testing.expectEqual(@as(u32, 2), last.id);
+    try std.testing.expectEqual(@as(usize, 1), cps.lastTurnStart());
+
+    const snap = try snapshotScript(alloc, "20260101-120000", 2, "/w/it's.zig");
+    defer alloc.free(snap);
+    try std.testing.expectEqualStrings(
+        "d=\"$HOME\"/'.cache/blitzdenk/checkpoints/20260101-120000'; mkdir -p \"$d\" && rm -f \"$d\"/2 \"$d\"/2.new; [ -e '/w/it'\\''s.zig' ] || { : > \"$d\"/2.new; exit; }; cp -p '/w/it'\\''s.zig' \"$d\"/2",
+        snap,
+    );
+    const restore = try restoreScript(alloc, "s", 0, "/w/a.zig");
+    defer alloc.free(restore);
+    try std.testing.expectEqualStrings(
+        "f=\"$HOME\"/'.cache/blitzdenk/checkpoints/s'/0; if [ -f \"$f\" ]; then cp -p \"$f\" '/w/a.zig'; elif [ -f \"$f\".new ]; then rm -f '/w/a.zig'; else echo missing snapshot >&2; exit 1; fi",
+        restore,
+    );
+}
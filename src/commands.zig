@@ -94,6 +94,14 @@ pub const Command = union(enum) {
         agent_type: u8 = @intFromEnum(r.ContextFactory.AgentType.general),
         fork: bool = false,
         chat_entry: ?ChatEntry = null,
+        /// answer the prompt without calling tools
+        tools_disabled: bool = false,
+        /// seeded into the todo list, e.g. from a scaffolding template
+        todos: []const r.prv.agent.Todo = &.{},
+        /// `@model:` name for the first turn
+        model_hint: ?[]const u8 = null,
+        /// input + output tokens before tool calls get refused, 0 is unlimited
+        token_budget: u64 = 0,
     };
 
     pub const CustomCmd = struct {
@@ -116,12 +124,16 @@ pub const Command = union(enum) {
     pub fn execute(self: *Self, app: *App) !void {
         const alloc = app.sessionAlloc();
         switch (self.*) {
-            .reset_session => app.reset(),
+            .reset_session => {
+                r.session.archiveAndReset(app);
+            },
             .cancel => {
                 if (app.main_agent_id) |id| {
                     app.event_bus.emit(app, .{ .agent_cancelled = .{ .id = id } }) catch {};
                 }
                 app.cancelPermissions();
+                app.cancelToolRetry();
+                app.hooks.dropChecks(app);
                 app.swarm.cancelAll();
                 app.dropStreamingPreview();
 
@@ -130,7 +142,7 @@ pub const Command = union(enum) {
                     app.streaming_entry = null;
                 }
 
-                app.running = false;
+                app.run_state = .idle;
                 app.auto_scroll = true;
             },
             .set_mode => |m| {
@@ -142,12 +154,14 @@ pub const Command = union(enum) {
                     const agent = app.swarm.getAgent(id).?;
                     agent.mode_idx = m;
                     agent.flags.force_full_reminder = true;
+                    // research mode narrows the toolset, going back restores it
+                    try app.context_factory.refreshAgentTools(agent);
                 }
             },
             .retry => {
                 if (app.main_agent_id) |id| {
                     app.swarm.retryAgent(id);
-                    app.running = true;
+                    app.run_state = .running;
                     app.auto_scroll = true;
                     app.scroll_offset = 0;
                 }
@@ -165,15 +179,25 @@ pub const Command = union(enum) {
                 if (app.scroll_offset == 0) app.auto_scroll = true;
             },
             .queue_agent_message => |arg| {
+                if (app.run_state == .cancelling) {
+                    try app.notifications.append(app.arena_app.allocator(), "cancelling, message not sent", .{});
+                    return;
+                }
+                // a double submit must not run the same prompt twice
+                if (arg.parts.len > 0 and arg.parts[0] == .text and app.queued.containsText(arg.agent_id, arg.parts[0].text)) {
+                    try app.notifications.append(app.arena_app.allocator(), "message is already queued", .{});
+                    return;
+                }
+
                 const parts = try r.util.deepClone(@TypeOf(arg.parts), arg.parts, alloc);
                 const chat_entry = if (arg.chat_entry) |en| try r.util.deepClone(ChatEntry, en, alloc) else null;
                 try app.queued.push(alloc, arg.agent_id, chat_entry, parts);
 
-                app.running = true;
+                const state = app.swarm.getSlotState(arg.agent_id);
+                app.run_state = if (state == .active) .queued else .running;
                 app.auto_scroll = true;
                 app.scroll_offset = 0;
 
-                const state = app.swarm.getSlotState(arg.agent_id);
                 if (state != .active) {
                     try app.swarm.runAgent(arg.agent_id);
                 }
@@ -183,7 +207,7 @@ pub const Command = union(enum) {
                     const ag = app.swarm.getAgent(id).?;
                     try app.event_bus.emit(app, .{ .agent_complete = id });
                     ag.requestCompaction();
-                    app.running = true;
+                    app.run_state = .running;
                     app.auto_scroll = true;
                 }
             },
@@ -202,6 +226,8 @@ pub const Command = union(enum) {
                 try app.reloadLspTools();
             },
             .spawn_agent => |arg| {
+                // reservation dropped while queued, e.g. the parent got canceled
+                if (app.swarm.getSlotState(arg.agent_id) != .reserved) return;
                 if (!arg.fork) {
                     switch (app.context_factory.buildAgentApiConfig(
                         @enumFromInt(arg.agent_type),
@@ -213,10 +239,10 @@ pub const Command = union(enum) {
                             app.swarm.releaseReservation(arg.agent_id);
                             if (arg.chat_entry) |en| {
                                 const entry = try r.util.deepClone(ChatEntry, en, alloc);
-                                try app.chat_entries.append(alloc, entry);
+                                try app.pushEntry(alloc, entry);
                             }
                             showProviderOnboarding(app, diagnostic);
-                            app.running = app.swarm.countActive() > 0;
+                            app.run_state = if (app.swarm.countActive() > 0) .running else .idle;
                             app.auto_scroll = true;
                             app.scroll_offset = 0;
                             app.dirty = true;
@@ -248,6 +274,20 @@ pub const Command = union(enum) {
                 if (app.context_factory.agents.get(@enumFromInt(arg.agent_type))) |meta| {
                     agent.max_allowed_tool_calls = meta.default_tool_call_budget;
                 }
+                agent.chat.tools_disabled = arg.tools_disabled;
+                agent.max_total_tokens = arg.token_budget;
+                if (arg.model_hint) |name| _ = app.useModelForTurn(arg.agent_id, name);
+                if (arg.todos.len > 0) {
+                    const agent_alloc = agent.arena.allocator();
+                    const g = agent.todo_list.lock(app.io);
+                    defer g.unlock();
+                    for (arg.todos) |todo| {
+                        _ = g.ptr.apply(.{ .add = .{
+                            .subject = try agent_alloc.dupe(u8, todo.subject),
+                            .description = try agent_alloc.dupe(u8, todo.description),
+                        } }, .user) catch break;
+                    }
+                }
 
                 try app.event_bus.emit(app, .{
                     .agent_created = .{ .id = arg.agent_id, .type_idx = agent.type_idx, .depth = agent.depth },
@@ -264,13 +304,14 @@ pub const Command = union(enum) {
 
                 if (arg.chat_entry) |en| {
                     const entry = try r.util.deepClone(ChatEntry, en, alloc);
-                    try app.chat_entries.append(alloc, entry);
+                    try app.pushEntry(alloc, entry);
                 }
 
                 const prompt = try r.util.deepClone(@TypeOf(arg.prompt), arg.prompt, alloc);
                 try app.swarm.runAgentWithMsg(arg.agent_id, prompt);
                 try app.event_bus.emit(app, .{ .agent_started = arg.agent_id });
-                app.running = true;
+                if (app.run_state != .queued) app.run_state = .running;
+                if (arg.parent_id == null) r.session.writeAutosave(app);
             },
             .push_notification => |msg| {
                 try app.notifications.append(app.arena_app.allocator(), "{s}", .{msg});
@@ -278,7 +319,7 @@ pub const Command = union(enum) {
             },
             .push_chat_entry => |en| {
                 const entry = try r.util.deepClone(ChatEntry, en, alloc);
-                try app.chat_entries.append(alloc, entry);
+                try app.pushEntry(alloc, entry);
             },
             .custom => |arg| {
                 try arg.func(arg.ptr, app);
@@ -305,13 +346,13 @@ pub const Command = union(enum) {
                 };
             },
             .attach_screenshot => |arg| {
-                _ = arg.media_type;
                 if (arg.data.len == 0) return;
 
                 const encoded_len = std.base64.standard.Encoder.calcSize(arg.data.len);
                 const encoded = try alloc.alloc(u8, encoded_len);
                 _ = std.base64.standard.Encoder.encode(encoded, arg.data);
                 app.screenshot_buf = encoded;
+                app.screenshot_media_type = try alloc.dupe(u8, arg.media_type);
                 app.dirty = true;
             },
             .add_tool => |arg| {
@@ -347,21 +388,21 @@ fn showProviderOnboarding(app: *App, diagnostic: r.ContextFactory.AgentConfigDia
                 "No default model/provider is configured. Edit {s} and choose a provider URL, model, and API-key environment variable.\n\n{s}",
                 .{ config_path, example },
             );
-            app.notifications.append(app.appAlloc(), "Configure a default provider/model in {s}", .{config_path}) catch {};
+            app.notifications.push(app.appAlloc(), .warn, "Configure a default provider/model in {s}", .{config_path}) catch {};
         },
         .invalid_provider => {
             app.pushSystemMessage(
                 "The configured provider is invalid or inactive. Check its handle and model binding in {s}.\n\n{s}",
                 .{ config_path, example },
             );
-            app.notifications.append(app.appAlloc(), "Configured provider is invalid or inactive", .{}) catch {};
+            app.notifications.push(app.appAlloc(), .warn, "Configured provider is invalid or inactive", .{}) catch {};
         },
         .missing_api_key => |name| {
             app.pushSystemMessage(
                 "Provider configuration is missing the required environment variable `{s}`. Set it in the environment that launches Blitzdenk. Configuration lives at {s}.\n\n{s}",
                 .{ name, config_path, example },
             );
-            app.notifications.append(app.appAlloc(), "Missing required environment variable: {s}", .{name}) catch {};
+            app.notifications.push(app.appAlloc(), .warn, "Missing required environment variable: {s}", .{name}) catch {};
         },
     }
 }
@@ -444,7 +485,7 @@ test "handled spawn configuration failure keeps processing queued commands" {
     try std.testing.expect(later_command_ran);
     try std.testing.expectEqual(@as(?r.prv.Swarm.AgentId, null), app.main_agent_id);
     try std.testing.expectEqual(@as(?r.prv.Swarm.SlotState, null), swarm.getSlotState(id));
-    try std.testing.expect(!app.running);
+    try std.testing.expectEqual(r.app.RunState.idle, app.run_state);
     try std.testing.expectEqual(@as(usize, 2), app.chat_entries.items.len);
     try std.testing.expectEqual(r.prv.adapter.Role.user, app.chat_entries.items[0].role);
     try std.testing.expectEqual(r.prv.adapter.Role.system, app.chat_entries.items[1].role);
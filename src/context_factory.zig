@@ -19,10 +19,12 @@ pub const general_default_tool_set = .{
     r.tools.agent.SendMessageToAgent,
     r.tools.agent.AwaitAgent,
     r.tools.agent.CancelAgent,
+    r.tools.task.TaskTool,
     r.tools.todos.ListTodosTool,
     r.tools.todos.UpdateTodoStateTool,
     r.tools.todos.CreateTodoTool,
     r.tools.patch.PatchTool,
+    r.tools.patch.ApplyPatchTool,
     r.tools.ask.AskTool,
     r.tools.ssh.EnterSshMode,
     r.tools.ssh.ExitSshMode,
@@ -30,6 +32,12 @@ pub const general_default_tool_set = .{
     r.tools.skill.LoadSkillTool,
     r.tools.start.StartMcpTool,
     r.tools.start.StartLspTool,
+    r.tools.git.GitCommitTool,
+    r.tools.owners.WhoOwnsTool,
+    r.tools.websearch.WebSearchTool,
+    r.tools.memory.MemoryTool,
+    r.tools.eval.EvalTool,
+    r.tools.draft.DraftEditTool,
 };
 
 pub const AgentDef = struct {
@@ -38,6 +46,8 @@ pub const AgentDef = struct {
     prompt: []const u8,
     in_agent_tool: bool = true,
     tools: AgentTools = .{},
+    /// skip the MCP and plugin tools every other agent gets
+    listed_tools_only: bool = false,
     model: ?AgentModelConfig = null,
     default_tool_call_budget: u32 = 1024,
 };
@@ -52,12 +62,27 @@ pub const ModeDef = struct {
 pub const Mode = enum(u6) {
     pub const Set = std.EnumSet(Mode);
     exec,
+    /// explore only, then report. Tools are cut down to `research_tools`
+    research,
     _,
 };
 
+/// The only tools an agent sees in research mode.
+pub const research_tools = [_][]const u8{
+    r.tools.read.ReadTool.def.name,
+    r.tools.rg.RipGrepTool.def.name,
+    r.tools.task.TaskTool.def.name,
+    r.tools.skill.LoadSkillTool.def.name,
+    r.lsp.TOOL_NAME,
+};
+
 pub const AgentType = enum(u6) {
     pub const Set = std.EnumSet(AgentType);
     general,
+    /// read-only investigator behind the `task` tool
+    task,
+    /// cheap model writing the edits of the `draft_edit` tool
+    draft,
     _,
 };
 
@@ -155,6 +180,13 @@ prompt_arena: std.heap.ArenaAllocator,
 io: std.Io,
 config_dir: ?std.Io.Dir,
 skill_dir: ?std.Io.Dir,
+/// `blitz.set_test_command`, replaces the detected one
+test_command: ?[]const u8 = null,
+/// `blitz.set_prompt_var`, filled into `{{name}}` in prompts
+prompt_vars: r.vars.Vars = .empty,
+/// `blitz.set_shell_history`, recent shell commands in the builder prompt, 0 is off
+shell_history: u32 = 0,
+home: []const u8 = "",
 flags: Flags = .{},
 // -------------------------------------------------------------------------------
 
@@ -183,6 +215,7 @@ pub fn init(alloc: std.mem.Allocator, io: std.Io, home: []const u8) !*Self {
         .io = io,
         .skill_dir = skill_dir,
         .config_dir = config_dir,
+        .home = home,
     };
 
     self.resetDefs();
@@ -216,6 +249,7 @@ pub fn buildAgentApiConfig(
             .model = ag_cfg.name,
             .provider = provider.provider_config,
             .reasoning_effort = ag_cfg.effort,
+            .stream = provider.stream,
         } };
     }
 
@@ -241,6 +275,7 @@ pub fn buildAgentApiConfig(
         .base_url = provider.getUrl(),
         .reasoning_effort = provider.reasoning_effort,
         .provider = provider.provider_config,
+        .stream = provider.stream,
     } };
 }
 
@@ -250,6 +285,21 @@ pub fn setAgentPrompt(self: *Self, agent_type: AgentType, prompt: []const u8) !v
     def.prompt = dup;
 }
 
+pub fn setTestCommand(self: *Self, command: []const u8) !void {
+    self.test_command = try self.prompt_arena.allocator().dupe(u8, command);
+}
+
+pub fn setPromptVar(self: *Self, name: []const u8, value: []const u8) !void {
+    if (!r.vars.validName(name)) return error.InvalidVarName;
+    const alloc = self.prompt_arena.allocator();
+    try self.prompt_vars.put(alloc, try alloc.dupe(u8, name), try alloc.dupe(u8, value));
+}
+
+/// Configured test command, else the one detected in the cwd.
+pub fn testCommand(self: *const Self, alloc: std.mem.Allocator) ?[]const u8 {
+    return self.test_command orelse r.testcmd.detect(self.io, alloc, std.Io.Dir.cwd());
+}
+
 pub fn setAgentModel(self: *Self, agent_type: AgentType, model: []const u8, effort: r.prv.config.ReasoningEffort, provider: r.prv.config.ProviderHandle) !void {
     const def = self.getAgentMut(agent_type) orelse return error.UnknownAgent;
     def.model = .{
@@ -331,7 +381,7 @@ pub fn getMode(self: *const Self, mode: Mode) ModeDef {
     return self.modes.get(mode) orelse .{ .name = "UNKNOWN", .prompt = "", .sparse = "" };
 }
 
-fn getAgent(self: *const Self, agent_type: AgentType) ?*const AgentDef {
+pub fn getAgent(self: *const Self, agent_type: AgentType) ?*const AgentDef {
     return if (self.agents.getPtrConst(agent_type).*) |*def| def else null;
 }
 
@@ -387,6 +437,9 @@ pub fn resetDefs(self: *Self) void {
     self.available_lsp_count = 0;
     self.agents = .initFill(null);
     self.modes = .initFill(null);
+    self.test_command = null;
+    self.prompt_vars = .empty;
+    self.shell_history = 0;
 
     self.agents.set(.general, .{
         .name = @tagName(AgentType.general),
@@ -405,6 +458,7 @@ pub fn resetDefs(self: *Self) void {
             r.tools.agent.SendMessageToAgent.def.name,
             r.tools.agent.AwaitAgent.def.name,
             r.tools.agent.CancelAgent.def.name,
+            r.tools.task.TaskTool.def.name,
             r.tools.todos.ListTodosTool.def.name,
             r.tools.todos.UpdateTodoStateTool.def.name,
             r.tools.todos.CreateTodoTool.def.name,
@@ -414,12 +468,46 @@ pub fn resetDefs(self: *Self) void {
         }),
     });
 
+    self.agents.set(.task, .{
+        .name = @tagName(AgentType.task),
+        .description =
+        \\Read-only investigator spawned by the task tool, returns findings with citations.
+        \\
+        ,
+        .prompt = @embedFile("prompts/task.md"),
+        .in_agent_tool = false,
+        .listed_tools_only = true,
+        .tools = .from(&.{
+            r.tools.read.ReadTool.def.name,
+            r.tools.rg.RipGrepTool.def.name,
+            r.tools.skill.LoadSkillTool.def.name,
+        }),
+    });
+
+    self.agents.set(.draft, .{
+        .name = @tagName(AgentType.draft),
+        .description =
+        \\Drafts search/replace edits for the draft_edit tool, runs without tools.
+        \\
+        ,
+        .prompt = @embedFile("prompts/draft.md"),
+        .in_agent_tool = false,
+        .listed_tools_only = true,
+    });
+
     self.modes.set(.exec, .{
         .name = "EXEC",
         .prompt = "",
         .sparse = "",
         .color = .red,
     });
+
+    self.modes.set(.research, .{
+        .name = "RESEARCH",
+        .prompt = @embedFile("prompts/research.md"),
+        .sparse = "Research mode: explore with read-only tools, change nothing, end with the findings report.",
+        .color = .cyan,
+    });
 }
 
 pub fn add(self: *Self, alloc: std.mem.Allocator, tool: r.prv.tool.Tool, flags: ToolFlags) !void {
@@ -470,8 +558,10 @@ pub fn refreshAgentTools(self: *const Self, agent: *r.prv.agent.Agent) !void {
 
     agent.chat.tools.items.len = 0;
     agent.tools.clearRetainingCapacity();
+    const research = agent.mode_idx == @intFromEnum(Mode.research);
     var it = self.iter(@enumFromInt(agent.type_idx));
     while (it.next()) |tool| {
+        if (research and !isResearchTool(tool.def.name)) continue;
         try agent.tools.append(alloc, tool);
 
         // Build the Agent tool schema from the registered agent definitions.
@@ -500,6 +590,13 @@ pub fn refreshAgentTools(self: *const Self, agent: *r.prv.agent.Agent) !void {
     }
 }
 
+fn isResearchTool(name: []const u8) bool {
+    for (research_tools) |allowed| {
+        if (std.mem.eql(u8, allowed, name)) return true;
+    }
+    return false;
+}
+
 fn findLoaded(self: *const Self, name: []const u8) ?r.prv.tool.Tool {
     for (self.loaded_tools.items) |entry| {
         if (std.mem.eql(u8, entry.tool.def.name, name)) return entry.tool;
@@ -525,6 +622,7 @@ const ToolIter = struct {
             self.listed_tools_done = true;
             self.i = 0;
         }
+        if (def.listed_tools_only) return null;
         while (self.i < self.factory.loaded_tools.items.len) {
             const en = self.factory.loaded_tools.items[self.i];
             self.i += 1;
@@ -706,6 +804,23 @@ pub fn build_system_prompt(
                 try w.writeAll("\n\n");
             } else |_| {}
         }
+        // the agent's cwd, it moves with :cd and :ssh
+        const absolute = std.fs.path.isAbsolute(cwd);
+        const memory_dir: ?std.Io.Dir = if (absolute) std.Io.Dir.openDirAbsolute(self.io, cwd, .{}) catch null else std.Io.Dir.cwd();
+        if (memory_dir) |dir| {
+            defer if (absolute) dir.close(self.io);
+            if (dir.openFile(self.io, r.memory.FILENAME, .{})) |memory_file| {
+                defer memory_file.close(self.io);
+                var buf: [256]u8 = undefined;
+                var file_reader = memory_file.reader(self.io, &buf);
+                // hand edits past the limit are cut
+                const notes = try alloc.alloc(u8, r.memory.MAX_BYTES);
+                const len = file_reader.interface.readSliceShort(notes) catch 0;
+                if (std.mem.trim(u8, notes[0..len], " \r\n").len > 0) {
+                    try w.print("# Project memory ({s}, kept with the memory tool):\n\n{s}\n\n", .{ r.memory.FILENAME, notes[0..len] });
+                }
+            } else |_| {}
+        }
     }
 
     _ = try w.print(
@@ -715,8 +830,17 @@ pub fn build_system_prompt(
         \\cwd: {s}
         \\
     , .{cwd});
+    if (self.testCommand(alloc)) |command| try w.print("test command: {s}\n", .{command});
+
+    if (agent_type == .general and self.shell_history > 0) {
+        const commands = r.shellhist.load(self.io, alloc, cwd, self.home, self.shell_history) catch &.{};
+        if (commands.len > 0) {
+            try w.writeAll("\n# Recent shell commands (oldest first):\n\n");
+            for (commands) |command| try w.print("$ {s}\n", .{command});
+        }
+    }
 
-    return allocating.written();
+    return r.vars.expand(alloc, allocating.written(), &self.prompt_vars);
 }
 
 pub const SkillMeta = struct {
@@ -0,0 +1,245 @@
+//! Dollar cost of token usage, priced from the LiteLLM model list. The list
+//! is cached in `~/.cache/blitzdenk/prices.json` and revalidated in the
+//! background once it is older than a day, with its ETag so an unchanged
+//! list is not downloaded again. Startup never waits on GitHub. Without a
+//! cached list costs are unknown, not zero. `blitz.set_model_price` entries
+//! win over the list, for self-hosted models or negotiated rates.
+const std = @import("std");
+const prv = @import("provider");
+const backup = @import("backup.zig");
+
+pub const PRICES_URL = "https://raw.githubusercontent.com/BerriAI/litellm/main/model_prices_and_context_window.json";
+pub const CACHE_FILE = ".cache/blitzdenk/prices.json";
+pub const ETAG_FILE = CACHE_FILE ++ ".etag";
+pub const TTL_S = 24 * 60 * 60;
+const MAX_LIST_BYTES = 32 * 1024 * 1024;
+
+/// USD per token. Cache rates fall back to the input rate.
+pub const Price = struct {
+    input: f64 = 0,
+    output: f64 = 0,
+    cache_read: ?f64 = null,
+    cache_write: ?f64 = null,
+};
+
+pub const PriceList = struct {
+    arena: std.heap.ArenaAllocator,
+    prices: std.StringArrayHashMapUnmanaged(Price) = .empty,
+
+    /// Chat models of a LiteLLM price list, everything else is skipped.
+    pub fn parse(gpa: std.mem.Allocator, json: []const u8) !PriceList {
+        var self: PriceList = .{ .arena = .init(gpa) };
+        errdefer self.deinit();
+
+        var scratch = std.heap.ArenaAllocator.init(gpa);
+        defer scratch.deinit();
+        const root = try std.json.parseFromSliceLeaky(std.json.Value, scratch.allocator(), json, .{});
+        if (root != .object) return error.InvalidPriceList;
+
+        const alloc = self.arena.allocator();
+        var it = root.object.iterator();
+        while (it.next()) |entry| {
+            if (entry.value_ptr.* != .object) continue;
+            const obj = entry.value_ptr.object;
+            const input = number(obj, "input_cost_per_token") orelse continue;
+            const output = number(obj, "output_cost_per_token") orelse continue;
+            const name = try std.ascii.allocLowerString(alloc, entry.key_ptr.*);
+            try self.prices.put(alloc, name, .{
+                .input = input,
+                .output = output,
+                .cache_read = number(obj, "cache_read_input_token_cost"),
+                .cache_write = number(obj, "cache_creation_input_token_cost"),
+            });
+        }
+        return self;
+    }
+
+    pub fn deinit(self: *PriceList) void {
+        self.arena.deinit();
+    }
+
+    pub fn put(self: *PriceList, model: []const u8, price: Price) !void {
+        const alloc = self.arena.allocator();
+        try self.prices.put(alloc, try std.ascii.allocLowerString(alloc, model), price);
+    }
+
+    pub fn clear(self: *PriceList) void {
+        self.prices = .empty;
+        _ = self.arena.reset(.retain_capacity);
+    }
+
+    /// Price for `model` as providers report it. Tries the exact name, then
+    /// without provider prefix and date suffix, then the closest list entry
+    /// sharing its prefix (`claude-sonnet-4-5` -> `claude-sonnet-4-5-20250929`).
+    pub fn lookup(self: *const PriceList, model: []const u8) ?Price {
+        var lower_buf: [128]u8 = undefined;
+        if (model.len == 0 or model.len > lower_buf.len) return null;
+        const lower = std.ascii.lowerString(&lower_buf, model);
+        if (self.prices.get(lower)) |p| return p;
+
+        const base = stripDate(lower[if (std.mem.lastIndexOfScalar(u8, lower, '/')) |i| i + 1 else 0..]);
+        if (self.prices.get(base)) |p| return p;
+
+        var best: ?Price = null;
+        var best_len: usize = 0;
+        for (self.prices.keys(), self.prices.values()) |key, price| {
+            const name = key[if (std.mem.lastIndexOfScalar(u8, key, '/')) |i| i + 1 else 0..];
+            if (std.mem.eql(u8, name, base)) return price;
+            // a dated release of the model, or the family the model belongs to
+            const related = (std.mem.startsWith(u8, name, base) and name[base.len] == '-') or
+                (std.mem.startsWith(u8, base, name) and base[name.len] == '-');
+            if (related and name.len > best_len) {
+                best = price;
+                best_len = name.len;
+            }
+        }
+        return best;
+    }
+};
+
+/// Dollars for `usage`. `input_tokens` excludes cache reads and writes.
+pub fn cost(price: Price, usage: prv.adapter.TokenUsage) f64 {
+    const in: f64 = @floatFromInt(usage.input_tokens);
+    const out: f64 = @floatFromInt(usage.output_tokens);
+    const read: f64 = @floatFromInt(usage.cached_tokens);
+    const write: f64 = @floatFromInt(usage.cache_creation_tokens);
+    return in * price.input +
+        out * price.output +
+        read * (price.cache_read orelse price.input) +
+        write * (price.cache_write orelse price.input);
+}
+
+/// Share of prompt tokens served from the cache, in percent. Null before
+/// the first prompt.
+pub fn cacheHitPercent(usage: prv.adapter.TokenUsage) ?u8 {
+    const prompt = usage.input_tokens + usage.cached_tokens + usage.cache_creation_tokens;
+    if (prompt == 0) return null;
+    return @intCast(usage.cached_tokens * 100 / prompt);
+}
+
+/// `$0.42`, `$12.30`, below a cent `<$0.01`.
+pub fn format(buf: []u8, dollars: f64) []const u8 {
+    if (dollars > 0 and dollars < 0.01) return "<$0.01";
+    return std.fmt.bufPrint(buf, "${d:.2}", .{dollars}) catch "$?";
+}
+
+/// `claude-3-5-haiku-20241022` -> `claude-3-5-haiku`, also `-2024-08-06`
+/// and `-latest`.
+fn stripDate(name: []const u8) []const u8 {
+    if (std.mem.endsWith(u8, name, "-latest")) return name[0 .. name.len - "-latest".len];
+    const n = name.len;
+    if (n > 9 and name[n - 9] == '-' and allDigits(name[n - 8 ..])) return name[0 .. n - 9];
+    if (n > 11 and name[n - 11] == '-' and name[n - 6] == '-' and name[n - 3] == '-' and
+        allDigits(name[n - 10 .. n - 6]) and allDigits(name[n - 5 .. n - 3]) and allDigits(name[n - 2 ..]))
+    {
+        return name[0 .. n - 11];
+    }
+    return name;
+}
+
+fn allDigits(s: []const u8) bool {
+    for (s) |c| {
+        if (!std.ascii.isDigit(c)) return false;
+    }
+    return true;
+}
+
+fn number(obj: std.json.ObjectMap, key: []const u8) ?f64 {
+    return switch (obj.get(key) orelse return null) {
+        .float => |f| f,
+        .integer => |i| @floatFromInt(i),
+        else => null,
+    };
+}
+
+/// Cached list under `home`, null when missing or unreadable.
+pub fn loadCached(io: std.Io, gpa: std.mem.Allocator, home: []const u8) ?PriceList {
+    const path = std.fs.path.join(gpa, &.{ home, CACHE_FILE }) catch return null;
+    defer gpa.free(path);
+    const file = std.Io.Dir.cwd().openFile(io, path, .{}) catch return null;
+    defer file.close(io);
+    var read_buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &read_buf);
+    const json = reader.interface.allocRemaining(gpa, .limited(MAX_LIST_BYTES)) catch return null;
+    defer gpa.free(json);
+    return PriceList.parse(gpa, json) catch |err| {
+        std.log.warn("price list {s} unusable: {s}", .{ path, @errorName(err) });
+        return null;
+    };
+}
+
+/// True when the cached list is missing or older than `TTL_S`.
+pub fn isStale(io: std.Io, home: []const u8) bool {
+    var path_buf: [std.posix.PATH_MAX]u8 = undefined;
+    const path = std.fmt.bufPrint(&path_buf, "{s}/" ++ CACHE_FILE, .{home}) catch return true;
+    const stat = std.Io.Dir.cwd().statFile(io, path, .{}) catch return true;
+    const now = std.Io.Clock.Timestamp.now(io, .real).raw.nanoseconds;
+    return now - stat.mtime.nanoseconds > @as(i96, TTL_S) * std.time.ns_per_s;
+}
+
+/// Shell script revalidating the list against its ETag. A new list is
+/// downloaded next to the cache and swapped in, an unchanged one (304, no
+/// body) only gets its mtime bumped. A failed download keeps the old file.
+pub fn fetchScript(alloc: std.mem.Allocator, home: []const u8) ![]const u8 {
+    const path = try std.fs.path.join(alloc, &.{ home, CACHE_FILE });
+    defer alloc.free(path);
+    const q_path = try backup.shellQuote(alloc, path);
+    defer alloc.free(q_path);
+    const q_dir = try backup.shellQuote(alloc, std.fs.path.dirname(path).?);
+    defer alloc.free(q_dir);
+    const etag = try std.fs.path.join(alloc, &.{ home, ETAG_FILE });
+    defer alloc.free(etag);
+    const q_etag = try backup.shellQuote(alloc, etag);
+    defer alloc.free(q_etag);
+
+    // without a cached list an old etag would turn the download into a 304
+    return std.fmt.allocPrint(
+        alloc,
+        "mkdir -p {s} && rm -f {s}.part && {{ [ -f {s} ] || rm -f {s}; }} && " ++
+            "curl -fsSL --max-time 60 --etag-compare {s} --etag-save {s}.part -o {s}.part '{s}' && " ++
+            "if [ -s {s}.part ]; then mv {s}.part {s} && mv {s}.part {s}; else rm -f {s}.part {s}.part && touch {s}; fi",
+        .{ q_dir, q_path, q_path, q_etag, q_etag, q_etag, q_path, PRICES_URL, q_path, q_path, q_path, q_etag, q_etag, q_path, q_etag, q_path },
+    );
+}
+
+test "price lookup and cost" {
+    const json =
+        \\{
+        \\  "sample_spec": {"input_cost_per_token": "0.0"},
+        \\  "claude-sonnet-4-5-20250929": {"input_cost_per_token": 3e-06, "output_cost_per_token": 1.5e-05, "cache_read_input_token_cost": 3e-07, "cache_creation_input_token_cost": 3.75e-06},
+        \\  "gpt-4o": {"input_cost_per_token": 2.5e-06, "output_cost_per_token": 1e-05},
+        \\  "gpt-4o-mini": {"input_cost_per_token": 1.5e-07, "output_cost_per_token": 6e-07},
+        \\  "ollama/llama3": {"input_cost_per_token": 0, "output_cost_per_token": 0},
+        \\  "dall-e-3": {"output_cost_per_pixel": 0.0}
+        \\}
+    ;
+    var list = try PriceList.parse(std.testing.allocator, json);
+    defer list.deinit();
+
+    try std.testing.expectEqual(@as(usize, 4), list.prices.count());
+    try std.testing.expectEqual(@as(f64, 2.5e-06), list.lookup("GPT-4o").?.input);
+    try std.testing.expectEqual(@as(f64, 1.5e-07), list.lookup("openai/gpt-4o-mini").?.input);
+    try std.testing.expectEqual(@as(f64, 2.5e-06), list.lookup("gpt-4o-2024-08-06").?.input);
+    try std.testing.expectEqual(@as(f64, 3e-06), list.lookup("claude-sonnet-4-5").?.input);
+    try std.testing.expectEqual(@as(f64, 0), list.lookup("llama3").?.input);
+    try std.testing.expect(list.lookup("mistral-large") == null);
+
+    const price = list.lookup("claude-sonnet-4-5").?;
+    const dollars = cost(price, .{ .input_tokens = 1_000_000, .output_tokens = 100_000, .cached_tokens = 1_000_000 });
+    try std.testing.expectApproxEqAbs(@as(f64, 3.0 + 1.5 + 0.3), dollars, 1e-9);
+    const written = cost(price, .{ .cache_creation_tokens = 1_000_000 });
+    try std.testing.expectApproxEqAbs(@as(f64, 3.75), written, 1e-9);
+    try std.testing.expectEqual(@as(?u8, 80), cacheHitPercent(.{ .input_tokens = 100, .cached_tokens = 800, .cache_creation_tokens = 100 }));
+    try std.testing.expectEqual(@as(?u8, null), cacheHitPercent(.{}));
+
+    var custom: PriceList = .{ .arena = .init(std.testing.allocator) };
+    defer custom.deinit();
+    try custom.put("Llama3", .{ .input = 1e-07, .output = 2e-07 });
+    try std.testing.expectEqual(@as(f64, 1e-07), custom.lookup("ollama/llama3").?.input);
+    custom.clear();
+    try std.testing.expect(custom.lookup("llama3") == null);
+
+    var buf: [16]u8 = undefined;
+    try std.testing.expectEqualStrings("$4.80", format(&buf, dollars));
+    try std.testing.expectEqualStrings("<$0.01", format(&buf, 0.004));
+}
@@ -1,7 +1,7 @@
 const std = @import("std");
 const r = @import("root.zig");
 
-const HEADER_ART =
+pub const HEADER_ART =
     \\██████╗ ██╗     ██╗████████╗███████╗██████╗ ███████╗███╗   ██╗██╗  ██╗
     \\██╔══██╗██║     ██║╚══██╔══╝╚══███╔╝██╔══██╗██╔════╝████╗  ██║██║ ██╔╝
     \\██████╔╝██║     ██║   ██║     ███╔╝ ██║  ██║█████╗  ██╔██╗ ██║█████╔╝
@@ -10,19 +10,37 @@ const HEADER_ART =
     \\╚═════╝ ╚══════╝╚═╝   ╚═╝   ╚══════╝╚═════╝ ╚══════╝╚═╝  ╚═══╝╚═╝  ╚═╝
 ;
 
+/// Title screen and throbber, set from lua via `set_appearance`.
+pub const Appearance = struct {
+    /// art above the dashboard, empty hides it
+    title: []const u8 = HEADER_ART,
+    spinner: r.tui.text_utils.SpinnerStyle = .dots,
+    /// project name and git branch below the title
+    banner: bool = true,
+};
+
 // TODO: load keybindings
-const keybinds = .{
+pub const keybinds = .{
     .{ "c+g", "toggle permission" },
     .{ "esc", "cancel" },
     .{ "c+n", "clear" },
     .{ "c+c", "quit" },
     .{ "c+u", "scroll up" },
     .{ "c+d", "scroll down" },
-    .{ "c+t", "show thinking" },
+    .{ "c+t", "usage breakdown" },
+    .{ "c+o", "changed files" },
+    .{ "c+k", "token heatmap" },
+    .{ "c+e", "quote last tool error" },
+    .{ "c+x", "tool errors, retry a call" },
+    .{ "c+f", "grep project" },
+    .{ "c+l", "jump to code reference" },
+    .{ "c+b", "session browser" },
+    .{ "c+s", "search chat" },
+    .{ "c+j", "select message, j/k move, y copy" },
 };
 
-pub fn build_header(frame: usize, alloc: std.mem.Allocator, out: *std.ArrayList(r.tui.Line)) !void {
-    var line_iter = std.mem.splitAny(u8, HEADER_ART, "\n");
+pub fn build_header(frame: usize, art: []const u8, alloc: std.mem.Allocator, out: *std.ArrayList(r.tui.Line)) !void {
+    var line_iter = std.mem.splitAny(u8, art, "\n");
     while (line_iter.next()) |line_text| {
         var l = r.tui.Line{};
         var col: u16 = 0;
@@ -56,8 +74,8 @@ pub fn build_header(frame: usize, alloc: std.mem.Allocator, out: *std.ArrayList(
 }
 
 pub fn build_info(app: *r.app.App, out: *std.ArrayList(r.tui.Line)) !void {
-    try build_header(app.frame_count, app.arena_frame.allocator(), out);
     const alloc = app.arena_frame.allocator();
+    if (app.appearance.title.len > 0) try build_header(app.frame_count, app.appearance.title, alloc, out);
 
     try out.append(
         alloc,
@@ -116,6 +134,25 @@ pub fn build_info(app: *r.app.App, out: *std.ArrayList(r.tui.Line)) !void {
 
     try out.append(alloc, line);
 
+    if (app.appearance.banner and app.swarm.exec.ssh_target == null) {
+        var l = r.tui.Line{};
+        try l.pushSpan(alloc, .{ .content = "├[project: ", .style = .{ .fg = app.theme.muted } });
+        try l.pushSpanPrint(alloc, "{s}", .{std.fs.path.basename(app.cwd)}, .{ .fg = app.theme.info, .modifier = .{ .bold = true } });
+        if (r.util.gitBranch(app.io, alloc, app.cwd)) |branch| {
+            try l.pushSpan(alloc, .{ .content = "  branch: ", .style = .{ .fg = app.theme.muted } });
+            try l.pushSpan(alloc, .{ .content = branch, .style = .{ .fg = app.theme.info } });
+        }
+        try out.append(alloc, l);
+    }
+
+    if (app.last_session_available) {
+        var l = r.tui.Line{};
+        try l.pushSpan(alloc, .{ .content = "├[previous session: ", .style = .{ .fg = app.theme.muted } });
+        try l.pushSpan(alloc, .{ .content = ":handoff", .style = .{ .fg = app.theme.info, .modifier = .{ .bold = true } } });
+        try l.pushSpan(alloc, .{ .content = " continues where you left off", .style = .{ .fg = app.theme.muted } });
+        try out.append(alloc, l);
+    }
+
     {
         var skill_count: usize = 0;
         if (app.context_factory.skill_dir) |skill_dir| {
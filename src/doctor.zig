@@ -0,0 +1,114 @@
+//! `blitz config doctor`: the config layers in the order they load and the
+//! settings they add up to. Every layer is a Lua script run against the same
+//! `blitz` table, so a later layer overrides what an earlier one set:
+//!
+//!   1. `~/.config/blitzdenk/blitz.lua`, the global config
+//!   2. installed packs, `~/.config/blitzdenk/packs/<name>/init.lua`
+//!   3. `~/.config/blitzdenk/settings.lua`, written by the `:settings` popup
+//!   4. `blitz.lua` in the project root
+const std = @import("std");
+const r = @import("root.zig");
+const App = r.app.App;
+
+pub const Layer = struct {
+    label: []const u8,
+    path: []const u8,
+    state: State,
+
+    pub const State = enum {
+        loaded,
+        missing,
+        failed,
+    };
+};
+
+pub fn write(w: *std.Io.Writer, app: *App, layers: []const Layer, env: *const std.process.Environ.Map) !void {
+    try w.writeAll("Config layers, later ones win:\n");
+    for (layers, 1..) |layer, i| {
+        try w.print("  {d}. {s: <8} {s} [{s}]\n", .{ i, layer.label, layer.path, @tagName(layer.state) });
+    }
+    const lua_error = app.lua_vm.getLastError();
+    if (lua_error.len > 0) try w.print("  lua error: {s}\n", .{lua_error});
+
+    try w.writeAll("\nEffective config:\n");
+    const cfg = &app.config;
+    if (cfg.default_model.bound) {
+        try w.print("  model            {s}\n", .{cfg.default_model.getName()});
+    } else {
+        try w.writeAll("  model            (none, set one with blitz.set_model)\n");
+    }
+    for (cfg.providers[0..cfg.provider_count], 0..) |*provider, i| {
+        const key = provider.getKeyEnvar();
+        const key_state = if (key.len == 0) "no key" else if (env.get(key) != null) "set" else "missing";
+        try w.print("  provider {d}       {s}{s}{s} ({s} {s})\n", .{ i, provider.getName(), if (provider.name_len > 0) " " else "", provider.getUrl(), key, key_state });
+    }
+    for (cfg.model_aliases[0..cfg.model_alias_count]) |*alias| {
+        try w.print("  alias            {s} -> {s}\n", .{ alias.getAlias(), alias.entry.getName() });
+    }
+
+    const factory = app.context_factory;
+    var agents = factory.agents.iterator();
+    while (agents.next()) |entry| {
+        const def = entry.value.* orelse continue;
+        const model = def.model orelse continue;
+        try w.print("  agent model      {s}: {s} ({s})\n", .{ def.name, model.name, @tagName(model.effort) });
+    }
+
+    try w.print("  test command     {s}\n", .{factory.testCommand(app.sessionAlloc()) orelse "(unknown)"});
+    if (factory.shell_history > 0) try w.print("  shell history    last {d} commands\n", .{factory.shell_history});
+    for (factory.prompt_vars.keys(), factory.prompt_vars.values()) |name, value| {
+        try w.print("  prompt var       {s} = {s}\n", .{ name, value });
+    }
+    try w.print("  compaction       at {d}%{s}{s}\n", .{
+        app.compact_threshold,
+        if (app.compact_model.len > 0) " with " else "",
+        app.compact_model,
+    });
+    try w.print("  retries          {d}, backoff {d}ms to {d}ms\n", .{ app.retry_policy.max_retries, app.retry_policy.base_delay_ms, app.retry_policy.max_delay_ms });
+    for (app.fallback_models) |model| try w.print("  fallback model   {s}\n", .{model});
+    for (app.custom_prices.prices.keys(), app.custom_prices.prices.values()) |model, price| {
+        try w.print("  model price      {s}: ${d} in, ${d} out per 1M tokens\n", .{ model, price.input * 1e6, price.output * 1e6 });
+    }
+    if (app.cost_confirm > 0) {
+        try w.print("  cost confirm     above ${d:.2} of input per turn\n", .{app.cost_confirm});
+    } else {
+        try w.writeAll("  cost confirm     off\n");
+    }
+    if (app.spend.budget > 0) try w.print("  daily budget     ${d:.2} over all instances\n", .{app.spend.budget});
+
+    try w.writeAll("  flags           ");
+    inline for (std.meta.fields(r.app.AppFlags)) |field| {
+        try w.print(" {s}={}", .{ field.name, @field(app.flags, field.name) });
+    }
+    try w.writeByte('\n');
+
+    {
+        const g = app.bash_allow.lock(app.io);
+        defer g.unlock();
+        for (g.ptr.patterns.items) |p| try w.print("  allow bash       {s}\n", .{p});
+    }
+    {
+        const g = app.bash_rules.lock(app.io);
+        defer g.unlock();
+        for (g.ptr.allow) |p| try w.print("  bash rule allow  {s}\n", .{p});
+        for (g.ptr.deny) |p| try w.print("  bash rule deny   {s}\n", .{p});
+    }
+    for (app.edit_allow.patterns.items) |p| try w.print("  allow edit       {s}\n", .{p});
+    try w.print("  sandbox          {s}\n", .{if (app.sandbox.enabled) "project root only" else "off"});
+    for (app.sandbox.items()) |p| try w.print("  sandbox allow    {s}\n", .{p});
+    if (app.vault.enabled) {
+        try w.print("  session crypto   {s}{s}, key {s}\n", .{
+            if (app.vault.passphrase_env.len > 0) "passphrase from " else "OS keychain",
+            app.vault.passphrase_env,
+            if (app.vault.key != null) "loaded" else "locked",
+        });
+    }
+    try w.print("  web search       {s}\n", .{@tagName(app.web_search.resolve(env))});
+    try w.print("  prompt links     {s}\n", .{@tagName(app.links.mode)});
+    try w.print("  inline images    {s}\n", .{@tagName(r.media.detect(env))});
+    for (app.hooks.hooks.items) |hook| try w.print("  hook             {s}: {s}\n", .{ @tagName(hook.event), hook.command });
+
+    try w.writeAll("  tools           ");
+    for (factory.loaded_tools.items) |entry| try w.print(" {s}", .{entry.tool.def.name});
+    try w.writeByte('\n');
+}
@@ -6,7 +6,7 @@ const Role = r.prv.adapter.Role;
 /// Events emitted by the app, swarm, and agent subsystems. The AppEvent
 /// union is the single source of truth — any code that needs to react to
 /// system state changes (e.g. Lua callbacks, logging, status-bar updates)
-/// should dispatch from a central `onEvent` call.
+/// should subscribe to the EventBus instead of polling app state.
 pub const AppEvent = union(enum) {
     session_reset,
     mode_changed: u8, // done
@@ -17,18 +17,46 @@ pub const AppEvent = union(enum) {
     agent_cancelled: struct { id: AgentId },
     compaction_started: struct { id: AgentId }, // done
     compaction_complete: struct { id: AgentId },
-    // TODO: emit from agent.zig — needs event bus accessible from Agent
+    // derived from the agent's tool_call broadcast on the main thread
     tool_call_started: struct { agent_id: AgentId, call_id: []const u8, name: []const u8 },
-    // TODO: emit from agent.zig — needs event bus accessible from Agent
+    // derived from the tool_result broadcast on the main thread
     tool_call_complete: struct { agent_id: AgentId, call_id: []const u8, name: []const u8, is_error: bool },
-    // TODO: emit from swarm.zig recordBroadcast — needs event bus threaded through Swarm
     agent_broadcast: struct { id: AgentId, role: Role },
-    // TODO: emit from swarm.zig requestPermission — needs event bus threaded through Swarm
     permission_requested: struct { call_id: ?[]const u8, level: r.prv.Swarm.PermissionLevel },
-    // TODO: emit from swarm.zig resolvePermission — needs event bus threaded through Swarm
     permission_resolved: struct { call_id: ?[]const u8, state: r.prv.Swarm.PermissionState },
     user_message_sent: []const u8,
     mcp_tools_reloaded,
+    usage_updated: r.prv.adapter.TokenUsage,
+
+    pub fn topic(self: AppEvent) Topic {
+        return switch (self) {
+            .session_reset, .mode_changed, .mcp_tools_reloaded => .session,
+            .agent_created,
+            .agent_started,
+            .agent_complete,
+            .agent_failed,
+            .agent_cancelled,
+            .compaction_started,
+            .compaction_complete,
+            => .agent,
+            .agent_broadcast, .user_message_sent => .message,
+            .tool_call_started, .tool_call_complete => .tool,
+            .permission_requested, .permission_resolved => .permission,
+            .usage_updated => .usage,
+        };
+    }
+};
+
+pub const Topic = enum { session, agent, message, tool, permission, usage };
+
+pub const TopicSet = std.EnumSet(Topic);
+
+/// Native in-process subscriber. Runs on the main thread inside `emit`,
+/// so handlers must not block.
+pub const Subscriber = struct {
+    ptr: *anyopaque,
+    topics: TopicSet = .initFull(),
+    on_event: *const fn (ptr: *anyopaque, app: *r.app.App, event: AppEvent) void,
 };
 
 pub const AppEventTag = @typeInfo(AppEvent).@"union".tag_type.?;
@@ -39,7 +67,28 @@ pub const Listner = struct {
 
 pub const EventBus = struct {
     listner: std.AutoHashMapUnmanaged(AppEventTag, std.ArrayList(Listner)) = .{},
+    subscribers: std.ArrayList(Subscriber) = .empty,
+
+    pub fn subscribe(self: *EventBus, alloc: std.mem.Allocator, sub: Subscriber) !void {
+        for (self.subscribers.items) |s| if (s.ptr == sub.ptr and s.on_event == sub.on_event) return;
+        try self.subscribers.append(alloc, sub);
+    }
+
+    pub fn unsubscribe(self: *EventBus, ptr: *anyopaque) void {
+        var i: usize = 0;
+        while (i < self.subscribers.items.len) {
+            if (self.subscribers.items[i].ptr == ptr) {
+                _ = self.subscribers.orderedRemove(i);
+            } else i += 1;
+        }
+    }
+
     pub fn emit(self: *const EventBus, app: *r.app.App, event: AppEvent) !void {
+        const t = event.topic();
+        for (self.subscribers.items) |sub| {
+            if (sub.topics.contains(t)) sub.on_event(sub.ptr, app, event);
+        }
+
         const listners = self.listner.get(event) orelse return;
         for (listners.items) |en| {
             // TODO: can explode, need new redesign of async lua
@@ -63,6 +112,7 @@ pub const EventBus = struct {
                 // .permission_resolved => |ev| app.lua_vm.invokeLuaFunction(en.func_ref, ev),
                 .user_message_sent => |msg| app.lua_vm.invokeLuaFunction(en.func_ref, msg),
                 .mcp_tools_reloaded => app.lua_vm.invokeLuaFunction(en.func_ref, {}),
+                .usage_updated => |ev| app.lua_vm.invokeLuaFunction(en.func_ref, ev),
                 else => {},
             }
         }
@@ -80,3 +130,82 @@ pub const EventBus = struct {
         try res.value_ptr.append(alloc, .{ .func_ref = func_ref });
     }
 };
+
+/// Appends every event as one JSON line to `.blitz/events.jsonl`.
+/// Subscribed when blitzdenk runs with `--log`.
+pub const JsonlLogger = struct {
+    fd: std.posix.fd_t = -1,
+
+    pub const FILENAME = r.util.BLITZ_DIR ++ "/events.jsonl";
+
+    pub fn open(self: *JsonlLogger, io: std.Io) void {
+        r.util.ensureBlitzDir(std.Io.Dir.cwd(), io) catch return;
+        const flags: std.posix.O = .{ .ACCMODE = .WRONLY, .CREAT = true, .APPEND = true };
+        self.fd = std.posix.openat(std.posix.AT.FDCWD, FILENAME, flags, 0o644) catch -1;
+    }
+
+    pub fn deinit(self: *JsonlLogger) void {
+        if (self.fd >= 0) _ = std.c.close(self.fd);
+        self.fd = -1;
+    }
+
+    pub fn subscriber(self: *JsonlLogger) Subscriber {
+        return .{ .ptr = self, .on_event = onEvent };
+    }
+
+    fn onEvent(ptr: *anyopaque, app: *r.app.App, event: AppEvent) void {
+        const self: *JsonlLogger = @ptrCast(@alignCast(ptr));
+        if (self.fd < 0) return;
+
+        const ts = std.Io.Clock.Timestamp.now(app.io, .real).raw.nanoseconds;
+        var buf: [4096]u8 = undefined;
+        var w = std.Io.Writer.fixed(&buf);
+        if (writeLine(&w, ts, event)) {
+            self.writeAll(w.buffered());
+            return;
+        } else |_| {}
+
+        // past the stack buffer, e.g. a long message
+        var big: std.Io.Writer.Allocating = .init(app.gpa);
+        defer big.deinit();
+        writeLine(&big.writer, ts, event) catch return;
+        self.writeAll(big.written());
+    }
+
+    fn writeAll(self: *JsonlLogger, bytes: []const u8) void {
+        var rest = bytes;
+        while (rest.len > 0) {
+            const n = std.c.write(self.fd, rest.ptr, rest.len);
+            if (n <= 0) return;
+            rest = rest[@intCast(n)..];
+        }
+    }
+
+    fn writeLine(w: *std.Io.Writer, timestamp_ns: i96, event: AppEvent) !void {
+        try std.json.Stringify.value(.{
+            .ts_ms = @divTrunc(timestamp_ns, std.time.ns_per_ms),
+            .topic = event.topic(),
+            .event = event,
+        }, .{}, w);
+        try w.writeByte('\n');
+    }
+};
+
+test "event topics" {
+    try std.testing.expectEqual(Topic.tool, (AppEvent{ .tool_call_started = .{
+        .agent_id = .{ .index = 0, .generation = 0 },
+        .call_id = "c",
+        .name = "read",
+    } }).topic());
+    try std.testing.expectEqual(Topic.usage, (AppEvent{ .usage_updated = .{} }).topic());
+}
+
+test "JsonlLogger writes one line per event" {
+    var buf: [256]u8 = undefined;
+    var w = std.Io.Writer.fixed(&buf);
+    try JsonlLogger.writeLine(&w, 2 * std.time.ns_per_ms, .{ .user_message_sent = "hi" });
+    try std.testing.expectEqualStrings(
+        "{\"ts_ms\":2,\"topic\":\"message\",\"event\":{\"user_message_sent\":\"hi\"}}\n",
+        w.buffered(),
+    );
+}
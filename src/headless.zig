@@ -0,0 +1,228 @@
+//! `blitz exec "prompt"`: one agent run without the TUI, for CI and scripts.
+//! The prompt comes from the arguments or stdin, the answer streams to
+//! stdout as plain text or, with `--json`, one JSON object per line. Nobody
+//! is around to answer a permission prompt, so requests are approved only
+//! for tools named in `--allow=bash,edit` (`--allow=all` for everything) and
+//! denied otherwise. Questions and plan approvals are always declined.
+const std = @import("std");
+const r = @import("root.zig");
+const App = r.app.App;
+const prv = r.prv;
+
+pub const MAX_PROMPT_BYTES = 1024 * 1024;
+const POLL_MS = 25;
+
+pub const Options = struct {
+    prompt: []const u8,
+    json: bool = false,
+    /// comma separated tool names, `all` approves every request
+    allow: []const u8 = "",
+};
+
+/// Read the prompt piped into `blitz exec`.
+pub fn readStdin(io: std.Io, alloc: std.mem.Allocator) ![]const u8 {
+    var buf: [4096]u8 = undefined;
+    var stdin = std.Io.File.stdin().reader(io, &buf);
+    const text = try stdin.interface.allocRemaining(alloc, .limited(MAX_PROMPT_BYTES));
+    return std.mem.trim(u8, text, " \t\r\n");
+}
+
+/// Run `opts.prompt` to completion. Returns the process exit status: 0 when
+/// the agent finished, 1 when it could not start, failed or hit a provider
+/// error.
+pub fn run(app: *App, opts: Options) !u8 {
+    var out_buf: [4096]u8 = undefined;
+    var stdout = std.Io.File.stdout().writer(app.io, &out_buf);
+    var out: Output = .{ .w = &stdout.interface, .json = opts.json };
+
+    if (opts.prompt.len == 0) {
+        try out.line("error", "empty prompt");
+        return 1;
+    }
+
+    const parts = try app.promptParts(app.sessionAlloc(), opts.prompt) orelse {
+        try out.notifications(app);
+        return 1;
+    };
+    const chat_entry = try app.promptChatEntry(app.sessionAlloc(), opts.prompt);
+    const id = app.swarm.reserveFreeSlot() orelse {
+        try out.line("error", "no free agent slot");
+        return 1;
+    };
+    app.user_turn +%= 1;
+    app.swarm.turn = app.user_turn;
+    try app.event_bus.emit(app, .{ .user_message_sent = opts.prompt });
+    try app.cmd_queue.append(app.io, .{ .spawn_agent = .{
+        .agent_id = id,
+        .prompt = parts,
+        .chat_entry = chat_entry,
+    } });
+    app.run_state = .running;
+    try app.cmd_queue.apply(app.io, app);
+
+    var failed = false;
+    while (app.isRunning()) {
+        if (!app.swarm.tickAll()) {
+            if (app.main_agent_id) |agent_id| {
+                if (app.swarm.getSlotState(agent_id) == .failed) {
+                    failed = true;
+                    try app.event_bus.emit(app, .{ .agent_failed = .{ .id = agent_id, .err = "" } });
+                } else {
+                    try app.event_bus.emit(app, .{ .agent_complete = agent_id });
+                }
+            }
+            app.run_state = .idle;
+        }
+
+        try resolvePermissions(app, opts.allow);
+        try app.tick();
+        try app.cmd_queue.apply(app.io, app);
+        if (try out.chat(app)) failed = true;
+        try out.notifications(app);
+        try out.w.flush();
+
+        if (app.isRunning()) std.Io.sleep(app.io, std.Io.Duration.fromMilliseconds(POLL_MS), .real) catch break;
+    }
+    r.session.clearAutosave(app.io);
+
+    if (app.main_agent_id == null) failed = true;
+    if (try out.chat(app)) failed = true;
+    try out.result(app, !failed);
+    try out.w.flush();
+    return if (failed) 1 else 0;
+}
+
+fn resolvePermissions(app: *App, allow: []const u8) !void {
+    const g = app.permission_queue.lock(app.io);
+    defer g.unlock();
+    for (g.ptr.items) |req| {
+        try app.event_bus.emit(app, .{ .permission_requested = .{ .call_id = req.call_id, .level = req.level } });
+        if (app.hooks.vetoPermission(app, req)) |reason| {
+            req.state = .{ .message = reason };
+            try app.event_bus.emit(app, .{ .permission_resolved = .{ .call_id = req.call_id, .state = req.state } });
+            req.event.set(app.io);
+            continue;
+        }
+        const approved = if (permissionTool(req.payload)) |tool|
+            allowed(allow, tool) or (req.level != .dangerous and app.permissionAllowlisted(req.payload))
+        else
+            false;
+        if (approved) try app.persist_permission_to_history(req);
+        req.state = if (approved) .approved else .denied;
+        try app.event_bus.emit(app, .{ .permission_resolved = .{ .call_id = req.call_id, .state = req.state } });
+        req.event.set(app.io);
+    }
+    g.ptr.clearRetainingCapacity();
+}
+
+/// Tool name a permission request is matched against in `--allow`, null for
+/// requests that need a human (questions, plan approval).
+pub fn permissionTool(payload: prv.Swarm.PermissionPayload) ?[]const u8 {
+    return switch (payload) {
+        .call => |call| call.tool_name,
+        .diff => r.tools.edit.EditTool.def.name,
+        .commit => r.tools.git.GitCommitTool.def.name,
+        .ask, .plan => null,
+    };
+}
+
+pub fn allowed(allow: []const u8, tool: []const u8) bool {
+    var it = std.mem.tokenizeScalar(u8, allow, ',');
+    while (it.next()) |raw| {
+        const name = std.mem.trim(u8, raw, " ");
+        if (std.mem.eql(u8, name, "all") or std.mem.eql(u8, name, tool)) return true;
+    }
+    return false;
+}
+
+const Output = struct {
+    w: *std.Io.Writer,
+    json: bool,
+    /// chat entries already written
+    printed: usize = 0,
+    /// newest notification already written
+    last_notification: ?[*]const u8 = null,
+
+    fn line(self: *Output, kind: []const u8, text: []const u8) !void {
+        if (self.json) {
+            try std.json.Stringify.value(.{ .type = kind, .text = text }, .{}, self.w);
+            try self.w.writeByte('\n');
+            return;
+        }
+        if (std.mem.eql(u8, kind, "message")) {
+            try self.w.print("{s}\n", .{text});
+        } else {
+            try self.w.print("[{s}] {s}\n", .{ kind, text });
+        }
+    }
+
+    /// Write new agent chat entries, true when one was a provider error.
+    fn chat(self: *Output, app: *App) !bool {
+        var provider_error = false;
+        const entries = app.chat_entries.items;
+        // compaction rebuilds the chat, never repeat what was written
+        self.printed = @min(self.printed, entries.len);
+        for (entries[self.printed..]) |entry| {
+            if (entry.role == .user) continue;
+            for (entry.parts) |part| switch (part) {
+                .message => |text| try self.line("message", text),
+                .plain_text => |text| try self.line("info", text),
+                .tool_call => |call| try self.line("tool", call.tool_name),
+                .diff => |diff| try self.line("edit", diff.path),
+                .provider_error => |err| {
+                    provider_error = true;
+                    try self.line("error", err.message);
+                },
+                .thinking, .plan => {},
+            };
+        }
+        self.printed = entries.len;
+        return provider_error;
+    }
+
+    fn notifications(self: *Output, app: *App) !void {
+        const history = app.notifications.history();
+        var fresh: usize = 0;
+        for (history) |entry| {
+            if (entry.used.msg.ptr == self.last_notification) break;
+            fresh += 1;
+        }
+        if (fresh == 0) return;
+        self.last_notification = history[0].used.msg.ptr;
+        var i = fresh;
+        while (i > 0) {
+            i -= 1;
+            const entry = history[i].used;
+            if (entry.level == .info) continue;
+            try self.line(entry.level.label(), entry.msg);
+        }
+    }
+
+    fn result(self: *Output, app: *App, ok: bool) !void {
+        const usage = app.swarm.usage();
+        const dollars = app.sessionCost();
+        const turns = if (app.main_agent_id) |id| if (app.swarm.getAgent(id)) |agent| agent.timing.turns else 0 else 0;
+        if (self.json) {
+            try std.json.Stringify.value(.{
+                .type = "result",
+                .ok = ok,
+                .input_tokens = usage.input_tokens,
+                .output_tokens = usage.output_tokens,
+                .cost = dollars,
+                .turns = turns,
+            }, .{}, self.w);
+            try self.w.writeByte('\n');
+            return;
+        }
+        if (!ok) try self.w.writeAll("[error] run failed\n");
+    }
+};
+
+test "exec allow list" {
+    try std.testing.expect(allowed("bash, edit", "edit"));
+    try std.testing.expect(allowed("all", "git_commit"));
+    try std.testing.expect(!allowed("", "bash"));
+    try std.testing.expect(!allowed("bash_extra", "bash"));
+
+    try std.testing.expectEqualStrings("bash", permissionTool(.{ .call = .{ .tool_name = "bash", .tool_arguments = "{}" } }).?);
+}
@@ -0,0 +1,528 @@
+//! Shell commands fired on app events, for a desktop notification or a
+//! webhook while blitzdenk runs in another window:
+//!
+//!     blitz.add_hook({ event = "turn_finished", command = "notify-send blitz {message}", min_seconds = 30 })
+//!
+//! Events are `turn_finished` (the main agent ended its run), `error` (the
+//! run failed) and `permission_requested` (a prompt waits for an answer).
+//! `{event}`, `{message}`, `{model}`, `{cost}` and `{seconds}` expand shell
+//! quoted, like the placeholders of `blitz.register_tool` commands.
+//! Commands run in the background through the command pool, always on this
+//! machine, and are reaped from `App.tick`, so a slow webhook never holds
+//! up the UI.
+//!
+//! Policy hooks get the action as a JSON object on stdin and decide on it:
+//!
+//!     blitz.add_hook({ event = "pre_tool", command = "~/.config/blitzdenk/no-generated.sh" })
+//!
+//! `pre_tool` runs before every tool call, `post_tool` after it with the
+//! result, `pre_run` before a prompt goes to the main agent and
+//! `on_permission` before a permission prompt. A hook that exits non-zero
+//! vetoes: the call is blocked, the result becomes an error, the prompt is
+//! held back or the permission denied, with its stdout (or stderr) as the
+//! reason. Output of a hook that exits zero is attached as a note. These run
+//! in turn on the command pool while the action waits, one running longer
+//! than `POLICY_TIMEOUT_MS` vetoes. `pre_run` sees every prompt of the main
+//! agent, typed, queued or started headless. `post_run` gets the
+//! finished run on stdin and runs in the background, its output shows up as
+//! a notification.
+const std = @import("std");
+const r = @import("root.zig");
+const App = r.app.App;
+const prv = r.prv;
+const events = r.events;
+
+/// hook commands running at once, later ones are dropped with a warning
+const MAX_RUNNING = 8;
+/// a policy hook running longer vetoes
+pub const POLICY_TIMEOUT_MS = 10_000;
+
+pub const Event = enum {
+    turn_finished,
+    permission_requested,
+    @"error",
+    pre_tool,
+    post_tool,
+    pre_run,
+    post_run,
+    on_permission,
+};
+
+pub const Hook = struct {
+    event: Event,
+    command: []const u8,
+    /// `turn_finished` and `error` stay quiet for runs shorter than this
+    min_seconds: u32 = 0,
+};
+
+/// Placeholder values of one firing.
+pub const Vars = struct {
+    event: Event,
+    message: []const u8 = "",
+    model: []const u8 = "",
+    cost: []const u8 = "",
+    seconds: i64 = 0,
+};
+
+/// What a policy hook makes of an action.
+pub const Verdict = union(enum) {
+    /// notes of the hooks that passed, empty when they printed nothing
+    allow: []const u8,
+    /// the reason of the first hook that failed
+    veto: []const u8,
+};
+
+/// stdin of `pre_tool` and `post_tool`, `result` only after the call
+pub const ToolPayload = struct {
+    event: Event,
+    tool: []const u8,
+    arguments: std.json.Value,
+    cwd: []const u8,
+    result: ?[]const u8 = null,
+    is_error: ?bool = null,
+};
+
+pub const RunPayload = struct {
+    event: Event,
+    /// `pre_run` only
+    prompt: ?[]const u8 = null,
+    /// `post_run` only: "finished" or "failed"
+    status: ?[]const u8 = null,
+    message: ?[]const u8 = null,
+    model: ?[]const u8 = null,
+    seconds: ?i64 = null,
+};
+
+pub const PermissionPayload = struct {
+    event: Event = .on_permission,
+    tool: []const u8,
+    level: []const u8,
+    /// the file of an edit or write
+    path: ?[]const u8 = null,
+    arguments: ?std.json.Value = null,
+};
+
+const Running = struct {
+    handle: prv.exec.CmdPool.Handle,
+    event: Event,
+};
+
+pub const PermissionVerdict = union(enum) {
+    allow,
+    deny: []const u8,
+};
+
+/// A policy check on the command pool, its hooks one after the other.
+/// Polled until it has a verdict, so a slow hook never holds up the UI.
+pub const Pending = struct {
+    event: Event,
+    stdin: []const u8,
+    /// index into `Hooks.hooks` to look for the next hook from
+    next: usize = 0,
+    handle: ?prv.exec.CmdPool.Handle = null,
+    started_ms: i64 = 0,
+    notes: std.Io.Writer.Allocating,
+
+    pub fn init(alloc: std.mem.Allocator, event: Event, payload: anytype) !Pending {
+        return .{
+            .event = event,
+            .stdin = try std.json.Stringify.valueAlloc(alloc, payload, .{ .emit_null_optional_fields = false }),
+            .notes = .init(alloc),
+        };
+    }
+
+    /// Reaps the running hook and starts the next one, the verdict once a
+    /// hook vetoed or all of them passed.
+    pub fn poll(self: *Pending, hooks: *const Hooks, app: *App) ?Verdict {
+        const alloc = self.notes.allocator;
+        const name = @tagName(self.event);
+        while (true) {
+            if (self.handle) |handle| {
+                const res = app.swarm.exec.poll(handle) orelse {
+                    if (prv.http.nowMs(app.io) - self.started_ms <= POLICY_TIMEOUT_MS) return null;
+                    self.cancel(app);
+                    return .{ .veto = std.fmt.allocPrint(alloc, "{s} hook timed out after {d}s", .{ name, POLICY_TIMEOUT_MS / std.time.ms_per_s }) catch "hook timed out" };
+                };
+                defer {
+                    app.swarm.exec.release(handle);
+                    self.handle = null;
+                }
+                const out = std.mem.trim(u8, res.stdout, " \t\r\n");
+                switch (res.ty) {
+                    .success => if (out.len > 0) {
+                        if (self.notes.written().len > 0) self.notes.writer.writeByte('\n') catch {};
+                        self.notes.writer.writeAll(out) catch {};
+                    },
+                    .failed => {
+                        const err = std.mem.trim(u8, res.stderr, " \t\r\n");
+                        const reason = if (out.len > 0) out else if (err.len > 0) err else "no reason given";
+                        return .{ .veto = alloc.dupe(u8, reason) catch "vetoed" };
+                    },
+                    .timeout => return .{ .veto = std.fmt.allocPrint(alloc, "{s} hook timed out", .{name}) catch "hook timed out" },
+                }
+            }
+
+            while (self.next < hooks.hooks.items.len and hooks.hooks.items[self.next].event != self.event) self.next += 1;
+            if (self.next >= hooks.hooks.items.len) return .{ .allow = self.notes.written() };
+            const hook = hooks.hooks.items[self.next];
+            self.next += 1;
+            self.handle = app.swarm.exec.runWithOpts(.{
+                .cwd = app.cwd,
+                .argv = &.{ "sh", "-c", hook.command },
+                .stdin_data = self.stdin,
+                .force_local = true,
+            }) catch |err| {
+                return .{ .veto = std.fmt.allocPrint(alloc, "{s} hook did not run: {s}", .{ name, @errorName(err) }) catch "hook did not run" };
+            };
+            self.started_ms = prv.http.nowMs(app.io);
+        }
+    }
+
+    /// Stops the running hook, the check gives no verdict.
+    pub fn cancel(self: *Pending, app: *App) void {
+        if (self.handle) |handle| app.swarm.exec.cancel(handle);
+        self.handle = null;
+    }
+};
+
+pub const Hooks = struct {
+    hooks: std.ArrayList(Hook) = .empty,
+    running: std.ArrayList(Running) = .empty,
+    /// the `pre_run` check holding back a prompt of the main agent
+    run_check: ?struct { agent: prv.Swarm.AgentId, messages: usize, pending: Pending } = null,
+    /// the `on_permission` check holding back the permission queue
+    permission_check: ?struct { req: *const prv.Swarm.PermissionReq, pending: Pending } = null,
+
+    pub fn add(self: *Hooks, alloc: std.mem.Allocator, hook: Hook) !void {
+        try self.hooks.append(alloc, .{
+            .event = hook.event,
+            .command = try alloc.dupe(u8, hook.command),
+            .min_seconds = hook.min_seconds,
+        });
+    }
+
+    /// Drops the hooks of the previous config, on reload.
+    pub fn clear(self: *Hooks) void {
+        self.hooks.clearRetainingCapacity();
+    }
+
+    pub fn subscriber(self: *Hooks) events.Subscriber {
+        return .{ .ptr = self, .topics = .initOne(.agent), .on_event = onEvent };
+    }
+
+    fn onEvent(ptr: *anyopaque, app: *App, event: events.AppEvent) void {
+        const self: *Hooks = @ptrCast(@alignCast(ptr));
+        if (self.hooks.items.len == 0) return;
+        const main_id = app.main_agent_id orelse return;
+        switch (event) {
+            .agent_complete => |id| {
+                if (!std.meta.eql(id, main_id) or app.run_state == .cancelling) return;
+                const agent = app.swarm.getAgent(id) orelse return;
+                self.fireRun(app, .turn_finished, agent, lastAgentText(agent));
+            },
+            .agent_failed => |ev| {
+                if (!std.meta.eql(ev.id, main_id)) return;
+                const agent = app.swarm.getAgent(ev.id) orelse return;
+                const message = if (ev.err.len > 0)
+                    ev.err
+                else if (agent.last_error) |err|
+                    @errorName(err)
+                else
+                    "run failed";
+                self.fireRun(app, .@"error", agent, message);
+            },
+            else => {},
+        }
+    }
+
+    fn fireRun(self: *Hooks, app: *App, event: Event, agent: *prv.agent.Agent, message: []const u8) void {
+        const seconds = @divTrunc(prv.http.nowMs(app.io) - agent.timing.start_ms, std.time.ms_per_s);
+        const vars: Vars = .{ .event = event, .message = message, .model = agent.config.model, .seconds = seconds };
+        self.fire(app, vars);
+        if (!self.has(.post_run)) return;
+        const payload = std.json.Stringify.valueAlloc(app.sessionAlloc(), RunPayload{
+            .event = .post_run,
+            .status = if (event == .turn_finished) "finished" else "failed",
+            .message = message,
+            .model = vars.model,
+            .seconds = seconds,
+        }, .{ .emit_null_optional_fields = false }) catch return;
+        for (self.hooks.items) |hook| {
+            if (hook.event != .post_run or seconds < hook.min_seconds) continue;
+            self.start(app, hook, .{ .event = .post_run, .message = message, .model = vars.model, .seconds = seconds }, payload) catch |err| {
+                app.notifications.push(app.arena_app.allocator(), .warn, "post_run hook not started: {s}", .{@errorName(err)}) catch {};
+            };
+        }
+    }
+
+    /// Starts every hook of `vars.event`. Called for `permission_requested`
+    /// once the prompt is on screen, so auto approved calls stay quiet.
+    pub fn fire(self: *Hooks, app: *App, vars: Vars) void {
+        var cost_buf: [32]u8 = undefined;
+        var filled = vars;
+        if (filled.cost.len == 0) {
+            if (app.sessionCost()) |dollars| filled.cost = r.cost.format(&cost_buf, dollars);
+        }
+        for (self.hooks.items) |hook| {
+            if (hook.event != vars.event) continue;
+            if (vars.event != .permission_requested and vars.seconds < hook.min_seconds) continue;
+            self.start(app, hook, filled, null) catch |err| {
+                app.notifications.push(app.arena_app.allocator(), .warn, "{s} hook not started: {s}", .{ @tagName(hook.event), @errorName(err) }) catch {};
+            };
+        }
+    }
+
+    fn start(self: *Hooks, app: *App, hook: Hook, vars: Vars, stdin: ?[]const u8) !void {
+        if (self.running.items.len >= MAX_RUNNING) return error.TooManyRunning;
+        const alloc = app.sessionAlloc();
+        const command = try render(alloc, hook.command, vars);
+        const handle = try app.swarm.exec.runWithOpts(.{
+            .cwd = app.cwd,
+            .argv = &.{ "sh", "-c", command },
+            .stdin_data = stdin,
+            .force_local = true,
+        });
+        errdefer app.swarm.exec.release(handle);
+        try self.running.append(app.appAlloc(), .{ .handle = handle, .event = hook.event });
+    }
+
+    pub fn has(self: *const Hooks, event: Event) bool {
+        for (self.hooks.items) |hook| {
+            if (hook.event == event) return true;
+        }
+        return false;
+    }
+
+    /// Runs the `event` policy hooks one after the other with `payload` as
+    /// JSON on stdin, up to the first veto. Waits for them, only for threads
+    /// that may block: the tick thread polls a `Pending` instead.
+    pub fn check(self: *const Hooks, app: *App, alloc: std.mem.Allocator, event: Event, payload: anytype) Verdict {
+        if (!self.has(event)) return .{ .allow = "" };
+        var pending = Pending.init(alloc, event, payload) catch return .{ .veto = "out of memory" };
+        while (true) {
+            if (pending.poll(self, app)) |verdict| return verdict;
+            std.Io.sleep(app.io, .fromMilliseconds(25), .real) catch {
+                pending.cancel(app);
+                return .{ .veto = "canceled" };
+            };
+        }
+    }
+
+    /// The `pre_run` verdict on the prompt `agent` is about to send. Only
+    /// the main agent's prompts are checked, the hooks run on the command
+    /// pool and the agent asks again every tick until they are done.
+    pub fn preRun(self: *Hooks, app: *App, agent: *prv.agent.Agent) prv.Swarm.PreRun {
+        const id = agent.swarm_id orelse return .{ .go = "" };
+        const main_id = app.main_agent_id orelse return .{ .go = "" };
+        if (!std.meta.eql(id, main_id) or !self.has(.pre_run)) return .{ .go = "" };
+
+        // a prompt sent again after a cancel is checked from the start
+        if (self.run_check) |*held| {
+            if (!std.meta.eql(held.agent, id) or held.messages != agent.chat.messages.items.len) {
+                held.pending.cancel(app);
+                self.run_check = null;
+            }
+        }
+        if (self.run_check == null) {
+            const alloc = app.sessionAlloc();
+            var prompt: std.Io.Writer.Allocating = .init(alloc);
+            for (agent.uncheckedPrompt()) |msg| for (msg.parts) |part| switch (part) {
+                .text => |text| prompt.writer.writeAll(text) catch {},
+                else => {},
+            };
+            const pending = Pending.init(alloc, .pre_run, RunPayload{ .event = .pre_run, .prompt = prompt.written() }) catch
+                return .{ .veto = "out of memory" };
+            self.run_check = .{ .agent = id, .messages = agent.chat.messages.items.len, .pending = pending };
+        }
+
+        const verdict = self.run_check.?.pending.poll(self, app) orelse return .wait;
+        self.run_check = null;
+        switch (verdict) {
+            .veto => |reason| {
+                app.notifications.push(app.arena_app.allocator(), .warn, "pre_run hook held the prompt back: {s}", .{reason}) catch {};
+                return .{ .veto = reason };
+            },
+            .allow => |note| return .{
+                .go = if (note.len > 0) std.fmt.allocPrint(app.sessionAlloc(), "[hook] {s}", .{note}) catch "" else "",
+            },
+        }
+    }
+
+    /// Runs a tool call between its `pre_tool` and `post_tool` hooks. Called
+    /// on the tool's thread through the swarm context.
+    pub fn runTool(self: *const Hooks, app: *App, func: prv.tool.ToolFn, ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+        if (!self.has(.pre_tool) and !self.has(.post_tool)) return func(ctx, call);
+        const alloc = ctx.alloc;
+        const arguments = std.json.parseFromSliceLeaky(std.json.Value, alloc, call.arguments, .{}) catch
+            std.json.Value{ .string = call.arguments };
+        var payload: ToolPayload = .{ .event = .pre_tool, .tool = call.name, .arguments = arguments, .cwd = ctx.cwd };
+
+        const pre_note = switch (self.check(app, alloc, .pre_tool, payload)) {
+            .veto => |reason| return .{
+                .call_id = call.id,
+                .name = call.name,
+                .content = std.fmt.allocPrint(alloc, "Blocked by a pre_tool hook: {s}", .{reason}) catch "Blocked by a pre_tool hook",
+                .is_error = true,
+            },
+            .allow => |note| note,
+        };
+
+        var result = func(ctx, call);
+        payload.event = .post_tool;
+        payload.result = result.content;
+        payload.is_error = result.is_error;
+        const post_note = switch (self.check(app, alloc, .post_tool, payload)) {
+            .veto => |reason| blk: {
+                result.is_error = true;
+                break :blk std.fmt.allocPrint(alloc, "post_tool hook failed: {s}", .{reason}) catch reason;
+            },
+            .allow => |note| note,
+        };
+        for ([_][]const u8{ pre_note, post_note }) |note| {
+            if (note.len == 0) continue;
+            result.content = std.fmt.allocPrint(alloc, "{s}\n\n[hook] {s}", .{ result.content, note }) catch result.content;
+        }
+        return result;
+    }
+
+    /// Runs the `on_permission` hooks for a pending request, the reason
+    /// when one of them denies it. Questions and plans are left to the user.
+    /// Waits for the hooks, the TUI polls `permissionVerdict` instead.
+    pub fn vetoPermission(self: *const Hooks, app: *App, req: *const prv.Swarm.PermissionReq) ?[]const u8 {
+        const alloc = app.sessionAlloc();
+        const payload = permissionPayload(alloc, req) orelse return null;
+        return permissionVeto(app, self.check(app, alloc, .on_permission, payload));
+    }
+
+    /// `vetoPermission` on the command pool: null while the hooks of `req`
+    /// still run, then the verdict. Asked again every tick until it has one.
+    pub fn permissionVerdict(self: *Hooks, app: *App, req: *const prv.Swarm.PermissionReq) ?PermissionVerdict {
+        if (!self.has(.on_permission)) return .allow;
+        if (self.permission_check) |held| {
+            if (held.req != req) self.dropPermissionCheck(app);
+        }
+        if (self.permission_check == null) {
+            const alloc = app.sessionAlloc();
+            const payload = permissionPayload(alloc, req) orelse return .allow;
+            const pending = Pending.init(alloc, .on_permission, payload) catch return .{ .deny = "out of memory" };
+            self.permission_check = .{ .req = req, .pending = pending };
+        }
+        const verdict = self.permission_check.?.pending.poll(self, app) orelse return null;
+        self.permission_check = null;
+        return if (permissionVeto(app, verdict)) |reason| .{ .deny = reason } else .allow;
+    }
+
+    /// Stops the `on_permission` hooks of a request nobody waits for anymore.
+    pub fn dropPermissionCheck(self: *Hooks, app: *App) void {
+        if (self.permission_check) |*held| held.pending.cancel(app);
+        self.permission_check = null;
+    }
+
+    /// Stops every policy check in flight, before a cancel or reset frees
+    /// the command pool slots they poll.
+    pub fn dropChecks(self: *Hooks, app: *App) void {
+        self.dropPermissionCheck(app);
+        if (self.run_check) |*held| held.pending.cancel(app);
+        self.run_check = null;
+    }
+
+    /// Releases finished hook commands and reports the ones that failed.
+    pub fn poll(self: *Hooks, app: *App) void {
+        var i: usize = 0;
+        while (i < self.running.items.len) {
+            const entry = self.running.items[i];
+            const res = app.swarm.exec.poll(entry.handle) orelse {
+                i += 1;
+                continue;
+            };
+            if (res.ty != .success) {
+                const stderr = std.mem.trim(u8, res.stderr, " \t\r\n");
+                const line = stderr[0 .. std.mem.indexOfScalar(u8, stderr, '\n') orelse stderr.len];
+                app.notifications.push(app.arena_app.allocator(), .warn, "hook failed ({s}): {s}", .{ @tagName(res.ty), line }) catch {};
+            } else if (entry.event == .post_run) {
+                const stdout = std.mem.trim(u8, res.stdout, " \t\r\n");
+                if (stdout.len > 0) app.notifications.push(app.arena_app.allocator(), .info, "post_run: {s}", .{stdout}) catch {};
+            }
+            app.swarm.exec.release(entry.handle);
+            _ = self.running.swapRemove(i);
+        }
+    }
+};
+
+fn permissionVeto(app: *App, verdict: Verdict) ?[]const u8 {
+    return switch (verdict) {
+        .veto => |reason| reason,
+        .allow => |note| {
+            if (note.len > 0) app.notifications.push(app.arena_app.allocator(), .info, "on_permission: {s}", .{note}) catch {};
+            return null;
+        },
+    };
+}
+
+fn permissionPayload(alloc: std.mem.Allocator, req: *const prv.Swarm.PermissionReq) ?PermissionPayload {
+    const tool = r.headless.permissionTool(req.payload) orelse return null;
+    var payload: PermissionPayload = .{ .tool = tool, .level = @tagName(req.level) };
+    switch (req.payload) {
+        .diff => |diff| payload.path = diff.path,
+        .call => |c| payload.arguments = std.json.parseFromSliceLeaky(std.json.Value, alloc, c.tool_arguments, .{}) catch null,
+        else => {},
+    }
+    return payload;
+}
+
+/// The text of the agent's last answer, empty when it ended on a tool call.
+fn lastAgentText(agent: *prv.agent.Agent) []const u8 {
+    const messages = agent.chat.messages.items;
+    var i = messages.len;
+    while (i > 0) {
+        i -= 1;
+        if (messages[i].role != .agent) continue;
+        for (messages[i].parts) |part| switch (part) {
+            .text => |text| return std.mem.trim(u8, text, " \t\r\n"),
+            else => {},
+        };
+        return "";
+    }
+    return "";
+}
+
+/// Fills the placeholders of a hook command, every value shell quoted.
+pub fn render(alloc: std.mem.Allocator, template: []const u8, vars: Vars) ![]const u8 {
+    const json = try std.json.Stringify.valueAlloc(alloc, vars, .{});
+    const args = try std.json.parseFromSliceLeaky(std.json.Value, alloc, json, .{});
+    return r.tools.command.expand(alloc, template, args.object);
+}
+
+test "hook commands" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const vars: Vars = .{ .event = .turn_finished, .message = "it's done", .model = "claude-sonnet-4-5", .cost = "$0.12", .seconds = 95 };
+    try std.testing.expectEqualStrings(
+        "notify-send 'turn_finished' 'it'\\''s done' # 'claude-sonnet-4-5' '$0.12' '95'",
+        try render(alloc, "notify-send {event} {message} # {model} {cost} {seconds}", vars),
+    );
+    try std.testing.expectEqualStrings(
+        "curl -d \"$HOME\" ${URL}",
+        try render(alloc, "curl -d \"$HOME\" ${URL}", vars),
+    );
+    try std.testing.expectEqual(Event.@"error", std.meta.stringToEnum(Event, "error").?);
+}
+
+test "policy hook payloads" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const arguments = try std.json.parseFromSliceLeaky(std.json.Value, alloc, "{\"path\":\"gen/api.zig\"}", .{});
+    const payload: ToolPayload = .{ .event = .pre_tool, .tool = "edit", .arguments = arguments, .cwd = "/src/app" };
+    try std.testing.expectEqualStrings(
+        "{\"event\":\"pre_tool\",\"tool\":\"edit\",\"arguments\":{\"path\":\"gen/api.zig\"},\"cwd\":\"/src/app\"}",
+        try std.json.Stringify.valueAlloc(alloc, payload, .{ .emit_null_optional_fields = false }),
+    );
+    try std.testing.expectEqualStrings(
+        "{\"event\":\"pre_run\",\"prompt\":\"fix it\"}",
+        try std.json.Stringify.valueAlloc(alloc, RunPayload{ .event = .pre_run, .prompt = "fix it" }, .{ .emit_null_optional_fields = false }),
+    );
+}
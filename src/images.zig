@@ -0,0 +1,94 @@
+//! `@image:path` in a prompt attaches the file as an image part. The ref is
+//! dropped from the prompt text, the file is read from the local disk and
+//! sent base64 encoded next to the text.
+const std = @import("std");
+const prv = @import("provider");
+
+pub const PREFIX = "@image:";
+pub const MAX_IMAGE_BYTES = 20 * 1024 * 1024;
+
+pub const Refs = struct {
+    /// the prompt without its image refs
+    text: []const u8,
+    paths: []const []const u8,
+};
+
+/// Split `@image:` refs off `input`. Refs are whitespace separated words,
+/// anything else stays as typed.
+pub fn parseRefs(alloc: std.mem.Allocator, input: []const u8) !Refs {
+    if (std.mem.indexOf(u8, input, PREFIX) == null) return .{ .text = input, .paths = &.{} };
+
+    var text = std.ArrayList(u8).empty;
+    var paths = std.ArrayList([]const u8).empty;
+    var i: usize = 0;
+    while (i < input.len) {
+        const word_start = i == 0 or std.ascii.isWhitespace(input[i - 1]);
+        if (word_start and std.mem.startsWith(u8, input[i..], PREFIX)) {
+            const start = i + PREFIX.len;
+            var end = start;
+            while (end < input.len and !std.ascii.isWhitespace(input[end])) end += 1;
+            if (end > start) {
+                try paths.append(alloc, input[start..end]);
+                i = end;
+                // eat one separator so `a @image:x b` reads `a b`
+                if (i < input.len and input[i] == ' ') i += 1;
+                continue;
+            }
+        }
+        try text.append(alloc, input[i]);
+        i += 1;
+    }
+    return .{
+        .text = std.mem.trim(u8, try text.toOwnedSlice(alloc), " \t\n"),
+        .paths = try paths.toOwnedSlice(alloc),
+    };
+}
+
+pub fn mediaType(path: []const u8) ?[]const u8 {
+    const ext = std.fs.path.extension(path);
+    var buf: [8]u8 = undefined;
+    if (ext.len == 0 or ext.len > buf.len) return null;
+    const lower = std.ascii.lowerString(&buf, ext);
+    if (std.mem.eql(u8, lower, ".png")) return "image/png";
+    if (std.mem.eql(u8, lower, ".jpg") or std.mem.eql(u8, lower, ".jpeg")) return "image/jpeg";
+    if (std.mem.eql(u8, lower, ".gif")) return "image/gif";
+    if (std.mem.eql(u8, lower, ".webp")) return "image/webp";
+    return null;
+}
+
+/// Read `path` (relative to `cwd`) into a base64 image part.
+pub fn load(io: std.Io, alloc: std.mem.Allocator, cwd: []const u8, path: []const u8) !prv.adapter.ImageContent {
+    const media_type = mediaType(path) orelse return error.UnsupportedImageType;
+    const resolved = try std.fs.path.resolve(alloc, &.{ cwd, path });
+    defer alloc.free(resolved);
+
+    const file = try std.Io.Dir.cwd().openFile(io, resolved, .{});
+    defer file.close(io);
+    var read_buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &read_buf);
+    const raw = try reader.interface.allocRemaining(alloc, .limited(MAX_IMAGE_BYTES));
+    defer alloc.free(raw);
+
+    const encoded = try alloc.alloc(u8, std.base64.standard.Encoder.calcSize(raw.len));
+    _ = std.base64.standard.Encoder.encode(encoded, raw);
+    return .{ .media_type = media_type, .data = encoded };
+}
+
+test "image refs" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const refs = try parseRefs(alloc, "compare @image:shots/a.png with @image:b.JPG");
+    try std.testing.expectEqualStrings("compare with", refs.text);
+    try std.testing.expectEqual(@as(usize, 2), refs.paths.len);
+    try std.testing.expectEqualStrings("shots/a.png", refs.paths[0]);
+    try std.testing.expectEqualStrings("b.JPG", refs.paths[1]);
+
+    const plain = try parseRefs(alloc, "mail me@image:x.png");
+    try std.testing.expectEqualStrings("mail me@image:x.png", plain.text);
+    try std.testing.expectEqual(@as(usize, 0), plain.paths.len);
+
+    try std.testing.expectEqualStrings("image/jpeg", mediaType("b.JPG").?);
+    try std.testing.expect(mediaType("notes.txt") == null);
+}
@@ -0,0 +1,395 @@
+//! `blitz import`, a one-shot importer for settings of other agent CLIs.
+//! Detects what it can, lists it and asks once before copying:
+//!
+//!   API keys      ~/.claude/settings.json `env`, ~/.codex/auth.json,
+//!                 ~/.aider.conf.yml -> ~/.config/blitzdenk/keys.env
+//!   allow rules   ~/.claude/settings.json `Bash(...)` -> .blitz/bash_allow
+//!   ignores       .aiderignore -> sensitive path globs in ./blitz.lua
+//!   commands      ~/.claude/commands/*.md, ~/.codex/prompts/*.md -> skills
+//!
+//! Keys stay out of blitz.lua, `keys.env` only fills variables the
+//! environment does not already set.
+const std = @import("std");
+const allowlist = @import("allowlist.zig");
+const sensitive = @import("sensitive.zig");
+
+pub const CONFIG_DIR = ".config/blitzdenk/";
+pub const KEYS_FILE = CONFIG_DIR ++ "keys.env";
+const MAX_FILE_BYTES = 256 * 1024;
+const MAX_COMMANDS = 64;
+
+pub const Source = enum {
+    claude_code,
+    codex,
+    aider,
+
+    pub fn label(self: Source) []const u8 {
+        return switch (self) {
+            .claude_code => "Claude Code",
+            .codex => "Codex",
+            .aider => "aider",
+        };
+    }
+};
+
+pub const Key = struct { name: []const u8, value: []const u8, source: Source };
+pub const Command = struct { name: []const u8, body: []const u8, source: Source };
+
+pub const Found = struct {
+    keys: std.ArrayList(Key) = .empty,
+    allow: std.ArrayList([]const u8) = .empty,
+    ignore: std.ArrayList([]const u8) = .empty,
+    commands: std.ArrayList(Command) = .empty,
+
+    pub fn isEmpty(self: *const Found) bool {
+        return self.keys.items.len == 0 and self.allow.items.len == 0 and
+            self.ignore.items.len == 0 and self.commands.items.len == 0;
+    }
+
+    fn addKey(self: *Found, alloc: std.mem.Allocator, key: Key) !void {
+        if (key.value.len == 0) return;
+        for (self.keys.items) |k| {
+            if (std.mem.eql(u8, k.name, key.name)) return;
+        }
+        try self.keys.append(alloc, key);
+    }
+};
+
+/// Scan `home` and the project in `cwd`. Unreadable sources are skipped.
+pub fn scan(io: std.Io, alloc: std.mem.Allocator, home: std.Io.Dir, cwd: std.Io.Dir) !Found {
+    var found: Found = .{};
+    if (readFile(io, alloc, home, ".claude/settings.json")) |content| {
+        parseClaudeSettings(alloc, &found, content) catch |err| std.log.warn("import: claude settings: {s}", .{@errorName(err)});
+    }
+    if (readFile(io, alloc, home, ".codex/auth.json")) |content| {
+        parseCodexAuth(alloc, &found, content) catch |err| std.log.warn("import: codex auth: {s}", .{@errorName(err)});
+    }
+    if (readFile(io, alloc, home, ".aider.conf.yml")) |content| try parseAiderConf(alloc, &found, content);
+    if (readFile(io, alloc, cwd, ".aiderignore")) |content| try parseIgnore(alloc, &found, content);
+    try scanCommands(io, alloc, home, ".claude/commands", .claude_code, &found);
+    try scanCommands(io, alloc, home, ".codex/prompts", .codex, &found);
+    return found;
+}
+
+fn readFile(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, path: []const u8) ?[]const u8 {
+    const file = dir.openFile(io, path, .{}) catch return null;
+    defer file.close(io);
+    var read_buf: [1024]u8 = undefined;
+    var file_reader = file.reader(io, &read_buf);
+    return file_reader.interface.allocRemaining(alloc, .limited(MAX_FILE_BYTES)) catch null;
+}
+
+fn scanCommands(io: std.Io, alloc: std.mem.Allocator, home: std.Io.Dir, path: []const u8, source: Source, found: *Found) !void {
+    var dir = home.openDir(io, path, .{ .iterate = true }) catch return;
+    defer dir.close(io);
+    var it = dir.iterate();
+    while (it.next(io) catch return) |entry| {
+        if (entry.kind != .file or !std.mem.endsWith(u8, entry.name, ".md")) continue;
+        if (found.commands.items.len >= MAX_COMMANDS) return;
+        const body = readFile(io, alloc, dir, entry.name) orelse continue;
+        try found.commands.append(alloc, .{
+            .name = try alloc.dupe(u8, entry.name[0 .. entry.name.len - 3]),
+            .body = body,
+            .source = source,
+        });
+    }
+}
+
+/// `env` entries ending in `_API_KEY` and `Bash(...)` permission rules.
+pub fn parseClaudeSettings(alloc: std.mem.Allocator, found: *Found, content: []const u8) !void {
+    const root = try std.json.parseFromSliceLeaky(std.json.Value, alloc, content, .{});
+    if (root != .object) return;
+    if (root.object.get("env")) |env| {
+        if (env == .object) {
+            var it = env.object.iterator();
+            while (it.next()) |e| {
+                if (!std.mem.endsWith(u8, e.key_ptr.*, "_API_KEY") or e.value_ptr.* != .string) continue;
+                try found.addKey(alloc, .{ .name = e.key_ptr.*, .value = e.value_ptr.string, .source = .claude_code });
+            }
+        }
+    }
+    const perms = root.object.get("permissions") orelse return;
+    if (perms != .object) return;
+    const allow = perms.object.get("allow") orelse return;
+    if (allow != .array) return;
+    for (allow.array.items) |rule| {
+        if (rule != .string) continue;
+        const pattern = try claudeBashPattern(alloc, rule.string) orelse continue;
+        try found.allow.append(alloc, pattern);
+    }
+}
+
+/// `Bash(cargo test:*)` -> `cargo test *`, `Bash(git status)` -> `git status`.
+pub fn claudeBashPattern(alloc: std.mem.Allocator, rule: []const u8) !?[]const u8 {
+    if (!std.mem.startsWith(u8, rule, "Bash(") or !std.mem.endsWith(u8, rule, ")")) return null;
+    const inner = std.mem.trim(u8, rule[5 .. rule.len - 1], " ");
+    if (inner.len == 0) return null;
+    if (std.mem.endsWith(u8, inner, ":*")) {
+        return try std.fmt.allocPrint(alloc, "{s} *", .{inner[0 .. inner.len - 2]});
+    }
+    return try alloc.dupe(u8, inner);
+}
+
+pub fn parseCodexAuth(alloc: std.mem.Allocator, found: *Found, content: []const u8) !void {
+    const root = try std.json.parseFromSliceLeaky(std.json.Value, alloc, content, .{});
+    if (root != .object) return;
+    const key = root.object.get("OPENAI_API_KEY") orelse return;
+    if (key != .string) return;
+    try found.addKey(alloc, .{ .name = "OPENAI_API_KEY", .value = key.string, .source = .codex });
+}
+
+/// Flat `key: value` lines, enough for the api key entries.
+pub fn parseAiderConf(alloc: std.mem.Allocator, found: *Found, content: []const u8) !void {
+    const known = [_]struct { []const u8, []const u8 }{
+        .{ "openai-api-key", "OPENAI_API_KEY" },
+        .{ "anthropic-api-key", "ANTHROPIC_API_KEY" },
+    };
+    var it = std.mem.splitScalar(u8, content, '\n');
+    while (it.next()) |line| {
+        const trimmed = std.mem.trim(u8, line, " \t\r");
+        const colon = std.mem.indexOfScalar(u8, trimmed, ':') orelse continue;
+        const key = std.mem.trim(u8, trimmed[0..colon], " ");
+        const value = std.mem.trim(u8, trimmed[colon + 1 ..], " \"'");
+        for (known) |k| {
+            if (std.mem.eql(u8, key, k[0])) {
+                try found.addKey(alloc, .{ .name = k[1], .value = value, .source = .aider });
+            }
+        }
+    }
+}
+
+/// gitignore style lines to sensitive globs, negations are dropped.
+pub fn parseIgnore(alloc: std.mem.Allocator, found: *Found, content: []const u8) !void {
+    var it = std.mem.splitScalar(u8, content, '\n');
+    while (it.next()) |line| {
+        var pattern = std.mem.trim(u8, line, " \t\r");
+        if (pattern.len == 0 or pattern[0] == '#' or pattern[0] == '!') continue;
+        if (pattern[0] == '/') pattern = pattern[1..];
+        if (std.mem.endsWith(u8, pattern, "/")) {
+            try found.ignore.append(alloc, try std.fmt.allocPrint(alloc, "{s}**", .{pattern}));
+        } else if (pattern.len > 0) {
+            try found.ignore.append(alloc, pattern);
+        }
+    }
+}
+
+/// Interactive entry point for `blitz import`.
+pub fn run(io: std.Io, alloc: std.mem.Allocator, home_path: []const u8) !void {
+    var home = try std.Io.Dir.openDirAbsolute(io, home_path, .{});
+    defer home.close(io);
+    const found = try scan(io, alloc, home, std.Io.Dir.cwd());
+    if (found.isEmpty()) {
+        std.debug.print("nothing to import from Claude Code, Codex or aider\n", .{});
+        return;
+    }
+
+    std.debug.print("Found:\n", .{});
+    for (found.keys.items) |k| std.debug.print("  key      {s} ({s}, ...{s})\n", .{ k.name, k.source.label(), k.value[k.value.len -| 4..] });
+    for (found.allow.items) |p| std.debug.print("  allow    {s}\n", .{p});
+    for (found.ignore.items) |p| std.debug.print("  ignore   {s}\n", .{p});
+    for (found.commands.items) |c| std.debug.print("  command  {s} ({s})\n", .{ c.name, c.source.label() });
+    std.debug.print("Import into blitzdenk? [y/N] ", .{});
+
+    var in_buf: [64]u8 = undefined;
+    var stdin = std.Io.File.stdin().reader(io, &in_buf);
+    const answer = stdin.interface.takeDelimiter('\n') catch null orelse "";
+    if (!std.mem.startsWith(u8, std.mem.trim(u8, answer, " \r"), "y")) {
+        std.debug.print("nothing imported\n", .{});
+        return;
+    }
+
+    try home.createDirPath(io, CONFIG_DIR);
+    if (found.keys.items.len > 0) {
+        const added = try writeKeys(io, alloc, home, found.keys.items);
+        std.debug.print("{d} key(s) -> ~/{s}\n", .{ added, KEYS_FILE });
+    }
+    if (found.allow.items.len > 0) {
+        var list: allowlist.BashAllowList = .{};
+        list.load(io, alloc);
+        var added: usize = 0;
+        for (found.allow.items) |p| {
+            if (try list.add(alloc, p)) added += 1;
+        }
+        try list.save(io);
+        std.debug.print("{d} allow pattern(s) -> {s}\n", .{ added, allowlist.FILENAME });
+    }
+    if (found.ignore.items.len > 0) {
+        try writeSensitivePaths(io, alloc, std.Io.Dir.cwd(), found.ignore.items);
+        std.debug.print("{d} ignore pattern(s) -> ./blitz.lua\n", .{found.ignore.items.len});
+    }
+    if (found.commands.items.len > 0) {
+        const added = try writeSkills(io, alloc, home, found.commands.items);
+        std.debug.print("{d} command(s) -> ~/" ++ CONFIG_DIR ++ "skills/\n", .{added});
+    }
+}
+
+/// Appends keys not already in `keys.env`, returns how many were new.
+fn writeKeys(io: std.Io, alloc: std.mem.Allocator, home: std.Io.Dir, found_keys: []const Key) !usize {
+    const existing = readFile(io, alloc, home, KEYS_FILE) orelse "";
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    try out.writer.writeAll(existing);
+    if (existing.len > 0 and existing[existing.len - 1] != '\n') try out.writer.writeByte('\n');
+
+    var added: usize = 0;
+    for (found_keys) |k| {
+        if (lookupKey(existing, k.name) != null) continue;
+        try out.writer.print("{s}={s}\n", .{ k.name, k.value });
+        added += 1;
+    }
+    const file = try home.createFile(io, KEYS_FILE, .{ .truncate = true, .permissions = .fromMode(0o600) });
+    defer file.close(io);
+    var write_buf: [1024]u8 = undefined;
+    var writer = file.writer(io, &write_buf);
+    try writer.interface.writeAll(out.written());
+    try writer.interface.flush();
+    return added;
+}
+
+fn lookupKey(content: []const u8, name: []const u8) ?[]const u8 {
+    var it = std.mem.splitScalar(u8, content, '\n');
+    while (it.next()) |line| {
+        const trimmed = std.mem.trim(u8, line, " \t\r");
+        const eq = std.mem.indexOfScalar(u8, trimmed, '=') orelse continue;
+        if (std.mem.eql(u8, trimmed[0..eq], name)) return trimmed[eq + 1 ..];
+    }
+    return null;
+}
+
+/// Fill unset variables in `env` from `~/.config/blitzdenk/keys.env`.
+pub fn loadKeys(io: std.Io, alloc: std.mem.Allocator, env: *std.process.Environ.Map) void {
+    const home_path = env.get("HOME") orelse return;
+    var home = std.Io.Dir.openDirAbsolute(io, home_path, .{}) catch return;
+    defer home.close(io);
+    const content = readFile(io, alloc, home, KEYS_FILE) orelse return;
+    var it = std.mem.splitScalar(u8, content, '\n');
+    while (it.next()) |line| {
+        const trimmed = std.mem.trim(u8, line, " \t\r");
+        if (trimmed.len == 0 or trimmed[0] == '#') continue;
+        const eq = std.mem.indexOfScalar(u8, trimmed, '=') orelse continue;
+        const name = trimmed[0..eq];
+        if (env.get(name) != null) continue;
+        env.put(name, trimmed[eq + 1 ..]) catch return;
+    }
+}
+
+const SENSITIVE_MARKER = "-- imported from .aiderignore by `blitz import`\n";
+
+/// `.aiderignore` belongs to the project, so its globs go into the
+/// project's `blitz.lua`. A block from an earlier import is replaced.
+fn writeSensitivePaths(io: std.Io, alloc: std.mem.Allocator, cwd: std.Io.Dir, patterns: []const []const u8) !void {
+    const existing = readFile(io, alloc, cwd, "blitz.lua") orelse "";
+    const content = try withSensitiveBlock(alloc, existing, patterns);
+    const file = try cwd.createFile(io, "blitz.lua", .{ .truncate = true });
+    defer file.close(io);
+    var write_buf: [1024]u8 = undefined;
+    var writer = file.writer(io, &write_buf);
+    try writer.interface.writeAll(content);
+    try writer.interface.flush();
+}
+
+/// `existing` with the imported `set_sensitive_paths` block swapped in.
+/// `set_sensitive_paths` replaces the list, so the defaults are carried over.
+pub fn withSensitiveBlock(alloc: std.mem.Allocator, existing: []const u8, patterns: []const []const u8) ![]const u8 {
+    var before = existing;
+    var after: []const u8 = "";
+    if (std.mem.indexOf(u8, existing, SENSITIVE_MARKER)) |start| {
+        before = existing[0..start];
+        if (std.mem.indexOfPos(u8, existing, start, "})\n")) |end| after = existing[end + 3 ..];
+    }
+
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    const w = &out.writer;
+    try w.writeAll(before);
+    if (before.len > 0 and before[before.len - 1] != '\n') try w.writeByte('\n');
+    try w.writeAll(SENSITIVE_MARKER ++ "blitz.set_sensitive_paths({\n");
+    var count: usize = 0;
+    for (sensitive.default_patterns) |p| {
+        try w.print("\t\"{s}\",\n", .{p});
+        count += 1;
+    }
+    for (patterns) |p| {
+        if (count >= sensitive.MAX_PATTERNS) break;
+        try w.print("\t{f},\n", .{std.json.fmt(p, .{})});
+        count += 1;
+    }
+    try w.writeAll("})\n");
+    try w.writeAll(after);
+    return out.written();
+}
+
+/// Commands become skills, existing skills with the same name are kept.
+fn writeSkills(io: std.Io, alloc: std.mem.Allocator, home: std.Io.Dir, commands: []const Command) !usize {
+    try home.createDirPath(io, CONFIG_DIR ++ "skills");
+    var added: usize = 0;
+    for (commands) |c| {
+        const path = try std.fmt.allocPrint(alloc, CONFIG_DIR ++ "skills/{s}.md", .{c.name});
+        if (home.statFile(io, path, .{})) |_| continue else |_| {}
+        const skill = try skillFromCommand(alloc, c);
+        const file = try home.createFile(io, path, .{});
+        defer file.close(io);
+        var write_buf: [1024]u8 = undefined;
+        var writer = file.writer(io, &write_buf);
+        try writer.interface.writeAll(skill);
+        try writer.interface.flush();
+        added += 1;
+    }
+    return added;
+}
+
+/// Skill markdown with a `name`/`description` header. The description is
+/// taken from the command's own front matter, else its first line.
+pub fn skillFromCommand(alloc: std.mem.Allocator, c: Command) ![]const u8 {
+    var body = c.body;
+    var description: []const u8 = "";
+    if (std.mem.startsWith(u8, body, "---\n")) {
+        if (std.mem.indexOf(u8, body[4..], "\n---")) |end| {
+            var lines = std.mem.splitScalar(u8, body[4..][0..end], '\n');
+            while (lines.next()) |line| {
+                if (std.mem.startsWith(u8, line, "description:")) description = std.mem.trim(u8, line["description:".len..], " \"'");
+            }
+            body = std.mem.trimLeft(u8, body[4 + end + 4 ..], "\n");
+        }
+    }
+    if (description.len == 0) {
+        const first = std.mem.sliceTo(std.mem.trimLeft(u8, body, "# \n"), '\n');
+        description = first[0..@min(first.len, 120)];
+    }
+    return std.fmt.allocPrint(alloc, "---\nname: {s}\ndescription: Imported {s} command. {s}\n---\n\n{s}", .{
+        c.name, c.source.label(), description, body,
+    });
+}
+
+test "import parsers" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+    var found: Found = .{};
+
+    try parseClaudeSettings(alloc, &found,
+        \\{"env": {"ANTHROPIC_API_KEY": "sk-ant-1", "DEBUG": "1"},
+        \\ "permissions": {"allow": ["Bash(cargo test:*)", "Bash(git status)", "Read(./src)"]}}
+    );
+    try parseAiderConf(alloc, &found, "model: sonnet\nopenai-api-key: \"sk-2\"\nanthropic-api-key: sk-3\n");
+    try parseIgnore(alloc, &found, "# comment\n/build/\n!keep.txt\n*.pem\n");
+
+    try std.testing.expectEqual(@as(usize, 2), found.keys.items.len);
+    try std.testing.expectEqualStrings("sk-ant-1", found.keys.items[0].value);
+    try std.testing.expectEqualStrings("OPENAI_API_KEY", found.keys.items[1].name);
+    try std.testing.expectEqualStrings("sk-2", found.keys.items[1].value);
+    try std.testing.expectEqualStrings("cargo test *", found.allow.items[0]);
+    try std.testing.expectEqualStrings("git status", found.allow.items[1]);
+    try std.testing.expectEqual(@as(usize, 2), found.allow.items.len);
+    try std.testing.expectEqualStrings("build/**", found.ignore.items[0]);
+    try std.testing.expectEqualStrings("*.pem", found.ignore.items[1]);
+
+    const skill = try skillFromCommand(alloc, .{ .name = "review", .body = "---\ndescription: Review the diff\n---\nLook at $ARGUMENTS", .source = .claude_code });
+    try std.testing.expectEqualStrings("---\nname: review\ndescription: Imported Claude Code command. Review the diff\n---\n\nLook at $ARGUMENTS", skill);
+
+    const once = try withSensitiveBlock(alloc, "blitz.set_model(\"x\")", &.{"*.pem"});
+    const twice = try withSensitiveBlock(alloc, once, &.{"*.pem"});
+    try std.testing.expectEqualStrings(once, twice);
+    try std.testing.expect(std.mem.startsWith(u8, once, "blitz.set_model(\"x\")\n" ++ SENSITIVE_MARKER));
+    const replaced = try withSensitiveBlock(alloc, try std.mem.concat(alloc, u8, &.{ once, "-- tail\n" }), &.{"*.key"});
+    try std.testing.expect(std.mem.indexOf(u8, replaced, "*.pem") == null);
+    try std.testing.expect(std.mem.endsWith(u8, replaced, "})\n-- tail\n"));
+}
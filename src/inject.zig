@@ -19,10 +19,12 @@ pub const InjectionsHooks = struct {
 
         inline for (.{
             &inject_mode_information,
+            &inject_research_information,
             &inject_todo_information,
             &inject_budget_information,
             &inject_processes_information,
             &inject_bg_agents_information,
+            &inject_directory_context,
         }) |cb| {
             try self._hooks.append(alloc, .{ .zig = cb });
         }
@@ -74,6 +76,106 @@ pub const InjectionsHooks = struct {
     }
 };
 
+const DIR_CONTEXT_FILE = "AGENTS.md";
+const DIR_CONTEXT_MAX_BYTES = 16 * 1024;
+const DIR_CONTEXT_MARKER = "[DIRECTORY CONTEXT] ";
+
+/// Area rules for monorepos: `frontend/AGENTS.md` is sent once, the first
+/// time a tool call touches a file below `frontend/`. The root AGENTS.md is
+/// part of the system prompt already.
+fn inject_directory_context(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.agent.Agent) !void {
+    if (app.context_factory.flags.skip_local_context_file) return;
+    const alloc = agent.arena.allocator();
+    const messages = agent.chat.messages.items;
+
+    // the agent turn whose tool calls just settled
+    var i = messages.len;
+    const last = while (i > 0) {
+        i -= 1;
+        if (messages[i].role == .agent) break messages[i];
+    } else return;
+
+    var sent: std.ArrayList([]const u8) = .empty;
+    for (last.parts) |part| {
+        if (part != .tool_call) continue;
+        const Args = struct { path: ?[]const u8 = null };
+        const args = std.json.parseFromSliceLeaky(Args, alloc, part.tool_call.arguments, .{ .ignore_unknown_fields = true }) catch continue;
+        const path = args.path orelse continue;
+
+        for (try contextDirs(alloc, app.cwd, path)) |dir| {
+            const rel = try std.fmt.allocPrint(alloc, "{s}/" ++ DIR_CONTEXT_FILE, .{dir});
+            if (containsStr(sent.items, rel) or alreadyInjected(messages, rel)) continue;
+            try sent.append(alloc, rel);
+
+            const abs = try std.fmt.allocPrint(alloc, "{s}/{s}", .{ app.cwd, rel });
+            const file = std.Io.Dir.cwd().openFile(app.io, abs, .{}) catch continue;
+            defer file.close(app.io);
+            var buf: [1024]u8 = undefined;
+            var file_reader = file.reader(app.io, &buf);
+            const raw = file_reader.interface.allocRemaining(alloc, .limited(DIR_CONTEXT_MAX_BYTES)) catch continue;
+            const content = try r.vars.expand(alloc, raw, &app.context_factory.prompt_vars);
+            try w.print(DIR_CONTEXT_MARKER ++ "{s}, rules for files under {s}/:\n{s}\n", .{ rel, dir, content });
+        }
+    }
+}
+
+fn containsStr(list: []const []const u8, needle: []const u8) bool {
+    for (list) |s| {
+        if (std.mem.eql(u8, s, needle)) return true;
+    }
+    return false;
+}
+
+fn alreadyInjected(messages: []const r.prv.adapter.Message, rel: []const u8) bool {
+    for (messages) |msg| {
+        if (msg.role != .user) continue;
+        for (msg.parts) |part| {
+            if (part != .text) continue;
+            var pos: usize = 0;
+            while (std.mem.indexOfPos(u8, part.text, pos, DIR_CONTEXT_MARKER)) |at| {
+                pos = at + DIR_CONTEXT_MARKER.len;
+                if (std.mem.startsWith(u8, part.text[pos..], rel)) return true;
+            }
+        }
+    }
+    return false;
+}
+
+/// Project relative directories containing `path`, outermost first. Paths
+/// outside `cwd` and the project root itself yield nothing.
+fn contextDirs(alloc: std.mem.Allocator, cwd: []const u8, path: []const u8) ![]const []const u8 {
+    var rel = path;
+    if (std.fs.path.isAbsolute(path)) {
+        if (!std.mem.startsWith(u8, path, cwd) or path.len <= cwd.len or path[cwd.len] != '/') return &.{};
+        rel = path[cwd.len + 1 ..];
+    }
+    while (std.mem.startsWith(u8, rel, "./")) rel = rel[2..];
+    if (std.mem.indexOf(u8, rel, "..") != null) return &.{};
+
+    var dirs: std.ArrayList([]const u8) = .empty;
+    var dir = std.fs.path.dirname(rel);
+    while (dir) |d| : (dir = std.fs.path.dirname(d)) {
+        try dirs.insert(alloc, 0, d);
+    }
+    return dirs.items;
+}
+
+test "contextDirs" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const dirs = try contextDirs(alloc, "/repo", "/repo/frontend/src/app.ts");
+    try std.testing.expectEqual(@as(usize, 2), dirs.len);
+    try std.testing.expectEqualStrings("frontend", dirs[0]);
+    try std.testing.expectEqualStrings("frontend/src", dirs[1]);
+
+    try std.testing.expectEqualStrings("backend", (try contextDirs(alloc, "/repo", "./backend/main.go"))[0]);
+    try std.testing.expectEqual(@as(usize, 0), (try contextDirs(alloc, "/repo", "README.md")).len);
+    try std.testing.expectEqual(@as(usize, 0), (try contextDirs(alloc, "/repo", "/repository/x/y.zig")).len);
+    try std.testing.expectEqual(@as(usize, 0), (try contextDirs(alloc, "/repo", "../other/x.zig")).len);
+}
+
 fn inject_processes_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.agent.Agent) !void {
     if (agent.bg_tasks.tryLock(app.swarm.pool.io)) |g| blk: {
         defer g.unlock();
@@ -137,10 +239,20 @@ fn inject_budget_information(w: *std.Io.Writer, _: *r.app.App, agent: *r.prv.age
 
 fn inject_todo_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.agent.Agent) !void {
     var has_todos: bool = false;
+    const changed = app.changedPaths(agent.arena.allocator()) catch &.{};
     if (agent.todo_list.tryLock(app.swarm.pool.io)) |g| {
         defer g.unlock();
         var unfinished: u32 = 0;
 
+        if (g.ptr.hasUnseenUserChanges()) {
+            try w.print("[TODO UPDATE] The user edited your todo list. Changed:", .{});
+            for (g.ptr.todos[0..g.ptr.count]) |t| {
+                if (t.changed_by == .user and t.rev > g.ptr.agent_seen) try w.print(" id:{d}", .{t.id});
+            }
+            try w.print(". Removed todos are gone, the list below is current.\n", .{});
+            g.ptr.markSeen();
+        }
+
         for (g.ptr.todos[0..g.ptr.count]) |t| {
             if (t.state != .done) unfinished += 1;
         }
@@ -150,9 +262,18 @@ fn inject_todo_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.age
                 .in_progress => {
                     try w.print("[ACTIVE TODO] id:{d} subject: {s}\n{s}\n", .{ t.id, t.subject, t.description });
                     has_todos = true;
+                    if (r.tools.todos.auditTodo(t, changed) == .touched) {
+                        try w.print("[TODO CHECK] id:{d} the files it references were modified. If the item is finished, mark it done with update_todo_state\n", .{t.id});
+                    }
                 },
                 .pending => {
-                    try w.print("[PENDING TODO] id:{d} subject: {s}\n", .{ t.id, t.subject });
+                    try w.print("[PENDING TODO] id:{d} subject: {s}", .{ t.id, t.subject });
+                    if (g.ptr.isBlocked(t)) {
+                        try w.writeAll(" (blocked, waits for");
+                        for (t.depends_on) |dep| try w.print(" id:{d}", .{dep});
+                        try w.writeByte(')');
+                    }
+                    try w.writeByte('\n');
                     has_todos = true;
                 },
                 else => {},
@@ -161,6 +282,19 @@ fn inject_todo_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.age
     }
 }
 
+fn inject_research_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.agent.Agent) !void {
+    if (agent.swarm_id != app.main_agent_id) return;
+    if (agent.mode_idx != @intFromEnum(r.ContextFactory.Mode.research)) return;
+
+    const turn = agent.iteration + 1;
+    if (turn >= app.research_turns) {
+        agent.chat.tools_disabled = true;
+        try w.print("[RESEARCH] Turn {d} of {d}, the budget is used up. No more tools, write the findings report now.\n", .{ turn, app.research_turns });
+        return;
+    }
+    try w.print("[RESEARCH] Turn {d} of {d}.\n", .{ turn, app.research_turns });
+}
+
 fn inject_mode_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.agent.Agent) !void {
 
     // mode main agent only
@@ -175,7 +309,9 @@ fn inject_mode_information(w: *std.Io.Writer, app: *r.app.App, agent: *r.prv.age
     agent.flags.force_full_reminder = false;
 
     if (reminder.len == 0) return;
-    _ = try w.print("<system-mode>{s}</system-mode>", .{reminder});
+    try w.writeAll("<system-mode>");
+    try r.vars.render(w, reminder, &app.context_factory.prompt_vars);
+    try w.writeAll("</system-mode>");
 
     try w.flush();
 }
@@ -16,6 +16,18 @@ pub const Action = union(enum) {
     cursor_down,
     toggle_skip,
     complete,
+    show_changes,
+    show_token_heatmap,
+    show_usage,
+    quote_tool_error,
+    tool_errors,
+    grep,
+    references,
+    sessions,
+    search_chat,
+    select_message,
+    next_tab,
+    prev_tab,
     lua: c_int,
 };
 
@@ -25,6 +37,8 @@ pub const KeyMap = struct {
 
     pub const defaults: []const KeyBind = &.{
         KeyBind{ .key = .{ .code = .tab }, .action = .complete },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .arrow_left }, .action = .prev_tab },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .arrow_right }, .action = .next_tab },
         KeyBind{ .key = .{ .code = .arrow_left }, .action = .cursor_left },
         KeyBind{ .key = .{ .code = .arrow_right }, .action = .cursor_right },
         KeyBind{ .key = .{ .code = .arrow_up }, .action = .cursor_up },
@@ -37,6 +51,16 @@ pub const KeyMap = struct {
         KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'z' } }, .action = .open_cmd },
         KeyBind{ .key = .{ .code = .esc }, .action = .cancel },
         KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'g' } }, .action = .toggle_skip },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'o' } }, .action = .show_changes },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'k' } }, .action = .show_token_heatmap },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 't' } }, .action = .show_usage },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'e' } }, .action = .quote_tool_error },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'x' } }, .action = .tool_errors },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'f' } }, .action = .grep },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'l' } }, .action = .references },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'b' } }, .action = .sessions },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 's' } }, .action = .search_chat },
+        KeyBind{ .key = .{ .mods = .{ .ctrl = true }, .code = .{ .char = 'j' } }, .action = .select_message },
     };
 
     pub fn parse(self: *const KeyMap, key: tui.Key) ?Action {
@@ -0,0 +1,210 @@
+//! Links in a prompt fetched into context before the turn starts, so a
+//! prompt does not need "read this page first". Pages come in through curl
+//! on the exec pool, always local, HTML turned into markdown. They are kept
+//! for the session: a link fetched once attaches again without a request.
+//! `blitz.set_link_context(mode)` picks what a new link does:
+//!
+//! - `ask` (default) lists the new links in a popup, enter fetches them
+//! - `auto` fetches them right away
+//! - `off` sends the prompt as typed
+const std = @import("std");
+const prv = @import("provider");
+const r = @import("root.zig");
+
+/// links fetched per prompt, the rest stay plain text
+pub const MAX_LINKS = 4;
+/// markdown kept per page, the rest is cut
+pub const MAX_PAGE_BYTES = 48 * 1024;
+const MAX_DOWNLOAD = "4000000";
+const TIMEOUT_S = "20";
+const USER_AGENT = "Mozilla/5.0 (X11; Linux x86_64) blitzdenk";
+
+pub const Mode = enum { off, ask, auto };
+
+pub const Link = struct {
+    url: []const u8,
+    state: State = .fetching,
+    handle: ?prv.exec.CmdPool.Handle = null,
+    /// the page as markdown, why it failed for `.failed`
+    content: []const u8 = "",
+
+    pub const State = enum { fetching, ready, failed, skipped };
+};
+
+pub const Links = struct {
+    mode: Mode = .ask,
+    items: std.ArrayList(Link) = .empty,
+    /// new links the `.links` popup asks about
+    asked: []const []const u8 = &.{},
+    /// the prompt in the input goes out once nothing is fetching
+    send_pending: bool = false,
+
+    pub fn find(self: *const Links, url: []const u8) ?*Link {
+        for (self.items.items) |*link| {
+            if (std.mem.eql(u8, link.url, url)) return link;
+        }
+        return null;
+    }
+
+    /// Links of `text` not fetched or skipped this session.
+    pub fn unseen(self: *const Links, alloc: std.mem.Allocator, text: []const u8) ![]const []const u8 {
+        var out = std.ArrayList([]const u8).empty;
+        for (try findUrls(alloc, text)) |url| {
+            if (self.find(url) == null) try out.append(alloc, url);
+        }
+        return out.toOwnedSlice(alloc);
+    }
+
+    pub fn fetching(self: *const Links) usize {
+        var n: usize = 0;
+        for (self.items.items) |link| n += @intFromBool(link.state == .fetching);
+        return n;
+    }
+
+    /// Start a download per url. One that can't start is marked failed.
+    pub fn start(self: *Links, alloc: std.mem.Allocator, exec: *prv.exec.CmdPool, urls: []const []const u8) !void {
+        for (urls) |url| {
+            var link: Link = .{ .url = try alloc.dupe(u8, url) };
+            link.handle = exec.runWithOpts(.{
+                .argv = &.{ "curl", "-fsSL", "--max-time", TIMEOUT_S, "--max-filesize", MAX_DOWNLOAD, "--proto", "=http,https", "-A", USER_AGENT, link.url },
+                .force_local = true,
+            }) catch |err| blk: {
+                link.state = .failed;
+                link.content = @errorName(err);
+                break :blk null;
+            };
+            try self.items.append(alloc, link);
+        }
+    }
+
+    /// Remember `urls` as not wanted, the prompt mentions them as text only.
+    pub fn skip(self: *Links, alloc: std.mem.Allocator, urls: []const []const u8) !void {
+        for (urls) |url| try self.items.append(alloc, .{ .url = try alloc.dupe(u8, url), .state = .skipped });
+    }
+
+    /// Collect finished downloads. Returns the links that failed this poll
+    /// through `failed`, true when any download finished.
+    pub fn poll(self: *Links, alloc: std.mem.Allocator, exec: *prv.exec.CmdPool, failed: *std.ArrayList(*const Link)) !bool {
+        var changed = false;
+        for (self.items.items) |*link| {
+            const handle = link.handle orelse continue;
+            const res = exec.poll(handle) orelse continue;
+            const page = if (res.ty == .success) pageText(alloc, res.stdout) else error.DownloadFailed;
+            if (page) |text| {
+                link.content = text;
+                link.state = .ready;
+            } else |err| {
+                const reason = std.mem.trim(u8, std.mem.sliceTo(res.stderr, '\n'), " \t\r");
+                link.content = try alloc.dupe(u8, if (reason.len > 0) reason else @errorName(err));
+                link.state = .failed;
+                try failed.append(alloc, link);
+            }
+            exec.release(handle);
+            link.handle = null;
+            changed = true;
+        }
+        return changed;
+    }
+
+    /// True once when the pending prompt can go out.
+    pub fn takeReady(self: *Links) bool {
+        if (!self.send_pending or self.fetching() > 0) return false;
+        self.send_pending = false;
+        return true;
+    }
+
+    /// Drop all links and stop their downloads, for a new session. The
+    /// list's memory belongs to the session arena.
+    pub fn clear(self: *Links, exec: *prv.exec.CmdPool) void {
+        for (self.items.items) |link| {
+            if (link.handle) |handle| exec.release(handle);
+        }
+        self.items = .empty;
+        self.asked = &.{};
+        self.send_pending = false;
+    }
+};
+
+/// http(s) URLs in `text`, in order and without duplicates, at most
+/// `MAX_LINKS`. Trailing punctuation and an unmatched `)` are not part of
+/// the link, `see https://x.org/a).` reads `https://x.org/a`.
+pub fn findUrls(alloc: std.mem.Allocator, text: []const u8) ![]const []const u8 {
+    var out = std.ArrayList([]const u8).empty;
+    var i: usize = 0;
+    while (i < text.len and out.items.len < MAX_LINKS) {
+        const rest = text[i..];
+        const scheme = if (std.mem.startsWith(u8, rest, "https://")) "https://" else if (std.mem.startsWith(u8, rest, "http://")) "http://" else "";
+        const word_start = i == 0 or !std.ascii.isAlphanumeric(text[i - 1]);
+        if (scheme.len == 0 or !word_start) {
+            i += 1;
+            continue;
+        }
+        var end = i + scheme.len;
+        while (end < text.len and !std.ascii.isWhitespace(text[end]) and text[end] != '<' and text[end] != '>' and text[end] != '"' and text[end] != '`') end += 1;
+        const url = trimUrl(text[i..end]);
+        i = end;
+        if (url.len <= scheme.len) continue;
+        for (out.items) |seen| {
+            if (std.mem.eql(u8, seen, url)) break;
+        } else try out.append(alloc, url);
+    }
+    return out.toOwnedSlice(alloc);
+}
+
+fn trimUrl(url: []const u8) []const u8 {
+    var s = url;
+    while (s.len > 0) {
+        const last = s[s.len - 1];
+        if (std.mem.indexOfScalar(u8, ".,;:!?'*", last) != null) {
+            s = s[0 .. s.len - 1];
+        } else if (last == ')' and std.mem.count(u8, s, "(") < std.mem.count(u8, s, ")")) {
+            s = s[0 .. s.len - 1];
+        } else break;
+    }
+    return s;
+}
+
+/// The body as text for the model: HTML as markdown, anything else as it
+/// came, cut to `MAX_PAGE_BYTES` on a UTF-8 boundary.
+pub fn pageText(alloc: std.mem.Allocator, body: []const u8) ![]const u8 {
+    const head = body[0..@min(body.len, 1024)];
+    const html = std.ascii.indexOfIgnoreCase(head, "<html") != null or std.ascii.indexOfIgnoreCase(head, "<!doctype html") != null;
+    const text = if (html) try r.tools.parse.htmlToMarkdown(alloc, body) else body;
+    const trimmed = std.mem.trim(u8, text, " \t\r\n");
+    if (trimmed.len <= MAX_PAGE_BYTES) return alloc.dupe(u8, trimmed);
+    var end: usize = MAX_PAGE_BYTES;
+    while (end > 0 and (trimmed[end] & 0xC0) == 0x80) end -= 1;
+    return std.fmt.allocPrint(alloc, "{s}\n\n[cut at {d} KB]", .{ trimmed[0..end], MAX_PAGE_BYTES / 1024 });
+}
+
+/// Context block for a fetched page, sent after the prompt text.
+pub fn block(alloc: std.mem.Allocator, link: *const Link) ![]const u8 {
+    return std.fmt.allocPrint(alloc, "<link url=\"{s}\">\n{s}\n</link>", .{ link.url, link.content });
+}
+
+test "links in a prompt" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const urls = try findUrls(alloc, "compare https://ziglang.org/download/ and (see https://en.wikipedia.org/wiki/Zig_(language)). Also https://ziglang.org/download/, xhttps://no.pe");
+    try std.testing.expectEqual(@as(usize, 2), urls.len);
+    try std.testing.expectEqualStrings("https://ziglang.org/download/", urls[0]);
+    try std.testing.expectEqualStrings("https://en.wikipedia.org/wiki/Zig_(language)", urls[1]);
+    try std.testing.expectEqual(@as(usize, 0), (try findUrls(alloc, "no links, just http:// here")).len);
+
+    var links: Links = .{};
+    try links.skip(alloc, urls[0..1]);
+    const fresh = try links.unseen(alloc, "again https://ziglang.org/download/ and http://localhost:8080/api");
+    try std.testing.expectEqual(@as(usize, 1), fresh.len);
+    try std.testing.expectEqualStrings("http://localhost:8080/api", fresh[0]);
+    try std.testing.expect(!links.takeReady());
+    links.send_pending = true;
+    try std.testing.expect(links.takeReady());
+    try std.testing.expect(!links.takeReady());
+
+    try std.testing.expectEqualStrings("plain text", try pageText(alloc, "  plain text\n"));
+    const long = try alloc.alloc(u8, MAX_PAGE_BYTES + 10);
+    @memset(long, 'a');
+    try std.testing.expect(std.mem.endsWith(u8, try pageText(alloc, long), "[cut at 48 KB]"));
+}
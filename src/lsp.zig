@@ -104,6 +104,18 @@ pub const Manager = struct {
         return self.tools.items;
     }
 
+    /// First workspace symbol named exactly `name`, across all servers.
+    pub fn findDefinition(self: *Manager, alloc: std.mem.Allocator, name: []const u8) ?SymbolLocation {
+        for (self.clients.items) |*client| {
+            const text = client.workspaceSymbols(alloc, name) catch |err| {
+                log.debug("workspace/symbol {s} failed: {s}", .{ name, @errorName(err) });
+                continue;
+            };
+            if (parseSymbolLocation(text, name)) |loc| return loc;
+        }
+        return null;
+    }
+
     fn addServer(self: *Manager, cfg: ServerConfig) !void {
         var client = try Client.start(self.alloc, self.io, cfg);
         errdefer client.deinit();
@@ -656,6 +668,26 @@ fn writeLocation(w: *std.Io.Writer, alloc: std.mem.Allocator, value: *const std.
     try w.print("{s}:{d}:{d}\n", .{ path, line + 1, ch + 1 });
 }
 
+pub const SymbolLocation = struct {
+    path: []const u8,
+    line: u32,
+};
+
+/// Picks `name path:line:col` out of the formatted workspace symbols.
+fn parseSymbolLocation(text: []const u8, name: []const u8) ?SymbolLocation {
+    var lines = std.mem.splitScalar(u8, text, '\n');
+    while (lines.next()) |raw| {
+        const line = std.mem.trim(u8, raw, " ");
+        if (!std.mem.startsWith(u8, line, name) or line.len <= name.len or line[name.len] != ' ') continue;
+        const loc = line[name.len + 1 ..];
+        const col_sep = std.mem.lastIndexOfScalar(u8, loc, ':') orelse continue;
+        const line_sep = std.mem.lastIndexOfScalar(u8, loc[0..col_sep], ':') orelse continue;
+        const line_no = std.fmt.parseInt(u32, loc[line_sep + 1 .. col_sep], 10) catch continue;
+        return .{ .path = loc[0..line_sep], .line = line_no };
+    }
+    return null;
+}
+
 fn formatSymbols(alloc: std.mem.Allocator, value: *const std.json.Value) ![]const u8 {
     const result = try responseResult(value);
     if (result == .null) return alloc.dupe(u8, "no symbols");
@@ -766,3 +798,12 @@ test "word offset" {
 
     try std.testing.expectEqual(@as(u32, 7), try getWordColumnOffset(std.testing.allocator, &pool, path, 2, "needle"));
 }
+
+test "parse symbol location" {
+    const text = "AppState /src/state.rs:10:1\nApp /src/app.rs:42:12\n  tick /src/app.rs:50:5\n";
+    const loc = parseSymbolLocation(text, "App").?;
+    try std.testing.expectEqualStrings("/src/app.rs", loc.path);
+    try std.testing.expectEqual(@as(u32, 42), loc.line);
+    try std.testing.expectEqual(@as(u32, 50), parseSymbolLocation(text, "tick").?.line);
+    try std.testing.expect(parseSymbolLocation("no symbols", "App") == null);
+}
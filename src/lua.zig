@@ -175,6 +175,7 @@ const LuaString: LuaType = .string;
 const LuaAny: LuaType = .any;
 const AgentIdOrNilDef = LuaType{ .raw = "BlitzAgentId|nil" };
 const StringOrNilDef = LuaType{ .raw = "string|nil" };
+const NumberOrNilDef = LuaType{ .raw = "number|nil" };
 const JsonEncodeRet = LuaType{ .raw = "string|nil, boolean" };
 const JsonDecodeRet = LuaType{ .raw = "any, boolean" };
 
@@ -228,10 +229,16 @@ const ThinkingDef = LuaType{ .table_def = .{ .name = "BlitzThinking", .fields =
     .{ .name = "type", .ty = LuaType.string },
     .{ .name = "budget_tokens", .ty = LuaType.integer, .optional = true },
 } } };
+/// filled in for `type = "openrouter"` providers
+const OPENROUTER_URL = "https://openrouter.ai/api/v1";
+const OPENROUTER_KEY_ENVAR = "OPENROUTER_API_KEY";
+
 const ProviderDef = LuaType{ .table_def = .{ .name = "BlitzProviderDef", .fields = &.{
-    .{ .name = "type", .ty = LuaType.string, .desc = "'openai' | 'response' | 'anthropic' | 'ollama'" },
-    .{ .name = "url", .ty = LuaType.string, .desc = "the endpoint url" },
-    .{ .name = "key_envar", .ty = LuaType.string, .desc = "the ENVAR holding the api key (not the key itself!)" },
+    .{ .name = "type", .ty = LuaType.string, .desc = "'openai' | 'response' | 'anthropic' | 'ollama' | 'openrouter'" },
+    .{ .name = "url", .ty = LuaType.string, .optional = true, .desc = "the endpoint url, optional for openrouter" },
+    .{ .name = "key_envar", .ty = LuaType.string, .optional = true, .desc = "the ENVAR holding the api key (not the key itself!), none for local servers" },
+    .{ .name = "name", .ty = LuaType.string, .optional = true, .desc = "profile name for blitz.use_provider and :providers" },
+    .{ .name = "model", .ty = LuaType.string, .optional = true, .desc = "model a switch to this profile starts on, also the default model while none is set" },
     .{ .name = "effort", .ty = LuaType.string, .optional = true },
     .{ .name = "temperature", .ty = LuaType.number, .optional = true },
     .{ .name = "max_tokens", .ty = LuaType.integer, .optional = true },
@@ -243,6 +250,8 @@ const ProviderDef = LuaType{ .table_def = .{ .name = "BlitzProviderDef", .fields
     .{ .name = "presence_penalty", .ty = LuaType.number, .optional = true },
     .{ .name = "enable_thinking", .ty = LuaType.boolean, .optional = true },
     .{ .name = "thinking", .ty = ThinkingDef, .optional = true },
+    .{ .name = "keep_alive", .ty = LuaType.integer, .optional = true, .desc = "ollama only: seconds the model stays loaded, -1 forever" },
+    .{ .name = "stream", .ty = LuaType.boolean, .optional = true, .desc = "false for endpoints without streaming, answers then show up once complete" },
 } } };
 
 const ThemeDef = LuaType{ .table_def = .{ .name = "BlitzTheme", .fields = &.{
@@ -260,6 +269,28 @@ const ThemeDef = LuaType{ .table_def = .{ .name = "BlitzTheme", .fields = &.{
     .{ .name = "diff_remove", .ty = LuaType.string, .optional = true },
 } } };
 
+const AppearanceDef = LuaType{ .table_def = .{ .name = "BlitzAppearance", .fields = &.{
+    .{ .name = "title", .ty = LuaType.string, .optional = true, .desc = "title screen art, \"\" hides it" },
+    .{ .name = "spinner", .ty = LuaType.string, .optional = true, .desc = "'dots' | 'bar' | 'wave' | 'line' | 'none'" },
+    .{ .name = "banner", .ty = LuaType.boolean, .optional = true, .desc = "project name and git branch on the title screen" },
+} } };
+
+const AppearanceArg = struct {
+    title: ?[]const u8 = null,
+    spinner: ?[]const u8 = null,
+    banner: ?bool = null,
+};
+
+const TodoNudgeDef = LuaType{ .table_def = .{ .name = "BlitzTodoNudge", .fields = &.{
+    .{ .name = "text", .ty = LuaType.string, .optional = true, .desc = "message sent when a run ends with open todos" },
+    .{ .name = "max", .ty = LuaType.integer, .optional = true, .desc = "nudges in a row before waiting for the user, 0 disables" },
+} } };
+
+const TodoNudgeArg = struct {
+    text: ?[]const u8 = null,
+    max: ?u32 = null,
+};
+
 const ThemeArg = struct {
     bg: ?[]const u8 = null,
     overlay_dark: ?[]const u8 = null,
@@ -302,7 +333,8 @@ const ToolDef = LuaType{ .table_def = .{ .name = "ToolDef", .fields = &.{
     .{ .name = "func", .ty = LuaType{ .raw_refs = .{
         .text = "fun(ctx: BlitzCtx, call: BlitzCall): BlitzStatus",
         .refs = &.{ CtxDef, CallDef, StatusDef },
-    } } },
+    } }, .optional = true },
+    .{ .name = "command", .ty = LuaType.string, .optional = true },
 } } };
 const AgentDef = LuaType{ .table_def = .{ .name = "BlitzAgentDef", .fields = &.{
     .{ .name = "name", .ty = LuaType.string },
@@ -319,6 +351,29 @@ const AppFlagsDef = LuaType{ .table_def = .{ .name = "BlitzAppFlags", .fields =
     .{ .name = "debug_log", .ty = LuaType.boolean, .optional = true },
     .{ .name = "ssh_agent_control", .ty = LuaType.boolean, .optional = true },
     .{ .name = "skip_permissions", .ty = LuaType.boolean, .optional = true },
+    .{ .name = "replay_approvals", .ty = LuaType.boolean, .optional = true },
+    .{ .name = "image_preview", .ty = LuaType.boolean, .optional = true },
+} } };
+const RetryPolicyDef = LuaType{ .table_def = .{ .name = "BlitzRetryPolicy", .fields = &.{
+    .{ .name = "max_retries", .ty = LuaType.integer, .optional = true },
+    .{ .name = "base_delay_ms", .ty = LuaType.integer, .optional = true },
+    .{ .name = "max_delay_ms", .ty = LuaType.integer, .optional = true },
+    .{ .name = "jitter", .ty = LuaType.number, .optional = true },
+} } };
+const WebSearchDef = LuaType{ .table_def = .{ .name = "BlitzWebSearch", .fields = &.{
+    .{ .name = "backend", .ty = LuaType.string, .optional = true },
+    .{ .name = "url", .ty = LuaType.string, .optional = true },
+    .{ .name = "brave_key_env", .ty = LuaType.string, .optional = true },
+} } };
+const WebSearchArg = struct {
+    backend: ?[]const u8 = null,
+    url: ?[]const u8 = null,
+    brave_key_env: ?[]const u8 = null,
+};
+const HookDef = LuaType{ .table_def = .{ .name = "BlitzHookDef", .fields = &.{
+    .{ .name = "event", .ty = LuaType.string },
+    .{ .name = "command", .ty = LuaType.string },
+    .{ .name = "min_seconds", .ty = LuaType.integer, .optional = true },
 } } };
 const McpServerDef = LuaType{ .table_def = .{ .name = "BlitzMcpServerDef", .fields = &.{
     .{ .name = "name", .ty = LuaType.string },
@@ -355,7 +410,10 @@ pub const Blitz = LuaType{
             .{ .name = "RET_ERR", .ty = LuaType.integer, .value = .{ .integer = lua.RET_ERR } },
             .{ .name = "RET_EXIT_LOOP", .ty = LuaType.integer, .value = .{ .integer = lua.RET_EXIT_LOOP } },
             .{ .name = "AGENT_GENERAL", .ty = LuaType.integer, .value = .{ .integer = 0 } },
+            .{ .name = "AGENT_TASK", .ty = LuaType.integer, .value = .{ .integer = 1 } },
+            .{ .name = "AGENT_DRAFT", .ty = LuaType.integer, .value = .{ .integer = 2 } },
             .{ .name = "MODE_EXEC", .ty = LuaType.integer, .value = .{ .integer = 0 } },
+            .{ .name = "MODE_RESEARCH", .ty = LuaType.integer, .value = .{ .integer = 1 } },
             .{ .name = "REQ_STATUS_PENDING", .ty = LuaType.integer, .value = .{ .integer = lua.REQ_STATUS_PENDING } },
             .{ .name = "REQ_STATUS_APPROVED", .ty = LuaType.integer, .value = .{ .integer = lua.REQ_STATUS_APPROVED } },
             .{ .name = "REQ_STATUS_DENIED", .ty = LuaType.integer, .value = .{ .integer = lua.REQ_STATUS_DENIED } },
@@ -367,7 +425,7 @@ pub const Blitz = LuaType{
             .{ .name = "AWAIT_INVALID", .ty = LuaType.integer, .value = .{ .integer = lua.AWAIT_INVALID } },
             .{
                 .name = "register_tool",
-                .desc = "Register a tool.",
+                .desc = "Register a tool. Either `func` runs it in Lua or `command` runs a shell command, `{arg}` filled with the quoted argument.",
                 .ty = LuaType{
                     .function = .{
                         .args = &.{.{ .name = "def", .ty = ToolDef }},
@@ -380,6 +438,12 @@ pub const Blitz = LuaType{
                                 var entry: LuaToolEntry = .{};
 
                                 entry.name_len = getStringField(state, def.idx, "name", &entry.name) orelse return error.InvalidToolName;
+                                if (vm.pack_namespace) |ns| {
+                                    var buf: [128]u8 = undefined;
+                                    const name = std.fmt.bufPrint(&buf, "{s}_{s}", .{ ns, entry.name[0..entry.name_len] }) catch return error.InvalidToolName;
+                                    @memcpy(entry.name[0..name.len], name);
+                                    entry.name_len = name.len;
+                                }
                                 entry.desc_len = getStringField(state, def.idx, "description", &entry.description) orelse return error.InvalidToolDescription;
 
                                 // schema (string) OR args (table) — at least one required
@@ -394,9 +458,13 @@ pub const Blitz = LuaType{
                                 }
 
                                 entry.L = state;
-                                _ = c.lua_getfield(state, def.idx, "func");
-                                if (c.lua_type(state, -1) != c.LUA_TFUNCTION) return error.InvalidToolFunc;
-                                entry.func_ref = c.luaL_ref(state, c.LUA_REGISTRYINDEX);
+                                if (getStringField(state, def.idx, "command", &entry.command)) |len| {
+                                    entry.command_len = len;
+                                } else {
+                                    _ = c.lua_getfield(state, def.idx, "func");
+                                    if (c.lua_type(state, -1) != c.LUA_TFUNCTION) return error.InvalidToolFunc;
+                                    entry.func_ref = c.luaL_ref(state, c.LUA_REGISTRYINDEX);
+                                }
 
                                 c.lua_newtable(state);
                                 entry.state_ref = c.luaL_ref(state, c.LUA_REGISTRYINDEX);
@@ -482,8 +550,10 @@ pub const Blitz = LuaType{
                         .fn_ptr = LuaFnBind((struct {
                             const Arg = struct {
                                 type: []const u8,
-                                url: []const u8,
-                                key_envar: []const u8,
+                                url: ?[]const u8 = null,
+                                key_envar: ?[]const u8 = null,
+                                name: ?[]const u8 = null,
+                                model: ?[]const u8 = null,
                                 effort: ?[]const u8 = null,
                                 temperature: ?f32 = null,
                                 max_tokens: ?u32 = null,
@@ -495,17 +565,26 @@ pub const Blitz = LuaType{
                                 presence_penalty: ?f32 = null,
                                 enable_thinking: ?bool = true,
                                 thinking: ?r.prv.adapter.Thinking = null,
+                                keep_alive: ?i32 = null,
+                                stream: ?bool = null,
                             };
 
                             fn lua_fn(a: *r.app.App, args: Arg) !r.prv.config.ProviderHandle {
-                                const slot = a.config.reserveProvider(args.url, args.key_envar) orelse return error.MaxProviderReached;
+                                // OpenAI compatible, with its endpoint and key filled in
+                                const openrouter = std.mem.eql(u8, args.type, "openrouter");
+                                const url = args.url orelse if (openrouter) OPENROUTER_URL else return error.MissingProviderUrl;
+                                const key_envar = args.key_envar orelse if (openrouter) OPENROUTER_KEY_ENVAR else "";
+                                const slot = a.config.reserveProvider(url, key_envar) orelse return error.MaxProviderReached;
+                                const name = args.name orelse if (openrouter) "openrouter" else "";
+                                if (a.config.findProvider(name) != null) return error.DuplicateProviderName;
+                                if (!slot.setProfile(name, args.model orelse "")) return error.ProviderProfileTooLong;
 
                                 if (args.effort) |eff| {
                                     slot.reasoning_effort = prv.config.parseReasoningEffort(eff) orelse return error.UnknownEffortValue;
                                 }
 
                                 const ptype: prv.adapter.Provider = blk: {
-                                    if (std.mem.eql(u8, args.type, "openai")) break :blk .openai;
+                                    if (openrouter or std.mem.eql(u8, args.type, "openai")) break :blk .openai;
                                     if (std.mem.eql(u8, args.type, "response")) break :blk .response;
                                     if (std.mem.eql(u8, args.type, "anthropic")) break :blk .anthropic;
                                     if (std.mem.eql(u8, args.type, "ollama")) break :blk .ollama;
@@ -540,10 +619,14 @@ pub const Blitz = LuaType{
                                         .max_tokens = args.max_tokens orelse 32000,
                                         .top_p = args.top_p,
                                         .top_k = args.top_k,
+                                        .keep_alive = args.keep_alive,
                                     } },
                                 };
+                                slot.stream = args.stream orelse true;
 
-                                return a.config.commitProvider();
+                                const handle = a.config.commitProvider();
+                                if (!a.config.default_model.bound and slot.model_len > 0) _ = a.config.setModel(slot.getModel(), handle);
+                                return handle;
                             }
                         }).lua_fn, "add_provider"),
                     },
@@ -610,6 +693,42 @@ pub const Blitz = LuaType{
                     }).lua_fn, "set_model"),
                 } },
             },
+            .{
+                .name = "use_provider",
+                .desc = "Switch the default model to the provider registered under `name`, on `model` or else the provider's own.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{
+                        .{ .name = "name", .ty = LuaType.string },
+                        .{ .name = "model", .ty = LuaType.string, .optional = true },
+                    },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, name: []const u8, model: ?[]const u8) !void {
+                            const handle = a.config.findProvider(name) orelse return error.UnknownProvider;
+                            if (model) |m| {
+                                if (!a.config.setModel(m, handle)) return error.ModelStringTooLong;
+                            } else if (!a.config.useProvider(handle)) return error.ProviderHasNoModel;
+                        }
+                    }).lua_fn, "use_provider"),
+                } },
+            },
+            .{
+                .name = "add_model_alias",
+                .desc = "Name a model for `@model:<alias>` prompt hints, which route a single message to it.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{
+                        .{ .name = "alias", .ty = LuaType.string },
+                        .{ .name = "model", .ty = LuaType.string },
+                        .{ .name = "handle", .ty = LuaType.integer },
+                    },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, alias: []const u8, model: []const u8, handle: u32) !void {
+                            if (!a.config.addModelAlias(alias, model, @enumFromInt(handle))) {
+                                return error.InvalidModelAlias;
+                            }
+                        }
+                    }).lua_fn, "add_model_alias"),
+                } },
+            },
             .{
                 .name = "set_model_agent",
                 .desc = "Set the model config for a specific agent.",
@@ -705,6 +824,88 @@ pub const Blitz = LuaType{
                     }).lua_fn, "context_percent"),
                 } },
             },
+            .{
+                .name = "session_cost",
+                .desc = "Return the session's cost in dollars from the LiteLLM price list, nil while no model is priced.",
+                .ty = LuaType{ .function = .{
+                    .ret = &NumberOrNilDef,
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App) !?f64 {
+                            return a.sessionCost();
+                        }
+                    }).lua_fn, "session_cost"),
+                } },
+            },
+            .{
+                .name = "set_model_price",
+                .desc =
+                \\Price a model in dollars per million tokens, wins over the LiteLLM list. Cache rates default to the input rate.
+                \\Example: blitz.set_model_price("qwen3-coder", 0.2, 0.8)
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{
+                        .{ .name = "model", .ty = LuaType.string },
+                        .{ .name = "input", .ty = LuaType.number },
+                        .{ .name = "output", .ty = LuaType.number },
+                        .{ .name = "cache_read", .ty = LuaType.number, .optional = true },
+                        .{ .name = "cache_write", .ty = LuaType.number, .optional = true },
+                    },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, model: []const u8, input: f64, output: f64, cache_read: ?f64, cache_write: ?f64) !void {
+                            const per_token = 1.0 / 1_000_000.0;
+                            try a.custom_prices.put(model, .{
+                                .input = input * per_token,
+                                .output = output * per_token,
+                                .cache_read = if (cache_read) |v| v * per_token else null,
+                                .cache_write = if (cache_write) |v| v * per_token else null,
+                            });
+                            a.dirty = true;
+                        }
+                    }).lua_fn, "set_model_price"),
+                } },
+            },
+            .{
+                .name = "set_cost_confirm",
+                .desc = "Ask before sending a prompt whose input is estimated above this many dollars (default 0.5). 0 never asks.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "dollars", .ty = LuaType.number }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, dollars: f64) !void {
+                            if (dollars < 0) return error.InvalidCost;
+                            a.cost_confirm = dollars;
+                        }
+                    }).lua_fn, "set_cost_confirm"),
+                } },
+            },
+            .{
+                .name = "set_daily_budget",
+                .desc = "Cap the dollars spent per day (UTC) over every running blitzdenk instance. Warns at 80%, refuses new turns at the cap. 0 turns it off.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "dollars", .ty = LuaType.number }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, dollars: f64) !void {
+                            if (dollars < 0) return error.InvalidBudget;
+                            a.spend.budget = dollars;
+                            a.spend.warned = null;
+                            // read the shared total on the next tick
+                            a.spend.next_check_ms = 0;
+                            a.spend.file_size = 0;
+                        }
+                    }).lua_fn, "set_daily_budget"),
+                } },
+            },
+            .{
+                .name = "set_link_context",
+                .desc = "What links in a new prompt do: \"ask\" (default) offers to fetch them, \"auto\" fetches them, \"off\" leaves them as text. Fetched pages go along as context.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "mode", .ty = LuaType.string }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, mode: []const u8) !void {
+                            a.links.mode = std.meta.stringToEnum(r.links.Mode, mode) orelse return error.UnknownLinkMode;
+                        }
+                    }).lua_fn, "set_link_context"),
+                } },
+            },
             .{
                 .name = "set_compact_edge",
                 .desc = "Set the default context edge, in tokens, used for statusbar percentage and auto-compaction.",
@@ -724,6 +925,45 @@ pub const Blitz = LuaType{
                     },
                 },
             },
+            .{
+                .name = "set_compact_threshold",
+                .desc = "Start auto-compaction at this percentage of the context edge (default 90).",
+                .ty = LuaType{
+                    .function = .{
+                        .args = &.{.{ .name = "percent", .ty = LuaType.integer }},
+                        .fn_ptr = LuaFnBind((struct {
+                            fn lua_fn(a: *r.app.App, percent: u32) !void {
+                                if (percent == 0 or percent > 100) return error.InvalidPercent;
+                                a.compact_threshold = @intCast(percent);
+                                for (&a.swarm.slots) |*slot| {
+                                    const slot_state = slot.state.load(.acquire);
+                                    if (slot_state == .free or slot_state == .reserved) continue;
+                                    slot.agent.compact_threshold = a.compact_threshold;
+                                }
+                            }
+                        }).lua_fn, "set_compact_threshold"),
+                    },
+                },
+            },
+            .{
+                .name = "set_compact_model",
+                .desc = "Write compaction summaries with this (cheaper) model on the agent's provider. Empty string uses the agent's model.",
+                .ty = LuaType{
+                    .function = .{
+                        .args = &.{.{ .name = "model", .ty = LuaType.string }},
+                        .fn_ptr = LuaFnBind((struct {
+                            fn lua_fn(a: *r.app.App, model: []const u8) !void {
+                                a.compact_model = try a.appAlloc().dupe(u8, model);
+                                for (&a.swarm.slots) |*slot| {
+                                    const slot_state = slot.state.load(.acquire);
+                                    if (slot_state == .free or slot_state == .reserved) continue;
+                                    slot.agent.compact_model = a.compact_model;
+                                }
+                            }
+                        }).lua_fn, "set_compact_model"),
+                    },
+                },
+            },
             .{
                 .name = "bind",
                 .desc =
@@ -776,11 +1016,16 @@ pub const Blitz = LuaType{
                             if (name.len > 128) return error.CommandNameTooLong;
 
                             var entry = LuaCommandEntry{
-                                .name_len = name.len,
                                 .func_ref = func.idx,
                                 .L = state,
                             };
-                            @memcpy(entry.name[0..name.len], name);
+                            const full = if (vm.pack_namespace) |ns|
+                                std.fmt.bufPrint(&entry.name, "{c}{s}.{s}", .{ name[0], ns, name[1..] }) catch return error.CommandNameTooLong
+                            else blk: {
+                                @memcpy(entry.name[0..name.len], name);
+                                break :blk entry.name[0..name.len];
+                            };
+                            entry.name_len = full.len;
                             vm.command_entries.appendAssumeCapacity(entry);
                         }
                     }).lua_fn, "add_command"),
@@ -801,6 +1046,221 @@ pub const Blitz = LuaType{
                     }).lua_fn, "set_agent_tools"),
                 } },
             },
+            .{
+                .name = "set_sensitive_paths",
+                .desc =
+                \\Replace the sensitive path globs guarded in read and ripgrep (default: .env, .env.*, secrets/**, ~/.ssh/*).
+                \\policy is "ask" (default, user confirms each read) or "block".
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{ .{ .name = "patterns", .ty = StringListDef }, .{ .name = "policy", .ty = LuaType.string, .optional = true } },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, patterns: [][]const u8, policy: ?[]const u8) !void {
+                            if (patterns.len > r.sensitive.MAX_PATTERNS) return error.TooManyPatterns;
+                            var guard = r.sensitive.SensitivePaths{};
+                            if (policy) |p| guard.policy = r.sensitive.Policy.fromString(p) orelse return error.UnknownPolicy;
+                            for (patterns) |pattern| {
+                                guard.patterns[guard.count] = try a.appAlloc().dupe(u8, pattern);
+                                guard.count += 1;
+                            }
+                            a.sensitive_paths = guard;
+                        }
+                    }).lua_fn, "set_sensitive_paths"),
+                } },
+            },
+            .{
+                .name = "set_sandbox",
+                .desc =
+                \\Keep writes of write, edit, patch and bash inside the project root (on by default).
+                \\allow lists paths outside that stay writable, `~/` and relative entries work. Example: blitz.set_sandbox(true, { "/tmp" })
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{ .{ .name = "enabled", .ty = LuaType.boolean }, .{ .name = "allow", .ty = StringListDef, .optional = true } },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, enabled: bool, allow: ?[][]const u8) !void {
+                            const paths = allow orelse &.{};
+                            if (paths.len > r.sandbox.MAX_ALLOWED) return error.TooManyPaths;
+                            var sandbox = r.sandbox.Sandbox{ .enabled = enabled };
+                            for (paths) |path| {
+                                sandbox.allowed[sandbox.count] = try a.appAlloc().dupe(u8, path);
+                                sandbox.count += 1;
+                            }
+                            a.sandbox = sandbox;
+                        }
+                    }).lua_fn, "set_sandbox"),
+                } },
+            },
+            .{
+                .name = "set_web_search",
+                .desc =
+                \\Backend of the web_search tool: "searxng" with the instance url, "brave" with the API key in brave_key_env
+                \\(default BRAVE_API_KEY) or "duckduckgo". "auto" (default) takes the first one configured, DuckDuckGo needs no setup.
+                \\Example: blitz.set_web_search({ backend = "searxng", url = "https://search.example.org" })
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "config", .ty = WebSearchDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, opts: WebSearchArg) !void {
+                            var config = tl.websearch.Config{};
+                            if (opts.backend) |name| config.backend = std.meta.stringToEnum(tl.websearch.Backend, name) orelse return error.UnknownSearchBackend;
+                            if (opts.url) |url| config.searxng_url = try a.appAlloc().dupe(u8, url);
+                            if (opts.brave_key_env) |env| config.brave_key_env = try a.appAlloc().dupe(u8, env);
+                            a.web_search = config;
+                        }
+                    }).lua_fn, "set_web_search"),
+                } },
+            },
+            .{
+                .name = "set_bash_rules",
+                .desc =
+                \\Glob rules for the bash tool, checked per command segment. allow skips the prompt, deny refuses without asking.
+                \\Every decision is noted in the chat. Example: blitz.set_bash_rules({ "cargo check*", "git status" }, { "git push --force*" })
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{ .{ .name = "allow", .ty = StringListDef }, .{ .name = "deny", .ty = StringListDef, .optional = true } },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, allow: [][]const u8, deny: ?[][]const u8) !void {
+                            const denied = deny orelse &.{};
+                            const owned_allow = try a.appAlloc().alloc([]const u8, allow.len);
+                            for (allow, owned_allow) |pattern, *slot| slot.* = try a.appAlloc().dupe(u8, pattern);
+                            const owned_deny = try a.appAlloc().alloc([]const u8, denied.len);
+                            for (denied, owned_deny) |pattern, *slot| slot.* = try a.appAlloc().dupe(u8, pattern);
+                            const rules = r.allowlist.BashRules{ .allow = owned_allow, .deny = owned_deny };
+                            const g = a.bash_rules.lock(a.io);
+                            defer g.unlock();
+                            g.ptr.* = rules;
+                        }
+                    }).lua_fn, "set_bash_rules"),
+                } },
+            },
+            .{
+                .name = "set_session_encryption",
+                .desc =
+                \\Encrypt stored sessions at rest with a key kept in the OS keychain (secret-tool or security), created on first use.
+                \\passphrase_env names an env var whose passphrase derives the key instead. `:sessions encrypt` seals existing files.
+                \\Spill files, the --log event log and prompt_history.json stay plaintext.
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{ .{ .name = "enabled", .ty = LuaType.boolean }, .{ .name = "passphrase_env", .ty = LuaType.string, .optional = true } },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, enabled: bool, passphrase_env: ?[]const u8) !void {
+                            const source = passphrase_env orelse "";
+                            // the cached key only fits the source it came from
+                            if (!std.mem.eql(u8, source, a.vault.passphrase_env)) {
+                                a.vault.passphrase_env = try a.appAlloc().dupe(u8, source);
+                                a.vault.key = null;
+                            }
+                            a.vault.enabled = enabled;
+                            if (!enabled) return;
+                            const home = a.swarm.exec.env.get("HOME") orelse return error.NoHomeFound;
+                            try a.vault.unlock(a.io, &a.swarm.exec, home);
+                        }
+                    }).lua_fn, "set_session_encryption"),
+                } },
+            },
+            .{
+                .name = "set_minimal_schemas",
+                .desc =
+                \\Send only the first sentence of each tool description to models matching these globs (e.g. "claude-*").
+                \\Saves prompt tokens per request, the token heatmap shows the difference.
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "models", .ty = StringListDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, models: [][]const u8) !void {
+                            const owned = try a.appAlloc().alloc([]const u8, models.len);
+                            for (models, owned) |model, *slot| slot.* = try a.appAlloc().dupe(u8, model);
+                            a.minimal_schema_models = owned;
+                        }
+                    }).lua_fn, "set_minimal_schemas"),
+                } },
+            },
+            .{
+                .name = "set_retry_policy",
+                .desc =
+                \\Retry transient provider errors (rate limits, overload, 5xx, dropped connections) with exponential backoff.
+                \\Missing fields keep their defaults: max_retries 3, base_delay_ms 1000, max_delay_ms 30000, jitter 0.25.
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "policy", .ty = RetryPolicyDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, policy: r.prv.retry.Policy) !void {
+                            if (policy.jitter < 0 or policy.jitter > 1) return error.InvalidJitter;
+                            a.retry_policy = policy;
+                            for (&a.swarm.slots) |*slot| {
+                                const slot_state = slot.state.load(.acquire);
+                                if (slot_state == .free or slot_state == .reserved) continue;
+                                slot.agent.retry_policy = policy;
+                            }
+                        }
+                    }).lua_fn, "set_retry_policy"),
+                } },
+            },
+            .{
+                .name = "set_fallback_models",
+                .desc =
+                \\Models on the same provider that take over, in order, once a request keeps failing. The agent stays on the fallback until its run ends.
+                \\Example: blitz.set_fallback_models({ "claude-sonnet-4-5", "claude-haiku-4-5" })
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "models", .ty = StringListDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, models: [][]const u8) !void {
+                            const owned = try a.appAlloc().alloc([]const u8, models.len);
+                            for (models, owned) |model, *slot| slot.* = try a.appAlloc().dupe(u8, model);
+                            a.fallback_models = owned;
+                            for (&a.swarm.slots) |*slot| {
+                                const slot_state = slot.state.load(.acquire);
+                                if (slot_state == .free or slot_state == .reserved) continue;
+                                slot.agent.fallback_models = owned;
+                                slot.agent.fallback_idx = 0;
+                            }
+                        }
+                    }).lua_fn, "set_fallback_models"),
+                } },
+            },
+            .{
+                .name = "add_hook",
+                .desc =
+                \\Run a shell command on an event: "turn_finished", "error" or "permission_requested". {message}, {model}, {cost}, {seconds} and {event} expand shell quoted.
+                \\min_seconds skips turn_finished, error and post_run for shorter runs. Commands run in the background.
+                \\Policy events "pre_tool", "post_tool", "pre_run" and "on_permission" pass the action as JSON on stdin and wait for the command: a non-zero exit vetoes with stdout as the reason, output of a zero exit is attached as a note. "post_run" gets the finished run on stdin.
+                \\Example: blitz.add_hook({ event = "turn_finished", command = "notify-send blitz {message}", min_seconds = 30 })
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "hook", .ty = HookDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        const Args = struct {
+                            event: []const u8,
+                            command: []const u8,
+                            min_seconds: ?u32,
+                        };
+
+                        fn lua_fn(a: *r.app.App, args: Args) !void {
+                            const event = std.meta.stringToEnum(r.hooks.Event, args.event) orelse return error.UnknownHookEvent;
+                            try a.hooks.add(a.appAlloc(), .{
+                                .event = event,
+                                .command = args.command,
+                                .min_seconds = args.min_seconds orelse 0,
+                            });
+                        }
+                    }).lua_fn, "add_hook"),
+                } },
+            },
+            .{
+                .name = "set_mouse_capture",
+                .desc =
+                \\Enable or disable mouse capture (default: enabled). Disabled keeps native terminal selection and copy, scrolling is keyboard only.
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "enabled", .ty = LuaType.boolean }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, enabled: bool) !void {
+                            a.flags.mouse_capture = enabled;
+                        }
+                    }).lua_fn, "set_mouse_capture"),
+                } },
+            },
             .{
                 .name = "set_prompt",
                 .desc = "Override the system prompt for a given agent type.",
@@ -813,6 +1273,63 @@ pub const Blitz = LuaType{
                     }).lua_fn, "set_prompt"),
                 } },
             },
+            .{
+                .name = "set_test_command",
+                .desc =
+                \\Set the project's test command, replaces the one detected from manifests.
+                \\Example: blitz.set_test_command("zig build test --summary failures")
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "command", .ty = LuaType.string }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, command: []const u8) !void {
+                            try a.context_factory.setTestCommand(command);
+                        }
+                    }).lua_fn, "set_test_command"),
+                } },
+            },
+            .{
+                .name = "set_shell_history",
+                .desc =
+                \\Put the last `count` shell commands into the system prompt of new sessions, 0 turns it off (default).
+                \\Reads `.blitz/shell_history` in the project if present, else the zsh, bash or fish history.
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "count", .ty = LuaType.integer }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, count: u32) !void {
+                            a.context_factory.shell_history = @min(count, r.shellhist.MAX_COMMANDS);
+                        }
+                    }).lua_fn, "set_shell_history"),
+                } },
+            },
+            .{
+                .name = "test_command",
+                .desc = "The project's test command, configured or detected. Empty when unknown.",
+                .ty = LuaType{ .function = .{
+                    .ret = &LuaString,
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App) ![]const u8 {
+                            return a.context_factory.testCommand(a.lua_vm.luaArena()) orelse "";
+                        }
+                    }).lua_fn, "test_command"),
+                } },
+            },
+            .{
+                .name = "set_prompt_var",
+                .desc =
+                \\Fill `{{name}}` in agent prompts, AGENTS.md files, mode prompts and skills with `value`.
+                \\Example: blitz.set_prompt_var("service_name", "billing")
+                ,
+                .ty = LuaType{ .function = .{
+                    .args = &.{ .{ .name = "name", .ty = LuaType.string }, .{ .name = "value", .ty = LuaType.string } },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, name: []const u8, value: []const u8) !void {
+                            try a.context_factory.setPromptVar(name, value);
+                        }
+                    }).lua_fn, "set_prompt_var"),
+                } },
+            },
             .{
                 .name = "set_mode_prompt",
                 .desc = "Override the mode reminder prompt (full variant).",
@@ -863,6 +1380,10 @@ pub const Blitz = LuaType{
                     .ret = &LuaInteger,
                     .fn_ptr = LuaFnBind((struct {
                         fn lua_fn(a: *r.app.App, name: []const u8, color: []const u8, prompt: []const u8, sparse: []const u8) !r.ContextFactory.Mode {
+                            if (a.lua_vm.pack_namespace) |ns| {
+                                const full = try r.pack.qualified(a.lua_vm.luaArena(), ns, name);
+                                return a.context_factory.addMode(full, prompt, sparse, color);
+                            }
                             return a.context_factory.addMode(name, prompt, sparse, color);
                         }
                     }).lua_fn, "add_mode"),
@@ -905,6 +1426,25 @@ pub const Blitz = LuaType{
                     }).lua_fn, "set_flags"),
                 } },
             },
+            .{
+                .name = "set_flag",
+                .desc = "Set one app flag by name, the others keep their value.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{ .{ .name = "name", .ty = LuaType.string }, .{ .name = "value", .ty = LuaType.boolean } },
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, name: []const u8, value: bool) !void {
+                            inline for (std.meta.fields(r.app.AppFlags)) |field| {
+                                if (std.mem.eql(u8, field.name, name)) {
+                                    @field(a.flags, field.name) = value;
+                                    a.dirty = true;
+                                    return;
+                                }
+                            }
+                            return error.UnknownFlag;
+                        }
+                    }).lua_fn, "set_flag"),
+                } },
+            },
             .{
                 .name = "get_theme",
                 .desc = "Return the current theme as a table of hex color strings.",
@@ -957,6 +1497,33 @@ pub const Blitz = LuaType{
                     }).lua_fn, "set_theme"),
                 } },
             },
+            .{
+                .name = "set_appearance",
+                .desc = "Customize the title art, throbber style and startup banner. Missing fields keep their current value.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "appearance", .ty = AppearanceDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, appearance: AppearanceArg) !void {
+                            if (appearance.title) |t| a.appearance.title = try a.appAlloc().dupe(u8, t);
+                            if (appearance.spinner) |s| a.appearance.spinner = r.tui.text_utils.SpinnerStyle.fromString(s) orelse return error.UnknownSpinner;
+                            if (appearance.banner) |b| a.appearance.banner = b;
+                        }
+                    }).lua_fn, "set_appearance"),
+                } },
+            },
+            .{
+                .name = "set_todo_nudge",
+                .desc = "Configure the follow-up sent when a run ends with open todos. Missing fields keep their current value.",
+                .ty = LuaType{ .function = .{
+                    .args = &.{.{ .name = "nudge", .ty = TodoNudgeDef }},
+                    .fn_ptr = LuaFnBind((struct {
+                        fn lua_fn(a: *r.app.App, nudge: TodoNudgeArg) !void {
+                            if (nudge.text) |t| a.todo_nudge.text = try a.appAlloc().dupe(u8, t);
+                            if (nudge.max) |m| a.todo_nudge.max = m;
+                        }
+                    }).lua_fn, "set_todo_nudge"),
+                } },
+            },
             .{
                 .name = "log",
                 .desc = "Write a debug log line.",
@@ -1029,6 +1596,8 @@ pub const BlitzToolDef = LuaType{
             .{ .name = "WRITE", .ty = LuaType.string, .value = .{ .string = tl.write.WriteTool.def.name } },
             .{ .name = "EDIT", .ty = LuaType.string, .value = .{ .string = tl.edit.EditTool.def.name } },
             .{ .name = "PATCH", .ty = LuaType.string, .value = .{ .string = tl.patch.PatchTool.def.name } },
+            .{ .name = "APPLY_PATCH", .ty = LuaType.string, .value = .{ .string = tl.patch.ApplyPatchTool.def.name } },
+            .{ .name = "DRAFT_EDIT", .ty = LuaType.string, .value = .{ .string = tl.draft.DraftEditTool.def.name } },
             .{ .name = "AGENT", .ty = LuaType.string, .value = .{ .string = tl.agent.AgentTool.def.name } },
             .{ .name = "LIST_TODOS", .ty = LuaType.string, .value = .{ .string = tl.todos.ListTodosTool.def.name } },
             .{ .name = "UPDATE_TODO_STATE", .ty = LuaType.string, .value = .{ .string = tl.todos.UpdateTodoStateTool.def.name } },
@@ -1039,10 +1608,16 @@ pub const BlitzToolDef = LuaType{
             .{ .name = "SEND_MESSAGE_TO_AGENT", .ty = LuaType.string, .value = .{ .string = tl.agent.SendMessageToAgent.def.name } },
             .{ .name = "AWAIT_AGENT", .ty = LuaType.string, .value = .{ .string = tl.agent.AwaitAgent.def.name } },
             .{ .name = "CANCEL_AGENT", .ty = LuaType.string, .value = .{ .string = tl.agent.CancelAgent.def.name } },
+            .{ .name = "TASK", .ty = LuaType.string, .value = .{ .string = tl.task.TaskTool.def.name } },
             .{ .name = "RIPGREP", .ty = LuaType.string, .value = .{ .string = tl.rg.RipGrepTool.def.name } },
             .{ .name = "LOADSKILL", .ty = LuaType.string, .value = .{ .string = tl.skill.LoadSkillTool.def.name } },
             .{ .name = "START_MCP", .ty = LuaType.string, .value = .{ .string = tl.start.StartMcpTool.def.name } },
             .{ .name = "START_LSP", .ty = LuaType.string, .value = .{ .string = tl.start.StartLspTool.def.name } },
+            .{ .name = "GIT_COMMIT", .ty = LuaType.string, .value = .{ .string = tl.git.GitCommitTool.def.name } },
+            .{ .name = "WHO_OWNS", .ty = LuaType.string, .value = .{ .string = tl.owners.WhoOwnsTool.def.name } },
+            .{ .name = "WEB_SEARCH", .ty = LuaType.string, .value = .{ .string = tl.websearch.WebSearchTool.def.name } },
+            .{ .name = "MEMORY", .ty = LuaType.string, .value = .{ .string = tl.memory.MemoryTool.def.name } },
+            .{ .name = "EVAL", .ty = LuaType.string, .value = .{ .string = tl.eval.EvalTool.def.name } },
             .{ .name = "LSP", .ty = LuaType.string, .value = .{ .string = r.lsp.TOOL_NAME } },
         },
     },
@@ -1068,6 +1643,7 @@ pub const BlitzEventDef = LuaType{
             .{ .name = "PERMISSION_RESOLVED", .desc = "Emitted when a permission request is resolved.", .ty = LuaType.integer, .value = .{ .integer = 13 } },
             .{ .name = "USER_MESSAGE_SENT", .desc = "Emitted after the user sends a message.", .ty = LuaType.integer, .value = .{ .integer = 14 } },
             .{ .name = "MCP_TOOLS_RELOADED", .desc = "Emitted after MCP tools are reloaded.", .ty = LuaType.integer, .value = .{ .integer = 15 } },
+            .{ .name = "USAGE_UPDATED", .desc = "Emitted when the session token usage changes. Receives the session totals.", .ty = LuaType.integer, .value = .{ .integer = 16 } },
             .{
                 .name = "add_listener",
                 .desc =
@@ -1573,6 +2149,9 @@ const LuaToolEntry = struct {
     desc_len: usize = 0,
     schema: [2048]u8 = undefined,
     schema_len: usize = 0,
+    /// shell command template, set instead of `func_ref`
+    command: [1024]u8 = undefined,
+    command_len: usize = 0,
     func_ref: c_int = c.LUA_NOREF,
     state_ref: c_int = c.LUA_NOREF,
     L: ?*c.lua_State = null,
@@ -1586,6 +2165,9 @@ const LuaToolEntry = struct {
     fn schemaSlice(self: *const LuaToolEntry) []const u8 {
         return self.schema[0..self.schema_len];
     }
+    fn commandSlice(self: *const LuaToolEntry) []const u8 {
+        return self.command[0..self.command_len];
+    }
 };
 
 const LuaAgentEntry = struct {
@@ -2005,6 +2587,8 @@ pub const LuaVm = struct {
     last_error_len: usize = 0,
     failed_ref: c_int = c.LUA_NOREF,
     exit_loop_ref: c_int = c.LUA_NOREF,
+    /// Name of the pack whose init.lua is running, prefixes what it adds.
+    pack_namespace: ?[]const u8 = null,
     /// Serializes lua_pcall across worker threads. Lua VMs are not
     /// thread-safe; native tools run in parallel, Lua tools serialize here.
     vm_mu: std.Io.Mutex = .init,
@@ -2110,6 +2694,13 @@ pub const LuaVm = struct {
         }
     }
 
+    /// Run a pack's init.lua, its commands, modes and tools get the pack name.
+    pub fn loadPack(self: *LuaVm, script: r.pack.Script) !void {
+        self.pack_namespace = script.pack;
+        defer self.pack_namespace = null;
+        try self.load(script.path);
+    }
+
     pub fn exec(self: *LuaVm, code: []const u8) !void {
         var buf: [8192]u8 = undefined;
         if (code.len >= buf.len) return error.CodeTooLong;
@@ -2160,6 +2751,24 @@ pub const LuaVm = struct {
         if (self.app) |a| {
             a.config.resetProviders();
             a.default_context_limit = app.CONTEXT_LIMIT;
+            a.compact_threshold = r.prv.compact.DEFAULT_THRESHOLD_PERCENT;
+            a.compact_model = "";
+            a.retry_policy = .{};
+            a.fallback_models = &.{};
+            a.custom_prices.clear();
+            a.cost_confirm = app.DEFAULT_COST_CONFIRM;
+            a.hooks.clear();
+            a.sandbox = .{};
+            a.web_search = .{};
+            a.links.mode = .ask;
+            a.spend.budget = 0;
+            {
+                const g = a.bash_rules.lock(a.io);
+                defer g.unlock();
+                g.ptr.* = .{};
+            }
+            // the key stays cached, a reload should not ask the keychain again
+            a.vault.enabled = false;
         }
         try self.initLuaState();
         if (self.app) |a| self.setApp(a);
@@ -2664,6 +3273,7 @@ fn failedResult(call: ToolCall, msg: []const u8) ToolResult {
 fn luaToolTrampoline(ctx: ToolContext, call: ToolCall) ToolResult {
     const vm = activeVm() orelse return failedResult(call, "no active lua vm");
     const entry = findEntry(vm, call.name) orelse return failedResult(call, "tool not found");
+    if (entry.command_len > 0) return tl.command.run(ctx, call, entry.commandSlice());
     const L = entry.L orelse return failedResult(call, "tool has no lua state");
 
     // Serialize across worker threads — Lua VM is not thread-safe.
@@ -2856,7 +3466,7 @@ fn awaitPermAndPush(state: *c.lua_State, io: std.Io, req: *r.prv.Swarm.Permissio
 
     switch (req.state) {
         .pending => return pushStatusNil(state, REQ_STATUS_DENIED),
-        .approved => return pushStatusNil(state, REQ_STATUS_APPROVED),
+        .approved, .edited => return pushStatusNil(state, REQ_STATUS_APPROVED),
         .denied => return pushStatusNil(state, REQ_STATUS_DENIED),
         .choice => |i| {
             c.lua_pushinteger(state, REQ_STATUS_CHOICE);
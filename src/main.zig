@@ -70,6 +70,28 @@ const ConfigLuaInfo = struct {
     dir_path: []const u8,
 };
 
+/// Run every installed pack's init.lua, false if one failed.
+fn loadPacks(app: *App, io: std.Io, arena: std.mem.Allocator, config_dir: []const u8) bool {
+    var ok = true;
+    for (r.pack.initScripts(io, arena, config_dir)) |script| {
+        app.lua_vm.loadPack(script) catch |err| {
+            ok = false;
+            std.log.scoped(.lua).err("failed to load pack {s}: {s} ({any})", .{ script.pack, app.lua_vm.getLastError(), err });
+        };
+    }
+    return ok;
+}
+
+/// `settings.lua` written by the settings popup, if there is one yet.
+fn loadSettings(app: *App, io: std.Io, path: []const u8) r.doctor.Layer.State {
+    _ = std.Io.Dir.cwd().statFile(io, path, .{}) catch return .missing;
+    app.lua_vm.load(path) catch |err| {
+        std.log.scoped(.lua).err("failed to load {s}: {s} ({any})", .{ path, app.lua_vm.getLastError(), err });
+        return .failed;
+    };
+    return .loaded;
+}
+
 fn ensureConfigLua(alloc: std.mem.Allocator, io: std.Io, env: *const std.process.Environ.Map) !ConfigLuaInfo {
     const HOME = env.get("HOME") orelse return error.NoHomeFound;
     var home_dir = try std.Io.Dir.openDirAbsolute(io, HOME, .{});
@@ -133,12 +155,25 @@ fn cwdBlitzLuaExists(io: std.Io) bool {
     return true;
 }
 
+/// Makes `path` the process cwd, so the project's `blitz.lua`, `.blitz/`
+/// state and every tool resolve against it no matter where blitz started.
+fn enterDir(path: []const u8) bool {
+    var buf: [std.posix.PATH_MAX]u8 = undefined;
+    const path_z = std.fmt.bufPrintZ(&buf, "{s}", .{path}) catch return false;
+    return std.c.chdir(path_z) == 0;
+}
+
 pub fn main(init: std.process.Init) !void {
     var pos_buf: [16][:0]const u8 = undefined;
     const split = CliArgs.split(init.minimal.args, &pos_buf);
     const cli_flags = split.flags;
     const command_result = CliCommand.parse(split.positional);
+    if (split.dir.len > 0 and !enterDir(split.dir)) {
+        std.debug.print("Error: cannot enter --dir {s}\n", .{split.dir});
+        std.process.exit(1);
+    }
     if (cli_flags.debug_log) openDebugLog(init.io);
+    r.import.loadKeys(init.io, init.arena.allocator(), init.environ_map);
 
     const cmd: CliCommand = switch (command_result) {
         .err => |txt| {
@@ -175,32 +210,136 @@ pub fn main(init: std.process.Init) !void {
             }
         },
         .run => |cwd_arg| {
+            if (!enterDir(cwd_arg)) {
+                std.debug.print("Error: cannot enter {s}\n", .{cwd_arg});
+                std.process.exit(1);
+            }
             var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
-            const len = try std.Io.Dir.cwd().realPathFile(init.io, cwd_arg, &cwd_buffer);
+            const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
             const cwd = cwd_buffer[0..len];
-            try run(
+            _ = try run(
                 cwd,
                 init.gpa,
                 init.arena.allocator(),
                 init.io,
                 init.environ_map,
                 cli_flags,
-                null,
+                .{ .tui = null },
             );
         },
         .prompt => |prompt| {
             var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
             const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
             const cwd = cwd_buffer[0..len];
-            try run(
+            _ = try run(
                 cwd,
                 init.gpa,
                 init.arena.allocator(),
                 init.io,
                 init.environ_map,
                 cli_flags,
-                prompt,
+                .{ .tui = prompt },
+            );
+        },
+        .exec => |arg| {
+            var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
+            const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
+            const prompt = arg orelse try r.headless.readStdin(init.io, init.arena.allocator());
+            const status = try run(
+                cwd_buffer[0..len],
+                init.gpa,
+                init.arena.allocator(),
+                init.io,
+                init.environ_map,
+                cli_flags,
+                .{ .exec = .{ .prompt = prompt, .json = cli_flags.json, .allow = split.allow } },
+            );
+            if (status != 0) std.process.exit(status);
+        },
+        .restore => |args| {
+            var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
+            const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
+            try r.backup.restore(init.io, init.arena.allocator(), cwd_buffer[0..len], args.session, args.file);
+        },
+        .import => {
+            const HOME = init.environ_map.get("HOME") orelse return error.NoHomeFound;
+            try r.import.run(init.io, init.arena.allocator(), HOME);
+        },
+        .config_export => |path| {
+            const HOME = init.environ_map.get("HOME") orelse return error.NoHomeFound;
+            try r.profile.exportProfile(init.io, init.arena.allocator(), HOME, path);
+        },
+        .config_import => |path| {
+            const HOME = init.environ_map.get("HOME") orelse return error.NoHomeFound;
+            try r.profile.importProfile(init.io, init.arena.allocator(), HOME, path);
+        },
+        .config_doctor => {
+            var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
+            const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
+            const status = try run(
+                cwd_buffer[0..len],
+                init.gpa,
+                init.arena.allocator(),
+                init.io,
+                init.environ_map,
+                cli_flags,
+                .doctor,
+            );
+            if (status != 0) std.process.exit(status);
+        },
+        .export_session => |opts| {
+            var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
+            const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
+            const status = try run(
+                cwd_buffer[0..len],
+                init.gpa,
+                init.arena.allocator(),
+                init.io,
+                init.environ_map,
+                cli_flags,
+                .{ .export_session = opts },
+            );
+            if (status != 0) std.process.exit(status);
+        },
+        .batch => |args| {
+            var cwd_buffer: [std.posix.PATH_MAX]u8 = undefined;
+            const len = try std.Io.Dir.cwd().realPathFile(init.io, ".", &cwd_buffer);
+            const alloc = init.arena.allocator();
+            const files: []const []const u8 = if (args.resume_id != null)
+                &.{}
+            else if (args.files.len > 0) blk: {
+                const list = try alloc.alloc([]const u8, args.files.len);
+                for (args.files, list) |file, *slot| slot.* = file;
+                break :blk list;
+            } else try r.batch.readFileList(init.io, alloc);
+            const status = try run(
+                cwd_buffer[0..len],
+                init.gpa,
+                alloc,
+                init.io,
+                init.environ_map,
+                cli_flags,
+                .{ .batch = .{ .prompt = args.prompt, .files = files, .resume_id = args.resume_id } },
+            );
+            if (status != 0) std.process.exit(status);
+        },
+        .bench => |args| {
+            var exe_buf: [std.posix.PATH_MAX]u8 = undefined;
+            const status = try r.bench.run(
+                init.io,
+                init.arena.allocator(),
+                init.gpa,
+                init.environ_map,
+                selfExe(init.io, init.minimal.args, &exe_buf),
+                args.suite,
+                args.task,
+                cli_flags.json,
             );
+            if (status != 0) std.process.exit(status);
+        },
+        .pack_install => |source| {
+            const HOME = init.environ_map.get("HOME") orelse return error.NoHomeFound;
+            try r.pack.install(init.io, init.arena.allocator(), HOME, source);
         },
         .help => {
             std.debug.print(
@@ -211,6 +350,20 @@ pub fn main(init: std.process.Init) !void {
                 \\/any/path            start tui in rel path to current cwd (optional)
                 \\help                 display this
                 \\prompt "STRING"      run in current cwd with initial input
+                \\exec ["STRING"|-]    run one prompt without the TUI, stdin when omitted
+                \\restore [SESSION [FILE]]
+                \\                     list or copy back pre-edit backups from .blitz/backups
+                \\import               copy keys, allow rules and commands from Claude Code, Codex, aider
+                \\pack install PATH|URL install a pack of skills, commands, modes and tools
+                \\config export [FILE] write config, prompts and allowlists to one TOML profile
+                \\config import FILE   restore a profile, secrets excluded, old files kept as .bak
+                \\config doctor        show which config files load, in order, and the merged result
+                \\export [FORMAT] [SESSION]
+                \\                     print a stored session, the last one here without a name, as json, markdown or html
+                \\batch "STRING" [FILE..]
+                \\                     one request per file via the provider batch API, stdin lists files when omitted
+                \\batch resume ID      wait for an earlier batch and write its answers to .blitz/batches/ID
+                \\bench [SUITE [TASK]] run the tasks in SUITE (./bench) and report pass rate, turns, tokens
                 \\debug
                 \\  webfetch URL       test webfetch
                 \\
@@ -218,12 +371,99 @@ pub fn main(init: std.process.Init) !void {
                 \\  --log              write debug.log in path
                 \\  --strict           request permissions
                 \\  --clean            skip local user context
+                \\  --json             exec: write JSON lines, bench: JSON report
+                \\  --allow=TOOL,..    exec: approve permissions for these tools (all for any)
+                \\  --dir=PATH         use PATH as the project root instead of the current dir
                 \\
             , .{});
         },
     }
 }
 
+/// `$EDITOR` on `path` in the foreground, vi without one.
+fn runEditor(io: std.Io, path: []const u8) !void {
+    var child = try std.process.spawn(io, .{
+        .argv = &.{ "/bin/sh", "-c", "${EDITOR:-vi} \"$1\"", "sh", path },
+        .stdin = .inherit,
+        .stdout = .inherit,
+        .stderr = .inherit,
+    });
+    _ = try child.wait(io);
+}
+
+/// A new turn from the prompt in `input`. It stays in the input while the
+/// links in it are fetched or an expensive turn waits for enter.
+fn startTurn(app: *App, io: std.Io, config_lua: ?ConfigLuaInfo, input: []const u8, cost_confirmed: bool) !void {
+    const model_hint = r.app.parseModelHint(input);
+    // `?question` asks without tool access, nothing gets read or edited
+    const body = if (model_hint) |h| h.rest else input;
+    const ask_only = body.len > 1 and body[0] == '?';
+    const prompt_text = if (ask_only) std.mem.trimLeft(u8, body[1..], " ") else body;
+
+    if (app.dailyBudgetReached()) return;
+    // links wait for their pages, or for the popup asking about them
+    if (!cost_confirmed and try app.holdForLinks(prompt_text)) return;
+    const parts = try app.promptParts(app.sessionAlloc(), prompt_text) orelse return;
+    // an expensive turn waits in the input until enter confirms it
+    if (!cost_confirmed and try app.openCostPreview(parts, if (model_hint) |h| h.name else null)) return;
+    const chat_entry = try app.promptChatEntry(app.sessionAlloc(), input);
+    app.screenshot_buf = null;
+
+    app.pushHistory(app.appAlloc(), app.inputSlice());
+    if (config_lua) |info| app.saveHistory(info.dir_path);
+    app.todo_nudges_sent = 0;
+    app.user_turn +%= 1;
+    app.swarm.turn = app.user_turn;
+    try app.event_bus.emit(app, .{ .user_message_sent = app.inputSlice() });
+    // state.pushChatMessage(.user, input);
+
+    if (app.main_agent_id) |id| {
+        try app.pushEntry(app.sessionAlloc(), chat_entry);
+        app.swarm.getAgent(id).?.chat.tools_disabled = ask_only;
+        if (model_hint) |h| _ = app.useModelForTurn(id, h.name);
+        try app.swarm.runAgentWithMsg(id, parts);
+    } else {
+        const id = app.swarm.reserveFreeSlot().?;
+        try app.cmd_queue.append(io, .{
+            .spawn_agent = .{
+                .agent_id = id,
+                .agent_type = @intFromEnum(reg.AgentType.general),
+                .prompt = parts,
+                .chat_entry = chat_entry,
+                .tools_disabled = ask_only,
+                .model_hint = if (model_hint) |h| h.name else null,
+            },
+        });
+    }
+
+    app.run_state = .running;
+    app.input_buffer.clearRetainingCapacity();
+}
+
+/// Path bench children are started with. A relative argv[0] would not
+/// survive the change into the task directory, a bare name is found on PATH.
+fn selfExe(io: std.Io, args: std.process.Args, buf: []u8) []const u8 {
+    var it = args.iterate();
+    const argv0 = it.next() orelse return "blitz";
+    if (std.mem.indexOfScalar(u8, argv0, '/') == null) return argv0;
+    const len = std.Io.Dir.cwd().realPathFile(io, argv0, buf) catch return argv0;
+    return buf[0..len];
+}
+
+pub const RunMode = union(enum) {
+    /// interactive, the input prefilled with the prompt
+    tui: ?[]const u8,
+    /// one headless run, see headless.zig
+    exec: r.headless.Options,
+    /// print the config layers and the effective config
+    doctor,
+    /// print a session transcript, see transcript.zig
+    export_session: r.transcript.Options,
+    /// submit or resume a provider batch, see batch.zig
+    batch: r.batch.Options,
+};
+
+/// Returns the exit status, only an exec run can fail with one.
 pub fn run(
     cwd: []const u8,
     gpa: std.mem.Allocator,
@@ -231,8 +471,8 @@ pub fn run(
     io: std.Io,
     env: *const std.process.Environ.Map,
     flags: CliFlags,
-    prompt: ?[]const u8,
-) !void {
+    mode: RunMode,
+) !u8 {
     // Ensure config blitz.lua exists, get paths
     const config_lua: ?ConfigLuaInfo = ensureConfigLua(arena, io, env) catch null;
 
@@ -240,11 +480,15 @@ pub fn run(
     const context_factory = try r.ContextFactory.init(arena, io, HOME);
     context_factory.flags.skip_local_context_file = flags.no_context;
 
-    var term = try tui.Terminal.init(arena, io);
-    errdefer term.deinit();
-    defer term.deinit();
+    var term_storage: ?tui.Terminal = if (mode == .tui) try tui.Terminal.init(arena, io) else null;
+    defer if (term_storage) |*t| t.deinit();
 
     var app = try App.init(io, gpa, context_factory, cwd);
+    if (flags.debug_log) {
+        app.event_log.open(io);
+        try app.event_bus.subscribe(app.appAlloc(), app.event_log.subscriber());
+    }
+    try app.event_bus.subscribe(app.appAlloc(), app.hooks.subscriber());
     const swarm = try gpa.create(prv.Swarm);
     defer {
         swarm.deinit();
@@ -260,11 +504,16 @@ pub fn run(
 
                 if (en.agent_id != a.main_agent_id) return;
                 if (en.role == .user) {
+                    var has_result = false;
                     for (en.parts) |part| switch (part) {
-                        .tool_result => |result| a.setToolResult(en.agent_id, result) catch {},
+                        .tool_result => |result| {
+                            a.setToolResult(en.agent_id, result) catch {};
+                            has_result = true;
+                        },
                         else => {},
                     };
-                    return;
+                    // queued so tick can emit tool_call_complete on the main thread
+                    if (!has_result) return;
                 }
                 if (en.role == .system) return;
 
@@ -272,6 +521,7 @@ pub fn run(
                 defer g.unlock();
 
                 g.ptr.appendBounded(en) catch return;
+                a.waker.wake();
             }
         }).func,
         .permission = (struct {
@@ -283,7 +533,9 @@ pub fn run(
                 g.ptr.appendBounded(en) catch {
                     en.state = .denied;
                     en.event.set(a.io);
+                    return;
                 };
+                a.waker.wake();
             }
         }).func,
         .build_config = (struct {
@@ -312,31 +564,70 @@ pub fn run(
         }).func,
         .gen_system_reminders = &App.genSystemRemindersOpaque,
         .pop_queued_message = &App.popQueuedMessageOpaque,
+        .run_tool = (struct {
+            fn func(ptr: *anyopaque, tool: r.prv.tool.ToolFn, ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall) r.prv.adapter.ToolResult {
+                const a: *App = @ptrCast(@alignCast(ptr));
+                return a.hooks.runTool(a, tool, ctx, call);
+            }
+        }).func,
+        .pre_run = (struct {
+            fn func(ptr: *anyopaque, agent: *r.prv.agent.Agent) r.prv.Swarm.PreRun {
+                const a: *App = @ptrCast(@alignCast(ptr));
+                // every prompt passes here: typed, queued, headless, bench
+                // tasks through exec, retries and spawned agents
+                if (a.budgetRefusal()) |reason| return .{ .veto = reason };
+                return a.hooks.preRun(a, agent);
+            }
+        }).func,
     }, env);
 
     app.swarm = swarm;
+    swarm.pool.waker = .{ .ptr = &app.waker, .func = &tui.Waker.wakeOpaque };
 
     // Lua VM holds an opaque pointer to App + a getter for the mutable cfg
     // (swarm.cfg is *const, so a sibling accessor unwraps the const).
     app.lua_vm.setApp(&app);
     app.lua_vm.clearLastError();
     var lua_load_failed = false;
+    // for `config doctor`, in load order
+    var layers = std.ArrayList(r.doctor.Layer).empty;
     if (config_lua) |info| {
         const inject = try std.fmt.allocPrint(arena, "package.path = \"{s}?.lua;\" .. package.path", .{info.dir_path});
         app.lua_vm.exec(inject) catch |err| {
             lua_load_failed = true;
             std.log.scoped(.lua).err("failed to configure lua package.path: {s} ({any})", .{ app.lua_vm.getLastError(), err });
         };
+        var state: r.doctor.Layer.State = .loaded;
         app.lua_vm.load(info.abs_path) catch |err| {
             lua_load_failed = true;
+            state = .failed;
             std.log.scoped(.lua).err("failed to load {s}: {s} ({any})", .{ info.abs_path, app.lua_vm.getLastError(), err });
         };
+        try layers.append(arena, .{ .label = "global", .path = info.abs_path, .state = state });
+        for (r.pack.initScripts(io, arena, info.dir_path)) |script| {
+            state = .loaded;
+            app.lua_vm.loadPack(script) catch |err| {
+                lua_load_failed = true;
+                state = .failed;
+                std.log.scoped(.lua).err("failed to load pack {s}: {s} ({any})", .{ script.pack, app.lua_vm.getLastError(), err });
+            };
+            try layers.append(arena, .{ .label = "pack", .path = script.path, .state = state });
+        }
+        const settings_path = try std.fmt.allocPrint(arena, "{s}{s}", .{ info.dir_path, r.settings.FILE });
+        state = loadSettings(&app, io, settings_path);
+        if (state == .failed) lua_load_failed = true;
+        try layers.append(arena, .{ .label = "settings", .path = settings_path, .state = state });
     }
     if (cwdBlitzLuaExists(io)) {
+        var state: r.doctor.Layer.State = .loaded;
         app.lua_vm.load("blitz.lua") catch |err| {
             lua_load_failed = true;
+            state = .failed;
             std.log.scoped(.lua).err("failed to load blitz.lua: {s} ({any})", .{ app.lua_vm.getLastError(), err });
         };
+        try layers.append(arena, .{ .label = "project", .path = "./blitz.lua", .state = state });
+    } else {
+        try layers.append(arena, .{ .label = "project", .path = "./blitz.lua", .state = .missing });
     }
     if (!lua_load_failed) app.lua_vm.clearLastError();
     app.lua_vm.readConfigFields();
@@ -349,6 +640,21 @@ pub fn run(
     const lsp_servers = try app.lua_vm.getEnabledLspServers(arena);
     app.lsp_manager.loadServers(lsp_servers);
     var lsp_tools = app.lsp_manager.registeredTools();
+    app.plugin_manager.load(&swarm.exec, cwd, if (config_lua) |info| info.dir_path else null, env.get("HOME"));
+    if (mode == .tui) try app.askToolTrust();
+    app.preloadModel();
+    app.loadPrices();
+    {
+        const g = app.bash_allow.lock(io);
+        defer g.unlock();
+        g.ptr.load(io, app.gpa);
+    }
+    app.edit_allow.load(io, app.gpa);
+    {
+        const g = app.memory.lock(io);
+        defer g.unlock();
+        g.ptr.load(io, app.gpa);
+    }
 
     for (lua_tools) |tool| {
         try context_factory.add(arena, tool, .all);
@@ -359,6 +665,9 @@ pub fn run(
     for (lsp_tools) |tool| {
         try context_factory.add(arena, tool.tool, tool.flags);
     }
+    for (app.plugin_manager.registeredTools()) |tool| {
+        try context_factory.add(arena, tool.tool, tool.flags);
+    }
 
     for (lua_binds) |bind| {
         try app.keymap.custom.append(app.appAlloc(), .{ .key = bind.key, .action = .{ .lua = bind.lua_fn } });
@@ -369,42 +678,134 @@ pub fn run(
         break :blk stat.mtime.nanoseconds;
     };
     var config_lua_mtime: i128 = if (config_lua) |info| scanDirMaxMtime(io, info.dir_path) else 0;
-    var reload_tick: u32 = 0;
+    var last_reload_check_ms: i64 = 0;
+    var last_frame_ms: i64 = prv.http.nowMs(io);
 
     app.reset();
     app.flags.skip_permissions = !flags.strict_mode;
 
     if (config_lua) |info| app.loadHistory(app.appAlloc(), info.dir_path);
 
+    const prompt = switch (mode) {
+        .tui => |p| p,
+        .exec => |opts| return r.headless.run(&app, opts),
+        .batch => |opts| return r.batch.run(&app, opts),
+        .export_session => |opts| return r.transcript.runCli(&app, opts),
+        .doctor => {
+            var out_buf: [4096]u8 = undefined;
+            var stdout = std.Io.File.stdout().writer(io, &out_buf);
+            try r.doctor.write(&stdout.interface, &app, layers.items, env);
+            try stdout.interface.flush();
+            return if (lua_load_failed) 1 else 0;
+        },
+    };
+    const term = &term_storage.?;
+    app.graphics = r.media.detect(env);
+
     if (prompt) |p| {
         try app.input_buffer.appendSlice(app.sessionAlloc(), p);
         app.input_cursor = @intCast(app.input_buffer.items.len);
+    } else {
+        _ = r.session.recoverInterruptedRun(&app) catch false;
+        app.last_session_available = !app.resume_pending and r.session.hasLastSession(io);
     }
 
+    var term_status: ?r.app.TerminalStatus = null;
+    var shown_image: ?struct { data: [*]const u8, area: tui.Rect } = null;
+
     main_loop: while (true) {
-        // tick notifications
-        const had_visible_notifications = app.notifications.hasVisible();
-        app.notifications.tick(1.0 / 60.0);
-        if (had_visible_notifications or app.notifications.hasVisible()) app.dirty = true;
+        term.setMouseCapture(app.flags.mouse_capture);
+        if (app.terminal_request) |request| {
+            app.terminal_request = null;
+            switch (request) {
+                .copy => |text| term.copyToClipboard(text),
+                .edit => |path| {
+                    term.pause();
+                    runEditor(io, path) catch |err| {
+                        app.notifications.push(app.arena_app.allocator(), .err, "editor: {s}", .{@errorName(err)}) catch {};
+                    };
+                    term.unpause();
+                    shown_image = null;
+                    app.dirty = true;
+                },
+                .scratch => |path| {
+                    term.pause();
+                    runEditor(io, path) catch |err| {
+                        app.notifications.push(app.arena_app.allocator(), .err, "editor: {s}", .{@errorName(err)}) catch {};
+                    };
+                    term.unpause();
+                    std.Io.Dir.cwd().deleteFile(io, path) catch {};
+                    std.Io.Dir.cwd().deleteDir(io, std.fs.path.dirname(path).?) catch {};
+                    shown_image = null;
+                    app.dirty = true;
+                },
+            }
+        }
+        // a prompt held for its links goes out once their pages are in
+        if (!app.isRunning() and app.input_buffer.items.len > 0 and app.links.takeReady()) {
+            try startTurn(&app, io, config_lua, try gpa.dupe(u8, app.inputSlice()), false);
+            app.dirty = true;
+        }
+        const next_status = app.terminalStatus();
+        if (term_status != next_status) {
+            term_status = next_status;
+            var title_buf: [256]u8 = undefined;
+            const title = std.fmt.bufPrint(&title_buf, "blitzdenk — {s} [{s}]", .{
+                std.fs.path.basename(app.cwd),
+                next_status.label(),
+            }) catch "blitzdenk";
+            term.setStatus(title, next_status.progress());
+        }
 
-        if (app.dirty or app.main_agent_id == null) {
+        if (app.notifications.tick(prv.http.nowMs(io))) app.dirty = true;
+
+        if (app.dirty or app.dashboardAnimating()) {
             try term.drawWith(&app, App.render);
-            app.frame_count +%= 1;
+            // advance by wall time, slower polls must not slow the spinner
+            const frame_ms = prv.http.nowMs(io);
+            app.frame_count +%= @max(1, @as(usize, @intCast(@divTrunc(@max(0, frame_ms - last_frame_ms), r.app.FRAME_MS))));
+            last_frame_ms = frame_ms;
             app.dirty = false;
         }
 
+        // the preview popup's image goes over the finished frame
+        const overlay = app.imageOverlay();
+        if (shown_image) |shown| {
+            const same = if (overlay) |view| shown.data == view.image.data.ptr and std.meta.eql(shown.area, view.area.?) else false;
+            if (!same) {
+                term.writeAt(0, 0, r.media.clearImages(app.graphics));
+                term.repaint();
+                shown_image = null;
+                app.dirty = true;
+            }
+        } else if (overlay) |view| {
+            const area = view.area.?;
+            const cells = r.media.fit(view.size, .{ .cols = area.width, .rows = area.height });
+            var escapes = std.Io.Writer.Allocating.init(app.gpa);
+            defer escapes.deinit();
+            r.media.writeImage(&escapes.writer, app.graphics, view.image.data, cells) catch {};
+            term.writeAt(area.x + (area.width - cells.cols) / 2, area.y, escapes.written());
+            shown_image = .{ .data = view.image.data.ptr, .area = area };
+        }
+
         // TODO: cleanup state
-        if (app.running) {
+        if (app.isRunning()) {
             if (!app.swarm.tickAll()) {
+                var completed = false;
                 if (app.main_agent_id) |agent_id| {
                     const slot_state = app.swarm.getSlotState(agent_id);
                     if (slot_state == .failed) {
                         try app.event_bus.emit(&app, .{ .agent_failed = .{ .id = agent_id, .err = "" } });
                     } else {
                         try app.event_bus.emit(&app, .{ .agent_complete = agent_id });
+                        completed = app.run_state != .cancelling;
                     }
+                    if (app.swarm.getAgent(agent_id)) |agent| app.pushRunSummary(agent);
                 }
-                app.running = false;
+                app.run_state = .idle;
+                r.session.clearAutosave(io);
+                app.restoreTurnModel();
+                if (!completed or !try app.nudgeUnfinishedTodos()) try app.advanceTasks(completed);
             }
             app.dirty = true;
         }
@@ -420,13 +821,44 @@ pub fn run(
 
             if (g.ptr.items.len == 0) break :perm;
 
-            for (0..g.ptr.items.len) |_| {
+            while (g.ptr.items.len > 0) {
+                // late requests of a run being stopped never open a dialog
+                if (app.run_state != .cancelling) {
+                    // the queue waits while on_permission hooks decide on its head
+                    const verdict = app.hooks.permissionVerdict(&app, g.ptr.items[0]) orelse break :perm;
+                    if (verdict == .deny) {
+                        const next = g.ptr.swapRemove(0);
+                        try app.event_bus.emit(&app, .{ .permission_requested = .{ .call_id = next.call_id, .level = next.level } });
+                        app.notifications.push(app.arena_app.allocator(), .warn, "on_permission hook denied: {s}", .{verdict.deny}) catch {};
+                        next.state = .{ .message = verdict.deny };
+                        try app.event_bus.emit(&app, .{ .permission_resolved = .{ .call_id = next.call_id, .state = next.state } });
+                        next.event.set(app.io);
+                        continue;
+                    }
+                }
+
                 const next = g.ptr.swapRemove(0);
                 const is_ask = next.payload == .ask or next.payload == .plan;
+                try app.event_bus.emit(&app, .{ .permission_requested = .{ .call_id = next.call_id, .level = next.level } });
+
+                if (app.run_state == .cancelling) {
+                    app.hooks.dropPermissionCheck(&app);
+                    next.state = .denied;
+                    try app.event_bus.emit(&app, .{ .permission_resolved = .{ .call_id = next.call_id, .state = next.state } });
+                    next.event.set(app.io);
+                    continue;
+                }
 
                 // check permission level against flags
-                if (app.flags.skip_permissions and !app.swarm.exec.ssh_active and !is_ask) {
+                const replayed = app.approvedBefore(next.payload);
+                if ((app.flags.skip_permissions and !app.swarm.exec.ssh_active and !is_ask) or
+                    (next.level != .dangerous and (app.permissionAllowlisted(next.payload) or replayed)))
+                {
+                    if (replayed and next.level != .dangerous and !app.flags.skip_permissions) {
+                        app.notifications.append(app.arena_app.allocator(), "approved again, identical to an earlier approval", .{}) catch {};
+                    }
                     try app.persist_permission_to_history(next);
+                    try app.event_bus.emit(&app, .{ .permission_resolved = .{ .call_id = next.call_id, .state = .approved } });
                     next.state = .approved;
                     next.event.set(app.io);
                     continue;
@@ -434,6 +866,10 @@ pub fn run(
 
                 if (app.swarm.getSlotState(next.agent_id) == .active) {
                     app.active_permission = next;
+                    app.hooks.fire(&app, .{
+                        .event = .permission_requested,
+                        .message = r.headless.permissionTool(next.payload) orelse "question",
+                    });
                     break :perm;
                 }
             }
@@ -447,9 +883,9 @@ pub fn run(
         }
 
         // Lua hot-reload: poll mtime every ~1s (cwd blitz.lua + config dir)
-        reload_tick +%= 1;
-        if (reload_tick >= 60) {
-            reload_tick = 0;
+        const now_ms = prv.http.nowMs(io);
+        if (now_ms - last_reload_check_ms >= 1000) {
+            last_reload_check_ms = now_ms;
             const new_cwd_mtime: i128 = blk: {
                 const stat = std.Io.Dir.cwd().statFile(io, "blitz.lua", .{}) catch break :blk 0;
                 break :blk stat.mtime.nanoseconds;
@@ -484,6 +920,9 @@ pub fn run(
                         lua_reload_failed = true;
                         std.log.scoped(.lua).err("hot-reload: failed to load {s}: {s} ({any})", .{ info.abs_path, app.lua_vm.getLastError(), err });
                     };
+                    if (!loadPacks(&app, io, arena, info.dir_path)) lua_reload_failed = true;
+                    const settings_path = try std.fmt.allocPrint(arena, "{s}{s}", .{ info.dir_path, r.settings.FILE });
+                    if (loadSettings(&app, io, settings_path) == .failed) lua_reload_failed = true;
                 }
                 if (cwdBlitzLuaExists(io)) {
                     app.lua_vm.load("blitz.lua") catch |err| {
@@ -494,6 +933,7 @@ pub fn run(
                 if (!lua_reload_failed) app.lua_vm.clearLastError();
                 app.lua_vm.readConfigFields();
                 try app.lua_vm.publishAvailableSystems(context_factory);
+                app.preloadModel();
                 app.dirty = true;
 
                 context_factory.clearTools();
@@ -521,6 +961,10 @@ pub fn run(
                 app.lsp_manager.loadServers(reloaded_lsp_servers);
                 lsp_tools = app.lsp_manager.registeredTools();
                 for (lsp_tools) |tool| try context_factory.add(arena, tool.tool, tool.flags);
+                // new and edited tools are picked up here, untrusted ones ask again
+                app.plugin_manager.load(&swarm.exec, app.cwd, if (config_lua) |info| info.dir_path else null, env.get("HOME"));
+                for (app.plugin_manager.registeredTools()) |tool| try context_factory.add(arena, tool.tool, tool.flags);
+                if (app.popup == null) try app.askToolTrust();
 
                 lua_binds = try app.lua_vm.getRegisteredKeybinds(arena);
                 app.keymap.custom.clearRetainingCapacity();
@@ -536,7 +980,8 @@ pub fn run(
             }
         }
 
-        term.pollAndEnqueue(16);
+        // idle: block on stdin, nothing to animate and no agent events to expect
+        _ = term.pollAndEnqueue(app.pollTimeoutMs(), &app.waker);
         try app.tick();
 
         while (true) {
@@ -547,18 +992,39 @@ pub fn run(
                     if (app.keymap.parse(k)) |action| {
                         switch (action) {
                             .exit => {
-                                if (app.active_permission == null and app.running) {
+                                if (app.active_permission == null and app.isRunning()) {
+                                    app.run_state = .cancelling;
                                     try app.cmd_queue.append(io, .cancel);
                                 } else {
+                                    // tools blocked on a dialog must not outlive the loop
+                                    app.cancelPermissions();
+                                    r.session.writeLastSession(&app);
                                     break :main_loop;
                                 }
                                 continue;
                             },
                             .cancel => {
-                                if (app.running) {
+                                if (app.popup != null) {
+                                    app.popup = null;
+                                    continue;
+                                }
+                                if (app.message_select.current != null) {
+                                    app.closeMessageSelect();
+                                    continue;
+                                }
+                                if (app.chat_search.active) {
+                                    app.closeChatSearch();
+                                    continue;
+                                }
+                                if (app.active_permission != null) {
+                                    try app.denyAndStop();
+                                } else if (app.isRunning()) {
+                                    app.run_state = .cancelling;
                                     try app.cmd_queue.append(io, .cancel);
                                 } else {
+                                    app.cancelToolRetry();
                                     app.screenshot_buf = null;
+                                    app.links.send_pending = false;
                                 }
                             },
                             .scroll_down => {
@@ -599,12 +1065,58 @@ pub fn run(
                                 continue;
                             },
                             .noop => {},
-                            .complete => {
-                                if (app.completion_suggestion) |sug| {
-                                    app.input_buffer.clearRetainingCapacity();
-                                    try app.input_buffer.appendSlice(app.sessionAlloc(), sug);
-                                    app.input_cursor = @intCast(sug.len);
-                                }
+                            .complete => if (!try app.grepOpenSelected() and !try app.referencesOpenSelected() and !try app.sessionsResumeSelected()) try app.acceptCompletion(),
+                            .show_changes => {
+                                if (app.popup != null) app.popup = null else try app.openChangesPopup();
+                                continue;
+                            },
+                            .show_token_heatmap => {
+                                if (app.popup != null) app.popup = null else try app.openTokenHeatmap();
+                                continue;
+                            },
+                            .show_usage => {
+                                if (app.popup != null) app.popup = null else try app.openUsagePopup();
+                                continue;
+                            },
+                            .quote_tool_error => {
+                                if (app.input_mode == .text) try app.quoteLastToolError();
+                                continue;
+                            },
+                            .tool_errors => {
+                                if (app.popup != null and app.popup.?.kind == .tool_errors) app.popup = null else try app.openToolErrorsPopup();
+                                continue;
+                            },
+                            .grep => {
+                                if (app.popup != null and app.popup.?.kind == .grep) app.popup = null else try app.openGrepPopup();
+                                continue;
+                            },
+                            .references => {
+                                if (app.popup != null and app.popup.?.kind == .references) app.popup = null else try app.openReferencesPopup();
+                                continue;
+                            },
+                            .sessions => {
+                                if (app.popup != null and app.popup.?.kind == .sessions) app.popup = null else try app.openSessionsPopup();
+                                continue;
+                            },
+                            .search_chat => {
+                                if (app.chat_search.active) app.closeChatSearch() else app.openChatSearch();
+                                continue;
+                            },
+                            .select_message => {
+                                if (app.input_mode == .text) app.selectMessage(false);
+                                continue;
+                            },
+                            .next_tab => {
+                                app.switchTab(1) catch |err| {
+                                    app.notifications.push(app.arena_app.allocator(), .err, "tab: {s}", .{@errorName(err)}) catch {};
+                                };
+                                continue;
+                            },
+                            .prev_tab => {
+                                app.switchTab(-1) catch |err| {
+                                    app.notifications.push(app.arena_app.allocator(), .err, "tab: {s}", .{@errorName(err)}) catch {};
+                                };
+                                continue;
                             },
                         }
                     }
@@ -612,15 +1124,19 @@ pub fn run(
                         .char => |c| {
                             switch (app.input_mode) {
                                 .text => {
-                                    app.appendBytes(k.textSlice());
+                                    if (!try app.messageSelectKey(c) and !try app.chatSearchKey(k.textSlice()) and !try app.grepKey(k.textSlice()) and !try app.tokenHeatmapKey(c) and !try app.bashAllowKey(c) and !try app.referencesKey(c) and !try app.sessionsKey(c) and !try app.checkpointsKey(c) and !try app.todosKey(c) and !try app.tasksKey(c) and !try app.memoryKey(c) and !try app.providersKey(c) and !try app.linksKey(c) and !try app.toolErrorsKey(c) and !try app.settingsKey(c) and !app.outputKey(c)) app.appendBytes(k.textSlice());
                                 },
                                 .perm_select => |*ps| {
                                     const entry = app.active_permission orelse break;
+                                    if (try app.reviewKey(c)) break;
+                                    if (c == 's') {
+                                        try app.denyAndStop();
+                                        break;
+                                    }
 
                                     const max_sel: u8 = switch (entry.payload) {
                                         .ask => |a| @intCast(@min(a.options.len, tools.ask.MAX_OPTIONS)),
-                                        .plan => 3,
-                                        else => 2,
+                                        .plan, .call, .diff, .commit => 3,
                                     };
                                     if (c == 'j' and ps.selected < max_sel) ps.selected += 1;
                                     if (c == 'k' and ps.selected > 0) ps.selected -= 1;
@@ -642,7 +1158,9 @@ pub fn run(
                             }
                         },
                         .arrow_up => switch (app.input_mode) {
-                            .text => if (!app.running) app.historyUp(),
+                            .text => if (app.popup) |*popup| {
+                                popup.moveSelection(-1);
+                            } else if (!app.isRunning()) app.historyUp(),
                             .perm_select => |*ps| {
                                 if (ps.selected > 0) ps.selected -= 1;
                             },
@@ -650,13 +1168,14 @@ pub fn run(
                             .passphrase => {},
                         },
                         .arrow_down => switch (app.input_mode) {
-                            .text => if (!app.running) app.historyDown(),
+                            .text => if (app.popup) |*popup| {
+                                popup.moveSelection(1);
+                            } else if (!app.isRunning()) app.historyDown(),
                             .perm_select => |*ps| {
                                 const entry = app.active_permission orelse break;
                                 const max_sel: u8 = switch (entry.payload) {
                                     .ask => |a| @intCast(@min(a.options.len, tools.ask.MAX_OPTIONS)),
-                                    .plan => 3,
-                                    else => 2,
+                                    .plan, .call, .diff, .commit => 3,
                                 };
                                 if (ps.selected < max_sel) ps.selected += 1;
                             },
@@ -664,7 +1183,7 @@ pub fn run(
                             .passphrase => {},
                         },
                         .backspace => switch (app.input_mode) {
-                            .text => app.deleteChar(),
+                            .text => if (!app.chatSearchBackspace() and !try app.grepBackspace()) app.deleteChar(),
                             .perm_select => {},
                             .perm_message => |*pm| {
                                 while (pm.len > 0) {
@@ -685,6 +1204,11 @@ pub fn run(
 
                                 const is_ask = entry.payload == .ask;
 
+                                if (pm.comment) {
+                                    try app.addReviewComment(pm.buf[0..pm.len]);
+                                    app.enterPermSelect();
+                                    break;
+                                }
                                 if (pm.len == 0) {
                                     app.enterPermSelect();
                                     break;
@@ -693,7 +1217,7 @@ pub fn run(
                                 if (is_ask) {
                                     app.resolveActivePermission(.{ .message = msg });
                                 } else {
-                                    app.resolveActivePermission(.denied);
+                                    try app.declineWithReview(msg);
                                 }
                                 app.auto_scroll = true;
                                 app.scroll_offset = 0;
@@ -715,14 +1239,14 @@ pub fn run(
                                     break;
                                 }
 
-                                // Generic 3-option (yes / no / enter message)
-                                switch (ps.selected) {
-                                    0 => {
-                                        try app.persist_permission_to_history(entry);
-                                        app.resolveActivePermission(.approved);
-                                    },
-                                    1 => app.resolveActivePermission(.denied),
-                                    2 => {
+                                // Generic 4-option (yes / always allow / no / enter message),
+                                // plans have no always allow
+                                const sel = if (entry.payload == .plan and ps.selected > 0) ps.selected + 1 else ps.selected;
+                                switch (sel) {
+                                    0 => try app.approveWithReview(),
+                                    1 => try app.alwaysAllowActivePermission(),
+                                    2 => try app.declineWithReview(""),
+                                    3 => {
                                         app.enterPermMessage();
                                         break;
                                     },
@@ -732,6 +1256,20 @@ pub fn run(
                                 app.scroll_offset = 0;
                             },
                             .text => {
+                                const cost_confirmed = app.costPreviewConfirmed();
+                                if (try app.linksConfirm()) break;
+                                if (try app.toolTrustConfirm()) {
+                                    // the reload loads what was just trusted
+                                    cwd_lua_mtime = -1;
+                                    last_reload_check_ms = 0;
+                                    break;
+                                }
+                                if (try app.toolErrorsConfirm()) break;
+                                if (try app.chatSearchConfirm()) break;
+                                if (try app.grepInsertSelected()) break;
+                                if (try app.referencesOpenSelected()) break;
+                                if (try app.sessionsResumeSelected()) break;
+                                if (try app.providersUseSelected()) break;
                                 if (app.input_buffer.items.len == 0) break;
                                 const input = gpa.dupe(u8, app.inputSlice()) catch break;
 
@@ -752,11 +1290,73 @@ pub fn run(
                                     if (cmd) |c| {
                                         switch (c) {
                                             .clear => {
-                                                app.reset();
+                                                r.session.archiveAndReset(&app);
                                                 break;
                                             },
                                             .help => {
-                                                app.pushSystemMessage("Not yet implemented. You are on your own!", .{});
+                                                app.input_buffer.clearRetainingCapacity();
+                                                try app.openHelpPopup();
+                                            },
+                                            .mouse => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                app.flags.mouse_capture = !app.flags.mouse_capture;
+                                                app.notifications.append(app.arena_app.allocator(), "mouse capture {s}", .{if (app.flags.mouse_capture) "on" else "off"}) catch {};
+                                            },
+                                            .research => |arg| {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                const research = reg.Mode.research;
+                                                const turns = std.fmt.parseInt(u32, arg, 10) catch 0;
+                                                if (turns > 0) app.research_turns = turns;
+                                                const next = if (turns == 0 and app.mode == research) reg.Mode.exec else research;
+                                                try app.cmd_queue.append(io, .{ .set_mode = @intFromEnum(next) });
+                                                if (next == research) {
+                                                    app.notifications.append(app.arena_app.allocator(), "research mode, {d} turns", .{app.research_turns}) catch {};
+                                                } else {
+                                                    app.notifications.append(app.arena_app.allocator(), "research mode off", .{}) catch {};
+                                                }
+                                            },
+                                            .expand => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                app.flags.expand_messages = !app.flags.expand_messages;
+                                                app.notifications.append(app.arena_app.allocator(), "long messages and explorations {s}", .{if (app.flags.expand_messages) "expanded" else "folded"}) catch {};
+                                            },
+                                            .notifications => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                try app.openNotificationsPopup();
+                                            },
+                                            .usage => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                try app.openUsagePopup();
+                                            },
+                                            .output => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                try app.openOutputPopup();
+                                            },
+                                            .errors => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                try app.openToolErrorsPopup();
+                                            },
+                                            .tab => |rest| {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                var args = std.mem.tokenizeScalar(u8, rest, ' ');
+                                                const sub = args.next() orelse "";
+                                                const result = if (std.mem.eql(u8, sub, "new"))
+                                                    app.openTab(args.next())
+                                                else if (std.mem.eql(u8, sub, "close"))
+                                                    app.closeTab()
+                                                else
+                                                    app.notifications.append(app.arena_app.allocator(), "usage: :tab new [name] | :tab close", .{});
+                                                result catch |err| {
+                                                    app.notifications.push(app.arena_app.allocator(), .err, "tab: {s}", .{@errorName(err)}) catch {};
+                                                };
+                                            },
+                                            .search => |query| {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                app.openChatSearch();
+                                                if (query.len > 0) {
+                                                    _ = try app.chatSearchKey(query);
+                                                    _ = try app.chatSearchConfirm();
+                                                }
                                             },
                                             .ssh => |args| {
                                                 handleSshCommand(&app, &app.swarm.exec, gpa, args);
@@ -772,6 +1372,101 @@ pub fn run(
                                                 }
                                                 app.input_buffer.clearRetainingCapacity();
                                             },
+                                            .@"resume" => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                const agent_id = app.main_agent_id orelse break;
+                                                if (!app.resume_pending or app.isRunning()) {
+                                                    app.notifications.append(app.arena_app.allocator(), "nothing to resume", .{}) catch {};
+                                                    break;
+                                                }
+                                                app.resume_pending = false;
+                                                try app.swarm.runAgent(agent_id);
+                                                try app.event_bus.emit(&app, .{ .agent_started = agent_id });
+                                                app.run_state = .running;
+                                            },
+                                            .handoff => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                if (app.isRunning()) {
+                                                    app.notifications.append(app.arena_app.allocator(), "agent is busy, cancel first", .{}) catch {};
+                                                    break;
+                                                }
+                                                try r.session.startHandoff(&app);
+                                            },
+                                            .compact => {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                if (app.isRunning()) {
+                                                    app.notifications.append(app.arena_app.allocator(), "agent is busy, cancel first", .{}) catch {};
+                                                    break;
+                                                }
+                                                if (app.main_agent_id == null) {
+                                                    app.notifications.append(app.arena_app.allocator(), "nothing to compact yet", .{}) catch {};
+                                                    break;
+                                                }
+                                                try app.cmd_queue.append(app.io, .compact);
+                                            },
+                                            .new => |args| {
+                                                try app.startTemplate(args.template, args.name);
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .allow => |pattern| {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                if (pattern.len == 0) {
+                                                    try app.openBashAllowPopup();
+                                                } else if (std.mem.startsWith(u8, pattern, "edit ")) {
+                                                    try app.allowEditPattern(pattern["edit ".len..]);
+                                                } else {
+                                                    try app.allowBashPattern(pattern);
+                                                }
+                                            },
+                                            .undo => |arg| {
+                                                app.input_buffer.clearRetainingCapacity();
+                                                try app.undoCommand(arg);
+                                            },
+                                            .todo => |arg| {
+                                                try app.todoCommand(arg);
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .task => |arg| {
+                                                app.taskCommand(arg) catch |err| {
+                                                    app.notifications.push(app.arena_app.allocator(), .err, "task: {s}", .{@errorName(err)}) catch {};
+                                                };
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .memory => |arg| {
+                                                try app.memoryCommand(arg);
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .provider => |arg| {
+                                                try app.switchProvider(arg);
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .image => |arg| {
+                                                try app.imageCommand(arg);
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .export_session => |arg| {
+                                                r.transcript.exportCommand(&app, arg) catch |err| {
+                                                    app.notifications.push(app.arena_app.allocator(), .err, "export: {s}", .{@errorName(err)}) catch {};
+                                                };
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .set => |arg| {
+                                                try app.setCommand(arg);
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
+                                            .sessions => |rest| {
+                                                var args_buf: [3][]const u8 = undefined;
+                                                var count: usize = 0;
+                                                var args = std.mem.tokenizeScalar(u8, rest, ' ');
+                                                while (args.next()) |arg| : (count += 1) {
+                                                    if (count == args_buf.len) break;
+                                                    args_buf[count] = arg;
+                                                }
+                                                app.sessionsCommand(args_buf[0..count]) catch |err| {
+                                                    app.notifications.push(app.arena_app.allocator(), .err, "sessions: {s}", .{@errorName(err)}) catch {};
+                                                };
+                                                app.input_buffer.clearRetainingCapacity();
+                                            },
                                             .ssh_off => {
                                                 app.swarm.exec.clearSsh();
                                                 app.notifications.append(app.arena_app.allocator(), "SSH mode disabled", .{}) catch {};
@@ -783,26 +1478,35 @@ pub fn run(
                                     break;
                                 }
 
-                                if (app.running) {
+                                const model_hint = r.app.parseModelHint(input);
+
+                                if (app.isRunning()) {
+                                    if (model_hint != null) {
+                                        app.notifications.append(app.arena_app.allocator(), "@model applies to a new turn, send it once the run ends", .{}) catch {};
+                                        break;
+                                    }
+                                    const follow_up_parts = if (app.main_agent_id) |agent_id|
+                                        try app.promptParts(app.swarm.getAgent(agent_id).?.arena.allocator(), input) orelse break
+                                    else
+                                        null;
+                                    // a follow-up adds to the same history, it is priced like a new turn
+                                    if (follow_up_parts) |parts| {
+                                        if (!cost_confirmed and try app.openCostPreview(parts, null)) break;
+                                    }
                                     app.pushHistory(app.appAlloc(), input);
                                     if (config_lua) |info| app.saveHistory(info.dir_path);
+                                    app.todo_nudges_sent = 0;
+                                    app.user_turn +%= 1;
+                                    app.swarm.turn = app.user_turn;
                                     try app.event_bus.emit(&app, .{ .user_message_sent = input });
                                     if (app.main_agent_id) |agent_id| {
                                         const ag = app.swarm.getAgent(agent_id).?;
+                                        // a follow-up always gets the tools back
+                                        ag.chat.tools_disabled = false;
                                         const alloc = ag.arena.allocator();
-                                        const len: usize = if (app.screenshot_buf != null) 2 else 1;
+                                        const parts = follow_up_parts.?;
 
-                                        const parts = try alloc.alloc(prv.adapter.ContentPart, len);
-                                        parts[0] = .{ .text = input };
-
-                                        if (app.screenshot_buf) |buf| {
-                                            parts[1] = .{ .image = .{
-                                                .media_type = "image/png",
-                                                .data = buf,
-                                            } };
-                                        }
-
-                                        const chat_msg = try ChatEntry.userMessageSimple(alloc, .user, input);
+                                        const chat_msg = try app.promptChatEntry(alloc, input);
                                         try app.cmd_queue.append(io, .{ .queue_agent_message = .{
                                             .agent_id = agent_id,
                                             .parts = parts,
@@ -816,43 +1520,7 @@ pub fn run(
                                     continue;
                                 }
 
-                                app.pushHistory(app.appAlloc(), app.inputSlice());
-                                if (config_lua) |info| app.saveHistory(info.dir_path);
-                                try app.event_bus.emit(&app, .{ .user_message_sent = app.inputSlice() });
-                                // state.pushChatMessage(.user, input);
-
-                                const alloc = gpa;
-                                const parts: []const prv.adapter.ContentPart = if (app.screenshot_buf) |img_data|
-                                    alloc.dupe(prv.adapter.ContentPart, &.{
-                                        .{ .text = input },
-                                        .{ .image = .{ .media_type = "image/png", .data = img_data } },
-                                    }) catch break
-                                else
-                                    alloc.dupe(prv.adapter.ContentPart, &.{
-                                        .{ .text = input },
-                                    }) catch break;
-
-                                app.screenshot_buf = null;
-
-                                const chat_entry = try ChatEntry.userMessageSimple(app.sessionAlloc(), .user, input);
-
-                                if (app.main_agent_id) |id| {
-                                    try app.chat_entries.append(app.sessionAlloc(), chat_entry);
-                                    try app.swarm.runAgentWithMsg(id, parts);
-                                } else {
-                                    const id = app.swarm.reserveFreeSlot().?;
-                                    try app.cmd_queue.append(io, .{
-                                        .spawn_agent = .{
-                                            .agent_id = id,
-                                            .agent_type = @intFromEnum(reg.AgentType.general),
-                                            .prompt = parts,
-                                            .chat_entry = chat_entry,
-                                        },
-                                    });
-                                }
-
-                                app.running = true;
-                                app.input_buffer.clearRetainingCapacity();
+                                try startTurn(&app, io, config_lua, input, cost_confirmed);
                             },
                             .passphrase => {
                                 handleSshUnlock(&app, &app.swarm.exec, gpa);
@@ -906,6 +1574,7 @@ pub fn run(
 
         try app.cmd_queue.apply(io, &app);
     }
+    return 0;
 }
 
 /// Probe `ssh -o BatchMode=yes user@host true`. On success → set SSH target
@@ -1069,11 +1738,58 @@ pub const AppCommand = union(enum) {
     ssh_off,
     /// change CWD
     cd: []const u8,
+    /// continue a run restored from an interrupted session
+    @"resume",
+    /// :allow <pattern> adds to the bash allowlist, bare :allow opens it
+    allow: []const u8,
+    /// toggle mouse capture
+    mouse,
+    /// toggle folding of huge messages and exploration blocks
+    expand,
+    /// open the notification log
+    notifications,
+    /// token and cost breakdown per prompt, model and tool
+    usage,
+    /// :research [turns] enters research mode, bare :research toggles it
+    research: []const u8,
+    /// start from a summary of the previous session
+    handoff,
+    /// summarize the older history of the main agent now
+    compact,
+    /// :new <template> <name> scaffolds a project from a template
+    new: NewArgs,
+    /// :sessions opens the session browser, `rename <old> <new>`, `delete <name>`
+    sessions: []const u8,
+    /// :undo reverts the last agent edit, `turn` the whole turn, `list` opens the checkpoints
+    undo: []const u8,
+    /// :todos opens the todo list, `:todo add|edit ...` changes it
+    todo: []const u8,
+    /// :settings opens the settings popup, `:set <key> <value>` changes one
+    set: []const u8,
+    /// :search <query> highlights the query in the chat and jumps to the newest match
+    search: []const u8,
+    /// page through the newest tool output that was cut for the model
+    output,
+    /// :tab new [name] opens a conversation next to this one, `:tab close` drops it
+    tab: []const u8,
+    /// :task <prompt> queues a prompt as its own run, bare :tasks opens the queue
+    task: []const u8,
+    /// :memory opens the project memory, `:memory add|edit ...` changes it
+    memory: []const u8,
+    /// :provider <name> switches to a provider profile, bare :providers lists them
+    provider: []const u8,
+    /// :image [path] previews a saved image, the newest one without a path
+    image: []const u8,
+    /// :export [json|markdown|html] [path] writes the session transcript
+    export_session: []const u8,
+    /// failed tool calls of the turn, enter runs one again
+    errors,
 
     pub const SshArgs = struct { user: []const u8, host: []const u8, cwd: []const u8 };
+    pub const NewArgs = struct { template: []const u8, name: []const u8 };
 
     pub fn parse(raw: []const u8) ?AppCommand {
-        const input = if (raw.len > 0 and (raw[0] == ':' or raw[1] == '/')) raw[1..] else raw;
+        const input = if (raw.len > 0 and (raw[0] == ':' or raw[0] == '/')) raw[1..] else raw;
         var it = std.mem.splitScalar(u8, input, ' ');
         const verb = it.first();
         const rest = it.rest();
@@ -1084,6 +1800,36 @@ pub const AppCommand = union(enum) {
 
         if (std.mem.eql(u8, verb, "clear")) return .clear;
         if (std.mem.eql(u8, verb, "help")) return .help;
+        if (std.mem.eql(u8, verb, "resume")) return .@"resume";
+        if (std.mem.eql(u8, verb, "mouse")) return .mouse;
+        if (std.mem.eql(u8, verb, "expand")) return .expand;
+        if (std.mem.eql(u8, verb, "notifications")) return .notifications;
+        if (std.mem.eql(u8, verb, "usage")) return .usage;
+        if (std.mem.eql(u8, verb, "research")) return .{ .research = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "handoff")) return .handoff;
+        if (std.mem.eql(u8, verb, "compact")) return .compact;
+        if (std.mem.eql(u8, verb, "new")) {
+            var args = std.mem.tokenizeScalar(u8, rest, ' ');
+            return .{ .new = .{ .template = args.next() orelse "", .name = args.next() orelse "" } };
+        }
+        if (std.mem.eql(u8, verb, "allow")) return .{ .allow = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "sessions")) return .{ .sessions = rest };
+        if (std.mem.eql(u8, verb, "undo")) return .{ .undo = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "todos")) return .{ .todo = "" };
+        if (std.mem.eql(u8, verb, "todo")) return .{ .todo = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "settings")) return .{ .set = "" };
+        if (std.mem.eql(u8, verb, "set")) return .{ .set = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "search")) return .{ .search = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "output")) return .output;
+        if (std.mem.eql(u8, verb, "tab")) return .{ .tab = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "tasks")) return .{ .task = "" };
+        if (std.mem.eql(u8, verb, "task")) return .{ .task = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "memory")) return .{ .memory = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "providers")) return .{ .provider = "" };
+        if (std.mem.eql(u8, verb, "provider")) return .{ .provider = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "image")) return .{ .image = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "export")) return .{ .export_session = std.mem.trim(u8, rest, " ") };
+        if (std.mem.eql(u8, verb, "errors")) return .errors;
         if (std.mem.eql(u8, verb, "ssh")) {
             if (rest.len == 0 or std.mem.eql(u8, rest, "off")) return .ssh_off;
             return parseSsh(rest);
@@ -1110,6 +1856,8 @@ pub const CliFlags = packed struct {
     strict_mode: bool = false,
     /// don't load AGENTS.md
     no_context: bool = false,
+    /// `exec` output as JSON lines
+    json: bool = false,
 
     fn applyToken(self: *CliFlags, tok: []const u8) bool {
         if (std.mem.eql(u8, tok, "--log")) {
@@ -1127,6 +1875,11 @@ pub const CliFlags = packed struct {
             return true;
         }
 
+        if (std.mem.eql(u8, tok, "--json")) {
+            self.json = true;
+            return true;
+        }
+
         return false;
     }
 };
@@ -1137,9 +1890,15 @@ pub const CliFlags = packed struct {
 pub const CliArgs = struct {
     flags: CliFlags,
     positional: []const [:0]const u8,
+    /// `--allow=bash,edit` for `exec`
+    allow: []const u8 = "",
+    /// `--dir=PATH`, the project root for every command
+    dir: []const u8 = "",
 
     pub fn split(args: std.process.Args, buf: [][:0]const u8) CliArgs {
         var flags = CliFlags{};
+        var allow: []const u8 = "";
+        var dir: []const u8 = "";
         var n: usize = 0;
 
         var it = args.iterate();
@@ -1147,6 +1906,8 @@ pub const CliArgs = struct {
 
         while (it.next()) |arg| {
             if (arg.len >= 2 and arg[0] == '-' and arg[1] == '-') {
+                if (std.mem.startsWith(u8, arg, "--allow=")) allow = arg["--allow=".len..];
+                if (std.mem.startsWith(u8, arg, "--dir=")) dir = arg["--dir=".len..];
                 _ = flags.applyToken(arg);
                 continue;
             }
@@ -1156,16 +1917,52 @@ pub const CliArgs = struct {
             }
         }
 
-        return .{ .flags = flags, .positional = buf[0..n] };
+        return .{ .flags = flags, .positional = buf[0..n], .allow = allow, .dir = dir };
     }
 };
 
 pub const CliCommand = union(enum) {
     run: []const u8, // '.', './', /full/path/to/dir
     prompt: []const u8, // prefill input in CWD
+    /// run one prompt without the TUI, null reads it from stdin
+    exec: ?[]const u8,
     debug: DebugCmd,
+    /// copy session backups back into the cwd
+    restore: RestoreArgs,
+    /// one-shot import of settings from other agent CLIs
+    import,
+    /// `pack install <path|url>`
+    pack_install: []const u8,
+    /// `config export [file]`, config and allowlists as one TOML profile
+    config_export: []const u8,
+    /// `config import <file>`
+    config_import: []const u8,
+    /// `config doctor`, the merged config as it loads
+    config_doctor,
+    /// `export [FORMAT] [SESSION]`, a session transcript on stdout
+    export_session: r.transcript.Options,
+    /// `bench [suite [task]]`, score recorded tasks with the current config
+    bench: BenchArgs,
+    /// `batch "PROMPT" [FILE...]` or `batch resume ID`, one request per file via the batch API
+    batch: BatchArgs,
     help,
 
+    pub const RestoreArgs = struct {
+        session: ?[]const u8 = null,
+        file: ?[]const u8 = null,
+    };
+
+    pub const BenchArgs = struct {
+        suite: []const u8 = r.bench.DEFAULT_SUITE,
+        task: ?[]const u8 = null,
+    };
+
+    pub const BatchArgs = struct {
+        prompt: []const u8 = "",
+        files: []const [:0]const u8 = &.{},
+        resume_id: ?[]const u8 = null,
+    };
+
     pub const DebugCmd = union(enum) {
         webfetch: []const u8,
     };
@@ -1187,7 +1984,68 @@ pub const CliCommand = union(enum) {
             return .{ .cmd = .{ .prompt = sub } };
         }
 
+        if (std.mem.eql(u8, head, "exec")) {
+            if (rest.len > 1) return .{ .err = "usage: exec [\"PROMPT\"|-] [--json] [--allow=bash,edit]" };
+            if (rest.len == 0 or std.mem.eql(u8, rest[0], "-")) return .{ .cmd = .{ .exec = null } };
+            return .{ .cmd = .{ .exec = rest[0] } };
+        }
+
         if (std.mem.eql(u8, head, "help")) return .{ .cmd = .help };
+        if (std.mem.eql(u8, head, "import")) return .{ .cmd = .import };
+
+        if (std.mem.eql(u8, head, "pack")) {
+            if (rest.len != 2 or !std.mem.eql(u8, rest[0], "install")) return .{ .err = "usage: pack install <path|url>" };
+            return .{ .cmd = .{ .pack_install = rest[1] } };
+        }
+
+        if (std.mem.eql(u8, head, "config")) {
+            const usage = "usage: config export [file] | config import <file> | config doctor";
+            if (rest.len == 0) return .{ .err = usage };
+            if (std.mem.eql(u8, rest[0], "export") and rest.len <= 2) {
+                return .{ .cmd = .{ .config_export = if (rest.len == 2) rest[1] else r.profile.DEFAULT_FILE } };
+            }
+            if (std.mem.eql(u8, rest[0], "import") and rest.len == 2) return .{ .cmd = .{ .config_import = rest[1] } };
+            if (std.mem.eql(u8, rest[0], "doctor") and rest.len == 1) return .{ .cmd = .config_doctor };
+            return .{ .err = usage };
+        }
+
+        if (std.mem.eql(u8, head, "export")) {
+            const usage = "usage: export [--format] [json|markdown|html] [SESSION]";
+            var opts: r.transcript.Options = .{};
+            for (rest) |arg| {
+                if (r.transcript.Format.parse(arg)) |format| {
+                    opts.format = format;
+                } else if (opts.session.len == 0) {
+                    opts.session = arg;
+                } else return .{ .err = usage };
+            }
+            return .{ .cmd = .{ .export_session = opts } };
+        }
+
+        if (std.mem.eql(u8, head, "batch")) {
+            if (rest.len == 0) return .{ .err = "usage: batch \"PROMPT\" [FILE...] | batch resume ID" };
+            if (std.mem.eql(u8, rest[0], "resume")) {
+                if (rest.len != 2) return .{ .err = "usage: batch resume ID" };
+                return .{ .cmd = .{ .batch = .{ .resume_id = rest[1] } } };
+            }
+            return .{ .cmd = .{ .batch = .{ .prompt = rest[0], .files = rest[1..] } } };
+        }
+
+        if (std.mem.eql(u8, head, "bench")) {
+            if (rest.len > 2) return .{ .err = "usage: bench [suite [task]] [--json]" };
+            return .{ .cmd = .{ .bench = .{
+                .suite = if (rest.len > 0) rest[0] else r.bench.DEFAULT_SUITE,
+                .task = if (rest.len > 1) rest[1] else null,
+            } } };
+        }
+
+        if (std.mem.eql(u8, head, "restore")) {
+            if (rest.len > 2) return .{ .err = "usage: restore [session [file]]" };
+            return .{ .cmd = .{ .restore = .{
+                .session = if (rest.len > 0) rest[0] else null,
+                .file = if (rest.len > 1) rest[1] else null,
+            } } };
+        }
 
         if (std.mem.eql(u8, head, "debug")) {
             if (rest.len == 0) return .{ .err = "missing debug command" };
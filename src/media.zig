@@ -0,0 +1,171 @@
+//! Images a model generates, e.g. through OpenRouter's image models. Each
+//! one is saved to `.blitz/media/<session>/image-<n>.<ext>` and its path
+//! goes into the chat. Terminals speaking the kitty graphics protocol
+//! (kitty, Ghostty) or iTerm2's inline images (iTerm2, WezTerm) also show
+//! it in a preview popup, `:image` opens it again. Sixel terminals get the
+//! path only, sixel wants the image decoded to pixels first. Turn the popup
+//! off with `blitz.set_flag("image_preview", false)`.
+const std = @import("std");
+const prv = @import("provider");
+const util = @import("util.zig");
+
+pub const MEDIA_DIR = util.BLITZ_DIR ++ "/media";
+/// kitty takes the payload in chunks of at most this many base64 bytes
+const KITTY_CHUNK = 4096;
+
+pub const Protocol = enum {
+    none,
+    kitty,
+    iterm2,
+
+    /// Whether images of `media_type` can be drawn, kitty only takes PNG
+    /// without decoding on our side.
+    pub fn shows(self: Protocol, media_type: []const u8) bool {
+        return switch (self) {
+            .none => false,
+            .kitty => std.mem.eql(u8, media_type, "image/png"),
+            .iterm2 => true,
+        };
+    }
+};
+
+/// Graphics protocol of the terminal, from the env vars it sets. Inside
+/// tmux the escapes would need passthrough, that counts as none.
+pub fn detect(env: *const std.process.Environ.Map) Protocol {
+    if (env.get("TMUX") != null) return .none;
+    const term = env.get("TERM") orelse "";
+    const program = env.get("TERM_PROGRAM") orelse "";
+    if (env.get("KITTY_WINDOW_ID") != null or std.mem.eql(u8, term, "xterm-kitty")) return .kitty;
+    if (std.mem.eql(u8, term, "xterm-ghostty") or std.mem.eql(u8, program, "ghostty")) return .kitty;
+    if (std.mem.eql(u8, program, "iTerm.app") or std.mem.eql(u8, program, "WezTerm")) return .iterm2;
+    if (std.mem.eql(u8, env.get("LC_TERMINAL") orelse "", "iTerm2")) return .iterm2;
+    return .none;
+}
+
+/// Decode `image` into the session's media folder as its `n`th image.
+/// Returns the path relative to the cwd. The media folder is listed in
+/// `.blitz/.gitignore`.
+pub fn save(io: std.Io, alloc: std.mem.Allocator, session: []const u8, n: usize, image: prv.adapter.ImageContent) ![]const u8 {
+    const decoder = std.base64.standard.Decoder;
+    const raw = try alloc.alloc(u8, decoder.calcSizeForSlice(image.data) catch return error.InvalidImageData);
+    defer alloc.free(raw);
+    decoder.decode(raw, image.data) catch return error.InvalidImageData;
+
+    const dir = try std.fs.path.join(alloc, &.{ MEDIA_DIR, session });
+    defer alloc.free(dir);
+    try std.Io.Dir.cwd().createDirPath(io, dir);
+    try util.ignoreInBlitzDir(std.Io.Dir.cwd(), io, "media/");
+    const path = try std.fmt.allocPrint(alloc, "{s}/image-{d}.{s}", .{ dir, n, extension(image.media_type) });
+    errdefer alloc.free(path);
+
+    const file = try std.Io.Dir.cwd().createFile(io, path, .{});
+    defer file.close(io);
+    var write_buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &write_buf);
+    try writer.interface.writeAll(raw);
+    try writer.interface.flush();
+    return path;
+}
+
+/// File extension for `media_type`, the subtype when it is a plain word.
+fn extension(media_type: []const u8) []const u8 {
+    if (std.mem.eql(u8, media_type, "image/jpeg")) return "jpg";
+    const slash = std.mem.indexOfScalar(u8, media_type, '/') orelse return "img";
+    const sub = media_type[slash + 1 ..];
+    if (sub.len == 0 or sub.len > 8) return "img";
+    for (sub) |ch| if (!std.ascii.isAlphanumeric(ch)) return "img";
+    return sub;
+}
+
+pub const Size = struct { width: u32, height: u32 };
+
+/// Pixel size from the IHDR chunk of a base64 PNG, null for anything else.
+pub fn pngSize(data: []const u8) ?Size {
+    // signature (8), chunk length (4), "IHDR" (4), width (4), height (4)
+    if (data.len < 32) return null;
+    var head: [24]u8 = undefined;
+    std.base64.standard.Decoder.decode(&head, data[0..32]) catch return null;
+    if (!std.mem.eql(u8, head[0..8], "\x89PNG\r\n\x1a\n") or !std.mem.eql(u8, head[12..16], "IHDR")) return null;
+    return .{
+        .width = std.mem.readInt(u32, head[16..20], .big),
+        .height = std.mem.readInt(u32, head[20..24], .big),
+    };
+}
+
+pub const Cells = struct { cols: u16, rows: u16 };
+
+/// The largest `cols`x`rows` box inside `max` keeping the image's aspect
+/// ratio. Cells are taken as twice as tall as wide.
+pub fn fit(size: ?Size, max: Cells) Cells {
+    const s = size orelse return max;
+    if (s.width == 0 or s.height == 0 or max.cols == 0 or max.rows == 0) return max;
+    const aspect = @as(f64, @floatFromInt(s.width)) / @as(f64, @floatFromInt(s.height)) * 2.0;
+    const by_rows = @as(f64, @floatFromInt(max.rows)) * aspect;
+    if (by_rows <= @as(f64, @floatFromInt(max.cols))) {
+        return .{ .cols = @max(1, @as(u16, @intFromFloat(by_rows))), .rows = max.rows };
+    }
+    const rows = @as(f64, @floatFromInt(max.cols)) / aspect;
+    return .{ .cols = max.cols, .rows = @max(1, @as(u16, @intFromFloat(rows))) };
+}
+
+/// Escapes drawing the base64 `data` into `cells` at the cursor.
+pub fn writeImage(w: *std.Io.Writer, protocol: Protocol, data: []const u8, cells: Cells) !void {
+    switch (protocol) {
+        .none => {},
+        .kitty => {
+            var i: usize = 0;
+            while (true) {
+                const end = @min(i + KITTY_CHUNK, data.len);
+                const more = @intFromBool(end < data.len);
+                if (i == 0) {
+                    // C=1 keeps the cursor put, q=2 silences the replies
+                    try w.print("\x1b_Ga=T,f=100,C=1,q=2,c={d},r={d},m={d};", .{ cells.cols, cells.rows, more });
+                } else {
+                    try w.print("\x1b_Gm={d};", .{more});
+                }
+                try w.writeAll(data[i..end]);
+                try w.writeAll("\x1b\\");
+                if (more == 0) break;
+                i = end;
+            }
+        },
+        .iterm2 => {
+            const size = std.base64.standard.Decoder.calcSizeForSlice(data) catch 0;
+            try w.print("\x1b]1337;File=inline=1;size={d};width={d};height={d};preserveAspectRatio=1:", .{ size, cells.cols, cells.rows });
+            try w.writeAll(data);
+            try w.writeAll("\x07");
+        },
+    }
+}
+
+/// Escapes removing what `writeImage` drew. iTerm2 images are plain cells,
+/// a repaint covers them.
+pub fn clearImages(protocol: Protocol) []const u8 {
+    return switch (protocol) {
+        .kitty => "\x1b_Ga=d,q=2\x1b\\",
+        .none, .iterm2 => "",
+    };
+}
+
+test "media sizes and escapes" {
+    // 1x1 PNG
+    const png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+    try std.testing.expectEqual(Size{ .width = 1, .height = 1 }, pngSize(png).?);
+    try std.testing.expect(pngSize("/9j/4AAQSkZJRgABAQAAAQABAAD/2wBDAAgGBgcGBQgHBwcJCQgKDBQNDAsLDBkSEw8UHRofHh0aHBwgJC4nICIsIxwcKDcpLDAxNDQ0Hyc5PTgyPC4zNDL/") == null);
+
+    try std.testing.expectEqual(Cells{ .cols = 40, .rows = 20 }, fit(.{ .width = 512, .height = 512 }, .{ .cols = 90, .rows = 20 }));
+    try std.testing.expectEqual(Cells{ .cols = 90, .rows = 11 }, fit(.{ .width = 2048, .height = 512 }, .{ .cols = 90, .rows = 20 }));
+    try std.testing.expectEqual(Cells{ .cols = 90, .rows = 20 }, fit(null, .{ .cols = 90, .rows = 20 }));
+
+    try std.testing.expectEqualStrings("png", extension("image/png"));
+    try std.testing.expectEqualStrings("jpg", extension("image/jpeg"));
+    try std.testing.expectEqualStrings("img", extension("image/svg+xml"));
+
+    var buf: [KITTY_CHUNK * 3]u8 = undefined;
+    var w = std.Io.Writer.fixed(&buf);
+    const data = [_]u8{'A'} ** (KITTY_CHUNK + 4);
+    try writeImage(&w, .kitty, &data, .{ .cols = 10, .rows = 5 });
+    try std.testing.expect(std.mem.startsWith(u8, w.buffered(), "\x1b_Ga=T,f=100,C=1,q=2,c=10,r=5,m=1;"));
+    try std.testing.expect(std.mem.endsWith(u8, w.buffered(), "\x1b\\\x1b_Gm=0;AAAA\x1b\\"));
+    try std.testing.expect(Protocol.kitty.shows("image/png") and !Protocol.kitty.shows("image/jpeg"));
+}
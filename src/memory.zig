@@ -0,0 +1,221 @@
+//! Project memory: notes the agent keeps across sessions in
+//! `.blitz/memory.md`. The `memory` tool adds, replaces and removes notes,
+//! `:memory` lists them for review. The file goes into the system prompt
+//! next to AGENTS.md, so it stays small: a note is a single line and the
+//! whole file is capped at `MAX_BYTES`.
+//!
+//!     ## Build
+//!     - tests need `zig build test -Dllvm`
+//!
+//! Notes sit under `## <section>` headings, bullets or plain lines. Hand
+//! edits in that shape survive the next save.
+const std = @import("std");
+const util = @import("util.zig");
+
+pub const FILENAME = util.BLITZ_DIR ++ "/memory.md";
+pub const MAX_BYTES = 8 * 1024;
+pub const MAX_NOTE_LEN = 400;
+pub const DEFAULT_SECTION = "Notes";
+
+pub const Note = struct {
+    section: []const u8,
+    text: []const u8,
+};
+
+pub const Memory = struct {
+    /// notes of one section stay next to each other, in file order
+    notes: std.ArrayList(Note) = .empty,
+
+    pub fn deinit(self: *Memory, alloc: std.mem.Allocator) void {
+        for (self.notes.items) |n| freeNote(alloc, n);
+        self.notes.deinit(alloc);
+    }
+
+    /// Bytes `render` writes.
+    pub fn size(self: *const Memory) usize {
+        var total: usize = 0;
+        for (self.notes.items, 0..) |n, i| {
+            if (self.startsSection(i)) total += "## \n".len + n.section.len + @intFromBool(i > 0);
+            total += "- \n".len + n.text.len;
+        }
+        return total;
+    }
+
+    pub fn render(self: *const Memory, w: *std.Io.Writer) !void {
+        for (self.notes.items, 0..) |n, i| {
+            if (self.startsSection(i)) try w.print("{s}## {s}\n", .{ if (i > 0) "\n" else "", n.section });
+            try w.print("- {s}\n", .{n.text});
+        }
+    }
+
+    fn startsSection(self: *const Memory, i: usize) bool {
+        return i == 0 or !std.mem.eql(u8, self.notes.items[i - 1].section, self.notes.items[i].section);
+    }
+
+    /// Appends `text` to `section`, a new section goes last. Section names
+    /// match case-insensitively.
+    pub fn add(self: *Memory, alloc: std.mem.Allocator, section: []const u8, text: []const u8) !void {
+        const name = std.mem.trim(u8, section, " \t#");
+        const heading = if (name.len > 0) name else DEFAULT_SECTION;
+        var at: ?usize = null;
+        for (self.notes.items, 0..) |n, i| {
+            if (!std.ascii.eqlIgnoreCase(n.section, heading)) continue;
+            if (std.mem.eql(u8, n.text, std.mem.trim(u8, text, " \t\r\n"))) return error.DuplicateNote;
+            at = i + 1;
+        }
+
+        const note_text = try normalize(alloc, text);
+        errdefer alloc.free(note_text);
+        const owned_section = try alloc.dupe(u8, if (at) |i| self.notes.items[i - 1].section else heading);
+        errdefer alloc.free(owned_section);
+        const grows = "- \n".len + note_text.len + (if (at == null) "\n## \n".len + owned_section.len else 0);
+        if (self.size() + grows > MAX_BYTES) return error.MemoryFull;
+
+        try self.notes.insert(alloc, at orelse self.notes.items.len, .{ .section = owned_section, .text = note_text });
+    }
+
+    pub fn replace(self: *Memory, alloc: std.mem.Allocator, idx: usize, text: []const u8) !void {
+        const note = &self.notes.items[idx];
+        const note_text = try normalize(alloc, text);
+        if (self.size() - note.text.len + note_text.len > MAX_BYTES) {
+            alloc.free(note_text);
+            return error.MemoryFull;
+        }
+        alloc.free(note.text);
+        note.text = note_text;
+    }
+
+    pub fn remove(self: *Memory, alloc: std.mem.Allocator, idx: usize) void {
+        if (idx >= self.notes.items.len) return;
+        freeNote(alloc, self.notes.orderedRemove(idx));
+    }
+
+    /// The note equal to `needle`, or else the only one containing it.
+    pub fn find(self: *const Memory, needle: []const u8) !usize {
+        const trimmed = std.mem.trim(u8, needle, " \t\r\n-");
+        if (trimmed.len == 0) return error.NoteNotFound;
+        var found: ?usize = null;
+        for (self.notes.items, 0..) |n, i| {
+            if (std.mem.eql(u8, n.text, trimmed)) return i;
+            if (std.mem.indexOf(u8, n.text, trimmed) == null) continue;
+            if (found != null) return error.AmbiguousNote;
+            found = i;
+        }
+        return found orelse error.NoteNotFound;
+    }
+
+    pub fn parse(self: *Memory, alloc: std.mem.Allocator, content: []const u8) !void {
+        var section: []const u8 = DEFAULT_SECTION;
+        var it = std.mem.splitScalar(u8, content, '\n');
+        while (it.next()) |line| {
+            const trimmed = std.mem.trim(u8, line, " \t\r");
+            if (trimmed.len == 0) continue;
+            if (trimmed[0] == '#') {
+                const name = std.mem.trim(u8, trimmed, " \t#");
+                if (name.len > 0) section = name;
+                continue;
+            }
+            const text = if (std.mem.startsWith(u8, trimmed, "- ") or std.mem.startsWith(u8, trimmed, "* ")) trimmed[2..] else trimmed;
+            self.add(alloc, section, text) catch |err| switch (err) {
+                error.DuplicateNote, error.EmptyNote, error.NoteTooLong => continue,
+                else => return err,
+            };
+        }
+    }
+
+    /// Load `<cwd>/.blitz/memory.md`, a missing file is an empty memory.
+    pub fn load(self: *Memory, io: std.Io, alloc: std.mem.Allocator) void {
+        const file = std.Io.Dir.cwd().openFile(io, FILENAME, .{}) catch return;
+        defer file.close(io);
+
+        var read_buf: [1024]u8 = undefined;
+        var file_reader = file.reader(io, &read_buf);
+        const content = file_reader.interface.allocRemaining(alloc, .limited(4 * MAX_BYTES)) catch return;
+        defer alloc.free(content);
+        self.parse(alloc, content) catch |err| {
+            std.log.warn("failed to parse {s}: {s}", .{ FILENAME, @errorName(err) });
+        };
+    }
+
+    /// Writes `<root>/.blitz/memory.md`, the process cwd for an empty root.
+    pub fn save(self: *const Memory, io: std.Io, root: []const u8) !void {
+        var dir = if (root.len > 0) try std.Io.Dir.openDirAbsolute(io, root, .{}) else std.Io.Dir.cwd();
+        defer if (root.len > 0) dir.close(io);
+        dir.createDirPath(io, util.BLITZ_DIR) catch {};
+        const file = try dir.createFile(io, FILENAME, .{});
+        defer file.close(io);
+
+        var write_buf: [1024]u8 = undefined;
+        var writer = file.writer(io, &write_buf);
+        try self.render(&writer.interface);
+        try writer.interface.flush();
+    }
+};
+
+fn freeNote(alloc: std.mem.Allocator, note: Note) void {
+    alloc.free(note.section);
+    alloc.free(note.text);
+}
+
+/// One trimmed line, line breaks become spaces.
+fn normalize(alloc: std.mem.Allocator, text: []const u8) ![]const u8 {
+    const trimmed = std.mem.trim(u8, text, " \t\r\n");
+    if (trimmed.len == 0) return error.EmptyNote;
+    if (trimmed.len > MAX_NOTE_LEN) return error.NoteTooLong;
+    const out = try alloc.dupe(u8, trimmed);
+    for (out) |*ch| {
+        if (ch.* == '\n' or ch.* == '\r') ch.* = ' ';
+    }
+    return out;
+}
+
+test "memory notes keep their sections" {
+    const alloc = std.testing.allocator;
+    var memory = Memory{};
+    defer memory.deinit(alloc);
+
+    try memory.parse(alloc,
+        \\# Project memory
+        \\## Build
+        \\- tests need `zig build test`
+        \\loose line
+        \\
+        \\## Style
+        \\* no `try` in deinit
+    );
+    try memory.add(alloc, "build", "release builds use -Doptimize=ReleaseSafe");
+    try memory.add(alloc, "", "the TUI redraws only when dirty");
+    try std.testing.expectError(error.DuplicateNote, memory.add(alloc, "Build", "loose line"));
+    try std.testing.expectError(error.EmptyNote, memory.add(alloc, "Build", " \n"));
+
+    try std.testing.expectEqual(@as(usize, 1), try memory.find("loose"));
+    try std.testing.expectError(error.AmbiguousNote, memory.find("build"));
+    try std.testing.expectError(error.NoteNotFound, memory.find("cmake"));
+    try memory.replace(alloc, try memory.find("deinit"), "no `try`\nin deinit");
+    memory.remove(alloc, try memory.find("loose line"));
+
+    var out = std.Io.Writer.Allocating.init(alloc);
+    defer out.deinit();
+    try memory.render(&out.writer);
+    try std.testing.expectEqualStrings(
+        \\## Build
+        \\- tests need `zig build test`
+        \\- release builds use -Doptimize=ReleaseSafe
+        \\
+        \\## Style
+        \\- no `try` in deinit
+        \\
+        \\## Notes
+        \\- the TUI redraws only when dirty
+        \\
+    , out.written());
+    try std.testing.expectEqual(out.written().len, memory.size());
+
+    var buf: [MAX_NOTE_LEN]u8 = undefined;
+    const full = for (0..64) |n| {
+        const note = try std.fmt.bufPrint(&buf, "{d:0>300}", .{n});
+        memory.add(alloc, "filler", note) catch |err| break err == error.MemoryFull;
+    } else false;
+    try std.testing.expect(full);
+    try std.testing.expect(memory.size() <= MAX_BYTES);
+}
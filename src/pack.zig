@@ -0,0 +1,259 @@
+//! `blitz pack install <path|url>`, shareable bundles of prompts, commands,
+//! modes and tools. A pack is a directory, a zip of one or a zip URL:
+//!
+//!   pack.json    `{"name": "acme", "description": "..."}`, name defaults
+//!                to the directory name
+//!   init.lua     commands, modes and lua tools, loaded after blitz.lua
+//!   skills/*.md  prompts and workflows, copied into the skill directory
+//!   tools/*      drop-in tool executables, see plugins.zig
+//!
+//! Installed to `~/.config/blitzdenk/packs/<name>/`. Everything a pack adds
+//! carries its name: command `:acme.review`, mode `acme.review`, skill
+//! `acme.review`, tool `acme_lint` (tool names only allow `_`), so packs
+//! collide neither with each other nor with the user's own config.
+const std = @import("std");
+const CONFIG_DIR = @import("main.zig").DEFAULT_CONFIG_PATH;
+
+pub const PACKS_DIR = CONFIG_DIR ++ "packs";
+pub const SKILLS_DIR = CONFIG_DIR ++ "skills";
+pub const MAX_NAME_LEN = 32;
+const MAX_FILE_BYTES = 256 * 1024;
+
+pub const Manifest = struct {
+    name: []const u8 = "",
+    description: []const u8 = "",
+};
+
+/// Lowercase letters, digits, `-` and `_`, the name ends up in paths, tool
+/// names and commands.
+pub fn validName(name: []const u8) bool {
+    if (name.len == 0 or name.len > MAX_NAME_LEN) return false;
+    for (name) |c| switch (c) {
+        'a'...'z', '0'...'9', '-', '_' => {},
+        else => return false,
+    };
+    return true;
+}
+
+pub fn parseManifest(alloc: std.mem.Allocator, content: []const u8) !Manifest {
+    return std.json.parseFromSliceLeaky(Manifest, alloc, content, .{ .ignore_unknown_fields = true });
+}
+
+/// `name` of a pack member as the rest of the app sees it, `acme.review`.
+pub fn qualified(alloc: std.mem.Allocator, pack: []const u8, name: []const u8) ![]const u8 {
+    return std.fmt.allocPrint(alloc, "{s}.{s}", .{ pack, name });
+}
+
+/// Skill markdown with its `name` replaced by the qualified one. Files
+/// without front matter get one, described by their first line.
+pub fn namespacedSkill(alloc: std.mem.Allocator, pack: []const u8, stem: []const u8, body: []const u8) ![]const u8 {
+    const name = try qualified(alloc, pack, stem);
+    defer alloc.free(name);
+
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    errdefer out.deinit();
+    const w = &out.writer;
+
+    if (std.mem.startsWith(u8, body, "---\n")) {
+        if (std.mem.indexOf(u8, body[4..], "\n---")) |end| {
+            try w.print("---\nname: {s}\n", .{name});
+            var lines = std.mem.splitScalar(u8, body[4..][0..end], '\n');
+            while (lines.next()) |line| {
+                if (std.mem.startsWith(u8, line, "name:")) continue;
+                try w.print("{s}\n", .{line});
+            }
+            try w.writeAll(body[4 + end + 1 ..]);
+            return out.toOwnedSlice();
+        }
+    }
+    const first = std.mem.sliceTo(std.mem.trimLeft(u8, body, "# \n"), '\n');
+    try w.print("---\nname: {s}\ndescription: {s}\n---\n\n{s}", .{ name, first[0..@min(first.len, 120)], body });
+    return out.toOwnedSlice();
+}
+
+/// Entry point for `blitz pack install`.
+pub fn install(io: std.Io, alloc: std.mem.Allocator, home_path: []const u8, source: []const u8) !void {
+    var home = try std.Io.Dir.openDirAbsolute(io, home_path, .{});
+    defer home.close(io);
+    try home.createDirPath(io, PACKS_DIR);
+
+    const packs_abs = try std.fs.path.join(alloc, &.{ home_path, PACKS_DIR });
+    const staging = try std.fs.path.join(alloc, &.{ packs_abs, ".incoming" });
+    removeTree(io, alloc, staging);
+    defer removeTree(io, alloc, staging);
+
+    var src_path = source;
+    if (std.mem.startsWith(u8, source, "http://") or std.mem.startsWith(u8, source, "https://")) {
+        try home.createDirPath(io, PACKS_DIR ++ "/.incoming");
+        const zip = try std.fs.path.join(alloc, &.{ staging, "pack.zip" });
+        if (!try runOk(io, alloc, &.{ "curl", "-fsSL", "-o", zip, source })) return;
+        src_path = try unpack(io, alloc, zip, staging) orelse return;
+    } else if (std.mem.endsWith(u8, source, ".zip")) {
+        try home.createDirPath(io, PACKS_DIR ++ "/.incoming");
+        src_path = try unpack(io, alloc, source, staging) orelse return;
+    }
+
+    var src = std.Io.Dir.cwd().openDir(io, src_path, .{ .iterate = true }) catch |err| {
+        std.debug.print("cannot open pack {s}: {s}\n", .{ src_path, @errorName(err) });
+        return;
+    };
+    defer src.close(io);
+
+    var manifest: Manifest = .{};
+    if (readFile(io, alloc, src, "pack.json")) |content| {
+        manifest = parseManifest(alloc, content) catch |err| {
+            std.debug.print("invalid pack.json: {s}\n", .{@errorName(err)});
+            return;
+        };
+    }
+    if (manifest.name.len == 0) manifest.name = std.fs.path.basename(src_path);
+    if (!validName(manifest.name)) {
+        std.debug.print("invalid pack name `{s}`, use up to {d} of a-z 0-9 - _\n", .{ manifest.name, MAX_NAME_LEN });
+        return;
+    }
+
+    // reinstalling replaces the pack and its skills
+    const dest = try std.fs.path.join(alloc, &.{ packs_abs, manifest.name });
+    removeTree(io, alloc, dest);
+    const src_contents = try std.fs.path.join(alloc, &.{ src_path, "." });
+    if (!try runOk(io, alloc, &.{ "cp", "-R", src_contents, dest })) return;
+
+    const skills = try installSkills(io, alloc, home, src, manifest.name);
+    const tools = countEntries(io, src, "tools");
+    const has_lua = if (src.statFile(io, "init.lua", .{})) |_| true else |_| false;
+
+    std.debug.print("installed pack {s} -> ~/{s}/{s}\n", .{ manifest.name, PACKS_DIR, manifest.name });
+    if (manifest.description.len > 0) std.debug.print("  {s}\n", .{manifest.description});
+    std.debug.print("  {d} skill(s) as {s}.<name>\n", .{ skills, manifest.name });
+    std.debug.print("  {d} tool(s) as {s}_<name>\n", .{ tools, manifest.name });
+    if (has_lua) std.debug.print("  init.lua, commands and modes as :{s}.<name>\n", .{manifest.name});
+}
+
+/// Extract `zip` into `staging`. A zip holding a single directory is that
+/// directory's pack.
+fn unpack(io: std.Io, alloc: std.mem.Allocator, zip: []const u8, staging: []const u8) !?[]const u8 {
+    const out = try std.fs.path.join(alloc, &.{ staging, "pack" });
+    if (!try runOk(io, alloc, &.{ "unzip", "-q", "-o", zip, "-d", out })) return null;
+
+    var dir = try std.Io.Dir.openDirAbsolute(io, out, .{ .iterate = true });
+    defer dir.close(io);
+    var single: ?[]const u8 = null;
+    var it = dir.iterate();
+    while (try it.next(io)) |entry| {
+        if (entry.kind != .directory or single != null) return out;
+        single = try alloc.dupe(u8, entry.name);
+    }
+    const name = single orelse return out;
+    return try std.fs.path.join(alloc, &.{ out, name });
+}
+
+/// Old skills of the same pack go first, so removed skills do not linger.
+fn installSkills(io: std.Io, alloc: std.mem.Allocator, home: std.Io.Dir, src: std.Io.Dir, pack: []const u8) !usize {
+    try home.createDirPath(io, SKILLS_DIR);
+    var skills_dir = try home.openDir(io, SKILLS_DIR, .{ .iterate = true });
+    defer skills_dir.close(io);
+
+    const prefix = try qualified(alloc, pack, "");
+    var stale = std.ArrayList([]const u8).empty;
+    var it = skills_dir.iterate();
+    while (try it.next(io)) |entry| {
+        if (entry.kind == .file and std.mem.startsWith(u8, entry.name, prefix)) try stale.append(alloc, try alloc.dupe(u8, entry.name));
+    }
+    for (stale.items) |name| skills_dir.deleteFile(io, name) catch {};
+
+    var dir = src.openDir(io, "skills", .{ .iterate = true }) catch return 0;
+    defer dir.close(io);
+    var added: usize = 0;
+    var src_it = dir.iterate();
+    while (try src_it.next(io)) |entry| {
+        if (entry.kind != .file or !std.mem.endsWith(u8, entry.name, ".md")) continue;
+        const body = readFile(io, alloc, dir, entry.name) orelse continue;
+        const stem = entry.name[0 .. entry.name.len - 3];
+        const skill = try namespacedSkill(alloc, pack, stem, body);
+        const file_name = try std.fmt.allocPrint(alloc, "{s}{s}", .{ prefix, entry.name });
+
+        const file = try skills_dir.createFile(io, file_name, .{});
+        defer file.close(io);
+        var write_buf: [1024]u8 = undefined;
+        var writer = file.writer(io, &write_buf);
+        try writer.interface.writeAll(skill);
+        try writer.interface.flush();
+        added += 1;
+    }
+    return added;
+}
+
+/// Every `<packs>/<name>/init.lua`, for the lua loader.
+pub fn initScripts(io: std.Io, alloc: std.mem.Allocator, config_dir: []const u8) []const Script {
+    var scripts = std.ArrayList(Script).empty;
+    const packs_path = std.fs.path.join(alloc, &.{ config_dir, "packs" }) catch return &.{};
+    var dir = std.Io.Dir.openDirAbsolute(io, packs_path, .{ .iterate = true }) catch return &.{};
+    defer dir.close(io);
+    var it = dir.iterate();
+    while (it.next(io) catch null) |entry| {
+        if (entry.kind != .directory or !validName(entry.name)) continue;
+        const path = std.fs.path.join(alloc, &.{ packs_path, entry.name, "init.lua" }) catch continue;
+        _ = std.Io.Dir.cwd().statFile(io, path, .{}) catch continue;
+        const name = alloc.dupe(u8, entry.name) catch continue;
+        scripts.append(alloc, .{ .pack = name, .path = path }) catch continue;
+    }
+    return scripts.items;
+}
+
+pub const Script = struct { pack: []const u8, path: []const u8 };
+
+fn countEntries(io: std.Io, src: std.Io.Dir, path: []const u8) usize {
+    var dir = src.openDir(io, path, .{ .iterate = true }) catch return 0;
+    defer dir.close(io);
+    var count: usize = 0;
+    var it = dir.iterate();
+    while (it.next(io) catch null) |entry| {
+        if (entry.name.len > 0 and entry.name[0] != '.') count += 1;
+    }
+    return count;
+}
+
+fn removeTree(io: std.Io, alloc: std.mem.Allocator, path: []const u8) void {
+    _ = std.process.run(alloc, io, .{ .argv = &.{ "rm", "-rf", path } }) catch {};
+}
+
+fn runOk(io: std.Io, alloc: std.mem.Allocator, argv: []const []const u8) !bool {
+    const res = try std.process.run(alloc, io, .{ .argv = argv });
+    switch (res.term) {
+        .exited => |code| if (code == 0) return true,
+        else => {},
+    }
+    std.debug.print("{s} failed: {s}\n", .{ argv[0], res.stderr });
+    return false;
+}
+
+fn readFile(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, path: []const u8) ?[]const u8 {
+    const file = dir.openFile(io, path, .{}) catch return null;
+    defer file.close(io);
+    var read_buf: [1024]u8 = undefined;
+    var file_reader = file.reader(io, &read_buf);
+    return file_reader.interface.allocRemaining(alloc, .limited(MAX_FILE_BYTES)) catch null;
+}
+
+test "pack names and skills" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    try std.testing.expect(validName("acme-tools_2"));
+    try std.testing.expect(!validName("Acme"));
+    try std.testing.expect(!validName("../x"));
+    try std.testing.expect(!validName(""));
+
+    const manifest = try parseManifest(alloc, "{\"name\": \"acme\", \"description\": \"team flows\", \"version\": 2}");
+    try std.testing.expectEqualStrings("acme", manifest.name);
+
+    try std.testing.expectEqualStrings(
+        "---\nname: acme.review\ndescription: Review the diff\n---\nLook at it",
+        try namespacedSkill(alloc, "acme", "review", "---\nname: review\ndescription: Review the diff\n---\nLook at it"),
+    );
+    try std.testing.expectEqualStrings(
+        "---\nname: acme.deploy\ndescription: Deploy steps\n---\n\n# Deploy steps\nrun it",
+        try namespacedSkill(alloc, "acme", "deploy", "# Deploy steps\nrun it"),
+    );
+}
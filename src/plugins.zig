@@ -0,0 +1,382 @@
+//! Drop-in tools: every executable in `.blitz/tools/` of the project or in
+//! `~/.config/blitzdenk/tools/` is asked for its schema with `--schema` and
+//! registered as a regular agent tool. Calls pass the arguments as JSON on
+//! stdin and hand stdout back to the agent. A project tool wins over a
+//! global one of the same name.
+//!
+//! Schema format printed by the executable:
+//! `{"name": "...", "description": "...", "parameters": { json schema }}`
+//!
+//! A cloned repository brings its own `.blitz/tools/` and anything may drop
+//! a file into `~/.config/blitzdenk/tools/`, so neither is run until the
+//! user trusted it: the TUI lists new ones once, enter records their path
+//! and sha256 in `~/.cache/blitzdenk/trusted_tools` and loads them. A
+//! changed file is new again. Pack tools come in through `:pack install`.
+const std = @import("std");
+const prv = @import("provider");
+const r = @import("root.zig");
+
+const log = std.log.scoped(.plugins);
+
+pub const TOOLS_DIR = r.util.BLITZ_DIR ++ "/tools";
+pub const SCHEMA_TIMEOUT_MS = 2000;
+pub const CALL_TIMEOUT_MS = 60_000;
+pub const MAX_OUTPUT = 256 * 1024;
+
+/// `<sha256> <path>` per line, under $HOME
+pub const TRUST_FILE = ".cache/blitzdenk/trusted_tools";
+const HASH_HEX_LEN = std.crypto.hash.sha2.Sha256.digest_length * 2;
+
+const ToolBinding = struct {
+    name: []const u8,
+    path: []const u8,
+};
+
+/// An executable waiting for the trust prompt, never run so far.
+pub const Untrusted = struct {
+    path: []const u8,
+    hash: [HASH_HEX_LEN]u8,
+};
+
+pub const Manager = struct {
+    alloc: std.mem.Allocator = undefined,
+    io: std.Io = undefined,
+    bindings: std.ArrayList(ToolBinding) = .empty,
+    tools: std.ArrayList(r.mcp.RegisteredTool) = .empty,
+    untrusted: std.ArrayList(Untrusted) = .empty,
+    /// content of TRUST_FILE as of the last load
+    trusted: []const u8 = "",
+    home: ?[]const u8 = null,
+
+    pub fn init(alloc: std.mem.Allocator, io: std.Io) Manager {
+        return .{ .alloc = alloc, .io = io };
+    }
+
+    pub fn deinit(self: *Manager) void {
+        self.clear();
+        if (active_manager == self) active_manager = null;
+        self.bindings.deinit(self.alloc);
+        self.tools.deinit(self.alloc);
+        self.untrusted.deinit(self.alloc);
+    }
+
+    pub fn clear(self: *Manager) void {
+        for (self.tools.items) |tool| {
+            self.alloc.free(tool.tool.def.description);
+            self.alloc.free(tool.tool.def.parameters_schema);
+        }
+        for (self.bindings.items) |binding| {
+            self.alloc.free(binding.name);
+            self.alloc.free(binding.path);
+        }
+        for (self.untrusted.items) |tool| self.alloc.free(tool.path);
+        self.bindings.clearRetainingCapacity();
+        self.tools.clearRetainingCapacity();
+        self.untrusted.clearRetainingCapacity();
+        self.alloc.free(self.trusted);
+        self.trusted = "";
+    }
+
+    pub fn registeredTools(self: *Manager) []const r.mcp.RegisteredTool {
+        return self.tools.items;
+    }
+
+    /// Scan `<cwd>/.blitz/tools`, `<config>/tools` and `<config>/packs/*/tools`
+    /// and register every executable that answers `--schema`. Pack tools are
+    /// named `<pack>_<tool>`. Broken plugins are logged and skipped, project
+    /// and config tools not trusted yet end up in `untrusted` without being run.
+    pub fn load(self: *Manager, exec: *prv.exec.CmdPool, cwd: []const u8, config_dir: ?[]const u8, home: ?[]const u8) void {
+        self.clear();
+        active_manager = self;
+        self.home = home;
+        self.trusted = self.readTrusted() catch "";
+
+        const dir_path = std.fs.path.join(self.alloc, &.{ cwd, TOOLS_DIR }) catch return;
+        defer self.alloc.free(dir_path);
+        self.loadDir(exec, dir_path, null, .ask);
+
+        const config = config_dir orelse return;
+        const global_path = std.fs.path.join(self.alloc, &.{ config, "tools" }) catch return;
+        defer self.alloc.free(global_path);
+        self.loadDir(exec, global_path, null, .ask);
+
+        const packs_path = std.fs.path.join(self.alloc, &.{ config, "packs" }) catch return;
+        defer self.alloc.free(packs_path);
+        var packs = std.Io.Dir.openDirAbsolute(self.io, packs_path, .{ .iterate = true }) catch return;
+        defer packs.close(self.io);
+        var it = packs.iterate();
+        while (it.next(self.io) catch null) |entry| {
+            if (entry.kind != .directory or !r.pack.validName(entry.name)) continue;
+            const tools_path = std.fs.path.join(self.alloc, &.{ packs_path, entry.name, "tools" }) catch continue;
+            defer self.alloc.free(tools_path);
+            self.loadDir(exec, tools_path, entry.name, .run);
+        }
+    }
+
+    /// `.ask` keeps executables out of the trust file in `untrusted`.
+    const Trust = enum { run, ask };
+
+    fn loadDir(self: *Manager, exec: *prv.exec.CmdPool, dir_path: []const u8, pack: ?[]const u8, trust: Trust) void {
+        var dir = std.Io.Dir.openDirAbsolute(self.io, dir_path, .{ .iterate = true }) catch return;
+        defer dir.close(self.io);
+
+        var it = dir.iterate();
+        while (it.next(self.io) catch null) |entry| {
+            if (entry.kind != .file and entry.kind != .sym_link) continue;
+            if (entry.name.len == 0 or entry.name[0] == '.') continue;
+
+            const path = std.fs.path.join(self.alloc, &.{ dir_path, entry.name }) catch continue;
+            if (trust == .ask) {
+                const hash = fileHash(self.io, path) catch |err| {
+                    log.warn("failed to hash plugin '{s}': {s}", .{ path, @errorName(err) });
+                    self.alloc.free(path);
+                    continue;
+                };
+                if (!isTrusted(self.trusted, path, &hash)) {
+                    self.untrusted.append(self.alloc, .{ .path = path, .hash = hash }) catch self.alloc.free(path);
+                    continue;
+                }
+            }
+            self.addPlugin(exec, path, pack) catch |err| {
+                log.warn("failed to load plugin '{s}': {s}", .{ path, @errorName(err) });
+                self.alloc.free(path);
+            };
+        }
+    }
+
+    fn addPlugin(self: *Manager, exec: *prv.exec.CmdPool, path: []const u8, pack: ?[]const u8) !void {
+        const result = try exec.runAndWaitTimeout(.{
+            .argv = &.{ path, "--schema" },
+            .force_local = true,
+        }, SCHEMA_TIMEOUT_MS);
+        defer exec.alloc.free(result.stdout);
+        defer exec.alloc.free(result.stderr);
+        if (result.ty != .success) return error.SchemaCommandFailed;
+
+        // `name`, `description` and `parameters` move into the binding and
+        // the tool, freed by `clear` once both appends went through
+        const schema = try parseSchema(self.alloc, result.stdout);
+        defer self.alloc.free(schema.name);
+        defer self.alloc.free(schema.description);
+        errdefer self.alloc.free(schema.parameters);
+
+        const name = if (pack) |p|
+            try std.fmt.allocPrint(self.alloc, "{s}_{s}", .{ p, schema.name })
+        else
+            try self.alloc.dupe(u8, schema.name);
+        errdefer self.alloc.free(name);
+        if (self.findBinding(name) != null) return error.DuplicateToolName;
+
+        const description = try std.fmt.allocPrint(self.alloc, "[plugin] {s}", .{schema.description});
+        errdefer self.alloc.free(description);
+
+        try self.bindings.ensureUnusedCapacity(self.alloc, 1);
+        try self.tools.ensureUnusedCapacity(self.alloc, 1);
+        self.bindings.appendAssumeCapacity(.{ .name = name, .path = path });
+        self.tools.appendAssumeCapacity(.{
+            .tool = .{
+                .def = .{
+                    .name = name,
+                    .description = description,
+                    .parameters_schema = schema.parameters,
+                },
+                .func = &toolTrampoline,
+            },
+            .flags = .{ .allowed_agents = .initFull(), .add_to_agents = true },
+        });
+    }
+
+    /// Records every tool of `untrusted` in the trust file, the next load
+    /// runs them.
+    pub fn trustUntrusted(self: *Manager) !void {
+        if (self.untrusted.items.len == 0) return;
+        const home = self.home orelse return error.NoHomeFound;
+        var content: std.Io.Writer.Allocating = .init(self.alloc);
+        defer content.deinit();
+        try content.writer.writeAll(self.trusted);
+        for (self.untrusted.items) |tool| try content.writer.print("{s} {s}\n", .{ tool.hash, tool.path });
+
+        var home_dir = try std.Io.Dir.openDirAbsolute(self.io, home, .{});
+        defer home_dir.close(self.io);
+        try home_dir.createDirPath(self.io, std.fs.path.dirname(TRUST_FILE).?);
+        const file = try home_dir.createFile(self.io, TRUST_FILE, .{ .truncate = true });
+        defer file.close(self.io);
+        var buf: [1024]u8 = undefined;
+        var writer = file.writer(self.io, &buf);
+        try writer.interface.writeAll(content.written());
+        try writer.interface.flush();
+    }
+
+    fn readTrusted(self: *Manager) ![]const u8 {
+        const home = self.home orelse return "";
+        var home_dir = try std.Io.Dir.openDirAbsolute(self.io, home, .{});
+        defer home_dir.close(self.io);
+        const file = try home_dir.openFile(self.io, TRUST_FILE, .{ .mode = .read_only });
+        defer file.close(self.io);
+        var buf: [1024]u8 = undefined;
+        var reader = file.reader(self.io, &buf);
+        return reader.interface.allocRemaining(self.alloc, .limited(1024 * 1024));
+    }
+
+    fn findBinding(self: *Manager, name: []const u8) ?ToolBinding {
+        for (self.bindings.items) |binding| {
+            if (std.mem.eql(u8, binding.name, name)) return binding;
+        }
+        return null;
+    }
+};
+
+var active_manager: ?*Manager = null;
+
+/// Hex sha256 of the executable at `path`.
+fn fileHash(io: std.Io, path: []const u8) ![HASH_HEX_LEN]u8 {
+    const file = try std.Io.Dir.openFileAbsolute(io, path, .{ .mode = .read_only });
+    defer file.close(io);
+    var buf: [1024]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    var hasher = std.crypto.hash.sha2.Sha256.init(.{});
+    var chunk: [16 * 1024]u8 = undefined;
+    while (true) {
+        const n = try reader.interface.readSliceShort(&chunk);
+        hasher.update(chunk[0..n]);
+        if (n < chunk.len) break;
+    }
+    return std.fmt.bytesToHex(hasher.finalResult(), .lower);
+}
+
+/// Whether `trusted` has the line `<hash> <path>`.
+pub fn isTrusted(trusted: []const u8, path: []const u8, hash: []const u8) bool {
+    var lines = std.mem.splitScalar(u8, trusted, '\n');
+    while (lines.next()) |line| {
+        const sep = std.mem.indexOfScalar(u8, line, ' ') orelse continue;
+        if (std.mem.eql(u8, line[0..sep], hash) and std.mem.eql(u8, line[sep + 1 ..], path)) return true;
+    }
+    return false;
+}
+
+pub const Schema = struct {
+    name: []const u8,
+    description: []const u8,
+    /// json schema, re-serialized
+    parameters: []const u8,
+};
+
+/// Parse the `--schema` answer. All returned slices are owned by `alloc`.
+pub fn parseSchema(alloc: std.mem.Allocator, raw: []const u8) !Schema {
+    const parsed = try std.json.parseFromSlice(std.json.Value, alloc, raw, .{});
+    defer parsed.deinit();
+
+    const obj = switch (parsed.value) {
+        .object => |o| o,
+        else => return error.InvalidSchema,
+    };
+
+    const name = switch (obj.get("name") orelse return error.InvalidSchema) {
+        .string => |s| s,
+        else => return error.InvalidSchema,
+    };
+    if (name.len == 0) return error.InvalidSchema;
+    for (name) |c| {
+        if (!std.ascii.isAlphanumeric(c) and c != '_' and c != '-') return error.InvalidSchema;
+    }
+
+    const description = switch (obj.get("description") orelse return error.InvalidSchema) {
+        .string => |s| s,
+        else => return error.InvalidSchema,
+    };
+
+    const params = obj.get("parameters") orelse return error.InvalidSchema;
+    if (params != .object) return error.InvalidSchema;
+
+    const name_owned = try alloc.dupe(u8, name);
+    errdefer alloc.free(name_owned);
+    const desc_owned = try alloc.dupe(u8, description);
+    errdefer alloc.free(desc_owned);
+
+    return .{
+        .name = name_owned,
+        .description = desc_owned,
+        .parameters = try std.json.Stringify.valueAlloc(alloc, params, .{}),
+    };
+}
+
+fn toolTrampoline(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const manager = active_manager orelse return r.tools.errResult(call, "plugin manager not initialized");
+    const binding = manager.findBinding(call.name) orelse return r.tools.errResult(call, "plugin tool binding not found");
+
+    // plugins are arbitrary executables, gate them like risky bash commands
+    const decision = ctx.requestPerm(call.id, .always_check, .{ .call = .{
+        .tool_name = call.name,
+        .tool_arguments = call.arguments,
+    } });
+    switch (decision) {
+        .approved => {},
+        .denied => return r.tools.errResult(call, "User declined plugin call"),
+        .message => |txt| {
+            const wrapped = std.fmt.allocPrint(
+                ctx.alloc,
+                "User declined plugin call and left feedback: {s}",
+                .{txt},
+            ) catch txt;
+            return r.tools.errResult(call, wrapped);
+        },
+        else => return r.tools.errResult(call, "permission unresolved"),
+    }
+
+    if (ctx.isCanceled()) return r.tools.errResult(call, "canceled");
+    r.tools.setToolStatusPrint(ctx, call, "plugin {s}", .{std.fs.path.basename(binding.path)});
+
+    const res = ctx.swarm.exec.runAndWaitTimeout(.{
+        .argv = &.{binding.path},
+        .stdin_data = call.arguments,
+        // plugins live next to the local checkout, never route them over ssh
+        .force_local = true,
+    }, CALL_TIMEOUT_MS) catch |err| {
+        const msg = std.fmt.allocPrint(ctx.alloc, "plugin failed to start: {s}", .{@errorName(err)}) catch "plugin failed to start";
+        return r.tools.errResult(call, msg);
+    };
+    defer ctx.swarm.exec.alloc.free(res.stdout);
+    defer ctx.swarm.exec.alloc.free(res.stderr);
+
+    return switch (res.ty) {
+        // cut and spilled like bash output, binary output comes back as text
+        .success => r.tools.okResult(call, r.tools.spillOutputToOwned(ctx, call, ctx.alloc.dupe(u8, res.stdout) catch "plugin output lost")),
+        .timeout => r.tools.errResult(call, "plugin timed out"),
+        .failed => r.tools.errResult(call, r.tools.ensureValidUtf8(ctx.alloc, std.fmt.allocPrint(
+            ctx.alloc,
+            "plugin exited with an error\n{s}\n{s}",
+            .{ res.stdout[0..@min(res.stdout.len, MAX_OUTPUT)], res.stderr[0..@min(res.stderr.len, 4096)] },
+        ) catch "plugin exited with an error")),
+    };
+}
+
+test "trusted tools are keyed by path and hash" {
+    const hash_a = "a" ** HASH_HEX_LEN;
+    const hash_b = "b" ** HASH_HEX_LEN;
+    const trusted = hash_a ++ " /src/app/.blitz/tools/jira\n" ++ hash_b ++ " /src/app/.blitz/tools/lint\n";
+    try std.testing.expect(isTrusted(trusted, "/src/app/.blitz/tools/jira", hash_a));
+    try std.testing.expect(isTrusted(trusted, "/src/app/.blitz/tools/lint", hash_b));
+    // edited since it was trusted
+    try std.testing.expect(!isTrusted(trusted, "/src/app/.blitz/tools/jira", hash_b));
+    // same file in another checkout
+    try std.testing.expect(!isTrusted(trusted, "/src/fork/.blitz/tools/jira", hash_a));
+    try std.testing.expect(!isTrusted("", "/src/app/.blitz/tools/jira", hash_a));
+}
+
+test "parseSchema" {
+    const alloc = std.testing.allocator;
+    const schema = try parseSchema(alloc,
+        \\{"name": "jira_issue", "description": "Fetch a jira issue",
+        \\ "parameters": {"type": "object", "properties": {"key": {"type": "string"}}}}
+    );
+    defer {
+        alloc.free(schema.name);
+        alloc.free(schema.description);
+        alloc.free(schema.parameters);
+    }
+    try std.testing.expectEqualStrings("jira_issue", schema.name);
+    try std.testing.expect(std.mem.indexOf(u8, schema.parameters, "\"key\"") != null);
+
+    try std.testing.expectError(error.InvalidSchema, parseSchema(alloc, "{\"name\": \"a b\", \"description\": \"\", \"parameters\": {}}"));
+    try std.testing.expectError(error.InvalidSchema, parseSchema(alloc, "{\"name\": \"x\", \"description\": \"\"}"));
+}
@@ -0,0 +1,394 @@
+//! `blitz config export [FILE]` and `blitz config import FILE`, the whole
+//! setup as one portable TOML profile:
+//!
+//!   config   every text file below `~/.config/blitzdenk/` (blitz.lua with
+//!            keymap, theme and modes, prompts, skills, templates, packs)
+//!   project  the allowlists of the current project, `.blitz/bash_allow`
+//!            and `.blitz/edit_allow`
+//!
+//! Secrets stay behind: `keys.env` and other `.env` files are skipped, and
+//! quoted values of `api_key`/`token`/`secret`/`password` assignments are
+//! replaced with `<redacted>`. Import writes the files back and keeps a
+//! `.bak` of every file it changes.
+const std = @import("std");
+const allowlist = @import("allowlist.zig");
+const CONFIG_DIR = @import("main.zig").DEFAULT_CONFIG_PATH;
+
+pub const DEFAULT_FILE = "blitz-profile.toml";
+pub const VERSION = 1;
+const MAX_FILE_BYTES = 256 * 1024;
+const REDACTED = "<redacted>";
+const secret_names: []const []const u8 = &.{ "api_key", "apikey", "token", "secret", "password" };
+const project_files: []const []const u8 = &.{ allowlist.FILENAME, allowlist.EDIT_FILENAME };
+
+pub const Scope = enum { config, project };
+
+pub const Entry = struct {
+    scope: Scope,
+    /// relative to the scope's root
+    path: []const u8,
+    content: []const u8,
+};
+
+/// Entry point for `blitz config export`.
+pub fn exportProfile(io: std.Io, alloc: std.mem.Allocator, home_path: []const u8, out_path: []const u8) !void {
+    var entries = std.ArrayList(Entry).empty;
+
+    const config_abs = try std.fs.path.join(alloc, &.{ home_path, CONFIG_DIR });
+    if (std.Io.Dir.openDirAbsolute(io, config_abs, .{ .iterate = true })) |dir| {
+        var config = dir;
+        defer config.close(io);
+        try collect(io, alloc, config, "", &entries);
+    } else |_| {}
+
+    for (project_files) |path| {
+        const content = readFile(io, alloc, std.Io.Dir.cwd(), path) orelse continue;
+        try entries.append(alloc, .{ .scope = .project, .path = path, .content = content });
+    }
+
+    if (entries.items.len == 0) {
+        std.debug.print("nothing to export, no ~/{s} and no project allowlists\n", .{CONFIG_DIR});
+        return;
+    }
+
+    const text = try serialize(alloc, entries.items);
+    const file = try std.Io.Dir.cwd().createFile(io, out_path, .{});
+    defer file.close(io);
+    var write_buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &write_buf);
+    try writer.interface.writeAll(text);
+    try writer.interface.flush();
+
+    std.debug.print("exported {d} file(s) to {s}, secrets excluded\n", .{ entries.items.len, out_path });
+}
+
+/// Entry point for `blitz config import`.
+pub fn importProfile(io: std.Io, alloc: std.mem.Allocator, home_path: []const u8, in_path: []const u8) !void {
+    const text = readFile(io, alloc, std.Io.Dir.cwd(), in_path) orelse {
+        std.debug.print("cannot read {s}\n", .{in_path});
+        return;
+    };
+    const entries = parse(alloc, text) catch |err| {
+        std.debug.print("invalid profile {s}: {s}\n", .{ in_path, @errorName(err) });
+        return;
+    };
+
+    var home = try std.Io.Dir.openDirAbsolute(io, home_path, .{});
+    defer home.close(io);
+    try home.createDirPath(io, CONFIG_DIR);
+    var config = try home.openDir(io, CONFIG_DIR, .{});
+    defer config.close(io);
+
+    var written: usize = 0;
+    for (entries) |entry| {
+        if (!safePath(entry.path)) {
+            std.debug.print("  skipped {s}, path leaves the profile root\n", .{entry.path});
+            continue;
+        }
+        const root = switch (entry.scope) {
+            .config => config,
+            .project => std.Io.Dir.cwd(),
+        };
+        const old = readFile(io, alloc, root, entry.path);
+        if (old) |content| {
+            if (std.mem.eql(u8, content, entry.content)) continue;
+            const bak = try std.fmt.allocPrint(alloc, "{s}.bak", .{entry.path});
+            try writeFile(io, root, bak, content);
+        }
+        if (std.fs.path.dirname(entry.path)) |parent| try root.createDirPath(io, parent);
+        try writeFile(io, root, entry.path, entry.content);
+        written += 1;
+        std.debug.print("  {s} {s}{s}\n", .{
+            if (old == null) "added  " else "updated",
+            if (entry.scope == .config) "~/" ++ CONFIG_DIR else "",
+            entry.path,
+        });
+    }
+    std.debug.print("imported {d} of {d} file(s) from {s}\n", .{ written, entries.len, in_path });
+    if (std.mem.indexOf(u8, text, REDACTED) != null) {
+        std.debug.print("some values were redacted on export, fill them in or use ~/{s}keys.env\n", .{CONFIG_DIR});
+    }
+}
+
+fn collect(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, prefix: []const u8, entries: *std.ArrayList(Entry)) !void {
+    var it = dir.iterate();
+    while (try it.next(io)) |entry| {
+        if (entry.name.len == 0 or entry.name[0] == '.') continue;
+        const path = if (prefix.len == 0)
+            try alloc.dupe(u8, entry.name)
+        else
+            try std.fmt.allocPrint(alloc, "{s}/{s}", .{ prefix, entry.name });
+        switch (entry.kind) {
+            .directory => {
+                var sub = dir.openDir(io, entry.name, .{ .iterate = true }) catch continue;
+                defer sub.close(io);
+                try collect(io, alloc, sub, path, entries);
+            },
+            .file => {
+                if (isSecretFile(entry.name)) continue;
+                const content = readFile(io, alloc, dir, entry.name) orelse continue;
+                // binaries (compiled drop-in tools) do not travel as text
+                if (!std.unicode.utf8ValidateSlice(content) or std.mem.indexOfScalar(u8, content, 0) != null) continue;
+                try entries.append(alloc, .{ .scope = .config, .path = path, .content = try redact(alloc, content) });
+            },
+            else => {},
+        }
+    }
+}
+
+/// Keys, backups and the prompt history, prompts often carry pasted tokens.
+fn isSecretFile(name: []const u8) bool {
+    return std.mem.endsWith(u8, name, ".env") or std.mem.endsWith(u8, name, ".bak") or
+        std.mem.eql(u8, name, "prompt_history.json");
+}
+
+/// Blank the quoted value of assignments whose name looks like a secret,
+/// `api_key = "sk-..."` becomes `api_key = "<redacted>"`.
+pub fn redact(alloc: std.mem.Allocator, content: []const u8) ![]const u8 {
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    errdefer out.deinit();
+    const w = &out.writer;
+
+    var last: usize = 0;
+    var i: usize = 0;
+    while (i < content.len) : (i += 1) {
+        if (content[i] != '=' and content[i] != ':') continue;
+        if (i + 1 < content.len and content[i + 1] == '=') {
+            i += 1;
+            continue;
+        }
+        if (!isSecretName(nameBefore(content[last..i]))) continue;
+
+        var open = i + 1;
+        while (open < content.len and (content[open] == ' ' or content[open] == '\t')) open += 1;
+        if (open >= content.len or (content[open] != '"' and content[open] != '\'')) continue;
+        const close = std.mem.indexOfScalarPos(u8, content, open + 1, content[open]) orelse continue;
+        if (close == open + 1 or std.mem.indexOfScalar(u8, content[open..close], '\n') != null) continue;
+
+        try w.writeAll(content[last .. open + 1]);
+        try w.writeAll(REDACTED);
+        last = close;
+        i = close;
+    }
+    try w.writeAll(content[last..]);
+    return out.toOwnedSlice();
+}
+
+/// Identifier right before an `=` or `:`, quotes and brackets skipped.
+fn nameBefore(text: []const u8) []const u8 {
+    const trimmed = std.mem.trimRight(u8, text, " \t\"']");
+    var start = trimmed.len;
+    while (start > 0 and (std.ascii.isAlphanumeric(trimmed[start - 1]) or trimmed[start - 1] == '_')) start -= 1;
+    return trimmed[start..];
+}
+
+fn isSecretName(name: []const u8) bool {
+    for (secret_names) |secret| {
+        if (std.ascii.endsWithIgnoreCase(name, secret)) return true;
+    }
+    return false;
+}
+
+/// Relative, without `..`, so an imported profile cannot write elsewhere.
+pub fn safePath(path: []const u8) bool {
+    if (path.len == 0 or std.fs.path.isAbsolute(path)) return false;
+    var it = std.mem.splitScalar(u8, path, '/');
+    while (it.next()) |segment| {
+        if (segment.len == 0 or std.mem.eql(u8, segment, "..")) return false;
+    }
+    return true;
+}
+
+pub fn serialize(alloc: std.mem.Allocator, entries: []const Entry) ![]const u8 {
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    errdefer out.deinit();
+    const w = &out.writer;
+    try w.print("# blitzdenk profile, secrets excluded\n# import with `blitz config import <file>`\nversion = {d}\n", .{VERSION});
+    for (entries) |entry| {
+        try w.print("\n[[file]]\nscope = \"{s}\"\npath = ", .{@tagName(entry.scope)});
+        try writeBasic(w, entry.path);
+        try w.writeAll("\ncontent = ");
+        // literal strings keep the files readable, they cannot hold `'''`
+        if (std.mem.indexOf(u8, entry.content, "'''") == null and
+            !std.mem.endsWith(u8, entry.content, "'") and
+            std.mem.indexOfScalar(u8, entry.content, '\r') == null)
+        {
+            try w.print("'''\n{s}'''\n", .{entry.content});
+        } else {
+            try writeBasic(w, entry.content);
+            try w.writeByte('\n');
+        }
+    }
+    return out.toOwnedSlice();
+}
+
+fn writeBasic(w: *std.Io.Writer, s: []const u8) !void {
+    try w.writeByte('"');
+    for (s) |c| switch (c) {
+        '"' => try w.writeAll("\\\""),
+        '\\' => try w.writeAll("\\\\"),
+        '\n' => try w.writeAll("\\n"),
+        '\r' => try w.writeAll("\\r"),
+        '\t' => try w.writeAll("\\t"),
+        0...8, 11, 12, 14...31, 127 => try w.print("\\u{x:0>4}", .{c}),
+        else => try w.writeByte(c),
+    };
+    try w.writeByte('"');
+}
+
+/// Reads the subset of TOML `serialize` writes: `[[file]]` tables of
+/// `key = "basic"` and `key = '''literal'''` strings.
+pub fn parse(alloc: std.mem.Allocator, text: []const u8) ![]const Entry {
+    var entries = std.ArrayList(Entry).empty;
+    var current: ?struct { scope: ?Scope = null, path: ?[]const u8 = null, content: ?[]const u8 = null } = null;
+    var pos: usize = 0;
+
+    while (pos < text.len) {
+        const line_end = std.mem.indexOfScalarPos(u8, text, pos, '\n') orelse text.len;
+        const line = std.mem.trim(u8, text[pos..line_end], " \t\r");
+        const next = @min(line_end + 1, text.len);
+
+        if (line.len == 0 or line[0] == '#') {
+            pos = next;
+            continue;
+        }
+        if (std.mem.eql(u8, line, "[[file]]")) {
+            if (current) |cur| try entries.append(alloc, try finish(cur.scope, cur.path, cur.content));
+            current = .{};
+            pos = next;
+            continue;
+        }
+
+        const eq = std.mem.indexOfScalar(u8, line, '=') orelse return error.InvalidProfile;
+        const key = std.mem.trim(u8, line[0..eq], " \t");
+        const raw = std.mem.trim(u8, line[eq + 1 ..], " \t");
+
+        var value: []const u8 = undefined;
+        if (std.mem.startsWith(u8, raw, "'''")) {
+            // value starts after the newline that follows the opening quotes
+            const start = if (raw.len == 3) next else pos + (std.mem.indexOf(u8, text[pos..], "'''").? + 3);
+            const end = std.mem.indexOfPos(u8, text, start, "'''") orelse return error.InvalidProfile;
+            value = text[start..end];
+            const close_end = std.mem.indexOfScalarPos(u8, text, end, '\n') orelse text.len;
+            pos = @min(close_end + 1, text.len);
+        } else if (raw.len > 0 and raw[0] == '"') {
+            value = try parseBasic(alloc, raw);
+            pos = next;
+        } else {
+            value = raw;
+            pos = next;
+        }
+
+        if (current) |*cur| {
+            if (std.mem.eql(u8, key, "scope")) {
+                cur.scope = std.meta.stringToEnum(Scope, value) orelse return error.InvalidProfile;
+            } else if (std.mem.eql(u8, key, "path")) {
+                cur.path = value;
+            } else if (std.mem.eql(u8, key, "content")) {
+                cur.content = value;
+            }
+        } else if (std.mem.eql(u8, key, "version")) {
+            const version = std.fmt.parseInt(u32, value, 10) catch return error.InvalidProfile;
+            if (version > VERSION) return error.UnsupportedVersion;
+        }
+    }
+    if (current) |cur| try entries.append(alloc, try finish(cur.scope, cur.path, cur.content));
+    return entries.items;
+}
+
+fn finish(scope: ?Scope, path: ?[]const u8, content: ?[]const u8) !Entry {
+    return .{
+        .scope = scope orelse .config,
+        .path = path orelse return error.InvalidProfile,
+        .content = content orelse return error.InvalidProfile,
+    };
+}
+
+fn parseBasic(alloc: std.mem.Allocator, raw: []const u8) ![]const u8 {
+    var out = std.ArrayList(u8).empty;
+    var i: usize = 1;
+    while (i < raw.len) : (i += 1) {
+        const c = raw[i];
+        if (c == '"') return out.items;
+        if (c != '\\') {
+            try out.append(alloc, c);
+            continue;
+        }
+        i += 1;
+        if (i >= raw.len) return error.InvalidProfile;
+        switch (raw[i]) {
+            'n' => try out.append(alloc, '\n'),
+            'r' => try out.append(alloc, '\r'),
+            't' => try out.append(alloc, '\t'),
+            '"' => try out.append(alloc, '"'),
+            '\\' => try out.append(alloc, '\\'),
+            'u' => {
+                if (i + 4 >= raw.len) return error.InvalidProfile;
+                const cp = std.fmt.parseInt(u21, raw[i + 1 .. i + 5], 16) catch return error.InvalidProfile;
+                var buf: [4]u8 = undefined;
+                const n = std.unicode.utf8Encode(cp, &buf) catch return error.InvalidProfile;
+                try out.appendSlice(alloc, buf[0..n]);
+                i += 4;
+            },
+            else => return error.InvalidProfile,
+        }
+    }
+    return error.InvalidProfile;
+}
+
+fn writeFile(io: std.Io, dir: std.Io.Dir, path: []const u8, content: []const u8) !void {
+    const file = try dir.createFile(io, path, .{});
+    defer file.close(io);
+    var write_buf: [1024]u8 = undefined;
+    var writer = file.writer(io, &write_buf);
+    try writer.interface.writeAll(content);
+    try writer.interface.flush();
+}
+
+fn readFile(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, path: []const u8) ?[]const u8 {
+    const file = dir.openFile(io, path, .{}) catch return null;
+    defer file.close(io);
+    var read_buf: [1024]u8 = undefined;
+    var file_reader = file.reader(io, &read_buf);
+    return file_reader.interface.allocRemaining(alloc, .limited(MAX_FILE_BYTES)) catch null;
+}
+
+test "profile round trip and redaction" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    try std.testing.expectEqualStrings(
+        "local p = { key_envar = \"OPENAI_KEY\", api_key = \"<redacted>\" }\nblitz.bind(\"<C-t>\", f)",
+        try redact(alloc, "local p = { key_envar = \"OPENAI_KEY\", api_key = \"sk-123\" }\nblitz.bind(\"<C-t>\", f)"),
+    );
+    try std.testing.expectEqualStrings("GITHUB_TOKEN='<redacted>'", try redact(alloc, "GITHUB_TOKEN='ghp_x'"));
+
+    try std.testing.expect(safePath("prompts/review.md"));
+    try std.testing.expect(!safePath("../.ssh/config"));
+    try std.testing.expect(!safePath("/etc/passwd"));
+    try std.testing.expect(!safePath("a//b"));
+
+    const entries = [_]Entry{
+        .{ .scope = .config, .path = "blitz.lua", .content = "blitz.set_theme(\"gruvbox\")\n" },
+        .{ .scope = .config, .path = "skills/quote.md", .content = "has ''' inside\tand \"quotes\"" },
+        .{ .scope = .project, .path = ".blitz/bash_allow", .content = "cargo *\n" },
+    };
+    const text = try serialize(alloc, &entries);
+    const parsed = try parse(alloc, text);
+    try std.testing.expectEqual(entries.len, parsed.len);
+    for (entries, parsed) |want, got| {
+        try std.testing.expectEqual(want.scope, got.scope);
+        try std.testing.expectEqualStrings(want.path, got.path);
+        try std.testing.expectEqualStrings(want.content, got.content);
+    }
+    try std.testing.expectError(error.UnsupportedVersion, parse(alloc, "version = 99\n"));
+}
+
+test "secret files stay out of the profile" {
+    try std.testing.expect(isSecretFile("keys.env"));
+    try std.testing.expect(isSecretFile("blitz.lua.bak"));
+    try std.testing.expect(isSecretFile("prompt_history.json"));
+    try std.testing.expect(!isSecretFile("blitz.lua"));
+    try std.testing.expect(!isSecretFile("history.md"));
+}
@@ -62,6 +62,8 @@ pub const OllamaConfig = struct {
     top_p: ?f32 = null,
     top_k: ?u32 = null,
     stop: ?[]const []const u8 = null,
+    /// seconds the model stays resident after a request, -1 keeps it loaded
+    keep_alive: ?i32 = null,
 };
 
 pub const OpenAiConfig = struct {
@@ -106,6 +108,8 @@ pub const Config = struct {
     base_url: []const u8,
     reasoning_effort: ?ReasoningEffort = null,
     provider: ProviderConfig,
+    /// false for endpoints without SSE, the answer arrives in one piece
+    stream: bool = true,
 };
 
 pub fn cloneConfig(alloc: Allocator, config: Config) !Config {
@@ -115,6 +119,7 @@ pub fn cloneConfig(alloc: Allocator, config: Config) !Config {
         .base_url = try alloc.dupe(u8, config.base_url),
         .reasoning_effort = config.reasoning_effort,
         .provider = try cloneProviderConfig(alloc, config.provider),
+        .stream = config.stream,
     };
 }
 
@@ -126,6 +131,7 @@ fn cloneProviderConfig(alloc: Allocator, config: ProviderConfig) !ProviderConfig
             .top_p = cfg.top_p,
             .top_k = cfg.top_k,
             .stop = try cloneStops(alloc, cfg.stop),
+            .keep_alive = cfg.keep_alive,
         } },
         .openai => |cfg| .{ .openai = .{
             .temperature = cfg.temperature,
@@ -221,6 +227,9 @@ pub const Message = struct {
     provider_items: []const []const u8 = &.{},
     flags: Flags = .{},
     time_ms: i64 = 0,
+    /// Provider-reported usage for the request that produced this message.
+    /// Only set on finalized agent messages.
+    usage: ?TokenUsage = null,
 
     pub const Flags = packed struct(u8) {
         allow_export: bool = true,
@@ -233,6 +242,7 @@ pub const Message = struct {
         msg.role = self.role;
         msg.flags = self.flags;
         msg.time_ms = self.time_ms;
+        msg.usage = self.usage;
         const provider_items = try gpa.alloc([]const u8, self.provider_items.len);
         for (self.provider_items, 0..) |item, i| provider_items[i] = try gpa.dupe(u8, item);
         msg.provider_items = provider_items;
@@ -353,15 +363,31 @@ pub fn openStream(
     };
 }
 
+/// First sentence (or first line) of a tool description.
+pub fn terseDescription(desc: []const u8) []const u8 {
+    const trimmed = std.mem.trim(u8, desc, " \n");
+    var end = std.mem.indexOfScalar(u8, trimmed, '\n') orelse trimmed.len;
+    if (std.mem.indexOf(u8, trimmed[0..end], ". ")) |dot| end = dot + 1;
+    return trimmed[0..end];
+}
+
 pub const Chat = struct {
     // deep copy required
     messages: std.ArrayList(Message) = .empty,
     // shallow copy allowed
     tools: std.ArrayList(ToolDef) = .empty,
+    /// Keep the tool definitions in the request (providers reject tool
+    /// history without them) but forbid calling any, `tool_choice: none`.
+    tools_disabled: bool = false,
+    /// Send only the first sentence of each tool description. Saves
+    /// thousands of tokens per request for models that know the tools well.
+    minimal_tool_descriptions: bool = false,
 
     pub fn clone(self: *const Chat, gpa: std.mem.Allocator) !Chat {
         var chat: Chat = .{
             .tools = try self.tools.clone(gpa),
+            .tools_disabled = self.tools_disabled,
+            .minimal_tool_descriptions = self.minimal_tool_descriptions,
         };
 
         for (self.messages.items) |*msg| {
@@ -380,6 +406,22 @@ pub const Chat = struct {
         });
     }
 
+    /// Description as it goes over the wire.
+    pub fn toolDescription(self: *const Chat, tool: ToolDef) []const u8 {
+        return if (self.minimal_tool_descriptions) terseDescription(tool.description) else tool.description;
+    }
+
+    /// Bytes of tool descriptions sent per request, verbose and minimal.
+    pub fn toolDescriptionBytes(self: *const Chat) struct { verbose: u64, minimal: u64 } {
+        var verbose: u64 = 0;
+        var minimal: u64 = 0;
+        for (self.tools.items) |tool| {
+            verbose += tool.description.len;
+            minimal += terseDescription(tool.description).len;
+        }
+        return .{ .verbose = verbose, .minimal = minimal };
+    }
+
     pub fn addTool(self: *Chat, alloc: Allocator, tool: ToolDef) !void {
         try self.tools.append(alloc, tool);
     }
@@ -504,3 +546,9 @@ test "streaming message chunks are reclaimable" {
     try std.testing.expectEqual(@as(usize, 4096), chat.messages.items[idx].parts[0].thinking.text.len);
     chat.messages.items[idx].freeParts(alloc);
 }
+
+test "terseDescription" {
+    try std.testing.expectEqualStrings("Read a file.", terseDescription("Read a file. Supports offset and limit.\nMore."));
+    try std.testing.expectEqualStrings("Executes a given bash command", terseDescription("Executes a given bash command\n\nIMPORTANT: ..."));
+    try std.testing.expectEqualStrings("short", terseDescription("short"));
+}
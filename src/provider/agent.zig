@@ -5,6 +5,7 @@ const apt = r.adapter;
 const http = r.http;
 const tc = r.tool;
 const compact = r.compact;
+const retry_policy = r.retry;
 
 const log = std.log.scoped(.agent);
 
@@ -88,13 +89,39 @@ pub const Todo = struct {
     subject: []const u8,
     description: []const u8,
     state: TodoState,
+    /// ids that have to be done first, removed ones count as done
+    depends_on: []const u32 = &.{},
+    /// list revision of the last change
+    rev: u64 = 0,
+    changed_by: TodoList.Origin = .agent,
 };
 
+/// Todos are addressed by stable ids and kept in execution order, earlier is
+/// more urgent. Every mutation goes through `apply` under the agent's lock,
+/// bumps the revision and records who made it, so the agent can be told
+/// about user edits instead of silently overwriting them.
 pub const TodoList = struct {
     pub const max_todos = 64;
     todos: [max_todos]Todo = undefined,
     count: usize = 0,
     next_id: u32 = 1,
+    revision: u64 = 0,
+    /// last revision the agent has seen, via a list or its own change
+    agent_seen: u64 = 0,
+
+    pub const Origin = enum { agent, user };
+
+    pub const Op = union(enum) {
+        add: struct { subject: []const u8, description: []const u8, depends_on: []const u32 = &.{} },
+        set_state: struct { id: u32, state: TodoState },
+        set_description: struct { id: u32, description: []const u8 },
+        set_subject: struct { id: u32, subject: []const u8 },
+        /// shift by `delta` positions, earlier is more urgent
+        move: struct { id: u32, delta: i32 },
+        remove: u32,
+    };
+
+    pub const ApplyError = error{ TodoNotFound, TodoListFull, TodoConflict };
 
     pub fn findById(self: *TodoList, id: u32) ?*Todo {
         for (self.todos[0..self.count]) |*t| {
@@ -102,6 +129,118 @@ pub const TodoList = struct {
         }
         return null;
     }
+
+    /// Applies `op` and returns the id it touched. Strings must outlive
+    /// the list. An agent change to a todo the user edited after the agent
+    /// last looked fails with `TodoConflict`; the agent has seen the
+    /// current state once the conflict is reported, a retry goes through.
+    pub fn apply(self: *TodoList, op: Op, origin: Origin) ApplyError!u32 {
+        const id = switch (op) {
+            .add => |a| blk: {
+                if (self.count >= max_todos) return error.TodoListFull;
+                for (a.depends_on) |dep| {
+                    if (self.findById(dep) == null) return error.TodoNotFound;
+                }
+                const id = self.next_id;
+                self.next_id += 1;
+                self.todos[self.count] = .{ .id = id, .subject = a.subject, .description = a.description, .state = .pending, .depends_on = a.depends_on };
+                self.count += 1;
+                break :blk id;
+            },
+            .set_state => |s| blk: {
+                const todo = try self.mutable(s.id, origin);
+                todo.state = s.state;
+                break :blk s.id;
+            },
+            .set_description => |d| blk: {
+                const todo = try self.mutable(d.id, origin);
+                todo.description = d.description;
+                break :blk d.id;
+            },
+            .set_subject => |d| blk: {
+                const todo = try self.mutable(d.id, origin);
+                todo.subject = d.subject;
+                break :blk d.id;
+            },
+            .move => |m| blk: {
+                _ = try self.mutable(m.id, origin);
+                const from = self.indexOf(m.id).?;
+                const to: usize = @intCast(std.math.clamp(@as(i64, @intCast(from)) + m.delta, 0, @as(i64, @intCast(self.count - 1))));
+                const todo = self.todos[from];
+                if (to < from) {
+                    std.mem.copyBackwards(Todo, self.todos[to + 1 .. from + 1], self.todos[to..from]);
+                } else if (to > from) {
+                    std.mem.copyForwards(Todo, self.todos[from..to], self.todos[from + 1 .. to + 1]);
+                }
+                self.todos[to] = todo;
+                break :blk m.id;
+            },
+            .remove => |rid| blk: {
+                _ = try self.mutable(rid, origin);
+                for (self.todos[0..self.count], 0..) |t, i| {
+                    if (t.id != rid) continue;
+                    std.mem.copyForwards(Todo, self.todos[i .. self.count - 1], self.todos[i + 1 .. self.count]);
+                    self.count -= 1;
+                    break;
+                }
+                break :blk rid;
+            },
+        };
+
+        self.revision += 1;
+        if (self.findById(id)) |todo| {
+            todo.rev = self.revision;
+            todo.changed_by = origin;
+        }
+        if (origin == .agent) self.agent_seen = self.revision;
+        return id;
+    }
+
+    /// The todo to work on next: the first one in progress, else the first
+    /// pending one whose dependencies are done. Null when everything left is
+    /// blocked or the list is finished.
+    pub fn nextActionable(self: *const TodoList) ?*const Todo {
+        for (self.todos[0..self.count]) |*t| {
+            if (t.state == .in_progress) return t;
+        }
+        for (self.todos[0..self.count]) |*t| {
+            if (t.state == .pending and !self.isBlocked(t.*)) return t;
+        }
+        return null;
+    }
+
+    pub fn isBlocked(self: *const TodoList, todo: Todo) bool {
+        for (todo.depends_on) |dep| {
+            const i = self.indexOf(dep) orelse continue;
+            if (self.todos[i].state != .done) return true;
+        }
+        return false;
+    }
+
+    fn indexOf(self: *const TodoList, id: u32) ?usize {
+        for (self.todos[0..self.count], 0..) |t, i| {
+            if (t.id == id) return i;
+        }
+        return null;
+    }
+
+    fn mutable(self: *TodoList, id: u32, origin: Origin) ApplyError!*Todo {
+        const todo = self.findById(id) orelse return error.TodoNotFound;
+        if (origin == .agent and todo.changed_by == .user and todo.rev > self.agent_seen) {
+            self.agent_seen = self.revision;
+            return error.TodoConflict;
+        }
+        return todo;
+    }
+
+    /// True if the user changed something the agent has not seen yet.
+    pub fn hasUnseenUserChanges(self: *const TodoList) bool {
+        return self.revision > self.agent_seen;
+    }
+
+    pub fn markSeen(self: *TodoList) void {
+        self.agent_seen = self.revision;
+    }
 };
 
 pub fn Guard(comptime T: type) type {
@@ -204,6 +343,38 @@ const loop_guard_force_rethink_warning =
     \\
 ;
 
+/// Wall clock bookkeeping of the current run. A turn is one model request
+/// plus the tool calls it triggered.
+pub const RunTiming = struct {
+    start_ms: i64 = 0,
+    turns: u32 = 0,
+    turn_start_ms: ?i64 = null,
+    turn_total_ms: i64 = 0,
+
+    pub fn begin(now_ms: i64) RunTiming {
+        return .{ .start_ms = now_ms, .turns = 1, .turn_start_ms = now_ms };
+    }
+
+    pub fn nextTurn(self: *RunTiming, now_ms: i64) void {
+        self.endTurn(now_ms);
+        self.turns += 1;
+        self.turn_start_ms = now_ms;
+    }
+
+    pub fn endTurn(self: *RunTiming, now_ms: i64) void {
+        const start = self.turn_start_ms orelse return;
+        self.turn_total_ms += now_ms - start;
+        self.turn_start_ms = null;
+    }
+
+    /// Average over finished turns, null before the first one finished.
+    pub fn avgTurnMs(self: *const RunTiming) ?i64 {
+        const finished = self.turns - @intFromBool(self.turn_start_ms != null);
+        if (finished == 0) return null;
+        return @divTrunc(self.turn_total_ms, finished);
+    }
+};
+
 pub const AgentFlags = packed struct {
     force_full_reminder: bool = false,
     is_fork: bool = false,
@@ -216,8 +387,6 @@ pub const AgentFlags = packed struct {
 // Fat and juicy
 pub const Agent = struct {
     pub const MAX_TOOL_CALLS = tc.MAX_TOOL_CALLS_PER_REQ;
-    pub const TIMEOUT_DURATION = 3;
-    pub const MAX_RETRIES = 3;
     pub const REQUEST_TIMEOUT_MS: u32 = 60_000;
     pub const MAX_DELTAS_PER_TICK: u32 = 32;
     pub const POOL_BACKOFF_SECONDS: f32 = 0.2;
@@ -236,6 +405,8 @@ pub const Agent = struct {
     state: State = .idle,
     pending_handle: ?http.RequestPool.RequestHandle = null,
     request_start_ms: ?i64 = null,
+    /// body of a non-streamed response as it came in so far
+    blocking_body: std.ArrayList(u8) = .empty,
     stream: ?apt.Stream = null,
     iteration: u32 = 0,
     max_iterations: u32 = 100,
@@ -248,11 +419,25 @@ pub const Agent = struct {
     bg_agents: Locked(BackgroundAgentList) = .{},
     todo_list: Locked(TodoList) = .{},
     retry_count: u32 = 0,
+    retry_policy: retry_policy.Policy = .{},
+    /// wait before the pending retry
+    retry_delay_ms: u32 = 0,
+    /// models tried in order once the current one gives up, same provider
+    fallback_models: []const []const u8 = &.{},
+    fallback_idx: usize = 0,
+    /// the configured model while a fallback answers, put back when the run ends
+    primary_model: ?[]const u8 = null,
+    /// the failed response the pending retry is about, reported if it is the last
+    last_failure: ?Failure = null,
     timeout: f32 = 0,
     session_id: [32]u8,
     last_input_context_size: u32 = 0, // track total context size
     context_limit: u32 = 128 * 1024, // everything above 128k context is dump
     compaction: compact.State = .{},
+    /// percent of `context_limit` that triggers auto compaction
+    compact_threshold: u8 = compact.DEFAULT_THRESHOLD_PERCENT,
+    /// model writing the compaction summary, empty uses the agent's own
+    compact_model: []const u8 = "",
     in_flight_usage: apt.TokenUsage = .{}, // streaming usage
     total_usage: apt.TokenUsage = .{}, // accumulated across turns
     approx_output_bytes: u64 = 0, // byte counter for token approximation
@@ -263,7 +448,12 @@ pub const Agent = struct {
     /// Settled tool results awaiting commit, keyed by call.id.
     tool_call_done: std.StringHashMapUnmanaged(apt.ToolResult) = .{},
     max_allowed_tool_calls: u32 = 64,
+    /// input + output tokens across turns, tool calls are refused past it. 0 is unlimited
+    max_total_tokens: u64 = 0,
     tool_call_count: u32 = 0,
+    timing: RunTiming = .{},
+    /// first message of a prompt the app has not let through yet
+    unchecked_prompt: ?usize = null,
     flags: AgentFlags = .{},
     loop_guard: LoopGuard = .{},
 
@@ -302,6 +492,8 @@ pub const Agent = struct {
         self.bg_agents = .{};
         self.todo_list = .{};
         self.retry_count = 0;
+        self.last_failure = null;
+        self.restorePrimaryModel();
         self.timeout = 0;
         self.last_input_context_size = 0;
         self.compaction = .{};
@@ -309,6 +501,7 @@ pub const Agent = struct {
         self.tool_call_runs = .{};
         self.tool_call_done = .{};
         self.tool_call_count = 0;
+        self.timing = .{};
         self.loop_guard = .{};
         _ = self.arena.reset(.free_all);
     }
@@ -335,10 +528,21 @@ pub const Agent = struct {
     }
 
     pub fn runWithMsg(self: *Agent, parts: []const apt.ContentPart) void {
+        self.markPrompt();
         self.chat.addMessage(self.arena.allocator(), .user, parts) catch {};
         self.run();
     }
 
+    /// The user messages waiting for the app's `pre_run` check.
+    pub fn uncheckedPrompt(self: *const Agent) []const apt.Message {
+        const start = self.unchecked_prompt orelse return &.{};
+        return self.chat.messages.items[@min(start, self.chat.messages.items.len)..];
+    }
+
+    fn markPrompt(self: *Agent) void {
+        if (self.unchecked_prompt == null) self.unchecked_prompt = self.chat.messages.items.len;
+    }
+
     pub fn run(self: *Agent) void {
         self.flags.is_thinking = false;
         self.flags.is_writing = false;
@@ -347,6 +551,7 @@ pub const Agent = struct {
         self.iteration = 0;
         self.retry_count = 0;
         self.last_error = null;
+        self.timing = .begin(http.nowMs(self.pool.io));
         self.loop_guard.clear();
     }
 
@@ -370,7 +575,20 @@ pub const Agent = struct {
         }
     }
 
+    pub const Failure = struct {
+        status: ?u16,
+        body: []const u8,
+        kind: r.errors.Kind,
+    };
+
     pub fn tick(self: *Agent, dt: f32, ctx: r.Swarm.SwarmContextV) TickResult {
+        const result = self.tickState(dt, ctx);
+        // a fallback model only stands in for the run it was needed in
+        if (result == .complete or result == .failed) self.restorePrimaryModel();
+        return result;
+    }
+
+    fn tickState(self: *Agent, dt: f32, ctx: r.Swarm.SwarmContextV) TickResult {
         switch (self.state) {
             .idle => return .idle,
             .compacting => {
@@ -380,6 +598,7 @@ pub const Agent = struct {
                     if (continue_after) {
                         self.state = .sending_request;
                     } else if (self.popQueuedParts(ctx)) |queued_parts| {
+                        self.markPrompt();
                         self.chat.addMessage(self.arena.allocator(), .user, queued_parts) catch |err| return self.fail(err);
                         self.iteration = 0;
                         self.retry_count = 0;
@@ -392,10 +611,8 @@ pub const Agent = struct {
                 return .pending;
             },
             .retry_timeout => {
-                if (self.retry_count >= MAX_RETRIES) return self.fail(self.last_error orelse error.TimeoutReached);
-
                 self.timeout += dt;
-                if (self.timeout > TIMEOUT_DURATION) {
+                if (self.timeout * std.time.ms_per_s >= @as(f32, @floatFromInt(self.retry_delay_ms))) {
                     self.timeout = 0;
                     self.state = .sending_request;
                 }
@@ -404,12 +621,30 @@ pub const Agent = struct {
             },
             .sending_request => {
                 while (self.popQueuedParts(ctx)) |queued_parts| {
+                    self.markPrompt();
                     self.chat.addMessage(self.arena.allocator(), .user, queued_parts) catch |err| return self.fail(err);
                     self.iteration = 0;
                     self.retry_count = 0;
                     self.last_error = null;
                 }
 
+                if (self.unchecked_prompt) |start| {
+                    if (ctx.pre_run) |pre_run| switch (pre_run(ctx.ptr, self)) {
+                        .wait => return .pending,
+                        .go => |note| if (note.len > 0) {
+                            const text = self.arena.allocator().dupe(u8, note) catch |err| return self.fail(err);
+                            self.appendPartsToLastMessage(&.{.{ .text = text }}) catch |err| return self.fail(err);
+                        },
+                        .veto => {
+                            // the prompt never happened, a retry resends the chat before it
+                            self.chat.messages.shrinkRetainingCapacity(@min(start, self.chat.messages.items.len));
+                            self.unchecked_prompt = null;
+                            return self.fail(error.PromptVetoed);
+                        },
+                    };
+                    self.unchecked_prompt = null;
+                }
+
                 if (!self.flags.turn_has_reminder) {
                     ctx.gen_system_reminders(ctx.ptr, self);
                     self.flags.turn_has_reminder = true;
@@ -419,6 +654,7 @@ pub const Agent = struct {
                     return .pending;
                 }
 
+                self.last_failure = null;
                 const request_start_ms = http.nowMs(self.pool.io);
                 self.pending_handle = apt.complete(
                     self.pool,
@@ -426,7 +662,7 @@ pub const Agent = struct {
                     &self.chat,
                     self.config,
                     .{
-                        .mode = .streaming,
+                        .mode = if (self.config.stream) .streaming else .blocking,
                         .session_id = &self.session_id,
                         .timeout_ms = REQUEST_TIMEOUT_MS,
                     },
@@ -442,6 +678,7 @@ pub const Agent = struct {
                     else => return self.fail(err),
                 };
                 self.request_start_ms = request_start_ms;
+                self.blocking_body = .empty;
                 self.state = .waiting_response;
                 return .pending;
             },
@@ -456,22 +693,26 @@ pub const Agent = struct {
             .waiting_response => {
                 const handle = self.pending_handle.?;
                 if (!self.pool.headersReady(handle)) return .pending;
+                // without streaming the whole answer has to be in first,
+                // drained every tick so the worker never stalls on a full queue
+                if (!self.config.stream) {
+                    const complete = self.pool.drainBody(handle, self.arena.allocator(), &self.blocking_body) catch |err| {
+                        self.pool.cancel(handle);
+                        self.pending_handle = null;
+                        self.request_start_ms = null;
+                        return self.retryOrFail(err);
+                    };
+                    if (!complete) return .pending;
+                }
 
-                self.startStreaming() catch |err| {
+                const started = if (self.config.stream) self.startStreaming() else self.finishBlocking();
+                started catch |err| {
                     if (self.pending_handle) |h| {
                         self.pool.cancel(h);
                         self.pending_handle = null;
                     }
                     self.request_start_ms = null;
-                    if (err == error.ProviderRequestFailed) return self.fail(err);
-                    if (self.retry_count < MAX_RETRIES) {
-                        self.retry_count += 1;
-                        self.last_error = err;
-                        self.state = .retry_timeout;
-                        self.timeout = 0;
-                        return .pending;
-                    }
-                    return self.fail(err);
+                    return self.retryOrFail(err);
                 };
                 return .pending;
             },
@@ -482,18 +723,10 @@ pub const Agent = struct {
                         self.pending_handle = null;
                     }
                     self.dropStream();
-                    if (err == error.ProviderRequestFailed) return self.fail(err);
-                    if (self.retry_count < MAX_RETRIES) {
-                        self.flags.is_thinking = false;
-                        self.flags.is_writing = false;
-                        self.flags.is_calling = false;
-                        self.retry_count += 1;
-                        self.last_error = err;
-                        self.state = .retry_timeout;
-                        self.timeout = 0;
-                        return .pending;
-                    }
-                    return self.fail(err);
+                    self.flags.is_thinking = false;
+                    self.flags.is_writing = false;
+                    self.flags.is_calling = false;
+                    return self.retryOrFail(err);
                 };
                 self.retry_count = 0;
                 return outcome;
@@ -505,18 +738,22 @@ pub const Agent = struct {
                     const should_exit = self.commitSettledResults(ctx) catch |err| return self.fail(err);
                     self.flags.turn_has_reminder = false;
 
+                    const now = http.nowMs(self.pool.io);
                     if (should_exit) {
+                        self.timing.endTurn(now);
                         self.state = .complete;
                         return .complete;
                     }
 
                     self.iteration += 1;
                     if (self.iteration >= self.max_iterations) {
+                        self.timing.endTurn(now);
                         self.state = .failed;
                         self.last_error = error.MaxIterationsReached;
                         return .failed;
                     }
 
+                    self.timing.nextTurn(now);
                     self.state = .sending_request;
                 }
                 return .pending;
@@ -534,7 +771,61 @@ pub const Agent = struct {
         return (cs / limit) * 100;
     }
 
+    /// Transient failures wait out the backoff and go again. Once the policy
+    /// gives up, or the model itself is the problem, the next fallback model
+    /// takes the request. The provider error only reaches the chat when
+    /// nothing is left to try.
+    fn retryOrFail(self: *Agent, err: anyerror) TickResult {
+        self.last_error = err;
+        const failure = self.last_failure;
+        // without a response it was the connection, worth another try
+        const transient = if (failure) |f| retry_policy.isTransient(f.status, f.kind) else err != error.ProviderRequestFailed;
+        if (transient and self.retry_count < self.retry_policy.max_retries) {
+            self.retry_count += 1;
+            const seed: u64 = @bitCast(http.nowMs(self.pool.io));
+            self.retry_delay_ms = self.retry_policy.delayMs(self.retry_count, seed +% self.retry_count);
+            log.warn("{s}: {s}, retry {d}/{d} in {d}ms", .{ self.config.model, @errorName(err), self.retry_count, self.retry_policy.max_retries, self.retry_delay_ms });
+            self.state = .retry_timeout;
+            self.timeout = 0;
+            return .pending;
+        }
+
+        const failover_ok = if (failure) |f| retry_policy.canFailover(f.kind) else true;
+        if (failover_ok and self.fallback_idx < self.fallback_models.len) {
+            const next = self.fallback_models[self.fallback_idx];
+            self.fallback_idx += 1;
+            log.warn("{s} failed ({s}), falling back to {s}", .{ self.config.model, @errorName(err), next });
+            if (self.primary_model == null) self.primary_model = self.config.model;
+            self.config.model = next;
+            self.retry_count = 0;
+            self.last_failure = null;
+            self.state = .sending_request;
+            return .pending;
+        }
+
+        if (failure) |f| self.reportProviderError(f.status, f.body);
+        self.last_failure = null;
+        return self.fail(err);
+    }
+
+    fn restorePrimaryModel(self: *Agent) void {
+        self.fallback_idx = 0;
+        const model = self.primary_model orelse return;
+        self.config.model = model;
+        self.primary_model = null;
+    }
+
+    /// Remember a failed response for `retryOrFail`.
+    fn rememberFailure(self: *Agent, status: ?u16, body: []const u8) void {
+        const alloc = self.arena.allocator();
+        // stream error events live in the stream arena, gone by the time it is reported
+        const owned = alloc.dupe(u8, body) catch body;
+        const info = r.errors.classify(alloc, status, owned);
+        self.last_failure = .{ .status = status, .body = owned, .kind = info.kind };
+    }
+
     fn fail(self: *Agent, err: ?anyerror) TickResult {
+        self.timing.endTurn(http.nowMs(self.pool.io));
         self.flags.is_thinking = false;
         self.flags.is_writing = false;
         self.flags.is_calling = false;
@@ -574,6 +865,7 @@ pub const Agent = struct {
         self.flags.is_thinking = false;
         self.flags.is_writing = false;
         self.flags.is_calling = false;
+        self.timing.endTurn(http.nowMs(self.pool.io));
         self.state = .complete;
     }
 
@@ -593,7 +885,7 @@ pub const Agent = struct {
             const body = self.pool.collectBody(handle, alloc) catch &.{};
             const snippet = body[0..@min(body.len, 2048)];
             log.warn("http {d} from provider: {s}", .{ status_code, snippet });
-            self.reportProviderError(status_code, body);
+            self.rememberFailure(status_code, body);
             self.pool.cancel(handle);
             self.pending_handle = null;
             self.request_start_ms = null;
@@ -612,6 +904,38 @@ pub const Agent = struct {
         self.state = .streaming_response;
     }
 
+    /// Blocking counterpart of `startStreaming` + `finishStream`, for
+    /// providers configured with `stream = false`.
+    fn finishBlocking(self: *Agent) !void {
+        const handle = self.pending_handle.?;
+        const status = self.pool.getStatus(handle) catch |err| {
+            self.pool.cancel(handle);
+            self.pending_handle = null;
+            self.request_start_ms = null;
+            return err;
+        };
+        const status_code: u16 = @intFromEnum(status);
+        const arena = self.arena.allocator();
+        const body = self.blocking_body.items;
+        self.blocking_body = .empty;
+
+        if (status_code < 200 or status_code >= 300) {
+            log.warn("http {d} from provider: {s}", .{ status_code, body[0..@min(body.len, 2048)] });
+            self.rememberFailure(status_code, body);
+            self.pool.cancel(handle);
+            self.pending_handle = null;
+            self.request_start_ms = null;
+            return error.ProviderRequestFailed;
+        }
+
+        const result = try apt.parseCompletion(arena, self.config, body);
+        _ = try self.chat.beginStreamingMessage(arena, .agent);
+        self.in_flight_usage = .{};
+        self.retry_count = 0;
+        // the turn ends here, tick picks the state set by completeTurn up
+        _ = self.completeTurn(result);
+    }
+
     /// Index of the in-progress streaming message. Valid while state is
     /// .streaming_response; the message is always the last appended.
     pub fn streamingMessageIndex(self: *const Agent) ?usize {
@@ -669,7 +993,7 @@ pub const Agent = struct {
                     self.last_input_context_size = @intCast(@min(total, std.math.maxInt(u32)));
                 },
                 .provider_error => |body| {
-                    self.reportProviderError(null, body);
+                    self.rememberFailure(null, body);
                     return error.ProviderRequestFailed;
                 },
                 .finish => return self.finishStream(ctx),
@@ -680,33 +1004,30 @@ pub const Agent = struct {
 
     fn reportProviderError(self: *Agent, status_code: ?u16, body: []const u8) void {
         const alloc = self.arena.allocator();
+        // the status travels with the broadcast, the UI renders the card
         const trimmed = std.mem.trim(u8, body, " \t\r\n");
-        const message = if (status_code) |status|
-            if (trimmed.len > 0)
-                std.fmt.allocPrint(alloc, "Provider error (HTTP {d})\n{s}", .{ status, trimmed }) catch trimmed
-            else
-                std.fmt.allocPrint(alloc, "Provider error (HTTP {d}): empty response body", .{status}) catch "Provider error"
-        else if (trimmed.len > 0)
-            std.fmt.allocPrint(alloc, "Provider error\n{s}", .{trimmed}) catch trimmed
-        else
-            "Provider error: empty response body";
+        const message = if (trimmed.len > 0) trimmed else "empty response body";
 
         const parts = alloc.alloc(apt.ContentPart, 1) catch return;
         parts[0] = .{ .text = message };
         const swarm = self.swarm orelse return;
         const id = self.swarm_id orelse return;
-        swarm.recordProviderError(id, parts);
+        swarm.recordProviderError(id, status_code, parts);
     }
 
     fn finishStream(self: *Agent, _: Swarm.SwarmContextV) !TickResult {
+        if (self.stream == null) return error.NoStream;
+        const result = try self.stream.?.finalize(self.arena.allocator());
+        return self.completeTurn(result);
+    }
+
+    /// Puts the final message in place of the streaming one and moves on to
+    /// tool execution or completion.
+    fn completeTurn(self: *Agent, result: apt.ResponseResult) TickResult {
         self.flags.is_thinking = false;
         self.flags.is_writing = false;
-        const arena = self.arena.allocator();
-        if (self.stream == null) return error.NoStream;
-        const stream = &self.stream.?;
         const msg_idx = self.chat.messages.items.len - 1;
 
-        const result = try stream.finalize(arena);
         const final_parts = filterEmptyTextParts(result.message.parts);
         self.chat.finalizeStreamingMessage(self.gpa, msg_idx, final_parts);
         self.chat.messages.items[msg_idx].provider_items = result.message.provider_items;
@@ -718,8 +1039,9 @@ pub const Agent = struct {
             // Prefer finalize's authoritative usage; fall back to last in-flight
             // value if the provider didn't report it on close.
             const final_usage = result.usage orelse self.in_flight_usage;
+            self.chat.messages.items[msg_idx].usage = final_usage;
             self.total_usage.add(final_usage);
-            swarm.recordUsage(self.config.model, final_usage);
+            swarm.recordUsage(self.config.model, final_usage, final_parts);
             if (self.swarm_id) |id| {
                 swarm.recordBroadcast(id, result.message.role, final_parts);
             }
@@ -752,6 +1074,7 @@ pub const Agent = struct {
         }
 
         self.flags.is_calling = false;
+        self.timing.endTurn(http.nowMs(self.pool.io));
         self.state = .complete;
         return .complete;
     }
@@ -799,13 +1122,15 @@ pub const Agent = struct {
         msg.parts = appended;
     }
 
-    fn runToolWrapper(
+    pub fn runToolWrapper(
         func: tc.ToolFn,
         ctx: tc.ToolContext,
         call: apt.ToolCall,
         done: *std.atomic.Value(bool),
     ) apt.ToolResult {
         defer done.store(true, .release);
+        const context = ctx.swarm.context;
+        if (context.run_tool) |run| return run(context.ptr, func, ctx, call);
         return func(ctx, call);
     }
 
@@ -844,6 +1169,17 @@ pub const Agent = struct {
                 continue;
             }
 
+            // `tool_choice: none` is only a request, some providers call tools anyway
+            if (self.chat.tools_disabled) {
+                try self.tool_call_done.put(alloc, call.id, .{
+                    .call_id = call.id,
+                    .name = call.name,
+                    .content = "Tools are disabled for this turn, answer without calling any",
+                    .is_error = true,
+                });
+                continue;
+            }
+
             const loop_count = try self.loop_guard.record(alloc, call);
             if (LoopGuard.warningForCount(loop_count)) |warning| {
                 try self.loop_guard.warnings.put(alloc, call.id, warning);
@@ -859,7 +1195,7 @@ pub const Agent = struct {
             }
 
             // Tool-call budget gate.
-            if (self.tool_call_count >= self.max_allowed_tool_calls) {
+            if (self.tool_call_count >= self.max_allowed_tool_calls or self.overTokenBudget()) {
                 std.log.debug("[TOOLCALL_LIMIT REACHED]", .{});
                 try self.tool_call_done.put(alloc, call.id, .{
                     .call_id = call.id,
@@ -880,6 +1216,21 @@ pub const Agent = struct {
                 continue;
             };
 
+            // a bad call is answered here, the tool only sees arguments that fit its schema
+            var checked_call = call;
+            switch (r.schema.check(alloc, tool.def.parameters_schema, call.arguments)) {
+                .ok => |arguments| checked_call.arguments = arguments,
+                .invalid => |msg| {
+                    try self.tool_call_done.put(alloc, call.id, .{
+                        .call_id = call.id,
+                        .name = call.name,
+                        .content = msg,
+                        .is_error = true,
+                    });
+                    continue;
+                },
+            }
+
             const slot = try alloc.create(tc.RunningTool);
             slot.* = .{ .fut = .{ .any_future = null, .result = undefined } };
             const tool_ctx = tc.ToolContext{
@@ -890,7 +1241,7 @@ pub const Agent = struct {
                 .cancel = &slot.cancel,
                 .cwd = swarm.context.cwd(swarm.context.ptr),
             };
-            slot.fut = std.Io.async(self.pool.io, runToolWrapper, .{ tool.func, tool_ctx, call, &slot.done });
+            slot.fut = std.Io.async(self.pool.io, runToolWrapper, .{ tool.func, tool_ctx, checked_call, &slot.done });
             // TODO: emit event_bus.tool_call_started — needs event bus accessible from Agent
             try self.tool_call_runs.put(alloc, call.id, slot);
             self.tool_call_count += 1;
@@ -954,7 +1305,7 @@ pub const Agent = struct {
             try self.appendPartsToLastMessage(queued_parts);
         }
 
-        if (self.tool_call_count >= self.max_allowed_tool_calls) {
+        if (self.tool_call_count >= self.max_allowed_tool_calls or self.overTokenBudget()) {
             try self.appendPartsToLastMessage(&.{
                 .{ .text = "<system_warning>!TOOL CALL LIMIT REACHED! Report your current findings back to the user</system_warning>" },
             });
@@ -963,6 +1314,11 @@ pub const Agent = struct {
         return exit_loop;
     }
 
+    fn overTokenBudget(self: *const Agent) bool {
+        if (self.max_total_tokens == 0) return false;
+        return self.total_usage.input_tokens + self.total_usage.output_tokens >= self.max_total_tokens;
+    }
+
     fn loopGuardWarningForResults(self: *Agent, results: []const apt.ToolResult) ?[]const u8 {
         var selected: ?LoopGuard.WarningLevel = null;
 
@@ -988,7 +1344,7 @@ pub const Agent = struct {
         swarm.recordBroadcast(id, .user, parts[0..results.len]);
     }
 
-    fn findTool(self: *Agent, name: []const u8) ?tc.Tool {
+    pub fn findTool(self: *Agent, name: []const u8) ?tc.Tool {
         for (self.tools.items) |tool| {
             if (std.mem.eql(u8, tool.def.name, name)) return tool;
         }
@@ -59,6 +59,10 @@ const AntSystemBlock = struct {
     cache_control: ?AntCacheControl = null,
 };
 
+const AntToolChoice = struct {
+    type: []const u8,
+};
+
 const AntRequest = struct {
     model: []const u8,
     max_tokens: u32,
@@ -66,6 +70,7 @@ const AntRequest = struct {
     system: ?[]const AntSystemBlock = null,
     messages: []const AntMessage,
     tools: ?[]const AntToolDef = null,
+    tool_choice: ?AntToolChoice = null,
     thinking: ?adapter.Thinking = null,
     output_config: ?AntOutputConfig = null,
     temperature: ?f32 = null,
@@ -176,6 +181,8 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
                     });
                 },
                 .image => |img| {
+                    // generated images stay local, assistant turns take text only
+                    if (msg.role == .agent) continue;
                     try content_blocks.append(allocator, .{
                         .type = "image",
                         .source = .{
@@ -275,7 +282,7 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
 
             try tool_defs_buf.append(allocator, .{
                 .name = tool.name,
-                .description = tool.description,
+                .description = chat.toolDescription(tool),
                 .input_schema = parsed.value,
             });
         }
@@ -317,6 +324,7 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
         .system = system_blocks,
         .messages = merged.items,
         .tools = tool_defs,
+        .tool_choice = if (chat.tools_disabled and tool_defs != null) .{ .type = "none" } else null,
         .thinking = ac.thinking,
         .output_config = if (config.reasoning_effort) |e| AntOutputConfig{ .effort = @tagName(e) } else null,
         .temperature = ac.temperature,
@@ -0,0 +1,300 @@
+//! Provider batch APIs: many independent requests submitted at once and
+//! answered within a day at about half the price. Anthropic takes the
+//! requests inline (`/messages/batches`), OpenAI wants them uploaded as a
+//! JSONL file first (`/files`, then `/batches`). Both are polled by id and
+//! hand back one JSONL line per request, matched up again by `custom_id`.
+//! Calls block, this is for `blitz batch`, not the agent loop.
+const std = @import("std");
+const adapter = @import("adapter.zig");
+const http = @import("http.zig");
+const openai = @import("openai.zig");
+const responses = @import("responses.zig");
+const anthropic = @import("anthropic.zig");
+const Allocator = std.mem.Allocator;
+
+const TIMEOUT_MS = 5 * 60 * 1000;
+const BOUNDARY = "blitzdenk-batch-boundary";
+
+pub const Request = struct {
+    custom_id: []const u8,
+    chat: *const adapter.Chat,
+};
+
+pub const Poll = struct {
+    done: bool,
+    succeeded: u64 = 0,
+    failed: u64 = 0,
+    pending: u64 = 0,
+    /// where the result lines are, Anthropic's results url or OpenAI's
+    /// output and error file ids
+    results: []const []const u8 = &.{},
+};
+
+pub const Result = struct {
+    custom_id: []const u8,
+    response: ?adapter.ResponseResult = null,
+    /// set when the request failed
+    err: []const u8 = "",
+};
+
+pub const Client = struct {
+    pool: *http.RequestPool,
+    alloc: Allocator,
+    cfg: adapter.Config,
+    /// response body of the last call that failed
+    last_error: []const u8 = "",
+
+    pub fn supported(provider: adapter.Provider) bool {
+        return provider != .ollama;
+    }
+
+    /// Submit `requests` as one batch, returns its id.
+    pub fn submit(self: *Client, requests: []const Request) ![]const u8 {
+        const lines = try encodeRequests(self.alloc, self.cfg, requests);
+        switch (self.cfg.provider) {
+            .anthropic => {
+                const payload = try std.fmt.allocPrint(self.alloc, "{{\"requests\":[{s}]}}", .{lines});
+                const body = try self.send(try self.url("/messages/batches"), .POST, payload, "application/json");
+                return self.field(body, "id");
+            },
+            .openai, .response => {
+                var form = std.Io.Writer.Allocating.init(self.alloc);
+                try form.writer.print(
+                    "--" ++ BOUNDARY ++ "\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\r\n" ++
+                        "--" ++ BOUNDARY ++ "\r\nContent-Disposition: form-data; name=\"file\"; filename=\"batch.jsonl\"\r\n" ++
+                        "Content-Type: application/jsonl\r\n\r\n{s}\r\n--" ++ BOUNDARY ++ "--\r\n",
+                    .{lines},
+                );
+                const upload = try self.send(try self.url("/files"), .POST, form.written(), "multipart/form-data; boundary=" ++ BOUNDARY);
+                const file_id = try self.field(upload, "id");
+                const payload = try std.json.Stringify.valueAlloc(self.alloc, .{
+                    .input_file_id = file_id,
+                    .endpoint = endpoint(std.meta.activeTag(self.cfg.provider)),
+                    .completion_window = "24h",
+                }, .{});
+                const body = try self.send(try self.url("/batches"), .POST, payload, "application/json");
+                return self.field(body, "id");
+            },
+            .ollama => return error.BatchUnsupported,
+        }
+    }
+
+    pub fn poll(self: *Client, id: []const u8) !Poll {
+        switch (self.cfg.provider) {
+            .anthropic => {
+                const body = try self.send(try self.url(try std.fmt.allocPrint(self.alloc, "/messages/batches/{s}", .{id})), .GET, null, null);
+                return parseAnthropicPoll(self.alloc, body);
+            },
+            .openai, .response => {
+                const body = try self.send(try self.url(try std.fmt.allocPrint(self.alloc, "/batches/{s}", .{id})), .GET, null, null);
+                return parseOpenAiPoll(self.alloc, body);
+            },
+            .ollama => return error.BatchUnsupported,
+        }
+    }
+
+    /// The results behind one `Poll.results` entry.
+    pub fn results(self: *Client, ref: []const u8) ![]Result {
+        const source = switch (self.cfg.provider) {
+            .anthropic => ref,
+            else => try self.url(try std.fmt.allocPrint(self.alloc, "/files/{s}/content", .{ref})),
+        };
+        const body = try self.send(source, .GET, null, null);
+        var out = std.ArrayList(Result).empty;
+        var lines = std.mem.tokenizeScalar(u8, body, '\n');
+        while (lines.next()) |line| {
+            if (std.mem.trim(u8, line, " \r").len == 0) continue;
+            try out.append(self.alloc, try parseResultLine(self.alloc, std.meta.activeTag(self.cfg.provider), line));
+        }
+        return out.items;
+    }
+
+    fn url(self: *Client, path: []const u8) ![]const u8 {
+        return std.fmt.allocPrint(self.alloc, "{s}{s}", .{ self.cfg.base_url, path });
+    }
+
+    fn field(self: *Client, body: []const u8, name: []const u8) ![]const u8 {
+        const value = try std.json.parseFromSliceLeaky(std.json.Value, self.alloc, body, .{});
+        if (value == .object) {
+            if (value.object.get(name)) |v| if (v == .string) return v.string;
+        }
+        self.last_error = body;
+        return error.UnexpectedResponse;
+    }
+
+    fn send(self: *Client, target: []const u8, method: std.http.Method, payload: ?[]const u8, content_type: ?[]const u8) ![]const u8 {
+        var headers = std.ArrayList(std.http.Header).empty;
+        if (content_type) |ct| try headers.append(self.alloc, .{ .name = "Content-Type", .value = ct });
+        switch (self.cfg.provider) {
+            .anthropic => {
+                try headers.append(self.alloc, .{ .name = "x-api-key", .value = self.cfg.api_key });
+                try headers.append(self.alloc, .{ .name = "anthropic-version", .value = "2023-06-01" });
+            },
+            else => try headers.append(self.alloc, .{
+                .name = "Authorization",
+                .value = try std.fmt.allocPrint(self.alloc, "Bearer {s}", .{self.cfg.api_key}),
+            }),
+        }
+
+        const handle = try self.pool.fetch(target, method, payload, headers.items, TIMEOUT_MS);
+        defer self.pool.release(handle);
+        const body = try self.pool.collectBody(handle, self.alloc);
+        const status_code: u16 = @intFromEnum(try self.pool.getStatus(handle));
+        if (status_code < 200 or status_code >= 300) {
+            self.last_error = body;
+            return error.BatchRequestFailed;
+        }
+        return body;
+    }
+};
+
+fn endpoint(provider: adapter.Provider) []const u8 {
+    return if (provider == .response) "/v1/responses" else "/v1/chat/completions";
+}
+
+/// One line per request, joined by `,` for Anthropic and `\n` for OpenAI.
+pub fn encodeRequests(alloc: Allocator, cfg: adapter.Config, requests: []const Request) ![]const u8 {
+    var out = std.Io.Writer.Allocating.init(alloc);
+    const w = &out.writer;
+    for (requests, 0..) |req, i| {
+        const id = try std.json.Stringify.valueAlloc(alloc, req.custom_id, .{});
+        switch (cfg.provider) {
+            .anthropic => {
+                if (i > 0) try w.writeByte(',');
+                const params = try anthropic.serializeRequest(alloc, req.chat, cfg, .blocking);
+                try w.print("{{\"custom_id\":{s},\"params\":{s}}}", .{ id, params });
+            },
+            .openai, .response => {
+                if (i > 0) try w.writeByte('\n');
+                const body = switch (cfg.provider) {
+                    .response => try responses.serializeRequest(alloc, req.chat, cfg, .blocking),
+                    else => try openai.serializeRequest(alloc, req.chat, cfg, .blocking),
+                };
+                try w.print("{{\"custom_id\":{s},\"method\":\"POST\",\"url\":\"{s}\",\"body\":{s}}}", .{ id, endpoint(std.meta.activeTag(cfg.provider)), body });
+            },
+            .ollama => return error.BatchUnsupported,
+        }
+    }
+    return out.written();
+}
+
+fn count(obj: std.json.ObjectMap, name: []const u8) u64 {
+    const v = obj.get(name) orelse return 0;
+    return if (v == .integer and v.integer > 0) @intCast(v.integer) else 0;
+}
+
+fn string(obj: std.json.ObjectMap, name: []const u8) ?[]const u8 {
+    const v = obj.get(name) orelse return null;
+    return if (v == .string) v.string else null;
+}
+
+pub fn parseAnthropicPoll(alloc: Allocator, body: []const u8) !Poll {
+    const value = try std.json.parseFromSliceLeaky(std.json.Value, alloc, body, .{});
+    if (value != .object) return error.UnexpectedResponse;
+    const obj = value.object;
+    var poll: Poll = .{ .done = std.mem.eql(u8, string(obj, "processing_status") orelse "", "ended") };
+    if (obj.get("request_counts")) |counts| if (counts == .object) {
+        poll.succeeded = count(counts.object, "succeeded");
+        poll.failed = count(counts.object, "errored") + count(counts.object, "canceled") + count(counts.object, "expired");
+        poll.pending = count(counts.object, "processing");
+    };
+    if (string(obj, "results_url")) |results_url| poll.results = try alloc.dupe([]const u8, &.{results_url});
+    return poll;
+}
+
+pub fn parseOpenAiPoll(alloc: Allocator, body: []const u8) !Poll {
+    const value = try std.json.parseFromSliceLeaky(std.json.Value, alloc, body, .{});
+    if (value != .object) return error.UnexpectedResponse;
+    const obj = value.object;
+    const status = string(obj, "status") orelse "";
+    const finished = [_][]const u8{ "completed", "failed", "expired", "cancelled" };
+    var poll: Poll = .{ .done = false };
+    for (finished) |f| {
+        if (std.mem.eql(u8, status, f)) poll.done = true;
+    }
+    if (obj.get("request_counts")) |counts| if (counts == .object) {
+        poll.succeeded = count(counts.object, "completed");
+        poll.failed = count(counts.object, "failed");
+        poll.pending = count(counts.object, "total") -| poll.succeeded -| poll.failed;
+    };
+    var refs = std.ArrayList([]const u8).empty;
+    if (string(obj, "output_file_id")) |id| try refs.append(alloc, id);
+    if (string(obj, "error_file_id")) |id| try refs.append(alloc, id);
+    poll.results = refs.items;
+    return poll;
+}
+
+pub fn parseResultLine(alloc: Allocator, provider: adapter.Provider, line: []const u8) !Result {
+    const value = try std.json.parseFromSliceLeaky(std.json.Value, alloc, line, .{});
+    if (value != .object) return error.UnexpectedResponse;
+    const obj = value.object;
+    var result: Result = .{ .custom_id = string(obj, "custom_id") orelse return error.UnexpectedResponse };
+
+    switch (provider) {
+        .anthropic => {
+            const outcome = obj.get("result") orelse return error.UnexpectedResponse;
+            if (outcome != .object) return error.UnexpectedResponse;
+            const kind = string(outcome.object, "type") orelse "";
+            if (!std.mem.eql(u8, kind, "succeeded")) {
+                result.err = if (outcome.object.get("error")) |e| try std.json.Stringify.valueAlloc(alloc, e, .{}) else kind;
+                return result;
+            }
+            const message = outcome.object.get("message") orelse return error.UnexpectedResponse;
+            result.response = try anthropic.parseResponse(alloc, try std.json.Stringify.valueAlloc(alloc, message, .{}));
+        },
+        .openai, .response => {
+            if (obj.get("error")) |e| if (e != .null) {
+                result.err = try std.json.Stringify.valueAlloc(alloc, e, .{});
+                return result;
+            };
+            const response = obj.get("response") orelse return error.UnexpectedResponse;
+            if (response != .object) return error.UnexpectedResponse;
+            const body = response.object.get("body") orelse return error.UnexpectedResponse;
+            const json = try std.json.Stringify.valueAlloc(alloc, body, .{});
+            if (count(response.object, "status_code") != 200) {
+                result.err = json;
+                return result;
+            }
+            result.response = if (provider == .response)
+                try responses.parseResponse(alloc, json)
+            else
+                try openai.parseResponse(alloc, json);
+        },
+        .ollama => return error.BatchUnsupported,
+    }
+    return result;
+}
+
+test "batch polling and result lines" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const ant = try parseAnthropicPoll(alloc,
+        \\{"id":"msgbatch_1","processing_status":"ended","request_counts":{"processing":0,"succeeded":2,"errored":1,"canceled":0,"expired":0},"results_url":"https://api.anthropic.com/v1/messages/batches/msgbatch_1/results"}
+    );
+    try std.testing.expect(ant.done);
+    try std.testing.expectEqual(@as(u64, 2), ant.succeeded);
+    try std.testing.expectEqual(@as(u64, 1), ant.failed);
+    try std.testing.expectEqual(@as(usize, 1), ant.results.len);
+
+    const oai = try parseOpenAiPoll(alloc,
+        \\{"id":"batch_1","status":"in_progress","request_counts":{"total":5,"completed":2,"failed":0},"output_file_id":null}
+    );
+    try std.testing.expect(!oai.done);
+    try std.testing.expectEqual(@as(u64, 3), oai.pending);
+    try std.testing.expectEqual(@as(usize, 0), oai.results.len);
+
+    const failed = try parseResultLine(alloc, .anthropic,
+        \\{"custom_id":"f1","result":{"type":"errored","error":{"type":"invalid_request_error","message":"bad"}}}
+    );
+    try std.testing.expectEqualStrings("f1", failed.custom_id);
+    try std.testing.expect(failed.response == null);
+    try std.testing.expect(std.mem.indexOf(u8, failed.err, "bad") != null);
+
+    const refused = try parseResultLine(alloc, .openai,
+        \\{"id":"r1","custom_id":"f2","response":{"status_code":400,"body":{"error":{"message":"nope"}}},"error":null}
+    );
+    try std.testing.expect(refused.response == null);
+    try std.testing.expect(std.mem.indexOf(u8, refused.err, "nope") != null);
+}
@@ -28,8 +28,8 @@ const SUMMARY_PREFIX =
     \\
 ;
 
-const AUTO_COMPACT_NUMERATOR: u64 = 9;
-const AUTO_COMPACT_DENOMINATOR: u64 = 10;
+/// Share of the context edge, in percent, at which auto compaction starts.
+pub const DEFAULT_THRESHOLD_PERCENT: u8 = 90;
 const RECENT_USER_MAX_TOKENS: u64 = 20_000;
 const COMPACTION_TIMEOUT_MS: u32 = 5 * 60_000;
 
@@ -78,7 +78,7 @@ pub fn maybeStart(self: *Agent) !bool {
         else => blk: {
             var compact_chat = try buildCompactPrompt(arena, &self.chat);
             compact_chat.tools = .empty;
-            break :blk apt.complete(self.pool, arena, &compact_chat, self.config, options);
+            break :blk apt.complete(self.pool, arena, &compact_chat, summaryConfig(self), options);
         },
     } catch |err| switch (err) {
         error.PoolExhausted => {
@@ -135,7 +135,7 @@ pub fn poll(self: *Agent) !bool {
 
     if (usage) |value| {
         self.total_usage.add(value);
-        if (self.swarm) |swarm| swarm.recordUsage(self.config.model, value);
+        if (self.swarm) |swarm| swarm.recordUsage(summaryConfig(self).model, value, &.{});
     }
 
     self.pool.release(handle);
@@ -173,7 +173,15 @@ fn shouldStart(self: *const Agent, estimate: u64) bool {
 }
 
 fn autoCompactLimit(self: *const Agent) u64 {
-    return (@as(u64, self.context_limit) * AUTO_COMPACT_NUMERATOR) / AUTO_COMPACT_DENOMINATOR;
+    return (@as(u64, self.context_limit) * self.compact_threshold) / 100;
+}
+
+/// The summary does not need the agent's model, a cheaper one on the same
+/// provider does the job. Responses compaction is server side and keeps it.
+fn summaryConfig(self: *const Agent) apt.Config {
+    var config = self.config;
+    if (self.compact_model.len > 0) config.model = self.compact_model;
+    return config;
 }
 
 pub fn estimateNextRequestTokens(self: *const Agent) u64 {
@@ -195,6 +203,28 @@ pub fn estimateNextRequestTokens(self: *const Agent) u64 {
     return approxTokens(bytes);
 }
 
+/// Estimated share of the next request taken by one message.
+pub fn messageTokens(msg: apt.Message) u64 {
+    var bytes: u64 = @tagName(msg.role).len + 8;
+    for (msg.provider_items) |item| bytes += item.len;
+    for (msg.parts) |part| bytes += partBytes(part);
+    return approxTokens(bytes);
+}
+
+/// Replace the heavy payloads of a message with a short marker, keeping
+/// tool call ids intact so call/result pairing stays valid. Canonical
+/// provider items are dropped, they would replay the original content.
+pub fn pruneMessage(alloc: std.mem.Allocator, msg: *apt.Message) !void {
+    const marker = try std.fmt.allocPrint(alloc, "[pruned by user, ~{d} tokens]", .{messageTokens(msg.*)});
+    for (msg.parts) |*part| switch (part.*) {
+        // signed thinking can't be edited, downgrade it to a plain marker
+        .text, .thinking, .image => part.* = .{ .text = marker },
+        .tool_call => |*call| call.arguments = "{}",
+        .tool_result => |*res| res.content = marker,
+    };
+    msg.provider_items = &.{};
+}
+
 fn partBytes(part: apt.ContentPart) u64 {
     return switch (part) {
         .text => |text| text.len,
@@ -205,7 +235,7 @@ fn partBytes(part: apt.ContentPart) u64 {
     };
 }
 
-fn approxTokens(bytes: u64) u64 {
+pub fn approxTokens(bytes: u64) u64 {
     return @max(1, (bytes + 2) / 3);
 }
 
@@ -378,6 +408,7 @@ fn cloneTodos(self: *Agent, alloc: std.mem.Allocator) !r.agent.TodoList {
     for (next.todos[0..next.count]) |*todo| {
         todo.subject = try alloc.dupe(u8, todo.subject);
         todo.description = try alloc.dupe(u8, todo.description);
+        todo.depends_on = try alloc.dupe(u32, todo.depends_on);
     }
     return next;
 }
@@ -517,6 +548,14 @@ test "auto compaction ignores maximum output tokens" {
 
     try testing.expect(!shouldStart(&agent, 89_999));
     try testing.expect(shouldStart(&agent, 90_000));
+
+    agent.compact_threshold = 50;
+    try testing.expect(!shouldStart(&agent, 49_999));
+    try testing.expect(shouldStart(&agent, 50_000));
+
+    agent.compact_model = "cheap";
+    try testing.expectEqualStrings("cheap", summaryConfig(&agent).model);
+    try testing.expectEqualStrings("test", agent.config.model);
 }
 
 test "responses compaction installs canonical output and rotates arena" {
@@ -550,3 +589,21 @@ test "responses compaction installs canonical output and rotates arena" {
     try testing.expectEqual(@as(usize, 2), agent.chat.messages.items[1].provider_items.len);
     try testing.expect(std.mem.indexOf(u8, agent.chat.messages.items[1].provider_items[1], "opaque") != null);
 }
+
+test "pruneMessage keeps tool call pairing" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const big = try alloc.alloc(u8, 3000);
+    @memset(big, 'x');
+    var parts = [_]apt.ContentPart{
+        .{ .tool_result = .{ .call_id = "c1", .name = "read", .content = big } },
+    };
+    var msg = apt.Message{ .role = .user, .parts = &parts };
+
+    const before = messageTokens(msg);
+    try pruneMessage(alloc, &msg);
+    try std.testing.expectEqualStrings("c1", msg.parts[0].tool_result.call_id);
+    try std.testing.expect(messageTokens(msg) < before / 10);
+}
@@ -14,10 +14,17 @@ pub const Provider = struct {
     url_len: usize = 0,
     key_envar: [128]u8 = undefined,
     key_len: usize = 0,
+    /// profile name for `blitz.use_provider` and `:providers`, empty for none
+    name: [64]u8 = undefined,
+    name_len: usize = 0,
+    /// model a switch to this provider starts on, empty keeps the current one
+    model: [256]u8 = undefined,
+    model_len: usize = 0,
     provider_config: adapter.ProviderConfig = .{ .openai = .{} },
     thinking_type_buf: [16]u8 = undefined,
     thinking_type_len: usize = 0,
     reasoning_effort: ?ReasoningEffort = null,
+    stream: bool = true,
     active: bool = false,
 
     pub fn getUrl(self: *const Provider) []const u8 {
@@ -28,6 +35,24 @@ pub const Provider = struct {
         return self.key_envar[0..self.key_len];
     }
 
+    pub fn getName(self: *const Provider) []const u8 {
+        return self.name[0..self.name_len];
+    }
+
+    pub fn getModel(self: *const Provider) []const u8 {
+        return self.model[0..self.model_len];
+    }
+
+    /// False when a string does not fit.
+    pub fn setProfile(self: *Provider, name: []const u8, model: []const u8) bool {
+        if (name.len > self.name.len or model.len > self.model.len) return false;
+        @memcpy(self.name[0..name.len], name);
+        self.name_len = name.len;
+        @memcpy(self.model[0..model.len], model);
+        self.model_len = model.len;
+        return true;
+    }
+
     pub fn setThinkingType(self: *Provider, s: []const u8) bool {
         if (s.len > self.thinking_type_buf.len) return false;
         @memcpy(self.thinking_type_buf[0..s.len], s);
@@ -51,6 +76,19 @@ pub const ModelEntry = struct {
     }
 };
 
+pub const MAX_MODEL_ALIASES = 16;
+
+/// Short name for `@model:<alias>` prompt hints.
+pub const ModelAlias = struct {
+    alias: [32]u8 = undefined,
+    alias_len: usize = 0,
+    entry: ModelEntry = .{},
+
+    pub fn getAlias(self: *const ModelAlias) []const u8 {
+        return self.alias[0..self.alias_len];
+    }
+};
+
 pub const MAX_DOCS = 32;
 
 pub const PathEntry = struct {
@@ -77,6 +115,8 @@ pub const BlitzdenkCfg = struct {
     providers: [MAX_PROVIDERS]Provider = @splat(.{}),
     provider_count: u32 = 0,
     default_model: ModelEntry = .{},
+    model_aliases: [MAX_MODEL_ALIASES]ModelAlias = @splat(.{}),
+    model_alias_count: u32 = 0,
 
     /// Reserve the next provider slot. Caller fills url/key_envar/provider_config
     /// (including the inline buffer for thinking.type) then calls
@@ -119,8 +159,66 @@ pub const BlitzdenkCfg = struct {
         return true;
     }
 
+    pub fn findProvider(self: *const BlitzdenkCfg, name: []const u8) ?ProviderHandle {
+        if (name.len == 0) return null;
+        for (self.providers[0..self.provider_count], 0..) |*prov, i| {
+            if (prov.active and std.mem.eql(u8, prov.getName(), name)) return @enumFromInt(i);
+        }
+        return null;
+    }
+
+    /// Moves the default model onto the provider, on its own default model
+    /// if it names one. False when it has none and no model is bound yet.
+    pub fn useProvider(self: *BlitzdenkCfg, handle: ProviderHandle) bool {
+        const idx = @intFromEnum(handle);
+        if (idx >= self.provider_count or !self.providers[idx].active) return false;
+        const model = self.providers[idx].getModel();
+        if (model.len > 0) return self.setModel(model, handle);
+        if (!self.default_model.bound) return false;
+        self.default_model.provider = handle;
+        return true;
+    }
+
+    /// Adds or replaces `alias`. False when the provider is unknown, a
+    /// string is too long or the alias table is full.
+    pub fn addModelAlias(self: *BlitzdenkCfg, alias: []const u8, name: []const u8, handle: ProviderHandle) bool {
+        const idx = @intFromEnum(handle);
+        if (idx >= self.provider_count or !self.providers[idx].active) return false;
+        if (alias.len == 0 or alias.len > 32 or name.len > 256) return false;
+
+        const slot = for (self.model_aliases[0..self.model_alias_count]) |*a| {
+            if (std.mem.eql(u8, a.getAlias(), alias)) break a;
+        } else blk: {
+            if (self.model_alias_count >= MAX_MODEL_ALIASES) return false;
+            self.model_alias_count += 1;
+            break :blk &self.model_aliases[self.model_alias_count - 1];
+        };
+        @memcpy(slot.alias[0..alias.len], alias);
+        slot.alias_len = alias.len;
+        @memcpy(slot.entry.name[0..name.len], name);
+        slot.entry.name_len = name.len;
+        slot.entry.provider = handle;
+        slot.entry.bound = true;
+        return true;
+    }
+
+    /// The aliased model, or `name` itself on the default provider.
+    pub fn resolveModel(self: *const BlitzdenkCfg, name: []const u8) ?ModelEntry {
+        for (self.model_aliases[0..self.model_alias_count]) |*a| {
+            if (std.mem.eql(u8, a.getAlias(), name)) return a.entry;
+        }
+        if (!self.default_model.bound or name.len > 256) return null;
+        var entry: ModelEntry = .{ .provider = self.default_model.provider, .bound = true, .name_len = name.len };
+        @memcpy(entry.name[0..name.len], name);
+        return entry;
+    }
+
     pub fn buildConfig(self: *const BlitzdenkCfg, env: *const std.process.Environ.Map) ?adapter.Config {
-        const entry = &self.default_model;
+        return self.buildConfigFor(&self.default_model, env);
+    }
+
+    /// `entry` must outlive the config, the model name points into it.
+    pub fn buildConfigFor(self: *const BlitzdenkCfg, entry: *const ModelEntry, env: *const std.process.Environ.Map) ?adapter.Config {
         if (!entry.bound) return null;
 
         const idx = @intFromEnum(entry.provider);
@@ -139,6 +237,7 @@ pub const BlitzdenkCfg = struct {
             .base_url = prov.getUrl(),
             .reasoning_effort = prov.reasoning_effort,
             .provider = prov.provider_config,
+            .stream = prov.stream,
         };
     }
 
@@ -146,9 +245,46 @@ pub const BlitzdenkCfg = struct {
         self.providers = @splat(.{});
         self.provider_count = 0;
         self.default_model = .{};
+        self.model_alias_count = 0;
     }
 };
 
+test "model aliases" {
+    var cfg: BlitzdenkCfg = .{};
+    _ = cfg.reserveProvider("https://api.anthropic.com/v1", "ANTHROPIC_API_KEY").?;
+    const handle = cfg.commitProvider();
+    try std.testing.expect(cfg.resolveModel("haiku") == null);
+    try std.testing.expect(cfg.setModel("claude-opus-4", handle));
+
+    try std.testing.expect(cfg.addModelAlias("haiku", "claude-haiku-3", handle));
+    try std.testing.expect(cfg.addModelAlias("haiku", "claude-haiku-4-5", handle));
+    try std.testing.expectEqual(@as(u32, 1), cfg.model_alias_count);
+    try std.testing.expect(!cfg.addModelAlias("fast", "x", @enumFromInt(3)));
+
+    try std.testing.expectEqualStrings("claude-haiku-4-5", cfg.resolveModel("haiku").?.getName());
+    try std.testing.expectEqualStrings("gpt-5.4-mini", cfg.resolveModel("gpt-5.4-mini").?.getName());
+}
+
+test "provider profiles" {
+    var cfg: BlitzdenkCfg = .{};
+    const local = cfg.reserveProvider("http://localhost:8080/v1", "").?;
+    try std.testing.expect(local.setProfile("llama", ""));
+    const llama = cfg.commitProvider();
+    const router = cfg.reserveProvider("https://openrouter.ai/api/v1", "OPENROUTER_API_KEY").?;
+    try std.testing.expect(router.setProfile("openrouter", "anthropic/claude-sonnet-4.5"));
+    const openrouter = cfg.commitProvider();
+
+    try std.testing.expectEqual(openrouter, cfg.findProvider("openrouter").?);
+    try std.testing.expect(cfg.findProvider("") == null);
+    try std.testing.expect(!cfg.useProvider(llama));
+
+    try std.testing.expect(cfg.useProvider(openrouter));
+    try std.testing.expectEqualStrings("anthropic/claude-sonnet-4.5", cfg.default_model.getName());
+    try std.testing.expect(cfg.useProvider(llama));
+    try std.testing.expectEqual(llama, cfg.default_model.provider);
+    try std.testing.expectEqualStrings("anthropic/claude-sonnet-4.5", cfg.default_model.getName());
+}
+
 test "parse reasoning effort" {
     try std.testing.expectEqual(.xhigh, parseReasoningEffort("xhigh"));
     try std.testing.expectEqual(null, parseReasoningEffort("medium"));
@@ -0,0 +1,149 @@
+//! Classify provider error responses into a handful of known causes, so the
+//! chat can show what went wrong and what to do instead of a raw JSON body.
+const std = @import("std");
+
+pub const Kind = enum {
+    invalid_api_key,
+    insufficient_quota,
+    rate_limited,
+    overloaded,
+    content_filter,
+    context_length,
+    model_not_found,
+    unknown,
+
+    pub fn title(self: Kind) []const u8 {
+        return switch (self) {
+            .invalid_api_key => "Invalid API key",
+            .insufficient_quota => "Quota exhausted",
+            .rate_limited => "Rate limited",
+            .overloaded => "Provider overloaded",
+            .content_filter => "Blocked by content filter",
+            .context_length => "Context too long",
+            .model_not_found => "Unknown model",
+            .unknown => "Provider error",
+        };
+    }
+
+    /// Suggested next step, empty for `unknown`.
+    pub fn hint(self: Kind) []const u8 {
+        return switch (self) {
+            .invalid_api_key => "Check the provider's key_envar in ~/.config/blitzdenk/blitz.lua and that the variable is exported.",
+            .insufficient_quota => "Top up the account or switch to another model in blitz.lua.",
+            .rate_limited => "Wait a moment, then retry with Ctrl+R.",
+            .overloaded => "Retry with Ctrl+R in a moment or switch to another model in blitz.lua.",
+            .content_filter => "Rephrase the request and retry with Ctrl+R.",
+            .context_length => "Start fresh with Ctrl+N and continue via :handoff, or lower the compact edge.",
+            .model_not_found => "Check the model name in blitz.lua.",
+            .unknown => "",
+        };
+    }
+};
+
+pub const Info = struct {
+    kind: Kind,
+    /// the provider's own message, or the raw body if none was found
+    message: []const u8,
+};
+
+/// `body` is the response body or stream error event, `status` the HTTP
+/// status if the request failed before streaming.
+pub fn classify(alloc: std.mem.Allocator, status: ?u16, body: []const u8) Info {
+    var message: []const u8 = std.mem.trim(u8, body, " \t\r\n");
+    var codes: std.ArrayList(u8) = .empty;
+
+    if (std.json.parseFromSliceLeaky(std.json.Value, alloc, body, .{})) |root| {
+        if (findError(root)) |err| {
+            if (err == .object) {
+                for ([_][]const u8{ "type", "code", "status" }) |field| {
+                    const v = err.object.get(field) orelse continue;
+                    if (v == .string) {
+                        codes.appendSlice(alloc, v.string) catch {};
+                        codes.append(alloc, ' ') catch {};
+                    }
+                }
+                if (err.object.get("message")) |m| {
+                    if (m == .string) message = m.string;
+                }
+            } else if (err == .string) {
+                message = err.string;
+            }
+        }
+    } else |_| {}
+
+    const haystack = std.ascii.allocLowerString(alloc, codes.items) catch codes.items;
+    const lower_message = std.ascii.allocLowerString(alloc, message) catch message;
+    return .{ .kind = kindOf(status, haystack, lower_message), .message = message };
+}
+
+/// The `error` object of the OpenAI, Anthropic, Gemini and Responses shapes.
+fn findError(root: std.json.Value) ?std.json.Value {
+    if (root != .object) return null;
+    if (root.object.get("error")) |err| return err;
+    if (root.object.get("response")) |resp| {
+        if (resp == .object) return resp.object.get("error");
+    }
+    // responses stream `{"type": "error", "code": ..., "message": ...}`
+    if (root.object.get("message") != null) return root;
+    return null;
+}
+
+fn kindOf(status: ?u16, codes: []const u8, message: []const u8) Kind {
+    const has = struct {
+        fn any(haystacks: []const []const u8, needles: []const []const u8) bool {
+            for (haystacks) |h| {
+                for (needles) |n| {
+                    if (std.mem.indexOf(u8, h, n) != null) return true;
+                }
+            }
+            return false;
+        }
+    }.any;
+    const all = &[_][]const u8{ codes, message };
+
+    if (has(all, &.{ "invalid_api_key", "authentication_error", "invalid x-api-key", "incorrect api key", "unauthenticated" })) return .invalid_api_key;
+    if (has(all, &.{ "insufficient_quota", "billing", "credit balance" })) return .insufficient_quota;
+    if (has(all, &.{ "content_filter", "content_policy", "content management policy" })) return .content_filter;
+    if (has(all, &.{ "context_length", "context window", "prompt is too long", "maximum context length" })) return .context_length;
+    if (has(all, &.{ "model_not_found", "does not exist", "not_found_error" })) return .model_not_found;
+    if (has(all, &.{"overloaded"})) return .overloaded;
+    if (has(all, &.{ "rate_limit", "resource_exhausted" })) return .rate_limited;
+
+    return switch (status orelse 0) {
+        401, 403 => .invalid_api_key,
+        402 => .insufficient_quota,
+        404 => .model_not_found,
+        429 => .rate_limited,
+        503, 529 => .overloaded,
+        else => .unknown,
+    };
+}
+
+test "classify provider errors" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const openai = classify(alloc, 401,
+        \\{"error": {"message": "Incorrect API key provided: sk-1234.", "type": "invalid_request_error", "code": "invalid_api_key"}}
+    );
+    try std.testing.expectEqual(Kind.invalid_api_key, openai.kind);
+    try std.testing.expectEqualStrings("Incorrect API key provided: sk-1234.", openai.message);
+
+    const anthropic = classify(alloc, null,
+        \\{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}
+    );
+    try std.testing.expectEqual(Kind.overloaded, anthropic.kind);
+
+    try std.testing.expectEqual(Kind.insufficient_quota, classify(alloc, 429,
+        \\{"error": {"message": "You exceeded your current quota", "code": "insufficient_quota"}}
+    ).kind);
+    try std.testing.expectEqual(Kind.content_filter, classify(alloc, 400,
+        \\{"error": {"message": "filtered", "code": "content_filter"}}
+    ).kind);
+
+    const raw = classify(alloc, 502, "Bad Gateway");
+    try std.testing.expectEqual(Kind.unknown, raw.kind);
+    try std.testing.expectEqualStrings("Bad Gateway", raw.message);
+    try std.testing.expectEqual(Kind.rate_limited, classify(alloc, 429, "").kind);
+}
@@ -28,10 +28,18 @@ pub const RequestPool = struct {
         generation: u32,
     };
 
+    pub const Wake = struct {
+        ptr: *anyopaque,
+        func: *const fn (*anyopaque) void,
+    };
+
     slots: [MAX_SLOTS]Slot = [_]Slot{.{}} ** MAX_SLOTS,
     allocator: std.mem.Allocator = undefined,
     io: std.Io = undefined,
     client: std.http.Client = undefined,
+    /// called from workers on headers, body data and completion, lets the
+    /// main loop sleep until there is something to tick
+    waker: ?Wake = null,
 
     pub fn init(self: *RequestPool, allocator: std.mem.Allocator, io: std.Io) !void {
         self.allocator = allocator;
@@ -90,6 +98,10 @@ pub const RequestPool = struct {
         return handle;
     }
 
+    fn notify(self: *RequestPool) void {
+        if (self.waker) |w| w.func(w.ptr);
+    }
+
     fn dupeHeaders(self: *RequestPool, headers: []const std.http.Header) ![]std.http.Header {
         if (headers.len == 0) return &.{};
         const duped = try self.allocator.alloc(std.http.Header, headers.len);
@@ -119,6 +131,7 @@ pub const RequestPool = struct {
         method: std.http.Method,
         duped_headers: []const std.http.Header,
     ) std.Io.Cancelable!void {
+        defer self.notify();
         defer self.allocator.free(duped_url);
         defer if (duped_payload) |p| self.allocator.free(p);
         defer self.freeHeaders(duped_headers);
@@ -173,6 +186,7 @@ pub const RequestPool = struct {
 
         slot.status = response.head.status;
         slot.headers_ready.store(true, .release);
+        self.notify();
         log.debug("headers received status={d}", .{@intFromEnum(response.head.status)});
 
         const reader = response.reader(&.{});
@@ -196,6 +210,7 @@ pub const RequestPool = struct {
                 error.Closed => return,
                 error.Canceled => return error.Canceled,
             };
+            self.notify();
         }
     }
 
@@ -280,6 +295,30 @@ pub const RequestPool = struct {
         return list.toOwnedSlice(allocator);
     }
 
+    /// Move the bytes buffered so far into `out` without waiting. Returns
+    /// true once the body is complete. A non-streamed response has to be
+    /// drained like this while it arrives, the worker stops once the queue
+    /// holds `BODY_BUF_SIZE` bytes.
+    pub fn drainBody(self: *RequestPool, handle: RequestHandle, allocator: std.mem.Allocator, out: *std.ArrayList(u8)) !bool {
+        const slot = &self.slots[handle.index];
+        if (slot.generation != handle.generation) return error.StaleHandle;
+        return drainSlot(self.io, slot, allocator, out);
+    }
+
+    fn drainSlot(io: std.Io, slot: *Slot, allocator: std.mem.Allocator, out: *std.ArrayList(u8)) !bool {
+        var scratch: [16 * 1024]u8 = undefined;
+        while (true) {
+            const n = slot.body.getUncancelable(io, &scratch, 0) catch |err| switch (err) {
+                error.Closed => {
+                    if (slot.err) |e| return e;
+                    return true;
+                },
+            };
+            if (n == 0) return false;
+            try out.appendSlice(allocator, scratch[0..n]);
+        }
+    }
+
     /// Release a settled slot. No-op on stale handles.
     pub fn release(self: *RequestPool, handle: RequestHandle) void {
         const slot = &self.slots[handle.index];
@@ -313,3 +352,30 @@ pub const RequestPool = struct {
         }
     }
 };
+
+test "drainBody passes a body larger than the queue" {
+    const io = std.testing.io;
+    const alloc = std.testing.allocator;
+    const slot = try alloc.create(RequestPool.Slot);
+    defer alloc.destroy(slot);
+    slot.* = .{};
+    slot.body = .init(&slot.body_buf);
+
+    var body: std.ArrayList(u8) = .empty;
+    defer body.deinit(alloc);
+
+    // 4x the queue, in pieces as the worker would put them, drained per tick
+    const total = 4 * RequestPool.BODY_BUF_SIZE;
+    var chunk: [16 * 1024]u8 = undefined;
+    var sent: usize = 0;
+    while (sent < total) : (sent += chunk.len) {
+        @memset(&chunk, @intCast((sent / chunk.len) % 251));
+        try slot.body.putAll(io, &chunk);
+        try std.testing.expect(!try RequestPool.drainSlot(io, slot, alloc, &body));
+    }
+    slot.body.close(io);
+    try std.testing.expect(try RequestPool.drainSlot(io, slot, alloc, &body));
+    try std.testing.expectEqual(total, body.items.len);
+    try std.testing.expectEqual(@as(u8, 0), body.items[0]);
+    try std.testing.expectEqual(@as(u8, 15), body.items[total - 1]);
+}
@@ -48,6 +48,7 @@ const OaiRequest = struct {
     stream: bool,
     stream_options: ?OaiStreamOptions = null,
     tools: ?[]const OaiToolDef = null,
+    tool_choice: ?[]const u8 = null,
     temperature: ?f32 = null,
     max_tokens: ?u32 = null,
     max_completion_tokens: ?u32 = null,
@@ -58,6 +59,7 @@ const OaiRequest = struct {
     frequency_penalty: ?f32 = null,
     presence_penalty: ?f32 = null,
     stop: ?[]const []const u8 = null,
+    keep_alive: ?i32 = null, // ollama
 };
 
 // -- OpenAI response types --
@@ -80,8 +82,28 @@ const OaiResponseMessage = struct {
     reasoning_content: ?[]const u8 = null,
     reasoning: ?[]const u8 = null,
     reasoning_details: ?std.json.Value = null,
+    images: ?[]const OaiImage = null,
 };
 
+// OpenRouter returns generated images next to the text as data urls.
+const OaiImage = struct {
+    type: ?[]const u8 = null,
+    image_url: ?struct {
+        url: ?[]const u8 = null,
+    } = null,
+};
+
+/// `data:<media type>;base64,<data>` as an image part, other urls are skipped.
+fn imagePart(arena: Allocator, image: OaiImage) !?adapter.ContentPart {
+    const url = (image.image_url orelse return null).url orelse return null;
+    if (!std.mem.startsWith(u8, url, "data:")) return null;
+    const sep = std.mem.indexOf(u8, url, ";base64,") orelse return null;
+    return .{ .image = .{
+        .media_type = try arena.dupe(u8, url[5..sep]),
+        .data = try arena.dupe(u8, url[sep + ";base64,".len ..]),
+    } };
+}
+
 const OaiChoice = struct {
     message: ?OaiResponseMessage = null,
     finish_reason: ?[]const u8 = null,
@@ -89,12 +111,31 @@ const OaiChoice = struct {
 
 const OaiPromptTokensDetails = struct {
     cached_tokens: ?u64 = null,
+    /// OpenRouter, prompt tokens written to a provider cache
+    cache_write_tokens: ?u64 = null,
 };
 
 const OaiUsage = struct {
     prompt_tokens: ?u64 = null,
     completion_tokens: ?u64 = null,
     prompt_tokens_details: ?OaiPromptTokensDetails = null,
+    /// LiteLLM proxies in front of Anthropic report cache writes here
+    cache_creation_input_tokens: ?u64 = null,
+
+    /// `prompt_tokens` includes cache reads and writes; normalize to
+    /// uncached-only so `input + cached + cache_creation` equals the true
+    /// prompt size across providers (matches Anthropic semantics).
+    fn tokenUsage(u: OaiUsage) adapter.TokenUsage {
+        const details = u.prompt_tokens_details orelse OaiPromptTokensDetails{};
+        const cached = details.cached_tokens orelse 0;
+        const written = details.cache_write_tokens orelse u.cache_creation_input_tokens orelse 0;
+        return .{
+            .input_tokens = (u.prompt_tokens orelse 0) -| cached -| written,
+            .output_tokens = u.completion_tokens orelse 0,
+            .cached_tokens = cached,
+            .cache_creation_tokens = written,
+        };
+    }
 };
 
 const OaiResponse = struct {
@@ -180,7 +221,8 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
         for (msg.parts) |part| {
             switch (part) {
                 .text => |t| try text_buf.appendSlice(allocator, t),
-                .image => has_images = true,
+                // generated images stay local, assistant turns take text only
+                .image => has_images = msg.role != .agent,
                 .tool_call => |tc| {
                     if (skipped_ids.contains(tc.id)) continue;
                     try tool_call_list.append(allocator, .{
@@ -285,7 +327,7 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
             try tool_defs_buf.append(allocator, .{
                 .function = .{
                     .name = tool.name,
-                    .description = tool.description,
+                    .description = chat.toolDescription(tool),
                     .parameters = parsed.value,
                 },
             });
@@ -300,6 +342,7 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
             .stream = mode == .streaming,
             .stream_options = if (mode == .streaming) .{} else null,
             .tools = tool_defs,
+            .tool_choice = if (chat.tools_disabled and tool_defs != null) "none" else null,
             .temperature = oc.temperature,
             .max_tokens = oc.max_tokens,
             .max_completion_tokens = oc.max_completion_tokens,
@@ -317,12 +360,14 @@ pub fn serializeRequest(allocator: Allocator, chat: *const adapter.Chat, config:
             .stream = mode == .streaming,
             .stream_options = if (mode == .streaming) .{} else null,
             .tools = tool_defs,
+            .tool_choice = if (chat.tools_disabled and tool_defs != null) "none" else null,
             .temperature = oc.temperature,
             .max_tokens = oc.max_tokens,
             .reasoning_effort = if (config.reasoning_effort) |e| @tagName(e) else null,
             .top_p = oc.top_p,
             .top_k = oc.top_k,
             .stop = oc.stop,
+            .keep_alive = oc.keep_alive,
         },
         .response, .anthropic => return error.NotImplemented,
     };
@@ -395,6 +440,12 @@ pub fn parseResponse(arena: Allocator, body: []const u8) !adapter.ResponseResult
                 try parts.append(arena, .{ .text = try arena.dupe(u8, content) });
             }
 
+            if (msg.images) |images| {
+                for (images) |image| {
+                    if (try imagePart(arena, image)) |part| try parts.append(arena, part);
+                }
+            }
+
             if (msg.tool_calls) |calls| {
                 for (calls) |call| {
                     const func = call.function orelse continue;
@@ -410,15 +461,7 @@ pub fn parseResponse(arena: Allocator, body: []const u8) !adapter.ResponseResult
         }
     }
 
-    if (parsed.value.usage) |u| {
-        const prompt = u.prompt_tokens orelse 0;
-        const cached = if (u.prompt_tokens_details) |d| (d.cached_tokens orelse 0) else 0;
-        usage = .{
-            .input_tokens = prompt -| cached,
-            .output_tokens = u.completion_tokens orelse 0,
-            .cached_tokens = cached,
-        };
-    }
+    if (parsed.value.usage) |u| usage = u.tokenUsage();
 
     if (parts.items.len == 0) return error.EmptyResponse;
     return .{
@@ -427,6 +470,26 @@ pub fn parseResponse(arena: Allocator, body: []const u8) !adapter.ResponseResult
     };
 }
 
+/// Ollama loads a model on an empty `/api/generate` request. The url is the
+/// native api next to the OpenAI compatible `/v1` base url.
+pub const OllamaPreload = struct {
+    url: []const u8,
+    body: []const u8,
+};
+
+pub fn ollamaPreload(alloc: Allocator, base_url: []const u8, model: []const u8, keep_alive: ?i32) !OllamaPreload {
+    var root = std.mem.trimRight(u8, base_url, "/");
+    if (std.mem.endsWith(u8, root, "/v1")) root = root[0 .. root.len - 3];
+
+    const url = try std.fmt.allocPrint(alloc, "{s}/api/generate", .{root});
+    errdefer alloc.free(url);
+    const body = try std.json.Stringify.valueAlloc(alloc, .{
+        .model = model,
+        .keep_alive = keep_alive,
+    }, .{ .emit_null_optional_fields = false });
+    return .{ .url = url, .body = body };
+}
+
 // -- Streaming parser --
 //
 // OpenAI SSE: "data: {...}\n\n" lines, terminated by "data: [DONE]". Usage
@@ -458,6 +521,7 @@ const OaiDelta = struct {
     reasoning: ?[]const u8 = null,
     reasoning_details: ?std.json.Value = null,
     tool_calls: ?[]const OaiDeltaToolCall = null,
+    images: ?[]const OaiImage = null,
 };
 
 const OaiStreamChoice = struct {
@@ -544,6 +608,7 @@ pub const StreamState = struct {
     // array on finalize and stuffed in ThinkingPart.signature for replay.
     reasoning_details_acc: std.ArrayList([]const u8) = .empty,
     tools: std.ArrayList(ToolAcc) = .empty,
+    images: std.ArrayList(adapter.ContentPart) = .empty,
     pending_text_thinking: std.ArrayList(PendingDelta) = .empty,
     pending_cursor: usize = 0,
     usage: ?adapter.TokenUsage = null,
@@ -677,12 +742,14 @@ pub const StreamState = struct {
                     if (d.content) |c| {
                         if (c.len > 0) try self.pushText(arena, c);
                     }
+                    if (d.images) |images| try self.captureImages(images);
                     if (d.tool_calls) |calls| {
                         for (calls) |tc| if (try self.pushDeltaToolCall(arena, tc)) |out| return out;
                     }
                 }
                 if (ch.message) |m| {
                     if (m.content) |c| try self.pushText(arena, c);
+                    if (m.images) |images| try self.captureImages(images);
                     if (m.tool_calls) |calls| {
                         for (calls) |rtc| {
                             const func = rtc.function orelse continue;
@@ -703,16 +770,7 @@ pub const StreamState = struct {
         }
 
         if (chunk.usage) |u| {
-            // OpenAI `prompt_tokens` includes cached tokens; normalize to
-            // uncached-only so `input + cached + cache_creation` equals true
-            // prompt size across providers (matches Anthropic semantics).
-            const prompt = u.prompt_tokens orelse 0;
-            const cached = if (u.prompt_tokens_details) |d| (d.cached_tokens orelse 0) else 0;
-            self.usage = .{
-                .input_tokens = prompt -| cached,
-                .output_tokens = u.completion_tokens orelse 0,
-                .cached_tokens = cached,
-            };
+            self.usage = u.tokenUsage();
             self.pending_usage = self.usage;
         } else if (chunk.done orelse false) {
             if (chunk.prompt_eval_count != null or chunk.eval_count != null) {
@@ -799,6 +857,13 @@ pub const StreamState = struct {
         return null;
     }
 
+    fn captureImages(self: *StreamState, images: []const OaiImage) !void {
+        // the line's parse arena is gone after applyChunk
+        for (images) |image| {
+            if (try imagePart(self.arena, image)) |part| try self.images.append(self.arena, part);
+        }
+    }
+
     fn composeReasoningDetails(self: *StreamState, arena: Allocator) !?[]const u8 {
         if (self.reasoning_details_acc.items.len == 0) return null;
         var buf: std.ArrayList(u8) = .empty;
@@ -960,6 +1025,7 @@ pub const StreamState = struct {
         }
 
         try parts.append(arena, .{ .text = try arena.dupe(u8, text_buf.items) });
+        for (self.images.items) |part| try parts.append(arena, try part.clone(arena));
         try parts.appendSlice(arena, valid_calls.items);
 
         const owned = try parts.toOwnedSlice(arena);
@@ -1001,6 +1067,32 @@ test "openai request stream mode is caller selected" {
     try testing.expect(obj.get("stream_options") == null);
 }
 
+test "openai request forbids tool calls for disabled tools" {
+    const testing = std.testing;
+    var arena_state = std.heap.ArenaAllocator.init(testing.allocator);
+    defer arena_state.deinit();
+    const arena = arena_state.allocator();
+
+    var chat: adapter.Chat = .{ .tools_disabled = true };
+    try chat.addMessage(arena, .user, &.{.{ .text = "hello" }});
+    try chat.addTool(arena, .{ .name = "read", .description = "read a file", .parameters_schema = "{}" });
+
+    const cfg: adapter.Config = .{
+        .api_key = "test",
+        .model = "model",
+        .base_url = "https://example.test/v1",
+        .provider = .{ .openai = .{} },
+    };
+
+    const payload = try serializeRequest(testing.allocator, &chat, cfg, .blocking);
+    defer testing.allocator.free(payload);
+
+    const parsed = try std.json.parseFromSlice(std.json.Value, arena, payload, .{});
+    const obj = parsed.value.object;
+    try testing.expect(obj.get("tools") != null);
+    try testing.expectEqualStrings("none", obj.get("tool_choice").?.string);
+}
+
 test "openai stream keeps tagged reasoning as thinking across schema fields" {
     const testing = std.testing;
     var arena_state = std.heap.ArenaAllocator.init(testing.allocator);
@@ -1049,6 +1141,27 @@ test "openai stream surfaces provider error payload" {
     }
 }
 
+test "openai stream keeps generated images" {
+    const testing = std.testing;
+    var arena_state = std.heap.ArenaAllocator.init(testing.allocator);
+    defer arena_state.deinit();
+    const arena = arena_state.allocator();
+
+    var stream = StreamState.init(arena);
+    const images = [_]OaiImage{
+        .{ .type = "image_url", .image_url = .{ .url = "data:image/png;base64,iVBORw0KGgo=" } },
+        .{ .type = "image_url", .image_url = .{ .url = "https://example.com/cat.png" } },
+    };
+    var choices = [_]OaiStreamChoice{.{ .delta = .{ .content = "here you go", .images = &images } }};
+    _ = try stream.applyChunk(arena, .{ .choices = &choices });
+
+    const result = try stream.finalize(arena);
+    try testing.expectEqual(@as(usize, 2), result.message.parts.len);
+    try testing.expectEqualStrings("here you go", result.message.parts[0].text);
+    try testing.expectEqualStrings("image/png", result.message.parts[1].image.media_type);
+    try testing.expectEqualStrings("iVBORw0KGgo=", result.message.parts[1].image.data);
+}
+
 test "reasoning_details round-trip: stream capture then replay in request" {
     const testing = std.testing;
     var arena_state = std.heap.ArenaAllocator.init(testing.allocator);
@@ -1147,3 +1260,12 @@ test "reasoning_details round-trip: stream capture then replay in request" {
         rd_out.array.items[1].object.get("text").?.string,
     );
 }
+
+test "ollama preload targets the native api" {
+    const alloc = std.testing.allocator;
+    const pre = try ollamaPreload(alloc, "http://localhost:11434/v1/", "qwen3", 1800);
+    defer alloc.free(pre.url);
+    defer alloc.free(pre.body);
+    try std.testing.expectEqualStrings("http://localhost:11434/api/generate", pre.url);
+    try std.testing.expectEqualStrings("{\"model\":\"qwen3\",\"keep_alive\":1800}", pre.body);
+}
@@ -35,7 +35,9 @@ fn roleName(role: adapter.Role) []const u8 {
 fn writeMessageItem(allocator: Allocator, w: *std.Io.Writer, first: *bool, msg: adapter.Message) !void {
     var has_content = false;
     for (msg.parts) |part| switch (part) {
-        .text, .image => has_content = true,
+        .text => has_content = true,
+        // generated images stay local, assistant turns take text only
+        .image => has_content = has_content or msg.role != .agent,
         else => {},
     };
     if (!has_content) return;
@@ -57,6 +59,7 @@ fn writeMessageItem(allocator: Allocator, w: *std.Io.Writer, first: *bool, msg:
             try w.writeByte('}');
         },
         .image => |image| {
+            if (msg.role == .agent) continue;
             if (!content_first) try w.writeByte(',');
             content_first = false;
             try w.writeAll("{\"type\":\"input_image\",\"image_url\":");
@@ -104,7 +107,7 @@ fn writeTools(w: *std.Io.Writer, chat: *const adapter.Chat) !void {
         try w.writeAll("{\"type\":\"function\",\"name\":");
         try writeJson(w, tool.name);
         try w.writeAll(",\"description\":");
-        try writeJson(w, tool.description);
+        try writeJson(w, chat.toolDescription(tool));
         try w.writeAll(",\"parameters\":");
         try w.writeAll(tool.parameters_schema);
         try w.writeByte('}');
@@ -153,6 +156,7 @@ fn serialize(allocator: Allocator, chat: *const adapter.Chat, config: Config, mo
         first = false;
         try w.writeAll("\"tools\":");
         try writeTools(w, chat);
+        if (chat.tools_disabled) try writeField(w, &first, "tool_choice", "none");
     }
 
     if (!is_compact) {
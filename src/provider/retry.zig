@@ -0,0 +1,69 @@
+//! When a failed provider request is tried again. Transient failures
+//! (dropped connections, rate limits, overload, 5xx) wait out an exponential
+//! backoff with jitter and go again on the same model. Once the retries are
+//! used up, or the model itself is the problem, the agent moves on to the
+//! next fallback model for the rest of its run.
+const std = @import("std");
+const errors = @import("errors.zig");
+
+pub const Policy = struct {
+    /// retries per model, 0 gives up on the first failure
+    max_retries: u32 = 3,
+    base_delay_ms: u32 = 1000,
+    max_delay_ms: u32 = 30_000,
+    /// share of the delay added or taken at random, so agents that hit the
+    /// same limit do not come back in lockstep
+    jitter: f32 = 0.25,
+
+    /// Wait before retry number `attempt`, counting from 1. `seed` picks
+    /// the jitter.
+    pub fn delayMs(self: Policy, attempt: u32, seed: u64) u32 {
+        const shift: u6 = @intCast(@min(attempt -| 1, 32));
+        const delay: f64 = @floatFromInt(@min(@as(u64, self.base_delay_ms) << shift, self.max_delay_ms));
+        if (self.jitter <= 0) return @intFromFloat(delay);
+        var prng = std.Random.DefaultPrng.init(seed);
+        const offset = (prng.random().float(f64) * 2 - 1) * delay * self.jitter;
+        return @intFromFloat(@max(0, delay + offset));
+    }
+};
+
+/// A failed response worth sending again to the same model. `status` is
+/// null for error events inside the stream.
+pub fn isTransient(status: ?u16, kind: errors.Kind) bool {
+    return switch (kind) {
+        .rate_limited, .overloaded => true,
+        .unknown => if (status) |s| s == 408 or s == 425 or s >= 500 else false,
+        else => false,
+    };
+}
+
+/// Another model might answer where this one failed. Bad keys, filtered
+/// content and oversized requests fail the same way everywhere.
+pub fn canFailover(kind: errors.Kind) bool {
+    return switch (kind) {
+        .invalid_api_key, .content_filter, .context_length => false,
+        else => true,
+    };
+}
+
+test "retry backoff and classification" {
+    const policy: Policy = .{ .jitter = 0 };
+    try std.testing.expectEqual(@as(u32, 1000), policy.delayMs(1, 0));
+    try std.testing.expectEqual(@as(u32, 4000), policy.delayMs(3, 0));
+    try std.testing.expectEqual(@as(u32, 30_000), policy.delayMs(12, 0));
+    try std.testing.expectEqual(@as(u32, 30_000), policy.delayMs(100, 0));
+
+    const jittered: Policy = .{};
+    for (0..32) |seed| {
+        const d = jittered.delayMs(2, seed);
+        try std.testing.expect(d >= 1500 and d <= 2500);
+    }
+
+    try std.testing.expect(isTransient(429, .rate_limited));
+    try std.testing.expect(isTransient(503, .unknown));
+    try std.testing.expect(isTransient(null, .overloaded));
+    try std.testing.expect(!isTransient(400, .unknown));
+    try std.testing.expect(!isTransient(404, .model_not_found));
+    try std.testing.expect(canFailover(.model_not_found));
+    try std.testing.expect(!canFailover(.invalid_api_key));
+}
@@ -7,7 +7,11 @@ pub const responses = @import("responses.zig");
 pub const anthropic = @import("anthropic.zig");
 pub const agent = @import("agent.zig");
 pub const compact = @import("compact.zig");
+pub const errors = @import("errors.zig");
+pub const retry = @import("retry.zig");
+pub const batch = @import("batch.zig");
 pub const tool = @import("tools.zig");
+pub const schema = @import("schema.zig");
 pub const config = @import("config.zig");
 pub const Swarm = @import("swarm.zig");
 pub const exec = @import("exec.zig");
@@ -0,0 +1,333 @@
+//! Tool call arguments checked against the tool's JSON schema before the
+//! tool runs. Models often quote numbers and booleans, pass one value where
+//! an array goes, or send `null` for an optional field they mean to leave
+//! out; those are coerced. Anything else comes back as a list of fields with
+//! the type they expect, so the retry gets it right the first time.
+//!
+//! Understood: `type` (a name or a list of them), `properties`, `required`,
+//! `items` and string `enum`s. Other keywords are not checked.
+const std = @import("std");
+
+const Allocator = std.mem.Allocator;
+const Value = std.json.Value;
+const Error = Allocator.Error || std.Io.Writer.Error;
+
+/// later ones mostly repeat the same mistake
+const MAX_ERRORS = 8;
+
+pub const Checked = union(enum) {
+    /// arguments to run the tool with, rewritten when something was coerced
+    ok: []const u8,
+    /// what is wrong with them, for the model
+    invalid: []const u8,
+};
+
+/// A schema that does not parse checks nothing, the arguments pass as sent.
+pub fn check(alloc: Allocator, schema: []const u8, arguments: []const u8) Checked {
+    var arena_state = std.heap.ArenaAllocator.init(alloc);
+    defer arena_state.deinit();
+    // out of memory: let the tool run and report it
+    return checkIn(arena_state.allocator(), alloc, schema, arguments) catch .{ .ok = arguments };
+}
+
+fn checkIn(arena: Allocator, alloc: Allocator, schema: []const u8, arguments: []const u8) !Checked {
+    const root = std.json.parseFromSliceLeaky(Value, arena, schema, .{}) catch return .{ .ok = arguments };
+    const raw = std.mem.trim(u8, arguments, " \t\r\n");
+    var args = std.json.parseFromSliceLeaky(Value, arena, if (raw.len == 0) "{}" else raw, .{}) catch {
+        return .{ .invalid = try std.fmt.allocPrint(alloc, "invalid arguments: not valid JSON: {s}", .{clip(raw, 200)}) };
+    };
+
+    var checker = Checker{ .arena = arena };
+    try checker.visit(root, &args, "");
+    if (checker.errors.items.len > 0) {
+        var out = std.Io.Writer.Allocating.init(alloc);
+        try out.writer.writeAll("invalid arguments, fix these and call again:");
+        for (checker.errors.items) |msg| try out.writer.print("\n- {s}", .{msg});
+        return .{ .invalid = try out.toOwnedSlice() };
+    }
+    if (!checker.coerced) return .{ .ok = arguments };
+    return .{ .ok = try std.json.Stringify.valueAlloc(alloc, args, .{}) };
+}
+
+const Checker = struct {
+    arena: Allocator,
+    errors: std.ArrayList([]const u8) = .empty,
+    coerced: bool = false,
+
+    fn fail(self: *Checker, comptime fmt: []const u8, args: anytype) !void {
+        if (self.errors.items.len == MAX_ERRORS) return;
+        try self.errors.append(self.arena, try std.fmt.allocPrint(self.arena, fmt, args));
+    }
+
+    fn visit(self: *Checker, schema: Value, value: *Value, path: []const u8) Error!void {
+        if (schema != .object) return;
+        const s = schema.object;
+        if (s.get("type")) |ty| {
+            if (!try self.matchType(ty, value)) {
+                return self.fail("`{s}` expected {s}, got {s}", .{ fieldName(path), try typeNames(self.arena, ty), try describe(self.arena, value.*) });
+            }
+        }
+        if (s.get("enum")) |allowed| {
+            if (allowed == .array) try self.checkEnum(allowed.array.items, value, path);
+        }
+        switch (value.*) {
+            .object => |*obj| try self.visitObject(s, obj, path),
+            .array => |arr| if (s.get("items")) |items| {
+                for (arr.items, 0..) |*item, i| {
+                    try self.visit(items, item, try std.fmt.allocPrint(self.arena, "{s}[{d}]", .{ path, i }));
+                }
+            },
+            else => {},
+        }
+    }
+
+    fn visitObject(self: *Checker, s: std.json.ObjectMap, obj: *std.json.ObjectMap, path: []const u8) !void {
+        const props: ?std.json.ObjectMap = if (s.get("properties")) |p| (if (p == .object) p.object else null) else null;
+        if (props) |ps| {
+            var it = ps.iterator();
+            while (it.next()) |entry| {
+                const field = entry.key_ptr.*;
+                const v = obj.getPtr(field) orelse continue;
+                // null is how most models say "not given"
+                if (v.* == .null and !allowsNull(entry.value_ptr.*)) {
+                    _ = obj.orderedRemove(field);
+                    self.coerced = true;
+                    continue;
+                }
+                try self.visit(entry.value_ptr.*, v, try joinPath(self.arena, path, field));
+            }
+        }
+
+        const required = s.get("required") orelse return;
+        if (required != .array) return;
+        for (required.array.items) |req| {
+            if (req != .string or obj.contains(req.string)) continue;
+            const field_path = try joinPath(self.arena, path, req.string);
+            const field_ty = if (props) |ps| (if (ps.get(req.string)) |p| (if (p == .object) p.object.get("type") else null) else null) else null;
+            if (field_ty) |ty| {
+                try self.fail("`{s}` is required ({s})", .{ field_path, try typeNames(self.arena, ty) });
+            } else {
+                try self.fail("`{s}` is required", .{field_path});
+            }
+        }
+    }
+
+    fn matchType(self: *Checker, ty: Value, value: *Value) !bool {
+        switch (ty) {
+            .string => |t| return self.coerce(t, value),
+            .array => |types| {
+                for (types.items) |t| {
+                    if (t == .string and isType(t.string, value.*)) return true;
+                }
+                for (types.items) |t| {
+                    if (t == .string and try self.coerce(t.string, value)) return true;
+                }
+                return false;
+            },
+            else => return true,
+        }
+    }
+
+    fn coerce(self: *Checker, t: []const u8, value: *Value) !bool {
+        if (isType(t, value.*)) return true;
+        const converted = try convert(self.arena, t, value.*) orelse return false;
+        value.* = converted;
+        self.coerced = true;
+        return true;
+    }
+
+    /// String enums, a value differing only in case becomes the listed one.
+    fn checkEnum(self: *Checker, allowed: []const Value, value: *Value, path: []const u8) !void {
+        if (value.* != .string) return;
+        for (allowed) |a| {
+            if (a == .string and std.mem.eql(u8, a.string, value.string)) return;
+        }
+        for (allowed) |a| {
+            if (a == .string and std.ascii.eqlIgnoreCase(a.string, value.string)) {
+                value.* = a;
+                self.coerced = true;
+                return;
+            }
+        }
+        var names = std.Io.Writer.Allocating.init(self.arena);
+        for (allowed, 0..) |a, i| {
+            if (i > 0) try names.writer.writeAll(", ");
+            try std.json.Stringify.value(a, .{}, &names.writer);
+        }
+        try self.fail("`{s}` must be one of {s}, got \"{s}\"", .{ fieldName(path), names.written(), clip(value.string, 40) });
+    }
+};
+
+fn isType(t: []const u8, value: Value) bool {
+    if (std.mem.eql(u8, t, "string")) return value == .string;
+    if (std.mem.eql(u8, t, "integer")) return value == .integer or (value == .number_string and std.mem.indexOfAny(u8, value.number_string, ".eE") == null);
+    if (std.mem.eql(u8, t, "number")) return value == .integer or value == .float or value == .number_string;
+    if (std.mem.eql(u8, t, "boolean")) return value == .bool;
+    if (std.mem.eql(u8, t, "array")) return value == .array;
+    if (std.mem.eql(u8, t, "object")) return value == .object;
+    if (std.mem.eql(u8, t, "null")) return value == .null;
+    return true;
+}
+
+/// `value` as type `t` when it holds one in another shape: "42" for 42,
+/// "true" for true, 3 for "3", a lone value for a one item array, JSON
+/// sent as a string.
+fn convert(arena: Allocator, t: []const u8, value: Value) !?Value {
+    if (std.mem.eql(u8, t, "integer")) {
+        switch (value) {
+            .string => |s| {
+                const n = std.fmt.parseInt(i64, std.mem.trim(u8, s, " "), 10) catch return null;
+                return .{ .integer = n };
+            },
+            .float => |f| {
+                if (@floor(f) != f or @abs(f) > 1 << 53) return null;
+                return .{ .integer = @intFromFloat(f) };
+            },
+            else => return null,
+        }
+    }
+    if (std.mem.eql(u8, t, "number")) {
+        if (value != .string) return null;
+        const s = std.mem.trim(u8, value.string, " ");
+        if (std.fmt.parseInt(i64, s, 10)) |n| return .{ .integer = n } else |_| {}
+        const f = std.fmt.parseFloat(f64, s) catch return null;
+        return if (std.math.isFinite(f)) .{ .float = f } else null;
+    }
+    if (std.mem.eql(u8, t, "boolean")) {
+        if (value != .string) return null;
+        const s = std.mem.trim(u8, value.string, " ");
+        if (std.ascii.eqlIgnoreCase(s, "true")) return .{ .bool = true };
+        if (std.ascii.eqlIgnoreCase(s, "false")) return .{ .bool = false };
+        return null;
+    }
+    if (std.mem.eql(u8, t, "string")) {
+        return switch (value) {
+            .integer => |n| .{ .string = try std.fmt.allocPrint(arena, "{d}", .{n}) },
+            .float => |f| .{ .string = try std.fmt.allocPrint(arena, "{d}", .{f}) },
+            .number_string => |s| .{ .string = s },
+            .bool => |b| .{ .string = if (b) "true" else "false" },
+            else => null,
+        };
+    }
+    if (std.mem.eql(u8, t, "array")) {
+        if (parsedString(arena, value, '[')) |parsed| return parsed;
+        if (value == .null or value == .object) return null;
+        const items = try arena.alloc(Value, 1);
+        items[0] = value;
+        return .{ .array = std.json.Array.fromOwnedSlice(arena, items) };
+    }
+    if (std.mem.eql(u8, t, "object")) return parsedString(arena, value, '{');
+    return null;
+}
+
+/// JSON inside a string value, when it starts with `open`.
+fn parsedString(arena: Allocator, value: Value, open: u8) ?Value {
+    if (value != .string) return null;
+    const s = std.mem.trim(u8, value.string, " \t\r\n");
+    if (s.len == 0 or s[0] != open) return null;
+    const parsed = std.json.parseFromSliceLeaky(Value, arena, s, .{}) catch return null;
+    const want: std.meta.Tag(Value) = if (open == '[') .array else .object;
+    return if (parsed == want) parsed else null;
+}
+
+fn allowsNull(schema: Value) bool {
+    if (schema != .object) return false;
+    const ty = schema.object.get("type") orelse return false;
+    return switch (ty) {
+        .string => |t| std.mem.eql(u8, t, "null"),
+        .array => |types| for (types.items) |t| {
+            if (t == .string and std.mem.eql(u8, t.string, "null")) break true;
+        } else false,
+        else => false,
+    };
+}
+
+fn typeNames(arena: Allocator, ty: Value) ![]const u8 {
+    switch (ty) {
+        .string => |t| return t,
+        .array => |types| {
+            var out = std.Io.Writer.Allocating.init(arena);
+            for (types.items, 0..) |t, i| {
+                if (t != .string) continue;
+                if (i > 0) try out.writer.writeAll(" or ");
+                try out.writer.writeAll(t.string);
+            }
+            return out.written();
+        },
+        else => return "a valid value",
+    }
+}
+
+fn describe(arena: Allocator, value: Value) ![]const u8 {
+    return switch (value) {
+        .null => "null",
+        .bool => |b| if (b) "boolean true" else "boolean false",
+        .integer => |n| std.fmt.allocPrint(arena, "number {d}", .{n}),
+        .float => |f| std.fmt.allocPrint(arena, "number {d}", .{f}),
+        .number_string => |s| std.fmt.allocPrint(arena, "number {s}", .{s}),
+        .string => |s| std.fmt.allocPrint(arena, "string \"{s}\"", .{clip(s, 40)}),
+        .array => "array",
+        .object => "object",
+    };
+}
+
+fn fieldName(path: []const u8) []const u8 {
+    return if (path.len == 0) "arguments" else path;
+}
+
+fn joinPath(arena: Allocator, path: []const u8, field: []const u8) ![]const u8 {
+    if (path.len == 0) return field;
+    return std.fmt.allocPrint(arena, "{s}.{s}", .{ path, field });
+}
+
+fn clip(s: []const u8, max: usize) []const u8 {
+    if (s.len <= max) return s;
+    var end = max;
+    while (end > 0 and (s[end] & 0xC0) == 0x80) end -= 1;
+    return s[0..end];
+}
+
+test "tool arguments are coerced or refused with the field" {
+    const alloc = std.testing.allocator;
+    const schema =
+        \\{"type": "object", "properties": {
+        \\  "path": {"type": "string"},
+        \\  "offset": {"type": "integer"},
+        \\  "recursive": {"type": "boolean"},
+        \\  "globs": {"type": "array", "items": {"type": "string"}},
+        \\  "mode": {"type": "string", "enum": ["read", "write"]},
+        \\  "limit": {"type": ["integer", "null"]}
+        \\}, "required": ["path"]}
+    ;
+
+    const same = check(alloc, schema, "{\"path\": \"a.zig\", \"offset\": 3}");
+    try std.testing.expectEqualStrings("{\"path\": \"a.zig\", \"offset\": 3}", same.ok);
+
+    const coerced = check(alloc, schema,
+        \\{"path": 7, "offset": "12", "recursive": "True", "globs": "*.zig", "mode": "READ", "limit": null, "extra": null}
+    );
+    defer alloc.free(coerced.ok);
+    try std.testing.expectEqualStrings(
+        \\{"path":"7","offset":12,"recursive":true,"globs":["*.zig"],"mode":"read","limit":null,"extra":null}
+    , coerced.ok);
+
+    const dropped = check(alloc, schema, "{\"path\": \"a\", \"offset\": null, \"globs\": \"[\\\"x\\\", \\\"y\\\"]\"}");
+    defer alloc.free(dropped.ok);
+    try std.testing.expectEqualStrings("{\"path\":\"a\",\"globs\":[\"x\",\"y\"]}", dropped.ok);
+
+    const invalid = check(alloc, schema, "{\"offset\": \"ten\", \"mode\": \"append\", \"globs\": [1, {}]}");
+    defer alloc.free(invalid.invalid);
+    try std.testing.expectEqualStrings(
+        \\invalid arguments, fix these and call again:
+        \\- `offset` expected integer, got string "ten"
+        \\- `globs[1]` expected string, got object
+        \\- `mode` must be one of "read", "write", got "append"
+        \\- `path` is required (string)
+    , invalid.invalid);
+
+    const broken = check(alloc, schema, "{\"path\": ");
+    defer alloc.free(broken.invalid);
+    try std.testing.expect(std.mem.startsWith(u8, broken.invalid, "invalid arguments: not valid JSON"));
+
+    try std.testing.expectEqualStrings("anything", check(alloc, "not a schema", "anything").ok);
+}
@@ -19,6 +19,7 @@ pub const PermissionPayload = union(enum) {
     diff: ToolDiff,
     ask: AskPayload,
     plan: PlanApprovalPayload,
+    commit: CommitPayload,
 };
 
 pub const PermissionReq = struct {
@@ -36,6 +37,18 @@ pub const PermissionState = union(enum) {
     denied,
     choice: u8,
     message: []const u8,
+    /// a diff approved in part, the content to write instead of `after`
+    edited: []const u8,
+};
+
+/// What the app makes of a prompt before it goes out.
+pub const PreRun = union(enum) {
+    /// still deciding, asked again next tick
+    wait,
+    /// send it, with a note to attach when not empty
+    go: []const u8,
+    /// drop the prompt and fail the run
+    veto: []const u8,
 };
 
 ///! the swarm vtable and hooks
@@ -52,6 +65,11 @@ pub const SwarmContextV = struct {
     gen_system_reminders: *const fn (*anyopaque, *Agent) void,
     pop_queued_message: *const fn (*anyopaque, AgentId, std.mem.Allocator) ?[]const apt.ContentPart,
 
+    /// wraps every tool call when set, runs on the tool's thread
+    run_tool: ?*const fn (*anyopaque, r.tool.ToolFn, r.tool.ToolContext, apt.ToolCall) apt.ToolResult = null,
+    /// asked before every new prompt goes out, polled on the tick thread
+    pre_run: ?*const fn (*anyopaque, *Agent) PreRun = null,
+
     pub fn cast(self: SwarmContextV, comptime T: type) *T {
         return @ptrCast(@alignCast(self.ptr));
     }
@@ -69,12 +87,40 @@ last_run_timestamp: ?i64 = null,
 token_stats: apt.TokenUsage = .{},
 /// Lifetime per-model totals. Survives reset(); freed in deinit.
 model_stats: std.StringArrayHashMapUnmanaged(apt.TokenUsage) = .{},
+/// Every response's usage, same lifetime as `model_stats`.
+usage_log: std.ArrayList(UsageRecord) = .empty,
+/// interned tool names for `usage_log`
+tool_names: std.StringArrayHashMapUnmanaged(void) = .{},
+/// user prompt count, set by the app to tag usage records
+turn: u32 = 0,
 
 // ----------------------------------
 pub const ToolDiff = struct {
     path: []const u8,
     before: ?[]const u8,
     after: []const u8,
+
+    /// Shrinking an existing file by at least this much looks like an
+    /// accidentally truncated write.
+    pub const TRUNCATION_WARN_PCT = 50;
+    const TRUNCATION_MIN_BYTES = 256;
+
+    /// Percentage the content shrinks by, if it is large enough to warn.
+    pub fn truncationWarning(self: ToolDiff) ?u8 {
+        const before = self.before orelse return null;
+        if (before.len < TRUNCATION_MIN_BYTES or self.after.len >= before.len) return null;
+        const pct: u8 = @intCast((before.len - self.after.len) * 100 / before.len);
+        return if (pct >= TRUNCATION_WARN_PCT) pct else null;
+    }
+};
+
+/// Usage of one provider response. Names are interned in the swarm.
+pub const UsageRecord = struct {
+    turn: u32,
+    model: []const u8,
+    usage: apt.TokenUsage,
+    /// tools the response called
+    tools: []const []const u8,
 };
 
 pub const AgentId = packed struct {
@@ -113,6 +159,7 @@ pub fn reserveFreeSlot(self: *Self) ?AgentId {
     for (&self.slots, 0..) |*slot, i| {
         if (slot.state.cmpxchgStrong(.free, .reserved, .acq_rel, .monotonic) == null) {
             slot.generation +%= 1;
+            slot.event = .unset;
             return .{ .index = @intCast(i), .generation = slot.generation };
         }
     }
@@ -127,7 +174,8 @@ pub fn releaseReservation(self: *Self, id: AgentId) void {
     if (id.index >= MAX_AGENTS) return;
     const slot = &self.slots[id.index];
     if (slot.generation != id.generation) return;
-    _ = slot.state.cmpxchgStrong(.reserved, .free, .acq_rel, .monotonic);
+    // wake a task tool waiting on a spawn that never happened
+    if (slot.state.cmpxchgStrong(.reserved, .free, .acq_rel, .monotonic) == null) slot.event.set(self.pool.io);
 }
 
 pub const BroadcastEntry = struct {
@@ -135,6 +183,8 @@ pub const BroadcastEntry = struct {
     role: apt.Role,
     parts: []const apt.ContentPart,
     plain_text: bool = false,
+    /// provider error broadcasts only, null for errors inside the stream
+    http_status: ?u16 = null,
 };
 
 pub const ToolCallPayload = struct {
@@ -153,6 +203,13 @@ pub const PlanApprovalPayload = struct {
     plan_text: []const u8,
 };
 
+pub const CommitPayload = struct {
+    message: []const u8,
+    files: []const []const u8,
+    /// `git diff --cached --stat --patch` of the files
+    diff: []const u8,
+};
+
 pub fn init(
     self: *Self,
     alloc: std.mem.Allocator,
@@ -194,10 +251,15 @@ pub fn deinit(self: *Self) void {
     var it = self.model_stats.iterator();
     while (it.next()) |entry| self.gpa.free(entry.key_ptr.*);
     self.model_stats.deinit(self.gpa);
+    for (self.usage_log.items) |record| self.gpa.free(record.tools);
+    self.usage_log.deinit(self.gpa);
+    for (self.tool_names.keys()) |name| self.gpa.free(name);
+    self.tool_names.deinit(self.gpa);
 }
 
-/// Accumulate usage globally and under the given model name.
-pub fn recordUsage(self: *Self, model: []const u8, u: apt.TokenUsage) void {
+/// Accumulate usage globally and under the given model name, and log it
+/// with the tools `parts` called.
+pub fn recordUsage(self: *Self, model: []const u8, u: apt.TokenUsage, parts: []const apt.ContentPart) void {
     self.token_stats.add(u);
     const gop = self.model_stats.getOrPut(self.gpa, model) catch return;
     if (!gop.found_existing) {
@@ -208,6 +270,34 @@ pub fn recordUsage(self: *Self, model: []const u8, u: apt.TokenUsage) void {
         gop.value_ptr.* = .{};
     }
     gop.value_ptr.add(u);
+    self.logUsage(gop.key_ptr.*, u, parts) catch {};
+}
+
+fn logUsage(self: *Self, model: []const u8, u: apt.TokenUsage, parts: []const apt.ContentPart) !void {
+    var calls: usize = 0;
+    for (parts) |part| calls += @intFromBool(part == .tool_call);
+    const tools = try self.gpa.alloc([]const u8, calls);
+    errdefer self.gpa.free(tools);
+    var i: usize = 0;
+    for (parts) |part| switch (part) {
+        .tool_call => |call| {
+            tools[i] = try self.internToolName(call.name);
+            i += 1;
+        },
+        else => {},
+    };
+    try self.usage_log.append(self.gpa, .{ .turn = self.turn, .model = model, .usage = u, .tools = tools });
+}
+
+fn internToolName(self: *Self, name: []const u8) ![]const u8 {
+    const gop = try self.tool_names.getOrPut(self.gpa, name);
+    if (!gop.found_existing) {
+        gop.key_ptr.* = self.gpa.dupe(u8, name) catch |err| {
+            _ = self.tool_names.pop();
+            return err;
+        };
+    }
+    return gop.key_ptr.*;
 }
 
 pub fn usage(self: *const Self) apt.TokenUsage {
@@ -500,6 +590,19 @@ pub fn countActive(self: *const Self) u32 {
     return count;
 }
 
+/// True when every active agent only waits on the network or a timer, so
+/// the UI can poll slower and rely on the pool's waker for stream data.
+pub fn allAwaitingNetwork(self: *const Self) bool {
+    for (&self.slots) |*s| {
+        if (s.state.load(.acquire) != .active) continue;
+        switch (s.agent.state) {
+            .waiting_response, .streaming_response, .retry_timeout, .awaiting_pool_slot => {},
+            else => return false,
+        }
+    }
+    return true;
+}
+
 pub fn getSlot(self: *Self, id: AgentId) ?*AgentSlot {
     if (id.index >= MAX_AGENTS) return null;
     const slot = &self.slots[id.index];
@@ -518,12 +621,13 @@ pub fn recordBroadcast(self: *Self, agent_id: AgentId, role: apt.Role, parts: []
     });
 }
 
-pub fn recordProviderError(self: *Self, agent_id: AgentId, parts: []const apt.ContentPart) void {
+pub fn recordProviderError(self: *Self, agent_id: AgentId, http_status: ?u16, parts: []const apt.ContentPart) void {
     self.context.broadcast(self.context.ptr, .{
         .agent_id = agent_id,
         .role = .agent,
         .parts = parts,
         .plain_text = true,
+        .http_status = http_status,
     });
 }
 
@@ -549,3 +653,11 @@ test "failed reservation is returned to the free pool" {
     swarm.releaseReservation(id);
     try std.testing.expectEqual(SlotState.reserved, swarm.slots[reused.index].state.load(.acquire));
 }
+
+test "ToolDiff.truncationWarning" {
+    const big = "x" ** 1000;
+    try std.testing.expectEqual(@as(?u8, 80), (ToolDiff{ .path = "a", .before = big, .after = big[0..200] }).truncationWarning());
+    try std.testing.expectEqual(@as(?u8, null), (ToolDiff{ .path = "a", .before = big, .after = big[0..900] }).truncationWarning());
+    try std.testing.expectEqual(@as(?u8, null), (ToolDiff{ .path = "a", .before = null, .after = "" }).truncationWarning());
+    try std.testing.expectEqual(@as(?u8, null), (ToolDiff{ .path = "a", .before = "short", .after = "" }).truncationWarning());
+}
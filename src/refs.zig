@@ -0,0 +1,121 @@
+//! Code references in chat prose, `src/agent.rs:142` or `foo::bar()`. The
+//! C-l popup lists the ones from the last answer and opens their definition.
+const std = @import("std");
+
+pub const MAX_REFS = 64;
+
+pub const Ref = struct {
+    /// as written in the message
+    text: []const u8,
+    kind: Kind,
+    pinned: bool = false,
+
+    pub const Kind = union(enum) {
+        location: Location,
+        /// last path segment of the symbol, `bar` for `foo::bar()`
+        symbol: []const u8,
+    };
+};
+
+pub const Location = struct {
+    path: []const u8,
+    line: u32 = 1,
+};
+
+const source_exts = [_][]const u8{ "zig", "rs", "py", "ts", "tsx", "js", "jsx", "go", "c", "h", "cc", "cpp", "hpp", "java", "kt", "rb", "lua", "md", "toml", "json", "yaml", "yml", "sh" };
+
+/// Appends the references of `text` to `out`, skipping duplicates.
+pub fn extract(alloc: std.mem.Allocator, text: []const u8, out: *std.ArrayList(Ref)) !void {
+    var pos: usize = 0;
+    while (std.mem.indexOfScalarPos(u8, text, pos, '`')) |open| {
+        const close = std.mem.indexOfScalarPos(u8, text, open + 1, '`') orelse break;
+        pos = close + 1;
+        const inner = std.mem.trim(u8, text[open + 1 .. close], " ");
+        // fences and multi line spans are code, not references
+        if (inner.len == 0 or std.mem.indexOfScalar(u8, inner, '\n') != null) continue;
+        if (classify(inner)) |kind| try append(alloc, out, .{ .text = inner, .kind = kind });
+    }
+
+    var it = std.mem.tokenizeAny(u8, text, " \t\r\n,;()[]`'\"");
+    while (it.next()) |token| {
+        const trimmed = std.mem.trimRight(u8, token, ".:");
+        const loc = parseLocation(trimmed) orelse continue;
+        try append(alloc, out, .{ .text = trimmed, .kind = .{ .location = loc } });
+    }
+}
+
+fn append(alloc: std.mem.Allocator, out: *std.ArrayList(Ref), ref: Ref) !void {
+    if (out.items.len >= MAX_REFS) return;
+    for (out.items) |existing| {
+        if (std.mem.eql(u8, existing.text, ref.text)) return;
+    }
+    try out.append(alloc, ref);
+}
+
+fn classify(inner: []const u8) ?Ref.Kind {
+    if (parseLocation(inner)) |loc| return .{ .location = loc };
+    if (isSourcePath(inner)) return .{ .location = .{ .path = inner } };
+    if (symbolName(inner)) |name| return .{ .symbol = name };
+    return null;
+}
+
+/// `path:line` or `path:line:col`, the path needs a `/` or a file extension.
+pub fn parseLocation(token: []const u8) ?Location {
+    const colon = std.mem.indexOfScalar(u8, token, ':') orelse return null;
+    const path = token[0..colon];
+    if (path.len == 0 or std.mem.indexOfScalar(u8, path, ' ') != null) return null;
+    if (std.mem.indexOfScalar(u8, path, '/') == null and std.mem.indexOfScalar(u8, path, '.') == null) return null;
+    var end = colon + 1;
+    while (end < token.len and std.ascii.isDigit(token[end])) end += 1;
+    const line = std.fmt.parseInt(u32, token[colon + 1 .. end], 10) catch return null;
+    if (end < token.len and token[end] != ':') return null;
+    return .{ .path = path, .line = @max(line, 1) };
+}
+
+fn isSourcePath(token: []const u8) bool {
+    if (std.mem.indexOfAny(u8, token, " :") != null) return false;
+    if (std.mem.indexOfScalar(u8, token, '/') != null) return true;
+    const dot = std.mem.lastIndexOfScalar(u8, token, '.') orelse return false;
+    for (source_exts) |ext| {
+        if (std.mem.eql(u8, token[dot + 1 ..], ext)) return true;
+    }
+    return false;
+}
+
+/// `foo::bar()`, `App.tick`, `parseHit(row)` -> the last segment.
+pub fn symbolName(token: []const u8) ?[]const u8 {
+    const head = token[0 .. std.mem.indexOfScalar(u8, token, '(') orelse token.len];
+    if (head.len == 0 or head.len > 80) return null;
+    for (head) |c| {
+        if (!std.ascii.isAlphanumeric(c) and c != '_' and c != ':' and c != '.') return null;
+    }
+    var start: usize = 0;
+    for (head, 0..) |c, i| {
+        if (c == ':' or c == '.') start = i + 1;
+    }
+    const name = head[start..];
+    if (name.len < 2 or !(std.ascii.isAlphabetic(name[0]) or name[0] == '_')) return null;
+    return name;
+}
+
+test "extract references" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var refs: std.ArrayList(Ref) = .empty;
+    try extract(alloc,
+        \\The loop lives in `Agent::tick()` (src/agent.rs:142), see also `src/app.zig:88:5`
+        \\and `App.render`. Config is in `blitz.lua`, run `cargo test` or visit https://x.io.
+    , &refs);
+
+    try std.testing.expectEqual(@as(usize, 5), refs.items.len);
+    try std.testing.expectEqualStrings("tick", refs.items[0].kind.symbol);
+    try std.testing.expectEqualStrings("src/app.zig", refs.items[1].kind.location.path);
+    try std.testing.expectEqual(@as(u32, 88), refs.items[1].kind.location.line);
+    try std.testing.expectEqualStrings("render", refs.items[2].kind.symbol);
+    try std.testing.expectEqualStrings("blitz.lua", refs.items[3].kind.location.path);
+    // `cargo test` has a space, not a symbol
+    try std.testing.expectEqualStrings("src/agent.rs:142", refs.items[4].text);
+    try std.testing.expectEqual(@as(u32, 142), refs.items[4].kind.location.line);
+}
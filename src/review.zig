@@ -0,0 +1,177 @@
+//! Review annotations on a pending diff. While an edit waits for permission,
+//! `n`/`p` pick a hunk and `c` attaches a short comment to it. Declining then
+//! sends the collected comments back as structured feedback, so the agent
+//! can revise exactly the parts that were flagged. `x` rejects the selected
+//! hunk, approving then writes only the hunks that are left.
+const std = @import("std");
+const tui = @import("tui/root.zig");
+
+pub const MAX_COMMENTS = 32;
+/// hunks past this one can only be approved or declined with the rest
+pub const MAX_HUNKS = 256;
+
+pub const Comment = struct {
+    hunk: usize,
+    /// first line number of the hunk, if known
+    line: ?u32,
+    text: []const u8,
+};
+
+pub const Review = struct {
+    hunk: usize = 0,
+    comments: std.ArrayList(Comment) = .empty,
+    rejected: std.StaticBitSet(MAX_HUNKS) = .initEmpty(),
+
+    pub fn add(self: *Review, alloc: std.mem.Allocator, lines: []const tui.DiffLine, text: []const u8) !bool {
+        const trimmed = std.mem.trim(u8, text, " \t");
+        if (trimmed.len == 0 or self.comments.items.len >= MAX_COMMENTS) return false;
+        try self.comments.append(alloc, .{
+            .hunk = self.hunk,
+            .line = hunkLine(lines, self.hunk),
+            .text = try alloc.dupe(u8, trimmed),
+        });
+        return true;
+    }
+
+    pub fn moveHunk(self: *Review, lines: []const tui.DiffLine, delta: i32) void {
+        const last = hunkCount(lines) -| 1;
+        if (delta < 0) self.hunk -|= @intCast(-delta) else self.hunk = @min(self.hunk + @as(usize, @intCast(delta)), last);
+    }
+
+    /// Flip the selected hunk between accepted and rejected.
+    pub fn toggleReject(self: *Review) void {
+        if (self.hunk < MAX_HUNKS) self.rejected.toggle(self.hunk);
+    }
+
+    pub fn isRejected(self: *const Review, hunk: usize) bool {
+        return hunk < MAX_HUNKS and self.rejected.isSet(hunk);
+    }
+
+    pub fn rejectedCount(self: *const Review) usize {
+        return self.rejected.count();
+    }
+
+    /// Comments live in the session arena, dropping them is enough.
+    pub fn clear(self: *Review) void {
+        self.* = .{};
+    }
+};
+
+/// Hunks are the runs of lines between `header` separators.
+pub fn hunkCount(lines: []const tui.DiffLine) usize {
+    var count: usize = 1;
+    for (lines) |l| {
+        if (l.kind == .header) count += 1;
+    }
+    return count;
+}
+
+fn hunkLine(lines: []const tui.DiffLine, hunk: usize) ?u32 {
+    var current: usize = 0;
+    for (lines) |l| {
+        if (l.kind == .header) {
+            current += 1;
+            continue;
+        }
+        if (current == hunk) {
+            if (l.line_number) |n| return n;
+        }
+    }
+    return null;
+}
+
+/// Diff lines with a marker above the selected hunk and comments below
+/// the hunk they belong to.
+pub fn annotate(alloc: std.mem.Allocator, lines: []const tui.DiffLine, review: *const Review) ![]const tui.DiffLine {
+    const count = hunkCount(lines);
+    var out: std.ArrayList(tui.DiffLine) = .empty;
+    var hunk: usize = 0;
+    var start = true;
+    for (lines) |l| {
+        if (l.kind == .header) {
+            try appendComments(alloc, &out, review, hunk);
+            try out.append(alloc, l);
+            hunk += 1;
+            start = true;
+            continue;
+        }
+        if (start and hunk == review.hunk) {
+            try out.append(alloc, .{ .kind = .header, .content = try std.fmt.allocPrint(alloc, "▶ hunk {d}/{d}{s} · n/p select · x reject · c comment", .{
+                hunk + 1,
+                count,
+                if (review.isRejected(hunk)) " rejected" else "",
+            }) });
+        } else if (start and review.isRejected(hunk)) {
+            try out.append(alloc, .{ .kind = .header, .content = try std.fmt.allocPrint(alloc, "✗ hunk {d}/{d} rejected", .{ hunk + 1, count }) });
+        }
+        start = false;
+        try out.append(alloc, l);
+    }
+    try appendComments(alloc, &out, review, hunk);
+    return out.items;
+}
+
+fn appendComments(alloc: std.mem.Allocator, out: *std.ArrayList(tui.DiffLine), review: *const Review, hunk: usize) !void {
+    for (review.comments.items) |c| {
+        if (c.hunk != hunk) continue;
+        try out.append(alloc, .{ .kind = .header, .content = try std.fmt.allocPrint(alloc, "» {s}", .{c.text}) });
+    }
+}
+
+/// Feedback message for a declined diff, `note` is the optional free text.
+pub fn compose(alloc: std.mem.Allocator, path: []const u8, review: *const Review, note: []const u8) ![]const u8 {
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    const w = &out.writer;
+    try w.print("Review comments on {s}:\n", .{path});
+    for (review.comments.items) |c| {
+        if (c.line) |n| {
+            try w.print("- hunk {d} (line {d}): {s}\n", .{ c.hunk + 1, n, c.text });
+        } else {
+            try w.print("- hunk {d}: {s}\n", .{ c.hunk + 1, c.text });
+        }
+    }
+    if (note.len > 0) try w.print("\n{s}\n", .{note});
+    try w.writeAll("Revise the change to address each comment.");
+    return out.written();
+}
+
+test "review comments compose into feedback" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const lines = [_]tui.DiffLine{
+        .{ .kind = .deletion, .line_number = 3, .content = "old" },
+        .{ .kind = .addition, .content = "new" },
+        .{ .kind = .header, .content = "..." },
+        .{ .kind = .context, .line_number = 40, .content = "fn main() {}" },
+    };
+    var review: Review = .{};
+    try std.testing.expect(try review.add(alloc, &lines, "rename this"));
+    review.moveHunk(&lines, 5);
+    try std.testing.expectEqual(@as(usize, 1), review.hunk);
+    try std.testing.expect(try review.add(alloc, &lines, " don't touch "));
+    try std.testing.expect(!try review.add(alloc, &lines, "  "));
+
+    const annotated = try annotate(alloc, &lines, &review);
+    try std.testing.expectEqual(@as(usize, 7), annotated.len);
+    try std.testing.expectEqualStrings("» rename this", annotated[2].content);
+
+    review.toggleReject();
+    try std.testing.expect(review.isRejected(1));
+    try std.testing.expectEqual(@as(usize, 1), review.rejectedCount());
+    review.moveHunk(&lines, -1);
+    const marked = try annotate(alloc, &lines, &review);
+    try std.testing.expectEqualStrings("✗ hunk 2/2 rejected", marked[5].content);
+    review.toggleReject();
+    review.toggleReject();
+    try std.testing.expectEqual(@as(usize, 1), review.rejectedCount());
+
+    const msg = try compose(alloc, "src/main.zig", &review, "");
+    try std.testing.expectEqualStrings(
+        \\Review comments on src/main.zig:
+        \\- hunk 1 (line 3): rename this
+        \\- hunk 2 (line 40): don't touch
+        \\Revise the change to address each comment.
+    , msg);
+}
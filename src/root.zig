@@ -4,15 +4,46 @@ pub const app = @import("app.zig");
 pub const prv = @import("provider");
 pub const ContextFactory = @import("context_factory.zig");
 pub const session = @import("session.zig");
+pub const sessions = @import("sessions.zig");
+pub const backup = @import("backup.zig");
+pub const checkpoint = @import("checkpoint.zig");
+pub const cost = @import("cost.zig");
+pub const spend = @import("spend.zig");
+pub const images = @import("images.zig");
+pub const links = @import("links.zig");
+pub const media = @import("media.zig");
+pub const transcript = @import("transcript.zig");
 pub const util = @import("util.zig");
 pub const keys = @import("keys.zig");
 pub const events = @import("events.zig");
 pub const lua = @import("lua.zig");
 pub const mcp = @import("mcp.zig");
 pub const lsp = @import("lsp.zig");
+pub const plugins = @import("plugins.zig");
 pub const tools = @import("tools/root.zig");
 pub const tui = @import("tui/root.zig");
 pub const cmd = @import("commands.zig");
 pub const c = @import("c");
 pub const inject = @import("inject.zig");
 pub const dash = @import("dashboard.zig");
+pub const suggest = @import("suggest.zig");
+pub const sensitive = @import("sensitive.zig");
+pub const sandbox = @import("sandbox.zig");
+pub const vault = @import("vault.zig");
+pub const memory = @import("memory.zig");
+pub const allowlist = @import("allowlist.zig");
+pub const template = @import("template.zig");
+pub const review = @import("review.zig");
+pub const import = @import("import.zig");
+pub const pack = @import("pack.zig");
+pub const profile = @import("profile.zig");
+pub const shellhist = @import("shellhist.zig");
+pub const testcmd = @import("testcmd.zig");
+pub const vars = @import("vars.zig");
+pub const headless = @import("headless.zig");
+pub const bench = @import("bench.zig");
+pub const batch = @import("batch.zig");
+pub const doctor = @import("doctor.zig");
+pub const settings = @import("settings.zig");
+pub const hooks = @import("hooks.zig");
+pub const refs = @import("refs.zig");
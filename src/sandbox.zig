@@ -0,0 +1,127 @@
+//! Workspace sandbox for mutating tools. Writes through `write`, `edit`,
+//! `patch`, `apply_patch`, `draft_edit` and the write targets of `bash`
+//! commands must land inside the project root, after symlinks are resolved.
+//! Paths outside need an allow entry:
+//!
+//!     blitz.set_sandbox(true, { "/tmp", "~/notes" })
+//!
+//! An entry covers the path and everything below it, `~/` entries are
+//! taken from the home directory and relative ones from the project root.
+//! `blitz.set_sandbox(false)` turns the check off. Reads are not limited,
+//! the sensitive path guard covers those, but `ripgrep` only searches
+//! paths the sandbox allows.
+const std = @import("std");
+
+pub const MAX_ALLOWED = 32;
+
+/// always writable, output sinks of shell commands
+const builtin_allowed = [_][]const u8{ "/dev/null", "/dev/stdout", "/dev/stderr", "/dev/tty" };
+
+pub const Sandbox = struct {
+    enabled: bool = true,
+    allowed: [MAX_ALLOWED][]const u8 = undefined,
+    count: usize = 0,
+
+    pub fn items(self: *const Sandbox) []const []const u8 {
+        return self.allowed[0..self.count];
+    }
+
+    /// `path` is absolute and canonical, see `canonicalize`.
+    pub fn allows(self: *const Sandbox, root: []const u8, home: ?[]const u8, path: []const u8) bool {
+        if (!self.enabled) return true;
+        if (within(root, path)) return true;
+        for (builtin_allowed) |dev| {
+            if (std.mem.eql(u8, dev, path)) return true;
+        }
+        var buf: [std.fs.max_path_bytes]u8 = undefined;
+        for (self.items()) |entry| {
+            const base = allowedPath(&buf, root, home, entry) orelse continue;
+            if (within(base, path)) return true;
+        }
+        return false;
+    }
+
+    /// `allows` with the root and the allow entries canonicalized like
+    /// `path`, so a symlinked checkout still writes to itself and an entry
+    /// reaching through a link covers where the link points.
+    pub fn allowsOnDisk(self: *const Sandbox, io: std.Io, alloc: std.mem.Allocator, root: []const u8, home: ?[]const u8, path: []const u8) bool {
+        if (!self.enabled) return true;
+        const real_root = canonicalize(io, alloc, root) catch root;
+        var real: Sandbox = .{};
+        var buf: [std.fs.max_path_bytes]u8 = undefined;
+        for (self.items()) |entry| {
+            const base = allowedPath(&buf, root, home, entry) orelse continue;
+            real.allowed[real.count] = canonicalize(io, alloc, base) catch continue;
+            real.count += 1;
+        }
+        return real.allows(real_root, home, path);
+    }
+};
+
+/// Absolute path an allow entry covers, `..` resolved.
+fn allowedPath(buf: []u8, root: []const u8, home: ?[]const u8, entry: []const u8) ?[]const u8 {
+    const trimmed = if (entry.len > 1) std.mem.trimRight(u8, entry, "/") else entry;
+    var fba = std.heap.FixedBufferAllocator.init(buf);
+    if (std.mem.eql(u8, trimmed, "~")) return home;
+    if (std.mem.startsWith(u8, trimmed, "~/")) {
+        const h = home orelse return null;
+        return std.fs.path.resolve(fba.allocator(), &.{ h, trimmed[2..] }) catch null;
+    }
+    // an absolute entry replaces the root
+    return std.fs.path.resolve(fba.allocator(), &.{ root, trimmed }) catch null;
+}
+
+/// `path` is `dir` itself or lies below it.
+pub fn within(dir: []const u8, path: []const u8) bool {
+    if (std.mem.eql(u8, dir, "/")) return path.len > 0 and path[0] == '/';
+    if (!std.mem.startsWith(u8, path, dir)) return false;
+    return path.len == dir.len or path[dir.len] == '/';
+}
+
+/// Resolves symlinks in the part of the absolute `path` that exists, the
+/// missing rest is appended as is. A link inside the project that points
+/// elsewhere so counts as outside.
+pub fn canonicalize(io: std.Io, alloc: std.mem.Allocator, path: []const u8) ![]const u8 {
+    const lexical = try std.fs.path.resolve(alloc, &.{path});
+    var existing: []const u8 = lexical;
+    var buf: [std.fs.max_path_bytes]u8 = undefined;
+    while (true) {
+        if (std.Io.Dir.cwd().realPathFile(io, existing, &buf)) |len| {
+            return std.fs.path.join(alloc, &.{ buf[0..len], lexical[existing.len..] });
+        } else |_| {}
+        existing = std.fs.path.dirname(existing) orelse return lexical;
+    }
+}
+
+test "sandbox keeps writes inside the root" {
+    var sandbox = Sandbox{};
+    const root = "/home/dev/project";
+    const home = "/home/dev";
+    try std.testing.expect(sandbox.allows(root, home, "/home/dev/project"));
+    try std.testing.expect(sandbox.allows(root, home, "/home/dev/project/src/main.zig"));
+    try std.testing.expect(!sandbox.allows(root, home, "/home/dev/project-old/main.zig"));
+    try std.testing.expect(!sandbox.allows(root, home, "/etc/hosts"));
+    try std.testing.expect(sandbox.allows(root, home, "/dev/null"));
+
+    sandbox.allowed[0] = "/tmp/";
+    sandbox.allowed[1] = "~/notes";
+    sandbox.allowed[2] = "../shared";
+    sandbox.count = 3;
+    try std.testing.expect(sandbox.allows(root, home, "/tmp/blitz/out.txt"));
+    try std.testing.expect(sandbox.allows(root, home, "/home/dev/notes/todo.md"));
+    try std.testing.expect(sandbox.allows(root, home, "/home/dev/shared/lib.zig"));
+    try std.testing.expect(!sandbox.allows(root, home, "/home/dev/.bashrc"));
+
+    // `..` is resolved before the entry is compared
+    sandbox.allowed[0] = "/tmp/cache/..";
+    sandbox.allowed[1] = "~/notes/../notes";
+    sandbox.count = 2;
+    try std.testing.expect(sandbox.allows(root, home, "/tmp/out.txt"));
+    try std.testing.expect(sandbox.allows(root, home, "/home/dev/notes/todo.md"));
+    try std.testing.expect(!sandbox.allows(root, home, "/home/dev/notes-old/todo.md"));
+    try std.testing.expect(!sandbox.allows(root, home, "/home/dev/.bashrc"));
+
+    sandbox.enabled = false;
+    try std.testing.expect(sandbox.allows(root, home, "/etc/hosts"));
+    try std.testing.expect(within("/", "/etc"));
+}
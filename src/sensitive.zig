@@ -0,0 +1,181 @@
+//! Sensitive path guard for read-style tools. Patterns are globs:
+//! `*` and `?` stay inside one path segment, `**` crosses segments.
+//! Patterns without a `/` match the file name anywhere, `~/` patterns are
+//! matched against the absolute path, everything else relative to cwd.
+const std = @import("std");
+
+pub const MAX_PATTERNS = 32;
+
+pub const default_patterns: []const []const u8 = &.{
+    ".env",
+    ".env.*",
+    "secrets/**",
+    "~/.ssh/*",
+};
+
+pub const Policy = enum {
+    /// ask the user before the content reaches the agent
+    ask,
+    /// never hand out the content
+    block,
+
+    pub fn fromString(s: []const u8) ?Policy {
+        if (std.mem.eql(u8, s, "ask")) return .ask;
+        if (std.mem.eql(u8, s, "block")) return .block;
+        return null;
+    }
+};
+
+pub const SensitivePaths = struct {
+    patterns: [MAX_PATTERNS][]const u8 = undefined,
+    count: usize = 0,
+    policy: Policy = .ask,
+
+    pub const defaults: SensitivePaths = blk: {
+        var s = SensitivePaths{};
+        for (default_patterns) |p| {
+            s.patterns[s.count] = p;
+            s.count += 1;
+        }
+        break :blk s;
+    };
+
+    pub fn items(self: *const SensitivePaths) []const []const u8 {
+        return self.patterns[0..self.count];
+    }
+
+    /// Returns the first pattern matching the absolute `path`.
+    pub fn match(self: *const SensitivePaths, cwd: []const u8, home: ?[]const u8, path: []const u8) ?[]const u8 {
+        for (self.items()) |pattern| {
+            if (matchPattern(pattern, cwd, home, path)) return pattern;
+        }
+        return null;
+    }
+};
+
+fn matchPattern(pattern: []const u8, cwd: []const u8, home: ?[]const u8, path: []const u8) bool {
+    if (std.mem.startsWith(u8, pattern, "~/")) {
+        const h = home orelse return false;
+        if (!std.mem.startsWith(u8, path, h)) return false;
+        const rest = path[h.len..];
+        if (rest.len == 0 or rest[0] != '/') return false;
+        return globMatch(pattern[2..], rest[1..]);
+    }
+
+    if (std.mem.indexOfScalar(u8, pattern, '/') == null) {
+        return globMatch(pattern, std.fs.path.basename(path));
+    }
+
+    if (pattern[0] == '/') return globMatch(pattern, path);
+
+    if (!std.mem.startsWith(u8, path, cwd)) return false;
+    const rest = path[cwd.len..];
+    if (rest.len == 0 or rest[0] != '/') return false;
+    return globMatch(pattern, rest[1..]);
+}
+
+pub fn globMatch(pattern: []const u8, text: []const u8) bool {
+    if (pattern.len == 0) return text.len == 0;
+
+    if (std.mem.startsWith(u8, pattern, "**")) {
+        var rest = pattern[2..];
+        if (rest.len > 0 and rest[0] == '/') rest = rest[1..];
+        if (rest.len == 0) return true;
+        var i: usize = 0;
+        while (i <= text.len) : (i += 1) {
+            if (i == 0 or text[i - 1] == '/') {
+                if (globMatch(rest, text[i..])) return true;
+            }
+        }
+        return false;
+    }
+
+    switch (pattern[0]) {
+        '*' => {
+            var i: usize = 0;
+            while (i <= text.len) : (i += 1) {
+                if (globMatch(pattern[1..], text[i..])) return true;
+                if (i < text.len and text[i] == '/') return false;
+            }
+            return false;
+        },
+        '?' => return text.len > 0 and text[0] != '/' and globMatch(pattern[1..], text[1..]),
+        else => return text.len > 0 and text[0] == pattern[0] and globMatch(pattern[1..], text[1..]),
+    }
+}
+
+/// Replace `path:...` lines of search output that point into sensitive
+/// files. Returns `output` unchanged when nothing matched.
+pub fn redactSearchOutput(
+    alloc: std.mem.Allocator,
+    guard: *const SensitivePaths,
+    cwd: []const u8,
+    home: ?[]const u8,
+    output: []const u8,
+) ![]const u8 {
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    var redacted: usize = 0;
+
+    var it = std.mem.splitScalar(u8, output, '\n');
+    var first = true;
+    while (it.next()) |line| {
+        if (!first) try out.writer.writeByte('\n');
+        first = false;
+
+        const colon = std.mem.indexOfScalar(u8, line, ':') orelse line.len;
+        const file = line[0..colon];
+        const abs = if (file.len > 0 and file[0] == '/')
+            file
+        else
+            try std.fs.path.resolve(alloc, &.{ cwd, file });
+
+        if (file.len > 0 and guard.match(cwd, home, abs) != null) {
+            redacted += 1;
+            try out.writer.print("{s}: [redacted: sensitive file]", .{file});
+            continue;
+        }
+        try out.writer.writeAll(line);
+    }
+
+    if (redacted == 0) {
+        out.deinit();
+        return output;
+    }
+    return out.toOwnedSlice();
+}
+
+test "globMatch" {
+    try std.testing.expect(globMatch(".env", ".env"));
+    try std.testing.expect(!globMatch(".env", ".envrc"));
+    try std.testing.expect(globMatch(".env.*", ".env.local"));
+    try std.testing.expect(globMatch("secrets/**", "secrets/a/b.txt"));
+    try std.testing.expect(globMatch("**/id_*", "a/b/id_rsa"));
+    try std.testing.expect(!globMatch("*.pem", "dir/key.pem"));
+    try std.testing.expect(globMatch("*", "id_rsa"));
+}
+
+test "SensitivePaths.match" {
+    const guard = SensitivePaths.defaults;
+    const cwd = "/w";
+    const home = "/home/u";
+
+    try std.testing.expect(guard.match(cwd, home, "/w/.env") != null);
+    try std.testing.expect(guard.match(cwd, home, "/w/sub/.env.prod") != null);
+    try std.testing.expect(guard.match(cwd, home, "/w/secrets/db/pw") != null);
+    try std.testing.expect(guard.match(cwd, home, "/home/u/.ssh/id_ed25519") != null);
+    try std.testing.expect(guard.match(cwd, home, "/w/src/main.zig") == null);
+    try std.testing.expect(guard.match(cwd, home, "/other/secrets/x") == null);
+}
+
+test "redactSearchOutput" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const guard = SensitivePaths.defaults;
+
+    const in = "src/a.zig:3:const key = 1;\n.env:1:API_KEY=hunter2";
+    const out = try redactSearchOutput(arena.allocator(), &guard, "/w", null, in);
+    try std.testing.expectEqualStrings("src/a.zig:3:const key = 1;\n.env: [redacted: sensitive file]", out);
+
+    const clean = "src/a.zig:3:x";
+    try std.testing.expect((try redactSearchOutput(arena.allocator(), &guard, "/w", null, clean)).ptr == clean.ptr);
+}
@@ -17,46 +17,102 @@ pub const Session = struct {
     };
 };
 
+/// Written while the main agent is running, removed once the run ends.
+/// Finding it on startup means the previous process died mid-run.
+pub const AUTOSAVE_PATH = util.BLITZ_DIR ++ "/autosave.json";
+
+/// The main session of the previous process, kept for `:handoff`.
+pub const LAST_SESSION_PATH = util.BLITZ_DIR ++ "/last_session.json";
+
 pub const SaveState = struct {
+    meta: r.sessions.Meta = .{},
+    /// stored session name, empty until the session is stored or opened as a tab
+    name: []const u8 = "",
     chat: []const prv.adapter.Message,
     chat_render: []const app.ChatEntry,
+    /// the main agent's todo list, so a switched back tab picks up its work
+    todos: []const prv.agent.Todo = &.{},
 };
 
 pub fn saveSession(a: *const app.App, w: *std.Io.Writer) !void {
-    const agent = a.mainAgent() orelse return error.NoActiveSessionToSave;
-
     var out = std.ArrayList(prv.adapter.Message).empty;
     defer out.deinit(a.gpa);
+    var todos: [prv.agent.TodoList.max_todos]prv.agent.Todo = undefined;
+    const save = try snapshot(a, &out, &todos);
 
-    for (agent.chat.messages.items) |msg| {
+    // sealed as a whole, without encryption it streams straight out
+    if (try a.vault.sealing() == null) {
+        try std.json.Stringify.value(save, .{}, w);
+        return w.flush();
+    }
+    const plain = try std.json.Stringify.valueAlloc(a.gpa, save, .{});
+    defer a.gpa.free(plain);
+    const sealed = try a.vault.seal(a.io, a.gpa, plain);
+    defer a.gpa.free(sealed);
+    try w.writeAll(sealed);
+    try w.flush();
+}
+
+/// The main session as it is saved, borrowing from the app. `out` takes the
+/// finished messages, `todos` the todo list.
+pub fn snapshot(
+    a: *const app.App,
+    out: *std.ArrayList(prv.adapter.Message),
+    todos: *[prv.agent.TodoList.max_todos]prv.agent.Todo,
+) !SaveState {
+    const agent = a.mainAgent() orelse return error.NoActiveSessionToSave;
+
+    const streaming_idx = agent.streamingMessageIndex();
+    for (agent.chat.messages.items, 0..) |msg, i| {
         if (!msg.flags.allow_export) continue;
+        // half streamed, the provider never finished it
+        if (streaming_idx == i) continue;
         try out.append(a.gpa, msg);
     }
 
-    const save = SaveState{
+    var meta: r.sessions.Meta = .{
+        .cwd = a.cwd,
+        .model = agent.config.model,
+        .usage = a.swarm.usage(),
+    };
+    for (out.items) |msg| {
+        if (msg.role != .user or msg.parts.len == 0 or msg.parts[0] != .text) continue;
+        meta.title = r.sessions.titleOf(msg.parts[0].text);
+        break;
+    }
+
+    const todo_count = blk: {
+        const g = agent.todo_list.lock(a.io);
+        defer g.unlock();
+        @memcpy(todos[0..g.ptr.count], g.ptr.todos[0..g.ptr.count]);
+        break :blk g.ptr.count;
+    };
+
+    return .{
+        .meta = meta,
+        .name = a.session_name orelse "",
         .chat = out.items[0..],
         .chat_render = a.chat_entries.items,
+        .todos = todos[0..todo_count],
     };
-
-    try std.json.Stringify.value(save, .{}, w);
-    try w.flush();
 }
 
 pub fn loadSession(a: *app.App, w: *std.Io.Reader) !void {
-    const alloc = a.appAlloc();
     const session_alloc = a.sessionAlloc();
 
-    a.reset();
-
-    // Parse JSON from reader
-    var json_reader = std.json.Reader.init(alloc, w);
-    defer json_reader.deinit();
+    const content = try w.allocRemaining(a.gpa, .limited(r.sessions.MAX_SESSION_BYTES));
+    defer a.gpa.free(content);
+    // sealed files need the key, plaintext ones load as they are
+    const plain = try a.vault.open(a.gpa, content);
+    defer if (plain.ptr != content.ptr) a.gpa.free(plain);
 
-    const parsed = try std.json.parseFromTokenSource(SaveState, alloc, &json_reader, .{
+    const parsed = try std.json.parseFromSlice(SaveState, a.gpa, plain, .{
         .ignore_unknown_fields = true,
     });
     defer parsed.deinit();
 
+    a.reset();
+
     const save = parsed.value;
 
     // Create new agent to hold restored chat
@@ -86,7 +142,328 @@ pub fn loadSession(a: *app.App, w: *std.Io.Reader) !void {
         try a.chat_entries.append(session_alloc, cloned);
     }
 
+    // Restore todos as already seen, a reload is not a user edit
+    if (save.todos.len > 0) {
+        const g = agent.todo_list.lock(a.io);
+        defer g.unlock();
+        const list = g.ptr;
+        for (save.todos[0..@min(save.todos.len, prv.agent.TodoList.max_todos)]) |todo| {
+            list.todos[list.count] = try util.deepClone(prv.agent.Todo, todo, agent.arena.allocator());
+            list.count += 1;
+            list.next_id = @max(list.next_id, todo.id + 1);
+            list.revision = @max(list.revision, todo.rev);
+        }
+        list.agent_seen = list.revision;
+    }
+
     a.main_agent_id = id;
     a.dirty = true;
-    a.running = false;
+    a.run_state = .idle;
+}
+
+pub fn writeAutosave(a: *const app.App) void {
+    const io = a.context_factory.io;
+    util.ensureBlitzDir(std.Io.Dir.cwd(), io) catch return;
+    const file = std.Io.Dir.cwd().createFile(io, AUTOSAVE_PATH, .{}) catch return;
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    saveSession(a, &writer.interface) catch |err| {
+        std.log.warn("autosave failed: {any}", .{err});
+    };
+}
+
+/// Keep the session for a later `:handoff`. Sessions without a single
+/// exchange are not worth a summary and leave the previous file alone.
+pub fn writeLastSession(a: *const app.App) void {
+    if (!hasExchange(a)) return;
+    storeSession(a);
+
+    const io = a.context_factory.io;
+    util.ensureBlitzDir(std.Io.Dir.cwd(), io) catch return;
+    const file = std.Io.Dir.cwd().createFile(io, LAST_SESSION_PATH, .{}) catch return;
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    saveSession(a, &writer.interface) catch |err| {
+        std.log.warn("saving last session failed: {any}", .{err});
+    };
+}
+
+fn hasExchange(a: *const app.App) bool {
+    const agent = a.mainAgent() orelse return false;
+    var exchanged: usize = 0;
+    for (agent.chat.messages.items) |msg| {
+        if (msg.role != .system) exchanged += 1;
+    }
+    return exchanged >= 2;
+}
+
+/// How long `:undo` brings back the session C-n or `:clear` archived.
+pub const ARCHIVE_UNDO_MS = 30_000;
+
+/// C-n and `:clear`: the session goes to the browser under its name and a
+/// fresh one starts. For `ARCHIVE_UNDO_MS` a bare `:undo` switches back.
+pub fn archiveAndReset(a: *app.App) void {
+    const name = if (hasExchange(a)) a.ensureSessionName() catch null else null;
+    writeLastSession(a);
+    if (name) |n| {
+        // the session arena goes with the reset
+        const len = @min(n.len, a.archived_buf.len);
+        @memcpy(a.archived_buf[0..len], n[0..len]);
+        a.archived_len = len;
+        a.archived_at_ms = prv.http.nowMs(a.io);
+    }
+    a.reset();
+    if (name != null) {
+        a.notifications.append(a.arena_app.allocator(), "archived as {s}, :undo brings it back", .{a.archived_buf[0..a.archived_len]}) catch {};
+    }
+}
+
+/// Resume the session `archiveAndReset` put away, false once the undo
+/// window is over or the fresh session already has work worth keeping.
+pub fn undoArchive(a: *app.App) !bool {
+    if (a.archived_len == 0 or prv.http.nowMs(a.io) - a.archived_at_ms > ARCHIVE_UNDO_MS) return false;
+    if (!untouched(a)) return false;
+    const name = a.archived_buf[0..a.archived_len];
+    a.archived_len = 0;
+    resumeStored(a, name) catch |err| {
+        try a.notifications.push(a.arena_app.allocator(), .err, "resume {s}: {s}", .{ name, @errorName(err) });
+    };
+    return true;
+}
+
+/// No prompt sent, no file changed and nothing running since the reset.
+fn untouched(a: *app.App) bool {
+    if (a.isRunning()) return false;
+    {
+        const g = a.checkpoints.lock(a.io);
+        defer g.unlock();
+        if (g.ptr.items.items.len > 0) return false;
+    }
+    const agent = a.mainAgent() orelse return true;
+    for (agent.chat.messages.items) |msg| {
+        if (msg.role != .system) return false;
+    }
+    return true;
+}
+
+/// Copy for the session browser, a resumed session overwrites its file.
+fn storeSession(a: *const app.App) void {
+    const io = a.context_factory.io;
+    const home = a.swarm.exec.env.get("HOME") orelse return;
+    var dir = r.sessions.openDir(io, home) catch |err| {
+        std.log.warn("no session directory: {any}", .{err});
+        return;
+    };
+    defer dir.close(io);
+
+    var name_buf: [128]u8 = undefined;
+    const now: u64 = @intCast(@divTrunc(std.Io.Clock.Timestamp.now(io, .real).raw.nanoseconds, std.time.ns_per_s));
+    const name = a.session_name orelse r.sessions.newName(&name_buf, a.cwd, now) catch return;
+    var path_buf: [256]u8 = undefined;
+    const path = r.sessions.fileName(&path_buf, name) catch return;
+
+    const file = dir.createFile(io, path, .{}) catch return;
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    saveSession(a, &writer.interface) catch |err| {
+        std.log.warn("storing session {s} failed: {any}", .{ name, err });
+    };
+}
+
+/// Resume a stored session from the browser, the current one is stored
+/// first so switching back and forth loses nothing.
+pub fn resumeStored(a: *app.App, name: []const u8) !void {
+    const io = a.context_factory.io;
+    const home = a.swarm.exec.env.get("HOME") orelse return error.NoHomeFound;
+    var dir = try r.sessions.openDir(io, home);
+    defer dir.close(io);
+    var path_buf: [256]u8 = undefined;
+    const file = try dir.openFile(io, try r.sessions.fileName(&path_buf, name), .{ .mode = .read_only });
+    defer file.close(io);
+
+    writeLastSession(a);
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    try loadSession(a, &reader.interface);
+
+    const agent = a.mainAgent() orelse return;
+    _ = try repairDanglingToolCalls(agent.arena.allocator(), &agent.chat.messages);
+    a.session_name = try a.sessionAlloc().dupe(u8, name);
+    a.pushSystemMessage("resumed session {s} ({d} messages)", .{ name, agent.chat.messages.items.len });
+}
+
+/// Park the current session and continue in the stored session `name`, a
+/// fresh one when nothing is stored under it yet. Backs the tabs.
+pub fn switchTo(a: *app.App, name: []const u8) !void {
+    const io = a.context_factory.io;
+    const home = a.swarm.exec.env.get("HOME") orelse return error.NoHomeFound;
+    if (a.mainAgent() != null) storeSession(a);
+
+    var dir = try r.sessions.openDir(io, home);
+    defer dir.close(io);
+    var path_buf: [256]u8 = undefined;
+    if (dir.openFile(io, try r.sessions.fileName(&path_buf, name), .{ .mode = .read_only })) |file| {
+        defer file.close(io);
+        var buf: [4096]u8 = undefined;
+        var reader = file.reader(io, &buf);
+        try loadSession(a, &reader.interface);
+        const agent = a.mainAgent() orelse return;
+        _ = try repairDanglingToolCalls(agent.arena.allocator(), &agent.chat.messages);
+    } else |err| switch (err) {
+        error.FileNotFound => a.reset(),
+        else => return err,
+    }
+    a.session_name = try a.sessionAlloc().dupe(u8, name);
+}
+
+pub fn hasLastSession(io: std.Io) bool {
+    _ = std.Io.Dir.cwd().statFile(io, LAST_SESSION_PATH, .{}) catch return false;
+    return true;
+}
+
+/// Continue where the previous session left off: its history is loaded and
+/// compacted right away, so the new session starts from a hand-off summary
+/// (decisions, open work, files in progress) instead of the full transcript.
+pub fn startHandoff(a: *app.App) !void {
+    const io = a.context_factory.io;
+    const file = std.Io.Dir.cwd().openFile(io, LAST_SESSION_PATH, .{ .mode = .read_only }) catch {
+        a.pushSystemMessage("no previous session in this project", .{});
+        return;
+    };
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    try loadSession(a, &reader.interface);
+
+    const agent = a.mainAgent() orelse return;
+    _ = try repairDanglingToolCalls(agent.arena.allocator(), &agent.chat.messages);
+
+    // the summary replaces the old transcript, on screen as well
+    a.chat_entries.clearRetainingCapacity();
+    a.last_session_available = false;
+    a.pushSystemMessage("hand-off: summarizing the previous session ({d} messages)", .{agent.chat.messages.items.len});
+    try a.cmd_queue.append(a.io, .compact);
+}
+
+pub fn clearAutosave(io: std.Io) void {
+    std.Io.Dir.cwd().deleteFile(io, AUTOSAVE_PATH) catch {};
+}
+
+/// Restore the session left behind by a process that died mid-run. Returns
+/// true when the restored agent has an unfinished turn that `:resume` can
+/// pick up.
+pub fn recoverInterruptedRun(a: *app.App) !bool {
+    const io = a.context_factory.io;
+    const file = std.Io.Dir.cwd().openFile(io, AUTOSAVE_PATH, .{ .mode = .read_only }) catch return false;
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+
+    loadSession(a, &reader.interface) catch |err| {
+        std.log.warn("failed to restore interrupted run: {any}", .{err});
+        clearAutosave(io);
+        return false;
+    };
+
+    const agent = a.mainAgent() orelse return false;
+    const repaired = try repairDanglingToolCalls(agent.arena.allocator(), &agent.chat.messages);
+    const items = agent.chat.messages.items;
+    a.resume_pending = items.len > 0 and items[items.len - 1].role == .user;
+
+    if (a.resume_pending) {
+        a.pushSystemMessage("previous run was interrupted ({d} unfinished tool calls). :resume to continue", .{repaired});
+    } else {
+        clearAutosave(io);
+    }
+    return a.resume_pending;
+}
+
+/// Answer every tool call that never got a result, so providers accept the
+/// history again. Returns the number of results added.
+pub fn repairDanglingToolCalls(alloc: std.mem.Allocator, messages: *std.ArrayList(prv.adapter.Message)) !usize {
+    var added: usize = 0;
+    var i: usize = 0;
+    while (i < messages.items.len) : (i += 1) {
+        const msg = messages.items[i];
+        if (msg.role != .agent) continue;
+
+        var missing: std.ArrayList(prv.adapter.ContentPart) = .empty;
+        for (msg.parts) |part| {
+            const call = switch (part) {
+                .tool_call => |c| c,
+                else => continue,
+            };
+            if (i + 1 < messages.items.len and hasResult(messages.items[i + 1], call.id)) continue;
+            try missing.append(alloc, .{ .tool_result = .{
+                .call_id = call.id,
+                .name = call.name,
+                .content = "interrupted: blitzdenk exited before this tool finished",
+                .is_error = true,
+            } });
+        }
+        if (missing.items.len == 0) continue;
+        added += missing.items.len;
+
+        const next = if (i + 1 < messages.items.len) &messages.items[i + 1] else null;
+        if (next != null and next.?.role == .user and isToolResultMessage(next.?.*)) {
+            try missing.insertSlice(alloc, 0, next.?.parts);
+            next.?.parts = missing.items;
+        } else {
+            try messages.insert(alloc, i + 1, .{ .role = .user, .parts = missing.items });
+        }
+    }
+    return added;
+}
+
+fn hasResult(msg: prv.adapter.Message, call_id: []const u8) bool {
+    for (msg.parts) |part| switch (part) {
+        .tool_result => |res| if (std.mem.eql(u8, res.call_id, call_id)) return true,
+        else => {},
+    };
+    return false;
+}
+
+fn isToolResultMessage(msg: prv.adapter.Message) bool {
+    for (msg.parts) |part| if (part == .tool_result) return true;
+    return false;
+}
+
+test "repairDanglingToolCalls" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var calls = [_]prv.adapter.ContentPart{
+        .{ .tool_call = .{ .id = "a", .name = "read", .arguments = "{}" } },
+        .{ .tool_call = .{ .id = "b", .name = "bash", .arguments = "{}" } },
+    };
+    var results = [_]prv.adapter.ContentPart{
+        .{ .tool_result = .{ .call_id = "a", .name = "read", .content = "ok" } },
+    };
+
+    var messages: std.ArrayList(prv.adapter.Message) = .empty;
+    try messages.append(alloc, .{ .role = .agent, .parts = &calls });
+    try messages.append(alloc, .{ .role = .user, .parts = &results });
+
+    try std.testing.expectEqual(@as(usize, 1), try repairDanglingToolCalls(alloc, &messages));
+    try std.testing.expectEqual(@as(usize, 2), messages.items.len);
+    try std.testing.expectEqual(@as(usize, 2), messages.items[1].parts.len);
+    try std.testing.expect(messages.items[1].parts[1].tool_result.is_error);
+
+    // a trailing call with no result message at all gets one appended
+    try messages.append(alloc, .{ .role = .agent, .parts = calls[1..] });
+    try std.testing.expectEqual(@as(usize, 1), try repairDanglingToolCalls(alloc, &messages));
+    try std.testing.expectEqual(prv.adapter.Role.user, messages.items[3].role);
+}
+
+test "SaveState reads files from before tabs" {
+    const parsed = try std.json.parseFromSlice(SaveState, std.testing.allocator,
+        \\{"meta":{"cwd":"/p","model":"m","title":"t"},"chat":[],"chat_render":[]}
+    , .{ .ignore_unknown_fields = true });
+    defer parsed.deinit();
+    try std.testing.expectEqualStrings("", parsed.value.name);
+    try std.testing.expectEqual(@as(usize, 0), parsed.value.todos.len);
 }
@@ -0,0 +1,293 @@
+//! Saved sessions across projects, one JSON file each under
+//! `~/.cache/blitzdenk/sessions/`. A session with at least one exchange is
+//! stored when it ends (`:clear`, C-n, exit). The C-b browser lists them and
+//! resumes, renames or deletes them.
+const std = @import("std");
+const prv = @import("provider");
+const vault_mod = @import("vault.zig");
+const Vault = vault_mod.Vault;
+
+pub const DIR = ".cache/blitzdenk/sessions";
+pub const EXT = ".json";
+pub const MAX_LISTED = 200;
+pub const MAX_SESSION_BYTES = 64 * 1024 * 1024;
+/// `meta` is serialized first, this much of a plaintext file covers it
+const META_HEAD_BYTES = 16 * 1024;
+const MAX_TITLE_BYTES = 60;
+
+/// Written next to the chat, read back for the browser.
+pub const Meta = struct {
+    cwd: []const u8 = "",
+    model: []const u8 = "",
+    /// first line of the first prompt
+    title: []const u8 = "",
+    usage: prv.adapter.TokenUsage = .{},
+};
+
+pub const Entry = struct {
+    /// file name without `.json`
+    name: []const u8,
+    meta: Meta,
+    /// last modified, ns since the epoch
+    mtime: i128,
+};
+
+pub fn openDir(io: std.Io, home: []const u8) !std.Io.Dir {
+    var home_dir = try std.Io.Dir.openDirAbsolute(io, home, .{});
+    defer home_dir.close(io);
+    try home_dir.createDirPath(io, DIR);
+    return home_dir.openDir(io, DIR, .{ .iterate = true });
+}
+
+/// Newest first, files that don't parse or can't be decrypted are listed
+/// without metadata. Only the metadata lands in `alloc`, file contents
+/// are read through `scratch` and freed per file.
+pub fn list(io: std.Io, alloc: std.mem.Allocator, scratch: std.mem.Allocator, dir: std.Io.Dir, vault: *const Vault) ![]Entry {
+    var out: std.ArrayList(Entry) = .empty;
+    var it = dir.iterate();
+    while (try it.next(io)) |entry| {
+        if (entry.kind != .file or !std.mem.endsWith(u8, entry.name, EXT)) continue;
+        if (out.items.len >= MAX_LISTED) break;
+        const stat = dir.statFile(io, entry.name, .{}) catch continue;
+        var arena = std.heap.ArenaAllocator.init(scratch);
+        defer arena.deinit();
+        try out.append(alloc, .{
+            .name = try alloc.dupe(u8, entry.name[0 .. entry.name.len - EXT.len]),
+            .meta = readMeta(io, alloc, arena.allocator(), dir, entry.name, vault) orelse .{},
+            .mtime = stat.mtime.nanoseconds,
+        });
+    }
+    std.mem.sort(Entry, out.items, {}, struct {
+        fn newer(_: void, a: Entry, b: Entry) bool {
+            return a.mtime > b.mtime;
+        }
+    }.newer);
+    return out.items;
+}
+
+/// Plaintext files are read up to `META_HEAD_BYTES`, sealed ones have to
+/// be read whole to be authenticated.
+fn readMeta(io: std.Io, alloc: std.mem.Allocator, scratch: std.mem.Allocator, dir: std.Io.Dir, file_name: []const u8, vault: *const Vault) ?Meta {
+    const file = dir.openFile(io, file_name, .{}) catch return null;
+    defer file.close(io);
+    var read_buf: [4096]u8 = undefined;
+    var file_reader = file.reader(io, &read_buf);
+    var head_buf: [META_HEAD_BYTES]u8 = undefined;
+    const head = head_buf[0 .. file_reader.interface.readSliceShort(&head_buf) catch return null];
+    if (!vault_mod.isSealed(head)) return parseMeta(alloc, scratch, head);
+
+    const rest = file_reader.interface.allocRemaining(scratch, .limited(MAX_SESSION_BYTES)) catch return null;
+    const content = std.mem.concat(scratch, u8, &.{ head, rest }) catch return null;
+    const plain = vault.open(scratch, content) catch return null;
+    return parseMeta(alloc, scratch, plain);
+}
+
+/// The leading `"meta"` object of a stored session, the rest of `json`
+/// is not looked at and may be cut off.
+fn parseMeta(alloc: std.mem.Allocator, scratch: std.mem.Allocator, json: []const u8) ?Meta {
+    var scanner = std.json.Scanner.initCompleteInput(scratch, json);
+    defer scanner.deinit();
+    if ((scanner.next() catch return null) != .object_begin) return null;
+    switch (scanner.next() catch return null) {
+        .string => |key| if (!std.mem.eql(u8, key, "meta")) return null,
+        else => return null,
+    }
+    return std.json.innerParse(Meta, alloc, &scanner, .{
+        .ignore_unknown_fields = true,
+        .allocate = .alloc_always,
+        .max_value_len = json.len,
+    }) catch null;
+}
+
+/// Seal the stored sessions still in plaintext, each through a temp file
+/// so a failed write leaves the original. Returns how many were sealed.
+pub fn encryptAll(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, vault: *const Vault) !usize {
+    if (try vault.sealing() == null) return error.EncryptionDisabled;
+    // names first, the renames below would change what iterating sees
+    var names: std.ArrayList([]const u8) = .empty;
+    defer {
+        for (names.items) |name| alloc.free(name);
+        names.deinit(alloc);
+    }
+    var it = dir.iterate();
+    while (try it.next(io)) |entry| {
+        if (entry.kind != .file or !std.mem.endsWith(u8, entry.name, EXT)) continue;
+        try names.append(alloc, try alloc.dupe(u8, entry.name));
+    }
+
+    var sealed: usize = 0;
+    for (names.items) |name| {
+        const content = blk: {
+            const file = try dir.openFile(io, name, .{});
+            defer file.close(io);
+            var read_buf: [4096]u8 = undefined;
+            var file_reader = file.reader(io, &read_buf);
+            break :blk try file_reader.interface.allocRemaining(alloc, .limited(MAX_SESSION_BYTES));
+        };
+        defer alloc.free(content);
+        if (vault_mod.isSealed(content)) continue;
+        const out = try vault.seal(io, alloc, content);
+        defer alloc.free(out);
+
+        var tmp_buf: [256]u8 = undefined;
+        const tmp_name = try std.fmt.bufPrint(&tmp_buf, ".{s}.tmp", .{name});
+        {
+            const file = try dir.createFile(io, tmp_name, .{});
+            defer file.close(io);
+            var write_buf: [4096]u8 = undefined;
+            var writer = file.writer(io, &write_buf);
+            try writer.interface.writeAll(out);
+            try writer.interface.flush();
+        }
+        try std.Io.Dir.rename(dir, tmp_name, dir, name, io);
+        sealed += 1;
+    }
+    return sealed;
+}
+
+pub fn fileName(buf: []u8, name: []const u8) ![]const u8 {
+    if (!validName(name)) return error.InvalidName;
+    return std.fmt.bufPrint(buf, "{s}" ++ EXT, .{name}) catch error.InvalidName;
+}
+
+pub fn rename(io: std.Io, dir: std.Io.Dir, old: []const u8, new: []const u8) !void {
+    var old_buf: [256]u8 = undefined;
+    var new_buf: [256]u8 = undefined;
+    const old_path = try fileName(&old_buf, old);
+    const new_path = try fileName(&new_buf, new);
+    if (dir.statFile(io, new_path, .{})) |_| return error.SessionExists else |_| {}
+    try std.Io.Dir.rename(dir, old_path, dir, new_path, io);
+}
+
+pub fn remove(io: std.Io, dir: std.Io.Dir, name: []const u8) !void {
+    var buf: [256]u8 = undefined;
+    try dir.deleteFile(io, try fileName(&buf, name));
+}
+
+pub fn validName(name: []const u8) bool {
+    if (name.len == 0 or name.len > 128 or name[0] == '.') return false;
+    for (name) |ch| {
+        if (!std.ascii.isAlphanumeric(ch) and ch != '-' and ch != '_' and ch != '.') return false;
+    }
+    return true;
+}
+
+/// `<project>-<yyyymmdd>-<hhmmss>`, the project being the last cwd segment.
+pub fn newName(buf: []u8, cwd: []const u8, unix_secs: u64) ![]const u8 {
+    const es = std.time.epoch.EpochSeconds{ .secs = unix_secs };
+    const year_day = es.getEpochDay().calculateYearDay();
+    const month_day = year_day.calculateMonthDay();
+    const day_secs = es.getDaySeconds();
+
+    var project_buf: [48]u8 = undefined;
+    const base = std.fs.path.basename(cwd);
+    const project = project_buf[0..@min(base.len, project_buf.len)];
+    for (project, base[0..project.len]) |*dst, ch| {
+        dst.* = if (std.ascii.isAlphanumeric(ch) or ch == '-' or ch == '_') ch else '_';
+    }
+    return std.fmt.bufPrint(buf, "{s}-{d}{d:0>2}{d:0>2}-{d:0>2}{d:0>2}{d:0>2}", .{
+        if (project.len == 0 or project[0] == '.') "session" else project,
+        year_day.year,
+        month_day.month.numeric(),
+        month_day.day_index + 1,
+        day_secs.getHoursIntoDay(),
+        day_secs.getMinutesIntoHour(),
+        day_secs.getSecondsIntoMinute(),
+    });
+}
+
+/// First line of `prompt`, cut on a char boundary.
+pub fn titleOf(prompt: []const u8) []const u8 {
+    const line = std.mem.trim(u8, prompt[0 .. std.mem.indexOfScalar(u8, prompt, '\n') orelse prompt.len], " \t\r");
+    if (line.len <= MAX_TITLE_BYTES) return line;
+    var end: usize = MAX_TITLE_BYTES;
+    while (end > 0 and (line[end] & 0xC0) == 0x80) end -= 1;
+    return line[0..end];
+}
+
+pub fn formatAge(buf: []u8, secs: i64) []const u8 {
+    const s: u64 = @intCast(@max(secs, 0));
+    return (if (s < 60)
+        std.fmt.bufPrint(buf, "now", .{})
+    else if (s < 3600)
+        std.fmt.bufPrint(buf, "{d}m ago", .{s / 60})
+    else if (s < 86400)
+        std.fmt.bufPrint(buf, "{d}h ago", .{s / 3600})
+    else
+        std.fmt.bufPrint(buf, "{d}d ago", .{s / 86400})) catch "";
+}
+
+test "session names" {
+    var buf: [128]u8 = undefined;
+    try std.testing.expectEqualStrings("blitz_denk-20241231-235958", try newName(&buf, "/home/me/blitz denk", 1735689598));
+    try std.testing.expectEqualStrings("session-19700101-000000", try newName(&buf, "/", 0));
+    try std.testing.expect(validName("my-api.v2"));
+    try std.testing.expect(!validName("../x"));
+    try std.testing.expectError(error.InvalidName, fileName(&buf, "a/b"));
+
+    try std.testing.expectEqualStrings("fix the parser", titleOf("  fix the parser\nit breaks on tabs"));
+    try std.testing.expectEqualStrings("3h ago", formatAge(&buf, 3 * 3600 + 5));
+}
+
+test "list, rename and remove sessions" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+    const io = std.testing.io;
+
+    var tmp = std.testing.tmpDir(.{ .iterate = true });
+    defer tmp.cleanup();
+    for ([_][]const u8{ "a.json", "b.json", "notes.txt" }, 0..) |name, i| {
+        const file = try tmp.dir.createFile(io, name, .{});
+        defer file.close(io);
+        var buf: [256]u8 = undefined;
+        var writer = file.writer(io, &buf);
+        try writer.interface.print("{{\"meta\": {{\"model\": \"m{d}\"}}, \"chat\": []}}", .{i});
+        try writer.interface.flush();
+    }
+
+    const entries = try list(io, alloc, std.testing.allocator, tmp.dir, &.{});
+    try std.testing.expectEqual(@as(usize, 2), entries.len);
+    for (entries) |e| {
+        try std.testing.expectEqualStrings(if (std.mem.eql(u8, e.name, "a")) "m0" else "m1", e.meta.model);
+    }
+
+    try rename(io, tmp.dir, "a", "kept");
+    try std.testing.expectError(error.SessionExists, rename(io, tmp.dir, "kept", "b"));
+    try remove(io, tmp.dir, "b");
+    const left = try list(io, alloc, std.testing.allocator, tmp.dir, &.{});
+    try std.testing.expectEqual(@as(usize, 1), left.len);
+    try std.testing.expectEqualStrings("kept", left[0].name);
+
+    // a chat past the head is never read, the meta still parses
+    const long = try std.mem.concat(alloc, u8, &.{ "{\"meta\": {\"title\": \"long\"}, \"chat\": [\"", "x" ** (2 * META_HEAD_BYTES), "\"]}" });
+    try std.testing.expectEqualStrings("long", parseMeta(alloc, std.testing.allocator, long[0..META_HEAD_BYTES]).?.title);
+}
+
+test "encrypting stored sessions" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+    const io = std.testing.io;
+
+    var tmp = std.testing.tmpDir(.{ .iterate = true });
+    defer tmp.cleanup();
+    {
+        const file = try tmp.dir.createFile(io, "a.json", .{});
+        defer file.close(io);
+        var buf: [256]u8 = undefined;
+        var writer = file.writer(io, &buf);
+        try writer.interface.writeAll("{\"meta\": {\"model\": \"m0\"}, \"chat\": []}");
+        try writer.interface.flush();
+    }
+
+    const vault = Vault{ .enabled = true, .key = [_]u8{3} ** vault_mod.KEY_LEN };
+    try std.testing.expectEqual(@as(usize, 1), try encryptAll(io, alloc, tmp.dir, &vault));
+    try std.testing.expectEqual(@as(usize, 0), try encryptAll(io, alloc, tmp.dir, &vault));
+
+    const entries = try list(io, alloc, std.testing.allocator, tmp.dir, &vault);
+    try std.testing.expectEqual(@as(usize, 1), entries.len);
+    try std.testing.expectEqualStrings("m0", entries[0].meta.model);
+    const locked = try list(io, alloc, std.testing.allocator, tmp.dir, &.{});
+    try std.testing.expectEqualStrings("", locked[0].meta.model);
+}
@@ -0,0 +1,257 @@
+//! The `:settings` popup: common options without opening a Lua file. A
+//! change is checked here, applied by running the same `blitz.set_*` call a
+//! config would, and kept as that call in `~/.config/blitzdenk/settings.lua`.
+//! That file loads after the global config and packs, before the project
+//! `blitz.lua`, so a project can still pin its own values. Each line ends
+//! with `-- <key>`, a later change of the same setting replaces it.
+const std = @import("std");
+const r = @import("root.zig");
+const App = r.app.App;
+
+pub const FILE = "settings.lua";
+const HEADER =
+    \\-- Written by blitzdenk from the :settings popup. Lines are replaced by key
+    \\-- when a setting changes there, anything else in this file is left alone.
+    \\
+;
+
+pub const Category = enum {
+    models,
+    permissions,
+    ui,
+    tools,
+};
+
+pub const Kind = union(enum) {
+    boolean,
+    integer: struct { min: u32, max: u32 },
+    string,
+    choice: []const []const u8,
+};
+
+pub const Id = enum {
+    model,
+    compact_model,
+    compact_threshold,
+    compact_edge,
+    max_retries,
+    replay_approvals,
+    ssh_agent_control,
+    show_thinking,
+    mouse_capture,
+    expand_messages,
+    image_preview,
+    spinner,
+    banner,
+    shell_history,
+    test_command,
+    todo_nudges,
+};
+
+pub const Setting = struct {
+    id: Id,
+    category: Category,
+    desc: []const u8,
+    kind: Kind,
+};
+
+pub const all = [_]Setting{
+    .{ .id = .model, .category = .models, .desc = "default model on the current provider", .kind = .string },
+    .{ .id = .compact_model, .category = .models, .desc = "model writing compaction summaries, empty for the agent's", .kind = .string },
+    .{ .id = .compact_threshold, .category = .models, .desc = "compact at this % of the context edge", .kind = .{ .integer = .{ .min = 1, .max = 100 } } },
+    .{ .id = .compact_edge, .category = .models, .desc = "context edge in tokens", .kind = .{ .integer = .{ .min = 1000, .max = 10_000_000 } } },
+    .{ .id = .max_retries, .category = .models, .desc = "retries of a transient provider error", .kind = .{ .integer = .{ .min = 0, .max = 20 } } },
+    .{ .id = .replay_approvals, .category = .permissions, .desc = "approve an identical request again without asking", .kind = .boolean },
+    .{ .id = .ssh_agent_control, .category = .permissions, .desc = "let the agent enter and leave ssh mode", .kind = .boolean },
+    .{ .id = .show_thinking, .category = .ui, .desc = "show model reasoning in the chat", .kind = .boolean },
+    .{ .id = .mouse_capture, .category = .ui, .desc = "wheel scrolling and in-app selection", .kind = .boolean },
+    .{ .id = .expand_messages, .category = .ui, .desc = "show huge messages in full", .kind = .boolean },
+    .{ .id = .image_preview, .category = .ui, .desc = "preview generated images inline", .kind = .boolean },
+    .{ .id = .spinner, .category = .ui, .desc = "throbber style", .kind = .{ .choice = &.{ "dots", "bar", "wave", "line", "none" } } },
+    .{ .id = .banner, .category = .ui, .desc = "project and branch on the title screen", .kind = .boolean },
+    .{ .id = .shell_history, .category = .tools, .desc = "recent shell commands in the system prompt", .kind = .{ .integer = .{ .min = 0, .max = r.shellhist.MAX_COMMANDS } } },
+    .{ .id = .test_command, .category = .tools, .desc = "command the agent runs the tests with", .kind = .string },
+    .{ .id = .todo_nudges, .category = .tools, .desc = "follow-ups when a run ends with open todos", .kind = .{ .integer = .{ .min = 0, .max = 20 } } },
+};
+
+pub fn find(key: []const u8) ?Setting {
+    const id = std.meta.stringToEnum(Id, key) orelse return null;
+    for (all) |setting| {
+        if (setting.id == id) return setting;
+    }
+    return null;
+}
+
+/// The value as the popup shows it and `:set` takes it.
+pub fn current(app: *App, alloc: std.mem.Allocator, id: Id) ![]const u8 {
+    return switch (id) {
+        .model => if (app.config.default_model.bound) app.config.default_model.getName() else "",
+        .compact_model => app.compact_model,
+        .compact_threshold => std.fmt.allocPrint(alloc, "{d}", .{app.compact_threshold}),
+        .compact_edge => std.fmt.allocPrint(alloc, "{d}", .{app.default_context_limit}),
+        .max_retries => std.fmt.allocPrint(alloc, "{d}", .{app.retry_policy.max_retries}),
+        .replay_approvals => boolStr(app.flags.replay_approvals),
+        .ssh_agent_control => boolStr(app.flags.ssh_agent_control),
+        .show_thinking => boolStr(app.flags.show_thinking),
+        .mouse_capture => boolStr(app.flags.mouse_capture),
+        .expand_messages => boolStr(app.flags.expand_messages),
+        .image_preview => boolStr(app.flags.image_preview),
+        .spinner => @tagName(app.appearance.spinner),
+        .banner => boolStr(app.appearance.banner),
+        .shell_history => std.fmt.allocPrint(alloc, "{d}", .{app.context_factory.shell_history}),
+        .test_command => app.context_factory.test_command orelse "",
+        .todo_nudges => std.fmt.allocPrint(alloc, "{d}", .{app.todo_nudge.max}),
+    };
+}
+
+fn boolStr(b: bool) []const u8 {
+    return if (b) "true" else "false";
+}
+
+/// `raw` checked against the setting's kind, as a Lua literal.
+pub fn literal(alloc: std.mem.Allocator, setting: Setting, raw: []const u8) ![]const u8 {
+    const text = std.mem.trim(u8, raw, " \t");
+    switch (setting.kind) {
+        .boolean => {
+            if (std.mem.eql(u8, text, "true") or std.mem.eql(u8, text, "on")) return "true";
+            if (std.mem.eql(u8, text, "false") or std.mem.eql(u8, text, "off")) return "false";
+            return error.NotABoolean;
+        },
+        .integer => |range| {
+            const n = std.fmt.parseInt(u32, text, 10) catch return error.NotANumber;
+            if (n < range.min or n > range.max) return error.OutOfRange;
+            return std.fmt.allocPrint(alloc, "{d}", .{n});
+        },
+        .string => return luaString(alloc, text),
+        .choice => |choices| {
+            for (choices) |choice| {
+                if (std.mem.eql(u8, choice, text)) return luaString(alloc, text);
+            }
+            return error.UnknownChoice;
+        },
+    }
+}
+
+/// What `:set` tells the user when `literal` refused a value.
+pub fn describeError(alloc: std.mem.Allocator, setting: Setting, err: anyerror) ![]const u8 {
+    return switch (err) {
+        error.NotABoolean => "expected true or false",
+        error.NotANumber => "expected a whole number",
+        error.OutOfRange => std.fmt.allocPrint(alloc, "expected {d} to {d}", .{ setting.kind.integer.min, setting.kind.integer.max }),
+        error.UnknownChoice => blk: {
+            var out = std.Io.Writer.Allocating.init(alloc);
+            try out.writer.writeAll("expected one of");
+            for (setting.kind.choice) |choice| try out.writer.print(" {s}", .{choice});
+            break :blk out.written();
+        },
+        else => @errorName(err),
+    };
+}
+
+fn luaString(alloc: std.mem.Allocator, s: []const u8) ![]const u8 {
+    var out = std.Io.Writer.Allocating.init(alloc);
+    const w = &out.writer;
+    try w.writeByte('"');
+    for (s) |ch| switch (ch) {
+        '"', '\\' => try w.print("\\{c}", .{ch}),
+        '\n' => try w.writeAll("\\n"),
+        0...9, 11...31, 127 => try w.print("\\{d:0>3}", .{ch}),
+        else => try w.writeByte(ch),
+    };
+    try w.writeByte('"');
+    return out.written();
+}
+
+/// The config line that applies `value`, a literal from `literal`. A model
+/// is saved with its provider's name, an unnamed provider is refused.
+pub fn luaCall(app: *App, alloc: std.mem.Allocator, id: Id, value: []const u8) ![]const u8 {
+    return switch (id) {
+        .model => blk: {
+            // by profile name, handles shift when providers are reordered or added
+            const name = app.config.providers[@intFromEnum(app.config.default_model.provider)].getName();
+            if (name.len == 0) return error.UnnamedProvider;
+            break :blk std.fmt.allocPrint(alloc, "blitz.use_provider({s}, {s})", .{ try luaString(alloc, name), value });
+        },
+        .compact_model => std.fmt.allocPrint(alloc, "blitz.set_compact_model({s})", .{value}),
+        .compact_threshold => std.fmt.allocPrint(alloc, "blitz.set_compact_threshold({s})", .{value}),
+        .compact_edge => std.fmt.allocPrint(alloc, "blitz.set_compact_edge({s})", .{value}),
+        .max_retries => std.fmt.allocPrint(alloc, "blitz.set_retry_policy({{ max_retries = {s}, base_delay_ms = {d}, max_delay_ms = {d}, jitter = {d} }})", .{
+            value,
+            app.retry_policy.base_delay_ms,
+            app.retry_policy.max_delay_ms,
+            app.retry_policy.jitter,
+        }),
+        .replay_approvals, .ssh_agent_control, .show_thinking, .mouse_capture, .expand_messages, .image_preview => std.fmt.allocPrint(alloc, "blitz.set_flag(\"{s}\", {s})", .{ @tagName(id), value }),
+        .spinner => std.fmt.allocPrint(alloc, "blitz.set_appearance({{ spinner = {s} }})", .{value}),
+        .banner => std.fmt.allocPrint(alloc, "blitz.set_appearance({{ banner = {s} }})", .{value}),
+        .shell_history => std.fmt.allocPrint(alloc, "blitz.set_shell_history({s})", .{value}),
+        .test_command => std.fmt.allocPrint(alloc, "blitz.set_test_command({s})", .{value}),
+        .todo_nudges => std.fmt.allocPrint(alloc, "blitz.set_todo_nudge({{ max = {s} }})", .{value}),
+    };
+}
+
+/// `content` of the settings file with the line for `id` set to `call`.
+pub fn upsert(alloc: std.mem.Allocator, content: []const u8, id: Id, call: []const u8) ![]const u8 {
+    var out = std.Io.Writer.Allocating.init(alloc);
+    const w = &out.writer;
+    const marker = try std.fmt.allocPrint(alloc, " -- {s}", .{@tagName(id)});
+    if (content.len == 0) try w.writeAll(HEADER);
+
+    var replaced = false;
+    var lines = std.mem.splitScalar(u8, std.mem.trimRight(u8, content, "\n"), '\n');
+    while (content.len > 0) {
+        const line = lines.next() orelse break;
+        if (std.mem.endsWith(u8, std.mem.trimRight(u8, line, " \r"), marker)) {
+            if (!replaced) try w.print("{s}{s}\n", .{ call, marker });
+            replaced = true;
+            continue;
+        }
+        try w.print("{s}\n", .{line});
+    }
+    if (!replaced) try w.print("{s}{s}\n", .{ call, marker });
+    return out.written();
+}
+
+/// Rewrite the line for `id` in the settings file of `config_dir`.
+pub fn save(io: std.Io, alloc: std.mem.Allocator, config_dir: std.Io.Dir, id: Id, call: []const u8) !void {
+    const content: []const u8 = blk: {
+        const file = config_dir.openFile(io, FILE, .{}) catch |err| switch (err) {
+            error.FileNotFound => break :blk "",
+            else => return err,
+        };
+        defer file.close(io);
+        var buf: [4096]u8 = undefined;
+        var reader = file.reader(io, &buf);
+        break :blk try reader.interface.allocRemaining(alloc, .limited(1024 * 1024));
+    };
+    const updated = try upsert(alloc, content, id, call);
+
+    const file = try config_dir.createFile(io, FILE, .{});
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    try writer.interface.writeAll(updated);
+    try writer.interface.flush();
+}
+
+test "settings validation and file updates" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const threshold = find("compact_threshold").?;
+    try std.testing.expectEqualStrings("80", try literal(alloc, threshold, " 80 "));
+    try std.testing.expectError(error.OutOfRange, literal(alloc, threshold, "0"));
+    try std.testing.expectError(error.NotANumber, literal(alloc, threshold, "lots"));
+    try std.testing.expectEqualStrings("true", try literal(alloc, find("banner").?, "on"));
+    try std.testing.expectError(error.UnknownChoice, literal(alloc, find("spinner").?, "spinny"));
+    try std.testing.expectEqualStrings("\"zig build \\\"test\\\"\"", try literal(alloc, find("test_command").?, "zig build \"test\""));
+    try std.testing.expect(find("nope") == null);
+
+    const first = try upsert(alloc, "", .compact_threshold, "blitz.set_compact_threshold(80)");
+    try std.testing.expect(std.mem.startsWith(u8, first, "-- Written by blitzdenk"));
+    const second = try upsert(alloc, first, .banner, "blitz.set_appearance({ banner = false })");
+    const third = try upsert(alloc, second, .compact_threshold, "blitz.set_compact_threshold(70)");
+    try std.testing.expect(std.mem.indexOf(u8, third, "(80)") == null);
+    try std.testing.expect(std.mem.endsWith(u8, third, "blitz.set_compact_threshold(70) -- compact_threshold\nblitz.set_appearance({ banner = false }) -- banner\n"));
+}
@@ -0,0 +1,147 @@
+//! Recent shell commands for the system prompt, opt-in with
+//! `blitz.set_shell_history(20)`. What was just tried in the terminal is
+//! usually what the next question is about. A project `.blitz/shell_history`
+//! (one command per line, appended from a shell hook) wins over the user's
+//! zsh, bash or fish history. Commands that look like they carry a secret
+//! are left out.
+const std = @import("std");
+
+pub const PROJECT_FILE = ".blitz/shell_history";
+pub const MAX_COMMANDS = 200;
+/// only the tail of a history file is read
+const MAX_TAIL_BYTES = 256 * 1024;
+
+pub const Format = enum {
+    /// one command per line, also bash with `#<epoch>` timestamp lines
+    plain,
+    /// `: <epoch>:<duration>;<command>` with EXTENDED_HISTORY, else plain
+    zsh,
+    /// `- cmd: <command>` entries
+    fish,
+};
+
+const user_files = [_]struct { path: []const u8, format: Format }{
+    .{ .path = ".zsh_history", .format = .zsh },
+    .{ .path = ".bash_history", .format = .plain },
+    .{ .path = ".local/share/fish/fish_history", .format = .fish },
+};
+
+const secret_words = [_][]const u8{ "password", "passwd", "secret", "token", "api_key", "apikey", "authorization:" };
+
+/// The last `n` distinct commands, oldest first. Empty when no history file
+/// is readable. The project file is looked up in the agent's `cwd`.
+pub fn load(io: std.Io, alloc: std.mem.Allocator, cwd: []const u8, home: []const u8, n: u32) ![]const []const u8 {
+    if (n == 0) return &.{};
+    const absolute = std.fs.path.isAbsolute(cwd);
+    const project: ?std.Io.Dir = if (absolute) std.Io.Dir.openDirAbsolute(io, cwd, .{}) catch null else std.Io.Dir.cwd();
+    if (project) |dir| {
+        defer if (absolute) dir.close(io);
+        if (readTail(io, alloc, dir, PROJECT_FILE)) |text| return parse(alloc, text, .plain, n);
+    }
+
+    var home_dir = std.Io.Dir.openDirAbsolute(io, home, .{}) catch return &.{};
+    defer home_dir.close(io);
+    // the most recently written file belongs to the shell in use
+    var newest: ?struct { text: []const u8, format: Format, mtime: i96 } = null;
+    for (user_files) |file| {
+        const stat = home_dir.statFile(io, file.path, .{}) catch continue;
+        if (newest != null and newest.?.mtime >= stat.mtime.nanoseconds) continue;
+        const text = readTail(io, alloc, home_dir, file.path) orelse continue;
+        newest = .{ .text = text, .format = file.format, .mtime = stat.mtime.nanoseconds };
+    }
+    const found = newest orelse return &.{};
+    return parse(alloc, found.text, found.format, n);
+}
+
+fn readTail(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, path: []const u8) ?[]const u8 {
+    const file = dir.openFile(io, path, .{}) catch return null;
+    defer file.close(io);
+    const size = (file.stat(io) catch return null).size;
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    const start = size -| MAX_TAIL_BYTES;
+    if (start > 0) reader.seekTo(start) catch return null;
+    const text = reader.interface.allocRemaining(alloc, .limited(MAX_TAIL_BYTES + 1)) catch return null;
+    // a cut first line is a partial command
+    if (start > 0) {
+        const nl = std.mem.indexOfScalar(u8, text, '\n') orelse return null;
+        return text[nl + 1 ..];
+    }
+    return text;
+}
+
+/// The last `n` distinct commands in `text`, oldest first.
+pub fn parse(alloc: std.mem.Allocator, text: []const u8, format: Format, n: u32) ![]const []const u8 {
+    const limit = @min(n, MAX_COMMANDS);
+    var newest_first = std.ArrayList([]const u8).empty;
+    var lines = std.mem.splitBackwardsScalar(u8, text, '\n');
+    collect: while (lines.next()) |line| {
+        if (newest_first.items.len >= limit) break;
+        const command = std.mem.trim(u8, commandOf(line, format) orelse continue, " \t\r");
+        if (command.len == 0 or looksSecret(command)) continue;
+        for (newest_first.items) |seen| {
+            if (std.mem.eql(u8, seen, command)) continue :collect;
+        }
+        try newest_first.append(alloc, command);
+    }
+    std.mem.reverse([]const u8, newest_first.items);
+    return newest_first.toOwnedSlice(alloc);
+}
+
+fn commandOf(line: []const u8, format: Format) ?[]const u8 {
+    switch (format) {
+        .plain => {
+            if (line.len > 1 and line[0] == '#' and std.ascii.isDigit(line[1])) return null;
+            return line;
+        },
+        .zsh => {
+            if (std.mem.startsWith(u8, line, ": ")) {
+                const semi = std.mem.indexOfScalar(u8, line, ';') orelse return null;
+                return line[semi + 1 ..];
+            }
+            return line;
+        },
+        .fish => {
+            const prefix = "- cmd: ";
+            if (!std.mem.startsWith(u8, line, prefix)) return null;
+            return line[prefix.len..];
+        },
+    }
+}
+
+fn looksSecret(command: []const u8) bool {
+    for (secret_words) |word| {
+        if (std.ascii.indexOfIgnoreCase(command, word) != null) return true;
+    }
+    return false;
+}
+
+test "shell history parsing" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const zsh =
+        \\: 1700000000:0;cargo build
+        \\: 1700000001:0;cargo test
+        \\: 1700000002:0;export GITHUB_TOKEN=ghp_x
+        \\: 1700000003:0;cargo build
+        \\git status
+        \\
+    ;
+    const commands = try parse(alloc, zsh, .zsh, 3);
+    try std.testing.expectEqual(@as(usize, 3), commands.len);
+    try std.testing.expectEqualStrings("cargo test", commands[0]);
+    try std.testing.expectEqualStrings("cargo build", commands[1]);
+    try std.testing.expectEqualStrings("git status", commands[2]);
+
+    const bash = try parse(alloc, "#1700000000\nmake\n#1700000001\nmake check\n", .plain, 10);
+    try std.testing.expectEqual(@as(usize, 2), bash.len);
+    try std.testing.expectEqualStrings("make check", bash[1]);
+
+    const fish = try parse(alloc, "- cmd: zig build\n  when: 1700000000\n- cmd: zig build test\n  when: 1700000001\n", .fish, 10);
+    try std.testing.expectEqual(@as(usize, 2), fish.len);
+    try std.testing.expectEqualStrings("zig build", fish[0]);
+
+    try std.testing.expectEqual(@as(usize, 0), (try parse(alloc, zsh, .zsh, 0)).len);
+}
@@ -0,0 +1,189 @@
+//! Dollars spent today over every blitzdenk instance on this machine. Each
+//! instance appends its priced responses to
+//! `~/.cache/blitzdenk/spend/<YYYY-MM-DD>.jsonl` (UTC days) as they finish,
+//! one line per response written with a single `O_APPEND` write, so
+//! instances running side by side don't tear each other's lines. The daily
+//! total is the sum of the file, re-read when it grew.
+//!
+//! `blitz.set_daily_budget(20)` caps that total. Every instance warns once
+//! past `NEAR_PERCENT` of the cap, and at the cap new runs are refused
+//! until the next day or a higher cap. A file that can't be read, one past
+//! `MAX_FILE_BYTES` included, refuses them too.
+const std = @import("std");
+
+pub const DIR = ".cache/blitzdenk/spend";
+/// warn once today's total passes this share of the budget
+pub const NEAR_PERCENT = 80;
+/// how often the file is checked for other instances' records
+pub const CHECK_MS = 5_000;
+const MAX_FILE_BYTES = 16 * 1024 * 1024;
+const MAX_INSTANCES = 64;
+
+/// One priced response, a line of the day's file.
+pub const Record = struct {
+    time_ms: i64,
+    pid: i32,
+    session: []const u8 = "",
+    model: []const u8,
+    dollars: f64,
+};
+
+pub const Totals = struct {
+    dollars: f64 = 0,
+    /// distinct processes that booked something
+    instances: usize = 0,
+};
+
+pub const Crossed = enum { near, reached };
+
+pub const Spend = struct {
+    /// `blitz.set_daily_budget`, dollars a day over all instances, 0 is no cap
+    budget: f64 = 0,
+    /// today's records as last read, this instance included
+    today: Totals = .{},
+    day: [10]u8 = @splat(0),
+    /// size of the day's file at the last read
+    file_size: u64 = 0,
+    fd: std.posix.fd_t = -1,
+    /// `usage_log` records already booked
+    booked: usize = 0,
+    next_check_ms: i64 = 0,
+    warned: ?Crossed = null,
+
+    pub fn deinit(self: *Spend) void {
+        if (self.fd >= 0) _ = std.c.close(self.fd);
+        self.fd = -1;
+    }
+
+    /// Append `record` to the file of `day`, opening it when the day turned.
+    pub fn book(self: *Spend, io: std.Io, home: []const u8, day: []const u8, record: Record) !void {
+        if (self.fd < 0 or !std.mem.eql(u8, &self.day, day)) try self.openDay(io, home, day);
+        var buf: [1024]u8 = undefined;
+        var w = std.Io.Writer.fixed(&buf);
+        try std.json.Stringify.value(record, .{}, &w);
+        try w.writeByte('\n');
+        if (std.c.write(self.fd, buf[0..w.end].ptr, w.end) < 0) return error.WriteFailed;
+    }
+
+    fn openDay(self: *Spend, io: std.Io, home: []const u8, day: []const u8) !void {
+        if (self.fd >= 0) _ = std.c.close(self.fd);
+        self.fd = -1;
+        self.rollOver(day);
+        var path_buf: [std.posix.PATH_MAX]u8 = undefined;
+        const dir = try std.fmt.bufPrint(&path_buf, "{s}/" ++ DIR, .{home});
+        try std.Io.Dir.cwd().createDirPath(io, dir);
+        const path = try std.fmt.bufPrint(&path_buf, "{s}/" ++ DIR ++ "/{s}.jsonl", .{ home, day });
+        const flags: std.posix.O = .{ .ACCMODE = .WRONLY, .CREAT = true, .APPEND = true };
+        self.fd = try std.posix.openat(std.posix.AT.FDCWD, path, flags, 0o644);
+    }
+
+    /// A new day starts from nothing, the warnings included.
+    fn rollOver(self: *Spend, day: []const u8) void {
+        if (std.mem.eql(u8, &self.day, day)) return;
+        @memcpy(&self.day, day[0..self.day.len]);
+        self.today = .{};
+        self.file_size = 0;
+        self.warned = null;
+    }
+
+    /// Re-read the day's file when it grew since the last read.
+    pub fn refresh(self: *Spend, io: std.Io, alloc: std.mem.Allocator, home: []const u8, day: []const u8) !void {
+        self.rollOver(day);
+        const path = try std.fmt.allocPrint(alloc, "{s}/" ++ DIR ++ "/{s}.jsonl", .{ home, day });
+        defer alloc.free(path);
+        const stat = std.Io.Dir.cwd().statFile(io, path, .{}) catch |err| switch (err) {
+            error.FileNotFound => return,
+            else => return err,
+        };
+        if (stat.size == self.file_size) return;
+        const file = try std.Io.Dir.cwd().openFile(io, path, .{});
+        defer file.close(io);
+
+        var read_buf: [4096]u8 = undefined;
+        var reader = file.reader(io, &read_buf);
+        const text = try reader.interface.allocRemaining(alloc, .limited(MAX_FILE_BYTES));
+        defer alloc.free(text);
+        self.today = try sum(alloc, text);
+        self.file_size = stat.size;
+    }
+
+    /// The budget line today's total crossed since the last call, once per
+    /// line and day.
+    pub fn crossed(self: *Spend) ?Crossed {
+        if (self.budget <= 0) return null;
+        const now: ?Crossed = if (self.today.dollars >= self.budget)
+            .reached
+        else if (self.today.dollars * 100 >= self.budget * NEAR_PERCENT)
+            .near
+        else
+            null;
+        const line = now orelse return null;
+        if (self.warned) |seen| if (@intFromEnum(seen) >= @intFromEnum(line)) return null;
+        self.warned = line;
+        return line;
+    }
+
+    pub fn reached(self: *const Spend) bool {
+        return self.budget > 0 and self.today.dollars >= self.budget;
+    }
+};
+
+/// Totals of a day's file. Lines that don't parse, e.g. one cut short by a
+/// crash, are skipped.
+pub fn sum(alloc: std.mem.Allocator, text: []const u8) !Totals {
+    var totals: Totals = .{};
+    var pids: [MAX_INSTANCES]i32 = undefined;
+    var lines = std.mem.tokenizeScalar(u8, text, '\n');
+    while (lines.next()) |line| {
+        const parsed = std.json.parseFromSlice(Record, alloc, line, .{ .ignore_unknown_fields = true }) catch continue;
+        defer parsed.deinit();
+        totals.dollars += parsed.value.dollars;
+        for (pids[0..totals.instances]) |pid| {
+            if (pid == parsed.value.pid) break;
+        } else if (totals.instances < pids.len) {
+            pids[totals.instances] = parsed.value.pid;
+            totals.instances += 1;
+        }
+    }
+    return totals;
+}
+
+/// `YYYY-MM-DD` of `now_ms`, in UTC.
+pub fn dayName(buf: *[10]u8, now_ms: i64) []const u8 {
+    const secs: u64 = @intCast(@max(@divTrunc(now_ms, std.time.ms_per_s), 0));
+    const year_day = (std.time.epoch.EpochSeconds{ .secs = secs }).getEpochDay().calculateYearDay();
+    const month_day = year_day.calculateMonthDay();
+    return std.fmt.bufPrint(buf, "{d:0>4}-{d:0>2}-{d:0>2}", .{
+        year_day.year,
+        month_day.month.numeric(),
+        month_day.day_index + 1,
+    }) catch unreachable;
+}
+
+test "daily spend over instances" {
+    const text =
+        \\{"time_ms":1,"pid":100,"session":"a","model":"gpt-4o","dollars":0.5}
+        \\{"time_ms":2,"pid":200,"session":"b","model":"gpt-4o","dollars":1.25}
+        \\{"time_ms":3,"pid":100,"session":"a","model":"claude-sonnet-4-5","dol
+        \\{"time_ms":4,"pid":100,"session":"a","model":"claude-sonnet-4-5","dollars":2.25}
+    ;
+    const totals = try sum(std.testing.allocator, text);
+    try std.testing.expectApproxEqAbs(@as(f64, 4.0), totals.dollars, 1e-9);
+    try std.testing.expectEqual(@as(usize, 2), totals.instances);
+
+    var day_buf: [10]u8 = undefined;
+    try std.testing.expectEqualStrings("2026-10-16", dayName(&day_buf, 1_792_152_000_000));
+
+    var spend: Spend = .{ .budget = 5, .today = .{ .dollars = 3 } };
+    try std.testing.expectEqual(@as(?Crossed, null), spend.crossed());
+    spend.today.dollars = 4.2;
+    try std.testing.expectEqual(@as(?Crossed, .near), spend.crossed());
+    try std.testing.expectEqual(@as(?Crossed, null), spend.crossed());
+    try std.testing.expect(!spend.reached());
+    spend.today.dollars = 5;
+    try std.testing.expectEqual(@as(?Crossed, .reached), spend.crossed());
+    try std.testing.expect(spend.reached());
+    spend.rollOver("2026-10-17");
+    try std.testing.expectEqual(@as(f64, 0), spend.today.dollars);
+    try std.testing.expect(spend.warned == null);
+}
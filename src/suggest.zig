@@ -0,0 +1,138 @@
+//! Type-ahead suggestions for the prompt. Shared by the completion popup,
+//! the command palette and the prompt footer hints so they always agree on
+//! what `tab` is going to insert.
+const std = @import("std");
+
+/// Byte range of the token under the cursor that a suggestion replaces.
+pub const Token = struct {
+    start: usize,
+    end: usize,
+    /// Text the candidates are matched against (without the trigger char for files).
+    prefix: []const u8,
+};
+
+pub const Trigger = union(enum) {
+    none,
+    /// `:` or `/` command at the start of the input.
+    command: Token,
+    /// `@path` token under the cursor.
+    file: Token,
+};
+
+pub fn triggerAt(input: []const u8, cursor: usize) Trigger {
+    if (input.len == 0) return .none;
+    const end = @min(cursor, input.len);
+
+    if (input[0] == ':' or input[0] == '/') {
+        const command_end = std.mem.indexOfScalar(u8, input[0..end], ' ') orelse end;
+        if (command_end == end) {
+            return .{ .command = .{ .start = 0, .end = command_end, .prefix = input[0..command_end] } };
+        }
+    }
+
+    var start = end;
+    while (start > 0 and !std.ascii.isWhitespace(input[start - 1])) start -= 1;
+    if (start < end and input[start] == '@') {
+        var token_end = end;
+        while (token_end < input.len and !std.ascii.isWhitespace(input[token_end])) token_end += 1;
+        return .{ .file = .{ .start = start, .end = token_end, .prefix = input[start + 1 .. end] } };
+    }
+
+    return .none;
+}
+
+pub fn startsWithIgnoreCase(value: []const u8, prefix: []const u8) bool {
+    if (prefix.len > value.len) return false;
+    for (prefix, 0..) |c, i| {
+        if (std.ascii.toLower(c) != std.ascii.toLower(value[i])) return false;
+    }
+    return true;
+}
+
+/// Append `@path` candidates for `prefix`, resolved against `cwd`. Directory
+/// entries get a trailing `/` so accepting one keeps the picker open on the
+/// next level. Hidden entries are only listed once the user typed a `.`.
+pub fn appendFileCandidates(
+    io: std.Io,
+    alloc: std.mem.Allocator,
+    cwd: []const u8,
+    prefix: []const u8,
+    out: *std.ArrayList([]const u8),
+    limit: usize,
+) !void {
+    const split = std.mem.lastIndexOfScalar(u8, prefix, '/');
+    const dir_part = if (split) |i| prefix[0 .. i + 1] else "";
+    const name_part = if (split) |i| prefix[i + 1 ..] else prefix;
+
+    const dir_path = if (dir_part.len > 0 and dir_part[0] == '/')
+        dir_part
+    else
+        try std.fmt.allocPrint(alloc, "{s}/{s}", .{ cwd, dir_part });
+
+    var dir = std.Io.Dir.openDirAbsolute(io, dir_path, .{ .iterate = true }) catch return;
+    defer dir.close(io);
+
+    const first = out.items.len;
+    var it = dir.iterate();
+    while (it.next(io) catch null) |entry| {
+        if (out.items.len - first >= limit) break;
+        if (entry.name.len > 0 and entry.name[0] == '.' and !(name_part.len > 0 and name_part[0] == '.')) continue;
+        if (!startsWithIgnoreCase(entry.name, name_part)) continue;
+
+        const slash: []const u8 = if (entry.kind == .directory) "/" else "";
+        try out.append(alloc, try std.fmt.allocPrint(alloc, "@{s}{s}{s}", .{ dir_part, entry.name, slash }));
+    }
+
+    std.mem.sort([]const u8, out.items[first..], {}, lessThan);
+}
+
+fn lessThan(_: void, a: []const u8, b: []const u8) bool {
+    return std.mem.lessThan(u8, a, b);
+}
+
+/// Replace the trigger token in `input` with `suggestion`. Returns the new
+/// cursor position.
+pub fn apply(
+    alloc: std.mem.Allocator,
+    input: *std.ArrayList(u8),
+    trigger: Trigger,
+    suggestion: []const u8,
+) !usize {
+    const token = switch (trigger) {
+        .none => return input.items.len,
+        // commands replace everything, arguments are part of the template
+        .command => Token{ .start = 0, .end = input.items.len, .prefix = "" },
+        .file => |t| t,
+    };
+    try input.replaceRange(alloc, token.start, token.end - token.start, suggestion);
+    return token.start + suggestion.len;
+}
+
+test "triggerAt" {
+    try std.testing.expect(triggerAt("", 0) == .none);
+    try std.testing.expect(triggerAt("hello", 5) == .none);
+
+    const cmd = triggerAt(":he", 3);
+    try std.testing.expectEqualStrings(":he", cmd.command.prefix);
+
+    // typing arguments ends the command hint
+    try std.testing.expect(triggerAt(":cd /tmp", 8) == .none);
+
+    const file = triggerAt("look at @src/ma please", 15);
+    try std.testing.expectEqualStrings("src/ma", file.file.prefix);
+    try std.testing.expectEqual(@as(usize, 8), file.file.start);
+    try std.testing.expectEqual(@as(usize, 15), file.file.end);
+
+    try std.testing.expect(triggerAt("mail me@", 4) == .none);
+}
+
+test "apply replaces the file token" {
+    const alloc = std.testing.allocator;
+    var input: std.ArrayList(u8) = .empty;
+    defer input.deinit(alloc);
+    try input.appendSlice(alloc, "read @sr and fix");
+
+    const cursor = try apply(alloc, &input, triggerAt(input.items, 8), "@src/");
+    try std.testing.expectEqualStrings("read @src/ and fix", input.items);
+    try std.testing.expectEqual(@as(usize, 10), cursor);
+}
@@ -0,0 +1,170 @@
+//! Project scaffolding templates, `:new <template> <name>`. A template is a
+//! directory under `~/.config/blitzdenk/templates/<template>/`:
+//!
+//!   PROMPT.md   instructions for the agent (required)
+//!   TODOS.md    `- subject: description` lines, seeded into the todo list
+//!   files/      reference skeleton, sent along with the prompt
+//!
+//! `{{name}}` is replaced with the project name everywhere.
+const std = @import("std");
+const prv = @import("provider");
+
+pub const TEMPLATE_DIR = "templates";
+pub const PLACEHOLDER = "{{name}}";
+const MAX_FILE_BYTES = 64 * 1024;
+const MAX_SKELETON_BYTES = 128 * 1024;
+const MAX_DEPTH = 8;
+
+pub const Template = struct {
+    prompt: []const u8,
+    todos: []const prv.agent.Todo,
+
+    /// Deep copy into `alloc`, to outlive the allocator it was loaded with.
+    pub fn dupe(self: Template, alloc: std.mem.Allocator) !Template {
+        const todos = try alloc.dupe(prv.agent.Todo, self.todos);
+        for (todos) |*todo| {
+            todo.subject = try alloc.dupe(u8, todo.subject);
+            todo.description = try alloc.dupe(u8, todo.description);
+            todo.depends_on = try alloc.dupe(u32, todo.depends_on);
+        }
+        return .{ .prompt = try alloc.dupe(u8, self.prompt), .todos = todos };
+    }
+};
+
+pub const LoadError = error{ InvalidName, TemplateNotFound, MissingPrompt } || std.mem.Allocator.Error;
+
+/// `config_dir` is `~/.config/blitzdenk`.
+pub fn load(io: std.Io, alloc: std.mem.Allocator, config_dir: std.Io.Dir, template: []const u8, name: []const u8) LoadError!Template {
+    if (!validName(template) or !validName(name)) return error.InvalidName;
+
+    var path_buf: [std.fs.max_path_bytes]u8 = undefined;
+    const path = std.fmt.bufPrint(&path_buf, TEMPLATE_DIR ++ "/{s}", .{template}) catch return error.InvalidName;
+    var dir = config_dir.openDir(io, path, .{ .iterate = true }) catch return error.TemplateNotFound;
+    defer dir.close(io);
+
+    const instructions = readFile(io, alloc, dir, "PROMPT.md") orelse return error.MissingPrompt;
+    const todos_src = readFile(io, alloc, dir, "TODOS.md") orelse "";
+
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    const w = &out.writer;
+    w.print("Scaffold a new project `{s}` in `./{s}` from the `{s}` template.\n\n", .{ name, name, template }) catch return error.OutOfMemory;
+    w.writeAll(instructions) catch return error.OutOfMemory;
+    if (dir.openDir(io, "files", .{ .iterate = true })) |files_dir| {
+        var files = files_dir;
+        defer files.close(io);
+        w.writeAll("\n\n# Skeleton\n\nCreate these files, adapted to the project:\n") catch return error.OutOfMemory;
+        var budget: usize = MAX_SKELETON_BYTES;
+        writeSkeleton(io, alloc, files, "", 0, w, &budget) catch |err| switch (err) {
+            error.OutOfMemory => return error.OutOfMemory,
+            else => std.log.warn("template {s}: skeleton incomplete: {s}", .{ template, @errorName(err) }),
+        };
+    } else |_| {}
+
+    return .{
+        .prompt = try substitute(alloc, out.written(), name),
+        .todos = try parseTodos(alloc, try substitute(alloc, todos_src, name)),
+    };
+}
+
+fn validName(name: []const u8) bool {
+    if (name.len == 0 or name[0] == '.') return false;
+    for (name) |ch| {
+        if (!std.ascii.isAlphanumeric(ch) and ch != '-' and ch != '_' and ch != '.') return false;
+    }
+    return true;
+}
+
+fn readFile(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir, name: []const u8) ?[]const u8 {
+    const file = dir.openFile(io, name, .{}) catch return null;
+    defer file.close(io);
+    var read_buf: [1024]u8 = undefined;
+    var file_reader = file.reader(io, &read_buf);
+    return file_reader.interface.allocRemaining(alloc, .limited(MAX_FILE_BYTES)) catch null;
+}
+
+fn writeSkeleton(
+    io: std.Io,
+    alloc: std.mem.Allocator,
+    dir: std.Io.Dir,
+    prefix: []const u8,
+    depth: usize,
+    w: *std.Io.Writer,
+    budget: *usize,
+) !void {
+    if (depth > MAX_DEPTH) return;
+    var it = dir.iterate();
+    while (try it.next(io)) |entry| {
+        const rel = try std.fmt.allocPrint(alloc, "{s}{s}", .{ prefix, entry.name });
+        switch (entry.kind) {
+            .directory => {
+                var sub = try dir.openDir(io, entry.name, .{ .iterate = true });
+                defer sub.close(io);
+                const sub_prefix = try std.fmt.allocPrint(alloc, "{s}/", .{rel});
+                try writeSkeleton(io, alloc, sub, sub_prefix, depth + 1, w, budget);
+            },
+            .file => {
+                const content = readFile(io, alloc, dir, entry.name) orelse continue;
+                if (content.len > budget.*) {
+                    try w.print("\n## {s}\n(too large, create it from scratch)\n", .{rel});
+                    continue;
+                }
+                budget.* -= content.len;
+                try w.print("\n## {s}\n```\n{s}\n```\n", .{ rel, std.mem.trimRight(u8, content, "\n") });
+            },
+            else => {},
+        }
+    }
+}
+
+pub fn substitute(alloc: std.mem.Allocator, text: []const u8, name: []const u8) ![]u8 {
+    return std.mem.replaceOwned(u8, alloc, text, PLACEHOLDER, name);
+}
+
+/// `- subject: description` per line, the description is optional.
+pub fn parseTodos(alloc: std.mem.Allocator, content: []const u8) ![]prv.agent.Todo {
+    var todos: std.ArrayList(prv.agent.Todo) = .empty;
+    var it = std.mem.splitScalar(u8, content, '\n');
+    while (it.next()) |line| {
+        const trimmed = std.mem.trim(u8, line, " \t\r");
+        if (!std.mem.startsWith(u8, trimmed, "- ")) continue;
+        if (todos.items.len >= prv.agent.TodoList.max_todos) break;
+        const body = std.mem.trim(u8, trimmed[2..], " ");
+        const sep = std.mem.indexOf(u8, body, ": ");
+        const subject = if (sep) |s| body[0..s] else body;
+        if (subject.len == 0) continue;
+        try todos.append(alloc, .{
+            .id = @intCast(todos.items.len + 1),
+            .subject = subject,
+            .description = if (sep) |s| std.mem.trim(u8, body[s + 2 ..], " ") else subject,
+            .state = .pending,
+        });
+    }
+    return todos.toOwnedSlice(alloc);
+}
+
+test "parseTodos" {
+    const alloc = std.testing.allocator;
+    const src = try substitute(alloc,
+        \\# todos
+        \\- Cargo setup: create {{name}}/Cargo.toml with axum and tokio
+        \\- Health route
+        \\not a todo
+        \\-
+    , "my-api");
+    defer alloc.free(src);
+    const todos = try parseTodos(alloc, src);
+    defer alloc.free(todos);
+
+    try std.testing.expectEqual(@as(usize, 2), todos.len);
+    try std.testing.expectEqualStrings("Cargo setup", todos[0].subject);
+    try std.testing.expectEqualStrings("create my-api/Cargo.toml with axum and tokio", todos[0].description);
+    try std.testing.expectEqualStrings("Health route", todos[1].description);
+    try std.testing.expectEqual(@as(u32, 2), todos[1].id);
+}
+
+test "validName" {
+    try std.testing.expect(validName("axum-service"));
+    try std.testing.expect(!validName("../etc"));
+    try std.testing.expect(!validName("a/b"));
+    try std.testing.expect(!validName(""));
+}
@@ -0,0 +1,116 @@
+//! Guess the project's test command from the manifests and lockfiles in
+//! the cwd. `blitz.set_test_command` in a project blitz.lua wins over the
+//! guess. The result lands in the system prompt's env section and is
+//! readable from Lua with `blitz.test_command()` for verification hooks.
+const std = @import("std");
+
+const Rule = struct {
+    file: []const u8,
+    command: []const u8,
+    /// node runners need a `test` script, make a `test:` target
+    needs: enum { nothing, package_script, make_target } = .nothing,
+};
+
+/// First match wins, lockfiles before their manifest.
+const rules = [_]Rule{
+    .{ .file = "build.zig", .command = "zig build test" },
+    .{ .file = "Cargo.toml", .command = "cargo test" },
+    .{ .file = "go.mod", .command = "go test ./..." },
+    .{ .file = "bun.lockb", .command = "bun test" },
+    .{ .file = "bun.lock", .command = "bun test" },
+    .{ .file = "pnpm-lock.yaml", .command = "pnpm test", .needs = .package_script },
+    .{ .file = "yarn.lock", .command = "yarn test", .needs = .package_script },
+    .{ .file = "package.json", .command = "npm test", .needs = .package_script },
+    .{ .file = "pytest.ini", .command = "pytest" },
+    .{ .file = "pyproject.toml", .command = "pytest" },
+    .{ .file = "setup.py", .command = "pytest" },
+    .{ .file = "tox.ini", .command = "tox" },
+    .{ .file = "mix.exs", .command = "mix test" },
+    .{ .file = "pom.xml", .command = "mvn test" },
+    .{ .file = "build.gradle", .command = "./gradlew test" },
+    .{ .file = "build.gradle.kts", .command = "./gradlew test" },
+    .{ .file = "Makefile", .command = "make test", .needs = .make_target },
+};
+
+/// What `pick` needs to know about the project root.
+pub const Probe = struct {
+    present: []const []const u8,
+    package_json: []const u8 = "",
+    makefile: []const u8 = "",
+
+    fn has(self: Probe, file: []const u8) bool {
+        for (self.present) |p| {
+            if (std.mem.eql(u8, p, file)) return true;
+        }
+        return false;
+    }
+};
+
+pub fn pick(probe: Probe) ?[]const u8 {
+    for (rules) |rule| {
+        if (!probe.has(rule.file)) continue;
+        switch (rule.needs) {
+            .nothing => return rule.command,
+            .package_script => if (hasPackageTestScript(probe.package_json)) return rule.command,
+            .make_target => if (hasMakeTestTarget(probe.makefile)) return rule.command,
+        }
+    }
+    return null;
+}
+
+/// Test command for the project in `dir`, null when nothing matches.
+pub fn detect(io: std.Io, alloc: std.mem.Allocator, dir: std.Io.Dir) ?[]const u8 {
+    var present = std.ArrayList([]const u8).empty;
+    defer present.deinit(alloc);
+    for (rules) |rule| {
+        _ = dir.statFile(io, rule.file, .{}) catch continue;
+        present.append(alloc, rule.file) catch return null;
+    }
+    if (present.items.len == 0) return null;
+
+    var package_buf: [16 * 1024]u8 = undefined;
+    var make_buf: [16 * 1024]u8 = undefined;
+    return pick(.{
+        .present = present.items,
+        .package_json = readHead(io, dir, "package.json", &package_buf),
+        .makefile = readHead(io, dir, "Makefile", &make_buf),
+    });
+}
+
+fn readHead(io: std.Io, dir: std.Io.Dir, path: []const u8, buf: []u8) []const u8 {
+    const file = dir.openFile(io, path, .{}) catch return "";
+    defer file.close(io);
+    var read_buf: [256]u8 = undefined;
+    var file_reader = file.reader(io, &read_buf);
+    const n = file_reader.interface.readSliceShort(buf) catch return "";
+    return buf[0..n];
+}
+
+fn hasPackageTestScript(content: []const u8) bool {
+    const scripts = std.mem.indexOf(u8, content, "\"scripts\"") orelse return false;
+    return std.mem.indexOf(u8, content[scripts..], "\"test\"") != null;
+}
+
+fn hasMakeTestTarget(content: []const u8) bool {
+    var it = std.mem.splitScalar(u8, content, '\n');
+    while (it.next()) |line| {
+        if (std.mem.startsWith(u8, line, "test:")) return true;
+    }
+    return false;
+}
+
+test "pick test command" {
+    try std.testing.expectEqualStrings("cargo test", pick(.{ .present = &.{ "Makefile", "Cargo.toml" } }).?);
+    try std.testing.expectEqualStrings("pnpm test", pick(.{
+        .present = &.{ "package.json", "pnpm-lock.yaml" },
+        .package_json = "{\"scripts\": {\"test\": \"vitest\"}}",
+    }).?);
+    // no test script, fall through to the python manifest
+    try std.testing.expectEqualStrings("pytest", pick(.{
+        .present = &.{ "package.json", "pyproject.toml" },
+        .package_json = "{\"name\": \"test\"}",
+    }).?);
+    try std.testing.expectEqualStrings("make test", pick(.{ .present = &.{"Makefile"}, .makefile = "all:\n\tcc a.c\ntest: all\n" }).?);
+    try std.testing.expect(pick(.{ .present = &.{"Makefile"}, .makefile = "all:\n" }) == null);
+    try std.testing.expect(pick(.{ .present = &.{} }) == null);
+}
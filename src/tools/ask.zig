@@ -8,7 +8,7 @@ pub const AskTool = prv.tool.Tool{
     .def = .{
         .name = "ask_user",
         .description =
-        \\Ask the user a multiple-choice question to clarify intent, resolve ambiguity, or let them pick an approach. The user picks one of the provided options, or types a custom reply. The selected text (or typed message) is returned as the tool result.
+        \\Ask the user a question to clarify intent, resolve ambiguity, or let them pick an approach. Prefer this over ending your turn with an open question whenever you need a decision to continue. The user picks one of the provided options, or types a custom reply. The selected text (or typed message) is returned as the tool result.
         \\
         ,
         .parameters_schema =
@@ -17,9 +17,9 @@ pub const AskTool = prv.tool.Tool{
         \\  "properties": {
         \\      "header": {"type": "string", "description": "Very short label displayed as a chip/tag. Examples: 'Auth method', 'Library', 'Approach'."},
         \\      "question": {"type": "string", "description": "The complete question to ask the user. Should be clear, specific, and end with a question mark."},
-        \\      "options": {"type": "array", "items": {"type": "string"}, "description": "1-8 short option strings the user can pick from. A custom-message option is always appended by the UI."}
+        \\      "options": {"type": "array", "items": {"type": "string"}, "description": "Up to 8 short option strings the user can pick from. Omit for a free-text answer. A custom-message option is always appended by the UI."}
         \\  },
-        \\  "required": ["header", "question", "options"]
+        \\  "required": ["header", "question"]
         \\}
         ,
     },
@@ -29,14 +29,13 @@ pub const AskTool = prv.tool.Tool{
 pub const Args = struct {
     header: []const u8,
     question: []const u8,
-    options: []const []const u8,
+    options: []const []const u8 = &.{},
 };
 
 fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
     const args = r.parseArgs(Args, ctx.alloc, call) orelse
-        return r.errResult(call, "invalid JSON arguments: expected {header, question, options}");
+        return r.errResult(call, "invalid JSON arguments: expected {header, question, options?}");
 
-    if (args.options.len == 0) return r.errResult(call, "options must contain at least one entry");
     if (args.options.len > MAX_OPTIONS) return r.errResult(call, "too many options (max 8)");
 
     r.setToolStatusPrint(ctx, call, "question {s}", .{args.question});
@@ -24,6 +24,14 @@ const approval_commands = [_][]const u8{
     "awk",     "tee",
 };
 
+/// Commands writing every path operand, and ones writing only their last.
+const write_all_commands = [_][]const u8{
+    "rm",    "rmdir",    "mv",    "touch",
+    "mkdir", "tee",      "chmod", "chown",
+    "chgrp", "truncate",
+};
+const write_last_commands = [_][]const u8{ "cp", "ln", "install", "rsync" };
+
 const Classification = enum {
     blocked,
     needs_approval,
@@ -151,6 +159,8 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
         return r.errResult(call, "rg pattern contains unquoted `$`. Shell expands `$var` before rg sees it, silently corrupting the regex. Single-quote the pattern: `rg 'pattern'`");
     }
 
+    if (guardWriteTargets(ctx, call, args.command)) |blocked| return blocked;
+
     // Replace full cwd paths with "." for cleaner output (stack buffer)
     var buf: [512]u8 = undefined;
     const cleaned_command_str = if (ctx.cwd.len > 0)
@@ -165,13 +175,27 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
 
     r.setToolStatusPrint(ctx, call, "{s}{s}", .{trunc, dots});
 
+    const rule = configRule(ctx, args.command);
+    if (rule == .deny) {
+        auditRule(ctx, "denied", rule.deny, args.command);
+        const msg = std.fmt.allocPrint(ctx.alloc, "command denied by the bash rule `{s}`", .{rule.deny}) catch "command denied by a bash rule";
+        return r.errResult(call, msg);
+    }
+
     const need_perm = switch (classifyCommand(args.command)) {
         .blocked => return r.errResult(call, "command is blocked for safety"),
-        .needs_approval, .sudo => true,
+        .needs_approval => blk: {
+            if (rule == .allow) {
+                auditRule(ctx, "allowed", rule.allow, args.command);
+                break :blk false;
+            }
+            break :blk !isAllowlisted(ctx, args.command);
+        },
+        .sudo => true,
         .allowed => false,
     };
 
-    if (need_perm) {
+    if (need_perm or sandboxBlind(ctx, args.command)) {
         const decision = ctx.requestPerm(call.id, .always_check, .{ .call = .{
             .tool_name = call.name,
             .tool_arguments = call.arguments,
@@ -236,16 +260,16 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
     const response = ctx.alloc.alloc(u8, res.stderr.len + res.stdout.len) catch return r.errResult(call, "oom");
     @memcpy(response[0..res.stdout.len], res.stdout);
     @memcpy(response[res.stdout.len..], res.stderr);
-    return r.okResult(call, r.truncateOutputToOwned(ctx.alloc, response, r.MAX_DISPLAY_BYTES, r.MAX_DISPLAY_LINES));
+    return r.okResult(call, r.spillOutputToOwned(ctx, call, response));
 }
 
-const RunError = error{ Timeout, Canceled, ExecFailed };
+pub const RunError = error{ Timeout, Canceled, ExecFailed };
 
 /// Race a foreground exec future against a wall-clock deadline. On timeout,
 /// the spawned process is killed and stdout/stderr are discarded. Polls both
 /// the slot's done flag and the deadline at 25 ms intervals; cooperative
 /// cancellation via ctx.isCanceled() also unwinds.
-fn runWithDeadline(
+pub fn runWithDeadline(
     ctx: prv.tool.ToolContext,
     opts: prv.exec.CmdPool.RunOpts,
     deadline_ms: i64,
@@ -319,6 +343,52 @@ fn classifyCommand(cmd: []const u8) Classification {
     return result;
 }
 
+/// Every segment matches the project allowlist. Subshells and redirects
+/// always go through the prompt.
+fn isAllowlisted(ctx: prv.tool.ToolContext, cmd: []const u8) bool {
+    if (containsSubshell(cmd) or containsRedirects(cmd)) return false;
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const g = app.bash_allow.lock(ctx.io);
+    defer g.unlock();
+    return allSegmentsMatch(g.ptr, cmd);
+}
+
+/// Verdict of the `blitz.set_bash_rules` patterns.
+fn configRule(ctx: prv.tool.ToolContext, cmd: []const u8) r.r.allowlist.BashRules.Decision {
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const g = app.bash_rules.lock(ctx.io);
+    defer g.unlock();
+    const decision = g.ptr.decide(ctx.alloc, cmd) catch return .ask;
+    // the patterns belong to the config, a reload may replace them
+    return switch (decision) {
+        .ask => .ask,
+        .allow => |p| .{ .allow = ctx.alloc.dupe(u8, p) catch return .ask },
+        .deny => |p| .{ .deny = ctx.alloc.dupe(u8, p) catch "" },
+    };
+}
+
+/// Rule decisions go into the chat, so the session shows what ran unasked.
+fn auditRule(ctx: prv.tool.ToolContext, verdict: []const u8, pattern: []const u8, cmd: []const u8) void {
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    // the entry is read on the UI thread, after this call returned
+    const parts = app.sessionAlloc().alloc(r.r.app.ChatPart, 1) catch return;
+    parts[0] = .{ .message = std.fmt.allocPrint(app.sessionAlloc(), "bash rule `{s}` {s}: {s}", .{ pattern, verdict, cmd }) catch return };
+    app.cmd_queue.append(ctx.io, .{ .push_chat_entry = .{ .role = .system, .parts = parts } }) catch {};
+}
+
+fn allSegmentsMatch(list: *const r.r.allowlist.BashAllowList, cmd: []const u8) bool {
+    var any = false;
+    var rest: []const u8 = cmd;
+    while (rest.len > 0) {
+        const segment, const remaining = nextSegment(rest);
+        rest = remaining;
+        if (std.mem.trim(u8, segment, " \t\n\r").len == 0) continue;
+        if (list.match(segment) == null) return false;
+        any = true;
+    }
+    return any;
+}
+
 fn isSudo(cmd: []const u8) bool {
     if (std.mem.find(u8, cmd, "sudo") != null) return true;
     return false;
@@ -344,28 +414,228 @@ fn containsRedirects(cmd: []const u8) bool {
     return false;
 }
 
-fn nextSegment(input: []const u8) struct { []const u8, []const u8 } {
-    var i: usize = 0;
-    while (i < input.len) : (i += 1) {
-        switch (input[i]) {
-            '|' => {
-                if (i + 1 < input.len and input[i + 1] == '|') {
-                    return .{ input[0..i], if (i + 2 < input.len) input[i + 2 ..] else "" };
+pub const nextSegment = r.r.allowlist.nextSegment;
+
+/// Holds commands writing outside the workspace sandbox. Only paths spelled
+/// out in the command are seen: redirect targets and the operands of file
+/// commands, relative ones taken after any `cd` earlier in the command.
+fn guardWriteTargets(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall, cmd: []const u8) ?prv.adapter.ToolResult {
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    if (!app.sandbox.enabled) return null;
+    const home = ctx.io.environ.process_environ.getPosix("HOME");
+    const targets = writeTargets(ctx.alloc, cmd) catch return null;
+    for (targets) |target| {
+        const path = expandHome(ctx.alloc, home, target) catch continue;
+        // other variables are unknown until the shell runs
+        if (std.mem.indexOfScalar(u8, path, '$') != null) continue;
+        const resolved = std.fs.path.resolve(ctx.alloc, &.{ ctx.cwd, path }) catch continue;
+        if (r.guardWorkspacePath(ctx, call, resolved)) |blocked| return blocked;
+    }
+    return null;
+}
+
+/// The sandbox is on but can't tell where the command writes, so it asks
+/// even when a rule or the allowlist would let it run.
+fn sandboxBlind(ctx: prv.tool.ToolContext, cmd: []const u8) bool {
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    if (!app.sandbox.enabled or ctx.swarm.exec.ssh_active) return false;
+    return hiddenWrites(ctx.alloc, cmd) catch true;
+}
+
+/// Interpreters whose `-c`/`-e` code may write anywhere.
+const inline_code_commands = [_][]const u8{
+    "sh",   "bash",    "zsh",     "dash",
+    "ksh",  "fish",    "python",  "python3",
+    "perl", "ruby",    "node",    "deno",
+    "php",  "lua",     "osascript",
+};
+/// `find` actions that write or run another command.
+const find_write_actions = [_][]const u8{
+    "-delete", "-exec",   "-execdir", "-ok",
+    "-okdir",  "-fprint", "-fprint0", "-fprintf",
+    "-fls",
+};
+
+/// Writes `writeTargets` can't name: subshells, variable targets, inline
+/// interpreter code, in-place edits, `dd of=`, `find -delete`/`-exec` and
+/// commands run through `xargs`.
+pub fn hiddenWrites(alloc: std.mem.Allocator, cmd: []const u8) !bool {
+    if (containsSubshell(cmd)) return true;
+    for (try writeTargets(alloc, cmd)) |target| {
+        const rest = if (std.mem.startsWith(u8, target, "${HOME}")) target[7..] else if (std.mem.startsWith(u8, target, "$HOME")) target[5..] else target;
+        if (std.mem.indexOfScalar(u8, rest, '$') != null) return true;
+    }
+
+    var rest: []const u8 = cmd;
+    while (rest.len > 0) {
+        const segment, const remaining = nextSegment(rest);
+        rest = remaining;
+        const words = try shellWords(alloc, segment);
+        var start: usize = 0;
+        // assignments and wrappers in front of the command that runs
+        while (start < words.len) : (start += 1) {
+            const word = words[start].text;
+            if (std.mem.indexOfScalar(u8, word, '=') != null) continue;
+            if (isInList(std.fs.path.basename(word), &.{ "env", "command", "nohup", "nice", "time", "timeout" })) continue;
+            break;
+        }
+        if (start >= words.len) continue;
+        const name = std.fs.path.basename(std.mem.trimLeft(u8, words[start].text, "\\"));
+        const args = words[start + 1 ..];
+
+        if (isInList(name, &.{ "xargs", "parallel" })) return true;
+        for (args) |arg| {
+            const a = arg.text;
+            if (isInList(name, &inline_code_commands) and isInList(a, &.{ "-c", "-e", "-E", "--eval", "--command" })) return true;
+            if (isInList(name, &.{ "sed", "gsed", "perl" })) {
+                if (std.mem.startsWith(u8, a, "--in-place")) return true;
+                if (a.len > 1 and a[0] == '-' and a[1] != '-' and std.mem.indexOfScalar(u8, a, 'i') != null) return true;
+            }
+            if (isInList(name, &.{ "awk", "gawk" }) and (std.mem.eql(u8, a, "inplace") or std.mem.eql(u8, a, "-i"))) return true;
+            if (std.mem.eql(u8, name, "dd") and std.mem.startsWith(u8, a, "of=")) return true;
+            if (std.mem.eql(u8, name, "find") and isInList(a, &find_write_actions)) return true;
+        }
+    }
+    return false;
+}
+
+fn expandHome(alloc: std.mem.Allocator, home: ?[]const u8, path: []const u8) ![]const u8 {
+    const h = home orelse return path;
+    for ([_][]const u8{ "~", "$HOME", "${HOME}" }) |prefix| {
+        if (!std.mem.startsWith(u8, path, prefix)) continue;
+        const rest = path[prefix.len..];
+        if (rest.len == 0 or rest[0] == '/') return std.mem.concat(alloc, u8, &.{ h, rest });
+    }
+    return path;
+}
+
+/// Paths a command writes to, as written, relative ones joined to the
+/// directory of a preceding `cd`.
+pub fn writeTargets(alloc: std.mem.Allocator, cmd: []const u8) ![]const []const u8 {
+    var targets: std.ArrayList([]const u8) = .empty;
+    var dir: ?[]const u8 = null;
+    var rest: []const u8 = cmd;
+    while (rest.len > 0) {
+        const segment, const remaining = nextSegment(rest);
+        rest = remaining;
+
+        var name: ?[]const u8 = null;
+        var operands: std.ArrayList([]const u8) = .empty;
+        for (try shellWords(alloc, segment)) |word| switch (word.kind) {
+            .write => try targets.append(alloc, try inDir(alloc, dir, word.text)),
+            .read => {},
+            .arg => {
+                if (name == null) {
+                    // `FOO=bar cmd` assignments before the command name
+                    if (std.mem.indexOfScalar(u8, word.text, '=') != null) continue;
+                    name = std.fs.path.basename(word.text);
+                } else if (word.text.len > 0 and word.text[0] != '-') {
+                    try operands.append(alloc, word.text);
                 }
-                return .{ input[0..i], if (i + 1 < input.len) input[i + 1 ..] else "" };
             },
-            '&' => {
-                if (i + 1 < input.len and input[i + 1] == '&') {
-                    return .{ input[0..i], if (i + 2 < input.len) input[i + 2 ..] else "" };
+        };
+
+        const cmd_name = name orelse continue;
+        if (std.mem.eql(u8, cmd_name, "cd")) {
+            if (operands.items.len == 1) dir = try inDir(alloc, dir, operands.items[0]);
+            continue;
+        }
+        const written: []const []const u8 = if (isInList(cmd_name, &write_all_commands))
+            operands.items
+        else if (isInList(cmd_name, &write_last_commands) and operands.items.len > 1)
+            operands.items[operands.items.len - 1 ..]
+        else
+            &.{};
+        for (written) |path| try targets.append(alloc, try inDir(alloc, dir, path));
+    }
+    return targets.items;
+}
+
+fn inDir(alloc: std.mem.Allocator, dir: ?[]const u8, path: []const u8) ![]const u8 {
+    const base = dir orelse return path;
+    if (path.len == 0 or std.fs.path.isAbsolute(path) or path[0] == '~' or path[0] == '$') return path;
+    return std.fs.path.join(alloc, &.{ base, path });
+}
+
+const Word = struct {
+    text: []const u8,
+    kind: Kind = .arg,
+
+    const Kind = enum {
+        arg,
+        /// target of `>` or `>>`
+        write,
+        /// source of `<`, a heredoc tag or the fd of `>&`, skipped
+        read,
+    };
+};
+
+/// Splits one command into words, quotes and backslashes removed and
+/// redirect operators turned into the kind of the word after them.
+fn shellWords(alloc: std.mem.Allocator, segment: []const u8) ![]const Word {
+    var words: std.ArrayList(Word) = .empty;
+    var cur: std.ArrayList(u8) = .empty;
+    var in_word = false;
+    var kind: Word.Kind = .arg;
+    var quote: u8 = 0;
+    var i: usize = 0;
+    while (i < segment.len) : (i += 1) {
+        const ch = segment[i];
+        if (quote != 0) {
+            if (ch == quote) {
+                quote = 0;
+            } else if (ch == '\\' and quote == '"' and i + 1 < segment.len) {
+                i += 1;
+                try cur.append(alloc, segment[i]);
+            } else {
+                try cur.append(alloc, ch);
+            }
+            continue;
+        }
+        switch (ch) {
+            '\'', '"' => {
+                quote = ch;
+                in_word = true;
+            },
+            '\\' => if (i + 1 < segment.len) {
+                i += 1;
+                try cur.append(alloc, segment[i]);
+                in_word = true;
+            },
+            ' ', '\t', '\n', '\r' => if (in_word) {
+                try words.append(alloc, .{ .text = try cur.toOwnedSlice(alloc), .kind = kind });
+                kind = .arg;
+                in_word = false;
+            },
+            '>', '<' => {
+                // `2>` and `&>`: the word before names the descriptor
+                if (in_word and !isFdPrefix(cur.items)) {
+                    try words.append(alloc, .{ .text = try cur.toOwnedSlice(alloc), .kind = kind });
+                }
+                cur.clearRetainingCapacity();
+                in_word = false;
+                kind = if (ch == '>') .write else .read;
+                while (i + 1 < segment.len and segment[i + 1] == ch) i += 1;
+                if (i + 1 < segment.len and segment[i + 1] == '&') {
+                    i += 1;
+                    kind = .read;
                 }
             },
-            ';' => {
-                return .{ input[0..i], if (i + 1 < input.len) input[i + 1 ..] else "" };
+            else => {
+                try cur.append(alloc, ch);
+                in_word = true;
             },
-            else => {},
         }
     }
-    return .{ input, "" };
+    if (in_word) try words.append(alloc, .{ .text = try cur.toOwnedSlice(alloc), .kind = kind });
+    return words.items;
+}
+
+fn isFdPrefix(word: []const u8) bool {
+    if (std.mem.eql(u8, word, "&")) return true;
+    if (word.len == 0) return false;
+    for (word) |c| if (!std.ascii.isDigit(c)) return false;
+    return true;
 }
 
 fn isRgCommand(cmd: []const u8) bool {
@@ -399,3 +669,77 @@ fn isInList(name: []const u8, list: []const []const u8) bool {
     }
     return false;
 }
+
+test "allSegmentsMatch" {
+    const alloc = std.testing.allocator;
+    var list: r.r.allowlist.BashAllowList = .{};
+    defer list.deinit(alloc);
+    try list.parse(alloc, "cargo *\ngit status\n");
+
+    try std.testing.expect(allSegmentsMatch(&list, "cargo build && git status"));
+    try std.testing.expect(!allSegmentsMatch(&list, "cargo build && git push"));
+    try std.testing.expect(!allSegmentsMatch(&list, "cargo build; rm -rf target"));
+    try std.testing.expect(!allSegmentsMatch(&list, "  "));
+    try std.testing.expect(!allSegmentsMatch(&list, "git status\nrm -rf ~"));
+    try std.testing.expect(!allSegmentsMatch(&list, "git status & rm -rf ~"));
+    try std.testing.expect(allSegmentsMatch(&list, "cargo build 2>&1 | cargo fmt"));
+    try std.testing.expect(allSegmentsMatch(&list, "cargo run -- 'a && b; c'"));
+}
+
+test "writeTargets" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const targets = try writeTargets(alloc, "cd build && make > log.txt 2>&1; cp ../a.txt /tmp/b.txt; rm -rf 'old dir' ~/x; echo hi >>/etc/motd; cat < in.txt | tee out.txt");
+    const expected = [_][]const u8{ "build/log.txt", "/tmp/b.txt", "build/old dir", "~/x", "/etc/motd", "build/out.txt" };
+    try std.testing.expectEqual(expected.len, targets.len);
+    for (expected, targets) |want, got| try std.testing.expectEqualStrings(want, got);
+
+    const quiet = try writeTargets(alloc, "FOO=1 zig build test 2>/dev/null");
+    try std.testing.expectEqual(@as(usize, 1), quiet.len);
+    try std.testing.expectEqualStrings("/dev/null", quiet[0]);
+    try std.testing.expectEqual(@as(usize, 0), (try writeTargets(alloc, "ls -la src && git status")).len);
+
+    const appended = try writeTargets(alloc, "echo x | tee -a /etc/profile");
+    try std.testing.expectEqual(@as(usize, 1), appended.len);
+    try std.testing.expectEqualStrings("/etc/profile", appended[0]);
+
+    for ([_][]const u8{ "true & rm -rf ~/elsewhere", "ls\nrm -rf ~/elsewhere" }) |cmd| {
+        const chained = try writeTargets(alloc, cmd);
+        try std.testing.expectEqual(@as(usize, 1), chained.len);
+        try std.testing.expectEqualStrings("~/elsewhere", chained[0]);
+    }
+}
+
+test "hiddenWrites" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    for ([_][]const u8{
+        "sed -i 's/a/b/' /etc/hosts",
+        "sed -ni 's/a/b/p' x",
+        "perl -pi -e 's/a/b/' ~/.bashrc",
+        "dd if=/dev/zero of=/tmp/x bs=1",
+        "sh -c 'echo hi > /etc/motd'",
+        "python3 -c 'open(\"/etc/x\", \"w\")'",
+        "env FOO=1 /usr/bin/python -c pass",
+        "find / -name '*.log' -delete",
+        "find . -exec rm {} +",
+        "ls | xargs rm",
+        "echo hi > \"$TARGET\"",
+        "echo $(whoami) > out",
+        "gawk -i inplace '{print}' x",
+        "ls\nsed -i 's/a/b/' x",
+        "true & dd of=/tmp/x",
+    }) |cmd| try std.testing.expect(try hiddenWrites(alloc, cmd));
+    for ([_][]const u8{
+        "sed 's/a/b/' x > out.txt",
+        "zig build test 2>/dev/null",
+        "find src -name '*.zig'",
+        "echo hi > $HOME/notes.txt",
+        "python3 scripts/gen.py",
+        "git status && ls -la",
+    }) |cmd| try std.testing.expect(!try hiddenWrites(alloc, cmd));
+}
@@ -0,0 +1,102 @@
+//! Shell command tools declared from Lua with `blitz.register_tool` and a
+//! `command` template instead of a `func`:
+//!
+//!     blitz.register_tool({ name = "cargo_test", description = "...",
+//!         args = { filter = { type = "string" } }, command = "cargo test {filter}" })
+//!
+//! `{name}` is replaced with the shell-quoted argument, arrays expand to one
+//! quoted word per item and missing arguments to nothing. `${VAR}` is left
+//! to the shell. The expanded command runs through the bash tool, so the
+//! usual safety checks, allowlist and permission prompt apply.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+pub fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall, template: []const u8) prv.adapter.ToolResult {
+    const args = std.json.parseFromSliceLeaky(std.json.Value, ctx.alloc, call.arguments, .{}) catch
+        return r.errResult(call, "invalid JSON arguments");
+    if (args != .object) return r.errResult(call, "invalid JSON arguments: expected an object");
+
+    const command = expand(ctx.alloc, template, args.object) catch return r.errResult(call, "oom");
+    var out = std.Io.Writer.Allocating.init(ctx.alloc);
+    std.json.Stringify.value(.{ .command = command }, .{}, &out.writer) catch return r.errResult(call, "oom");
+
+    return r.bash.BashTool.func(ctx, .{
+        .id = call.id,
+        .name = call.name,
+        .arguments = out.written(),
+    });
+}
+
+/// `template` with every `{name}` filled from `args`.
+pub fn expand(alloc: std.mem.Allocator, template: []const u8, args: std.json.ObjectMap) ![]const u8 {
+    var out = std.Io.Writer.Allocating.init(alloc);
+    errdefer out.deinit();
+    const w = &out.writer;
+
+    var rest = template;
+    while (std.mem.indexOfScalar(u8, rest, '{')) |open| {
+        const close = std.mem.indexOfScalarPos(u8, rest, open + 1, '}') orelse break;
+        const name = rest[open + 1 .. close];
+        const shell_var = open > 0 and rest[open - 1] == '$';
+        if (shell_var or !validName(name)) {
+            try w.writeAll(rest[0 .. open + 1]);
+            rest = rest[open + 1 ..];
+            continue;
+        }
+        try w.writeAll(rest[0..open]);
+        if (args.get(name)) |value| try writeValue(alloc, w, value);
+        rest = rest[close + 1 ..];
+    }
+    try w.writeAll(rest);
+    return out.toOwnedSlice();
+}
+
+fn validName(name: []const u8) bool {
+    if (name.len == 0) return false;
+    for (name) |ch| {
+        if (!std.ascii.isAlphanumeric(ch) and ch != '_') return false;
+    }
+    return true;
+}
+
+fn writeValue(alloc: std.mem.Allocator, w: *std.Io.Writer, value: std.json.Value) !void {
+    switch (value) {
+        .null => {},
+        .string => |s| try writeQuoted(alloc, w, s),
+        .array => |items| for (items.items, 0..) |item, i| {
+            if (i > 0) try w.writeByte(' ');
+            try writeValue(alloc, w, item);
+        },
+        else => {
+            const text = try std.json.Stringify.valueAlloc(alloc, value, .{});
+            defer alloc.free(text);
+            try writeQuoted(alloc, w, text);
+        },
+    }
+}
+
+fn writeQuoted(alloc: std.mem.Allocator, w: *std.Io.Writer, s: []const u8) !void {
+    const quoted = try r.r.backup.shellQuote(alloc, s);
+    defer alloc.free(quoted);
+    try w.writeAll(quoted);
+}
+
+test "command templates" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const args = try std.json.parseFromSliceLeaky(std.json.Value, alloc,
+        \\{"filter": "it's slow", "jobs": 4, "files": ["a.rs", "b c.rs"], "release": null}
+    , .{});
+
+    try std.testing.expectEqualStrings(
+        "cargo test 'it'\\''s slow' -j '4' -- 'a.rs' 'b c.rs'",
+        try expand(alloc, "cargo test {filter} -j {jobs} -- {files}", args.object),
+    );
+    try std.testing.expectEqualStrings(
+        "make  ${HOME} { filter } {}",
+        try expand(alloc, "make {release} {missing}${HOME} { filter } {}", args.object),
+    );
+}
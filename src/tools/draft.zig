@@ -0,0 +1,296 @@
+//! Two-stage edits for edit-heavy sessions. The main agent describes the
+//! change, a `draft` sub-agent on a cheaper model writes the concrete
+//! search/replace blocks, and the main agent only gets the resulting diff
+//! back to verify. Opt in by giving the draft agent a model and the main
+//! agent the tool:
+//!
+//!     blitz.set_model_agent(blitz.AGENT_DRAFT, "claude-haiku-4-5", "low", 0)
+//!     blitz.set_agent_tools(blitz.AGENT_GENERAL, { ..., blitz.tools.DRAFT_EDIT })
+//!
+//! The drafter sees the whole file in its prompt and runs without tools, so
+//! a draft is one cheap request.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+/// files larger than this are left to the edit tool
+pub const MAX_DRAFT_BYTES = 256 * 1024;
+
+pub const DraftEditTool = prv.tool.Tool{
+    .def = .{
+        .name = "draft_edit",
+        .description =
+        \\Have a faster model carry out a described change to one file, then review the diff it produced.
+        \\
+        \\Write the instruction like a precise review comment: what to change, where, and the exact names and code to use. The drafter sees only the file and your instruction.
+        \\The change is applied once the user approves it and you get the diff back. Verify it: if the draft is wrong or incomplete, fix it with the edit tool rather than drafting again.
+        \\Prefer edit for one-line changes you can write out yourself. The file must have been read first.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "path": {"type": "string", "description": "The file to change"},
+        \\      "instruction": {"type": "string", "description": "What to change, precise enough to carry out without other context"}
+        \\  },
+        \\  "required": ["path", "instruction"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const alloc = ctx.alloc;
+    const Args = struct {
+        path: []const u8,
+        instruction: []const u8,
+    };
+    const args = std.json.parseFromSliceLeaky(Args, alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call,
+        \\invalid JSON arguments, expected `{"path": "...", "instruction": "..."}`
+    );
+    if (args.path.len == 0) return r.errResult(call, "path is empty");
+    if (args.instruction.len == 0) return r.errResult(call, "instruction is empty");
+    r.setToolStatusPrint(ctx, call, "draft {s}", .{args.path});
+
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const draft_def = app.context_factory.getAgent(.draft) orelse
+        return r.errResult(call, "draft agent is not defined");
+    if (draft_def.model == null) return r.errResult(call,
+        \\No draft model configured, use the edit tool. The user can set one with blitz.set_model_agent(blitz.AGENT_DRAFT, ...).
+    );
+
+    const resolved = std.fs.path.resolve(alloc, &.{ ctx.cwd, args.path }) catch
+        return r.errResult(call, "failed to resolve path");
+    if (r.guardWorkspacePath(ctx, call, resolved)) |blocked| return blocked;
+    {
+        const g = ctx.agent().file_stats.lock(ctx.io);
+        defer g.unlock();
+        if (g.ptr.get(resolved) == null) {
+            return r.errResult(call, "File has not been read yet. Read it first before drafting an edit.");
+        }
+    }
+
+    const read_res = ctx.swarm.exec.runAndWait(.{ .argv = &.{ "cat", resolved } }) catch
+        return r.errResult(call, "failed to read file");
+    defer ctx.swarm.exec.alloc.free(read_res.stdout);
+    defer ctx.swarm.exec.alloc.free(read_res.stderr);
+    if (read_res.ty != .success) return r.errResult(call, "cannot read file");
+    if (read_res.stdout.len > MAX_DRAFT_BYTES) return r.errResult(call, "file is too large to draft, use the edit tool");
+    const before = alloc.dupe(u8, read_res.stdout) catch return r.errResult(call, "oom");
+
+    const draft = runDrafter(ctx, call, args.path, args.instruction, before) catch |err| switch (err) {
+        error.Canceled => return r.errResult(call, "canceled"),
+        error.NoSlot => return r.errResult(call, "No agent slots left"),
+        error.QueueFull => return r.errResult(call, "command queue is full, inform user"),
+        error.DraftFailed => return r.errResult(call, "the draft agent failed, use the edit tool"),
+        error.OutOfMemory => return r.errResult(call, "oom"),
+    };
+
+    const blocks = parseBlocks(alloc, draft) catch |err| {
+        const msg = std.fmt.allocPrint(alloc, "draft unusable ({s}), use the edit tool. The drafter answered:\n{s}", .{ @errorName(err), draft }) catch draft;
+        return r.errResult(call, msg);
+    };
+    var after = applyBlocks(alloc, before, blocks) catch |err| {
+        const msg = std.fmt.allocPrint(alloc, "draft does not apply ({s}), nothing was written. Use the edit tool. The drafter answered:\n{s}", .{ @errorName(err), draft }) catch draft;
+        return r.errResult(call, msg);
+    };
+    if (std.mem.eql(u8, before, after)) return r.errResult(call, "the draft changes nothing");
+
+    const decision = ctx.requestPerm(call.id, .always_check, .{ .diff = .{
+        .before = before,
+        .after = after,
+        .path = args.path,
+    } });
+    switch (decision) {
+        .approved => {},
+        .edited => |content| after = content,
+        .denied => return r.errResult(call, "User declined the drafted edit"),
+        .message => |txt| {
+            const wrapped = std.fmt.allocPrint(alloc, "User declined the drafted edit and left feedback: {s}", .{txt}) catch txt;
+            return r.errResult(call, wrapped);
+        },
+        else => return r.errResult(call, "permission unresolved"),
+    }
+    if (ctx.isCanceled()) return r.errResult(call, "canceled");
+
+    r.backupBeforeWrite(ctx, resolved);
+    r.checkpointBeforeWrite(ctx, resolved, call.name);
+    const write_res = ctx.swarm.exec.runAndWait(.{
+        .argv = &.{ "tee", resolved },
+        .stdin_data = after,
+    }) catch return r.errResult(call, "failed to start process");
+    defer ctx.swarm.exec.alloc.free(write_res.stdout);
+    defer ctx.swarm.exec.alloc.free(write_res.stderr);
+    if (write_res.ty != .success) return r.errResult(call, "write failed");
+    r.recordFileChange(ctx, resolved, .modified, call.name);
+
+    const diff = renderDiff(alloc, args.path, before, after) catch return r.okResult(call, "draft applied");
+    if (decision == .edited) {
+        return r.okResult(call, std.fmt.allocPrint(alloc, "{s}\n{s}", .{ r.partialApprovalNote(alloc, args.path), diff }) catch diff);
+    }
+    return r.okResult(call, diff);
+}
+
+const DraftError = error{ Canceled, NoSlot, QueueFull, DraftFailed, OutOfMemory };
+
+/// Spawns the drafter with the file in its prompt and waits for its answer.
+fn runDrafter(
+    ctx: prv.tool.ToolContext,
+    call: prv.adapter.ToolCall,
+    path: []const u8,
+    instruction: []const u8,
+    content: []const u8,
+) DraftError![]const u8 {
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const child_id = ctx.swarm.reserveFreeSlot() orelse return error.NoSlot;
+
+    const parts = try ctx.alloc.alloc(prv.adapter.ContentPart, 1);
+    parts[0] = .{ .text = try std.fmt.allocPrint(ctx.alloc,
+        \\Instruction: {s}
+        \\
+        \\<file path="{s}">
+        \\{s}
+        \\</file>
+    , .{ instruction, path, content }) };
+
+    app.cmd_queue.append(ctx.io, .{
+        .spawn_agent = .{
+            .agent_id = child_id,
+            .parent_id = ctx.self_id,
+            .agent_type = @intFromEnum(r.reg.AgentType.draft),
+            .prompt = parts,
+            .tools_disabled = true,
+        },
+    }) catch {
+        ctx.swarm.releaseReservation(child_id);
+        return error.QueueFull;
+    };
+    r.setToolChild(ctx, call, child_id);
+
+    const slot = &ctx.swarm.slots[child_id.index];
+    slot.event.wait(ctx.io) catch {
+        r.task.stopChild(ctx, child_id);
+        return error.Canceled;
+    };
+    if (ctx.isCanceled()) {
+        r.task.stopChild(ctx, child_id);
+        return error.Canceled;
+    }
+
+    const state = ctx.swarm.getSlotState(child_id) orelse return error.DraftFailed;
+    defer ctx.swarm.releaseAgent(child_id);
+    if (state == .failed) return error.DraftFailed;
+    const usage = ctx.swarm.getAgent(child_id).?.total_usage;
+    r.setToolStatusPrint(ctx, call, "draft {s} ({d} tokens)", .{ path, usage.input_tokens + usage.output_tokens });
+    return ctx.alloc.dupe(u8, prv.tool.extractChildResult(ctx.swarm, child_id));
+}
+
+pub const Block = struct {
+    search: []const u8,
+    replace: []const u8,
+};
+
+const SEARCH_MARK = "<<<<<<< SEARCH";
+const SPLIT_MARK = "=======";
+const REPLACE_MARK = ">>>>>>> REPLACE";
+
+/// Search/replace blocks of a draft. Text around the blocks is ignored, a
+/// draft without any block is refused with the drafter's `CANNOT:` reason
+/// still in the message shown to the main agent.
+pub fn parseBlocks(alloc: std.mem.Allocator, draft: []const u8) ![]const Block {
+    var blocks: std.ArrayList(Block) = .empty;
+    var rest = draft;
+    while (std.mem.indexOf(u8, rest, SEARCH_MARK ++ "\n")) |start| {
+        const body = rest[start + SEARCH_MARK.len + 1 ..];
+        const split = markerLine(body, SPLIT_MARK) orelse return error.MissingSeparator;
+        const after_split = body[split + SPLIT_MARK.len ..];
+        const replace_start = if (after_split.len > 0 and after_split[0] == '\n') @as(usize, 1) else 0;
+        const end = markerLine(after_split, REPLACE_MARK) orelse return error.MissingReplaceEnd;
+        try blocks.append(alloc, .{
+            .search = body[0..split],
+            .replace = if (end > replace_start) after_split[replace_start..end] else "",
+        });
+        rest = after_split[end + REPLACE_MARK.len ..];
+    }
+    if (blocks.items.len == 0) return error.NoBlocks;
+    return blocks.items;
+}
+
+/// Offset of `marker` standing on a line of its own, the text before it
+/// keeps its final newline.
+fn markerLine(text: []const u8, marker: []const u8) ?usize {
+    var pos: usize = 0;
+    while (std.mem.indexOfPos(u8, text, pos, marker)) |at| {
+        const line_start = at == 0 or text[at - 1] == '\n';
+        const end = at + marker.len;
+        const line_end = end == text.len or text[end] == '\n' or text[end] == '\r';
+        if (line_start and line_end) return at;
+        pos = at + 1;
+    }
+    return null;
+}
+
+/// Applies the blocks in order, each search text must occur exactly once.
+pub fn applyBlocks(alloc: std.mem.Allocator, content: []const u8, blocks: []const Block) ![]const u8 {
+    var current = content;
+    for (blocks) |block| {
+        if (block.search.len == 0) return error.EmptySearch;
+        const at = std.mem.indexOf(u8, current, block.search) orelse return error.SearchNotFound;
+        if (std.mem.indexOfPos(u8, current, at + 1, block.search) != null) return error.SearchNotUnique;
+        current = try std.mem.concat(alloc, u8, &.{ current[0..at], block.replace, current[at + block.search.len ..] });
+    }
+    return current;
+}
+
+/// The diff the main agent verifies, in the shape of the diff popup.
+fn renderDiff(alloc: std.mem.Allocator, path: []const u8, before: []const u8, after: []const u8) ![]const u8 {
+    var lines: std.ArrayList(r.tui.DiffLine) = .empty;
+    r.r.app.emitDiffLines(&lines, .{ .path = path, .before = before, .after = after }, alloc);
+
+    var out: std.Io.Writer.Allocating = .init(alloc);
+    try out.writer.print("draft applied to {s}, verify it and fix mistakes with edit:\n", .{path});
+    for (lines.items) |line| {
+        const prefix = switch (line.kind) {
+            .context => " ",
+            .addition => "+",
+            .deletion => "-",
+            .header => "",
+        };
+        try out.writer.print("{s}{s}\n", .{ prefix, line.content });
+    }
+    return out.written();
+}
+
+test "draft blocks apply in order and must be unique" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const draft =
+        \\<<<<<<< SEARCH
+        \\const a = 1;
+        \\=======
+        \\const a = 2;
+        \\>>>>>>> REPLACE
+        \\<<<<<<< SEARCH
+        \\// old
+        \\
+        \\=======
+        \\>>>>>>> REPLACE
+    ;
+    const blocks = try parseBlocks(alloc, draft);
+    try std.testing.expectEqual(@as(usize, 2), blocks.len);
+    try std.testing.expectEqualStrings("const a = 1;\n", blocks[0].search);
+    try std.testing.expectEqualStrings("const a = 2;\n", blocks[0].replace);
+    try std.testing.expectEqualStrings("", blocks[1].replace);
+
+    const after = try applyBlocks(alloc, "// old\n\nconst a = 1;\nconst b = a;\n", blocks);
+    try std.testing.expectEqualStrings("const a = 2;\nconst b = a;\n", after);
+
+    try std.testing.expectError(error.SearchNotUnique, applyBlocks(alloc, "const a = 1;\nconst a = 1;\n", blocks[0..1]));
+    try std.testing.expectError(error.SearchNotFound, applyBlocks(alloc, "const b = 1;\n", blocks[0..1]));
+    try std.testing.expectError(error.NoBlocks, parseBlocks(alloc, "CANNOT: the file has no such function"));
+}
@@ -56,6 +56,7 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
 
     const resolved = std.fs.path.resolve(alloc, &.{ ctx.cwd, args.path }) catch
         return r.errResult(call, "failed to resolve path");
+    if (r.guardWorkspacePath(ctx, call, resolved)) |blocked| return blocked;
 
     if (std.mem.eql(u8, args.old_string, args.new_string)) {
         return r.errResult(call, "No changes to make: old_string and new_string are exactly the same.");
@@ -95,7 +96,7 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
         const diag = diagnoseMismatch(alloc, file_content, args.old_string);
         return r.errResult(call, diag);
     }
-    const new_content = replacement.?;
+    var new_content = replacement.?;
 
     const decision = ctx.requestPerm(call.id, .always_check, .{ .diff = .{
         .before = file_content,
@@ -104,6 +105,7 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
     } });
     switch (decision) {
         .approved => {},
+        .edited => |content| new_content = content,
         .denied => return r.errResult(call, "User declined edit"),
         .message => |txt| {
             const wrapped = std.fmt.allocPrint(
@@ -118,6 +120,8 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
 
     if (ctx.isCanceled()) return r.errResult(call, "canceled");
 
+    r.backupBeforeWrite(ctx, resolved);
+    r.checkpointBeforeWrite(ctx, resolved, call.name);
     const write_res = ctx.swarm.exec.runAndWait(.{
         .argv = &.{ "tee", resolved },
         .stdin_data = new_content,
@@ -133,6 +137,9 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
         return r.errResult(call, msg);
     }
 
+    r.recordFileChange(ctx, resolved, .modified, "edit");
+
+    if (decision == .edited) return r.okResult(call, r.partialApprovalNote(alloc, args.path));
     return r.okResult(call, std.fmt.allocPrint(alloc, "edit applied to {s}", .{args.path}) catch "edit applied successfully");
 }
 
@@ -0,0 +1,132 @@
+//! `eval`: run a small snippet in a throwaway directory to check a bit of
+//! logic (bit math, a regex, an edge case) without touching project files.
+//! Every run asks for permission, the snippet is arbitrary code. With
+//! `bwrap` it runs without network on a read-only root, only its temp dir
+//! is writable. Without it `unshare` still cuts the network, and as a last
+//! resort it runs unisolated. While the workspace sandbox is on a run
+//! without `bwrap` is refused, it could write anywhere.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+const DEFAULT_TIMEOUT_MS = 10_000;
+const MAX_TIMEOUT_MS = 30_000;
+
+pub const Language = enum { zig, python, node };
+
+pub const EvalTool = prv.tool.Tool{
+    .def = .{
+        .name = "eval",
+        .description =
+        \\Run a small self-contained snippet and return its output. Use it to verify small logic questions (bit math, regexes, parsing edge cases) instead of editing project files or writing scratch files.
+        \\
+        \\The snippet starts in a fresh temporary directory that is deleted afterwards, it cannot import project code. It has no network and, where bwrap is installed, only its temporary directory is writable, so never use it to touch files or the network. Zig snippets containing `test "..."` blocks run with `zig test`, otherwise with `zig run`. The user approves every run.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "language": {"type": "string", "enum": ["zig", "python", "node"]},
+        \\      "code": {"type": "string", "description": "Complete program, printed output is returned"},
+        \\      "timeout_ms": {"type": "number", "default": 10000, "description": "Kill the snippet after this many milliseconds, at most 30000"}
+        \\  },
+        \\  "required": ["language", "code"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const Args = struct {
+        language: Language,
+        code: []const u8,
+        timeout_ms: i64 = DEFAULT_TIMEOUT_MS,
+    };
+    const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call, "invalid arguments: expected {\"language\": \"zig|python|node\", \"code\": \"...\"}");
+
+    if (std.mem.trim(u8, args.code, " \t\r\n").len == 0) return r.errResult(call, "empty snippet");
+    const lines = std.mem.count(u8, args.code, "\n") + 1;
+    r.setToolStatusPrint(ctx, call, "eval {s} ({d} lines)", .{ @tagName(args.language), lines });
+
+    const decision = ctx.requestPerm(call.id, .always_check, .{ .call = .{
+        .tool_name = call.name,
+        .tool_arguments = call.arguments,
+    } });
+    switch (decision) {
+        .approved => {},
+        .denied => return r.errResult(call, "User declined eval"),
+        .message => |txt| {
+            const wrapped = std.fmt.allocPrint(ctx.alloc, "User declined eval and left feedback: {s}", .{txt}) catch txt;
+            return r.errResult(call, wrapped);
+        },
+        else => return r.errResult(call, "permission unresolved"),
+    }
+    if (ctx.isCanceled()) return r.errResult(call, "canceled");
+
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    // like bash writes, the sandbox does not cover the remote over ssh
+    const strict = app.sandbox.enabled and !ctx.swarm.exec.ssh_active;
+    const res = r.bash.runWithDeadline(ctx, .{
+        .argv = &.{ "/bin/sh", "-c", script(args.language, args.code, strict) },
+        .stdin_data = args.code,
+    }, std.math.clamp(args.timeout_ms, 1, MAX_TIMEOUT_MS)) catch |err| switch (err) {
+        error.Timeout => return r.errResult(call, "snippet timed out and was killed"),
+        error.Canceled => return r.errResult(call, "canceled"),
+        error.ExecFailed => return r.errResult(call, "exec failed"),
+    };
+    defer ctx.swarm.exec.alloc.free(res.stdout);
+    defer ctx.swarm.exec.alloc.free(res.stderr);
+
+    const response = ctx.alloc.alloc(u8, res.stderr.len + res.stdout.len) catch return r.errResult(call, "oom");
+    @memcpy(response[0..res.stdout.len], res.stdout);
+    @memcpy(response[res.stdout.len..], res.stderr);
+    const output = r.spillOutputToOwned(ctx, call, response);
+    if (res.ty != .success) {
+        const msg = std.fmt.allocPrint(ctx.alloc, "snippet failed:\n{s}", .{output}) catch output;
+        return r.errResult(call, msg);
+    }
+    return r.okResult(call, if (output.len == 0) "(no output)" else output);
+}
+
+/// Shell script reading the snippet from stdin into a temp dir and running
+/// it there through `isolate`, the dir is removed on exit. `strict` refuses
+/// to run without `bwrap`.
+pub fn script(language: Language, code: []const u8, strict: bool) []const u8 {
+    return switch (strict) {
+        inline else => |s| switch (language) {
+            .zig => if (std.mem.indexOf(u8, code, "test \"") != null)
+                wrap(s, "zig", "zig test snippet.zig")
+            else
+                wrap(s, "zig", "zig run snippet.zig"),
+            .python => wrap(s, "py", "python3 snippet.py"),
+            .node => wrap(s, "js", "node snippet.js"),
+        },
+    };
+}
+
+const prelude = "d=$(mktemp -d) || exit 1; trap 'rm -rf \"$d\"' EXIT; cd \"$d\" || exit 1; export ZIG_GLOBAL_CACHE_DIR=\"$d/.zig-global\"; ";
+/// read-only root, private /tmp, no network, only the temp dir writable
+const bwrap = "bwrap --ro-bind / / --dev /dev --proc /proc --tmpfs /tmp --bind \"$d\" \"$d\" --chdir \"$d\" --unshare-all --die-with-parent";
+const has_bwrap = "command -v bwrap >/dev/null 2>&1 && bwrap --ro-bind / / --unshare-all true 2>/dev/null";
+
+fn wrap(comptime strict: bool, comptime ext: []const u8, comptime cmd: []const u8) []const u8 {
+    const fallback = if (strict)
+        "else echo 'eval: bwrap is needed to isolate the snippet while the workspace sandbox is on' >&2; return 126; fi; }; "
+    else
+        "elif unshare -rn true 2>/dev/null; then unshare -rn \"$@\"; else \"$@\"; fi; }; ";
+    return prelude ++ "isolate() { if " ++ has_bwrap ++ "; then " ++ bwrap ++ " \"$@\"; " ++ fallback ++
+        "cat > snippet." ++ ext ++ " && isolate " ++ cmd;
+}
+
+test "eval scripts" {
+    try std.testing.expect(std.mem.endsWith(u8, script(.zig, "pub fn main() void {}", false), "isolate zig run snippet.zig"));
+    try std.testing.expect(std.mem.endsWith(u8, script(.zig, "test \"bits\" {}", false), "isolate zig test snippet.zig"));
+    try std.testing.expect(std.mem.endsWith(u8, script(.python, "print(1 << 3)", false), "isolate python3 snippet.py"));
+    try std.testing.expect(std.mem.startsWith(u8, script(.node, "", false), "d=$(mktemp -d)"));
+    try std.testing.expect(std.mem.indexOf(u8, script(.node, "", false), "unshare -rn") != null);
+    try std.testing.expect(std.mem.indexOf(u8, script(.node, "", true), "unshare -rn") == null);
+    try std.testing.expect(std.mem.indexOf(u8, script(.python, "", true), "--unshare-all --die-with-parent") != null);
+}
@@ -0,0 +1,194 @@
+//! `git_commit`: stage files and commit them with a conventional-commit
+//! message. The diff of the files against HEAD is shown in the permission
+//! prompt, they are staged only after approval and the index is put back
+//! when the commit fails. A tree with merge conflicts is refused.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+const MAX_PREVIEW_BYTES = 256 * 1024;
+
+pub const CONVENTIONAL_TYPES = [_][]const u8{ "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert" };
+
+pub const GitCommitTool = prv.tool.Tool{
+    .def = .{
+        .name = "git_commit",
+        .description =
+        \\Stage the given files and commit them. The user reviews their diff before anything is staged or committed.
+        \\
+        \\The message must follow Conventional Commits: `type(scope): subject`, type one of feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert. Scope and a `!` for breaking changes are optional. Subject in imperative mood, no trailing period, at most 72 characters. Add a body after a blank line when the why is not obvious.
+        \\
+        \\Only the listed files are committed, other staged changes stay staged. Refuses while the working tree has unresolved conflicts. Returns the commit hash.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "files": {"type": "array", "items": {"type": "string"}, "description": "Paths to stage and commit, relative to the cwd"},
+        \\      "message": {"type": "string", "description": "Conventional commit message"}
+        \\  },
+        \\  "required": ["files","message"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const Args = struct {
+        files: []const []const u8,
+        message: []const u8,
+    };
+    const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call, "invalid arguments");
+
+    if (args.files.len == 0) return r.errResult(call, "no files given");
+    const message = std.mem.trim(u8, args.message, " \t\r\n");
+    if (!isConventional(message)) return r.errResult(call,
+        \\message is not a conventional commit, use `type(scope): subject` with type one of feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert
+    );
+
+    const subject = std.mem.sliceTo(message, '\n');
+    r.setToolStatusPrint(ctx, call, "git commit: {s}", .{subject});
+
+    const conflicts = switch (git(ctx, &.{ "diff", "--name-only", "--diff-filter=U" })) {
+        .ok => |out| out,
+        .failed => |err| return gitErr(ctx, call, "not a git repository", err),
+    };
+    if (std.mem.trim(u8, conflicts, " \n").len > 0) {
+        const msg = std.fmt.allocPrint(ctx.alloc, "refusing to commit, unresolved conflicts in:\n{s}", .{conflicts}) catch "refusing to commit, unresolved conflicts";
+        return r.errResult(call, msg);
+    }
+
+    const diff = preview(ctx, args.files) catch |err| switch (err) {
+        error.OutOfMemory => return r.errResult(call, "oom"),
+        error.GitFailed => return r.errResult(call, "git diff failed"),
+    };
+    if (std.mem.trim(u8, diff, " \n").len == 0) return r.errResult(call, "nothing to commit in the given files");
+
+    const decision = ctx.requestPerm(call.id, .always_check, .{ .commit = .{
+        .message = message,
+        .files = args.files,
+        .diff = diff[0..@min(diff.len, MAX_PREVIEW_BYTES)],
+    } });
+    switch (decision) {
+        .approved => {},
+        .denied => return r.errResult(call, "User declined the commit, nothing was staged"),
+        .message => |txt| {
+            const wrapped = std.fmt.allocPrint(ctx.alloc, "User declined the commit (nothing was staged) and left feedback: {s}", .{txt}) catch txt;
+            return r.errResult(call, wrapped);
+        },
+        else => return r.errResult(call, "permission unresolved"),
+    }
+    if (ctx.isCanceled()) return r.errResult(call, "canceled");
+
+    // the index as it was, put back when staging or the commit fails
+    const index_tree = switch (git(ctx, &.{"write-tree"})) {
+        .ok => |out| std.mem.trim(u8, out, " \n"),
+        .failed => |err| return gitErr(ctx, call, "git write-tree failed", err),
+    };
+    const add = argv(ctx.alloc, &.{ "add", "--" }, args.files) orelse return r.errResult(call, "oom");
+    switch (git(ctx, add)) {
+        .ok => {},
+        .failed => |err| {
+            _ = git(ctx, &.{ "read-tree", index_tree });
+            return gitErr(ctx, call, "git add failed", err);
+        },
+    }
+    const commit = argv(ctx.alloc, &.{ "commit", "-q", "-m", message, "--" }, args.files) orelse return r.errResult(call, "oom");
+    switch (git(ctx, commit)) {
+        .ok => {},
+        .failed => |err| {
+            _ = git(ctx, &.{ "read-tree", index_tree });
+            return gitErr(ctx, call, "git commit failed, the index is as it was", err);
+        },
+    }
+
+    const hash = switch (git(ctx, &.{ "rev-parse", "--short", "HEAD" })) {
+        .ok => |out| std.mem.trim(u8, out, " \n"),
+        .failed => "?",
+    };
+    r.setToolStatusPrint(ctx, call, "git commit {s}: {s}", .{ hash, subject });
+    const text = std.fmt.allocPrint(ctx.alloc, "committed {s}: {s}", .{ hash, subject }) catch "committed";
+    return r.okResult(call, text);
+}
+
+/// What committing `files` changes: their diff against HEAD, staged and
+/// unstaged, and the untracked ones listed. Nothing touches the index.
+fn preview(ctx: prv.tool.ToolContext, files: []const []const u8) error{ OutOfMemory, GitFailed }![]const u8 {
+    const against_head = argv(ctx.alloc, &.{ "diff", "HEAD", "--stat", "--patch", "--" }, files) orelse return error.OutOfMemory;
+    const diff = switch (git(ctx, against_head)) {
+        .ok => |out| out,
+        // no commit yet, the working tree against the empty index
+        .failed => switch (git(ctx, argv(ctx.alloc, &.{ "diff", "--stat", "--patch", "--" }, files) orelse return error.OutOfMemory)) {
+            .ok => |out| out,
+            .failed => return error.GitFailed,
+        },
+    };
+    const list = argv(ctx.alloc, &.{ "ls-files", "--others", "--exclude-standard", "--" }, files) orelse return error.OutOfMemory;
+    const untracked = switch (git(ctx, list)) {
+        .ok => |out| out,
+        .failed => return error.GitFailed,
+    };
+    var out: std.ArrayList(u8) = .empty;
+    try out.appendSlice(ctx.alloc, diff);
+    var names = std.mem.tokenizeScalar(u8, untracked, '\n');
+    while (names.next()) |name| {
+        try out.print(ctx.alloc, "new file {s}\n", .{name});
+    }
+    return out.items;
+}
+
+/// `type(scope)!: subject`, the scope and `!` optional.
+pub fn isConventional(message: []const u8) bool {
+    const subject = std.mem.sliceTo(message, '\n');
+    const colon = std.mem.indexOf(u8, subject, ": ") orelse return false;
+    if (std.mem.trim(u8, subject[colon + 2 ..], " ").len == 0) return false;
+
+    var head = subject[0..colon];
+    if (std.mem.endsWith(u8, head, "!")) head = head[0 .. head.len - 1];
+    if (std.mem.indexOfScalar(u8, head, '(')) |open| {
+        if (!std.mem.endsWith(u8, head, ")") or head.len - open < 3) return false;
+        head = head[0..open];
+    }
+    for (CONVENTIONAL_TYPES) |ty| {
+        if (std.mem.eql(u8, head, ty)) return true;
+    }
+    return false;
+}
+
+/// `head ++ files`, the git arguments for a command taking a path list.
+fn argv(alloc: std.mem.Allocator, head: []const []const u8, files: []const []const u8) ?[]const []const u8 {
+    return std.mem.concat(alloc, []const u8, &.{ head, files }) catch null;
+}
+
+const GitResult = union(enum) {
+    /// stdout
+    ok: []const u8,
+    /// stderr, or why git could not run
+    failed: []const u8,
+};
+
+fn git(ctx: prv.tool.ToolContext, args: []const []const u8) GitResult {
+    const full = argv(ctx.alloc, &.{"git"}, args) orelse return .{ .failed = "oom" };
+    const res = ctx.swarm.exec.runAndWait(.{ .cwd = ctx.cwd, .argv = full }) catch return .{ .failed = "failed to spawn git" };
+    defer ctx.swarm.exec.alloc.free(res.stdout);
+    defer ctx.swarm.exec.alloc.free(res.stderr);
+    if (res.ty != .success) return .{ .failed = ctx.alloc.dupe(u8, std.mem.trim(u8, res.stderr, " \n")) catch "git failed" };
+    return .{ .ok = ctx.alloc.dupe(u8, res.stdout) catch return .{ .failed = "oom" } };
+}
+
+fn gitErr(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall, what: []const u8, stderr: []const u8) prv.adapter.ToolResult {
+    const msg = std.fmt.allocPrint(ctx.alloc, "{s}: {s}", .{ what, stderr }) catch what;
+    return r.errResult(call, msg);
+}
+
+test "conventional commit messages" {
+    try std.testing.expect(isConventional("feat: add git commit tool"));
+    try std.testing.expect(isConventional("fix(tui)!: keep scroll on resize\n\nbody"));
+    try std.testing.expect(!isConventional("Add git commit tool"));
+    try std.testing.expect(!isConventional("feature: add it"));
+    try std.testing.expect(!isConventional("fix(): empty scope"));
+    try std.testing.expect(!isConventional("fix: "));
+}
@@ -0,0 +1,137 @@
+//! `memory`: the agent's notes about the project, kept in
+//! `.blitz/memory.md` across sessions and sent with the system prompt. See
+//! `memory.zig` for the file format and limits. Every change is shown as a
+//! diff of the file and needs approval, a note outlives the session.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+const store = r.r.memory;
+
+pub const MemoryTool = prv.tool.Tool{
+    .def = .{
+        .name = "memory",
+        .description =
+        \\Project memory that outlives this session. The notes are already in your system prompt under "Project memory".
+        \\
+        \\Save durable facts a later session would otherwise have to rediscover: build and test commands, conventions, pitfalls, where things live, user preferences for this project. Not task progress, use todos for that. Notes are one line each, grouped by section. Fix or remove notes that turn out wrong.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "action": {"type": "string", "enum": ["read", "add", "replace", "remove"]},
+        \\      "section": {"type": "string", "description": "Section heading for add, e.g. \"Build\" (default \"Notes\")"},
+        \\      "note": {"type": "string", "description": "Note text for add and replace, a single line"},
+        \\      "match": {"type": "string", "description": "Text of the note to replace or remove, must match exactly one note"}
+        \\  },
+        \\  "required": ["action"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+const Action = enum { read, add, replace, remove };
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const Args = struct {
+        action: Action,
+        section: []const u8 = "",
+        note: []const u8 = "",
+        match: []const u8 = "",
+    };
+    const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call, "invalid arguments: expected {\"action\": \"read|add|replace|remove\", ...}");
+
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    // the change is made on a draft, the lock is not held over the dialog
+    const before = blk: {
+        const g = app.memory.lock(ctx.io);
+        defer g.unlock();
+        var out = std.Io.Writer.Allocating.init(ctx.alloc);
+        g.ptr.render(&out.writer) catch return r.errResult(call, "oom");
+        break :blk out.written();
+    };
+    var draft: store.Memory = .{};
+    draft.parse(ctx.alloc, before) catch return r.errResult(call, "oom");
+
+    switch (args.action) {
+        .read => {
+            r.setToolStatusPrint(ctx, call, "read memory", .{});
+            if (draft.notes.items.len == 0) return r.okResult(call, "memory is empty");
+            return r.okResult(call, before);
+        },
+        .add => {
+            r.setToolStatusPrint(ctx, call, "remember {s}", .{args.note});
+            draft.add(ctx.alloc, args.section, args.note) catch |err| return failed(ctx, call, err);
+        },
+        .replace => {
+            r.setToolStatusPrint(ctx, call, "update memory {s}", .{args.note});
+            const idx = draft.find(args.match) catch |err| return failed(ctx, call, err);
+            draft.replace(ctx.alloc, idx, args.note) catch |err| return failed(ctx, call, err);
+        },
+        .remove => {
+            r.setToolStatusPrint(ctx, call, "forget {s}", .{args.match});
+            const idx = draft.find(args.match) catch |err| return failed(ctx, call, err);
+            draft.remove(ctx.alloc, idx);
+        },
+    }
+
+    var after = std.Io.Writer.Allocating.init(ctx.alloc);
+    draft.render(&after.writer) catch return r.errResult(call, "oom");
+    const decision = ctx.requestPerm(call.id, .always_check, .{ .diff = .{
+        .before = before,
+        .after = after.written(),
+        .path = store.FILENAME,
+    } });
+    var content = after.written();
+    switch (decision) {
+        .approved => {},
+        .edited => |edited| content = edited,
+        .denied => return r.errResult(call, "User declined the memory change"),
+        .message => |txt| {
+            const wrapped = std.fmt.allocPrint(ctx.alloc, "User declined the memory change and left feedback: {s}", .{txt}) catch txt;
+            return r.errResult(call, wrapped);
+        },
+        else => return r.errResult(call, "permission unresolved"),
+    }
+    if (ctx.isCanceled()) return r.errResult(call, "canceled");
+
+    const g = app.memory.lock(ctx.io);
+    defer g.unlock();
+    const memory = g.ptr;
+    var current = std.Io.Writer.Allocating.init(ctx.alloc);
+    memory.render(&current.writer) catch return r.errResult(call, "oom");
+    if (!std.mem.eql(u8, current.written(), before)) return r.errResult(call, "memory changed meanwhile, read it again and redo the change");
+    var next: store.Memory = .{};
+    next.parse(app.gpa, content) catch |err| {
+        next.deinit(app.gpa);
+        return failed(ctx, call, err);
+    };
+    memory.deinit(app.gpa);
+    memory.* = next;
+
+    memory.save(ctx.io, ctx.cwd) catch |err| return failed(ctx, call, err);
+    const msg = std.fmt.allocPrint(ctx.alloc, "saved to {s} ({d} notes, {d}/{d} bytes)", .{
+        store.FILENAME,
+        memory.notes.items.len,
+        memory.size(),
+        store.MAX_BYTES,
+    }) catch "saved";
+    return r.okResult(call, msg);
+}
+
+fn failed(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall, err: anyerror) prv.adapter.ToolResult {
+    const reason = switch (err) {
+        error.EmptyNote => "note is empty",
+        error.NoteTooLong => std.fmt.comptimePrint("note is longer than {d} bytes, keep it to one line", .{store.MAX_NOTE_LEN}),
+        error.DuplicateNote => "that note already exists",
+        error.MemoryFull => std.fmt.comptimePrint("memory is full ({d} bytes), remove or merge notes first", .{store.MAX_BYTES}),
+        error.NoteNotFound => "no note matches, call with action read to see them",
+        error.AmbiguousNote => "several notes match, quote more of the one you mean",
+        else => std.fmt.allocPrint(ctx.alloc, "memory failed: {s}", .{@errorName(err)}) catch "memory failed",
+    };
+    return r.errResult(call, reason);
+}
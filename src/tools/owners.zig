@@ -0,0 +1,286 @@
+//! `who_owns`: likely owners and reviewers of paths, from the CODEOWNERS
+//! rule that matches each path and a `git blame` summary of who wrote its
+//! lines. Directories, which blame cannot take, get the authors of their
+//! recent commits instead. Read-only, no permission needed.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+/// where GitHub looks for the file, first hit wins
+pub const CODEOWNERS_PATHS = [_][]const u8{ ".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS" };
+const MAX_PATHS = 20;
+const TOP_AUTHORS = 3;
+const LOG_COMMITS = "200";
+
+pub const WhoOwnsTool = prv.tool.Tool{
+    .def = .{
+        .name = "who_owns",
+        .description =
+        \\Likely owners of files or directories: the matching CODEOWNERS rule and the main authors by `git blame` (recent commit authors for directories).
+        \\
+        \\Use it before finishing a change to name who should review it, or to find who to ask about unfamiliar code.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "paths": {"type": "array", "items": {"type": "string"}, "description": "Files or directories, relative to the cwd"}
+        \\  },
+        \\  "required": ["paths"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const Args = struct {
+        paths: []const []const u8,
+    };
+    const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call, "invalid arguments");
+    if (args.paths.len == 0) return r.errResult(call, "no paths given");
+    const paths = args.paths[0..@min(args.paths.len, MAX_PATHS)];
+
+    r.setToolStatusPrint(ctx, call, "who owns {s}{s}", .{ paths[0], if (paths.len > 1) " ..." else "" });
+
+    // CODEOWNERS patterns are relative to the repository root
+    const prefix = switch (git(ctx, &.{ "rev-parse", "--show-prefix" })) {
+        .ok => |out| std.mem.trim(u8, out, " \r\n"),
+        .failed => |err| {
+            const msg = std.fmt.allocPrint(ctx.alloc, "not a git repository: {s}", .{err}) catch "not a git repository";
+            return r.errResult(call, msg);
+        },
+    };
+    const rules = loadCodeowners(ctx);
+
+    var out = std.Io.Writer.Allocating.init(ctx.alloc);
+    const w = &out.writer;
+    for (paths) |path| {
+        describe(ctx, w, rules, prefix, path) catch return r.errResult(call, "oom");
+    }
+    if (rules.len == 0) w.writeAll("(no CODEOWNERS file)\n") catch {};
+    return r.okResult(call, out.written());
+}
+
+fn describe(ctx: prv.tool.ToolContext, w: *std.Io.Writer, rules: []const Rule, prefix: []const u8, path: []const u8) !void {
+    const rel = if (std.mem.startsWith(u8, path, "./")) path[2..] else path;
+    const repo_path = std.mem.trimRight(u8, try std.fmt.allocPrint(ctx.alloc, "{s}{s}", .{ prefix, rel }), "/");
+    try w.print("{s}\n", .{path});
+
+    if (owners(rules, repo_path)) |rule| {
+        try w.print("  owners: {s} (CODEOWNERS line {d}: {s})\n", .{ rule.owners, rule.line, rule.pattern });
+    } else if (rules.len > 0) {
+        try w.writeAll("  owners: none in CODEOWNERS\n");
+    }
+
+    var blamed = true;
+    const text = switch (git(ctx, &.{ "blame", "-w", "--line-porcelain", "--", rel })) {
+        .ok => |out| out,
+        .failed => blk: {
+            blamed = false;
+            break :blk switch (git(ctx, &.{ "log", "-n", LOG_COMMITS, "--format=author %an%nauthor-mail <%ae>", "--", rel })) {
+                .ok => |out| out,
+                .failed => |err| {
+                    try w.print("  git: {s}\n", .{err});
+                    return;
+                },
+            };
+        },
+    };
+    const authors = try countAuthors(ctx.alloc, text);
+    if (authors.len == 0) {
+        try w.writeAll("  authors: no history\n");
+        return;
+    }
+    try w.writeAll(if (blamed) "  authors by lines:" else "  authors by recent commits:");
+    try writeAuthors(w, authors);
+}
+
+pub const Rule = struct {
+    pattern: []const u8,
+    /// the owners as written, `@org/team @user mail@example.com`
+    owners: []const u8,
+    line: usize,
+};
+
+fn loadCodeowners(ctx: prv.tool.ToolContext) []const Rule {
+    for (CODEOWNERS_PATHS) |file| {
+        const spec = std.fmt.allocPrint(ctx.alloc, ":{s}", .{file}) catch return &.{};
+        // the copy in the index, as committed or staged
+        const content = switch (git(ctx, &.{ "show", spec })) {
+            .ok => |out| out,
+            .failed => continue,
+        };
+        return parseCodeowners(ctx.alloc, content) catch &.{};
+    }
+    return &.{};
+}
+
+pub fn parseCodeowners(alloc: std.mem.Allocator, content: []const u8) ![]const Rule {
+    var rules = std.ArrayList(Rule).empty;
+    var lines = std.mem.splitScalar(u8, content, '\n');
+    var line_no: usize = 0;
+    while (lines.next()) |raw| {
+        line_no += 1;
+        const line = std.mem.trim(u8, std.mem.sliceTo(raw, '#'), " \t\r");
+        if (line.len == 0) continue;
+        const split = std.mem.indexOfAny(u8, line, " \t") orelse line.len;
+        try rules.append(alloc, .{
+            .pattern = line[0..split],
+            .owners = std.mem.trim(u8, line[split..], " \t"),
+            .line = line_no,
+        });
+    }
+    return rules.items;
+}
+
+/// The last rule matching `path`, as in GitHub. A rule without owners
+/// leaves the path unowned.
+pub fn owners(rules: []const Rule, path: []const u8) ?Rule {
+    var i = rules.len;
+    while (i > 0) {
+        i -= 1;
+        if (!matches(rules[i].pattern, path)) continue;
+        return if (rules[i].owners.len > 0) rules[i] else null;
+    }
+    return null;
+}
+
+/// gitignore style: a pattern with a slash before its end is anchored at
+/// the root, one without matches at any depth, and a matching directory
+/// covers everything below it. `docs/*` stops at files directly in `docs/`,
+/// as on GitHub.
+pub fn matches(pattern: []const u8, path: []const u8) bool {
+    const dir_only = std.mem.endsWith(u8, pattern, "/");
+    var pat = std.mem.trimRight(u8, pattern, "/");
+    if (pat.len == 0) return false;
+    const anchored = std.mem.indexOfScalar(u8, pat, '/') != null;
+    if (pat[0] == '/') pat = pat[1..];
+
+    var start: usize = 0;
+    while (start <= path.len) {
+        if (matchesFrom(pat, path[start..], dir_only)) return true;
+        if (anchored) return false;
+        start = (std.mem.indexOfScalarPos(u8, path, start, '/') orelse return false) + 1;
+    }
+    return false;
+}
+
+/// `pat` matches `path` itself or one of its parent directories.
+fn matchesFrom(pat: []const u8, path: []const u8, dir_only: bool) bool {
+    if (!dir_only and r.r.sensitive.globMatch(pat, path)) return true;
+    if (std.mem.endsWith(u8, pat, "/*")) return false;
+    var i: usize = 0;
+    while (std.mem.indexOfScalarPos(u8, path, i, '/')) |slash| : (i = slash + 1) {
+        if (r.r.sensitive.globMatch(pat, path[0..slash])) return true;
+    }
+    return false;
+}
+
+pub const Author = struct {
+    name: []const u8,
+    count: usize,
+};
+
+/// Tallies the `author` lines of `git blame --line-porcelain` or of the
+/// matching `git log` format, most frequent first.
+pub fn countAuthors(alloc: std.mem.Allocator, text: []const u8) ![]const Author {
+    var counts: std.StringArrayHashMapUnmanaged(usize) = .empty;
+    var name: []const u8 = "";
+    var lines = std.mem.splitScalar(u8, text, '\n');
+    while (lines.next()) |line| {
+        if (std.mem.startsWith(u8, line, "author ")) {
+            name = line["author ".len..];
+        } else if (std.mem.startsWith(u8, line, "author-mail ")) {
+            const key = try std.fmt.allocPrint(alloc, "{s} {s}", .{ name, line["author-mail ".len..] });
+            const entry = try counts.getOrPut(alloc, key);
+            entry.value_ptr.* = if (entry.found_existing) entry.value_ptr.* + 1 else 1;
+        }
+    }
+
+    const authors = try alloc.alloc(Author, counts.count());
+    for (counts.keys(), counts.values(), authors) |key, count, *author| author.* = .{ .name = key, .count = count };
+    std.mem.sortUnstable(Author, authors, {}, moreLines);
+    return authors;
+}
+
+fn moreLines(_: void, a: Author, b: Author) bool {
+    if (a.count != b.count) return a.count > b.count;
+    return std.mem.lessThan(u8, a.name, b.name);
+}
+
+fn writeAuthors(w: *std.Io.Writer, authors: []const Author) !void {
+    var total: usize = 0;
+    for (authors) |author| total += author.count;
+    for (authors[0..@min(authors.len, TOP_AUTHORS)], 0..) |author, i| {
+        try w.print("{s} {s} {d}%", .{ if (i == 0) "" else ",", author.name, author.count * 100 / total });
+    }
+    if (authors.len > TOP_AUTHORS) try w.print(", {d} more", .{authors.len - TOP_AUTHORS});
+    try w.writeByte('\n');
+}
+
+const GitResult = union(enum) {
+    /// stdout
+    ok: []const u8,
+    /// stderr, or why git could not run
+    failed: []const u8,
+};
+
+fn git(ctx: prv.tool.ToolContext, args: []const []const u8) GitResult {
+    const full = std.mem.concat(ctx.alloc, []const u8, &.{ &.{"git"}, args }) catch return .{ .failed = "oom" };
+    const res = ctx.swarm.exec.runAndWait(.{ .cwd = ctx.cwd, .argv = full }) catch return .{ .failed = "failed to spawn git" };
+    defer ctx.swarm.exec.alloc.free(res.stdout);
+    defer ctx.swarm.exec.alloc.free(res.stderr);
+    if (res.ty != .success) return .{ .failed = ctx.alloc.dupe(u8, std.mem.trim(u8, res.stderr, " \n")) catch "git failed" };
+    return .{ .ok = ctx.alloc.dupe(u8, res.stdout) catch return .{ .failed = "oom" } };
+}
+
+test "codeowners matching and blame summary" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const rules = try parseCodeowners(alloc,
+        \\# default owners
+        \\*               @org/core
+        \\*.lua           @alice   # config people
+        \\/src/provider/  @bob
+        \\docs/*          @carol
+        \\build/
+        \\
+    );
+    try std.testing.expectEqual(@as(usize, 5), rules.len);
+    try std.testing.expectEqualStrings("@org/core", owners(rules, "src/app.zig").?.owners);
+    try std.testing.expectEqualStrings("@alice", owners(rules, "src/blitz_defs.lua").?.owners);
+    try std.testing.expectEqualStrings("@bob", owners(rules, "src/provider/batch.zig").?.owners);
+    try std.testing.expectEqualStrings("@carol", owners(rules, "docs/index.md").?.owners);
+    try std.testing.expectEqualStrings("@org/core", owners(rules, "docs/api/index.md").?.owners);
+    try std.testing.expect(owners(rules, "tools/build/out.txt") == null);
+    try std.testing.expect(!matches("/src/provider/", "lib/src/provider/x.zig"));
+
+    const blame =
+        \\4b0e2f1 1 1 2
+        \\author Alice
+        \\author-mail <alice@example.com>
+        \\	const std = @import("std");
+        \\4b0e2f1 2 2
+        \\author Alice
+        \\author-mail <alice@example.com>
+        \\	const r = @import("root.zig");
+        \\9c1d3a0 3 3 1
+        \\author Bob
+        \\author-mail <bob@example.com>
+        \\	pub fn main() void {}
+        \\
+    ;
+    const authors = try countAuthors(alloc, blame);
+    try std.testing.expectEqual(@as(usize, 2), authors.len);
+    try std.testing.expectEqualStrings("Alice <alice@example.com>", authors[0].name);
+    try std.testing.expectEqual(@as(usize, 2), authors[0].count);
+
+    var out = std.Io.Writer.Allocating.init(alloc);
+    try writeAuthors(&out.writer, authors);
+    try std.testing.expectEqualStrings(" Alice <alice@example.com> 66%, Bob <bob@example.com> 33%\n", out.written());
+}
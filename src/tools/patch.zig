@@ -108,20 +108,95 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
 
     if (args.patch.len == 0) return r.errResult(call, "patch is empty");
 
-    // 1. Parse the patch.
     const parsed = parsePatch(alloc, args.patch) catch |err| {
         const msg = std.fmt.allocPrint(alloc, "patch parse error: {s}", .{@errorName(err)}) catch
             "patch parse error";
         return r.errResult(call, msg);
     };
-    const patch = parsed.value;
+    return applyPatch(ctx, call, parsed.value);
+}
+
+pub const ApplyPatchTool = prv.tool.Tool{
+    .def = .{
+        .name = "apply_patch",
+        .description =
+        \\Apply a unified diff, the output of `git diff` or `diff -u`, to one or more files.
+        \\Every hunk is checked against the current file contents before anything is written, then the whole diff is applied or nothing is: if one file fails, the files already written are restored.
+        \\- `a/` and `b/` path prefixes are stripped, `/dev/null` as the old path adds a file, as the new path deletes one, differing paths rename.
+        \\- Hunks are located by their context lines, the line numbers in `@@` headers are ignored. Keep the usual 3 lines of context around every change.
+        \\- Files you change must have been read first.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "diff": {"type": "string", "description": "The unified diff"}
+        \\  },
+        \\  "required": ["diff"]
+        \\}
+        ,
+    },
+    .func = &runApplyPatch,
+};
+
+const ApplyPatchArgs = struct { diff: []const u8 };
 
+fn runApplyPatch(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const alloc = ctx.alloc;
+    r.setToolStatusPrint(ctx, call, "apply_patch", .{});
+
+    const args = (std.json.parseFromSlice(ApplyPatchArgs, alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call,
+        \\invalid JSON arguments, expected `{"diff": "..."}`
+    )).value;
+    if (args.diff.len == 0) return r.errResult(call, "diff is empty");
+
+    // models trained on the envelope format send it here as well
+    const patch = if (peekTrimmedStartsWith(stripCommonWrappers(args.diff), "*** Begin Patch"))
+        (parsePatch(alloc, args.diff) catch |err| return parseErrorResult(alloc, call, err)).value
+    else
+        parseUnifiedDiff(alloc, args.diff) catch |err| return parseErrorResult(alloc, call, err);
+    return applyPatch(ctx, call, patch);
+}
+
+fn parseErrorResult(alloc: std.mem.Allocator, call: prv.adapter.ToolCall, err: ParseError) prv.adapter.ToolResult {
+    const detail = switch (err) {
+        error.NoMatch => "no `--- a/path` and `+++ b/path` file header found",
+        error.MissingHunkContext => "a hunk without context or removed lines cannot be located, regenerate it with context",
+        else => @errorName(err),
+    };
+    const msg = std.fmt.allocPrint(alloc, "diff parse error: {s}", .{detail}) catch "diff parse error";
+    return r.errResult(call, msg);
+}
+
+/// One command of a patch, checked and rendered but not written yet.
+const Staged = struct {
+    /// path as the model wrote it, for messages and the permission prompt
+    path: []const u8,
+    resolved: []const u8,
+    cmd: PatchCommand,
+    preview: Preview,
+};
+
+/// Validates every command against the current files, asks for each file,
+/// then writes the previews. A failed write restores what was written
+/// before it, so a patch lands completely or not at all.
+fn applyPatch(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall, patch: Patch) prv.adapter.ToolResult {
+    const alloc = ctx.alloc;
     if (patch.commands.len == 0) {
         return r.errResult(call, "patch contains no file operations");
     }
 
-    // 2. Per-command: verify (preview before/after), request permission, apply.
-    var applied: usize = 0;
+    // 1. Dry run: read the files and apply the hunks in memory.
+    var staged: std.ArrayList(Staged) = .empty;
+    defer {
+        for (staged.items) |st| {
+            if (st.preview.before) |b| alloc.free(b);
+            if (st.preview.after) |a| alloc.free(a);
+        }
+        staged.deinit(alloc);
+    }
     for (patch.commands, 0..) |cmd, ci| {
         if (ctx.isCanceled()) return r.errResult(call, "canceled");
 
@@ -129,7 +204,7 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
         const resolved = std.fs.path.resolve(alloc, &.{ ctx.cwd, cmd_path }) catch
             return r.errResult(call, "failed to resolve path");
 
-        r.setToolStatusPrint(ctx, call, "patch {s}", .{cmd_path});
+        r.setToolStatusPrint(ctx, call, "{s} {s}", .{ call.name, cmd_path });
 
         // For updates, the file must have been read first (matches edit.zig policy).
         if (cmd == .file_update) {
@@ -146,25 +221,51 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
             }
         }
 
-        // Build before/after preview.
-        const preview = buildPreview(ctx, resolved, cmd) catch |err| {
-            const msg = std.fmt.allocPrint(alloc, "cannot preview {s} (cmd #{d}): {s}", .{
-                cmd_path, ci, @errorName(err),
-            }) catch "preview failed";
+        const abs_cmd = withResolvedPath(alloc, ctx.cwd, cmd, resolved) catch
+            return r.errResult(call, "failed to resolve path");
+        if (r.guardWorkspacePath(ctx, call, resolved)) |blocked| return blocked;
+        switch (abs_cmd) {
+            .file_update => |u| if (u.move_to) |dest| {
+                if (r.guardWorkspacePath(ctx, call, dest)) |blocked| return blocked;
+            },
+            else => {},
+        }
+        var diag: ApplyDiagnostics = .{ .path = cmd_path };
+        // an earlier command of this patch may already have changed the file
+        const base = stagedContent(staged.items, resolved);
+        const preview = buildPreview(ctx, resolved, abs_cmd, base, &diag) catch |err| {
+            const msg = std.fmt.allocPrint(
+                alloc,
+                "patch does not apply at command #{d} ({s}): {s}. anchor=\"{s}\" hunk_index={d} detail={s}. Nothing was written.",
+                .{ ci, cmd_path, @errorName(err), diag.expected_anchor, diag.hunk_index, diag.message },
+            ) catch "patch does not apply";
             return r.errResult(call, msg);
         };
-        defer {
-            if (preview.before) |b| alloc.free(b);
-            if (preview.after) |a| alloc.free(a);
-        }
+        staged.append(alloc, .{ .path = cmd_path, .resolved = resolved, .cmd = abs_cmd, .preview = preview }) catch
+            return r.errResult(call, "oom");
+    }
 
+    // 2. Confirm every file before the first write.
+    var partial: usize = 0;
+    for (staged.items, 0..) |*st, si| {
         const decision = ctx.requestPerm(call.id, .always_check, .{ .diff = .{
-            .before = preview.before,
-            .after = preview.after orelse "",
-            .path = cmd_path,
+            .before = st.preview.before,
+            .after = st.preview.after orelse "",
+            .path = st.path,
         } });
         switch (decision) {
             .approved => {},
+            .edited => |content| {
+                // a later command would write the rejected hunks back
+                const again = for (staged.items[si + 1 ..]) |later| {
+                    if (std.mem.eql(u8, later.resolved, st.resolved)) break true;
+                } else false;
+                if (st.cmd == .file_delete or again) {
+                    return r.errResult(call, "User rejected part of the patch, nothing was written. Ask which hunks to keep.");
+                }
+                st.preview.after = content;
+                partial += 1;
+            },
             .denied => return r.errResult(call, "User declined patch"),
             .message => |txt| {
                 const wrapped = std.fmt.allocPrint(
@@ -176,33 +277,98 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
             },
             else => return r.errResult(call, "permission unresolved"),
         }
-
-        if (ctx.isCanceled()) return r.errResult(call, "canceled");
-
-        // Apply against resolved absolute path.
-        const abs_cmd = withResolvedPath(alloc, ctx.cwd, cmd, resolved) catch
-            return r.errResult(call, "failed to resolve path");
-        var diag: ApplyDiagnostics = .{};
-        executeCommand(ctx, abs_cmd, &diag) catch |err| {
+    }
+    if (ctx.isCanceled()) return r.errResult(call, "canceled");
+
+    // 3. Write, rolling back on the first failure.
+    for (staged.items, 0..) |st, ci| {
+        if (st.cmd != .file_add) r.backupBeforeWrite(ctx, st.resolved);
+        r.checkpointBeforeWrite(ctx, st.resolved, call.name);
+        var diag: ApplyDiagnostics = .{ .path = st.path };
+        writeStaged(ctx, st, &diag) catch |err| {
+            var undo = ci + 1;
+            while (undo > 0) {
+                undo -= 1;
+                rollbackStaged(ctx, staged.items[undo]);
+            }
             const msg = std.fmt.allocPrint(
                 alloc,
-                "patch apply failed at command #{d} ({s}): {s}. anchor=\"{s}\" hunk_index={d} detail={s}",
-                .{ ci, diag.path, @errorName(err), diag.expected_anchor, diag.hunk_index, diag.message },
-            ) catch "patch apply failed";
+                "patch apply failed at command #{d} ({s}): {s}. detail={s}. All files were restored.",
+                .{ ci, st.path, @errorName(err), diag.message },
+            ) catch "patch apply failed, all files were restored";
             return r.errResult(call, msg);
         };
+    }
 
+    for (staged.items) |st| {
         // Update FileStats so subsequent edits don't block on "file not read".
-        updateFileStats(ctx, resolved, abs_cmd);
-
-        applied += 1;
+        updateFileStats(ctx, st.resolved, st.cmd);
+        switch (st.cmd) {
+            .file_add => r.recordFileChange(ctx, st.resolved, .added, call.name),
+            .file_delete => r.recordFileChange(ctx, st.resolved, .deleted, call.name),
+            .file_update => |u| if (u.move_to) |dst| {
+                r.recordFileChange(ctx, st.resolved, .deleted, call.name);
+                r.recordFileChange(ctx, dst, .added, call.name);
+            } else r.recordFileChange(ctx, st.resolved, .modified, call.name),
+        }
     }
 
-    const msg = std.fmt.allocPrint(alloc, "patch applied: {d} command(s)", .{applied}) catch
+    if (partial > 0) {
+        const msg = std.fmt.allocPrint(alloc, "patch applied: {d} command(s). The user rejected some hunks in {d} file(s), only the rest was written. Re-read those files before editing them again.", .{ staged.items.len, partial }) catch
+            "patch applied in part";
+        return r.okResult(call, msg);
+    }
+    const msg = std.fmt.allocPrint(alloc, "patch applied: {d} command(s)", .{staged.items.len}) catch
         "patch applied";
     return r.okResult(call, msg);
 }
 
+/// Content of `path` after the commands staged so far, null when none of
+/// them touched it. A deleted or moved away file is empty.
+fn stagedContent(staged: []const Staged, path: []const u8) ?[]const u8 {
+    var i = staged.len;
+    while (i > 0) {
+        i -= 1;
+        const st = staged[i];
+        switch (st.cmd) {
+            .file_add => if (std.mem.eql(u8, st.resolved, path)) return st.preview.after,
+            .file_delete => if (std.mem.eql(u8, st.resolved, path)) return "",
+            .file_update => |u| {
+                const out_path = u.move_to orelse st.resolved;
+                if (std.mem.eql(u8, out_path, path)) return st.preview.after;
+                if (std.mem.eql(u8, st.resolved, path)) return "";
+            },
+        }
+    }
+    return null;
+}
+
+fn writeStaged(ctx: prv.tool.ToolContext, st: Staged, diag: *ApplyDiagnostics) ApplyError!void {
+    switch (st.cmd) {
+        .file_add => try writeFileViaExec(ctx, st.resolved, st.preview.after orelse "", diag),
+        .file_delete => try deleteFileViaExec(ctx, st.resolved, diag),
+        .file_update => |u| {
+            try writeFileViaExec(ctx, u.move_to orelse st.resolved, st.preview.after orelse "", diag);
+            if (u.move_to != null) try deleteFileViaExec(ctx, st.resolved, diag);
+        },
+    }
+}
+
+/// Best effort undo of `writeStaged`, also for a half done one.
+fn rollbackStaged(ctx: prv.tool.ToolContext, st: Staged) void {
+    var diag: ApplyDiagnostics = .{};
+    switch (st.cmd) {
+        .file_add => deleteFileViaExec(ctx, st.resolved, &diag) catch {},
+        .file_delete => if (st.preview.before) |before| writeFileViaExec(ctx, st.resolved, before, &diag) catch {},
+        .file_update => |u| {
+            if (u.move_to) |dst| {
+                if (!std.mem.eql(u8, dst, st.resolved)) deleteFileViaExec(ctx, dst, &diag) catch {};
+            }
+            if (st.preview.before) |before| writeFileViaExec(ctx, st.resolved, before, &diag) catch {};
+        },
+    }
+}
+
 fn commandPath(cmd: PatchCommand) []const u8 {
     return switch (cmd) {
         .file_add => |a| a.path,
@@ -233,11 +399,15 @@ const Preview = struct {
 };
 
 /// Build a before/after preview for the permission prompt. Does not write
-/// anything. Allocates `before` and `after` from `alloc`; caller frees.
+/// anything. `base` stands in for the file on disk when an earlier command
+/// of the same patch changed it. Allocates `before` and `after` from
+/// `alloc`; caller frees.
 fn buildPreview(
     ctx: prv.tool.ToolContext,
     abs_path: []const u8,
     cmd: PatchCommand,
+    base: ?[]const u8,
+    diag: *ApplyDiagnostics,
 ) !Preview {
     const alloc = ctx.alloc;
     switch (cmd) {
@@ -246,14 +416,16 @@ fn buildPreview(
             return .{ .before = null, .after = after };
         },
         .file_delete => {
-            const before = try readFileViaExec(ctx, abs_path, true, null);
+            const before = if (base) |b| try alloc.dupe(u8, b) else try readFileViaExec(ctx, abs_path, true, diag);
             return .{ .before = before, .after = null };
         },
         .file_update => |u| {
-            const before = (try readFileViaExec(ctx, abs_path, false, null)) orelse return ApplyError.FileNotFound;
+            const before = if (base) |b|
+                try alloc.dupe(u8, b)
+            else
+                (try readFileViaExec(ctx, abs_path, false, diag)) orelse return ApplyError.FileNotFound;
             errdefer alloc.free(before);
-            var diag: ApplyDiagnostics = .{};
-            const after = try applyHunks(alloc, before, u.hunks, &diag);
+            const after = try applyHunks(alloc, before, u.hunks, diag);
             return .{ .before = before, .after = after };
         },
     }
@@ -328,6 +500,7 @@ pub const ParseError = error{
     NoMatch,
     EmptyUpdateFile,
     InvalidHunkLine,
+    MissingHunkContext,
 } || std.mem.Allocator.Error;
 
 pub const MAX_FILE_BYTES: usize = 1 * 1024 * 1024; // 1 MiB
@@ -715,6 +888,121 @@ pub fn parsePatch(alloc: std.mem.Allocator, bytes: []const u8) ParseError!Result
     return ok(Patch, .{ .commands = owned }, final_rest);
 }
 
+// ---- unified diff --------------------------------------------------------
+
+/// Parses a unified diff, as written by `git diff` or `diff -u`, into the
+/// commands of the patch envelope. Lines outside file sections (`diff --git`,
+/// `index`, mode lines, prose) are skipped. Hunks are sized by the counts in
+/// their `@@` header, the line numbers themselves are dropped: like envelope
+/// hunks they are located by context.
+pub fn parseUnifiedDiff(alloc: std.mem.Allocator, bytes: []const u8) ParseError!Patch {
+    const rows = try splitLines(alloc, stripCommonWrappers(bytes));
+    defer alloc.free(rows);
+    for (rows) |*row| row.* = std.mem.trimRight(u8, row.*, "\r");
+
+    var cmds: std.ArrayList(PatchCommand) = .empty;
+    defer cmds.deinit(alloc);
+
+    var i: usize = 0;
+    while (i < rows.len) {
+        if (!std.mem.startsWith(u8, rows[i], "--- ") or i + 1 == rows.len or !std.mem.startsWith(u8, rows[i + 1], "+++ ")) {
+            i += 1;
+            continue;
+        }
+        const old_path = diffPath(rows[i]["--- ".len..]);
+        const new_path = diffPath(rows[i + 1]["+++ ".len..]);
+        i += 2;
+
+        var hunks: std.ArrayList(Hunk) = .empty;
+        defer hunks.deinit(alloc);
+        while (i < rows.len and std.mem.startsWith(u8, rows[i], "@@ ")) {
+            var old_left, var new_left = try hunkCounts(rows[i]);
+            i += 1;
+            var lines: std.ArrayList(HunkLine) = .empty;
+            defer lines.deinit(alloc);
+            var anchored = false;
+            while (old_left > 0 or new_left > 0) : (i += 1) {
+                if (i == rows.len) return ParseError.UnexpectedEof;
+                const row = rows[i];
+                // editors like to strip the space of blank context lines
+                const marker: u8 = if (row.len == 0) ' ' else row[0];
+                const text = if (row.len == 0) row else row[1..];
+                switch (marker) {
+                    ' ' => {
+                        try lines.append(alloc, .{ .context = text });
+                        old_left -|= 1;
+                        new_left -|= 1;
+                        anchored = true;
+                    },
+                    '-' => {
+                        try lines.append(alloc, .{ .delete = text });
+                        old_left -|= 1;
+                        anchored = true;
+                    },
+                    '+' => {
+                        try lines.append(alloc, .{ .add = text });
+                        new_left -|= 1;
+                    },
+                    // `\ No newline at end of file`
+                    '\\' => {},
+                    else => return ParseError.InvalidHunkLine,
+                }
+            }
+            if (i < rows.len and std.mem.startsWith(u8, rows[i], "\\")) i += 1;
+            if (!anchored and !isDevNull(old_path)) return ParseError.MissingHunkContext;
+            try hunks.append(alloc, .{ .header = null, .lines = try lines.toOwnedSlice(alloc), .end_of_file = false });
+        }
+
+        if (isDevNull(old_path)) {
+            var added: std.ArrayList([]const u8) = .empty;
+            defer added.deinit(alloc);
+            for (hunks.items) |h| for (h.lines) |hl| switch (hl) {
+                .add => |t| try added.append(alloc, t),
+                else => {},
+            };
+            try cmds.append(alloc, .{ .file_add = .{ .path = new_path, .lines = try added.toOwnedSlice(alloc) } });
+        } else if (isDevNull(new_path)) {
+            try cmds.append(alloc, .{ .file_delete = .{ .path = old_path } });
+        } else {
+            if (hunks.items.len == 0) return ParseError.EmptyUpdateFile;
+            try cmds.append(alloc, .{ .file_update = .{
+                .path = old_path,
+                .move_to = if (std.mem.eql(u8, old_path, new_path)) null else new_path,
+                .hunks = try hunks.toOwnedSlice(alloc),
+            } });
+        }
+    }
+
+    if (cmds.items.len == 0) return ParseError.NoMatch;
+    return .{ .commands = try cmds.toOwnedSlice(alloc) };
+}
+
+/// `a/src/x.zig\t2024-01-01 ...` -> `src/x.zig`
+fn diffPath(raw: []const u8) []const u8 {
+    const path = std.mem.trim(u8, raw[0 .. std.mem.indexOfScalar(u8, raw, '\t') orelse raw.len], " ");
+    if (std.mem.startsWith(u8, path, "a/") or std.mem.startsWith(u8, path, "b/")) return path[2..];
+    return path;
+}
+
+fn isDevNull(path: []const u8) bool {
+    return std.mem.eql(u8, path, "/dev/null");
+}
+
+/// Old and new line counts of `@@ -12,7 +12,9 @@ fn name`, a missing count
+/// is one.
+fn hunkCounts(header: []const u8) ParseError!struct { usize, usize } {
+    var it = std.mem.tokenizeScalar(u8, header["@@ ".len..], ' ');
+    const old_range = it.next() orelse return ParseError.InvalidHunkLine;
+    const new_range = it.next() orelse return ParseError.InvalidHunkLine;
+    if (old_range[0] != '-' or new_range[0] != '+') return ParseError.InvalidHunkLine;
+    return .{ try rangeCount(old_range[1..]), try rangeCount(new_range[1..]) };
+}
+
+fn rangeCount(range: []const u8) ParseError!usize {
+    const comma = std.mem.indexOfScalar(u8, range, ',') orelse return 1;
+    return std.fmt.parseInt(usize, range[comma + 1 ..], 10) catch return ParseError.InvalidHunkLine;
+}
+
 // ---- apply ---------------------------------------------------------------
 
 /// Split source into lines. Empty trailing slice represents the final newline
@@ -1682,3 +1970,53 @@ test "applyHunks two hunks sequential, second after first" {
 
     try testing.expectEqualStrings("a\nB\nc\nd\nE\nf\n", out);
 }
+
+test "parseUnifiedDiff reads git diff sections" {
+    var arena = std.heap.ArenaAllocator.init(testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const diff =
+        \\diff --git a/src/a.zig b/src/a.zig
+        \\index 1111111..2222222 100644
+        \\--- a/src/a.zig
+        \\+++ b/src/a.zig
+        \\@@ -1,3 +1,3 @@ fn main() {
+        \\ const a = 1;
+        \\-const b = 2;
+        \\+const b = 3;
+        \\
+        \\--- /dev/null
+        \\+++ b/new.txt
+        \\@@ -0,0 +1,2 @@
+        \\+hello
+        \\+world
+        \\--- a/old.txt
+        \\+++ /dev/null
+        \\@@ -1 +0,0 @@
+        \\-bye
+        \\\ No newline at end of file
+        \\
+    ;
+    const patch = try parseUnifiedDiff(alloc, diff);
+    try testing.expectEqual(@as(usize, 3), patch.commands.len);
+
+    const update = patch.commands[0].file_update;
+    try testing.expectEqualStrings("src/a.zig", update.path);
+    try testing.expect(update.move_to == null);
+    var diag: ApplyDiagnostics = .{};
+    const after = try applyHunks(alloc, "const a = 1;\nconst b = 2;\n\nx\n", update.hunks, &diag);
+    try testing.expectEqualStrings("const a = 1;\nconst b = 3;\n\nx\n", after);
+
+    const add = patch.commands[1].file_add;
+    try testing.expectEqualStrings("new.txt", add.path);
+    try testing.expectEqual(@as(usize, 2), add.lines.len);
+    try testing.expectEqualStrings("world", add.lines[1]);
+    try testing.expectEqualStrings("old.txt", patch.commands[2].file_delete.path);
+
+    const renamed = try parseUnifiedDiff(alloc, "--- a/x.txt\t2024-01-01 10:00:00\n+++ b/y.txt\n@@ -1 +1 @@\n-a\n+b\n");
+    try testing.expectEqualStrings("y.txt", renamed.commands[0].file_update.move_to.?);
+
+    try testing.expectError(ParseError.MissingHunkContext, parseUnifiedDiff(alloc, "--- a/x\n+++ b/x\n@@ -3,0 +4 @@\n+new\n"));
+    try testing.expectError(ParseError.NoMatch, parseUnifiedDiff(alloc, "no diff here"));
+}
@@ -117,6 +117,8 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
     const resolved = std.fs.path.resolve(ctx.alloc, &.{ ctx.cwd, args.path }) catch
         return r.errResult(call, "failed to resolve path");
 
+    if (r.guardSensitivePath(ctx, call, resolved)) |denied| return denied;
+
     const full_read = args.offset == null and args.limit == null;
 
     const app = ctx.swarm.context.cast(@import("../app.zig").App);
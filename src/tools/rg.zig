@@ -6,50 +6,213 @@ pub const RipGrepTool = r.prv.tool.Tool{
         .name = "ripgrep",
         .description =
         \\Ripgrep file and text search. Use this tool instead for any file and text related search task.
+        \\Returns `path:line:text` for matches and `path-line-text` for context lines.
         \\
         ,
         .parameters_schema =
         \\{
         \\  "type": "object",
         \\  "properties": {
-        \\      "args": {"type": "string", "description": "the rg command string without `rg`"}
+        \\      "pattern": {"type": "string", "description": "regex to search for (rust regex syntax)"},
+        \\      "paths": {"type": "array", "items": {"type": "string"}, "description": "files or directories inside the project, default the project root"},
+        \\      "glob": {"type": "string", "description": "only search files matching this glob, e.g. `*.zig` or `!tests/**`"},
+        \\      "ignore_case": {"type": "boolean"},
+        \\      "fixed_strings": {"type": "boolean", "description": "treat the pattern as a literal string"},
+        \\      "context": {"type": "integer", "description": "lines of context around each match"}
         \\  },
-        \\  "required": ["args"]
+        \\  "required": ["pattern"]
         \\}
         ,
     },
     .func = &run,
 };
 
+const MAX_PATHS = 16;
+const MAX_CONTEXT = 10;
+
+/// rg runs without a shell, the pattern and every path are single argv
+/// entries behind `--`.
 fn run(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall) r.prv.adapter.ToolResult {
     const Args = struct {
-        args: []const u8,
+        pattern: []const u8,
+        paths: []const []const u8 = &.{},
+        glob: ?[]const u8 = null,
+        ignore_case: bool = false,
+        fixed_strings: bool = false,
+        context: u32 = 0,
     };
 
     const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
         .ignore_unknown_fields = true,
     }) catch {
-        return r.errResult(call, "invalid JSON arguments: expected {\"path\": \"...\"}");
+        return r.errResult(call, "invalid JSON arguments: expected {\"pattern\": \"...\", \"paths\": [\"...\"]}");
     };
+    if (args.paths.len > MAX_PATHS) return r.errResult(call, "too many paths, search a common parent directory instead");
 
-    r.setToolStatusPrint(ctx, call, "rg  {s}", .{
-        args.args,
-    });
+    r.setToolStatusPrint(ctx, call, "rg  {s}", .{args.pattern});
 
-    var buf: [255]u8 = undefined;
-    const rg_str = std.fmt.bufPrint(&buf, "rg {s}", .{args.args}) catch "rg";
+    var argv: std.ArrayList([]const u8) = .empty;
+    argv.appendSlice(ctx.alloc, &.{ "rg", "--with-filename", "--json" }) catch return r.errResult(call, "oom");
+    if (args.ignore_case) argv.append(ctx.alloc, "--ignore-case") catch return r.errResult(call, "oom");
+    if (args.fixed_strings) argv.append(ctx.alloc, "--fixed-strings") catch return r.errResult(call, "oom");
+    if (args.context > 0) {
+        const flag = std.fmt.allocPrint(ctx.alloc, "--context={d}", .{@min(args.context, MAX_CONTEXT)}) catch return r.errResult(call, "oom");
+        argv.append(ctx.alloc, flag) catch return r.errResult(call, "oom");
+    }
+    if (args.glob) |glob| {
+        const flag = std.fmt.allocPrint(ctx.alloc, "--glob={s}", .{glob}) catch return r.errResult(call, "oom");
+        argv.append(ctx.alloc, flag) catch return r.errResult(call, "oom");
+    }
+    argv.appendSlice(ctx.alloc, &.{ "--", args.pattern }) catch return r.errResult(call, "oom");
+    for (args.paths) |path| {
+        const resolved = std.fs.path.resolve(ctx.alloc, &.{ ctx.cwd, path }) catch
+            return r.errResult(call, "failed to resolve path");
+        if (r.guardSearchPath(ctx, call, resolved)) |denied| return denied;
+        if (r.guardSensitivePath(ctx, call, resolved)) |denied| return denied;
+        argv.append(ctx.alloc, resolved) catch return r.errResult(call, "oom");
+    }
+    if (args.paths.len == 0) argv.append(ctx.alloc, ".") catch return r.errResult(call, "oom");
 
     const raw = ctx.swarm.exec.runAndWaitTimeout(.{
-        .argv = &.{
-            "sh",
-            "-c",
-            rg_str,
-        },
+        .cwd = ctx.cwd,
+        .argv = argv.items,
     }, 10_000) catch
         return r.errResult(call, "failed to spawn command process");
 
-    const result = raw.toOwned(ctx.alloc) catch
-        return r.errResult(call, "failed to format rg output");
+    const hits = parseJson(ctx.alloc, raw.stdout) catch
+        return r.errResult(call, "failed to read rg output");
+    if (hits.len == 0) {
+        const stderr = std.mem.trim(u8, raw.stderr, " \t\r\n");
+        if (stderr.len > 0) return r.errResult(call, ctx.alloc.dupe(u8, stderr) catch "rg failed");
+        return r.okResult(call, "no matches");
+    }
+
+    // Matches inside sensitive files never reach the transcript.
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const home = ctx.io.environ.process_environ.getPosix("HOME");
+    var out: std.Io.Writer.Allocating = .init(ctx.alloc);
+    var hidden: ?[]const u8 = null;
+    for (hits) |hit| {
+        const abs = std.fs.path.resolve(ctx.alloc, &.{ ctx.cwd, hit.path }) catch hit.path;
+        if (app.sensitive_paths.match(ctx.cwd, home, abs) != null) {
+            if (hidden == null or !std.mem.eql(u8, hidden.?, hit.path)) {
+                out.writer.print("{s}: [redacted: sensitive file]\n", .{hit.path}) catch {};
+            }
+            hidden = hit.path;
+            continue;
+        }
+        out.writer.print("{s}{c}{d}{c}{s}\n", .{ hit.path, hit.sep(), hit.line, hit.sep(), hit.text }) catch {};
+    }
+
+    return r.okResult(call, r.spillOutputToOwned(ctx, call, out.written()));
+}
+
+pub const JsonHit = struct {
+    path: []const u8,
+    line: u64,
+    text: []const u8,
+    context: bool,
+
+    fn sep(self: JsonHit) u8 {
+        return if (self.context) '-' else ':';
+    }
+};
+
+/// Match and context lines of `rg --json` output, in order. Paths that are
+/// not valid UTF-8 come base64 encoded and are skipped.
+pub fn parseJson(alloc: std.mem.Allocator, output: []const u8) ![]const JsonHit {
+    const Text = struct { text: ?[]const u8 = null };
+    const Data = struct {
+        path: Text = .{},
+        lines: Text = .{},
+        line_number: ?u64 = null,
+    };
+    const Event = struct { type: []const u8, data: Data = .{} };
+
+    var hits: std.ArrayList(JsonHit) = .empty;
+    var lines = std.mem.tokenizeScalar(u8, output, '\n');
+    while (lines.next()) |line| {
+        const ev = std.json.parseFromSliceLeaky(Event, alloc, line, .{ .ignore_unknown_fields = true }) catch continue;
+        const context = std.mem.eql(u8, ev.type, "context");
+        if (!context and !std.mem.eql(u8, ev.type, "match")) continue;
+        const path = ev.data.path.text orelse continue;
+        const text = ev.data.lines.text orelse continue;
+        try hits.append(alloc, .{
+            .path = if (std.mem.startsWith(u8, path, "./")) path[2..] else path,
+            .line = ev.data.line_number orelse 0,
+            .text = std.mem.trimRight(u8, text, "\r\n"),
+            .context = context,
+        });
+    }
+    return hits.toOwnedSlice(alloc);
+}
+
+// ── User search ──────────────────────────────────────────────────────
+// The C-f grep popup runs the same rg as the tool, with fixed flags.
+
+pub const MAX_USER_HITS = 200;
+
+pub fn userSearchArgv(query: []const u8) [9][]const u8 {
+    return .{ "rg", "--line-number", "--no-heading", "--color=never", "--smart-case", "--max-count=20", "--max-columns=200", "-e", query };
+}
+
+/// Definition sites of `name` across common languages, first hit only.
+pub fn definitionPattern(alloc: std.mem.Allocator, name: []const u8) ![]const u8 {
+    return std.fmt.allocPrint(alloc, "\\b(fn|def|func|function|class|struct|enum|union|trait|type|interface|impl|mod|const|let|var)\\s+{s}\\b", .{name});
+}
+
+pub fn definitionArgv(pattern: []const u8) [8][]const u8 {
+    return .{ "rg", "--line-number", "--no-heading", "--color=never", "--max-count=1", "--max-columns=200", "-e", pattern };
+}
+
+pub const Hit = struct {
+    path: []const u8,
+    line: u32,
+    text: []const u8,
+};
+
+/// One `path:line:text` row of `rg --line-number --no-heading`. Paths may
+/// contain `:`, the first `:<digits>:` is the separator.
+pub fn parseHit(row: []const u8) ?Hit {
+    var pos: usize = 0;
+    while (std.mem.indexOfScalarPos(u8, row, pos, ':')) |colon| {
+        pos = colon + 1;
+        if (colon == 0) continue;
+        const end = std.mem.indexOfScalarPos(u8, row, pos, ':') orelse return null;
+        const line = std.fmt.parseInt(u32, row[pos..end], 10) catch continue;
+        return .{ .path = row[0..colon], .line = line, .text = row[end + 1 ..] };
+    }
+    return null;
+}
+
+test "parseHit" {
+    const hit = parseHit("src/app.zig:42:    pub fn tick(self: *App) !void {").?;
+    try std.testing.expectEqualStrings("src/app.zig", hit.path);
+    try std.testing.expectEqual(@as(u32, 42), hit.line);
+    try std.testing.expectEqualStrings("    pub fn tick(self: *App) !void {", hit.text);
+
+    const odd = parseHit("a:b/c.txt:7:x:1:y").?;
+    try std.testing.expectEqualStrings("a:b/c.txt", odd.path);
+    try std.testing.expectEqualStrings("x:1:y", odd.text);
+
+    try std.testing.expect(parseHit("no separators") == null);
+}
 
-    return r.okResult(call, r.truncateOutputToOwned(ctx.alloc, result, r.MAX_DISPLAY_BYTES, r.MAX_DISPLAY_LINES));
+test "parseJson" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const output =
+        \\{"type":"begin","data":{"path":{"text":"./src/a.zig"}}}
+        \\{"type":"context","data":{"path":{"text":"./src/a.zig"},"lines":{"text":"// before\n"},"line_number":2,"absolute_offset":9,"submatches":[]}}
+        \\{"type":"match","data":{"path":{"text":"./src/a.zig"},"lines":{"text":"fn run() void {}\n"},"line_number":3,"absolute_offset":19,"submatches":[{"match":{"text":"run"},"start":3,"end":6}]}}
+        \\{"type":"match","data":{"path":{"bytes":"c3JjL2L/LnppZw=="},"lines":{"text":"run\n"},"line_number":1,"absolute_offset":0,"submatches":[]}}
+        \\{"type":"end","data":{"path":{"text":"./src/a.zig"},"binary_offset":null,"stats":{}}}
+        \\{"type":"summary","data":{"elapsed_total":{"human":"0.01s"},"stats":{}}}
+    ;
+    const hits = try parseJson(arena.allocator(), output);
+    try std.testing.expectEqual(@as(usize, 2), hits.len);
+    try std.testing.expect(hits[0].context);
+    try std.testing.expectEqualStrings("src/a.zig", hits[1].path);
+    try std.testing.expectEqual(@as(u64, 3), hits[1].line);
+    try std.testing.expectEqualStrings("fn run() void {}", hits[1].text);
 }
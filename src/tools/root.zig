@@ -7,19 +7,33 @@ pub const todos = @import("todo.zig");
 pub const ask = @import("ask.zig");
 pub const ssh = @import("ssh.zig");
 pub const agent = @import("agent.zig");
+pub const task = @import("task.zig");
 pub const edit = @import("edit.zig");
 pub const write = @import("write.zig");
 pub const parse = @import("htmlparser.zig");
 pub const reg = @import("../context_factory.zig");
 pub const patch = @import("patch.zig");
+pub const draft = @import("draft.zig");
 pub const r = @import("../root.zig");
 pub const tui = r.tui;
 pub const rg = @import("rg.zig");
 pub const skill = @import("skill.zig");
 pub const start = @import("start.zig");
+pub const git = @import("git.zig");
+pub const owners = @import("owners.zig");
+pub const eval = @import("eval.zig");
+pub const command = @import("command.zig");
+pub const websearch = @import("websearch.zig");
+pub const memory = @import("memory.zig");
 
 pub const MAX_DISPLAY_BYTES = 32 * 1024;
 pub const MAX_DISPLAY_LINES = 1000;
+/// full tool output that did not fit the preview, one file per call
+pub const SPILL_DIR = r.util.BLITZ_DIR ++ "/spill";
+/// spill files kept per project, the oldest go first
+pub const MAX_SPILL_FILES = 64;
+/// and none outlives a week
+const SPILL_MAX_AGE_NS = 7 * std.time.ns_per_day;
 
 pub fn fmtSpan(ctx: *r.prv.tool.ToolContext, comptime fmt: []const u8, args: anytype, style: tui.Style) r.tui.Span {
     const app: *r.app.App = @ptrCast(@alignCast(ctx.swarm.context.ptr));
@@ -92,6 +106,106 @@ pub fn setToolChild(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall, c
     app.setToolChild(ctx.self_id, call.id, child_id) catch {};
 }
 
+/// Gate reads of paths on the sensitive list. Returns an error result when
+/// the read must not happen, null when the tool may continue.
+pub fn guardSensitivePath(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall, resolved: []const u8) ?r.prv.adapter.ToolResult {
+    const app = ctx.swarm.context.cast(r.app.App);
+    const home = ctx.io.environ.process_environ.getPosix("HOME");
+    const pattern = app.sensitive_paths.match(ctx.cwd, home, resolved) orelse return null;
+
+    const blocked = std.fmt.allocPrint(
+        ctx.alloc,
+        "{s} matches the sensitive path pattern `{s}`. The content was withheld. Do not try to access it through other tools.",
+        .{ resolved, pattern },
+    ) catch "sensitive file withheld";
+
+    if (app.sensitive_paths.policy == .block) return errResult(call, blocked);
+
+    const question = std.fmt.allocPrint(ctx.alloc, "The agent wants to read {s} (matches `{s}`).", .{ resolved, pattern }) catch resolved;
+    const decision = ctx.requestPerm(call.id, .dangerous, .{ .ask = .{
+        .header = "sensitive file",
+        .question = question,
+        .options = &.{ "allow this read", "deny" },
+    } });
+    return switch (decision) {
+        .choice => |idx| if (idx == 0) null else errResult(call, blocked),
+        else => errResult(call, blocked),
+    };
+}
+
+/// Result text for a change the user approved only in part, the rejected
+/// hunks were left as they were.
+pub fn partialApprovalNote(alloc: std.mem.Allocator, path: []const u8) []const u8 {
+    return std.fmt.allocPrint(alloc, "The user rejected some hunks, only the rest was written to {s}. Re-read the file before editing it again and do not reapply the rejected changes unless asked.", .{path}) catch
+        "The user rejected some hunks, only the rest was written.";
+}
+
+/// Gate writes on the workspace sandbox. Returns an error result when
+/// `resolved` lies outside the project root and every allow entry, null
+/// when the tool may continue. Remote paths under SSH are not checked.
+pub fn guardWorkspacePath(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall, resolved: []const u8) ?r.prv.adapter.ToolResult {
+    const app = ctx.swarm.context.cast(r.app.App);
+    if (!app.sandbox.enabled or ctx.swarm.exec.ssh_active) return null;
+    const canonical = r.sandbox.canonicalize(ctx.io, ctx.alloc, resolved) catch resolved;
+    const home = ctx.io.environ.process_environ.getPosix("HOME");
+    if (app.sandbox.allowsOnDisk(ctx.io, ctx.alloc, ctx.cwd, home, canonical)) return null;
+    const msg = std.fmt.allocPrint(
+        ctx.alloc,
+        "{s} is outside the project root {s}, writes there are blocked. Work inside the project, or ask the user to allow the path with blitz.set_sandbox.",
+        .{ canonical, ctx.cwd },
+    ) catch "path is outside the project root";
+    return errResult(call, msg);
+}
+
+/// Gate a search path of `ripgrep` on the project root and the sandbox
+/// allow entries, so a search can't wander through the rest of the disk.
+/// Remote paths under SSH are not checked.
+pub fn guardSearchPath(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall, resolved: []const u8) ?r.prv.adapter.ToolResult {
+    const app = ctx.swarm.context.cast(r.app.App);
+    if (ctx.swarm.exec.ssh_active) return null;
+    const canonical = r.sandbox.canonicalize(ctx.io, ctx.alloc, resolved) catch resolved;
+    const home = ctx.io.environ.process_environ.getPosix("HOME");
+    if (app.sandbox.allowsOnDisk(ctx.io, ctx.alloc, ctx.cwd, home, canonical)) return null;
+    const msg = std.fmt.allocPrint(
+        ctx.alloc,
+        "{s} is outside the project root {s}, search inside the project.",
+        .{ canonical, ctx.cwd },
+    ) catch "path is outside the project root";
+    return errResult(call, msg);
+}
+
+/// Copy `path` to the session backup before the first modification this
+/// session. Best effort, a failed backup never blocks the write.
+pub fn backupBeforeWrite(ctx: r.prv.tool.ToolContext, path: []const u8) void {
+    const app = ctx.swarm.context.cast(r.app.App);
+    if (!app.claimBackup(path)) return;
+
+    const script = r.backup.backupScript(ctx.alloc, ctx.cwd, app.backup_session, path) catch return;
+    defer ctx.alloc.free(script);
+    const res = ctx.swarm.exec.runAndWait(.{ .argv = &.{ "/bin/sh", "-c", script } }) catch return;
+    ctx.swarm.exec.alloc.free(res.stdout);
+    ctx.swarm.exec.alloc.free(res.stderr);
+}
+
+/// Snapshot `path` before a tool modifies it, `:undo` puts it back. Also
+/// called for new files, undoing those deletes them. Best effort as well.
+pub fn checkpointBeforeWrite(ctx: r.prv.tool.ToolContext, path: []const u8, tool: []const u8) void {
+    const app = ctx.swarm.context.cast(r.app.App);
+    const cp = app.addCheckpoint(path, tool) orelse return;
+
+    const script = r.checkpoint.snapshotScript(ctx.alloc, app.backup_session, cp.id, path) catch return;
+    defer ctx.alloc.free(script);
+    const res = ctx.swarm.exec.runAndWait(.{ .argv = &.{ "/bin/sh", "-c", script } }) catch return;
+    ctx.swarm.exec.alloc.free(res.stdout);
+    ctx.swarm.exec.alloc.free(res.stderr);
+}
+
+/// Track a file touched by a tool in the session's workspace change set.
+pub fn recordFileChange(ctx: r.prv.tool.ToolContext, path: []const u8, kind: r.app.FileChangeKind, tool: []const u8) void {
+    const app = ctx.swarm.context.cast(r.app.App);
+    app.recordFileChange(path, kind, tool) catch {};
+}
+
 pub fn errResult(call: prv.adapter.ToolCall, msg: []const u8) prv.adapter.ToolResult {
     return .{
         .call_id = call.id,
@@ -173,6 +287,92 @@ pub fn truncateOutputToOwned(
     return ensureValidUtf8(alloc, raw);
 }
 
+/// `truncateOutputToOwned` that keeps what it cuts. Output over the display
+/// limits is written to `SPILL_DIR/<call id>.txt`, the model gets the
+/// preview and the path to page through with `read` and the user can open
+/// the whole output with `:output`. Over SSH the spill file would sit on
+/// the wrong machine, so the output is only truncated there.
+pub fn spillOutputToOwned(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall, output: []const u8) []const u8 {
+    const preview = truncateOutputToOwned(ctx.alloc, output, MAX_DISPLAY_BYTES, MAX_DISPLAY_LINES);
+    const lines = countLines(output);
+    if ((output.len <= MAX_DISPLAY_BYTES and lines <= MAX_DISPLAY_LINES) or ctx.swarm.exec.ssh_active) return preview;
+
+    const rel = spillPath(ctx.alloc, call.id) catch return preview;
+    const abs = writeSpill(ctx, rel, output) catch return preview;
+    const app: *r.app.App = @ptrCast(@alignCast(ctx.swarm.context.ptr));
+    app.setToolSpill(ctx.self_id, call.id, abs, lines) catch {};
+    return std.fmt.allocPrint(
+        ctx.alloc,
+        "{s}\nThe full output ({d} lines) is saved in {s}, page through it with read offset/limit or search it with rg instead of running this again.",
+        .{ preview, lines, rel },
+    ) catch preview;
+}
+
+/// Writes below the agent's cwd, so the relative path in the hint is the
+/// one its `read` resolves. Returns the absolute path. The spill dir is
+/// git ignored and pruned to `MAX_SPILL_FILES` on every write.
+fn writeSpill(ctx: r.prv.tool.ToolContext, rel: []const u8, output: []const u8) ![]const u8 {
+    const path = if (ctx.cwd.len > 0) try std.fs.path.join(ctx.alloc, &.{ ctx.cwd, rel }) else rel;
+    const dir = std.fs.path.dirname(path).?;
+    try std.Io.Dir.cwd().createDirPath(ctx.io, dir);
+    {
+        var project = if (ctx.cwd.len > 0) try std.Io.Dir.openDirAbsolute(ctx.io, ctx.cwd, .{}) else std.Io.Dir.cwd();
+        defer if (ctx.cwd.len > 0) project.close(ctx.io);
+        try r.util.ignoreInBlitzDir(project, ctx.io, "spill/");
+    }
+    const file = try std.Io.Dir.cwd().createFile(ctx.io, path, .{});
+    defer file.close(ctx.io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(ctx.io, &buf);
+    try writer.interface.writeAll(output);
+    try writer.interface.flush();
+    pruneSpill(ctx.io, ctx.alloc, dir);
+    return path;
+}
+
+/// Drops spill files past the newest `MAX_SPILL_FILES` and those older
+/// than `SPILL_MAX_AGE_NS`.
+fn pruneSpill(io: std.Io, alloc: std.mem.Allocator, dir_path: []const u8) void {
+    var dir = std.Io.Dir.cwd().openDir(io, dir_path, .{ .iterate = true }) catch return;
+    defer dir.close(io);
+    const Spill = struct { name: []const u8, mtime: i128 };
+    var files: std.ArrayList(Spill) = .empty;
+    defer {
+        for (files.items) |f| alloc.free(f.name);
+        files.deinit(alloc);
+    }
+    var it = dir.iterate();
+    while (it.next(io) catch null) |entry| {
+        if (entry.kind != .file or !std.mem.endsWith(u8, entry.name, ".txt")) continue;
+        const stat = dir.statFile(io, entry.name, .{}) catch continue;
+        const name = alloc.dupe(u8, entry.name) catch return;
+        files.append(alloc, .{ .name = name, .mtime = stat.mtime.nanoseconds }) catch {
+            alloc.free(name);
+            return;
+        };
+    }
+    std.mem.sort(Spill, files.items, {}, struct {
+        fn newer(_: void, a: Spill, b: Spill) bool {
+            return a.mtime > b.mtime;
+        }
+    }.newer);
+    const now = std.Io.Clock.Timestamp.now(io, .real).raw.nanoseconds;
+    for (files.items, 0..) |f, i| {
+        if (i < MAX_SPILL_FILES and now - f.mtime < SPILL_MAX_AGE_NS) continue;
+        dir.deleteFile(io, f.name) catch {};
+    }
+}
+
+/// Call ids come from the provider, anything outside `[A-Za-z0-9_-]`
+/// becomes `_`.
+pub fn spillPath(alloc: std.mem.Allocator, call_id: []const u8) ![]const u8 {
+    const name = try alloc.dupe(u8, if (call_id.len > 0) call_id else "call");
+    for (name) |*ch| {
+        if (!std.ascii.isAlphanumeric(ch.*) and ch.* != '_' and ch.* != '-') ch.* = '_';
+    }
+    return std.fmt.allocPrint(alloc, SPILL_DIR ++ "/{s}.txt", .{name});
+}
+
 /// Walk end back so it never splits a multi-byte UTF-8 sequence.
 fn utf8Floor(s: []const u8, end: usize) usize {
     var i = @min(end, s.len);
@@ -182,7 +382,7 @@ fn utf8Floor(s: []const u8, end: usize) usize {
 }
 
 /// Return `raw` if already valid UTF-8; otherwise owned lossy copy (U+FFFD).
-fn ensureValidUtf8(alloc: std.mem.Allocator, raw: []const u8) []const u8 {
+pub fn ensureValidUtf8(alloc: std.mem.Allocator, raw: []const u8) []const u8 {
     if (std.unicode.utf8ValidateSlice(raw)) return raw;
     return std.fmt.allocPrint(alloc, "{f}", .{std.unicode.fmtUtf8(raw)}) catch
         "(binary output; failed to sanitize utf-8)";
@@ -206,6 +406,13 @@ test {
     @import("std").testing.refAllDecls(@This());
 }
 
+test "spillPath keeps call ids inside the spill dir" {
+    const testing = std.testing;
+    const path = try spillPath(testing.allocator, "toolu_01A/../x");
+    defer testing.allocator.free(path);
+    try testing.expectEqualStrings(SPILL_DIR ++ "/toolu_01A____x.txt", path);
+}
+
 test "truncateOutputToOwned keeps valid utf8 as string payload" {
     const testing = std.testing;
     const in = "hello\nworld";
@@ -62,7 +62,7 @@ fn run(ctx: r.prv.tool.ToolContext, call: r.prv.adapter.ToolCall) r.prv.adapter.
                     return r.errResult(call, "failed to load skill, inform the user");
                 };
 
-                return r.okResult(call, skill_content);
+                return r.okResult(call, r.r.vars.expand(ctx.alloc, skill_content, &app.context_factory.prompt_vars) catch skill_content);
             }
         }
 
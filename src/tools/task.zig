@@ -0,0 +1,121 @@
+//! Blocking sub-agent for scoped read-only investigations. Spawns a `task`
+//! agent with its own chat and a token budget, waits for it and returns only
+//! its final answer, so the search noise stays out of the parent's context.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+pub const DEFAULT_TOKEN_BUDGET = 200_000;
+
+pub const TaskTool = prv.tool.Tool{
+    .def = .{
+        .name = "task",
+        .description =
+        \\Delegate a scoped, read-only investigation to a sub-agent and wait for its answer.
+        \\
+        \\The sub-agent has its own context, can only read and search files, and returns a single message with findings and `path:line` citations. Only that message lands in your context.
+        \\
+        \\Use it for:
+        \\- open-ended searches across many files ("where is X handled", "how does Y flow")
+        \\- questions that need a lot of reading but a short answer
+        \\- several independent investigations at once: issue multiple task calls in one message
+        \\
+        \\Do not use it for a known file or a single grep, use read or rg directly. The sub-agent cannot edit, run commands or ask the user.
+        \\
+        \\Write the prompt as a self-contained brief: what to find, where to start if known, and what the answer should contain.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "description": {"type": "string", "description": "A short (3-5 word) description of the task"},
+        \\      "prompt": {"type": "string", "description": "The investigation brief for the sub-agent"},
+        \\      "token_budget": {"type": "integer", "description": "Token budget for the sub-agent, defaults to 200000"}
+        \\  },
+        \\  "required": ["description","prompt"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const Args = struct {
+        description: []const u8,
+        prompt: []const u8,
+        token_budget: u64 = DEFAULT_TOKEN_BUDGET,
+    };
+
+    const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call, "invalid arguments");
+
+    const app = ctx.swarm.context.cast(@import("../app.zig").App);
+    const child_id = ctx.swarm.reserveFreeSlot() orelse
+        return r.errResult(call, "No agent slots left");
+
+    const prompt = std.fmt.allocPrint(ctx.alloc,
+        \\Your Task: {s}
+        \\
+        \\{s}
+    , .{ args.description, args.prompt }) catch return r.errResult(call, "out of memory");
+
+    const parts = ctx.alloc.alloc(prv.adapter.ContentPart, 1) catch
+        return r.errResult(call, "oom");
+    parts[0] = .{ .text = prompt };
+
+    app.cmd_queue.append(ctx.io, .{
+        .spawn_agent = .{
+            .agent_id = child_id,
+            .parent_id = ctx.self_id,
+            .agent_type = @intFromEnum(r.reg.AgentType.task),
+            .prompt = parts,
+            .token_budget = args.token_budget,
+        },
+    }) catch {
+        ctx.swarm.releaseReservation(child_id);
+        return r.errResult(call, "command queue is full, inform user");
+    };
+
+    r.setToolChild(ctx, call, child_id);
+    r.setToolStatusPrint(ctx, call, "task -> {s}", .{args.description});
+
+    // The slot event fires on completion, failure or a failed spawn.
+    const slot = &ctx.swarm.slots[child_id.index];
+    slot.event.wait(ctx.io) catch {
+        stopChild(ctx, child_id);
+        return r.errResult(call, "canceled");
+    };
+    if (ctx.isCanceled()) {
+        stopChild(ctx, child_id);
+        return r.errResult(call, "canceled");
+    }
+
+    const state = ctx.swarm.getSlotState(child_id) orelse
+        return r.errResult(call, "sub-agent could not be started");
+    const child = ctx.swarm.getAgent(child_id).?;
+    const usage = child.total_usage;
+    const text = ctx.alloc.dupe(u8, prv.tool.extractChildResult(ctx.swarm, child_id)) catch {
+        ctx.swarm.releaseAgent(child_id);
+        return r.errResult(call, "oom");
+    };
+    ctx.swarm.releaseAgent(child_id);
+
+    r.setToolStatusPrint(ctx, call, "task -> {s} ({d} tokens)", .{ args.description, usage.input_tokens + usage.output_tokens });
+
+    return .{
+        .call_id = call.id,
+        .name = call.name,
+        .content = text,
+        .is_error = state == .failed,
+    };
+}
+
+/// The parent got canceled while waiting, take the sub-agent down with it.
+pub fn stopChild(ctx: prv.tool.ToolContext, child_id: prv.Swarm.AgentId) void {
+    const state = ctx.swarm.getSlotState(child_id) orelse return;
+    // not spawned yet, the queued spawn sees the dropped reservation
+    if (state == .reserved) return ctx.swarm.releaseReservation(child_id);
+    if (state == .active) ctx.swarm.getAgent(child_id).?.cancel();
+    ctx.swarm.releaseAgent(child_id);
+}
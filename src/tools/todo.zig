@@ -41,13 +41,14 @@ pub const CreateTodoTool = tc.Tool{
         \\
         \\NOTE that you should not use this tool if there is only one trivial todo to do. In this case you are better off just doing the todo directly.
         \\
-        \\All todos are created with status \`pending\`.
+        \\All todos are created with status \`pending\` and appended to the list. Work the list top down, todos with \`depends_on\` wait until those are done.
         \\
         ,
         .parameters_schema =
         \\{"type":"object","properties":{
         \\  "subject": {"type": "string", "description":"A brief, actionable title in imperative form"},
-        \\  "description":{"type":"string","description":"What needs to be done"}
+        \\  "description":{"type":"string","description":"What needs to be done"},
+        \\  "depends_on":{"type":"array","items":{"type":"integer"},"description":"IDs of todos that must be done before this one"}
         \\},"required":["description", "subject"]}
         ,
     },
@@ -90,7 +91,7 @@ pub const UpdateTodoStateTool = tc.Tool{
 // ── Tool Implementations ─────────────────────────────────────────────
 
 fn createTodo(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
-    const args = r.parseArgs(struct { subject: []const u8, description: []const u8 }, ctx.alloc, call) orelse
+    const args = r.parseArgs(struct { subject: []const u8, description: []const u8, depends_on: []const u32 = &.{} }, ctx.alloc, call) orelse
         return r.errResult(call, "invalid arguments: expected {\"description\": \"...\"}");
 
     r.setToolStatusPrint(ctx, call, "new todo {s}", .{args.subject});
@@ -99,23 +100,16 @@ fn createTodo(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
         return r.errResult(call, "out of memory");
     const description = ctx.alloc.dupe(u8, args.description) catch
         return r.errResult(call, "out of memory");
+    const depends_on = ctx.alloc.dupe(u32, args.depends_on) catch
+        return r.errResult(call, "out of memory");
 
     const id = blk: {
         const g = ctx.agent().todo_list.lock(ctx.io);
         defer g.unlock();
-        if (g.ptr.count >= TodoList.max_todos)
-            return r.errResult(call, "todo list full (max 64 todos)");
-
-        const tid = g.ptr.next_id;
-        g.ptr.next_id += 1;
-        g.ptr.todos[g.ptr.count] = .{
-            .id = tid,
-            .subject = subject,
-            .description = description,
-            .state = .pending,
+        break :blk g.ptr.apply(.{ .add = .{ .subject = subject, .description = description, .depends_on = depends_on } }, .agent) catch |err| switch (err) {
+            error.TodoNotFound => return r.errResult(call, "depends_on names a todo that does not exist. Call list_todos"),
+            else => return applyErrResult(ctx, call, g.ptr, err, 0),
         };
-        g.ptr.count += 1;
-        break :blk tid;
     };
 
     const msg = std.fmt.allocPrint(ctx.alloc, "Todo created with ID {d}", .{id}) catch
@@ -133,6 +127,7 @@ fn getTodo(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
         defer g.unlock();
         const todo = g.ptr.findById(args.id) orelse
             return r.errResult(call, "todo not found");
+        g.ptr.markSeen();
         break :blk Todo{
             .id = todo.id,
             .subject = ctx.alloc.dupe(u8, todo.subject) catch
@@ -157,6 +152,7 @@ fn listTodos(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
     const snap = blk: {
         const g = ctx.agent().todo_list.lock(ctx.io);
         defer g.unlock();
+        g.ptr.markSeen();
         if (g.ptr.count == 0) break :blk &[_]Todo{};
         const buf = ctx.alloc.alloc(Todo, g.ptr.count) catch
             return r.errResult(call, "out of memory");
@@ -168,6 +164,8 @@ fn listTodos(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
                 .description = ctx.alloc.dupe(u8, t.description) catch
                     return r.errResult(call, "out of memory"),
                 .state = t.state,
+                .depends_on = ctx.alloc.dupe(u32, t.depends_on) catch
+                    return r.errResult(call, "out of memory"),
             };
         }
         break :blk @as([]const Todo, buf);
@@ -175,6 +173,14 @@ fn listTodos(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
 
     if (snap.len == 0) return r.okResult(call, "No todos.");
 
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const changed = app.changedPaths(ctx.alloc) catch &.{};
+    const suspicious = ctx.alloc.alloc(bool, snap.len) catch
+        return r.errResult(call, "out of memory");
+    for (snap, suspicious) |todo, *flag| {
+        flag.* = todo.state == .done and auditTodo(todo, changed) == .untouched;
+    }
+
     const spans = ctx.alloc.alloc(r.tui.Span, 1 + snap.len * 2) catch
         return r.errResult(call, "out of memory");
     const lines = ctx.alloc.alloc([]const r.tui.Span, 1 + snap.len) catch
@@ -191,7 +197,10 @@ fn listTodos(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
                 .done => .green,
             } },
         };
-        spans[start + 1] = .{
+        spans[start + 1] = if (suspicious[i]) .{
+            .content = std.fmt.allocPrint(ctx.alloc, " {s} (done, but no referenced file changed)", .{todo.subject}) catch todo.subject,
+            .style = .{ .fg = .yellow },
+        } else .{
             .content = std.fmt.allocPrint(ctx.alloc, " {s}", .{todo.subject}) catch todo.subject,
         };
         lines[i + 1] = spans[start .. start + 2];
@@ -199,9 +208,16 @@ fn listTodos(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
     r.setToolStatusParagraph(ctx, call, lines) catch {};
 
     var allocating = std.Io.Writer.Allocating.init(ctx.alloc);
-    for (snap) |todo| {
-        allocating.writer.print("{d}. [{s}] subject: {s}\n", .{
-            todo.id, todo.state.toString(), todo.subject,
+    for (snap, suspicious) |todo, flag| {
+        allocating.writer.print("{d}. [{s}] subject: {s}", .{ todo.id, todo.state.toString(), todo.subject }) catch
+            return r.errResult(call, "out of memory");
+        for (todo.depends_on, 0..) |dep, i| {
+            allocating.writer.print("{s}{d}", .{ if (i == 0) " (after " else ", ", dep }) catch
+                return r.errResult(call, "out of memory");
+        }
+        allocating.writer.print("{s}{s}\n", .{
+            if (todo.depends_on.len > 0) ")" else "",
+            if (flag) " (suspicious: none of the referenced files were changed)" else "",
         }) catch return r.errResult(call, "out of memory");
     }
 
@@ -218,9 +234,9 @@ fn updateTodoState(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
     const snap = blk: {
         const g = ctx.agent().todo_list.lock(ctx.io);
         defer g.unlock();
-        const todo = g.ptr.findById(args.id) orelse
-            return r.errResult(call, "todo not found");
-        todo.state = new_state;
+        _ = g.ptr.apply(.{ .set_state = .{ .id = args.id, .state = new_state } }, .agent) catch |err|
+            return applyErrResult(ctx, call, g.ptr, err, args.id);
+        const todo = g.ptr.findById(args.id).?;
         break :blk .{
             .id = todo.id,
             .subject = ctx.alloc.dupe(u8, todo.subject) catch
@@ -248,14 +264,169 @@ fn updateTodoDetail(ctx: tc.ToolContext, call: apt.ToolCall) apt.ToolResult {
     const id = blk: {
         const g = ctx.agent().todo_list.lock(ctx.io);
         defer g.unlock();
-        const todo = g.ptr.findById(args.id) orelse
-            return r.errResult(call, "todo not found");
-        todo.description = new_desc;
-        break :blk todo.id;
+        break :blk g.ptr.apply(.{ .set_description = .{ .id = args.id, .description = new_desc } }, .agent) catch |err|
+            return applyErrResult(ctx, call, g.ptr, err, args.id);
     };
 
     const msg = std.fmt.allocPrint(ctx.alloc, "Todo {d} description updated", .{id}) catch
         return r.errResult(call, "out of memory");
 
     return r.okResult(call, msg);
-}
\ No newline at end of file
+}
+
+/// Called with the todo lock held, `list` reflects the current state.
+fn applyErrResult(ctx: tc.ToolContext, call: apt.ToolCall, list: *TodoList, err: TodoList.ApplyError, id: u32) apt.ToolResult {
+    return switch (err) {
+        error.TodoListFull => r.errResult(call, "todo list full (max 64 todos)"),
+        error.TodoNotFound => r.errResult(call, "todo not found, it may have been removed by the user. Call list_todos"),
+        error.TodoConflict => blk: {
+            const todo = list.findById(id).?;
+            const msg = std.fmt.allocPrint(ctx.alloc,
+                \\Conflict: the user changed todo {d} since you last read it. Current state: [{s}] subject: {s}
+                \\ description: {s}
+                \\Nothing was changed. Retry if your update still applies.
+            , .{ todo.id, todo.state.toString(), todo.subject, todo.description }) catch
+                break :blk r.errResult(call, "out of memory");
+            break :blk r.errResult(call, msg);
+        },
+    };
+}
+
+// ── File audit ───────────────────────────────────────────────────────
+// Todos that name files are checked against the workspace change set.
+
+/// What the tool audit trail says about the files a todo mentions.
+pub const FileAudit = enum {
+    no_refs,
+    /// at least one referenced file was changed this session
+    touched,
+    /// files are referenced but none of them changed
+    untouched,
+};
+
+pub fn auditTodo(todo: Todo, changed_paths: []const []const u8) FileAudit {
+    var any = false;
+    for ([_][]const u8{ todo.subject, todo.description }) |text| {
+        var it = PathRefIterator{ .text = text };
+        while (it.next()) |ref| {
+            any = true;
+            for (changed_paths) |path| {
+                if (pathMatchesRef(path, ref)) return .touched;
+            }
+        }
+    }
+    return if (any) .untouched else .no_refs;
+}
+
+/// Tokens that look like file paths: `src/app.zig`, `build.zig:12`, `README.md`.
+pub const PathRefIterator = struct {
+    text: []const u8,
+    pos: usize = 0,
+
+    pub fn next(self: *PathRefIterator) ?[]const u8 {
+        const delims = " \t\r\n,;()[]{}<>`'\"";
+        while (self.pos < self.text.len) {
+            while (self.pos < self.text.len and std.mem.indexOfScalar(u8, delims, self.text[self.pos]) != null) self.pos += 1;
+            const start = self.pos;
+            while (self.pos < self.text.len and std.mem.indexOfScalar(u8, delims, self.text[self.pos]) == null) self.pos += 1;
+            var token = std.mem.trimRight(u8, self.text[start..self.pos], ".:!?");
+            if (std.mem.indexOf(u8, token, "://") != null) continue;
+            // drop a `:line` suffix
+            if (std.mem.lastIndexOfScalar(u8, token, ':')) |colon| token = token[0..colon];
+            if (looksLikePath(token)) return token;
+        }
+        return null;
+    }
+};
+
+fn looksLikePath(token: []const u8) bool {
+    if (token.len < 3) return false;
+    const base = std.fs.path.basename(token);
+    const dot = std.mem.lastIndexOfScalar(u8, base, '.') orelse return false;
+    const ext = base[dot + 1 ..];
+    if (dot == 0 and std.mem.indexOfScalar(u8, token, '/') == null) return false;
+    if (ext.len == 0 or ext.len > 5) return false;
+    var has_alpha = false;
+    for (ext) |c| {
+        if (!std.ascii.isAlphanumeric(c)) return false;
+        if (std.ascii.isAlphabetic(c)) has_alpha = true;
+    }
+    return has_alpha and !std.mem.eql(u8, token, "e.g") and !std.mem.eql(u8, token, "i.e");
+}
+
+/// `path` is absolute, `ref` as written in the todo, usually project relative.
+fn pathMatchesRef(path: []const u8, ref: []const u8) bool {
+    const trimmed = if (std.mem.startsWith(u8, ref, "./")) ref[2..] else ref;
+    if (std.mem.eql(u8, path, trimmed)) return true;
+    return path.len > trimmed.len and std.mem.endsWith(u8, path, trimmed) and path[path.len - trimmed.len - 1] == '/';
+}
+
+test "TodoList.apply reports user edits as conflicts" {
+    var list: TodoList = .{};
+    const a = try list.apply(.{ .add = .{ .subject = "a", .description = "" } }, .agent);
+    const b = try list.apply(.{ .add = .{ .subject = "b", .description = "" } }, .agent);
+    try std.testing.expect(!list.hasUnseenUserChanges());
+
+    _ = try list.apply(.{ .set_state = .{ .id = a, .state = .done } }, .user);
+    try std.testing.expect(list.hasUnseenUserChanges());
+    try std.testing.expectError(error.TodoConflict, list.apply(.{ .set_state = .{ .id = a, .state = .in_progress } }, .agent));
+    // the conflict showed the agent the current state, a retry goes through
+    _ = try list.apply(.{ .set_state = .{ .id = a, .state = .in_progress } }, .agent);
+
+    _ = try list.apply(.{ .remove = a }, .user);
+    try std.testing.expectError(error.TodoNotFound, list.apply(.{ .set_state = .{ .id = a, .state = .done } }, .agent));
+    try std.testing.expectEqual(@as(usize, 1), list.count);
+    try std.testing.expectEqual(b, list.todos[0].id);
+    list.markSeen();
+    try std.testing.expect(!list.hasUnseenUserChanges());
+}
+
+test "TodoList user edits reorder and rename" {
+    var list: TodoList = .{};
+    const a = try list.apply(.{ .add = .{ .subject = "a", .description = "" } }, .agent);
+    const b = try list.apply(.{ .add = .{ .subject = "b", .description = "" } }, .agent);
+    const c = try list.apply(.{ .add = .{ .subject = "c", .description = "" } }, .agent);
+
+    _ = try list.apply(.{ .move = .{ .id = c, .delta = -5 } }, .user);
+    try std.testing.expectEqual(c, list.todos[0].id);
+    try std.testing.expectEqual(a, list.todos[1].id);
+    _ = try list.apply(.{ .move = .{ .id = c, .delta = 1 } }, .user);
+    try std.testing.expectEqual(a, list.todos[0].id);
+    try std.testing.expectEqual(c, list.todos[1].id);
+    try std.testing.expectEqual(b, list.todos[2].id);
+
+    _ = try list.apply(.{ .set_subject = .{ .id = b, .subject = "renamed" } }, .user);
+    try std.testing.expectEqualStrings("renamed", list.findById(b).?.subject);
+    try std.testing.expect(list.hasUnseenUserChanges());
+}
+
+test "TodoList next actionable todo respects dependencies" {
+    var list: TodoList = .{};
+    const a = try list.apply(.{ .add = .{ .subject = "a", .description = "" } }, .agent);
+    const b = try list.apply(.{ .add = .{ .subject = "b", .description = "", .depends_on = &.{a} } }, .agent);
+    try std.testing.expectError(error.TodoNotFound, list.apply(.{ .add = .{ .subject = "x", .description = "", .depends_on = &.{99} } }, .agent));
+
+    // b is blocked until a is done, even when moved to the top
+    _ = try list.apply(.{ .move = .{ .id = b, .delta = -1 } }, .user);
+    try std.testing.expect(list.isBlocked(list.findById(b).?.*));
+    try std.testing.expectEqual(a, list.nextActionable().?.id);
+
+    _ = try list.apply(.{ .set_state = .{ .id = a, .state = .done } }, .agent);
+    try std.testing.expectEqual(b, list.nextActionable().?.id);
+    _ = try list.apply(.{ .remove = a }, .user);
+    try std.testing.expect(!list.isBlocked(list.findById(b).?.*));
+    _ = try list.apply(.{ .set_state = .{ .id = b, .state = .done } }, .user);
+    try std.testing.expect(list.nextActionable() == null);
+}
+
+test "todo file audit" {
+    var it = PathRefIterator{ .text = "Fix parsing in `src/app.zig:120`, see e.g. README.md. Bump 1.5 and visit https://x.io/a.html" };
+    try std.testing.expectEqualStrings("src/app.zig", it.next().?);
+    try std.testing.expectEqualStrings("README.md", it.next().?);
+    try std.testing.expect(it.next() == null);
+
+    const todo = Todo{ .id = 1, .subject = "Parser", .description = "update ./src/app.zig", .state = .done };
+    try std.testing.expectEqual(FileAudit.touched, auditTodo(todo, &.{"/repo/src/app.zig"}));
+    try std.testing.expectEqual(FileAudit.untouched, auditTodo(todo, &.{"/repo/src/myapp.zig"}));
+    try std.testing.expectEqual(FileAudit.no_refs, auditTodo(.{ .id = 2, .subject = "Think", .description = "plan it", .state = .done }, &.{}));
+}
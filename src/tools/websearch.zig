@@ -0,0 +1,336 @@
+//! `web_search`: titles, URLs and snippets for a query, for the agent to
+//! pick pages from. Three backends, configured with
+//! `blitz.set_web_search`:
+//!
+//! - a SearXNG instance (`url`), asked for its JSON output
+//! - the Brave Search API, the key read from `brave_key_env`
+//! - DuckDuckGo's HTML page, scraped, needs no setup
+//!
+//! `auto` (the default) takes the first of them that is configured.
+const prv = @import("provider");
+const r = @import("root.zig");
+const std = @import("std");
+
+pub const DEFAULT_RESULTS = 5;
+const MAX_RESULTS = 10;
+const TIMEOUT_MS = 15_000;
+const MAX_BODY_BYTES = 2 * 1024 * 1024;
+const BRAVE_URL = "https://api.search.brave.com/res/v1/web/search";
+const DUCKDUCKGO_URL = "https://html.duckduckgo.com/html/";
+
+pub const Backend = enum { auto, searxng, brave, duckduckgo };
+
+pub const Config = struct {
+    backend: Backend = .auto,
+    /// SearXNG base url, `https://search.example.org`
+    searxng_url: []const u8 = "",
+    brave_key_env: []const u8 = "BRAVE_API_KEY",
+
+    /// The backend `auto` stands for: SearXNG when a url is set, Brave when
+    /// its key is in the env, DuckDuckGo otherwise.
+    pub fn resolve(self: Config, env: *const std.process.Environ.Map) Backend {
+        if (self.backend != .auto) return self.backend;
+        if (self.searxng_url.len > 0) return .searxng;
+        if (env.get(self.brave_key_env)) |key| if (key.len > 0) return .brave;
+        return .duckduckgo;
+    }
+};
+
+pub const Hit = struct {
+    title: []const u8,
+    url: []const u8,
+    snippet: []const u8 = "",
+};
+
+pub const WebSearchTool = prv.tool.Tool{
+    .def = .{
+        .name = "web_search",
+        .description =
+        \\Search the web. Returns titles, URLs and snippets of the top results.
+        \\
+        \\Use it for documentation, error messages, release notes and anything newer than your training data. Snippets are short, fetch a page before relying on it.
+        ,
+        .parameters_schema =
+        \\{
+        \\  "type": "object",
+        \\  "properties": {
+        \\      "query": {"type": "string", "description": "The search query"},
+        \\      "count": {"type": "integer", "description": "Number of results, 1 to 10 (default 5)"}
+        \\  },
+        \\  "required": ["query"]
+        \\}
+        ,
+    },
+    .func = &run,
+};
+
+fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolResult {
+    const Args = struct {
+        query: []const u8,
+        count: u8 = DEFAULT_RESULTS,
+    };
+    const args = std.json.parseFromSliceLeaky(Args, ctx.alloc, call.arguments, .{
+        .ignore_unknown_fields = true,
+    }) catch return r.errResult(call, "invalid arguments: expected {\"query\": \"...\"}");
+    const query = std.mem.trim(u8, args.query, " \t\r\n");
+    if (query.len == 0) return r.errResult(call, "empty query");
+    const count = std.math.clamp(args.count, 1, MAX_RESULTS);
+
+    const app = ctx.swarm.context.cast(r.r.app.App);
+    const config = app.web_search;
+    const backend = config.resolve(ctx.swarm.exec.env);
+    r.setToolStatusPrint(ctx, call, "\"{s}\" ({s})", .{ query, @tagName(backend) });
+
+    const hits = search(ctx, config, backend, query, count) catch |err| {
+        const msg = std.fmt.allocPrint(ctx.alloc, "{s} search failed: {s}", .{ @tagName(backend), @errorName(err) }) catch "search failed";
+        return r.errResult(call, msg);
+    };
+    if (hits.len == 0) return r.okResult(call, "no results");
+
+    var out = std.Io.Writer.Allocating.init(ctx.alloc);
+    const w = &out.writer;
+    for (hits[0..@min(hits.len, count)], 1..) |hit, i| {
+        w.print("{d}. {s}\n   {s}\n", .{ i, hit.title, hit.url }) catch return r.errResult(call, "oom");
+        if (hit.snippet.len > 0) w.print("   {s}\n", .{hit.snippet}) catch return r.errResult(call, "oom");
+    }
+    return r.okResult(call, out.written());
+}
+
+fn search(ctx: prv.tool.ToolContext, config: Config, backend: Backend, query: []const u8, count: u8) ![]const Hit {
+    var url = std.Io.Writer.Allocating.init(ctx.alloc);
+    const w = &url.writer;
+    switch (backend) {
+        .searxng => {
+            if (config.searxng_url.len == 0) return error.NoSearxngUrl;
+            try w.print("{s}/search?format=json&q=", .{std.mem.trimRight(u8, config.searxng_url, "/")});
+            try writeQuery(w, query);
+            return parseSearxng(ctx.alloc, try get(ctx, url.written(), &.{}));
+        },
+        .brave => {
+            const key = ctx.swarm.exec.env.get(config.brave_key_env) orelse return error.BraveKeyNotSet;
+            try w.print("{s}?count={d}&q=", .{ BRAVE_URL, count });
+            try writeQuery(w, query);
+            return parseBrave(ctx.alloc, try get(ctx, url.written(), &.{
+                .{ .name = "Accept", .value = "application/json" },
+                .{ .name = "X-Subscription-Token", .value = key },
+            }));
+        },
+        .duckduckgo, .auto => {
+            try w.print("{s}?q=", .{DUCKDUCKGO_URL});
+            try writeQuery(w, query);
+            return parseDuckDuckGo(ctx.alloc, try get(ctx, url.written(), &.{
+                // the html endpoint turns away clients without one
+                .{ .name = "User-Agent", .value = "Mozilla/5.0 (X11; Linux x86_64) blitzdenk" },
+            }));
+        },
+    }
+}
+
+/// Body of a GET, drained while it streams so a large page can't stall
+/// the worker. Gives up on timeout and when the run is cancelled.
+fn get(ctx: prv.tool.ToolContext, url: []const u8, headers: []const std.http.Header) ![]const u8 {
+    const pool = &ctx.swarm.pool;
+    const handle = try pool.fetch(url, .GET, null, headers, TIMEOUT_MS);
+    defer pool.release(handle);
+
+    var body: std.ArrayList(u8) = .empty;
+    while (true) {
+        if (try pool.nextChunk(handle)) |chunk| {
+            defer pool.dropChunk(chunk);
+            if (body.items.len + chunk.len > MAX_BODY_BYTES) return error.ResponseTooLarge;
+            try body.appendSlice(ctx.alloc, chunk);
+            continue;
+        }
+        if (pool.isStreamDone(handle)) {
+            // the last chunks may land between the poll and the done flag
+            while (try pool.nextChunk(handle)) |chunk| {
+                defer pool.dropChunk(chunk);
+                try body.appendSlice(ctx.alloc, chunk);
+            }
+            break;
+        }
+        if (pool.timedOut(handle)) return error.Timeout;
+        if (ctx.isCanceled()) return error.Canceled;
+        std.Io.sleep(ctx.io, std.Io.Duration.fromMilliseconds(25), .real) catch return error.Canceled;
+    }
+    const status = try pool.getStatus(handle);
+    if (status.class() != .success) return error.HttpError;
+    return body.items;
+}
+
+/// Query string encoding, spaces become `+`.
+fn writeQuery(w: *std.Io.Writer, text: []const u8) !void {
+    for (text) |ch| {
+        if (std.ascii.isAlphanumeric(ch) or ch == '-' or ch == '_' or ch == '.' or ch == '~') {
+            try w.writeByte(ch);
+        } else if (ch == ' ') {
+            try w.writeByte('+');
+        } else {
+            try w.print("%{X:0>2}", .{ch});
+        }
+    }
+}
+
+pub fn parseSearxng(alloc: std.mem.Allocator, body: []const u8) ![]const Hit {
+    const Response = struct {
+        results: []const struct {
+            title: []const u8 = "",
+            url: []const u8 = "",
+            content: []const u8 = "",
+        } = &.{},
+    };
+    const parsed = try std.json.parseFromSliceLeaky(Response, alloc, body, .{ .ignore_unknown_fields = true });
+    var hits: std.ArrayList(Hit) = .empty;
+    for (parsed.results) |res| {
+        if (res.url.len == 0) continue;
+        try hits.append(alloc, .{ .title = res.title, .url = res.url, .snippet = res.content });
+    }
+    return hits.items;
+}
+
+pub fn parseBrave(alloc: std.mem.Allocator, body: []const u8) ![]const Hit {
+    const Response = struct {
+        web: struct {
+            results: []const struct {
+                title: []const u8 = "",
+                url: []const u8 = "",
+                description: []const u8 = "",
+            } = &.{},
+        } = .{},
+    };
+    const parsed = try std.json.parseFromSliceLeaky(Response, alloc, body, .{ .ignore_unknown_fields = true });
+    var hits: std.ArrayList(Hit) = .empty;
+    for (parsed.web.results) |res| {
+        if (res.url.len == 0) continue;
+        // descriptions mark the matched words with <strong>
+        try hits.append(alloc, .{ .title = try plainText(alloc, res.title), .url = res.url, .snippet = try plainText(alloc, res.description) });
+    }
+    return hits.items;
+}
+
+/// Results of DuckDuckGo's HTML page: `result__a` links carry the title and
+/// a redirect with the target in `uddg`, `result__snippet` the text below.
+pub fn parseDuckDuckGo(alloc: std.mem.Allocator, html: []const u8) ![]const Hit {
+    var hits: std.ArrayList(Hit) = .empty;
+    var pos: usize = 0;
+    while (std.mem.indexOfPos(u8, html, pos, "class=\"result__a\"")) |class_at| {
+        const tag_start = std.mem.lastIndexOf(u8, html[0..class_at], "<a") orelse break;
+        const tag_end = std.mem.indexOfScalarPos(u8, html, class_at, '>') orelse break;
+        const title_end = std.mem.indexOfPos(u8, html, tag_end, "</a>") orelse break;
+        pos = title_end;
+
+        const href = attribute(html[tag_start..tag_end], "href") orelse continue;
+        const target = try resultUrl(alloc, href) orelse continue;
+        var hit = Hit{ .title = try plainText(alloc, html[tag_end + 1 .. title_end]), .url = target };
+
+        const next = std.mem.indexOfPos(u8, html, pos, "class=\"result__a\"") orelse html.len;
+        if (std.mem.indexOfPos(u8, html[0..next], pos, "class=\"result__snippet\"")) |snippet_at| {
+            if (std.mem.indexOfScalarPos(u8, html, snippet_at, '>')) |text_start| {
+                const text_end = std.mem.indexOfPos(u8, html[0..next], text_start, "</a>") orelse next;
+                hit.snippet = try plainText(alloc, html[text_start + 1 .. text_end]);
+            }
+        }
+        try hits.append(alloc, hit);
+    }
+    return hits.items;
+}
+
+fn attribute(tag: []const u8, comptime name: []const u8) ?[]const u8 {
+    const start = (std.mem.indexOf(u8, tag, " " ++ name ++ "=\"") orelse return null) + name.len + 3;
+    const end = std.mem.indexOfScalarPos(u8, tag, start, '"') orelse return null;
+    return tag[start..end];
+}
+
+/// The target of a result link, null for ads.
+fn resultUrl(alloc: std.mem.Allocator, href: []const u8) !?[]const u8 {
+    const link = try plainText(alloc, href);
+    if (std.mem.indexOf(u8, link, "duckduckgo.com/y.js") != null) return null;
+    const at = std.mem.indexOf(u8, link, "uddg=") orelse return link;
+    const value = link[at + 5 ..];
+    return try percentDecode(alloc, value[0 .. std.mem.indexOfScalar(u8, value, '&') orelse value.len]);
+}
+
+fn percentDecode(alloc: std.mem.Allocator, text: []const u8) ![]const u8 {
+    var out: std.ArrayList(u8) = .empty;
+    var i: usize = 0;
+    while (i < text.len) : (i += 1) {
+        if (text[i] == '%' and i + 2 < text.len) {
+            if (std.fmt.parseInt(u8, text[i + 1 .. i + 3], 16)) |byte| {
+                try out.append(alloc, byte);
+                i += 2;
+                continue;
+            } else |_| {}
+        }
+        try out.append(alloc, if (text[i] == '+') ' ' else text[i]);
+    }
+    return out.items;
+}
+
+/// `html` without tags, common entities decoded and whitespace collapsed.
+fn plainText(alloc: std.mem.Allocator, html: []const u8) ![]const u8 {
+    const entities = [_]struct { []const u8, u8 }{
+        .{ "&amp;", '&' }, .{ "&lt;", '<' },  .{ "&gt;", '>' },
+        .{ "&quot;", '"' }, .{ "&#x27;", '\'' }, .{ "&#39;", '\'' },
+        .{ "&nbsp;", ' ' },
+    };
+    var out: std.ArrayList(u8) = .empty;
+    var i: usize = 0;
+    outer: while (i < html.len) {
+        const ch = html[i];
+        if (ch == '<') {
+            i = (std.mem.indexOfScalarPos(u8, html, i, '>') orelse html.len) + 1;
+            continue;
+        }
+        if (ch == '&') {
+            for (entities) |entity| {
+                if (std.mem.startsWith(u8, html[i..], entity[0])) {
+                    try out.append(alloc, entity[1]);
+                    i += entity[0].len;
+                    continue :outer;
+                }
+            }
+        }
+        const space = ch == ' ' or ch == '\n' or ch == '\t' or ch == '\r';
+        if (!space or (out.items.len > 0 and out.items[out.items.len - 1] != ' ')) {
+            try out.append(alloc, if (space) ' ' else ch);
+        }
+        i += 1;
+    }
+    return std.mem.trimRight(u8, out.items, " ");
+}
+
+test "web search result parsing" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    const ddg =
+        \\<div class="result results_links"><h2 class="result__title">
+        \\<a rel="nofollow" class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fziglang.org%2Fdocumentation%2F&amp;rut=abc">Zig <b>Documentation</b></a>
+        \\</h2><a class="result__snippet" href="x">The <b>Zig</b> language &amp; std.</a></div>
+        \\<div class="result"><a rel="nofollow" class="result__a" href="https://duckduckgo.com/y.js?ad=1">Ad</a></div>
+        \\<div class="result"><a class="result__a" rel="nofollow" href="https://example.com/">Example</a></div>
+    ;
+    const hits = try parseDuckDuckGo(alloc, ddg);
+    try std.testing.expectEqual(@as(usize, 2), hits.len);
+    try std.testing.expectEqualStrings("Zig Documentation", hits[0].title);
+    try std.testing.expectEqualStrings("https://ziglang.org/documentation/", hits[0].url);
+    try std.testing.expectEqualStrings("The Zig language & std.", hits[0].snippet);
+    try std.testing.expectEqualStrings("https://example.com/", hits[1].url);
+    try std.testing.expectEqualStrings("", hits[1].snippet);
+
+    const searx = try parseSearxng(alloc,
+        \\{"query": "zig", "results": [{"title": "Zig", "url": "https://ziglang.org", "content": "A language", "engine": "x"}, {"title": "no url"}]}
+    );
+    try std.testing.expectEqual(@as(usize, 1), searx.len);
+    try std.testing.expectEqualStrings("A language", searx[0].snippet);
+
+    const brave = try parseBrave(alloc,
+        \\{"type": "search", "web": {"results": [{"title": "Zig", "url": "https://ziglang.org", "description": "The <strong>Zig</strong> site"}]}}
+    );
+    try std.testing.expectEqualStrings("The Zig site", brave[0].snippet);
+
+    var query = std.Io.Writer.Allocating.init(alloc);
+    try writeQuery(&query.writer, "zig std.Io & c++");
+    try std.testing.expectEqualStrings("zig+std.Io+%26+c%2B%2B", query.written());
+}
@@ -37,14 +37,21 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
 
     const resolved = std.fs.path.resolve(alloc, &.{ ctx.cwd, args.path }) catch
         return r.errResult(call, "failed to resolve path");
+    if (r.guardWorkspacePath(ctx, call, resolved)) |blocked| return blocked;
+
+    // show what changes, not only the new content
+    const existed = fileExists(ctx, resolved);
+    const before = if (existed) readExisting(ctx, resolved) else null;
 
     const decision = ctx.requestPerm(call.id, .always_check, .{ .diff = .{
-        .before = null,
+        .before = before,
         .after = args.content,
         .path = args.path,
     } });
+    var content = args.content;
     switch (decision) {
         .approved => {},
+        .edited => |edited| content = edited,
         .denied => return r.errResult(call, "User declined write"),
         .message => |txt| {
             const wrapped = std.fmt.allocPrint(
@@ -59,7 +66,9 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
 
     if (ctx.isCanceled()) return r.errResult(call, "canceled");
 
-    const res = runWrite(ctx, resolved, args.content) orelse
+    if (existed) r.backupBeforeWrite(ctx, resolved);
+    r.checkpointBeforeWrite(ctx, resolved, call.name);
+    const res = runWrite(ctx, resolved, content) orelse
         return r.errResult(call, "failed to start process");
     defer ctx.swarm.exec.alloc.free(res.stdout);
     defer ctx.swarm.exec.alloc.free(res.stderr);
@@ -86,9 +95,27 @@ fn run(ctx: prv.tool.ToolContext, call: prv.adapter.ToolCall) prv.adapter.ToolRe
         look.value_ptr.* = .{ .last_read = now, .last_write = now };
     }
 
+    r.recordFileChange(ctx, resolved, if (existed) .modified else .added, "write");
+
+    if (decision == .edited) return r.okResult(call, r.partialApprovalNote(alloc, args.path));
     return r.okResult(call, "file written successfully");
 }
 
+fn fileExists(ctx: prv.tool.ToolContext, resolved: []const u8) bool {
+    const res = ctx.swarm.exec.runAndWait(.{ .argv = &.{ "test", "-e", resolved } }) catch return false;
+    defer ctx.swarm.exec.alloc.free(res.stdout);
+    defer ctx.swarm.exec.alloc.free(res.stderr);
+    return res.ty == .success;
+}
+
+fn readExisting(ctx: prv.tool.ToolContext, resolved: []const u8) ?[]const u8 {
+    const res = ctx.swarm.exec.runAndWait(.{ .argv = &.{ "cat", resolved } }) catch return null;
+    defer ctx.swarm.exec.alloc.free(res.stdout);
+    defer ctx.swarm.exec.alloc.free(res.stderr);
+    if (res.ty != .success) return null;
+    return ctx.alloc.dupe(u8, res.stdout) catch null;
+}
+
 fn runWrite(ctx: prv.tool.ToolContext, resolved: []const u8, content: []const u8) ?prv.exec.CmdResult {
     if (std.fs.path.dirname(resolved)) |dir| {
         const cmd_str = std.fmt.allocPrint(ctx.alloc, "mkdir -p {s} && tee {s}", .{ dir, resolved }) catch
@@ -0,0 +1,566 @@
+//! Session transcripts to share. `:export [json|markdown|html] [path]`
+//! writes the current session to `.blitz/exports/<session>.<ext>`, `blitz
+//! export [FORMAT] [SESSION]` prints a stored session, the project's last
+//! one without a name. A transcript is the chat as the TUI showed it: every
+//! entry with the time it entered the chat, tool calls with their arguments
+//! and results, edits with their diffs, and the session's tokens and cost.
+//! Entries of sessions saved before they were timed go without a time.
+//! Exports are plain text, also with session encryption on.
+const std = @import("std");
+const prv = @import("provider");
+const r = @import("root.zig");
+const util = @import("util.zig");
+
+const App = r.app.App;
+const SaveState = r.session.SaveState;
+
+pub const EXPORT_DIR = util.BLITZ_DIR ++ "/exports";
+
+pub const Format = enum {
+    json,
+    markdown,
+    html,
+
+    pub fn parse(name: []const u8) ?Format {
+        if (std.mem.eql(u8, name, "md")) return .markdown;
+        return std.meta.stringToEnum(Format, name);
+    }
+
+    /// From the extension of `path`, null when it names none of them.
+    pub fn fromPath(path: []const u8) ?Format {
+        const ext = std.fs.path.extension(path);
+        if (ext.len < 2) return null;
+        if (std.mem.eql(u8, ext, ".htm")) return .html;
+        return parse(ext[1..]);
+    }
+
+    pub fn extension(self: Format) []const u8 {
+        return switch (self) {
+            .json => "json",
+            .markdown => "md",
+            .html => "html",
+        };
+    }
+};
+
+pub const Transcript = struct {
+    save: SaveState,
+    /// dollars, null when no model of the session has a known price
+    cost: ?f64 = null,
+};
+
+/// Render `t` as `format`. Scratch goes to `alloc` unfreed, pass an arena.
+pub fn write(alloc: std.mem.Allocator, w: *std.Io.Writer, format: Format, t: Transcript) !void {
+    switch (format) {
+        .json => try writeJson(alloc, w, t),
+        .markdown => try writeMarkdown(alloc, w, t),
+        .html => try writeHtml(w, t),
+    }
+    try w.flush();
+}
+
+/// `:export`, the format and path in any order. The format defaults to the
+/// path's extension, then JSON.
+pub fn exportCommand(a: *App, arg: []const u8) !void {
+    const notify = a.arena_app.allocator();
+    var format: ?Format = null;
+    var path: []const u8 = "";
+    var words = std.mem.tokenizeScalar(u8, arg, ' ');
+    while (words.next()) |word| {
+        if (Format.parse(word)) |f| format = f else path = word;
+    }
+    if (a.mainAgent() == null) {
+        try a.notifications.append(notify, "nothing to export yet", .{});
+        return;
+    }
+
+    const name = try a.ensureSessionName();
+    const fmt = format orelse Format.fromPath(path) orelse .json;
+    const alloc = a.sessionAlloc();
+    if (path.len == 0) path = try std.fmt.allocPrint(alloc, "{s}/{s}.{s}", .{ EXPORT_DIR, name, fmt.extension() });
+
+    var messages = std.ArrayList(prv.adapter.Message).empty;
+    defer messages.deinit(a.gpa);
+    var todos: [prv.agent.TodoList.max_todos]prv.agent.Todo = undefined;
+    const save = try r.session.snapshot(a, &messages, &todos);
+
+    if (std.fs.path.dirname(path)) |dir| try std.Io.Dir.cwd().createDirPath(a.io, dir);
+    const file = try std.Io.Dir.cwd().createFile(a.io, path, .{});
+    defer file.close(a.io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(a.io, &buf);
+    var arena = std.heap.ArenaAllocator.init(a.gpa);
+    defer arena.deinit();
+    try write(arena.allocator(), &writer.interface, fmt, .{ .save = save, .cost = a.sessionCost() });
+    try a.notifications.append(notify, "exported {d} entries to {s}", .{ save.chat_render.len, path });
+}
+
+pub const Options = struct {
+    format: Format = .json,
+    /// stored session name, the project's last session when empty
+    session: []const u8 = "",
+};
+
+/// `blitz export`, the transcript goes to stdout.
+pub fn runCli(a: *App, opts: Options) !u8 {
+    const io = a.io;
+    const source = if (opts.session.len > 0) opts.session else r.session.LAST_SESSION_PATH;
+    const content = readSession(a, opts.session) catch |err| {
+        std.debug.print("Error: cannot read session {s}: {s}\n", .{ source, @errorName(err) });
+        return 1;
+    };
+    defer a.gpa.free(content);
+    const plain = a.vault.open(a.gpa, content) catch |err| {
+        std.debug.print("Error: cannot open session {s}: {s}\n", .{ source, @errorName(err) });
+        return 1;
+    };
+    defer if (plain.ptr != content.ptr) a.gpa.free(plain);
+    const parsed = try std.json.parseFromSlice(SaveState, a.gpa, plain, .{
+        .ignore_unknown_fields = true,
+    });
+    defer parsed.deinit();
+
+    // the cached list only, no refresh for a one-shot command
+    if (a.prices == null) {
+        if (a.swarm.exec.env.get("HOME")) |home| a.prices = r.cost.loadCached(io, a.gpa, home);
+    }
+    const meta = parsed.value.meta;
+    const cost = if (a.modelPrice(meta.model)) |price| r.cost.cost(price, meta.usage) else null;
+
+    var arena = std.heap.ArenaAllocator.init(a.gpa);
+    defer arena.deinit();
+    var out_buf: [4096]u8 = undefined;
+    var stdout = std.Io.File.stdout().writer(io, &out_buf);
+    try write(arena.allocator(), &stdout.interface, opts.format, .{ .save = parsed.value, .cost = cost });
+    return 0;
+}
+
+fn readSession(a: *App, name: []const u8) ![]u8 {
+    const io = a.io;
+    const file = if (name.len == 0)
+        try std.Io.Dir.cwd().openFile(io, r.session.LAST_SESSION_PATH, .{ .mode = .read_only })
+    else blk: {
+        const home = a.swarm.exec.env.get("HOME") orelse return error.NoHomeFound;
+        var dir = try r.sessions.openDir(io, home);
+        defer dir.close(io);
+        var path_buf: [256]u8 = undefined;
+        break :blk try dir.openFile(io, try r.sessions.fileName(&path_buf, name), .{ .mode = .read_only });
+    };
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var reader = file.reader(io, &buf);
+    return reader.interface.allocRemaining(a.gpa, .limited(r.sessions.MAX_SESSION_BYTES));
+}
+
+/// A tool call and its result, looked up by id in the saved messages.
+const ToolRun = struct {
+    call: ?prv.adapter.ToolCall = null,
+    result: ?prv.adapter.ToolResult = null,
+};
+
+fn findTool(chat: []const prv.adapter.Message, id: []const u8) ToolRun {
+    var run: ToolRun = .{};
+    for (chat) |msg| {
+        for (msg.parts) |part| switch (part) {
+            .tool_call => |call| {
+                if (std.mem.eql(u8, call.id, id)) run.call = call;
+            },
+            .tool_result => |res| {
+                if (std.mem.eql(u8, res.call_id, id)) run.result = res;
+            },
+            else => {},
+        };
+    }
+    return run;
+}
+
+/// `2026-10-16 14:03:09 UTC`, `2026-10-16T14:03:09Z` with `iso`. Null for
+/// untimed entries.
+fn formatTime(buf: []u8, ms: i64, iso: bool) ?[]const u8 {
+    if (ms <= 0) return null;
+    const es = std.time.epoch.EpochSeconds{ .secs = @intCast(@divTrunc(ms, std.time.ms_per_s)) };
+    const year_day = es.getEpochDay().calculateYearDay();
+    const month_day = year_day.calculateMonthDay();
+    const day_secs = es.getDaySeconds();
+    const args = .{
+        year_day.year,
+        month_day.month.numeric(),
+        month_day.day_index + 1,
+        day_secs.getHoursIntoDay(),
+        day_secs.getMinutesIntoHour(),
+        day_secs.getSecondsIntoMinute(),
+    };
+    if (iso) return std.fmt.bufPrint(buf, "{d:0>4}-{d:0>2}-{d:0>2}T{d:0>2}:{d:0>2}:{d:0>2}Z", args) catch null;
+    return std.fmt.bufPrint(buf, "{d:0>4}-{d:0>2}-{d:0>2} {d:0>2}:{d:0>2}:{d:0>2} UTC", args) catch null;
+}
+
+fn roleLabel(role: prv.adapter.Role) []const u8 {
+    return switch (role) {
+        .system => "System",
+        .user => "User",
+        .agent => "Agent",
+    };
+}
+
+fn title(save: SaveState) []const u8 {
+    return if (save.meta.title.len > 0) save.meta.title else "blitzdenk session";
+}
+
+fn diffPrefix(kind: r.tui.DiffLineKind) []const u8 {
+    return switch (kind) {
+        .addition => "+",
+        .deletion => "-",
+        .context => " ",
+        .header => "",
+    };
+}
+
+fn writeDiff(w: *std.Io.Writer, diff: r.app.ChatPart.DiffEntry) !void {
+    for (diff.diff_lines) |line| try w.print("{s}{s}\n", .{ diffPrefix(line.kind), line.content });
+}
+
+fn writePlan(w: *std.Io.Writer, plan: r.app.ChatPart.PlanEntry) !void {
+    for (plan.lines) |line| {
+        for (line.spans.items) |span| try w.writeAll(span.content);
+        try w.writeByte('\n');
+    }
+}
+
+// ── JSON ──
+
+const JsonDoc = struct {
+    session: []const u8,
+    title: []const u8,
+    cwd: []const u8,
+    model: []const u8,
+    usage: prv.adapter.TokenUsage,
+    cost_usd: ?f64,
+    entries: []const JsonEntry,
+};
+
+const JsonEntry = struct {
+    role: prv.adapter.Role,
+    time: ?[]const u8,
+    parts: []const JsonPart,
+};
+
+const JsonPart = struct {
+    type: []const u8,
+    text: ?[]const u8 = null,
+    tool: ?[]const u8 = null,
+    arguments: ?std.json.Value = null,
+    result: ?[]const u8 = null,
+    is_error: ?bool = null,
+    path: ?[]const u8 = null,
+    diff: ?[]const u8 = null,
+    kind: ?[]const u8 = null,
+    http_status: ?u16 = null,
+};
+
+fn writeJson(alloc: std.mem.Allocator, w: *std.Io.Writer, t: Transcript) !void {
+    const save = t.save;
+    const entries = try alloc.alloc(JsonEntry, save.chat_render.len);
+    for (save.chat_render, entries) |entry, *out| {
+        var time_buf: [32]u8 = undefined;
+        const time = formatTime(&time_buf, entry.time_ms, true);
+        const parts = try alloc.alloc(JsonPart, entry.parts.len);
+        for (entry.parts, parts) |part, *p| p.* = try jsonPart(alloc, save.chat, part);
+        out.* = .{
+            .role = entry.role,
+            .time = if (time) |s| try alloc.dupe(u8, s) else null,
+            .parts = parts,
+        };
+    }
+    const doc = JsonDoc{
+        .session = save.name,
+        .title = save.meta.title,
+        .cwd = save.meta.cwd,
+        .model = save.meta.model,
+        .usage = save.meta.usage,
+        .cost_usd = t.cost,
+        .entries = entries,
+    };
+    try std.json.Stringify.value(doc, .{ .whitespace = .indent_2, .emit_null_optional_fields = false }, w);
+    try w.writeByte('\n');
+}
+
+fn jsonPart(alloc: std.mem.Allocator, chat: []const prv.adapter.Message, part: r.app.ChatPart) !JsonPart {
+    return switch (part) {
+        .message => |text| .{ .type = "text", .text = text },
+        .plain_text => |text| .{ .type = "text", .text = text },
+        .thinking => |text| .{ .type = "thinking", .text = text },
+        .provider_error => |err| .{ .type = "error", .text = err.message, .kind = @tagName(err.kind), .http_status = err.http_status },
+        .plan => |plan| blk: {
+            var out = std.Io.Writer.Allocating.init(alloc);
+            try writePlan(&out.writer, plan);
+            break :blk .{ .type = "plan", .text = out.written() };
+        },
+        .diff => |diff| blk: {
+            var out = std.Io.Writer.Allocating.init(alloc);
+            try writeDiff(&out.writer, diff);
+            break :blk .{ .type = "diff", .path = diff.path, .diff = out.written() };
+        },
+        .tool_call => |entry| blk: {
+            const run = findTool(chat, entry.call_id);
+            // arguments stay structured when they parse, as sent otherwise
+            const args: ?std.json.Value = if (run.call) |call|
+                std.json.parseFromSliceLeaky(std.json.Value, alloc, call.arguments, .{}) catch .{ .string = call.arguments }
+            else
+                null;
+            break :blk .{
+                .type = "tool_call",
+                .tool = entry.tool_name,
+                .arguments = args,
+                .result = if (run.result) |res| res.content else null,
+                .is_error = if (run.result) |res| res.is_error else null,
+            };
+        },
+    };
+}
+
+// ── Markdown ──
+
+fn writeMarkdown(alloc: std.mem.Allocator, w: *std.Io.Writer, t: Transcript) !void {
+    const save = t.save;
+    try w.print("# {s}\n\n", .{title(save)});
+    if (save.name.len > 0) try w.print("- session: {s}\n", .{save.name});
+    try w.print("- project: `{s}`\n- model: `{s}`\n", .{ save.meta.cwd, save.meta.model });
+    try writeUsage(w, "- ", t);
+    try w.writeByte('\n');
+
+    for (save.chat_render) |entry| {
+        var time_buf: [32]u8 = undefined;
+        try w.print("## {s}", .{roleLabel(entry.role)});
+        if (formatTime(&time_buf, entry.time_ms, false)) |time| try w.print(", {s}", .{time});
+        try w.writeAll("\n\n");
+        for (entry.parts) |part| try markdownPart(alloc, w, save.chat, part);
+    }
+}
+
+fn writeUsage(w: *std.Io.Writer, bullet: []const u8, t: Transcript) !void {
+    const usage = t.save.meta.usage;
+    try w.print("{s}tokens: {d} in, {d} out, {d} cached\n", .{ bullet, usage.input_tokens, usage.output_tokens, usage.cached_tokens });
+    var cost_buf: [32]u8 = undefined;
+    if (t.cost) |dollars| try w.print("{s}cost: {s}\n", .{ bullet, r.cost.format(&cost_buf, dollars) });
+}
+
+fn markdownPart(alloc: std.mem.Allocator, w: *std.Io.Writer, chat: []const prv.adapter.Message, part: r.app.ChatPart) !void {
+    switch (part) {
+        .message, .plain_text => |text| try w.print("{s}\n\n", .{text}),
+        .thinking => |text| {
+            try w.writeAll("<details><summary>Thinking</summary>\n\n");
+            try w.print("{s}\n\n</details>\n\n", .{text});
+        },
+        .provider_error => |err| {
+            try w.print("**Error** ({s}", .{@tagName(err.kind)});
+            if (err.http_status) |status| try w.print(", HTTP {d}", .{status});
+            try w.print("): {s}\n\n", .{err.message});
+        },
+        .plan => |plan| {
+            try w.writeAll("**Plan**\n\n");
+            try writePlan(w, plan);
+            try w.writeByte('\n');
+        },
+        .diff => |diff| {
+            try w.print("**Edit** `{s}`\n\n", .{diff.path});
+            var body = std.Io.Writer.Allocating.init(alloc);
+            try writeDiff(&body.writer, diff);
+            try writeFenced(w, "diff", body.written());
+        },
+        .tool_call => |entry| {
+            const run = findTool(chat, entry.call_id);
+            try w.print("**Tool** `{s}`\n\n", .{entry.tool_name});
+            if (run.call) |call| try writeFenced(w, "json", call.arguments);
+            if (run.result) |res| {
+                try w.print("<details><summary>{s}</summary>\n\n", .{if (res.is_error) "Error" else "Result"});
+                try writeFenced(w, "", res.content);
+                try w.writeAll("</details>\n\n");
+            }
+        },
+    }
+}
+
+/// Code block around `body`, the fence longer than any backtick run in it.
+fn writeFenced(w: *std.Io.Writer, lang: []const u8, body: []const u8) !void {
+    var longest: usize = 0;
+    var run: usize = 0;
+    for (body) |ch| {
+        run = if (ch == '`') run + 1 else 0;
+        longest = @max(longest, run);
+    }
+    const fence = @max(3, longest + 1);
+    for (0..fence) |_| try w.writeByte('`');
+    try w.print("{s}\n{s}", .{ lang, body });
+    if (!std.mem.endsWith(u8, body, "\n")) try w.writeByte('\n');
+    for (0..fence) |_| try w.writeByte('`');
+    try w.writeAll("\n\n");
+}
+
+// ── HTML ──
+
+const HTML_STYLE =
+    \\body{font:15px/1.5 system-ui,sans-serif;max-width:60rem;margin:2rem auto;padding:0 1rem;color:#222}
+    \\section{border-left:3px solid #ccc;padding:0 1rem;margin:1.5rem 0}
+    \\section.user{border-color:#4a7fd4}section.agent{border-color:#4caf50}
+    \\h2{font-size:1rem}time{color:#888;font-weight:normal}
+    \\pre{background:#f6f6f6;padding:.5rem;overflow-x:auto;white-space:pre-wrap}
+    \\.text{white-space:pre-wrap}.add{color:#1a7f37}.del{color:#cf222e}.err{color:#cf222e}
+;
+
+fn writeHtml(w: *std.Io.Writer, t: Transcript) !void {
+    const save = t.save;
+    try w.writeAll("<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>");
+    try escapeHtml(w, title(save));
+    try w.print("</title>\n<style>{s}</style></head>\n<body>\n<h1>", .{HTML_STYLE});
+    try escapeHtml(w, title(save));
+    try w.writeAll("</h1>\n<ul>\n");
+    if (save.name.len > 0) try htmlItem(w, "session", save.name);
+    try htmlItem(w, "project", save.meta.cwd);
+    try htmlItem(w, "model", save.meta.model);
+    try writeUsage(w, "<li>", t);
+    try w.writeAll("</ul>\n");
+
+    for (save.chat_render) |entry| {
+        var time_buf: [32]u8 = undefined;
+        try w.print("<section class=\"{s}\">\n<h2>{s}", .{ @tagName(entry.role), roleLabel(entry.role) });
+        if (formatTime(&time_buf, entry.time_ms, false)) |time| try w.print(" <time>{s}</time>", .{time});
+        try w.writeAll("</h2>\n");
+        for (entry.parts) |part| try htmlPart(w, save.chat, part);
+        try w.writeAll("</section>\n");
+    }
+    try w.writeAll("</body></html>\n");
+}
+
+fn htmlItem(w: *std.Io.Writer, label: []const u8, value: []const u8) !void {
+    try w.print("<li>{s}: ", .{label});
+    try escapeHtml(w, value);
+    try w.writeByte('\n');
+}
+
+fn htmlPart(w: *std.Io.Writer, chat: []const prv.adapter.Message, part: r.app.ChatPart) !void {
+    switch (part) {
+        .message, .plain_text => |text| {
+            try w.writeAll("<div class=\"text\">");
+            try escapeHtml(w, text);
+            try w.writeAll("</div>\n");
+        },
+        .thinking => |text| {
+            try w.writeAll("<details><summary>Thinking</summary><pre>");
+            try escapeHtml(w, text);
+            try w.writeAll("</pre></details>\n");
+        },
+        .provider_error => |err| {
+            try w.print("<p class=\"err\">Error ({s}", .{@tagName(err.kind)});
+            if (err.http_status) |status| try w.print(", HTTP {d}", .{status});
+            try w.writeAll("): ");
+            try escapeHtml(w, err.message);
+            try w.writeAll("</p>\n");
+        },
+        .plan => |plan| {
+            try w.writeAll("<p><b>Plan</b></p><pre>");
+            for (plan.lines) |line| {
+                for (line.spans.items) |span| try escapeHtml(w, span.content);
+                try w.writeByte('\n');
+            }
+            try w.writeAll("</pre>\n");
+        },
+        .diff => |diff| {
+            try w.writeAll("<p><b>Edit</b> <code>");
+            try escapeHtml(w, diff.path);
+            try w.writeAll("</code></p><pre>");
+            for (diff.diff_lines) |line| {
+                const class = switch (line.kind) {
+                    .addition => "add",
+                    .deletion => "del",
+                    .context, .header => "",
+                };
+                try w.print("<span class=\"{s}\">{s}", .{ class, diffPrefix(line.kind) });
+                try escapeHtml(w, line.content);
+                try w.writeAll("</span>\n");
+            }
+            try w.writeAll("</pre>\n");
+        },
+        .tool_call => |entry| {
+            const run = findTool(chat, entry.call_id);
+            try w.writeAll("<details><summary>Tool <code>");
+            try escapeHtml(w, entry.tool_name);
+            try w.writeAll("</code></summary>\n");
+            if (run.call) |call| {
+                try w.writeAll("<pre>");
+                try escapeHtml(w, call.arguments);
+                try w.writeAll("</pre>\n");
+            }
+            if (run.result) |res| {
+                try w.print("<pre{s}>", .{if (res.is_error) " class=\"err\"" else ""});
+                try escapeHtml(w, res.content);
+                try w.writeAll("</pre>\n");
+            }
+            try w.writeAll("</details>\n");
+        },
+    }
+}
+
+fn escapeHtml(w: *std.Io.Writer, text: []const u8) !void {
+    for (text) |ch| switch (ch) {
+        '&' => try w.writeAll("&amp;"),
+        '<' => try w.writeAll("&lt;"),
+        '>' => try w.writeAll("&gt;"),
+        '"' => try w.writeAll("&quot;"),
+        else => try w.writeByte(ch),
+    };
+}
+
+test "transcript formats" {
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const alloc = arena.allocator();
+
+    var user_parts = [_]r.app.ChatPart{.{ .message = "list <files>" }};
+    var agent_parts = [_]r.app.ChatPart{
+        .{ .tool_call = .{ .agent_id = .{ .index = 0, .generation = 0 }, .call_id = "c1", .tool_name = "bash" } },
+        .{ .diff = .{ .path = "a.zig", .diff_lines = &.{
+            .{ .kind = .deletion, .content = "old" },
+            .{ .kind = .addition, .content = "new ```" },
+        } } },
+    };
+    var call_parts = [_]prv.adapter.ContentPart{.{ .tool_call = .{ .id = "c1", .name = "bash", .arguments = "{\"command\":\"ls\"}" } }};
+    var result_parts = [_]prv.adapter.ContentPart{.{ .tool_result = .{ .call_id = "c1", .name = "bash", .content = "main.zig" } }};
+    const t = Transcript{
+        .save = .{
+            .meta = .{ .cwd = "/p", .model = "m", .title = "list files", .usage = .{ .input_tokens = 10, .output_tokens = 5 } },
+            .name = "p-20261016-140309",
+            .chat = &.{
+                .{ .role = .agent, .parts = &call_parts },
+                .{ .role = .user, .parts = &result_parts },
+            },
+            .chat_render = &.{
+                .{ .role = .user, .parts = &user_parts, .time_ms = 1_792_159_389_000 },
+                .{ .role = .agent, .parts = &agent_parts },
+            },
+        },
+        .cost = 0.5,
+    };
+
+    var out = std.Io.Writer.Allocating.init(alloc);
+    try write(alloc, &out.writer, .json, t);
+    const parsed = try std.json.parseFromSliceLeaky(std.json.Value, alloc, out.written(), .{});
+    const entries = parsed.object.get("entries").?.array.items;
+    try std.testing.expectEqualStrings("2026-10-16T14:03:09Z", entries[0].object.get("time").?.string);
+    try std.testing.expect(entries[1].object.get("time") == null);
+    const call = entries[1].object.get("parts").?.array.items[0].object;
+    try std.testing.expectEqualStrings("ls", call.get("arguments").?.object.get("command").?.string);
+    try std.testing.expectEqualStrings("main.zig", call.get("result").?.string);
+
+    out.clearRetainingCapacity();
+    try write(alloc, &out.writer, .markdown, t);
+    try std.testing.expect(std.mem.indexOf(u8, out.written(), "## User, 2026-10-16 14:03:09 UTC\n") != null);
+    try std.testing.expect(std.mem.indexOf(u8, out.written(), "````diff\n-old\n+new ```\n````") != null);
+    try std.testing.expect(std.mem.indexOf(u8, out.written(), "- cost: $0.50\n") != null);
+
+    out.clearRetainingCapacity();
+    try write(alloc, &out.writer, .html, t);
+    try std.testing.expect(std.mem.indexOf(u8, out.written(), "list &lt;files&gt;") != null);
+
+    try std.testing.expectEqual(Format.html, Format.fromPath("out/session.html").?);
+    try std.testing.expectEqual(Format.markdown, Format.parse("md").?);
+}
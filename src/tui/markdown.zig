@@ -87,6 +87,7 @@ pub const HighlightTheme = struct {
     list_marker: r.Style = .{ .fg = .cyan },
     quote: r.Style = .{ .fg = .bright_cyan, .modifier = .{ .italic = true } },
     hr: r.Style = .{ .fg = .bright_cyan },
+    math: r.Style = .{ .fg = .bright_magenta, .modifier = .{ .italic = true } },
     plain: r.Style = .{},
 };
 
@@ -121,6 +122,8 @@ pub const MarkdownStreamingHighlighter = struct {
     /// owned copy of code block's lang tag — buffer may realloc so we can't slice it.
     code_lang_buf: [32]u8 = undefined,
     code_lang_len: u8 = 0,
+    /// between `\[` and `\]` (or `$$`) lines, delimiters are dropped
+    in_math_block: bool = false,
 
     pub fn init(alloc: std.mem.Allocator) Self {
         return .{ .alloc = alloc };
@@ -205,6 +208,23 @@ pub const MarkdownStreamingHighlighter = struct {
                 return .{ .span = .{ .content = "\n", .style = self.theme.plain } };
             }
 
+            // Display math: `\[` / `\]` or `$$` alone on a line. The delimiter
+            // lines vanish, the formula lines keep their text in math style.
+            const math_line = std.mem.trim(u8, line, " \t\r");
+            const opens_math = std.mem.eql(u8, math_line, "\\[") or std.mem.eql(u8, math_line, "$$");
+            const closes_math = std.mem.eql(u8, math_line, "\\]") or std.mem.eql(u8, math_line, "$$");
+            if ((!self.in_math_block and opens_math) or (self.in_math_block and closes_math)) {
+                self.in_math_block = !self.in_math_block;
+                self.cursor = line_end + 1;
+                self.at_line_start = true;
+                return self.consume();
+            }
+            if (self.in_math_block and line.len > 0) {
+                self.cursor = line_end;
+                self.at_line_start = true;
+                return .{ .span = .{ .content = math_line, .style = self.theme.math } };
+            }
+
             // Horizontal rule: --- (or more) alone on line.
             // Advance cursor *up to* (not past) the trailing '\n' so the next
             // consume() sees an empty line and emits the line break naturally.
@@ -309,7 +329,7 @@ pub const MarkdownStreamingHighlighter = struct {
         while (end < buf.len) : (end += 1) {
             const c = buf[end];
             if (c == '\n') break;
-            if (c == '*' or c == '_' or c == '`') {
+            if (c == '*' or c == '_' or c == '`' or c == '\\') {
                 // only break if this really opens a styled span
                 if (self.peekEmphasis(end)) break;
             }
@@ -326,7 +346,7 @@ pub const MarkdownStreamingHighlighter = struct {
         return .{ .span = .{ .content = slice, .style = self.theme.plain } };
     }
 
-    const EmphasisKind = enum { code, bold, italic };
+    const EmphasisKind = enum { code, bold, italic, math };
     const EmphasisHit = struct {
         kind: EmphasisKind,
         open_len: usize, // bytes of the opening marker (1 or 2)
@@ -347,6 +367,14 @@ pub const MarkdownStreamingHighlighter = struct {
             if (nl) |n| if (n < close) return null;
             return .{ .kind = .code, .open_len = 1, .close = close };
         }
+        // inline math `\( .. \)` or `\[ .. \]` on one line
+        if (c == '\\' and pos + 1 < buf.len and (buf[pos + 1] == '(' or buf[pos + 1] == '[')) {
+            const closing: []const u8 = if (buf[pos + 1] == '(') "\\)" else "\\]";
+            const close = std.mem.indexOfPos(u8, buf, pos + 2, closing) orelse return null;
+            const nl = std.mem.indexOfScalarPos(u8, buf, pos + 2, '\n');
+            if (nl) |n| if (n < close) return null;
+            return .{ .kind = .math, .open_len = 2, .close = close };
+        }
         if (c == '*' and pos + 1 < buf.len and buf[pos + 1] == '*') {
             const close = findDouble(buf, pos + 2, '*') orelse return null;
             const nl = std.mem.indexOfScalarPos(u8, buf, pos + 2, '\n');
@@ -371,6 +399,7 @@ pub const MarkdownStreamingHighlighter = struct {
             .code => self.theme.inline_code,
             .bold => self.theme.bold,
             .italic => self.theme.italic,
+            .math => self.theme.math,
         };
         self.cursor = hit.close + hit.open_len;
         return .{ .span = .{ .content = buf[pos + hit.open_len .. hit.close], .style = style } };
@@ -896,3 +925,30 @@ test "markdown: final table row without newline is tagged" {
     try std.testing.expect(got.items.len > 0);
     try std.testing.expectEqual(r.Span.Kind.table_row, got.items[got.items.len - 1].kind);
 }
+
+test "markdown: math delimiters are stripped" {
+    const alloc = std.testing.allocator;
+    var hl = MarkdownStreamingHighlighter.init(alloc);
+    defer hl.deinit();
+
+    try hl.feed("area \\(\\pi r^2\\) done\n\\[\nE = mc^2\n\\]\nafter\n");
+    hl.finish();
+
+    var got: std.ArrayList(r.Span) = .empty;
+    defer got.deinit(alloc);
+    while (true) switch (hl.consume()) {
+        .span => |s| try got.append(alloc, s),
+        .need_bytes => unreachable,
+        .done => break,
+    };
+
+    const joined = try collectPlain(got.items, alloc);
+    defer alloc.free(joined);
+    try std.testing.expectEqualStrings("area \\pi r^2 done\nE = mc^2\nafter\n", joined);
+
+    var math_spans: usize = 0;
+    for (got.items) |s| {
+        if (s.style.modifier.italic and s.style.fg == .bright_magenta) math_spans += 1;
+    }
+    try std.testing.expectEqual(@as(usize, 2), math_spans);
+}
@@ -31,6 +31,7 @@ pub const Buffer = buf.Buffer;
 
 // Terminal
 pub const Terminal = term.Terminal;
+pub const Waker = term.Waker;
 pub const Event = term.Terminal.Event;
 pub const Key = term.Terminal.Key;
 pub const KeyCode = term.Terminal.KeyCode;
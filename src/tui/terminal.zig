@@ -35,16 +35,58 @@ pub fn RingQueue(comptime T: type, comptime capacity: usize) type {
     };
 }
 
+/// Self-pipe that lets worker threads wake the main loop out of
+/// `pollAndEnqueue`, so it can block while agents wait on the network.
+pub const Waker = struct {
+    fds: [2]posix.fd_t = .{ -1, -1 },
+
+    pub fn init() Waker {
+        var fds: [2]posix.fd_t = undefined;
+        if (posix.errno(posix.system.pipe2(&fds, .{ .NONBLOCK = true, .CLOEXEC = true })) != .SUCCESS) return .{};
+        return .{ .fds = fds };
+    }
+
+    pub fn deinit(self: *Waker) void {
+        for (self.fds) |fd| {
+            if (fd >= 0) _ = posix.system.close(fd);
+        }
+        self.* = .{};
+    }
+
+    /// Thread safe. A full pipe already means a pending wake.
+    pub fn wake(self: *Waker) void {
+        if (self.fds[1] < 0) return;
+        const byte = [1]u8{1};
+        _ = posix.system.write(self.fds[1], &byte, 1);
+    }
+
+    /// Type-erased `wake` for code outside the tui.
+    pub fn wakeOpaque(ptr: *anyopaque) void {
+        const self: *Waker = @ptrCast(@alignCast(ptr));
+        self.wake();
+    }
+
+    fn drain(self: *Waker) void {
+        var buf: [64]u8 = undefined;
+        while (true) {
+            const n = posix.read(self.fds[0], &buf) catch return;
+            if (n < buf.len) return;
+        }
+    }
+};
+
 pub const Terminal = struct {
     current: Buffer,
     previous: Buffer,
     original_termios: posix.termios,
+    raw_termios: posix.termios,
     stdout: std.Io.File,
     io: std.Io,
     allocator: std.mem.Allocator,
     input_queue: RingQueue(Event, 64) = .{},
     last_size: Rect,
     selection: ?Selection = null,
+    mouse_capture: bool = true,
 
     pub const Selection = struct {
         anchor_x: u16,
@@ -94,13 +136,15 @@ pub const Terminal = struct {
         // Enter alternate screen + hide cursor + bracketed paste + mouse (SGR + drag)
         var buf: [80]u8 = undefined;
         var w = stdout.writerStreaming(io, &buf);
-        w.interface.writeAll("\x1b[?1049h\x1b[?25l\x1b[2J\x1b[?2004h\x1b[?1000h\x1b[?1002h\x1b[?1006h") catch {};
+        // push the window title so deinit can restore it
+        w.interface.writeAll("\x1b[22;0t\x1b[?1049h\x1b[?25l\x1b[2J\x1b[?2004h\x1b[?1000h\x1b[?1002h\x1b[?1006h") catch {};
         w.interface.flush() catch {};
 
         return .{
             .current = current,
             .previous = previous,
             .original_termios = original,
+            .raw_termios = raw,
             .stdout = stdout,
             .io = io,
             .allocator = allocator,
@@ -112,7 +156,7 @@ pub const Terminal = struct {
         // Disable mouse + bracketed paste, show cursor, leave alternate screen
         var buf: [80]u8 = undefined;
         var w = self.stdout.writerStreaming(self.io, &buf);
-        w.interface.writeAll("\x1b[?1006l\x1b[?1002l\x1b[?1000l\x1b[?2004l\x1b[?25h\x1b[?1049l") catch {};
+        w.interface.writeAll("\x1b]9;4;0\x07\x1b[?1006l\x1b[?1002l\x1b[?1000l\x1b[?2004l\x1b[?25h\x1b[?1049l\x1b[23;0t") catch {};
         w.interface.flush() catch {};
 
         // Restore original termios
@@ -294,9 +338,84 @@ pub const Terminal = struct {
         return try out.toOwnedSlice(alloc);
     }
 
+    /// Without capture the terminal keeps its native selection and copy,
+    /// scrolling is left to the keyboard.
+    pub fn setMouseCapture(self: *Terminal, enabled: bool) void {
+        if (self.mouse_capture == enabled) return;
+        self.mouse_capture = enabled;
+        self.selection = null;
+        var write_buf: [64]u8 = undefined;
+        var w = self.stdout.writerStreaming(self.io, &write_buf);
+        w.interface.writeAll(if (enabled) "\x1b[?1000h\x1b[?1002h\x1b[?1006h" else "\x1b[?1006l\x1b[?1002l\x1b[?1000l") catch return;
+        w.interface.flush() catch {};
+    }
+
+    /// OSC 9;4 taskbar/tab progress, understood by Windows Terminal,
+    /// ConEmu, Ghostty, WezTerm and others. Ignored elsewhere.
+    pub const Progress = enum(u8) {
+        none = 0,
+        err = 2,
+        indeterminate = 3,
+        paused = 4,
+    };
+
+    /// Set the window title (OSC 2) and the progress indicator.
+    pub fn setStatus(self: *Terminal, title: []const u8, progress: Progress) void {
+        var write_buf: [512]u8 = undefined;
+        var w = self.stdout.writerStreaming(self.io, &write_buf);
+        w.interface.print("\x1b]2;{s}\x07\x1b]9;4;{d}\x07", .{ title, @intFromEnum(progress) }) catch return;
+        w.interface.flush() catch {};
+    }
+
+    /// Write `bytes` with the cursor at `x`,`y`, for escapes the cell buffer
+    /// knows nothing about, e.g. inline images.
+    pub fn writeAt(self: *Terminal, x: u16, y: u16, bytes: []const u8) void {
+        var write_buf: [4096]u8 = undefined;
+        var w = self.stdout.writerStreaming(self.io, &write_buf);
+        w.interface.print("\x1b[{d};{d}H", .{ @as(u32, y) + 1, @as(u32, x) + 1 }) catch return;
+        w.interface.writeAll(bytes) catch return;
+        w.interface.flush() catch {};
+    }
+
+    /// Hand the screen to a foreground child such as an editor, `unpause`
+    /// takes it back.
+    pub fn pause(self: *Terminal) void {
+        var buf: [64]u8 = undefined;
+        var w = self.stdout.writerStreaming(self.io, &buf);
+        w.interface.writeAll("\x1b[?1006l\x1b[?1002l\x1b[?1000l\x1b[?2004l\x1b[?25h\x1b[?1049l") catch {};
+        w.interface.flush() catch {};
+        posix.tcsetattr(self.stdout.handle, .FLUSH, self.original_termios) catch {};
+    }
+
+    pub fn unpause(self: *Terminal) void {
+        posix.tcsetattr(self.stdout.handle, .FLUSH, self.raw_termios) catch {};
+        var buf: [64]u8 = undefined;
+        var w = self.stdout.writerStreaming(self.io, &buf);
+        w.interface.writeAll("\x1b[?1049h\x1b[?25l\x1b[?2004h") catch {};
+        if (self.mouse_capture) w.interface.writeAll("\x1b[?1000h\x1b[?1002h\x1b[?1006h") catch {};
+        w.interface.flush() catch {};
+        self.repaint();
+    }
+
+    /// Clear the screen and draw every cell again on the next frame, wipes
+    /// whatever `writeAt` left behind.
+    pub fn repaint(self: *Terminal) void {
+        var write_buf: [16]u8 = undefined;
+        var w = self.stdout.writerStreaming(self.io, &write_buf);
+        w.interface.writeAll("\x1b[2J") catch return;
+        w.interface.flush() catch {};
+        self.previous.clear();
+    }
+
     fn copySelectionOsc52(self: *Terminal) void {
         const text = self.extractSelection(self.allocator) catch return;
         defer self.allocator.free(text);
+        self.copyToClipboard(text);
+    }
+
+    /// Put `text` on the system clipboard with OSC 52, which reaches the
+    /// local clipboard over SSH too.
+    pub fn copyToClipboard(self: *Terminal, text: []const u8) void {
         if (text.len == 0) return;
 
         const encoder = std.base64.standard.Encoder;
@@ -489,8 +608,10 @@ pub const Terminal = struct {
         };
     }
 
-    /// Poll stdin and enqueue all parsed events into the ring queue.
-    pub fn pollAndEnqueue(self: *Terminal, timeout_ms: i32) void {
+    /// Poll stdin and enqueue all parsed events into the ring queue. A
+    /// `waker` signal from another thread ends the wait early as well.
+    /// Returns true when the waker fired.
+    pub fn pollAndEnqueue(self: *Terminal, timeout_ms: i32, waker: ?*Waker) bool {
         const cur = self.size();
         if (cur.width != self.last_size.width or cur.height != self.last_size.height) {
             self.last_size = cur;
@@ -498,20 +619,29 @@ pub const Terminal = struct {
         }
 
         const stdin_fd = std.Io.File.stdin().handle;
-        var fds = [_]posix.pollfd{.{
+        var fds = [_]posix.pollfd{ .{
             .fd = stdin_fd,
             .events = posix.POLL.IN,
             .revents = 0,
-        }};
+        }, .{
+            .fd = if (waker) |w| w.fds[0] else -1,
+            .events = posix.POLL.IN,
+            .revents = 0,
+        } };
+
+        const ready = posix.poll(&fds, timeout_ms) catch return false;
+        if (ready == 0) return false;
 
-        const ready = posix.poll(&fds, timeout_ms) catch return;
-        if (ready == 0) return;
+        const woke = fds[1].revents & posix.POLL.IN != 0;
+        if (woke) waker.?.drain();
+        if (fds[0].revents & posix.POLL.IN == 0) return woke;
 
         var buf: [256]u8 = undefined;
-        const n = posix.read(stdin_fd, &buf) catch return;
-        if (n == 0) return;
+        const n = posix.read(stdin_fd, &buf) catch return woke;
+        if (n == 0) return woke;
 
         self.parseAndEnqueue(buf[0..n]);
+        return woke;
     }
 
     /// Pop the next buffered event. Returns `.none` when the queue is empty.
@@ -174,6 +174,33 @@ pub fn renderError(buf: *Buffer, last_error: ?anyerror, detail: ?[]const u8, x:
     }
 }
 
+pub const SpinnerStyle = enum {
+    dots,
+    bar,
+    wave,
+    line,
+    none,
+
+    pub fn fromString(s: []const u8) ?SpinnerStyle {
+        return std.meta.stringToEnum(SpinnerStyle, s);
+    }
+};
+
+pub fn spinner(style: SpinnerStyle, frame_count: usize) []const u8 {
+    return switch (style) {
+        .dots => spinnerDots(frame_count),
+        .bar => spinnerBar(frame_count),
+        .wave => spinnerWave(frame_count),
+        .line => spinnerLine(frame_count),
+        .none => "",
+    };
+}
+
+pub fn spinnerLine(frame_count: usize) []const u8 {
+    const frames = [_][]const u8{ "-", "\\", "|", "/" };
+    return frames[(frame_count / 6) % frames.len];
+}
+
 pub fn spinnerDots(frame_count: usize) []const u8 {
     const frames = [_][]const u8{ "⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏" };
     return frames[(frame_count / 6) % frames.len];
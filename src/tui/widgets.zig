@@ -543,6 +543,27 @@ test "Paragraph reverse scroll_offset skips bottom rows" {
     try std.testing.expectEqual(@as(u21, 'b'), buf.get(0, 1).char);
 }
 
+test "Paragraph fold keeps head and tail" {
+    const alloc = std.testing.allocator;
+    var p: Paragraph = .{};
+    defer p.deinit(alloc);
+    try p.appendText(alloc, "1\n2\n3\n4\n5\n6", .{});
+
+    var marker: Line = .{};
+    try marker.pushText(alloc, "...", .{});
+    try p.fold(alloc, 2, 2, marker);
+    try std.testing.expectEqual(@as(usize, 5), p.lines.items.len);
+    try std.testing.expectEqualStrings("2", p.lines.items[1].spans.items[0].content);
+    try std.testing.expectEqualStrings("...", p.lines.items[2].spans.items[0].content);
+    try std.testing.expectEqualStrings("5", p.lines.items[3].spans.items[0].content);
+
+    // nothing left to hide
+    var unused: Line = .{};
+    defer unused.deinit(alloc);
+    try p.fold(alloc, 2, 2, unused);
+    try std.testing.expectEqual(@as(usize, 5), p.lines.items.len);
+}
+
 // ── Diff ──
 
 pub const DiffLineKind = enum { context, addition, deletion, header };
@@ -828,6 +849,15 @@ pub const Paragraph = struct {
         try self.lines.append(alloc, line);
     }
 
+    /// Keeps the first `head` and last `tail` lines and puts `marker` in
+    /// place of everything between. No-op if there is nothing to hide.
+    pub fn fold(self: *Paragraph, alloc: std.mem.Allocator, head: usize, tail: usize, marker: Line) !void {
+        const n = self.lines.items.len;
+        if (n <= head + tail + 1) return;
+        for (self.lines.items[head .. n - tail]) |*l| l.deinit(alloc);
+        try self.lines.replaceRange(alloc, head, n - tail - head, &.{marker});
+    }
+
     fn borderSet(self: *const Paragraph) ?BorderSet {
         return switch (self.border) {
             .none => null,
@@ -7,6 +7,58 @@ pub fn ensureBlitzDir(dir: std.Io.Dir, io: std.Io) !void {
     try dir.createDirPath(io, BLITZ_DIR);
 }
 
+/// Adds `entry` to `.blitz/.gitignore` below `dir` unless it is listed, so
+/// runtime output like spill files stays out of `git add -A`. A gitignore
+/// too long to read is left alone.
+pub fn ignoreInBlitzDir(dir: std.Io.Dir, io: std.Io, entry: []const u8) !void {
+    try ensureBlitzDir(dir, io);
+    const path = BLITZ_DIR ++ "/.gitignore";
+    var content: [16 * 1024]u8 = undefined;
+    const old: []const u8 = blk: {
+        const file = dir.openFile(io, path, .{}) catch break :blk "";
+        defer file.close(io);
+        var buf: [4096]u8 = undefined;
+        var reader = file.reader(io, &buf);
+        const n = try reader.interface.readSliceShort(&content);
+        if (n == content.len) return;
+        break :blk content[0..n];
+    };
+    var lines = std.mem.tokenizeAny(u8, old, "\r\n");
+    while (lines.next()) |line| {
+        if (std.mem.eql(u8, std.mem.trim(u8, line, " \t"), entry)) return;
+    }
+
+    const file = try dir.createFile(io, path, .{});
+    defer file.close(io);
+    var buf: [4096]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    try writer.interface.writeAll(old);
+    if (old.len > 0 and old[old.len - 1] != '\n') try writer.interface.writeByte('\n');
+    try writer.interface.print("{s}\n", .{entry});
+    try writer.interface.flush();
+}
+
+/// Current branch of the repository at `cwd`, read from `.git/HEAD`.
+/// Detached heads show the short commit hash.
+pub fn gitBranch(io: std.Io, alloc: std.mem.Allocator, cwd: []const u8) ?[]const u8 {
+    var dir = std.Io.Dir.openDirAbsolute(io, cwd, .{}) catch return null;
+    defer dir.close(io);
+    const file = dir.openFile(io, ".git/HEAD", .{}) catch return null;
+    defer file.close(io);
+    var buf: [256]u8 = undefined;
+    var file_reader = file.reader(io, &buf);
+    const content = file_reader.interface.allocRemaining(alloc, .limited(256)) catch return null;
+    return parseGitHead(content);
+}
+
+pub fn parseGitHead(content: []const u8) ?[]const u8 {
+    const head = std.mem.trim(u8, content, " \r\n");
+    const prefix = "ref: refs/heads/";
+    if (std.mem.startsWith(u8, head, prefix)) return head[prefix.len..];
+    if (head.len >= 7 and std.mem.indexOfScalar(u8, head, ' ') == null) return head[0..7];
+    return null;
+}
+
 ///Mostly clones `T`. passthrough for function ptr and anything opaque.
 pub fn deepClone(comptime T: type, value: T, alloc: std.mem.Allocator) !T {
     return switch (@typeInfo(T)) {
@@ -65,6 +117,30 @@ test "ensureBlitzDir creates the runtime-data directory idempotently" {
     blitz_dir.close(std.testing.io);
 }
 
+test "ignoreInBlitzDir lists each entry once" {
+    var tmp = std.testing.tmpDir(.{});
+    defer tmp.cleanup();
+    const io = std.testing.io;
+
+    try ignoreInBlitzDir(tmp.dir, io, "spill/");
+    try ignoreInBlitzDir(tmp.dir, io, "spill/");
+    try ignoreInBlitzDir(tmp.dir, io, "media/");
+
+    const file = try tmp.dir.openFile(io, BLITZ_DIR ++ "/.gitignore", .{});
+    defer file.close(io);
+    var buf: [64]u8 = undefined;
+    var reader = file.reader(io, &.{});
+    const n = try reader.interface.readSliceShort(&buf);
+    try std.testing.expectEqualStrings("spill/\nmedia/\n", buf[0..n]);
+}
+
+test "parseGitHead" {
+    try std.testing.expectEqualStrings("main", parseGitHead("ref: refs/heads/main\n").?);
+    try std.testing.expectEqualStrings("feat/x", parseGitHead("ref: refs/heads/feat/x").?);
+    try std.testing.expectEqualStrings("a305540", parseGitHead("a305540bdd0e1f\n").?);
+    try std.testing.expect(parseGitHead("") == null);
+}
+
 test "deepClone primitives" {
     var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
     defer arena.deinit();
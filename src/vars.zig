@@ -0,0 +1,62 @@
+//! Project prompt variables. `blitz.set_prompt_var("service", "billing")` in
+//! a project blitz.lua fills `{{service}}` in agent prompts, AGENTS.md files,
+//! mode prompts and skills, so shared templates stay generic. Unknown names
+//! are left as typed.
+const std = @import("std");
+
+pub const Vars = std.StringArrayHashMapUnmanaged([]const u8);
+
+pub const MAX_NAME_LEN = 64;
+
+pub fn validName(name: []const u8) bool {
+    if (name.len == 0 or name.len > MAX_NAME_LEN) return false;
+    for (name) |ch| {
+        if (!std.ascii.isAlphanumeric(ch) and ch != '_' and ch != '-' and ch != '.') return false;
+    }
+    return true;
+}
+
+/// Write `text` to `w` with every known `{{name}}` replaced.
+pub fn render(w: *std.Io.Writer, text: []const u8, vars: *const Vars) !void {
+    var rest = text;
+    while (std.mem.indexOf(u8, rest, "{{")) |open| {
+        const close = std.mem.indexOfPos(u8, rest, open + 2, "}}") orelse break;
+        const name = std.mem.trim(u8, rest[open + 2 .. close], " ");
+        if (vars.get(name)) |value| {
+            try w.writeAll(rest[0..open]);
+            try w.writeAll(value);
+        } else {
+            try w.writeAll(rest[0 .. close + 2]);
+        }
+        rest = rest[close + 2 ..];
+    }
+    try w.writeAll(rest);
+}
+
+/// `text` with its variables filled, `text` itself when there is nothing to do.
+pub fn expand(alloc: std.mem.Allocator, text: []const u8, vars: *const Vars) ![]const u8 {
+    if (vars.count() == 0 or std.mem.indexOf(u8, text, "{{") == null) return text;
+    var out = std.Io.Writer.Allocating.init(alloc);
+    errdefer out.deinit();
+    try render(&out.writer, text, vars);
+    return out.toOwnedSlice();
+}
+
+test "prompt variables" {
+    const alloc = std.testing.allocator;
+    var vars: Vars = .empty;
+    defer vars.deinit(alloc);
+    try vars.put(alloc, "service_name", "billing");
+    try vars.put(alloc, "team", "payments");
+
+    const out = try expand(alloc, "You work on {{service_name}} for {{ team }}, see {{unknown}} and {{broken", &vars);
+    defer alloc.free(out);
+    try std.testing.expectEqualStrings("You work on billing for payments, see {{unknown}} and {{broken", out);
+
+    const same = "no variables here";
+    try std.testing.expectEqual(same.ptr, (try expand(alloc, same, &vars)).ptr);
+
+    try std.testing.expect(validName("service_name"));
+    try std.testing.expect(!validName("two words"));
+    try std.testing.expect(!validName(""));
+}
@@ -0,0 +1,208 @@
+//! Encryption at rest for session files: the browser copies in
+//! `~/.cache/blitzdenk/sessions/`, the autosave and the last session kept
+//! for `:handoff`. Off by default. `blitz.set_session_encryption(true)`
+//! keeps the key in the OS keychain (`secret-tool` on Linux, `security` on
+//! macOS) and creates it there on first use. With the name of an env var
+//! the key is derived from the passphrase it holds instead:
+//!
+//!     blitz.set_session_encryption(true, "BLITZ_SESSION_PASSPHRASE")
+//!
+//! Sealed files are XChaCha20-Poly1305 behind a magic header. Plaintext
+//! files keep loading, `:sessions encrypt` seals the stored ones. Spill
+//! files, the `--log` event log, the spend ledger and `prompt_history.json`
+//! are not sessions and stay plaintext.
+const std = @import("std");
+const builtin = @import("builtin");
+const prv = @import("provider");
+
+const Aead = std.crypto.aead.chacha_poly.XChaCha20Poly1305;
+
+pub const MAGIC = "blitzdenk-sealed-v1\n";
+pub const KEY_LEN = Aead.key_length;
+const HEADER_LEN = MAGIC.len + Aead.nonce_length + Aead.tag_length;
+/// OWASP recommendation for PBKDF2-HMAC-SHA256
+const PBKDF2_ROUNDS = 600_000;
+const SALT_LEN = 16;
+/// per install, next to the stored sessions
+pub const SALT_FILE = ".cache/blitzdenk/sessions/.salt";
+const KEYCHAIN_SERVICE = "blitzdenk";
+const KEYCHAIN_ACCOUNT = "session-key";
+
+pub const Vault = struct {
+    enabled: bool = false,
+    /// env var holding the passphrase, empty for the OS keychain
+    passphrase_env: []const u8 = "",
+    /// fetched once per key source, survives config reloads
+    key: ?[KEY_LEN]u8 = null,
+
+    /// Whether new files get sealed. Without a key saving fails instead of
+    /// silently falling back to plaintext.
+    pub fn sealing(self: *const Vault) !?*const [KEY_LEN]u8 {
+        if (!self.enabled) return null;
+        return if (self.key) |*k| k else error.SessionKeyLocked;
+    }
+
+    /// Fetch the key of the configured source, a no-op once it is known.
+    pub fn unlock(self: *Vault, io: std.Io, exec: *prv.exec.CmdPool, home: []const u8) !void {
+        if (self.key != null) return;
+        if (self.passphrase_env.len > 0) {
+            const passphrase = exec.env.get(self.passphrase_env) orelse return error.PassphraseNotSet;
+            if (passphrase.len == 0) return error.PassphraseNotSet;
+            var salt: [SALT_LEN]u8 = undefined;
+            try loadSalt(io, home, &salt);
+            self.key = try deriveKey(passphrase, &salt);
+        } else {
+            self.key = try keychainKey(io, exec);
+        }
+    }
+
+    /// `plain` sealed behind the header, or as is when encryption is off.
+    pub fn seal(self: *const Vault, io: std.Io, alloc: std.mem.Allocator, plain: []const u8) ![]const u8 {
+        const key = try self.sealing() orelse return plain;
+        return sealWith(io, alloc, key.*, plain);
+    }
+
+    /// Plaintext of a file written by `seal`, plaintext files pass through.
+    pub fn open(self: *const Vault, alloc: std.mem.Allocator, data: []const u8) ![]const u8 {
+        if (!isSealed(data)) return data;
+        const key = self.key orelse return error.SessionEncrypted;
+        return openWith(alloc, key, data);
+    }
+};
+
+pub fn isSealed(data: []const u8) bool {
+    return std.mem.startsWith(u8, data, MAGIC);
+}
+
+fn sealWith(io: std.Io, alloc: std.mem.Allocator, key: [KEY_LEN]u8, plain: []const u8) ![]const u8 {
+    const out = try alloc.alloc(u8, HEADER_LEN + plain.len);
+    @memcpy(out[0..MAGIC.len], MAGIC);
+    const nonce = out[MAGIC.len..][0..Aead.nonce_length];
+    io.random(nonce);
+    const tag = out[MAGIC.len + Aead.nonce_length ..][0..Aead.tag_length];
+    Aead.encrypt(out[HEADER_LEN..], tag, plain, MAGIC, nonce.*, key);
+    return out;
+}
+
+fn openWith(alloc: std.mem.Allocator, key: [KEY_LEN]u8, data: []const u8) ![]const u8 {
+    if (data.len < HEADER_LEN) return error.SessionCorrupted;
+    const nonce = data[MAGIC.len..][0..Aead.nonce_length];
+    const tag = data[MAGIC.len + Aead.nonce_length ..][0..Aead.tag_length];
+    const out = try alloc.alloc(u8, data.len - HEADER_LEN);
+    Aead.decrypt(out, data[HEADER_LEN..], tag.*, MAGIC, nonce.*, key) catch return error.WrongSessionKey;
+    return out;
+}
+
+fn deriveKey(passphrase: []const u8, salt: *const [SALT_LEN]u8) ![KEY_LEN]u8 {
+    var key: [KEY_LEN]u8 = undefined;
+    try std.crypto.pwhash.pbkdf2(&key, passphrase, salt, PBKDF2_ROUNDS, std.crypto.auth.hmac.sha2.HmacSha256);
+    return key;
+}
+
+/// The install's salt, created on first use. Losing it makes passphrase
+/// sealed files unreadable, like losing the passphrase.
+fn loadSalt(io: std.Io, home: []const u8, salt: *[SALT_LEN]u8) !void {
+    var home_dir = try std.Io.Dir.openDirAbsolute(io, home, .{});
+    defer home_dir.close(io);
+    if (home_dir.openFile(io, SALT_FILE, .{ .mode = .read_only })) |file| {
+        defer file.close(io);
+        var buf: [SALT_LEN]u8 = undefined;
+        var reader = file.reader(io, &buf);
+        try reader.interface.readSliceAll(salt);
+        return;
+    } else |err| switch (err) {
+        error.FileNotFound => {},
+        else => return err,
+    }
+    io.random(salt);
+    try home_dir.createDirPath(io, std.fs.path.dirname(SALT_FILE).?);
+    const file = try home_dir.createFile(io, SALT_FILE, .{});
+    defer file.close(io);
+    var buf: [SALT_LEN]u8 = undefined;
+    var writer = file.writer(io, &buf);
+    try writer.interface.writeAll(salt);
+    try writer.interface.flush();
+}
+
+/// Hex key from the OS keychain, a new random one is stored on first use.
+/// Only a lookup the backend answers with "not found" counts as first use,
+/// a locked keychain or a failing D-Bus must not replace the key every
+/// sealed file depends on.
+fn keychainKey(io: std.Io, exec: *prv.exec.CmdPool) ![KEY_LEN]u8 {
+    const lookup: []const []const u8 = switch (builtin.os.tag) {
+        .macos => &.{ "security", "find-generic-password", "-s", KEYCHAIN_SERVICE, "-a", KEYCHAIN_ACCOUNT, "-w" },
+        else => &.{ "secret-tool", "lookup", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT },
+    };
+    const found = try exec.runAndWait(.{ .argv = lookup, .force_local = true });
+    defer exec.alloc.free(found.stdout);
+    defer exec.alloc.free(found.stderr);
+    var key: [KEY_LEN]u8 = undefined;
+    if (found.ty == .success) {
+        const hex = std.mem.trim(u8, found.stdout, " \r\n");
+        if (hex.len != KEY_LEN * 2) return error.InvalidKeychainKey;
+        _ = std.fmt.hexToBytes(&key, hex) catch return error.InvalidKeychainKey;
+        return key;
+    }
+    if (found.ty != .failed or !keyNotFound(builtin.os.tag, found.stdout, found.stderr)) return error.KeychainUnavailable;
+
+    io.random(&key);
+    const hex = std.fmt.bytesToHex(key, .lower);
+    const store = switch (builtin.os.tag) {
+        .macos => try exec.runAndWait(.{
+            .argv = &.{ "security", "add-generic-password", "-U", "-s", KEYCHAIN_SERVICE, "-a", KEYCHAIN_ACCOUNT, "-w", &hex },
+            .force_local = true,
+        }),
+        else => try exec.runAndWait(.{
+            .argv = &.{ "secret-tool", "store", "--label=blitzdenk sessions", "service", KEYCHAIN_SERVICE, "account", KEYCHAIN_ACCOUNT },
+            .stdin_data = &hex,
+            .force_local = true,
+        }),
+    };
+    defer exec.alloc.free(store.stdout);
+    defer exec.alloc.free(store.stderr);
+    if (store.ty != .success) return error.KeychainUnavailable;
+    return key;
+}
+
+/// Whether a failed lookup means there is no key yet. `secret-tool` exits
+/// 1 without output, `security` says so on stderr.
+fn keyNotFound(os: std.Target.Os.Tag, stdout: []const u8, stderr: []const u8) bool {
+    const out = std.mem.trim(u8, stdout, " \r\n");
+    const err = std.mem.trim(u8, stderr, " \r\n");
+    return switch (os) {
+        .macos => std.mem.indexOf(u8, err, "could not be found in the keychain") != null,
+        else => out.len == 0 and err.len == 0,
+    };
+}
+
+test "only a missing keychain entry creates a key" {
+    try std.testing.expect(keyNotFound(.linux, "", ""));
+    try std.testing.expect(!keyNotFound(.linux, "", "Cannot autolaunch D-Bus without X11 $DISPLAY"));
+    try std.testing.expect(!keyNotFound(.linux, "", "secret-tool: Cannot create an item in a locked collection"));
+    try std.testing.expect(keyNotFound(.macos, "", "security: SecKeychainSearchCopyNext: The specified item could not be found in the keychain.\n"));
+    try std.testing.expect(!keyNotFound(.macos, "", "security: SecKeychainSearchCopyNext: User interaction is not allowed."));
+}
+
+test "sealed sessions round trip" {
+    const alloc = std.testing.allocator;
+    const io = std.testing.io;
+    const salt = [_]u8{7} ** SALT_LEN;
+    const vault = Vault{ .enabled = true, .key = try deriveKey("correct horse", &salt) };
+
+    const plain = "{\"meta\":{\"title\":\"secret\"}}";
+    const sealed = try vault.seal(io, alloc, plain);
+    defer alloc.free(sealed);
+    try std.testing.expect(isSealed(sealed));
+    try std.testing.expect(std.mem.indexOf(u8, sealed, "secret") == null);
+
+    const opened = try vault.open(alloc, sealed);
+    defer alloc.free(opened);
+    try std.testing.expectEqualStrings(plain, opened);
+    try std.testing.expectEqualStrings(plain, try vault.open(alloc, plain));
+
+    var other = Vault{ .enabled = true, .key = [_]u8{1} ** KEY_LEN };
+    try std.testing.expectError(error.WrongSessionKey, other.open(alloc, sealed));
+    other.key = null;
+    try std.testing.expectError(error.SessionEncrypted, other.open(alloc, sealed));
+    try std.testing.expectError(error.SessionKeyLocked, other.seal(io, alloc, plain));
+}